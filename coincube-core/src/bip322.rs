@@ -0,0 +1,526 @@
+//! BIP-0322 generic message signing.
+//!
+//! A message is signed for an address by spending a virtual transaction (`to_spend`) which
+//! commits to both the message and the address' Script, with a second virtual transaction
+//! (`to_sign`). Since `to_sign` is a regular transaction it goes through the usual PSBT workflow
+//! and can be signed by the hot signer as well as by any signing device.
+//!
+//! Additional inputs spending actual coins can be added to `to_sign` in order to prove control
+//! over these coins, in which case the signature must be in the "full" format. This is what we
+//! use to produce proofs of reserves.
+
+use miniscript::{
+    bitcoin::{
+        self,
+        absolute::LockTime,
+        base64::{engine::general_purpose::STANDARD as BASE64, Engine},
+        consensus::{self, encode},
+        hashes::{sha256, Hash, HashEngine},
+        opcodes,
+        psbt::Psbt,
+        script, secp256k1, sighash,
+        transaction::Version,
+        Amount, OutPoint, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Txid, Witness,
+    },
+    interpreter::Interpreter,
+    psbt::PsbtExt,
+};
+
+use std::{error, fmt};
+
+use serde::{Deserialize, Serialize};
+
+const MESSAGE_TAG: &[u8] = b"BIP0322-signed-message";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Bip322Error {
+    /// The signature is neither valid base64 nor a valid witness stack or transaction.
+    InvalidEncoding(String),
+    /// The transaction in a full signature is not a valid `to_sign` for this message.
+    InvalidToSign(&'static str),
+    /// The number of previous outputs given does not match the number of additional inputs.
+    PrevoutsMismatch { expected: usize, got: usize },
+    /// The signed PSBT could not be finalized.
+    Finalization(String),
+    /// The witness for the input at this index does not satisfy its Script.
+    InvalidSignature(usize, String),
+}
+
+impl fmt::Display for Bip322Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidEncoding(e) => write!(f, "Invalid signature encoding: {}", e),
+            Self::InvalidToSign(e) => write!(f, "Invalid 'to_sign' transaction: {}", e),
+            Self::PrevoutsMismatch { expected, got } => write!(
+                f,
+                "Expected {} previous outputs for the additional inputs, got {}",
+                expected, got
+            ),
+            Self::Finalization(e) => write!(f, "Failed to finalize the signed message: {}", e),
+            Self::InvalidSignature(i, e) => {
+                write!(f, "Invalid signature for input at index {}: {}", i, e)
+            }
+        }
+    }
+}
+
+impl error::Error for Bip322Error {}
+
+/// The encoding of a BIP-0322 signature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SignatureFormat {
+    /// Only the witness stack of the first input of `to_sign`.
+    Simple,
+    /// The whole `to_sign` transaction.
+    Full,
+}
+
+/// The BIP-0340 tagged hash of the message.
+pub fn message_hash(message: &[u8]) -> sha256::Hash {
+    let tag = sha256::Hash::hash(MESSAGE_TAG);
+    let mut engine = sha256::Hash::engine();
+    engine.input(tag.as_byte_array());
+    engine.input(tag.as_byte_array());
+    engine.input(message);
+    sha256::Hash::from_engine(engine)
+}
+
+/// The virtual transaction committing to the message and the challenge Script.
+pub fn to_spend(script_pubkey: &ScriptBuf, message: &[u8]) -> Transaction {
+    let script_sig = script::Builder::new()
+        .push_opcode(opcodes::OP_0)
+        .push_slice(message_hash(message).to_byte_array())
+        .into_script();
+    Transaction {
+        version: Version(0),
+        lock_time: LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint::new(Txid::all_zeros(), 0xFF_FF_FF_FF),
+            script_sig,
+            sequence: Sequence::ZERO,
+            witness: Witness::new(),
+        }],
+        output: vec![TxOut {
+            value: Amount::ZERO,
+            script_pubkey: script_pubkey.clone(),
+        }],
+    }
+}
+
+/// The unsigned virtual transaction spending `to_spend`, along with the given coins.
+pub fn to_sign(to_spend: &Transaction, coins: &[OutPoint]) -> Transaction {
+    let input = std::iter::once(OutPoint::new(to_spend.compute_txid(), 0))
+        .chain(coins.iter().copied())
+        .map(|previous_output| TxIn {
+            previous_output,
+            sequence: Sequence::ZERO,
+            ..TxIn::default()
+        })
+        .collect();
+    Transaction {
+        version: Version(0),
+        lock_time: LockTime::ZERO,
+        input,
+        output: vec![TxOut {
+            value: Amount::ZERO,
+            script_pubkey: script::Builder::new()
+                .push_opcode(opcodes::all::OP_RETURN)
+                .into_script(),
+        }],
+    }
+}
+
+/// Create the PSBT of the `to_sign` transaction for this message and challenge Script, spending
+/// the given coins in addition to `to_spend`.
+///
+/// Only the spent outputs are set in the PSBT inputs. The caller is expected to add the
+/// information signers need (BIP32 derivations, witness Script, ..).
+pub fn to_sign_psbt(
+    script_pubkey: &ScriptBuf,
+    message: &[u8],
+    coins: &[(OutPoint, TxOut)],
+) -> Psbt {
+    let to_spend = to_spend(script_pubkey, message);
+    let outpoints: Vec<_> = coins.iter().map(|(op, _)| *op).collect();
+    let mut psbt = Psbt::from_unsigned_tx(to_sign(&to_spend, &outpoints))
+        .expect("The transaction is unsigned.");
+    psbt.inputs[0].witness_utxo = Some(to_spend.output[0].clone());
+    psbt.inputs[0].non_witness_utxo = Some(to_spend);
+    for (psbt_in, (_, txout)) in psbt.inputs.iter_mut().skip(1).zip(coins) {
+        psbt_in.witness_utxo = Some(txout.clone());
+    }
+    psbt
+}
+
+/// Finalize a signed `to_sign` PSBT and encode the resulting signature in the given format.
+///
+/// The simple format can only be used if `to_sign` does not spend any coin.
+pub fn finalize(
+    mut psbt: Psbt,
+    format: SignatureFormat,
+    secp: &secp256k1::Secp256k1<impl secp256k1::Verification>,
+) -> Result<String, Bip322Error> {
+    if format == SignatureFormat::Simple && psbt.inputs.len() > 1 {
+        return Err(Bip322Error::Finalization(
+            "a proof of funds must use the full format".to_string(),
+        ));
+    }
+    psbt.finalize_mut(secp).map_err(|errs| {
+        Bip322Error::Finalization(
+            errs.iter()
+                .map(|e| e.to_string())
+                .collect::<Vec<_>>()
+                .join(", "),
+        )
+    })?;
+    let tx = psbt.extract_tx_unchecked_fee_rate();
+    Ok(match format {
+        SignatureFormat::Simple => BASE64.encode(encode::serialize(&tx.input[0].witness)),
+        SignatureFormat::Full => BASE64.encode(encode::serialize(&tx)),
+    })
+}
+
+/// Verify a BIP-0322 signature, in either the simple or the full format, of a message for the
+/// given challenge Script.
+///
+/// For a proof of funds the previous outputs of the additional inputs must be given, in order.
+/// Note this only checks the signatures, it is up to the caller to check these coins are unspent.
+pub fn verify(
+    script_pubkey: &ScriptBuf,
+    message: &[u8],
+    signature: &str,
+    prevouts: &[TxOut],
+    secp: &secp256k1::Secp256k1<impl secp256k1::Verification>,
+) -> Result<SignatureFormat, Bip322Error> {
+    let bytes = BASE64
+        .decode(signature.trim())
+        .map_err(|e| Bip322Error::InvalidEncoding(e.to_string()))?;
+    let to_spend = to_spend(script_pubkey, message);
+
+    let (to_sign, format) = match consensus::deserialize::<Transaction>(&bytes) {
+        Ok(tx) => {
+            if tx.version != Version(0) && tx.version != Version::TWO {
+                return Err(Bip322Error::InvalidToSign("unexpected version"));
+            }
+            if tx.input.first().map(|txin| txin.previous_output)
+                != Some(OutPoint::new(to_spend.compute_txid(), 0))
+            {
+                return Err(Bip322Error::InvalidToSign(
+                    "first input must spend 'to_spend'",
+                ));
+            }
+            if tx.output.len() != 1
+                || tx.output[0].value != Amount::ZERO
+                || !tx.output[0].script_pubkey.is_op_return()
+            {
+                return Err(Bip322Error::InvalidToSign(
+                    "must have a single empty OP_RETURN output",
+                ));
+            }
+            (tx, SignatureFormat::Full)
+        }
+        Err(_) => {
+            let witness = consensus::deserialize::<Witness>(&bytes)
+                .map_err(|e| Bip322Error::InvalidEncoding(e.to_string()))?;
+            let mut tx = to_sign(&to_spend, &[]);
+            tx.input[0].witness = witness;
+            (tx, SignatureFormat::Simple)
+        }
+    };
+
+    if to_sign.input.len() != prevouts.len() + 1 {
+        return Err(Bip322Error::PrevoutsMismatch {
+            expected: to_sign.input.len() - 1,
+            got: prevouts.len(),
+        });
+    }
+    let spent: Vec<TxOut> = std::iter::once(to_spend.output[0].clone())
+        .chain(prevouts.iter().cloned())
+        .collect();
+    let prevouts = sighash::Prevouts::All(&spent);
+    for (i, (txin, txout)) in to_sign.input.iter().zip(spent.iter()).enumerate() {
+        let interpreter = Interpreter::from_txdata(
+            &txout.script_pubkey,
+            &txin.script_sig,
+            &txin.witness,
+            txin.sequence,
+            to_sign.lock_time,
+        )
+        .map_err(|e| Bip322Error::InvalidSignature(i, e.to_string()))?;
+        for res in interpreter.iter(secp, &to_sign, i, &prevouts) {
+            res.map_err(|e| Bip322Error::InvalidSignature(i, e.to_string()))?;
+        }
+    }
+
+    Ok(format)
+}
+
+/// A coin part of a proof of reserves.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReserveCoin {
+    pub outpoint: OutPoint,
+    #[serde(with = "bitcoin::amount::serde::as_sat")]
+    pub amount: Amount,
+    pub script_pubkey: ScriptBuf,
+}
+
+/// A proof of control over a set of coins, as a BIP-0322 full signature of a message for the
+/// address of the first coin which also spends all the coins.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReserveProof {
+    pub message: String,
+    pub address: bitcoin::Address<bitcoin::address::NetworkUnchecked>,
+    pub network: bitcoin::Network,
+    pub coins: Vec<ReserveCoin>,
+    /// The base64-encoded `to_sign` transaction.
+    pub proof: String,
+}
+
+impl ReserveProof {
+    /// The total value of the coins.
+    pub fn total(&self) -> Amount {
+        self.coins.iter().map(|c| c.amount).sum()
+    }
+
+    /// Check the proof is valid. Returns the total value of the coins.
+    pub fn verify(
+        &self,
+        secp: &secp256k1::Secp256k1<impl secp256k1::Verification>,
+    ) -> Result<Amount, Bip322Error> {
+        let address = self
+            .address
+            .clone()
+            .require_network(self.network)
+            .map_err(|e| Bip322Error::InvalidEncoding(e.to_string()))?;
+        let to_sign: Transaction = BASE64
+            .decode(self.proof.trim())
+            .ok()
+            .and_then(|bytes| consensus::deserialize(&bytes).ok())
+            .ok_or(Bip322Error::InvalidEncoding(
+                "proof is not a base64-encoded transaction".to_string(),
+            ))?;
+        let spent = to_sign
+            .input
+            .iter()
+            .skip(1)
+            .map(|txin| txin.previous_output);
+        if !spent.eq(self.coins.iter().map(|c| c.outpoint)) {
+            return Err(Bip322Error::InvalidToSign(
+                "inputs do not match the coins of the proof",
+            ));
+        }
+        let prevouts: Vec<_> = self
+            .coins
+            .iter()
+            .map(|c| TxOut {
+                value: c.amount,
+                script_pubkey: c.script_pubkey.clone(),
+            })
+            .collect();
+        match verify(
+            &address.script_pubkey(),
+            self.message.as_bytes(),
+            &self.proof,
+            &prevouts,
+            secp,
+        )? {
+            SignatureFormat::Full => Ok(self.total()),
+            SignatureFormat::Simple => Err(Bip322Error::InvalidToSign(
+                "a proof of reserves must use the full format",
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{descriptors::CoincubeDescriptor, signer::HotSigner};
+    use miniscript::bitcoin::{bip32, Network};
+    use std::str::FromStr;
+
+    fn key(signer: &HotSigner, secp: &secp256k1::Secp256k1<secp256k1::All>) -> String {
+        let origin = bip32::DerivationPath::from_str("m/48'/1'/0'/2'").unwrap();
+        format!(
+            "[{}/48'/1'/0'/2']{}/<0;1>/*",
+            signer.fingerprint(secp),
+            signer.xpub_at(&origin, secp)
+        )
+    }
+
+    // Sign and finalize a `to_sign` PSBT for the address at this index with the given signer.
+    fn sign(
+        desc: &CoincubeDescriptor,
+        signer: &HotSigner,
+        message: &str,
+        coins: &[(OutPoint, u32)],
+        format: SignatureFormat,
+        secp: &secp256k1::Secp256k1<secp256k1::All>,
+    ) -> (ScriptBuf, Vec<TxOut>, Result<String, Bip322Error>) {
+        let addr_desc = desc.receive_descriptor().derive(0.into(), secp);
+        let coins_descs: Vec<_> = coins
+            .iter()
+            .map(|(op, index)| {
+                let coin_desc = desc.receive_descriptor().derive((*index).into(), secp);
+                let txout = TxOut {
+                    value: Amount::from_sat(100_000),
+                    script_pubkey: coin_desc.script_pubkey(),
+                };
+                (coin_desc, (*op, txout))
+            })
+            .collect();
+        let coins: Vec<_> = coins_descs.iter().map(|(_, c)| c.clone()).collect();
+        let mut psbt = to_sign_psbt(&addr_desc.script_pubkey(), message.as_bytes(), &coins);
+        addr_desc.update_psbt_in(&mut psbt.inputs[0]);
+        for (psbt_in, (coin_desc, _)) in psbt.inputs.iter_mut().skip(1).zip(&coins_descs) {
+            coin_desc.update_psbt_in(psbt_in);
+        }
+        let psbt = signer.sign_psbt(psbt, secp).unwrap();
+        (
+            addr_desc.script_pubkey(),
+            coins.into_iter().map(|(_, txout)| txout).collect(),
+            finalize(psbt, format, secp),
+        )
+    }
+
+    #[test]
+    fn message_hashes() {
+        // Test vectors from BIP-0322.
+        assert_eq!(
+            message_hash(b"").to_string(),
+            "c90c269c4f8fcbe6880f72a721ddfbf1914268a794cbb21cfafee13770ae19f1"
+        );
+        assert_eq!(
+            message_hash(b"Hello World").to_string(),
+            "f0eb03b1a75ac6d9847f55c624a99169b5dccba2a31f5b23bea77ba270de0a7a"
+        );
+    }
+
+    #[test]
+    fn virtual_transactions() {
+        // Test vectors from BIP-0322, for the address
+        // bc1q9vza2e8x573nczrlzms0wvx3gsqjx7vavgkx0l.
+        let spk = ScriptBuf::from_hex("00142b05d564e6a7a33c087f16e0f730d1440123799d").unwrap();
+        let to_spend_tx = to_spend(&spk, b"");
+        assert_eq!(
+            to_spend_tx.compute_txid().to_string(),
+            "c5680aa69bb8d860bf82d4e9cd3504b55dde018de765a91bb566283c545a99a7"
+        );
+        assert_eq!(
+            to_sign(&to_spend_tx, &[]).compute_txid().to_string(),
+            "1e9654e951a5ba44c8604c4de6c67fd78a27e81dcadcfe1edf638ba3aaebaed6"
+        );
+        let to_spend_tx = to_spend(&spk, b"Hello World");
+        assert_eq!(
+            to_spend_tx.compute_txid().to_string(),
+            "b79d196740ad5217771c1098fc4a4b51e0535c32236c71f1ea4d61a2d603352b"
+        );
+        assert_eq!(
+            to_sign(&to_spend_tx, &[]).compute_txid().to_string(),
+            "88737ae86f2077145f93cc4b153ae9a1cb8d56afa511988c149c5c8c9d93bddf"
+        );
+    }
+
+    #[test]
+    fn sign_and_verify() {
+        let secp = secp256k1::Secp256k1::new();
+        let (prim_signer, recov_signer) = (
+            HotSigner::generate(Network::Testnet).unwrap(),
+            HotSigner::generate(Network::Testnet).unwrap(),
+        );
+        let (prim_key, recov_key) = (key(&prim_signer, &secp), key(&recov_signer, &secp));
+        let descs = [
+            format!(
+                "wsh(or_d(pk({}),and_v(v:pkh({}),older(52560))))",
+                prim_key, recov_key
+            ),
+            format!("tr({},and_v(v:pk({}),older(52560)))", prim_key, recov_key),
+        ];
+        let coin = OutPoint::from_str(
+            "4613e078e4cdbb0fce1bc6e44b028f0e11621a134a1605efdc456c32d155c922:19",
+        )
+        .unwrap();
+
+        for desc in descs {
+            let desc = CoincubeDescriptor::from_str(&desc).unwrap();
+            for format in [SignatureFormat::Simple, SignatureFormat::Full] {
+                let (spk, _, sig) = sign(&desc, &prim_signer, "Hello World", &[], format, &secp);
+                let sig = sig.unwrap();
+                assert_eq!(verify(&spk, b"Hello World", &sig, &[], &secp), Ok(format));
+                // Another message, or another address, doesn't verify.
+                assert!(verify(&spk, b"Hello World!", &sig, &[], &secp).is_err());
+                let other_spk = desc
+                    .receive_descriptor()
+                    .derive(1.into(), &secp)
+                    .script_pubkey();
+                assert!(verify(&other_spk, b"Hello World", &sig, &[], &secp).is_err());
+            }
+
+            // The recovery key alone can't sign as the timelock isn't satisfied.
+            let (_, _, sig) = sign(
+                &desc,
+                &recov_signer,
+                "Hello World",
+                &[],
+                SignatureFormat::Simple,
+                &secp,
+            );
+            assert!(matches!(sig, Err(Bip322Error::Finalization(_))));
+
+            // Proof of funds over a coin at another address.
+            let (spk, prevouts, sig) = sign(
+                &desc,
+                &prim_signer,
+                "Reserves",
+                &[(coin, 5)],
+                SignatureFormat::Full,
+                &secp,
+            );
+            let sig = sig.unwrap();
+            assert_eq!(
+                verify(&spk, b"Reserves", &sig, &prevouts, &secp),
+                Ok(SignatureFormat::Full)
+            );
+            // The previous outputs must be given.
+            assert!(matches!(
+                verify(&spk, b"Reserves", &sig, &[], &secp),
+                Err(Bip322Error::PrevoutsMismatch { .. })
+            ));
+            // And must be correct. Taproot signatures commit to the amounts of all the inputs,
+            // segwit v0 ones only to that of the input they sign.
+            let mut wrong_prevouts = prevouts.clone();
+            wrong_prevouts[0].value = Amount::from_sat(1);
+            assert!(matches!(
+                verify(&spk, b"Reserves", &sig, &wrong_prevouts, &secp),
+                Err(Bip322Error::InvalidSignature(..))
+            ));
+
+            let proof = ReserveProof {
+                message: "Reserves".to_string(),
+                address: bitcoin::Address::from_script(&spk, Network::Testnet)
+                    .unwrap()
+                    .into_unchecked(),
+                network: Network::Testnet,
+                coins: vec![ReserveCoin {
+                    outpoint: coin,
+                    amount: prevouts[0].value,
+                    script_pubkey: prevouts[0].script_pubkey.clone(),
+                }],
+                proof: sig,
+            };
+            assert_eq!(proof.verify(&secp), Ok(Amount::from_sat(100_000)));
+
+            // A proof of funds can't be in the simple format.
+            let (_, _, sig) = sign(
+                &desc,
+                &prim_signer,
+                "Reserves",
+                &[(coin, 5)],
+                SignatureFormat::Simple,
+                &secp,
+            );
+            assert!(matches!(sig, Err(Bip322Error::Finalization(_))));
+        }
+    }
+}
@@ -687,6 +687,31 @@ impl CoincubePolicy {
         &self.recovery_paths
     }
 
+    /// The same policy with each of its keys replaced by the result of the given function, for
+    /// instance to rehearse spending from it with other keys or on another network. All the keys
+    /// are checked again.
+    pub fn map_keys<F>(&self, mut f: F) -> Result<CoincubePolicy, CoincubePolicyError>
+    where
+        F: FnMut(&descriptor::DescriptorPublicKey) -> descriptor::DescriptorPublicKey,
+    {
+        let mut map_path = |path: &PathInfo| match path {
+            PathInfo::Single(key) => PathInfo::Single(f(key)),
+            PathInfo::Multi(k, keys) => PathInfo::Multi(*k, keys.iter().map(&mut f).collect()),
+        };
+        let primary_path = map_path(&self.primary_path);
+        let recovery_paths = self
+            .recovery_paths
+            .iter()
+            .map(|(timelock, path)| (*timelock, map_path(path)))
+            .collect();
+        CoincubePolicy::_new(
+            primary_path,
+            recovery_paths,
+            self.is_taproot,
+            /* compile = */ true,
+        )
+    }
+
     fn into_policy(
         self,
     ) -> Result<miniscript::policy::Concrete<descriptor::DescriptorPublicKey>, CoincubePolicyError>
@@ -588,6 +588,41 @@ impl CoincubeDescriptor {
         indexes
     }
 
+    /// Get the derivation index of this Script and whether it is for the change keychain, if it
+    /// derives from this descriptor at the last step of one of the given derivation paths.
+    pub fn spk_derivation<'a>(
+        &self,
+        spk: &bitcoin::Script,
+        der_paths: impl IntoIterator<Item = &'a bip32::DerivationPath>,
+        secp: &secp256k1::Secp256k1<impl secp256k1::Verification>,
+    ) -> Option<(bip32::ChildNumber, bool)> {
+        der_paths
+            .into_iter()
+            .filter_map(|der_path| der_path.into_iter().last().copied())
+            .filter(|index| index.is_normal())
+            .find_map(|index| {
+                if self
+                    .change_desc
+                    .derive(index, secp)
+                    .script_pubkey()
+                    .as_script()
+                    == spk
+                {
+                    Some((index, true))
+                } else if self
+                    .receive_desc
+                    .derive(index, secp)
+                    .script_pubkey()
+                    .as_script()
+                    == spk
+                {
+                    Some((index, false))
+                } else {
+                    None
+                }
+            })
+    }
+
     /// Prune the BIP32 derivations in all the PSBT inputs for all the spending paths but the given
     /// one.
     pub fn prune_bip32_derivs(&self, mut psbt: Psbt, spending_path: &PathInfo) -> Psbt {
@@ -1473,6 +1508,52 @@ mod tests {
         roundtrip("tr(xpub661MyMwAqRbcGqmqNapgQ9kqrLcDeZLHPktzsBcZXTtNx7aEay8NKQPizKcpu2fUejNbZzhZQaZLeDWL3nt8zg9QbFLBUTRQu4qqcSzeEmF/<0;1>/*,{{and_v(v:multi_a(1,[b4e32970]xpub661MyMwAqRbcEbs6ohRoUqTckEfLeT3vB2EsuWuckrEuDSKqdFXV6so8xJb4kvA4ZxT6hCydyFKsKwJrDm2LgSfTCphVqZgQbLzF49KwaXc/<0;1>/*,[c318e87f]xpub661MyMwAqRbcG2qnrFJ2MhKFSHehbVkK38gFfG7zXwasN51dKrL4kffj1HRd2zFhAZeQsjYKS8YaiN4sC4gVPHR28qXdQf7pf7nbYoefg6T/<0;1>/*),older(1678)),{and_v(v:pk([6c0d38a3]xpub661MyMwAqRbcF87hAvenL8GHW7qxhtn8Y9zHVkQbuTsd6RVtWkhBY5gh6m4Rua9ENmYDx7jTb8kbiyVB9iaLAbyRudxPFVTFoGPp6rTqoZn/<0;1>/*),older(42)),and_v(v:multi_a(2,[2e1370a6]xpub661MyMwAqRbcGRzCgSNLW7VFUFdwvC1dFXmKgWbZwQERj2QfNQuy5diCQSHNXuQYSS9FwXykLeWKtnZ5yRJ4ZHZzYqWf13FUY4PbDpBhipr/<0;1>/*,[fae2633e]xpub661MyMwAqRbcG9qKwZ7F363Mx3Ai3H2aMXAWTjvYCZrH4wqDEDLnsVghWFrwTKwpDGGzsSDCL7vPTiaiY7DhhdV2bY6RdPNGd7bF9om1MFz/<0;1>/*,[2ae87e33]xpub661MyMwAqRbcGw8ZvGfdLEjhCk4YC9hZrrUceKipiH32ANDMQccYFqq91kH8RpcwGiPnCbUWFo1S6ZGY2GxbVdJFsMYXqzpL1byJ1D3G2Mh/<0;1>/*),older(43))}},multi_a(2,[40f48611]xpub661MyMwAqRbcGUkDb45NBcMYwaaSE3fhsMNwvdf2psYhrqhFmRJY9n8irJuEB3juhK5LQPBiiqdr2gixMmC7Nmtg3Mwu4C5wbeagaAzbb9W/<0;1>/*,[a2bdfbe5]xpub661MyMwAqRbcH228eUBaJvc7Va1y7cGyEH9DZ5vPneKgZDX8eMsSd8PHS3uRYCFySyHPy3VfGfS8vKb5FzcS2MbNorNVv2c3Hn7AvVJJZ73/<0;1>/*,[028ece7a]xpub661MyMwAqRbcG9W1pZzs7rvWVtHeW1anzABj8iQRBbnz8yLf7vgUmYkVsydLf1hLffibgfzUjTBcrNCDKaBNnuqLtsp1xyiLSZJyLDtEjkF/<0;1>/*)})#xgzxdvrv");
     }
 
+    #[test]
+    fn policy_map_keys() {
+        let to_test_network = |key: &DescriptorPublicKey| match key {
+            DescriptorPublicKey::MultiXPub(xpub) => {
+                let mut xpub = xpub.clone();
+                xpub.xkey.network = bitcoin::NetworkKind::Test;
+                DescriptorPublicKey::MultiXPub(xpub)
+            }
+            _ => unreachable!("all keys must be of MultiXpub type"),
+        };
+
+        // The same policy with the same keys, for another network.
+        for desc_str in [
+            "wsh(or_d(multi(3,[aabbccdd]xpub6Eze7yAT3Y1wGrnzedCNVYDXUqa9NmHVWck5emBaTbXtURbe1NWZbK9bsz1TiVE7Cz341PMTfYgFw1KdLWdzcM1UMFTcdQfCYhhXZ2HJvTW/<0;1>/*,[aabb0011/10/4893]xpub6Bw79HbNSeS2xXw1sngPE3ehnk1U3iSPCgLYzC9LpN8m9nDuaKLZvkg8QXxL5pDmEmQtYscmUD8B9MkAAZbh6vxPzNXMaLfGQ9Sb3z85qhR/<0;1>/*,[aabb0022]xpub67zuTXF9Ln4731avKTBSawoVVNRuMfmRvkL7kLUaLBRqma9ZqdHBJg9qx8cPUm3oNQMiXT4TmGovXNoQPuwg17RFcVJ8YrnbcooN7pxVJqC/<0;1>/*),and_v(v:multi(2,[aabbccdd]xpub69cP4Y7S9TWcbSNxmk6CEDBsoaqr3ZEdjHuZcHxEFFKGh569RsJNr2V27XGhsbH9FXgWUEmKXRN7c5wQfq2VPjt31xP9VsYnVUyU8HcVevm/<0;1>/*,[aabb0011]xpub6AA2N8RALRYgLD6jT1iXYCEDkndTeZndMtWPbtNX6sY5dPiLtf2T88ahdxrGXMUPoNadgR86sFhBXWQVgifPzDYbY9ZtwK4gqzx4y5Da1DW/<0;1>/*,[aabb0022/10/4893]xpub6AyxexvxizZJffF153evmfqHcE9MV88fCNCAtP3jQjXJHwrAKri71Tq9jWUkPxj9pja4u6AkCPHY7atgxzSEa2HtDwJfrRWKK4fsfQg4o77/<0;1>/*),older(26352))))#csjdk94l",
+            "tr([8344c025]xpub661MyMwAqRbcG2SYC6YSRsUGvcSxXEZm1kjiQRTEaAqart1PQk1N1hVTTEsGfaBx6xQ5gDYXXtbourodE6ZE5qZTnaMgmehNs8GGEEY9YK6/<0;1>/*,and_v(v:pk([158fd0ef]xpub661MyMwAqRbcF2KsCnvJ4mqWXXrwd3799wCyQrLk2iNDC6CfK8UcfnABdeTpXyoJnBhRTybmtBLDAuTuHye1eQMq43BSLtR2miA6t9KqmWU/<0;1>/*),older(4242)))#zy3kddhj",
+        ] {
+            let desc = CoincubeDescriptor::from_str(desc_str).unwrap();
+            let policy = desc.policy().map_keys(to_test_network).unwrap();
+            let drill_desc = CoincubeDescriptor::new(policy.clone());
+            assert!(drill_desc.all_xpubs_net_is(bitcoin::Network::Signet));
+            assert_eq!(drill_desc.is_taproot(), desc.is_taproot());
+            assert_eq!(
+                drill_desc.policy().recovery_paths().keys().collect::<Vec<_>>(),
+                desc.policy().recovery_paths().keys().collect::<Vec<_>>()
+            );
+            assert_eq!(
+                drill_desc.policy().primary_path().thresh_origins(),
+                desc.policy().primary_path().thresh_origins()
+            );
+            // The descriptor is a valid Coincube descriptor, with its unspendable internal key
+            // recomputed for Taproot.
+            CoincubeDescriptor::from_str(&drill_desc.to_string()).unwrap();
+        }
+
+        // The keys are checked again.
+        let desc = CoincubeDescriptor::from_str("wsh(or_d(pk([aabbccdd]xpub6Eze7yAT3Y1wGrnzedCNVYDXUqa9NmHVWck5emBaTbXtURbe1NWZbK9bsz1TiVE7Cz341PMTfYgFw1KdLWdzcM1UMFTcdQfCYhhXZ2HJvTW/<0;1>/*),and_v(v:pkh([aabbccdd]xpub688Hn4wScQAAiYJLPg9yH27hUpfZAUnmJejRQBCiwfP5PEDzjWMNW1wChcninxr5gyavFqbbDjdV1aK5USJz8NDVjUy7FRQaaqqXHh5SbXe/<0;1>/*),older(52560))))#7437yjrs").unwrap();
+        let first_key = match desc.policy().primary_path() {
+            PathInfo::Single(key) => key.clone(),
+            _ => unreachable!(),
+        };
+        assert!(matches!(
+            desc.policy().map_keys(|_| first_key.clone()),
+            Err(CoincubePolicyError::DuplicateKey(_))
+        ));
+    }
+
     fn psbt_from_str(psbt_str: &str) -> Psbt {
         Psbt::from_str(psbt_str).unwrap()
     }
@@ -2279,6 +2360,32 @@ mod tests {
             indexes[1],
             ChangeOutput::DepositAddress { index: 2 }
         ));
+
+        // The derivation of an output's Script can be found from its BIP32 derivations.
+        let psbt_out = &psbt.outputs[2];
+        let der_paths: Vec<_> = psbt_out
+            .bip32_derivation
+            .values()
+            .map(|(_, der_path)| der_path)
+            .chain(
+                psbt_out
+                    .tap_key_origins
+                    .values()
+                    .map(|(_, (_, der_path))| der_path),
+            )
+            .collect();
+        assert_eq!(
+            desc.spk_derivation(
+                &psbt.unsigned_tx.output[2].script_pubkey,
+                der_paths.clone(),
+                secp
+            ),
+            Some((bip32::ChildNumber::from(424242), false))
+        );
+        assert_eq!(
+            desc.spk_derivation(&psbt.unsigned_tx.output[0].script_pubkey, der_paths, secp),
+            None
+        );
     }
 
     #[test]
@@ -1,4 +1,7 @@
+pub mod bip322;
 pub mod descriptors;
+pub mod privacy;
+pub mod psbtv2;
 pub mod random;
 pub mod signer;
 pub mod spend;
@@ -0,0 +1,517 @@
+//! Local assessment of the privacy of coins and spends.
+//!
+//! Chain analysis relies on a few well known heuristics to cluster the coins of a wallet and to
+//! tell apart the payment and the change outputs of a transaction. This module checks coins and
+//! spend PSBTs against these heuristics so the user can be warned before signing. Nothing is
+//! queried from outside the wallet: the assessment is computed from the PSBT, the labels and the
+//! addresses of the coins of the wallet.
+
+use crate::descriptors::CoincubeDescriptor;
+
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt,
+};
+
+use miniscript::bitcoin::{self, psbt::Psbt, secp256k1};
+
+/// A payment of a multiple of this amount is considered to be a round amount, for which the change
+/// output is the one which is not round.
+pub const ROUND_AMOUNT_SATS: u64 = 10_000;
+
+/// A privacy leak of a coin or of a spend.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PrivacyWarning {
+    /// Other coins of the wallet were received on the address of this coin.
+    ReusedCoinAddress { coins: usize },
+    /// These inputs spend coins received on an address on which other coins are left unspent,
+    /// which will be linked to this transaction once spent.
+    CoinsLeftOnInputAddress { inputs: Vec<usize>, coins: usize },
+    /// This output pays to an address which already received coins of the wallet.
+    ReusedOutputAddress { output: usize },
+    /// This payment output has a round amount while the change does not.
+    RoundAmount { output: usize },
+    /// This change output uses a different script type than all the payment outputs.
+    ChangeScriptType { output: usize },
+    /// The inputs spend coins from differently labelled sources, linking them together.
+    MixedSources { labels: Vec<String> },
+    /// This number of inputs could be removed from the spend, they needlessly link coins together.
+    UnnecessaryInputs { count: usize },
+}
+
+impl fmt::Display for PrivacyWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::ReusedCoinAddress { coins } => write!(
+                f,
+                "The address of this coin was reused, {} other coin(s) were received on it.",
+                coins
+            ),
+            Self::CoinsLeftOnInputAddress { inputs, coins } => write!(
+                f,
+                "Input(s) {} spend coins received on an address on which {} other coin(s) are left, \
+                 they will be linked to this transaction once spent.",
+                inputs
+                    .iter()
+                    .map(|i| format!("#{}", i))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                coins
+            ),
+            Self::ReusedOutputAddress { output } => write!(
+                f,
+                "Output #{} pays to an address which already received coins.",
+                output
+            ),
+            Self::RoundAmount { output } => write!(
+                f,
+                "The round amount of the payment of output #{} gives away which output is the \
+                 change.",
+                output
+            ),
+            Self::ChangeScriptType { output } => write!(
+                f,
+                "The change output #{} has a different address type than the payments, which \
+                 gives it away.",
+                output
+            ),
+            Self::MixedSources { labels } => write!(
+                f,
+                "Coins from different sources ({}) are spent together, linking them.",
+                labels.join(", ")
+            ),
+            Self::UnnecessaryInputs { count } => write!(
+                f,
+                "{} input(s) are not needed to pay for this transaction and needlessly link coins \
+                 together.",
+                count
+            ),
+        }
+    }
+}
+
+/// The privacy assessment of a coin or a spend.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PrivacyAssessment {
+    pub warnings: Vec<PrivacyWarning>,
+}
+
+impl PrivacyAssessment {
+    /// A score out of 100, lowered by each of the warnings depending on how much it leaks.
+    pub fn score(&self) -> u8 {
+        let penalty: u8 = self
+            .warnings
+            .iter()
+            .map(|w| match w {
+                PrivacyWarning::ReusedCoinAddress { .. }
+                | PrivacyWarning::CoinsLeftOnInputAddress { .. }
+                | PrivacyWarning::ReusedOutputAddress { .. }
+                | PrivacyWarning::MixedSources { .. } => 30,
+                PrivacyWarning::ChangeScriptType { .. } => 20,
+                PrivacyWarning::RoundAmount { .. } | PrivacyWarning::UnnecessaryInputs { .. } => 10,
+            })
+            .fold(0, u8::saturating_add);
+        100u8.saturating_sub(penalty)
+    }
+}
+
+/// Assess the privacy of each of these coins of the wallet, given as their outpoint and the Script
+/// they were received on.
+pub fn assess_coins<'a>(
+    coins: impl IntoIterator<Item = (bitcoin::OutPoint, &'a bitcoin::Script)>,
+) -> HashMap<bitcoin::OutPoint, PrivacyAssessment> {
+    let coins: Vec<_> = coins.into_iter().collect();
+    let mut counts = HashMap::<&bitcoin::Script, usize>::new();
+    for (_, spk) in &coins {
+        *counts.entry(*spk).or_insert(0) += 1;
+    }
+    coins
+        .iter()
+        .map(|(op, spk)| {
+            let mut assessment = PrivacyAssessment::default();
+            let others = counts[spk] - 1;
+            if others > 0 {
+                assessment
+                    .warnings
+                    .push(PrivacyWarning::ReusedCoinAddress { coins: others });
+            }
+            (*op, assessment)
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ScriptType {
+    P2pkh,
+    P2sh,
+    P2wpkh,
+    P2wsh,
+    P2tr,
+    Other,
+}
+
+impl ScriptType {
+    fn of(spk: &bitcoin::Script) -> Self {
+        if spk.is_p2pkh() {
+            Self::P2pkh
+        } else if spk.is_p2sh() {
+            Self::P2sh
+        } else if spk.is_p2wpkh() {
+            Self::P2wpkh
+        } else if spk.is_p2wsh() {
+            Self::P2wsh
+        } else if spk.is_p2tr() {
+            Self::P2tr
+        } else {
+            Self::Other
+        }
+    }
+}
+
+/// Assess the privacy of a spend of this descriptor before it is signed.
+///
+/// The `labels` of the wallet are used to tell the source of the coins spent: the label of the
+/// coin, or else that of the address it was received on. The `wallet_scripts` are the Scripts all
+/// the coins of the wallet, spent ones included, were received on, each with the coins still
+/// unspent on it.
+pub fn assess_spend(
+    desc: &CoincubeDescriptor,
+    secp: &secp256k1::Secp256k1<impl secp256k1::Verification>,
+    psbt: &Psbt,
+    labels: &HashMap<String, String>,
+    wallet_scripts: &HashMap<bitcoin::ScriptBuf, Vec<bitcoin::OutPoint>>,
+    network: bitcoin::Network,
+) -> PrivacyAssessment {
+    let tx = &psbt.unsigned_tx;
+    let mut warnings = Vec::new();
+
+    // The coins spent, if the PSBT carries them.
+    let prevouts: Option<Vec<&bitcoin::TxOut>> = psbt
+        .inputs
+        .iter()
+        .map(|psbt_in| psbt_in.witness_utxo.as_ref())
+        .collect();
+
+    // Address reuse. Spending all the coins of a reused address together leaks nothing more than
+    // the reuse itself, but leaving some behind links their future spend to this one.
+    if let Some(prevouts) = &prevouts {
+        let spent: HashSet<_> = tx.input.iter().map(|txin| txin.previous_output).collect();
+        let mut inputs_by_spk = HashMap::<&bitcoin::Script, Vec<usize>>::new();
+        for (i, txo) in prevouts.iter().enumerate() {
+            inputs_by_spk
+                .entry(txo.script_pubkey.as_script())
+                .or_default()
+                .push(i);
+        }
+        let mut left: Vec<_> = inputs_by_spk
+            .into_iter()
+            .filter_map(|(spk, inputs)| {
+                let coins = wallet_scripts
+                    .get(spk)?
+                    .iter()
+                    .filter(|op| !spent.contains(op))
+                    .count();
+                (coins > 0).then_some((inputs, coins))
+            })
+            .collect();
+        left.sort();
+        warnings.extend(
+            left.into_iter()
+                .map(|(inputs, coins)| PrivacyWarning::CoinsLeftOnInputAddress { inputs, coins }),
+        );
+    }
+    for (i, txo) in tx.output.iter().enumerate() {
+        let spent_from = prevouts
+            .as_ref()
+            .is_some_and(|p| p.iter().any(|prev| prev.script_pubkey == txo.script_pubkey));
+        if spent_from || wallet_scripts.contains_key(&txo.script_pubkey) {
+            warnings.push(PrivacyWarning::ReusedOutputAddress { output: i });
+        }
+    }
+
+    // Change identifiability. OP_RETURN outputs are neither payments nor change.
+    let change_indexes: HashSet<usize> = desc
+        .change_indexes(psbt, secp)
+        .into_iter()
+        .map(|c| c.index())
+        .collect();
+    let payments: Vec<(usize, &bitcoin::TxOut)> = tx
+        .output
+        .iter()
+        .enumerate()
+        .filter(|(i, txo)| !change_indexes.contains(i) && !txo.script_pubkey.is_op_return())
+        .collect();
+    let is_round = |txo: &bitcoin::TxOut| txo.value.to_sat().is_multiple_of(ROUND_AMOUNT_SATS);
+    if !payments.is_empty() && !change_indexes.is_empty() {
+        let change_is_round = change_indexes
+            .iter()
+            .filter_map(|i| tx.output.get(*i))
+            .any(is_round);
+        if !change_is_round {
+            warnings.extend(
+                payments
+                    .iter()
+                    .filter(|(_, txo)| is_round(txo))
+                    .map(|(i, _)| PrivacyWarning::RoundAmount { output: *i }),
+            );
+        }
+        let mut change_indexes: Vec<_> = change_indexes.iter().copied().collect();
+        change_indexes.sort();
+        for i in change_indexes {
+            let change_type = ScriptType::of(&tx.output[i].script_pubkey);
+            if payments
+                .iter()
+                .all(|(_, txo)| ScriptType::of(&txo.script_pubkey) != change_type)
+            {
+                warnings.push(PrivacyWarning::ChangeScriptType { output: i });
+            }
+        }
+    }
+
+    // Mixing of coins from different sources.
+    let sources: BTreeSet<&String> = tx
+        .input
+        .iter()
+        .enumerate()
+        .filter_map(|(i, txin)| {
+            labels
+                .get(&txin.previous_output.to_string())
+                .or_else(|| {
+                    let txo = prevouts.as_ref()?.get(i)?;
+                    let address =
+                        bitcoin::Address::from_script(&txo.script_pubkey, network).ok()?;
+                    labels.get(&address.to_string())
+                })
+                .filter(|label| !label.is_empty())
+        })
+        .collect();
+    if sources.len() > 1 {
+        warnings.push(PrivacyWarning::MixedSources {
+            labels: sources.into_iter().cloned().collect(),
+        });
+    }
+
+    // Inputs which are not needed to pay for the payments and the fee: removing them would only
+    // decrease the change. Self-transfers have no payment to pay for.
+    if let (Some(prevouts), false, false) =
+        (&prevouts, payments.is_empty(), change_indexes.is_empty())
+    {
+        let inputs_value: bitcoin::Amount = prevouts.iter().map(|txo| txo.value).sum();
+        let outputs_value: bitcoin::Amount = tx.output.iter().map(|txo| txo.value).sum();
+        let change_value: bitcoin::Amount = change_indexes
+            .iter()
+            .filter_map(|i| tx.output.get(*i))
+            .map(|txo| txo.value)
+            .sum();
+        if let Some(needed) = inputs_value
+            .checked_sub(outputs_value)
+            .and_then(|fee| (outputs_value - change_value).checked_add(fee))
+        {
+            let mut values: Vec<_> = prevouts.iter().map(|txo| txo.value).collect();
+            values.sort();
+            let mut remaining = inputs_value;
+            let mut count = 0;
+            for value in values {
+                if remaining - value < needed {
+                    break;
+                }
+                remaining -= value;
+                count += 1;
+            }
+            if count > 0 {
+                warnings.push(PrivacyWarning::UnnecessaryInputs { count });
+            }
+        }
+    }
+
+    PrivacyAssessment { warnings }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use miniscript::bitcoin::{absolute, transaction, Amount, OutPoint, TxIn, TxOut};
+    use std::str::FromStr;
+
+    fn txo(spk: &bitcoin::ScriptBuf, sats: u64) -> TxOut {
+        TxOut {
+            script_pubkey: spk.clone(),
+            value: Amount::from_sat(sats),
+        }
+    }
+
+    #[test]
+    fn privacy_assessment() {
+        let secp = secp256k1::Secp256k1::verification_only();
+        let desc = CoincubeDescriptor::from_str("wsh(or_d(pk([8a550171/48'/1'/0'/2']tpubDFnCs5ZaCqopaNhgLCiXAwbkaBdcnuMt1VFoPsRpUrpidyvzG67MYjkfxw6HnTBhHqeU3xw2ioNBVcWY3jXwGhSyppEQvtn38GsL7RH1eef/<0;1>/*),and_v(v:pkh([8a550171/48'/1'/0'/2']tpubDFnCs5ZaCqopaNhgLCiXAwbkaBdcnuMt1VFoPsRpUrpidyvzG67MYjkfxw6HnTBhHqeU3xw2ioNBVcWY3jXwGhSyppEQvtn38GsL7RH1eef/<2;3>/*),older(52596))))#jz5sm0xn").unwrap();
+        let receive = |i: u32| desc.receive_descriptor().derive(i.into(), &secp);
+        let change = desc.change_descriptor().derive(42.into(), &secp);
+        let (a, b) = (receive(0).script_pubkey(), receive(1).script_pubkey());
+        let recipient = bitcoin::Address::from_str("bc1qvrl2849aggm6qry9ea7xqp2kk39j8vaa8r3cwg")
+            .unwrap()
+            .assume_checked()
+            .script_pubkey();
+        let outpoint = |vout: u32| OutPoint {
+            txid: bitcoin::Txid::from_str(
+                "f7bd1b2a995b689d326e51eb742eb1088c4a8f110d9cb56128fd553acc9f88e5",
+            )
+            .unwrap(),
+            vout,
+        };
+
+        // Two coins were received on the same address.
+        let coins = assess_coins([
+            (outpoint(0), a.as_script()),
+            (outpoint(1), a.as_script()),
+            (outpoint(2), b.as_script()),
+        ]);
+        assert_eq!(
+            coins[&outpoint(0)].warnings,
+            vec![PrivacyWarning::ReusedCoinAddress { coins: 1 }]
+        );
+        assert_eq!(coins[&outpoint(0)].score(), 70);
+        assert!(coins[&outpoint(2)].warnings.is_empty());
+        assert_eq!(coins[&outpoint(2)].score(), 100);
+
+        // Spend the three coins to a single round payment to a P2WPKH address, with change.
+        let mut psbt = Psbt::from_unsigned_tx(bitcoin::Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: (0..3)
+                .map(|i| TxIn {
+                    previous_output: outpoint(i),
+                    ..TxIn::default()
+                })
+                .collect(),
+            output: vec![
+                txo(&recipient, 100_000),
+                txo(&change.script_pubkey(), 349_000),
+            ],
+        })
+        .unwrap();
+        for (psbt_in, prevout) in
+            psbt.inputs
+                .iter_mut()
+                .zip([txo(&a, 50_000), txo(&a, 200_000), txo(&b, 200_000)])
+        {
+            psbt_in.witness_utxo = Some(prevout);
+        }
+        change.update_change_psbt_out(&mut psbt.outputs[1]);
+        let labels = HashMap::from([
+            (outpoint(0).to_string(), "Exchange".to_string()),
+            (
+                bitcoin::Address::from_script(&b, bitcoin::Network::Testnet)
+                    .unwrap()
+                    .to_string(),
+                "Salary".to_string(),
+            ),
+        ]);
+        // A third coin is left unspent on the reused address.
+        let wallet_scripts = HashMap::from([
+            (a.clone(), vec![outpoint(0), outpoint(1), outpoint(3)]),
+            (b.clone(), vec![outpoint(2)]),
+        ]);
+        let assessment = assess_spend(
+            &desc,
+            &secp,
+            &psbt,
+            &labels,
+            &wallet_scripts,
+            bitcoin::Network::Testnet,
+        );
+        assert_eq!(
+            assessment.warnings,
+            vec![
+                PrivacyWarning::CoinsLeftOnInputAddress {
+                    inputs: vec![0, 1],
+                    coins: 1
+                },
+                PrivacyWarning::RoundAmount { output: 0 },
+                PrivacyWarning::ChangeScriptType { output: 1 },
+                PrivacyWarning::MixedSources {
+                    labels: vec!["Exchange".to_string(), "Salary".to_string()]
+                },
+                // A single 200k coin is enough to pay for 100k and the fee.
+                PrivacyWarning::UnnecessaryInputs { count: 2 },
+            ]
+        );
+        assert_eq!(assessment.score(), 0);
+        // Spending all the coins of the reused address together is fine.
+        let wallet_scripts = HashMap::from([
+            (a.clone(), vec![outpoint(0), outpoint(1)]),
+            (b.clone(), vec![outpoint(2)]),
+        ]);
+        let assessment = assess_spend(
+            &desc,
+            &secp,
+            &psbt,
+            &labels,
+            &wallet_scripts,
+            bitcoin::Network::Testnet,
+        );
+        assert!(!assessment
+            .warnings
+            .iter()
+            .any(|w| matches!(w, PrivacyWarning::CoinsLeftOnInputAddress { .. })));
+        assert_eq!(assessment.score(), 30);
+
+        // Paying a non-round amount to an address of the same type, with a single coin, is fine.
+        // Unless the recipient address already received coins of the wallet, even spent ones.
+        let mut psbt = Psbt::from_unsigned_tx(bitcoin::Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: outpoint(2),
+                ..TxIn::default()
+            }],
+            output: vec![txo(&a, 123_456), txo(&change.script_pubkey(), 75_544)],
+        })
+        .unwrap();
+        psbt.inputs[0].witness_utxo = Some(txo(&b, 200_000));
+        change.update_change_psbt_out(&mut psbt.outputs[1]);
+        let assessment = assess_spend(
+            &desc,
+            &secp,
+            &psbt,
+            &labels,
+            &HashMap::new(),
+            bitcoin::Network::Testnet,
+        );
+        assert!(assessment.warnings.is_empty());
+        let assessment = assess_spend(
+            &desc,
+            &secp,
+            &psbt,
+            &labels,
+            &HashMap::from([(a.clone(), vec![])]),
+            bitcoin::Network::Testnet,
+        );
+        assert_eq!(
+            assessment.warnings,
+            vec![PrivacyWarning::ReusedOutputAddress { output: 0 }]
+        );
+
+        // A self-transfer of all the coins has no change to give away nor input to spare.
+        let mut psbt = Psbt::from_unsigned_tx(bitcoin::Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: outpoint(2),
+                ..TxIn::default()
+            }],
+            output: vec![txo(&change.script_pubkey(), 199_000)],
+        })
+        .unwrap();
+        psbt.inputs[0].witness_utxo = Some(txo(&b, 200_000));
+        change.update_change_psbt_out(&mut psbt.outputs[0]);
+        let assessment = assess_spend(
+            &desc,
+            &secp,
+            &psbt,
+            &labels,
+            &HashMap::new(),
+            bitcoin::Network::Testnet,
+        );
+        assert!(assessment.warnings.is_empty());
+    }
+}
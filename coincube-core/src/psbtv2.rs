@@ -0,0 +1,539 @@
+//! Support for version 2 PSBTs (BIP-0370).
+//!
+//! We use version 0 PSBTs internally, as does the signing stack we depend upon. Version 2 PSBTs
+//! are converted to version 0 on import by building the unsigned transaction from the per-input
+//! and per-output fields. The version 2 fields which can't be expressed in the unsigned
+//! transaction (the modifiable flags, the locktimes required by inputs and the fallback locktime
+//! when it is not the transaction's one) are kept as unknown fields, so that converting back to
+//! version 2 gives back the original PSBT.
+
+use miniscript::bitcoin::{
+    absolute::{LockTime, LOCK_TIME_THRESHOLD},
+    base64::{engine::general_purpose::STANDARD as BASE64, Engine},
+    consensus::{self, encode::VarInt},
+    hashes::Hash,
+    psbt::Psbt,
+    transaction::Version,
+    Amount, OutPoint, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Txid, Witness,
+};
+
+use std::{
+    convert::{TryFrom, TryInto},
+    error, fmt,
+};
+
+const MAGIC: &[u8] = b"psbt\xff";
+
+const GLOBAL_UNSIGNED_TX: u8 = 0x00;
+const GLOBAL_TX_VERSION: u8 = 0x02;
+const GLOBAL_FALLBACK_LOCKTIME: u8 = 0x03;
+const GLOBAL_INPUT_COUNT: u8 = 0x04;
+const GLOBAL_OUTPUT_COUNT: u8 = 0x05;
+const GLOBAL_VERSION: u8 = 0xfb;
+
+const IN_PREVIOUS_TXID: u8 = 0x0e;
+const IN_OUTPUT_INDEX: u8 = 0x0f;
+const IN_SEQUENCE: u8 = 0x10;
+const IN_REQUIRED_TIME_LOCKTIME: u8 = 0x11;
+const IN_REQUIRED_HEIGHT_LOCKTIME: u8 = 0x12;
+
+const OUT_AMOUNT: u8 = 0x03;
+const OUT_SCRIPT: u8 = 0x04;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PsbtError {
+    /// The string is not valid base64.
+    Base64(String),
+    /// The data does not start with the PSBT magic bytes.
+    InvalidMagic,
+    /// The key-value maps could not be decoded.
+    Encoding(String),
+    /// The same key appears twice in a map.
+    DuplicateKey(Vec<u8>),
+    /// Only versions 0 and 2 are defined.
+    UnsupportedVersion(u32),
+    /// A field required in version 2 PSBTs is missing.
+    MissingField(&'static str),
+    /// A field which must be excluded from version 2 PSBTs is present.
+    UnexpectedField(&'static str),
+    /// A field has an invalid value.
+    InvalidField(&'static str),
+    /// No locktime satisfies the requirements of all the inputs.
+    IncompatibleLocktimes,
+    /// The resulting version 0 PSBT is invalid.
+    Psbt(String),
+}
+
+impl fmt::Display for PsbtError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Base64(e) => write!(f, "Invalid base64 encoding: {}", e),
+            Self::InvalidMagic => write!(f, "Invalid PSBT magic bytes"),
+            Self::Encoding(e) => write!(f, "Invalid PSBT encoding: {}", e),
+            Self::DuplicateKey(k) => write!(f, "Duplicate PSBT key: {:x?}", k),
+            Self::UnsupportedVersion(v) => write!(f, "Unsupported PSBT version: {}", v),
+            Self::MissingField(field) => write!(f, "Missing required field {}", field),
+            Self::UnexpectedField(field) => {
+                write!(f, "Field {} is not allowed in a version 2 PSBT", field)
+            }
+            Self::InvalidField(field) => write!(f, "Invalid value for field {}", field),
+            Self::IncompatibleLocktimes => write!(
+                f,
+                "Inputs require both a height-based and a time-based locktime"
+            ),
+            Self::Psbt(e) => write!(f, "Invalid PSBT: {}", e),
+        }
+    }
+}
+
+impl error::Error for PsbtError {}
+
+/// A raw key-value pair of a PSBT map. The key includes its type.
+type Pair = (Vec<u8>, Vec<u8>);
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, pos: 0 }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pos >= self.bytes.len()
+    }
+
+    fn read_compact_size(&mut self) -> Result<u64, PsbtError> {
+        let (VarInt(n), len) = consensus::deserialize_partial::<VarInt>(&self.bytes[self.pos..])
+            .map_err(|e| PsbtError::Encoding(e.to_string()))?;
+        self.pos += len;
+        Ok(n)
+    }
+
+    fn read_slice(&mut self, len: u64) -> Result<&'a [u8], PsbtError> {
+        let end = usize::try_from(len)
+            .ok()
+            .and_then(|len| self.pos.checked_add(len))
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| PsbtError::Encoding("unexpected end of data".to_string()))?;
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    /// Read a map up to and including its separator.
+    fn read_map(&mut self) -> Result<Vec<Pair>, PsbtError> {
+        let mut map: Vec<Pair> = Vec::new();
+        loop {
+            let key_len = self.read_compact_size()?;
+            if key_len == 0 {
+                return Ok(map);
+            }
+            let key = self.read_slice(key_len)?.to_vec();
+            let value_len = self.read_compact_size()?;
+            let value = self.read_slice(value_len)?.to_vec();
+            if map.iter().any(|(k, _)| *k == key) {
+                return Err(PsbtError::DuplicateKey(key));
+            }
+            map.push((key, value));
+        }
+    }
+}
+
+fn write_map(out: &mut Vec<u8>, map: &[Pair]) {
+    for (key, value) in map {
+        out.extend(consensus::serialize(&VarInt(key.len() as u64)));
+        out.extend(key);
+        out.extend(consensus::serialize(&VarInt(value.len() as u64)));
+        out.extend(value);
+    }
+    out.push(0x00);
+}
+
+fn get(map: &[Pair], key_type: u8) -> Option<&[u8]> {
+    map.iter()
+        .find(|(k, _)| k[..] == [key_type])
+        .map(|(_, v)| &v[..])
+}
+
+fn take(map: &mut Vec<Pair>, key_type: u8) -> Option<Vec<u8>> {
+    let index = map.iter().position(|(k, _)| k[..] == [key_type])?;
+    Some(map.remove(index).1)
+}
+
+fn u32_value(value: &[u8], field: &'static str) -> Result<u32, PsbtError> {
+    value
+        .try_into()
+        .map(u32::from_le_bytes)
+        .map_err(|_| PsbtError::InvalidField(field))
+}
+
+fn compact_size_value(value: &[u8], field: &'static str) -> Result<u64, PsbtError> {
+    let mut reader = Reader::new(value);
+    match reader.read_compact_size() {
+        Ok(n) if reader.is_empty() => Ok(n),
+        _ => Err(PsbtError::InvalidField(field)),
+    }
+}
+
+/// Determine the locktime of the transaction as per BIP-0370, from the fallback locktime and
+/// the (time, height) locktimes required by each input.
+fn locktime(
+    fallback: Option<u32>,
+    required: &[(Option<u32>, Option<u32>)],
+) -> Result<LockTime, PsbtError> {
+    let required: Vec<_> = required
+        .iter()
+        .filter(|(time, height)| time.is_some() || height.is_some())
+        .collect();
+    if required.is_empty() {
+        return Ok(LockTime::from_consensus(fallback.unwrap_or(0)));
+    }
+    // A height-based locktime is preferred when both types are possible.
+    let lock_time = if required.iter().all(|(_, height)| height.is_some()) {
+        required.iter().filter_map(|(_, height)| *height).max()
+    } else if required.iter().all(|(time, _)| time.is_some()) {
+        required.iter().filter_map(|(time, _)| *time).max()
+    } else {
+        None
+    };
+    lock_time
+        .map(LockTime::from_consensus)
+        .ok_or(PsbtError::IncompatibleLocktimes)
+}
+
+/// Convert the content of a version 2 PSBT, after its global map, to a serialized version 0
+/// PSBT.
+fn v2_to_v0(mut global: Vec<Pair>, mut reader: Reader) -> Result<Vec<u8>, PsbtError> {
+    if get(&global, GLOBAL_UNSIGNED_TX).is_some() {
+        return Err(PsbtError::UnexpectedField("PSBT_GLOBAL_UNSIGNED_TX"));
+    }
+    take(&mut global, GLOBAL_VERSION);
+    let tx_version = take(&mut global, GLOBAL_TX_VERSION)
+        .ok_or(PsbtError::MissingField("PSBT_GLOBAL_TX_VERSION"))
+        .and_then(|v| u32_value(&v, "PSBT_GLOBAL_TX_VERSION"))?;
+    let input_count = take(&mut global, GLOBAL_INPUT_COUNT)
+        .ok_or(PsbtError::MissingField("PSBT_GLOBAL_INPUT_COUNT"))
+        .and_then(|v| compact_size_value(&v, "PSBT_GLOBAL_INPUT_COUNT"))?;
+    let output_count = take(&mut global, GLOBAL_OUTPUT_COUNT)
+        .ok_or(PsbtError::MissingField("PSBT_GLOBAL_OUTPUT_COUNT"))
+        .and_then(|v| compact_size_value(&v, "PSBT_GLOBAL_OUTPUT_COUNT"))?;
+    let fallback_locktime = get(&global, GLOBAL_FALLBACK_LOCKTIME)
+        .map(|v| u32_value(v, "PSBT_GLOBAL_FALLBACK_LOCKTIME"))
+        .transpose()?;
+
+    let mut inputs = Vec::new();
+    let mut required_locktimes = Vec::new();
+    let mut txins = Vec::new();
+    for _ in 0..input_count {
+        let mut input = reader.read_map()?;
+        let txid = take(&mut input, IN_PREVIOUS_TXID)
+            .ok_or(PsbtError::MissingField("PSBT_IN_PREVIOUS_TXID"))
+            .and_then(|v| {
+                <[u8; 32]>::try_from(v)
+                    .map(Txid::from_byte_array)
+                    .map_err(|_| PsbtError::InvalidField("PSBT_IN_PREVIOUS_TXID"))
+            })?;
+        let vout = take(&mut input, IN_OUTPUT_INDEX)
+            .ok_or(PsbtError::MissingField("PSBT_IN_OUTPUT_INDEX"))
+            .and_then(|v| u32_value(&v, "PSBT_IN_OUTPUT_INDEX"))?;
+        let sequence = take(&mut input, IN_SEQUENCE)
+            .map(|v| u32_value(&v, "PSBT_IN_SEQUENCE").map(Sequence))
+            .transpose()?
+            .unwrap_or(Sequence::MAX);
+        // Kept as unknown fields in the version 0 PSBT.
+        let time = get(&input, IN_REQUIRED_TIME_LOCKTIME)
+            .map(|v| {
+                u32_value(v, "PSBT_IN_REQUIRED_TIME_LOCKTIME")
+                    .ok()
+                    .filter(|t| *t >= LOCK_TIME_THRESHOLD)
+                    .ok_or(PsbtError::InvalidField("PSBT_IN_REQUIRED_TIME_LOCKTIME"))
+            })
+            .transpose()?;
+        let height = get(&input, IN_REQUIRED_HEIGHT_LOCKTIME)
+            .map(|v| {
+                u32_value(v, "PSBT_IN_REQUIRED_HEIGHT_LOCKTIME")
+                    .ok()
+                    .filter(|h| *h > 0 && *h < LOCK_TIME_THRESHOLD)
+                    .ok_or(PsbtError::InvalidField("PSBT_IN_REQUIRED_HEIGHT_LOCKTIME"))
+            })
+            .transpose()?;
+        required_locktimes.push((time, height));
+        txins.push(TxIn {
+            previous_output: OutPoint::new(txid, vout),
+            script_sig: ScriptBuf::new(),
+            sequence,
+            witness: Witness::new(),
+        });
+        inputs.push(input);
+    }
+
+    let mut outputs = Vec::new();
+    let mut txouts = Vec::new();
+    for _ in 0..output_count {
+        let mut output = reader.read_map()?;
+        let value = take(&mut output, OUT_AMOUNT)
+            .ok_or(PsbtError::MissingField("PSBT_OUT_AMOUNT"))
+            .and_then(|v| {
+                <[u8; 8]>::try_from(v)
+                    .ok()
+                    .and_then(|v| u64::try_from(i64::from_le_bytes(v)).ok())
+                    .map(Amount::from_sat)
+                    .ok_or(PsbtError::InvalidField("PSBT_OUT_AMOUNT"))
+            })?;
+        let script_pubkey = take(&mut output, OUT_SCRIPT)
+            .map(ScriptBuf::from_bytes)
+            .ok_or(PsbtError::MissingField("PSBT_OUT_SCRIPT"))?;
+        txouts.push(TxOut {
+            value,
+            script_pubkey,
+        });
+        outputs.push(output);
+    }
+    if !reader.is_empty() {
+        return Err(PsbtError::Encoding("trailing data".to_string()));
+    }
+
+    // The fallback locktime is only kept, as an unknown field, if it can't be deduced from the
+    // locktime of the unsigned transaction.
+    let lock_time = locktime(fallback_locktime, &required_locktimes)?;
+    let has_required_locktime = required_locktimes
+        .iter()
+        .any(|(time, height)| time.is_some() || height.is_some());
+    if !has_required_locktime && fallback_locktime.is_some_and(|l| l != 0) {
+        take(&mut global, GLOBAL_FALLBACK_LOCKTIME);
+    }
+    let unsigned_tx = Transaction {
+        version: Version(tx_version as i32),
+        lock_time,
+        input: txins,
+        output: txouts,
+    };
+    global.insert(
+        0,
+        (vec![GLOBAL_UNSIGNED_TX], consensus::serialize(&unsigned_tx)),
+    );
+
+    let mut out = MAGIC.to_vec();
+    for map in std::iter::once(&global)
+        .chain(inputs.iter())
+        .chain(outputs.iter())
+    {
+        write_map(&mut out, map);
+    }
+    Ok(out)
+}
+
+/// Deserialize a PSBT of either version 0 or version 2. Version 2 PSBTs are converted to
+/// version 0.
+pub fn deserialize(bytes: &[u8]) -> Result<Psbt, PsbtError> {
+    let mut reader = Reader::new(bytes.strip_prefix(MAGIC).ok_or(PsbtError::InvalidMagic)?);
+    let global = reader.read_map()?;
+    let version = get(&global, GLOBAL_VERSION)
+        .map(|v| u32_value(v, "PSBT_GLOBAL_VERSION"))
+        .transpose()?
+        .unwrap_or(0);
+    let v0_bytes = match version {
+        0 => bytes.to_vec(),
+        2 => v2_to_v0(global, reader)?,
+        v => return Err(PsbtError::UnsupportedVersion(v)),
+    };
+    Psbt::deserialize(&v0_bytes).map_err(|e| PsbtError::Psbt(e.to_string()))
+}
+
+/// Parse a base64-encoded PSBT of either version 0 or version 2. Version 2 PSBTs are converted
+/// to version 0.
+pub fn from_base64(s: &str) -> Result<Psbt, PsbtError> {
+    let bytes = BASE64
+        .decode(s.trim())
+        .map_err(|e| PsbtError::Base64(e.to_string()))?;
+    deserialize(&bytes)
+}
+
+/// Serialize a PSBT as a version 2 PSBT.
+pub fn serialize_v2(psbt: &Psbt) -> Vec<u8> {
+    let v0_bytes = psbt.serialize();
+    let mut reader = Reader::new(&v0_bytes[MAGIC.len()..]);
+    let mut global = reader
+        .read_map()
+        .expect("rust-bitcoin serialization must be valid");
+    let tx = &psbt.unsigned_tx;
+
+    let mut maps = Vec::with_capacity(tx.input.len() + tx.output.len());
+    let mut has_required_locktime = false;
+    for txin in &tx.input {
+        let mut input = reader
+            .read_map()
+            .expect("rust-bitcoin serialization must be valid");
+        has_required_locktime |= get(&input, IN_REQUIRED_TIME_LOCKTIME).is_some()
+            || get(&input, IN_REQUIRED_HEIGHT_LOCKTIME).is_some();
+        for key_type in [IN_PREVIOUS_TXID, IN_OUTPUT_INDEX, IN_SEQUENCE] {
+            take(&mut input, key_type);
+        }
+        input.push((
+            vec![IN_PREVIOUS_TXID],
+            txin.previous_output.txid.to_byte_array().to_vec(),
+        ));
+        input.push((
+            vec![IN_OUTPUT_INDEX],
+            txin.previous_output.vout.to_le_bytes().to_vec(),
+        ));
+        if txin.sequence != Sequence::MAX {
+            input.push((vec![IN_SEQUENCE], txin.sequence.0.to_le_bytes().to_vec()));
+        }
+        maps.push(input);
+    }
+    for txout in &tx.output {
+        let mut output = reader
+            .read_map()
+            .expect("rust-bitcoin serialization must be valid");
+        take(&mut output, OUT_AMOUNT);
+        take(&mut output, OUT_SCRIPT);
+        output.push((
+            vec![OUT_AMOUNT],
+            (txout.value.to_sat() as i64).to_le_bytes().to_vec(),
+        ));
+        output.push((vec![OUT_SCRIPT], txout.script_pubkey.to_bytes()));
+        maps.push(output);
+    }
+
+    for key_type in [
+        GLOBAL_UNSIGNED_TX,
+        GLOBAL_TX_VERSION,
+        GLOBAL_INPUT_COUNT,
+        GLOBAL_OUTPUT_COUNT,
+        GLOBAL_VERSION,
+    ] {
+        take(&mut global, key_type);
+    }
+    global.push((vec![GLOBAL_TX_VERSION], tx.version.0.to_le_bytes().to_vec()));
+    global.push((
+        vec![GLOBAL_INPUT_COUNT],
+        consensus::serialize(&VarInt(tx.input.len() as u64)),
+    ));
+    global.push((
+        vec![GLOBAL_OUTPUT_COUNT],
+        consensus::serialize(&VarInt(tx.output.len() as u64)),
+    ));
+    global.push((vec![GLOBAL_VERSION], 2u32.to_le_bytes().to_vec()));
+    // Unless the locktime is determined by the inputs, the transaction's one is the fallback.
+    // Keep any fallback locktime imported from a version 2 PSBT if it is still accurate.
+    if !has_required_locktime {
+        let lock_time = tx.lock_time.to_consensus_u32();
+        let fallback = get(&global, GLOBAL_FALLBACK_LOCKTIME)
+            .and_then(|v| u32_value(v, "PSBT_GLOBAL_FALLBACK_LOCKTIME").ok())
+            .unwrap_or(0);
+        if fallback != lock_time {
+            take(&mut global, GLOBAL_FALLBACK_LOCKTIME);
+            if lock_time != 0 {
+                global.push((
+                    vec![GLOBAL_FALLBACK_LOCKTIME],
+                    lock_time.to_le_bytes().to_vec(),
+                ));
+            }
+        }
+    }
+
+    let mut out = MAGIC.to_vec();
+    for map in std::iter::once(&mut global).chain(maps.iter_mut()) {
+        map.sort();
+        write_map(&mut out, map);
+    }
+    out
+}
+
+/// Encode a PSBT in base64 as a version 2 PSBT.
+pub fn to_base64_v2(psbt: &Psbt) -> String {
+    BASE64.encode(serialize_v2(psbt))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    // Version 2 PSBTs with 1 input and 2 outputs, from BIP-0370. The second and third PSBTs
+    // require a time-based and a height-based locktime respectively.
+    const V2_PSBTS: &[&str] = &[
+        "cHNidP8BAgQCAAAAAQQBAQEFAQIB+wQCAAAAAAEOIAsK2SFBnByHGXNdctxzn56p4GONH+TB7vD5lECEgV/IAQ8EAAAAAAABAwgArw8DAAAAAAEEFgAUxDD2TEdW2jENvRoIVXLvKZkmJywAAQMIi73rCwAAAAABBBYAFE3Rk6yWSlasG54cyoRU/i9HT4UTAA==",
+        "cHNidP8BAgQCAAAAAQQBAQEFAQIB+wQCAAAAAAEOIAsK2SFBnByHGXNdctxzn56p4GONH+TB7vD5lECEgV/IAQ8EAAAAAAERBIyNxGAAAQMIAK8PAwAAAAABBBYAFMQw9kxHVtoxDb0aCFVy7ymZJicsAAEDCIu96wsAAAAAAQQWABRN0ZOslkpWrBueHMqEVP4vR0+FEwA=",
+        "cHNidP8BAgQCAAAAAQQBAQEFAQIB+wQCAAAAAAEOIAsK2SFBnByHGXNdctxzn56p4GONH+TB7vD5lECEgV/IAQ8EAAAAAAESBBAnAAAAAQMIAK8PAwAAAAABBBYAFMQw9kxHVtoxDb0aCFVy7ymZJicsAAEDCIu96wsAAAAAAQQWABRN0ZOslkpWrBueHMqEVP4vR0+FEwA=",
+        "cHNidP8BAgQCAAAAAQMEAAAAAAEEAQEBBQECAfsEAgAAAAABDiALCtkhQZwchxlzXXLcc5+eqeBjjR/kwe7w+ZRAhIFfyAEPBAAAAAAAAQMIAK8PAwAAAAABBBYAFMQw9kxHVtoxDb0aCFVy7ymZJicsAAEDCIu96wsAAAAAAQQWABRN0ZOslkpWrBueHMqEVP4vR0+FEwA=",
+    ];
+
+    #[test]
+    fn v2_round_trip() {
+        let expected_locktimes = [0, 1_623_494_028, 10_000, 0];
+        for (psbt_str, lock_time) in V2_PSBTS.iter().zip(expected_locktimes) {
+            let psbt = from_base64(psbt_str).unwrap();
+            let tx = &psbt.unsigned_tx;
+            assert_eq!(tx.version, Version::TWO);
+            assert_eq!(tx.lock_time.to_consensus_u32(), lock_time);
+            assert_eq!(tx.input.len(), 1);
+            assert_eq!(
+                tx.input[0].previous_output,
+                OutPoint::from_str(
+                    "c85f81844094f9f0eec1e41f8d63e0a99e9f73dc725d7319871c9c4121d90a0b:0"
+                )
+                .unwrap()
+            );
+            assert_eq!(tx.input[0].sequence, Sequence::MAX);
+            assert_eq!(
+                tx.output
+                    .iter()
+                    .map(|txo| txo.value.to_sat())
+                    .collect::<Vec<_>>(),
+                vec![51_359_488, 199_998_859]
+            );
+            assert_eq!(to_base64_v2(&psbt), *psbt_str);
+        }
+
+        // When all inputs requiring a locktime accept a height-based one, it is used.
+        let psbt = from_base64("cHNidP8BAgQCAAAAAQQBAgEFAQIB+wQCAAAAAAEOIAsK2SFBnByHGXNdctxzn56p4GONH+TB7vD5lECEgV/IAQ8EAAAAAAERBIyNxGABEgQQJwAAAAEOIDsK2SFBnByHGXNdctxzn56p4GONH+TB7vD5lECEgV/IAQ8EAQAAAAESBCgjAAAAAQMIAK8PAwAAAAABBBYAFMQw9kxHVtoxDb0aCFVy7ymZJicsAAEDCIu96wsAAAAAAQQWABRN0ZOslkpWrBueHMqEVP4vR0+FEwA=").unwrap();
+        assert_eq!(psbt.unsigned_tx.lock_time.to_consensus_u32(), 10_000);
+    }
+
+    #[test]
+    fn v0_round_trip() {
+        let psbt_str = "cHNidP8BAF4CAAAAAU2eiiiqTjQHmDarPBbpDO7b/jXeU3ABO20p0sZ3U7SoAAAAAAD9////AaQiAAAAAAAAIlEg+5nFiKkeVa9DFXLNvpIRcDNU7a4hN2QQhb7LHBad+AAVWgMAAAEBK+YjAAAAAAAAIlEgKA3Jqw7wXvY+ggshuLnufWEMZvDvz5fd7guPe74OFr9BFOwaZX+B87gSAqM66+YwA2L5da0h0+PPsDMXht+IcnRsHCjA6OkyoLbI1pYXZBVcKqW6G8fXOBHIUtxyVltu/VVAP8MQWa0ipMEXw6XfBexyPOQfb7TJpX6+KCiz2XA/mnwRyuYibz0aLl5/ZFNFvvgN5D+JYrmGACcafbXZOAtyw0IVwXv1NpDYfRDm2LstW9CzwDg86+y3PAi9ipB5m3acrIhsHCjA6OkyoLbI1pYXZBVcKqW6G8fXOBHIUtxyVltu/VUoIBYAvFRImNeU9Uegt66wQrOOwURL8+t2LjLQLCllgNHOrQP//wCywEIVwXv1NpDYfRDm2LstW9CzwDg86+y3PAi9ipB5m3acrIhsb1/MmmOazbHeRxkVrKn2+tW/CKyAMUbx3oUK+GaB3h1HIMjdaFdS9My6uOk2lBdGjnFLNSfvvRhvUGWk6VdVgyMLrCDsGmV/gfO4EgKjOuvmMANi+XWtIdPjz7AzF4bfiHJ0bLpSnMAhFhYAvFRImNeU9Uegt66wQrOOwURL8+t2LjLQLCllgNHOPQFvX8yaY5rNsd5HGRWsqfb61b8IrIAxRvHehQr4ZoHeHZ4cGYMwAACAAQAAgAAAAIACAACAAgAAAAAAAAAhFnv1NpDYfRDm2LstW9CzwDg86+y3PAi9ipB5m3acrIhsDQB8Rh5dAAAAAAAAAAAhFsjdaFdS9My6uOk2lBdGjnFLNSfvvRhvUGWk6VdVgyMLPQEcKMDo6TKgtsjWlhdkFVwqpbobx9c4EchS3HJWW279VZ4cGYMwAACAAQAAgAAAAIACAACAAAAAAAAAAAAhFuwaZX+B87gSAqM66+YwA2L5da0h0+PPsDMXht+IcnRsPQEcKMDo6TKgtsjWlhdkFVwqpbobx9c4EchS3HJWW279VTsZE+EwAACAAQAAgAAAAIACAACAAAAAAAAAAAABFyB79TaQ2H0Q5ti7LVvQs8A4POvstzwIvYqQeZt2nKyIbAEYIFHdAhNfvRTz8EPSAs+Gf/HrAULFx3vOs18D0PWq9kGwAAEFIDNwiV3+PJcHk97y59EcUfNkHjBBPZjvSN/Hgn0S01LQAQZzAcAnIClBgez9GIlLyjqN3NPltEhBDUjmDbsiVpWlba2IMurdrQP//wCyAcBGIBs6CbrR7SfZwJL6Q4beOPUvPbetXt/T/QmplPDbPQAwrCBGP2ABIvtgyIu7uSrKRE6rIY3VNZOEJ028nuqeWNSqTbpSnCEHGzoJutHtJ9nAkvpDht449S89t61e39P9CamU8Ns9ADA9AY7TqQXjhS0u6aC8jSA+/MN5WjE8uYIs5D4/oTu1eC9PnhwZgzAAAIABAACAAAAAgAIAAIABAAAAAQAAACEHKUGB7P0YiUvKOo3c0+W0SEENSOYNuyJWlaVtrYgy6t09ARHbEQ0ckrM/6qD8+TyaH5SmkKpv4e0rE07oC0VK4TxBnhwZgzAAAIABAACAAAAAgAIAAIADAAAAAQAAACEHM3CJXf48lweT3vLn0RxR82QeMEE9mO9I38eCfRLTUtANAHxGHl0BAAAAAQAAACEHRj9gASL7YMiLu7kqykROqyGN1TWThCdNvJ7qnljUqk09AY7TqQXjhS0u6aC8jSA+/MN5WjE8uYIs5D4/oTu1eC9POxkT4TAAAIABAACAAAAAgAIAAIABAAAAAQAAAAA=";
+        let psbt = Psbt::from_str(psbt_str).unwrap();
+        // Version 0 PSBTs are parsed as is.
+        assert_eq!(from_base64(psbt_str).unwrap(), psbt);
+        // Non-final sequence and non-zero locktime are preserved through version 2.
+        let v2 = serialize_v2(&psbt);
+        let v2_psbt = deserialize(&v2).unwrap();
+        assert_eq!(v2_psbt, psbt);
+        assert_eq!(serialize_v2(&v2_psbt), v2);
+    }
+
+    #[test]
+    fn v2_invalid() {
+        // Unsigned transaction is excluded from version 2.
+        assert_eq!(
+            from_base64("cHNidP8BADMCAAAAAQsK2SFBnByHGXNdctxzn56p4GONH+TB7vD5lECEgV/IAAAAAAD/////AAAAAAABAgQCAAAAAQQBAQEFAQIB+wQCAAAAAAEOIAsK2SFBnByHGXNdctxzn56p4GONH+TB7vD5lECEgV/IAQ8EAAAAAAABAwgArw8DAAAAAAEEFgAUxDD2TEdW2jENvRoIVXLvKZkmJywAAQMIi73rCwAAAAABBBYAFE3Rk6yWSlasG54cyoRU/i9HT4UTAA=="),
+            Err(PsbtError::UnexpectedField("PSBT_GLOBAL_UNSIGNED_TX"))
+        );
+        // Input count is required.
+        assert_eq!(
+            from_base64("cHNidP8BAgQCAAAAAQUBAgH7BAIAAAAAAQ4gCwrZIUGcHIcZc11y3HOfnqngY40f5MHu8PmUQISBX8gBDwQAAAAAAAEDCACvDwMAAAAAAQQWABTEMPZMR1baMQ29GghVcu8pmSYnLAABAwiLvesLAAAAAAEEFgAUTdGTrJZKVqwbnhzKhFT+L0dPhRMA"),
+            Err(PsbtError::MissingField("PSBT_GLOBAL_INPUT_COUNT"))
+        );
+        // Previous txid is required.
+        assert_eq!(
+            from_base64("cHNidP8BAgQCAAAAAQQBAQEFAQIB+wQCAAAAAAEPBAAAAAAAAQMIAK8PAwAAAAABBBYAFMQw9kxHVtoxDb0aCFVy7ymZJicsAAEDCIu96wsAAAAAAQQWABRN0ZOslkpWrBueHMqEVP4vR0+FEwA="),
+            Err(PsbtError::MissingField("PSBT_IN_PREVIOUS_TXID"))
+        );
+        // Output amount is required.
+        assert_eq!(
+            from_base64("cHNidP8BAgQCAAAAAQQBAQEFAQIB+wQCAAAAAAEOIAsK2SFBnByHGXNdctxzn56p4GONH+TB7vD5lECEgV/IAQ8EAAAAAAABBBYAFMQw9kxHVtoxDb0aCFVy7ymZJicsAAEDCIu96wsAAAAAAQQWABRN0ZOslkpWrBueHMqEVP4vR0+FEwA="),
+            Err(PsbtError::MissingField("PSBT_OUT_AMOUNT"))
+        );
+        // One input requires a time-based locktime and the other a height-based one.
+        assert_eq!(
+            from_base64("cHNidP8BAgQCAAAAAQQBAgEFAQIB+wQCAAAAAAEOIAsK2SFBnByHGXNdctxzn56p4GONH+TB7vD5lECEgV/IAQ8EAAAAAAERBIyNxGAAAQ4gOwrZIUGcHIcZc11y3HOfnqngY40f5MHu8PmUQISBX8gBDwQBAAAAARIEECcAAAABAwgArw8DAAAAAAEEFgAUxDD2TEdW2jENvRoIVXLvKZkmJywAAQMIi73rCwAAAAABBBYAFE3Rk6yWSlasG54cyoRU/i9HT4UTAA=="),
+            Err(PsbtError::IncompatibleLocktimes)
+        );
+    }
+}
@@ -19,7 +19,7 @@ use miniscript::bitcoin::{
     ecdsa,
     hashes::Hash,
     key::TapTweak,
-    psbt::{Input as PsbtIn, Psbt},
+    psbt::{Input as PsbtIn, Psbt, PsbtSighashType},
     secp256k1, sighash,
 };
 
@@ -32,6 +32,11 @@ pub enum SignerError {
     MnemonicStorage(io::Error),
     InsanePsbt,
     IncompletePsbt,
+    /// An input requests a sighash type we were not allowed to sign with.
+    UnsupportedSighash {
+        input_index: usize,
+        sighash_type: PsbtSighashType,
+    },
 }
 
 impl fmt::Display for SignerError {
@@ -46,12 +51,62 @@ impl fmt::Display for SignerError {
                 f,
                 "The PSBT is missing some information necessary for signing."
             ),
+            Self::UnsupportedSighash {
+                input_index,
+                sighash_type,
+            } => write!(
+                f,
+                "Refusing to sign input #{} with sighash type {}.",
+                input_index, sighash_type
+            ),
         }
     }
 }
 
 impl error::Error for SignerError {}
 
+/// Which sighash types requested by the inputs of a PSBT a signer accepts to sign with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SighashPolicy {
+    /// Only sign with SIGHASH_ALL, or SIGHASH_DEFAULT for Taproot inputs.
+    #[default]
+    AllOnly,
+    /// Also sign with sighash types which do not commit to the whole transaction: SIGHASH_NONE,
+    /// SIGHASH_SINGLE and those with the SIGHASH_ANYONECANPAY flag.
+    AllowNonStandard,
+}
+
+/// Whether this sighash type commits to all the inputs and outputs of the transaction.
+pub fn is_standard_sighash(sighash_type: PsbtSighashType, is_taproot: bool) -> bool {
+    let sighash_type = sighash_type.to_u32();
+    sighash_type == 0x01 || (is_taproot && sighash_type == 0x00)
+}
+
+// Whether this PSBT input spends a Taproot output. Same as for signing, assume it does if there is
+// no witness script.
+fn is_taproot_input(psbt_in: &PsbtIn) -> bool {
+    psbt_in.witness_script.is_none()
+}
+
+/// Whether this PSBT input requests to be signed with a sighash type that does not commit to the
+/// whole transaction.
+pub fn requests_non_standard_sighash(psbt_in: &PsbtIn) -> bool {
+    psbt_in
+        .sighash_type
+        .is_some_and(|s| !is_standard_sighash(s, is_taproot_input(psbt_in)))
+}
+
+/// Get the index and requested sighash type of all the inputs of this PSBT which request to be
+/// signed with a sighash type that does not commit to the whole transaction.
+pub fn non_standard_sighashes(psbt: &Psbt) -> Vec<(usize, PsbtSighashType)> {
+    psbt.inputs
+        .iter()
+        .enumerate()
+        .filter(|(_, psbt_in)| requests_non_standard_sighash(psbt_in))
+        .filter_map(|(i, psbt_in)| psbt_in.sighash_type.map(|s| (i, s)))
+        .collect()
+}
+
 pub const MNEMONICS_FOLDER_NAME: &str = "mnemonics";
 
 // TODO: zeroize, mlock, etc.. For now we don't even encrypt the seed on disk so that'd be
@@ -241,6 +296,7 @@ impl HotSigner {
         master_fingerprint: bip32::Fingerprint,
         psbt_in: &mut PsbtIn,
         input_index: usize,
+        sighash_type: sighash::EcdsaSighashType,
     ) -> Result<(), SignerError> {
         // First of all compute the sighash for this input. We assume P2WSH spend: the sighash
         // script code is always the witness script.
@@ -253,7 +309,6 @@ impl HotSigner {
             .as_ref()
             .ok_or(SignerError::IncompletePsbt)?
             .value;
-        let sighash_type = sighash::EcdsaSighashType::All;
         let sighash = sighash_cache
             .p2wsh_signature_hash(input_index, witscript, value, sighash_type)
             .map_err(|_| SignerError::InsanePsbt)?;
@@ -284,6 +339,7 @@ impl HotSigner {
     }
 
     // Provide a BIP340 signature for this transaction input from the PSBT input information.
+    #[allow(clippy::too_many_arguments)]
     fn sign_taproot(
         &self,
         secp: &secp256k1::Secp256k1<secp256k1::All>,
@@ -292,8 +348,8 @@ impl HotSigner {
         prevouts: &[bitcoin::TxOut],
         psbt_in: &mut PsbtIn,
         input_index: usize,
+        sighash_type: sighash::TapSighashType,
     ) -> Result<(), SignerError> {
-        let sighash_type = sighash::TapSighashType::Default;
         let prevouts = sighash::Prevouts::All(prevouts);
 
         // If the details of the internal key are filled, provide a keypath signature.
@@ -359,11 +415,24 @@ impl HotSigner {
 
     /// Sign all inputs of the given PSBT.
     ///
-    /// **This does not perform any check. It will blindly sign anything that's passed.**
+    /// **This does not perform any check besides refusing to sign inputs which request a sighash
+    /// type other than SIGHASH_ALL (or SIGHASH_DEFAULT for Taproot). It will blindly sign anything
+    /// else that's passed.**
     pub fn sign_psbt(
+        &self,
+        psbt: Psbt,
+        secp: &secp256k1::Secp256k1<secp256k1::All>,
+    ) -> Result<Psbt, SignerError> {
+        self.sign_psbt_with_policy(psbt, secp, SighashPolicy::AllOnly)
+    }
+
+    /// Sign all inputs of the given PSBT, with the sighash type requested by each input if the
+    /// policy allows it.
+    pub fn sign_psbt_with_policy(
         &self,
         mut psbt: Psbt,
         secp: &secp256k1::Secp256k1<secp256k1::All>,
+        sighash_policy: SighashPolicy,
     ) -> Result<Psbt, SignerError> {
         let master_fingerprint = self.fingerprint(secp);
         let mut sighash_cache = sighash::SighashCache::new(&psbt.unsigned_tx);
@@ -379,15 +448,38 @@ impl HotSigner {
 
         // Sign each input in the PSBT.
         for i in 0..psbt.inputs.len() {
-            if psbt.inputs[i].witness_script.is_some() {
+            let is_taproot = is_taproot_input(&psbt.inputs[i]);
+            let requested_sighash = psbt.inputs[i].sighash_type;
+            let unsupported = |sighash_type| SignerError::UnsupportedSighash {
+                input_index: i,
+                sighash_type,
+            };
+            if let Some(sighash_type) = requested_sighash {
+                if sighash_policy == SighashPolicy::AllOnly
+                    && !is_standard_sighash(sighash_type, is_taproot)
+                {
+                    return Err(unsupported(sighash_type));
+                }
+            }
+
+            if !is_taproot {
+                let sighash_type = requested_sighash
+                    .map(|s| s.ecdsa_hash_ty().map_err(|_| unsupported(s)))
+                    .transpose()?
+                    .unwrap_or(sighash::EcdsaSighashType::All);
                 self.sign_p2wsh(
                     secp,
                     &mut sighash_cache,
                     master_fingerprint,
                     &mut psbt.inputs[i],
                     i,
+                    sighash_type,
                 )?;
             } else {
+                let sighash_type = requested_sighash
+                    .map(|s| s.taproot_hash_ty().map_err(|_| unsupported(s)))
+                    .transpose()?
+                    .unwrap_or(sighash::TapSighashType::Default);
                 self.sign_taproot(
                     secp,
                     &mut sighash_cache,
@@ -395,6 +487,7 @@ impl HotSigner {
                     &prevouts,
                     &mut psbt.inputs[i],
                     i,
+                    sighash_type,
                 )?;
             }
         }
@@ -836,6 +929,55 @@ mod tests {
         let psbt = prim_signer_b.sign_psbt(psbt, &secp).unwrap();
         assert!(psbt.inputs[0].partial_sigs.is_empty());
         assert_eq!(psbt.inputs[1].partial_sigs.len(), 1);
+
+        // Explicitly requesting SIGHASH_ALL is fine.
+        let mut psbt = dummy_psbt.clone();
+        psbt.inputs[1].sighash_type = Some(sighash::EcdsaSighashType::All.into());
+        assert!(non_standard_sighashes(&psbt).is_empty());
+        let psbt = prim_signer_a.sign_psbt(psbt, &secp).unwrap();
+        assert!(psbt.inputs.iter().all(|psbt_in| psbt_in
+            .partial_sigs
+            .values()
+            .all(|sig| sig.sighash_type == sighash::EcdsaSighashType::All)));
+
+        // But we refuse to sign with a sighash type which doesn't commit to the whole transaction,
+        // unless explicitly allowed to.
+        for sighash_type in [
+            sighash::EcdsaSighashType::None,
+            sighash::EcdsaSighashType::Single,
+            sighash::EcdsaSighashType::AllPlusAnyoneCanPay,
+        ] {
+            let mut psbt = dummy_psbt.clone();
+            psbt.inputs[1].sighash_type = Some(sighash_type.into());
+            assert_eq!(
+                non_standard_sighashes(&psbt),
+                vec![(1, PsbtSighashType::from(sighash_type))]
+            );
+            assert!(matches!(
+                prim_signer_a.sign_psbt(psbt.clone(), &secp),
+                Err(SignerError::UnsupportedSighash { input_index: 1, sighash_type: s }) if s == PsbtSighashType::from(sighash_type)
+            ));
+            let psbt = prim_signer_a
+                .sign_psbt_with_policy(psbt, &secp, SighashPolicy::AllowNonStandard)
+                .unwrap();
+            assert!(psbt.inputs[0]
+                .partial_sigs
+                .values()
+                .all(|sig| sig.sighash_type == sighash::EcdsaSighashType::All));
+            assert!(psbt.inputs[1]
+                .partial_sigs
+                .values()
+                .all(|sig| sig.sighash_type == sighash_type));
+            assert_eq!(psbt.inputs[1].partial_sigs.len(), 1);
+        }
+
+        // SIGHASH_DEFAULT doesn't exist for P2WSH inputs.
+        let mut psbt = dummy_psbt.clone();
+        psbt.inputs[0].sighash_type = Some(sighash::TapSighashType::Default.into());
+        assert!(matches!(
+            prim_signer_a.sign_psbt_with_policy(psbt, &secp, SighashPolicy::AllowNonStandard),
+            Err(SignerError::UnsupportedSighash { input_index: 0, .. })
+        ));
     }
 
     #[test]
@@ -1156,6 +1298,48 @@ mod tests {
             .inputs
             .iter()
             .all(|psbt_in| psbt_in.partial_sigs.is_empty()));
+
+        // SIGHASH_DEFAULT and SIGHASH_ALL may be requested for Taproot inputs.
+        let mut psbt = dummy_psbt.clone();
+        psbt.inputs[0].sighash_type = Some(sighash::TapSighashType::Default.into());
+        psbt.inputs[1].sighash_type = Some(sighash::TapSighashType::All.into());
+        assert!(non_standard_sighashes(&psbt).is_empty());
+        let psbt = prim_signer_a.sign_psbt(psbt, &secp).unwrap();
+        assert_eq!(
+            psbt.inputs[0].tap_key_sig.unwrap().sighash_type,
+            sighash::TapSighashType::Default
+        );
+        assert_eq!(
+            psbt.inputs[1].tap_key_sig.unwrap().sighash_type,
+            sighash::TapSighashType::All
+        );
+
+        // Others are refused unless explicitly allowed.
+        let mut psbt = dummy_psbt.clone();
+        psbt.inputs[0].sighash_type = Some(sighash::TapSighashType::SinglePlusAnyoneCanPay.into());
+        assert_eq!(
+            non_standard_sighashes(&psbt),
+            vec![(
+                0,
+                PsbtSighashType::from(sighash::TapSighashType::SinglePlusAnyoneCanPay)
+            )]
+        );
+        assert!(prim_signer_a
+            .sign_psbt(psbt.clone(), &secp)
+            .unwrap_err()
+            .to_string()
+            .contains("Refusing to sign input #0 with sighash type"));
+        let psbt = prim_signer_a
+            .sign_psbt_with_policy(psbt, &secp, SighashPolicy::AllowNonStandard)
+            .unwrap();
+        assert_eq!(
+            psbt.inputs[0].tap_key_sig.unwrap().sighash_type,
+            sighash::TapSighashType::SinglePlusAnyoneCanPay
+        );
+        assert_eq!(
+            psbt.inputs[1].tap_key_sig.unwrap().sighash_type,
+            sighash::TapSighashType::Default
+        );
     }
 
     #[test]
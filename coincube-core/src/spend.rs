@@ -2,7 +2,7 @@ use crate::descriptors;
 
 use std::{
     collections::{BTreeMap, HashMap},
-    convert::TryInto,
+    convert::{TryFrom, TryInto},
     fmt,
     time::Duration,
 };
@@ -35,6 +35,20 @@ pub const MAX_FEE: bitcoin::Amount = bitcoin::Amount::ONE_BTC;
 /// Assume that paying more than 1000sat/vb in feerate is a bug.
 pub const MAX_FEERATE: u64 = 1_000;
 
+/// `MAX_FEERATE` expressed in sats/kvb, the unit used for feerates in the spend APIs.
+pub const MAX_FEERATE_KVB: u64 = MAX_FEERATE * 1_000;
+
+/// The minimum feerate (sats/kvb) we allow, Bitcoin Core's default minimum relay feerate of
+/// 0.1 sat/vb.
+pub const MIN_FEERATE_KVB: u64 = 100;
+
+/// The maximum size of the data carried by an OP_RETURN output for it to be relayed by Bitcoin Core
+/// nodes with the default `-datacarriersize`. Only a single such output is standard.
+pub const MAX_OP_RETURN_DATA_SIZE: usize = 80;
+
+/// Maximum number of coin selection rounds to find the fee to subtract from the outputs' amounts.
+const SUBTRACT_FEE_MAX_ROUNDS: usize = 10;
+
 /// Do not set locktime if tip age in seconds is older than this.
 // See also https://github.com/bitcoin/bitcoin/blob/ecd23656db174adef61d3bd753d02698c3528192/src/wallet/spend.cpp#L906.
 pub const MAX_ANTI_FEE_SNIPING_TIP_AGE_SECS: u64 = 8 * 60 * 60; // 8 hours
@@ -44,14 +58,17 @@ pub enum InsaneFeeInfo {
     NegativeFee,
     InvalidFeerate,
     TooHighFee(u64),
-    TooHighFeerate(u64),
+    TooHighFeerate(/* sats/kvb */ u64),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum SpendCreationError {
-    InvalidFeerate(/* sats/vb */ u64),
+    InvalidFeerate(/* sats/kvb */ u64),
     FeeEstimationFailed,
     InvalidOutputValue(bitcoin::Amount),
+    OpReturnDataTooLarge(/* size in bytes */ usize),
+    /// The index of an output to deduct the fee from is not that of a destination.
+    InvalidSubtractFeeFrom(usize),
     InsaneFees(InsaneFeeInfo),
     SanityCheckFailure(Psbt),
     FetchingTransaction(bitcoin::OutPoint),
@@ -61,8 +78,22 @@ pub enum SpendCreationError {
 impl fmt::Display for SpendCreationError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Self::InvalidFeerate(sats_vb) => write!(f, "Invalid feerate: {} sats/vb.", sats_vb),
+            Self::InvalidFeerate(sats_kvb) => write!(
+                f,
+                "Invalid feerate: {} sats/vb.",
+                format_feerate_kvb(*sats_kvb)
+            ),
             Self::InvalidOutputValue(amount) => write!(f, "Invalid output value '{}'.", amount),
+            Self::OpReturnDataTooLarge(size) => write!(
+                f,
+                "OP_RETURN data of {} bytes is larger than the standard maximum of {} bytes.",
+                size, MAX_OP_RETURN_DATA_SIZE
+            ),
+            Self::InvalidSubtractFeeFrom(index) => write!(
+                f,
+                "Cannot deduct the fee from output {}, it is not a destination.",
+                index
+            ),
             Self::InsaneFees(info) => write!(
                 f,
                 "We assume transactions with a fee larger than {} or a feerate larger than {} sats/vb are a mistake. \
@@ -73,7 +104,8 @@ impl fmt::Display for SpendCreationError {
                     InsaneFeeInfo::NegativeFee => "would have a negative fee".to_string(),
                     InsaneFeeInfo::TooHighFee(f) => format!("{} sats in fees", f),
                     InsaneFeeInfo::InvalidFeerate => "would have an invalid feerate".to_string(),
-                    InsaneFeeInfo::TooHighFeerate(r) => format!("has a feerate of {} sats/vb", r),
+                    InsaneFeeInfo::TooHighFeerate(r) =>
+                        format!("has a feerate of {} sats/vb", format_feerate_kvb(*r)),
                 },
             ),
             Self::FetchingTransaction(op) => {
@@ -92,6 +124,56 @@ impl fmt::Display for SpendCreationError {
 
 impl std::error::Error for SpendCreationError {}
 
+/// Convert a feerate in sats/vb, possibly fractional, to sats/kvb. Returns `None` if it isn't a
+/// positive number or if it's more precise than a thousandth of a sat/vb.
+pub fn feerate_kvb_from_vb(feerate_vb: f64) -> Option<u64> {
+    let feerate_kvb = feerate_vb * 1_000.0;
+    if !feerate_kvb.is_finite() || feerate_kvb <= 0.0 || feerate_kvb > u64::MAX as f64 {
+        return None;
+    }
+    let rounded = feerate_kvb.round();
+    // Tolerate the imprecision of the floating point representation of the decimal value.
+    if (feerate_kvb - rounded).abs() > 1e-6 {
+        return None;
+    }
+    Some(rounded as u64)
+}
+
+/// Parse a feerate in sats/vb, possibly fractional, to sats/kvb.
+pub fn parse_feerate_vb(s: &str) -> Option<u64> {
+    s.trim().parse::<f64>().ok().and_then(feerate_kvb_from_vb)
+}
+
+/// Display a feerate in sats/kvb as a decimal number of sats/vb, without trailing zeros.
+pub fn format_feerate_kvb(feerate_kvb: u64) -> String {
+    let (int, frac) = (feerate_kvb / 1_000, feerate_kvb % 1_000);
+    if frac == 0 {
+        int.to_string()
+    } else {
+        format!("{}.{:03}", int, frac)
+            .trim_end_matches('0')
+            .to_string()
+    }
+}
+
+/// Get the data carried by an OP_RETURN output's Script, if it is one. Returns the concatenation
+/// of all the pushes following the OP_RETURN, or `None` if anything else than a push follows it.
+pub fn op_return_data(script: &bitcoin::Script) -> Option<Vec<u8>> {
+    if !script.is_op_return() {
+        return None;
+    }
+    let mut data = Vec::new();
+    for instruction in script.instructions().skip(1) {
+        match instruction.ok()? {
+            bitcoin::script::Instruction::PushBytes(bytes) => {
+                data.extend_from_slice(bytes.as_bytes())
+            }
+            bitcoin::script::Instruction::Op(_) => return None,
+        }
+    }
+    Some(data)
+}
+
 // Sanity check the value of a transaction output.
 fn check_output_value(value: bitcoin::Amount) -> Result<(), SpendCreationError> {
     if value > bitcoin::Amount::MAX_MONEY || value.to_sat() < DUST_OUTPUT_SATS {
@@ -146,20 +228,25 @@ fn sanity_check_psbt(
 
     // Check the feerate isn't insane.
     let tx_vb = spent_desc.unsigned_tx_max_vbytes(tx, use_primary_path);
-    let feerate_sats_vb = abs_fee
-        .checked_div(tx_vb)
+    let feerate_sats_kvb = abs_fee
+        .checked_mul(1_000)
+        .and_then(|fee_msats| fee_msats.checked_div(tx_vb))
         .ok_or(SpendCreationError::InsaneFees(
             InsaneFeeInfo::InvalidFeerate,
         ))?;
-    if !(1..=MAX_FEERATE).contains(&feerate_sats_vb) {
+    if !(MIN_FEERATE_KVB..=MAX_FEERATE_KVB).contains(&feerate_sats_kvb) {
         return Err(SpendCreationError::InsaneFees(
-            InsaneFeeInfo::TooHighFeerate(feerate_sats_vb),
+            InsaneFeeInfo::TooHighFeerate(feerate_sats_kvb),
         ));
     }
 
-    // Check for dust outputs
+    // Check for dust outputs. OP_RETURN outputs are unspendable and carry no value.
     for txo in psbt.unsigned_tx.output.iter() {
-        if txo.value < txo.script_pubkey.minimal_non_dust() {
+        if txo.script_pubkey.is_op_return() {
+            if txo.value.to_sat() > 0 {
+                return Err(SpendCreationError::SanityCheckFailure(psbt.clone()));
+            }
+        } else if txo.value < txo.script_pubkey.minimal_non_dust() {
             return Err(SpendCreationError::SanityCheckFailure(psbt.clone()));
         }
     }
@@ -251,9 +338,9 @@ impl bdk_coin_select::BnbMetric for LowestFeeChangeCondition {
 ///
 /// `change_txo` is the change output to add if needed (with any value).
 ///
-/// `feerate_vb` is the minimum feerate (in sats/vb). Note that the selected coins
+/// `feerate_kvb` is the minimum feerate (in sats/kvb). Note that the selected coins
 /// and change may result in a slightly lower feerate than this as the underlying
-/// function instead uses a minimum feerate of `feerate_vb / 4.0` sats/wu.
+/// function instead uses a minimum feerate of `feerate_kvb / 4000.0` sats/wu.
 ///
 /// If this is a replacement spend using RBF, then `replaced_fee` should be set to
 /// the total fees (in sats) of the transaction(s) being replaced, including any
@@ -269,7 +356,7 @@ fn select_coins_for_spend(
     candidate_coins: &[CandidateCoin],
     base_tx: bitcoin::Transaction,
     change_txo: bitcoin::TxOut,
-    feerate_vb: f32,
+    feerate_kvb: u64,
     replaced_fee: Option<u64>,
     max_sat_weight: u64,
     must_have_change: bool,
@@ -287,10 +374,6 @@ fn select_coins_for_spend(
     };
     let n_outputs_nochange = base_tx.output.len();
     let max_input_weight = TXIN_BASE_WEIGHT + max_sat_weight;
-    // Get feerate as u64 for calculation relating to ancestor below.
-    // We expect `feerate_vb` to be a positive integer, but take ceil()
-    // just in case to be sure we pay enough for ancestors.
-    let feerate_vb_u64 = feerate_vb.ceil() as u64;
     let witness_factor: u64 = WITNESS_SCALE_FACTOR
         .try_into()
         .expect("scale factor must fit in u64");
@@ -307,7 +390,9 @@ fn select_coins_for_spend(
                     .map(|info| {
                         // The implied ancestor vsize if the fee had been paid at our target feerate.
                         let ancestor_vsize_at_feerate = <u32 as Into<u64>>::into(info.fee)
-                            .checked_div(feerate_vb_u64)
+                            .checked_mul(1_000)
+                            .expect("fee in msats must fit in u64")
+                            .checked_div(feerate_kvb)
                             .expect("feerate is greater than zero");
                         // If the actual ancestor vsize is bigger than the implied vsize, we will need to
                         // pay the difference in order for the combined feerate to be at the target value.
@@ -343,7 +428,7 @@ fn select_coins_for_spend(
     // Now set the change policy. We use a policy which ensures no change output is created with a
     // lower value than our custom dust limit. NOTE: the change output weight must not account for
     // a potential difference in the size of the outputs count varint.
-    let feerate = FeeRate::from_sat_per_vb(feerate_vb);
+    let feerate = FeeRate::from_sat_per_vb(feerate_kvb as f32 / 1_000.0);
     let long_term_feerate = FeeRate::from_sat_per_vb(LONG_TERM_FEERATE_VB);
     let change_output_weight = change_txo.weight().to_wu();
     let drain_weights = DrainWeights {
@@ -532,6 +617,29 @@ pub struct SpendOutputAddress {
     pub info: Option<AddrInfo>,
 }
 
+// Set the value of the outputs of `tx` at `indexes` to their `nominal` amounts minus an equal part
+// of `fee`. The first of them also pays the remainder of the split.
+fn subtract_fee_from_outputs(
+    tx: &mut bitcoin::Transaction,
+    nominal: &[bitcoin::Amount],
+    indexes: &[usize],
+    fee: u64,
+) -> Result<(), SpendCreationError> {
+    let n_outputs: u64 = indexes.len().try_into().expect("Must fit in a u64");
+    let (share, remainder) = (fee / n_outputs, fee % n_outputs);
+    for (i, index) in indexes.iter().enumerate() {
+        let to_subtract = if i == 0 { share + remainder } else { share };
+        let value = nominal[*index]
+            .checked_sub(bitcoin::Amount::from_sat(to_subtract))
+            .ok_or(SpendCreationError::InvalidOutputValue(
+                bitcoin::Amount::ZERO,
+            ))?;
+        check_output_value(value)?;
+        tx.output[*index].value = value;
+    }
+    Ok(())
+}
+
 /// A trait for getting a wallet transaction by its txid.
 pub trait TxGetter {
     /// Get a wallet transaction. Allows for a cache by making the access mutable.
@@ -539,13 +647,13 @@ pub trait TxGetter {
 }
 
 /// Specify the fee requirements for a transaction. In both cases set a target feerate in satoshi
-/// per virtual byte. For RBF also set a minimum fee in satoshis for this transaction. See
+/// per thousand virtual bytes. For RBF also set a minimum fee in satoshis for this transaction. See
 /// <https://github.com/bitcoin/bitcoin/blob/master/doc/policy/mempool-replacements.md> for more
 /// information about how it should be set.
 pub enum SpendTxFees {
-    /// The target feerate in sats/vb for this transaction.
+    /// The target feerate in sats/kvb for this transaction.
     Regular(u64),
-    /// The (target feerate in sats/vb, total fees in sats of transaction(s) to be replaced
+    /// The (target feerate in sats/kvb, total fees in sats of transaction(s) to be replaced
     /// including descendants) for this transaction.
     Rbf(u64, u64),
 }
@@ -554,6 +662,8 @@ pub enum SpendTxFees {
 pub enum CreateSpendWarning {
     ChangeAddedToFee(u64),
     AdditionalFeeForAncestors(u64),
+    /// A destination is one of our own addresses which already received coins.
+    AddressReuse(String),
 }
 
 impl fmt::Display for CreateSpendWarning {
@@ -576,6 +686,12 @@ impl fmt::Display for CreateSpendWarning {
                 amt,
                 if *amt > 1 { "s" } else { "" },
             ),
+            CreateSpendWarning::AddressReuse(addr) => write!(
+                f,
+                "Address reuse: the wallet address {} already received coins. Sending to it \
+                again links this transaction to the previous ones. Prefer a new address.",
+                addr,
+            ),
         }
     }
 }
@@ -592,7 +708,8 @@ pub struct CreateSpendRes {
 /// Create a PSBT for a transaction spending some, or all, of `candidate_coins` to `destinations`.
 /// Important information for signers will be populated. Will refuse to create outputs worth less
 /// than `DUST_OUTPUT_SATS`. Will refuse to create a transaction paying more than `MAX_FEE`
-/// satoshis in fees or whose feerate is larger than `MAX_FEERATE` sats/vb.
+/// satoshis in fees or whose feerate is larger than `MAX_FEERATE` sats/vb, nor one whose feerate
+/// is lower than `MIN_FEERATE_KVB` sats/kvb.
 ///
 /// More about the parameters:
 /// * `main_descriptor`: the multipath Coincube descriptor, used to derive the addresses of the
@@ -604,11 +721,17 @@ pub struct CreateSpendRes {
 ///   transaction created. If empty all the `candidate_coins` get spent and a single change output
 ///   is created to the provided `change_addr`. Can be used to sweep all, or some, coins from the
 ///   wallet.
+/// * `subtract_fee_from`: the indexes in `destinations` of the outputs whose amount the fee is
+///   deducted from, split equally between them. The selected coins then only need to cover the
+///   destinations' amounts. Will refuse to create an output worth less than `DUST_OUTPUT_SATS`
+///   once the fee is deducted.
+/// * `op_return`: data to be carried by a zero-value OP_RETURN output, added after the
+///   destinations. Must not be larger than `MAX_OP_RETURN_DATA_SIZE`.
 /// * `candidate_coins`: a list of coins to consider including as input of the transaction. If
 ///   `destinations` is empty, they will all be included as inputs of the transaction. Otherwise, a
 ///   coin selection algorithm will be run to spend the most efficient subset of them to meet the
 ///   `destinations` requirements.
-/// * `fees`: the target feerate (in sats/vb) and, if necessary, minimum absolute fee for this tx.
+/// * `fees`: the target feerate (in sats/kvb) and, if necessary, minimum absolute fee for this tx.
 /// * `change_addr`: the address to use for a change output if we need to create one. Can be set to
 ///   an external address (if combined with an empty list of `destinations` it's useful to sweep some
 ///   or all coins of a wallet to an external address).
@@ -619,6 +742,8 @@ pub fn create_spend(
     secp: &secp256k1::Secp256k1<secp256k1::VerifyOnly>,
     tx_getter: &mut impl TxGetter,
     destinations: &[(SpendOutputAddress, bitcoin::Amount)],
+    subtract_fee_from: &[usize],
+    op_return: Option<&[u8]>,
     candidate_coins: &[CandidateCoin],
     fees: SpendTxFees,
     change_addr: SpendOutputAddress,
@@ -639,13 +764,23 @@ pub fn create_spend(
     // 4. Finalize the PSBT and sanity check it before returning it.
 
     let mut warnings = Vec::new();
-    let (feerate_vb, replaced_fee) = match fees {
+    let (feerate_kvb, replaced_fee) = match fees {
         SpendTxFees::Regular(feerate) => (feerate, None),
         SpendTxFees::Rbf(feerate, fee) => (feerate, Some(fee)),
     };
     let is_self_send = destinations.is_empty();
-    if feerate_vb < 1 {
-        return Err(SpendCreationError::InvalidFeerate(feerate_vb));
+    if let Some(index) = subtract_fee_from.iter().find(|i| **i >= destinations.len()) {
+        return Err(SpendCreationError::InvalidSubtractFeeFrom(*index));
+    }
+    if feerate_kvb < MIN_FEERATE_KVB {
+        return Err(SpendCreationError::InvalidFeerate(feerate_kvb));
+    }
+    // The coin selection works with floating point feerates. Don't let it run with a value so
+    // large it couldn't be represented (the sanity checks would refuse the result anyways).
+    if feerate_kvb > u64::from(u16::MAX) * 1_000 {
+        return Err(SpendCreationError::InsaneFees(
+            InsaneFeeInfo::TooHighFeerate(feerate_kvb),
+        ));
     }
 
     // Create transaction with no inputs and no outputs.
@@ -680,6 +815,17 @@ pub fn create_spend(
         psbt_outs.push(psbt_out);
     }
     assert_eq!(tx.output.is_empty(), is_self_send);
+    if let Some(data) = op_return {
+        let data = <&bitcoin::script::PushBytes>::try_from(data)
+            .ok()
+            .filter(|data| data.len() <= MAX_OP_RETURN_DATA_SIZE)
+            .ok_or(SpendCreationError::OpReturnDataTooLarge(data.len()))?;
+        tx.output.push(bitcoin::TxOut {
+            value: bitcoin::Amount::ZERO,
+            script_pubkey: bitcoin::ScriptBuf::new_op_return(data),
+        });
+        psbt_outs.push(PsbtOut::default());
+    }
 
     // Now compute whether we'll need a change output while automatically selecting coins to be
     // used as input if necessary.
@@ -707,29 +853,92 @@ pub fn create_spend(
         // At this point the transaction still has no input and no change output, as expected
         // by the coins selection helper function.
         assert!(tx.input.is_empty());
-        assert_eq!(tx.output.len(), destinations.len());
-        // TODO: Introduce general conversion error type.
-        let feerate_vb: f32 = {
-            let fr: u16 = feerate_vb.try_into().map_err(|_| {
-                SpendCreationError::InsaneFees(InsaneFeeInfo::TooHighFeerate(feerate_vb))
-            })?;
-            fr
-        }
-        .into();
+        assert_eq!(
+            tx.output.len(),
+            destinations.len() + usize::from(op_return.is_some())
+        );
         let max_sat_wu = main_descriptor
             .max_sat_weight(use_primary_path)
             .try_into()
             .expect("Weight must fit in a u64");
-        select_coins_for_spend(
-            candidate_coins,
-            tx.clone(),
-            change_txo.clone(),
-            feerate_vb,
-            replaced_fee,
-            max_sat_wu,
-            is_self_send,
-        )
-        .map_err(SpendCreationError::CoinSelection)?
+        if subtract_fee_from.is_empty() {
+            select_coins_for_spend(
+                candidate_coins,
+                tx.clone(),
+                change_txo.clone(),
+                feerate_kvb,
+                replaced_fee,
+                max_sat_wu,
+                is_self_send,
+            )
+            .map_err(SpendCreationError::CoinSelection)?
+        } else {
+            // The fee depends on the selected coins, which depend on the outputs' amounts net of
+            // the fee. Start by deducting no fee and lower the outputs' amounts as long as the
+            // fee of the resulting selection is larger than the deducted one.
+            let nominal: Vec<_> = destinations.iter().map(|(_, amount)| *amount).collect();
+            let nominal_total: bitcoin::Amount = nominal.iter().copied().sum();
+            let available: bitcoin::Amount = candidate_coins.iter().map(|c| c.amount).sum();
+            let mut fee_to_subtract = 0;
+            let mut round = 0;
+            let res = loop {
+                round += 1;
+                let res = select_coins_for_spend(
+                    candidate_coins,
+                    tx.clone(),
+                    change_txo.clone(),
+                    feerate_kvb,
+                    replaced_fee,
+                    max_sat_wu,
+                    is_self_send,
+                );
+                match res {
+                    Ok(res) => {
+                        let value_in: u64 = res.selected.iter().map(|c| c.amount.to_sat()).sum();
+                        let value_out: u64 = tx.output.iter().map(|o| o.value.to_sat()).sum();
+                        let fee = value_in - value_out - res.change_amount.to_sat();
+                        if fee <= fee_to_subtract || round == SUBTRACT_FEE_MAX_ROUNDS {
+                            // If we deducted more than necessary, give back the surplus to the
+                            // recipients as long as the change output remains above dust.
+                            let surplus = fee_to_subtract.saturating_sub(fee);
+                            if surplus > 0
+                                && res.change_amount.to_sat() >= DUST_OUTPUT_SATS + surplus
+                            {
+                                subtract_fee_from_outputs(
+                                    &mut tx,
+                                    &nominal,
+                                    subtract_fee_from,
+                                    fee,
+                                )?;
+                                break CoinSelectionRes {
+                                    change_amount: res.change_amount
+                                        - bitcoin::Amount::from_sat(surplus),
+                                    max_change_amount: res.max_change_amount
+                                        - bitcoin::Amount::from_sat(surplus),
+                                    ..res
+                                };
+                            }
+                            break res;
+                        }
+                        fee_to_subtract = fee;
+                    }
+                    Err(e) => {
+                        // There isn't enough to pay the fee on top of the outputs' amounts.
+                        // Deduct what's missing from them too, unless the coins can't even cover
+                        // the outputs' amounts.
+                        if round == SUBTRACT_FEE_MAX_ROUNDS
+                            || available < nominal_total
+                            || fee_to_subtract + e.missing > MAX_FEE.to_sat()
+                        {
+                            return Err(SpendCreationError::CoinSelection(e));
+                        }
+                        fee_to_subtract += e.missing;
+                    }
+                }
+                subtract_fee_from_outputs(&mut tx, &nominal, subtract_fee_from, fee_to_subtract)?;
+            };
+            res
+        }
     };
     // If necessary, add a change output.
     // For a self-send, coin selection will only find solutions with change and will otherwise
@@ -814,11 +1023,118 @@ pub fn create_spend(
     })
 }
 
+/// Coins whose input would cost at least this share, in percent, of their value to spend at the
+/// expected future feerate are considered worth consolidating.
+pub const CONSOLIDATION_MIN_FEE_PERCENT: u64 = 1;
+
+/// The maximum number of coins to consolidate in a single transaction.
+pub const MAX_CONSOLIDATION_COINS: usize = 200;
+
+/// A recommendation to consolidate coins now, rather than spending them one by one once the
+/// feerate rose.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConsolidationAdvice {
+    /// The coins to consolidate, smallest first.
+    pub coins: Vec<bitcoin::OutPoint>,
+    /// The total value of the coins to consolidate.
+    pub amount: bitcoin::Amount,
+    /// The fee of the consolidation transaction at the current feerate.
+    pub fee: bitcoin::Amount,
+    /// The fees saved spending the consolidated coin at the future feerate rather than each of
+    /// the coins, the fee of the consolidation transaction deducted.
+    pub savings: bitcoin::Amount,
+}
+
+// The fee, rounded up, for this size at this feerate.
+fn fee_for_vbytes(vbytes: u64, feerate_kvb: u64) -> bitcoin::Amount {
+    bitcoin::Amount::from_sat(vbytes.saturating_mul(feerate_kvb).div_ceil(1_000))
+}
+
+/// Recommend which of the `coins` (outpoint and value) to consolidate, at the current feerate,
+/// for spending them later at the expected future feerate to cost less. Both feerates are in
+/// sats/kvb. The coins recommended are the smallest ones, whose input would cost at least
+/// [`CONSOLIDATION_MIN_FEE_PERCENT`] of their value at the future feerate, as estimated from the
+/// primary path satisfaction size of the descriptor.
+///
+/// Returns `None` if consolidating isn't worth it: the feerate isn't expected to rise, there are
+/// less than two such coins or the fee of the consolidation exceeds the savings.
+pub fn consolidation_advice(
+    main_descriptor: &descriptors::CoincubeDescriptor,
+    secp: &secp256k1::Secp256k1<secp256k1::VerifyOnly>,
+    coins: &[(bitcoin::OutPoint, bitcoin::Amount)],
+    feerate_kvb: u64,
+    future_feerate_kvb: u64,
+) -> Option<ConsolidationAdvice> {
+    if future_feerate_kvb <= feerate_kvb {
+        return None;
+    }
+    let input_vbytes: u64 = main_descriptor
+        .spender_input_size(true)
+        .try_into()
+        .expect("Must fit in a u64");
+    let input_fee_now = fee_for_vbytes(input_vbytes, feerate_kvb);
+    let input_fee_later = fee_for_vbytes(input_vbytes, future_feerate_kvb);
+
+    // Coins which don't pay for their own input at the current feerate would only lose value.
+    let mut coins: Vec<_> = coins
+        .iter()
+        .filter(|(_, amount)| {
+            *amount > input_fee_now
+                && input_fee_later.to_sat().saturating_mul(100)
+                    >= amount
+                        .to_sat()
+                        .saturating_mul(CONSOLIDATION_MIN_FEE_PERCENT)
+        })
+        .copied()
+        .collect();
+    coins.sort_by_key(|(outpoint, amount)| (*amount, *outpoint));
+    coins.truncate(MAX_CONSOLIDATION_COINS);
+    if coins.len() < 2 {
+        return None;
+    }
+
+    // The consolidation transaction has a single output, to one of our addresses.
+    let script_pubkey = main_descriptor
+        .receive_descriptor()
+        .derive(0.into(), secp)
+        .script_pubkey();
+    let tx = bitcoin::Transaction {
+        version: bitcoin::transaction::Version::TWO,
+        lock_time: LockTime::ZERO,
+        input: vec![bitcoin::TxIn::default(); coins.len()],
+        output: vec![bitcoin::TxOut {
+            value: bitcoin::Amount::ZERO,
+            script_pubkey,
+        }],
+    };
+    let fee = fee_for_vbytes(
+        main_descriptor.unsigned_tx_max_vbytes(&tx, true),
+        feerate_kvb,
+    );
+    let amount: bitcoin::Amount = coins.iter().map(|(_, amount)| *amount).sum();
+    if amount.checked_sub(fee)? < bitcoin::Amount::from_sat(DUST_OUTPUT_SATS) {
+        return None;
+    }
+
+    // Spending the coins later costs an input each. Once consolidated, it costs a single input.
+    let cost_later = input_fee_later * coins.len() as u64;
+    let savings = cost_later
+        .checked_sub(input_fee_later)?
+        .checked_sub(fee)
+        .filter(|savings| *savings > bitcoin::Amount::ZERO)?;
+    Some(ConsolidationAdvice {
+        coins: coins.into_iter().map(|(outpoint, _)| outpoint).collect(),
+        amount,
+        fee,
+        savings,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    use std::time::Duration;
+    use std::{str::FromStr, time::Duration};
 
     use miniscript::bitcoin::absolute::{Height, LockTime};
 
@@ -931,4 +1247,125 @@ mod tests {
             LockTime::from_height(1).unwrap() // subtract 90
         );
     }
+
+    #[test]
+    fn feerate_conversion() {
+        assert_eq!(feerate_kvb_from_vb(1.0), Some(1_000));
+        assert_eq!(feerate_kvb_from_vb(0.1), Some(100));
+        assert_eq!(feerate_kvb_from_vb(2.345), Some(2_345));
+        assert_eq!(feerate_kvb_from_vb(1.0001), None);
+        assert_eq!(feerate_kvb_from_vb(0.0), None);
+        assert_eq!(feerate_kvb_from_vb(-1.0), None);
+        assert_eq!(feerate_kvb_from_vb(f64::NAN), None);
+        assert_eq!(feerate_kvb_from_vb(f64::INFINITY), None);
+
+        assert_eq!(parse_feerate_vb("1.5"), Some(1_500));
+        assert_eq!(parse_feerate_vb(" 12 "), Some(12_000));
+        assert_eq!(parse_feerate_vb("NaN"), None);
+        assert_eq!(parse_feerate_vb("1,5"), None);
+
+        assert_eq!(format_feerate_kvb(1_000), "1");
+        assert_eq!(format_feerate_kvb(100), "0.1");
+        assert_eq!(format_feerate_kvb(2_345), "2.345");
+        assert_eq!(format_feerate_kvb(12_050), "12.05");
+    }
+
+    #[test]
+    fn consolidation_advisor() {
+        let desc = descriptors::CoincubeDescriptor::from_str("wsh(or_d(pk([8a550171/48'/1'/0'/2']tpubDFnCs5ZaCqopaNhgLCiXAwbkaBdcnuMt1VFoPsRpUrpidyvzG67MYjkfxw6HnTBhHqeU3xw2ioNBVcWY3jXwGhSyppEQvtn38GsL7RH1eef/<0;1>/*),and_v(v:pkh([8a550171/48'/1'/0'/2']tpubDFnCs5ZaCqopaNhgLCiXAwbkaBdcnuMt1VFoPsRpUrpidyvzG67MYjkfxw6HnTBhHqeU3xw2ioNBVcWY3jXwGhSyppEQvtn38GsL7RH1eef/<2;3>/*),older(52596))))#jz5sm0xn").unwrap();
+        let secp = secp256k1::Secp256k1::verification_only();
+        let txid = bitcoin::Txid::from_str(
+            "f7bd1b2a995b689d326e51eb742eb1088c4a8f110d9cb56128fd553acc9f88e5",
+        )
+        .unwrap();
+        let coin = |vout, sats| {
+            (
+                bitcoin::OutPoint::new(txid, vout),
+                bitcoin::Amount::from_sat(sats),
+            )
+        };
+        let input_vbytes = desc.spender_input_size(true) as u64;
+
+        // The small coins are worth consolidating, not the large one, nor the one which would not
+        // even pay for its own input at the current feerate of 2 sat/vb.
+        let coins = [
+            coin(0, 30_000),
+            coin(1, 1_000_000_000),
+            coin(2, 10_000),
+            coin(3, 20_000),
+            coin(4, 150),
+        ];
+        let advice = consolidation_advice(&desc, &secp, &coins, 2_000, 50_000).unwrap();
+        assert_eq!(advice.coins, vec![coins[2].0, coins[3].0, coins[0].0]);
+        assert_eq!(advice.amount, bitcoin::Amount::from_sat(60_000));
+        assert!(advice.fee > fee_for_vbytes(input_vbytes * 3, 2_000));
+        assert_eq!(
+            advice.savings,
+            fee_for_vbytes(input_vbytes, 50_000) * 2 - advice.fee
+        );
+
+        // Not worth it if the feerate isn't expected to rise enough, or with a single coin.
+        assert_eq!(
+            consolidation_advice(&desc, &secp, &coins, 2_000, 2_000),
+            None
+        );
+        assert_eq!(
+            consolidation_advice(&desc, &secp, &coins, 2_000, 2_100),
+            None
+        );
+        assert_eq!(
+            consolidation_advice(&desc, &secp, &coins[..2], 2_000, 50_000),
+            None
+        );
+    }
+
+    #[test]
+    fn subtract_fee_from_destinations_only() {
+        struct NoTx;
+        impl TxGetter for NoTx {
+            fn get_tx(&mut self, _: &bitcoin::Txid) -> Option<bitcoin::Transaction> {
+                None
+            }
+        }
+        let desc = descriptors::CoincubeDescriptor::from_str("wsh(or_d(pk([8a550171/48'/1'/0'/2']tpubDFnCs5ZaCqopaNhgLCiXAwbkaBdcnuMt1VFoPsRpUrpidyvzG67MYjkfxw6HnTBhHqeU3xw2ioNBVcWY3jXwGhSyppEQvtn38GsL7RH1eef/<0;1>/*),and_v(v:pkh([8a550171/48'/1'/0'/2']tpubDFnCs5ZaCqopaNhgLCiXAwbkaBdcnuMt1VFoPsRpUrpidyvzG67MYjkfxw6HnTBhHqeU3xw2ioNBVcWY3jXwGhSyppEQvtn38GsL7RH1eef/<2;3>/*),older(52596))))#jz5sm0xn").unwrap();
+        let secp = secp256k1::Secp256k1::verification_only();
+        let address = |index: u32| SpendOutputAddress {
+            addr: desc
+                .receive_descriptor()
+                .derive(index.into(), &secp)
+                .address(bitcoin::Network::Testnet),
+            info: None,
+        };
+        let res = create_spend(
+            &desc,
+            &secp,
+            &mut NoTx,
+            &[(address(0), bitcoin::Amount::from_sat(10_000))],
+            &[0, 1],
+            None,
+            &[],
+            SpendTxFees::Regular(1_000),
+            address(1),
+            LockTime::ZERO,
+        );
+        assert!(matches!(
+            res,
+            Err(SpendCreationError::InvalidSubtractFeeFrom(1))
+        ));
+    }
+
+    #[test]
+    fn op_return_data_extraction() {
+        let data = b"coincube timestamp".as_slice();
+        let push = <&bitcoin::script::PushBytes>::try_from(data).unwrap();
+        let script = bitcoin::ScriptBuf::new_op_return(push);
+        assert_eq!(op_return_data(&script), Some(data.to_vec()));
+        let empty = bitcoin::ScriptBuf::from_bytes(vec![0x6a]);
+        assert_eq!(op_return_data(&empty), Some(vec![]));
+        let p2wpkh = bitcoin::ScriptBuf::from_bytes([vec![0x00, 0x14], vec![0; 20]].concat());
+        assert_eq!(op_return_data(&p2wpkh), None);
+        // OP_RETURN <push> OP_1 OP_DROP
+        let non_push = bitcoin::ScriptBuf::from_bytes(vec![0x6a, 0x01, 0xff, 0x51, 0x75]);
+        assert_eq!(op_return_data(&non_push), None);
+    }
 }
@@ -0,0 +1,614 @@
+//! Accounting report of the wallet transactions: one row per wallet input, per output and per
+//! fee, with the fiat value at confirmation time and an optional cost basis.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::Display,
+    io::{self, Write},
+    path::PathBuf,
+};
+
+use chrono::DateTime;
+use coincube_core::miniscript::bitcoin::{Address, Amount, OutPoint, Txid};
+
+use crate::{
+    daemon::model::HistoryTransaction,
+    services::fiat::{Currency, PriceSource},
+};
+
+/// How the acquisition cost of the disposed coins is computed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CostBasis {
+    #[default]
+    Off,
+    /// First in, first out.
+    Fifo,
+    /// Last in, first out.
+    Lifo,
+}
+
+pub const ALL_COST_BASES: [CostBasis; 3] = [CostBasis::Off, CostBasis::Fifo, CostBasis::Lifo];
+
+impl Display for CostBasis {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Off => write!(f, "No cost basis"),
+            Self::Fifo => write!(f, "FIFO cost basis"),
+            Self::Lifo => write!(f, "LIFO cost basis"),
+        }
+    }
+}
+
+/// Options of the accounting export.
+#[derive(Debug, Clone, Default)]
+pub struct AccountingExport {
+    /// Source and currency of the historical prices, if fiat values are wanted.
+    pub fiat: Option<(PriceSource, Currency)>,
+    /// File caching the historical prices, so that they are available offline.
+    pub price_history: Option<PathBuf>,
+    pub cost_basis: CostBasis,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Classification {
+    /// Coins received from outside the wallet.
+    Receive,
+    /// Coins sent outside the wallet.
+    Send,
+    /// All the outputs are ours.
+    SelfTransfer,
+    /// Several of our coins merged into a single one of ours.
+    Consolidation,
+}
+
+impl Display for Classification {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Receive => write!(f, "receive"),
+            Self::Send => write!(f, "send"),
+            Self::SelfTransfer => write!(f, "self-transfer"),
+            Self::Consolidation => write!(f, "consolidation"),
+        }
+    }
+}
+
+pub fn classify(tx: &HistoryTransaction) -> Classification {
+    if tx.coins.is_empty() {
+        Classification::Receive
+    } else if tx.change_indexes.len() < tx.tx.output.len() {
+        Classification::Send
+    } else if tx.coins.len() > 1 && tx.tx.output.len() == 1 {
+        Classification::Consolidation
+    } else {
+        Classification::SelfTransfer
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RowKind {
+    Input,
+    Output,
+    Fee,
+}
+
+impl Display for RowKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Input => write!(f, "input"),
+            Self::Output => write!(f, "output"),
+            Self::Fee => write!(f, "fee"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccountingRow {
+    pub time: Option<u32>,
+    pub txid: Txid,
+    pub block: Option<i32>,
+    pub classification: Classification,
+    pub kind: RowKind,
+    /// Index of the input or output in the transaction.
+    pub index: Option<u32>,
+    pub address: Option<Address>,
+    /// Whether the input or output belongs to the wallet.
+    pub is_mine: bool,
+    pub amount: Amount,
+    pub label: Option<String>,
+    /// Price of a bitcoin on the day of the transaction confirmation.
+    pub price: Option<f64>,
+    pub fiat_value: Option<f64>,
+    /// Acquisition cost of the coins disposed of by an external output or a fee.
+    pub cost_basis: Option<f64>,
+    pub gain: Option<f64>,
+}
+
+/// Columns of the CSV export, with their JSON schema type and description.
+const COLUMNS: [(&str, &str, &str); 16] = [
+    (
+        "date",
+        "string",
+        "Confirmation date, in UTC, or empty if unconfirmed.",
+    ),
+    ("txid", "string", "Transaction id."),
+    (
+        "block",
+        "integer",
+        "Confirmation block height, or empty if unconfirmed.",
+    ),
+    (
+        "type",
+        "string",
+        "Transaction classification: receive, send, self-transfer or consolidation.",
+    ),
+    (
+        "row",
+        "string",
+        "What this row is about: input, output or fee.",
+    ),
+    (
+        "index",
+        "integer",
+        "Index of the input or output in the transaction.",
+    ),
+    ("address", "string", "Address of the input or output."),
+    (
+        "mine",
+        "boolean",
+        "Whether the input or output belongs to the wallet.",
+    ),
+    (
+        "amount_btc",
+        "number",
+        "Value of the input, output or fee, in BTC.",
+    ),
+    (
+        "label",
+        "string",
+        "Label of the input, output, address or transaction.",
+    ),
+    (
+        "price",
+        "number",
+        "Price of a bitcoin on the confirmation day.",
+    ),
+    (
+        "fiat_value",
+        "number",
+        "Value of the row at the price of the confirmation day.",
+    ),
+    (
+        "cost_basis",
+        "number",
+        "Acquisition cost of the coins disposed of.",
+    ),
+    (
+        "gain",
+        "number",
+        "Fiat value minus the cost basis of the coins disposed of.",
+    ),
+    ("currency", "string", "Currency of the fiat columns."),
+    ("cost_basis_method", "string", "FIFO or LIFO."),
+];
+
+/// Coins acquired at a price, to compute the cost basis of the coins disposed of.
+struct Lots {
+    method: CostBasis,
+    lots: VecDeque<(u64, Option<f64>)>,
+}
+
+impl Lots {
+    fn acquire(&mut self, amount: Amount, price: Option<f64>) {
+        self.lots.push_back((amount.to_sat(), price));
+    }
+
+    /// Remove the given amount from the lots, returning its cost if known.
+    fn dispose(&mut self, amount: Amount) -> Option<f64> {
+        let mut remaining = amount.to_sat();
+        let mut cost = Some(0.0);
+        while remaining > 0 {
+            let lot = match self.method {
+                CostBasis::Lifo => self.lots.back_mut(),
+                _ => self.lots.front_mut(),
+            };
+            let Some((sats, price)) = lot else {
+                // We don't know where the rest of the coins come from.
+                return None;
+            };
+            let taken = remaining.min(*sats);
+            cost = cost
+                .zip(*price)
+                .map(|(cost, price)| cost + Amount::from_sat(taken).to_btc() * price);
+            remaining -= taken;
+            *sats -= taken;
+            if *sats == 0 {
+                match self.method {
+                    CostBasis::Lifo => self.lots.pop_back(),
+                    _ => self.lots.pop_front(),
+                };
+            }
+        }
+        cost
+    }
+}
+
+/// Build the rows of the report, from the oldest transaction to the most recent one.
+/// `prices` are the prices of a bitcoin at the confirmation of each transaction.
+pub fn accounting_rows(
+    txs: &[HistoryTransaction],
+    prices: &HashMap<Txid, f64>,
+    cost_basis: CostBasis,
+) -> Vec<AccountingRow> {
+    let mut txs: Vec<_> = txs.iter().collect();
+    txs.sort_by_key(|tx| (tx.time.is_none(), tx.time, tx.txid));
+    let mut lots = (cost_basis != CostBasis::Off).then(|| Lots {
+        method: cost_basis,
+        lots: VecDeque::new(),
+    });
+
+    let mut rows = Vec::new();
+    for tx in txs {
+        let classification = classify(tx);
+        let price = prices.get(&tx.txid).copied();
+        let label = |outpoint: Option<OutPoint>, address: Option<&Address>| {
+            outpoint
+                .and_then(|o| tx.labels.get(&o.to_string()))
+                .or_else(|| address.and_then(|a| tx.labels.get(&a.to_string())))
+                .or_else(|| tx.labels.get(&tx.txid.to_string()))
+                .cloned()
+        };
+        let row =
+            |kind, index, address: Option<Address>, is_mine, amount: Amount, label| AccountingRow {
+                time: tx.time,
+                txid: tx.txid,
+                block: tx.height,
+                classification,
+                kind,
+                index,
+                address,
+                is_mine,
+                amount,
+                label,
+                price,
+                fiat_value: price.map(|p| amount.to_btc() * p),
+                cost_basis: None,
+                gain: None,
+            };
+
+        for (index, input) in tx.tx.input.iter().enumerate() {
+            if let Some(coin) = tx.coins.get(&input.previous_output) {
+                rows.push(row(
+                    RowKind::Input,
+                    Some(index as u32),
+                    Some(coin.address.clone()),
+                    true,
+                    coin.amount,
+                    label(Some(coin.outpoint), Some(&coin.address)),
+                ));
+            }
+        }
+        for (index, output) in tx.tx.output.iter().enumerate() {
+            let address = Address::from_script(&output.script_pubkey, tx.network).ok();
+            let is_mine = tx.change_indexes.contains(&index);
+            let outpoint = OutPoint::new(tx.txid, index as u32);
+            let mut row = row(
+                RowKind::Output,
+                Some(index as u32),
+                address.clone(),
+                is_mine,
+                output.value,
+                label(Some(outpoint), address.as_ref()),
+            );
+            if let Some(lots) = lots.as_mut() {
+                if classification == Classification::Receive && is_mine {
+                    lots.acquire(output.value, price);
+                } else if !is_mine && !tx.coins.is_empty() {
+                    // Only the external outputs funded by our coins are disposals.
+                    row.cost_basis = lots.dispose(output.value);
+                    row.gain = row.fiat_value.zip(row.cost_basis).map(|(v, c)| v - c);
+                }
+            }
+            rows.push(row);
+        }
+        // Only the fees we paid.
+        if let Some(fee) = tx.fee_amount.filter(|_| !tx.coins.is_empty()) {
+            let mut row = row(RowKind::Fee, None, None, true, fee, label(None, None));
+            if let Some(lots) = lots.as_mut() {
+                row.cost_basis = lots.dispose(fee);
+                row.gain = row.fiat_value.zip(row.cost_basis).map(|(v, c)| v - c);
+            }
+            rows.push(row);
+        }
+    }
+    rows
+}
+
+fn csv_field(field: impl Display) -> String {
+    let field = field.to_string();
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field
+    }
+}
+
+fn fiat_field(value: Option<f64>, currency: Currency) -> String {
+    value
+        .map(|v| format!("{:.*}", currency.decimals(), v))
+        .unwrap_or_default()
+}
+
+/// Write the rows as CSV, with the columns described by [`json_schema`].
+pub fn write_csv<W: Write>(
+    writer: &mut W,
+    rows: &[AccountingRow],
+    currency: Option<Currency>,
+    cost_basis: CostBasis,
+) -> io::Result<()> {
+    let header: Vec<_> = COLUMNS.iter().map(|(name, ..)| *name).collect();
+    writeln!(writer, "{}", header.join(","))?;
+    let method = match cost_basis {
+        CostBasis::Off => "",
+        CostBasis::Fifo => "FIFO",
+        CostBasis::Lifo => "LIFO",
+    };
+    for row in rows {
+        let date = row
+            .time
+            .and_then(|t| DateTime::from_timestamp(t as i64, 0))
+            .map(|d| d.format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_default();
+        let fiat = |value: Option<f64>| currency.map(|c| fiat_field(value, c)).unwrap_or_default();
+        let fields = [
+            date,
+            row.txid.to_string(),
+            row.block.map(|b| b.to_string()).unwrap_or_default(),
+            row.classification.to_string(),
+            row.kind.to_string(),
+            row.index.map(|i| i.to_string()).unwrap_or_default(),
+            row.address
+                .as_ref()
+                .map(|a| a.to_string())
+                .unwrap_or_default(),
+            row.is_mine.to_string(),
+            format!("{:.8}", row.amount.to_btc()),
+            csv_field(row.label.as_deref().unwrap_or_default()),
+            fiat(row.price),
+            fiat(row.fiat_value),
+            fiat(row.cost_basis),
+            fiat(row.gain),
+            currency.map(|c| c.to_string()).unwrap_or_default(),
+            method.to_string(),
+        ];
+        writeln!(writer, "{}", fields.join(","))?;
+    }
+    Ok(())
+}
+
+/// JSON schema of a row of the CSV export. Empty fields are nulls.
+pub fn json_schema() -> serde_json::Value {
+    let properties: serde_json::Map<_, _> = COLUMNS
+        .iter()
+        .map(|(name, kind, description)| {
+            (
+                name.to_string(),
+                serde_json::json!({
+                    "type": [kind, "null"],
+                    "description": description,
+                }),
+            )
+        })
+        .collect();
+    serde_json::json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "Coincube accounting export row",
+        "type": "object",
+        "properties": properties,
+        "required": ["txid", "type", "row", "mine", "amount_btc"],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::daemon::model::Coin;
+    use coincube_core::miniscript::bitcoin::{
+        absolute, bip32::ChildNumber, hashes::Hash, transaction::Version, Network, ScriptBuf,
+        Transaction, TxIn, TxOut, WPubkeyHash,
+    };
+    use std::str::FromStr;
+
+    fn address(i: u8) -> Address {
+        let script = ScriptBuf::new_p2wpkh(&WPubkeyHash::from_byte_array([i; 20]));
+        Address::from_script(&script, Network::Bitcoin).unwrap()
+    }
+
+    /// A transaction spending the given coins, with outputs to the given addresses (the
+    /// first ones being ours), confirmed at `time`.
+    fn history_tx(
+        time: u32,
+        coins: Vec<Coin>,
+        outputs: &[(u8, u64)],
+        ours: usize,
+        fee: u64,
+    ) -> HistoryTransaction {
+        let tx = Transaction {
+            version: Version::TWO,
+            lock_time: absolute::LockTime::from_consensus(time),
+            input: coins
+                .iter()
+                .map(|c| TxIn {
+                    previous_output: c.outpoint,
+                    ..TxIn::default()
+                })
+                .chain(coins.is_empty().then(TxIn::default))
+                .collect(),
+            output: outputs
+                .iter()
+                .map(|(i, sats)| TxOut {
+                    value: Amount::from_sat(*sats),
+                    script_pubkey: address(*i).script_pubkey(),
+                })
+                .collect(),
+        };
+        let mut history_tx = HistoryTransaction::new(
+            tx,
+            Some(time as i32),
+            Some(time),
+            coins,
+            (0..ours).collect(),
+            Network::Bitcoin,
+        );
+        history_tx.fee_amount = Some(Amount::from_sat(fee));
+        history_tx
+    }
+
+    fn coin_of(tx: &HistoryTransaction, vout: u32) -> Coin {
+        let output = &tx.tx.output[vout as usize];
+        Coin {
+            outpoint: OutPoint::new(tx.txid, vout),
+            amount: output.value,
+            address: Address::from_script(&output.script_pubkey, Network::Bitcoin).unwrap(),
+            derivation_index: ChildNumber::from_normal_idx(0).unwrap(),
+            block_height: tx.height,
+            is_immature: false,
+            is_change: false,
+            is_from_self: false,
+            spend_info: None,
+        }
+    }
+
+    #[test]
+    fn accounting_report() {
+        // Receive 1 BTC at 10k and 1 BTC at 20k, then send 1.5 BTC at 30k with a 0.001 BTC fee
+        // and 0.499 BTC of change, then consolidate the change at 40k.
+        let receive1 = history_tx(1_000, Vec::new(), &[(1, 100_000_000)], 1, 0);
+        let receive2 = history_tx(2_000, Vec::new(), &[(2, 100_000_000)], 1, 0);
+        let send = history_tx(
+            3_000,
+            vec![coin_of(&receive1, 0), coin_of(&receive2, 0)],
+            &[(3, 49_900_000), (9, 150_000_000)],
+            1,
+            100_000,
+        );
+        let consolidation = history_tx(
+            4_000,
+            vec![coin_of(&send, 0)],
+            &[(4, 49_800_000)],
+            1,
+            100_000,
+        );
+        assert_eq!(classify(&receive1), Classification::Receive);
+        assert_eq!(classify(&send), Classification::Send);
+        assert_eq!(classify(&consolidation), Classification::SelfTransfer);
+
+        let mut receive1 = receive1;
+        receive1
+            .labels
+            .insert(receive1.txid.to_string(), "Salary, March".to_string());
+        let prices = HashMap::from([
+            (receive1.txid, 10_000.0),
+            (receive2.txid, 20_000.0),
+            (send.txid, 30_000.0),
+            (consolidation.txid, 40_000.0),
+        ]);
+        let txs = [consolidation.clone(), send.clone(), receive2, receive1];
+
+        let rows = accounting_rows(&txs, &prices, CostBasis::Fifo);
+        let kinds: Vec<_> = rows.iter().map(|r| (r.classification, r.kind)).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (Classification::Receive, RowKind::Output),
+                (Classification::Receive, RowKind::Output),
+                (Classification::Send, RowKind::Input),
+                (Classification::Send, RowKind::Input),
+                (Classification::Send, RowKind::Output),
+                (Classification::Send, RowKind::Output),
+                (Classification::Send, RowKind::Fee),
+                (Classification::SelfTransfer, RowKind::Input),
+                (Classification::SelfTransfer, RowKind::Output),
+                (Classification::SelfTransfer, RowKind::Fee),
+            ]
+        );
+        assert_eq!(rows[0].label.as_deref(), Some("Salary, March"));
+
+        // FIFO: the 1.5 BTC sent cost 1 BTC at 10k and 0.5 BTC at 20k.
+        let sent = &rows[5];
+        assert!(!sent.is_mine);
+        assert_eq!(sent.fiat_value, Some(45_000.0));
+        assert_eq!(sent.cost_basis, Some(20_000.0));
+        assert_eq!(sent.gain, Some(25_000.0));
+        // The fee is a disposal too.
+        let fee = &rows[6];
+        assert_eq!(fee.amount, Amount::from_sat(100_000));
+        assert_eq!(fee.fiat_value, Some(30.0));
+        assert_eq!(fee.cost_basis, Some(20.0));
+        // Change outputs are neither acquisitions nor disposals.
+        assert_eq!(rows[4].cost_basis, None);
+
+        // LIFO: the 1.5 BTC sent cost 1 BTC at 20k and 0.5 BTC at 10k.
+        let rows = accounting_rows(&txs, &prices, CostBasis::Lifo);
+        assert_eq!(rows[5].cost_basis, Some(25_000.0));
+        // Without cost basis nor prices.
+        let rows = accounting_rows(&txs, &HashMap::new(), CostBasis::Off);
+        assert!(rows
+            .iter()
+            .all(|r| r.price.is_none() && r.cost_basis.is_none()));
+
+        let rows = accounting_rows(&txs, &prices, CostBasis::Fifo);
+        let mut csv = Vec::new();
+        write_csv(&mut csv, &rows, Some(Currency::USD), CostBasis::Fifo).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(lines.len(), rows.len() + 1);
+        assert_eq!(
+            lines[0],
+            "date,txid,block,type,row,index,address,mine,amount_btc,label,price,fiat_value,cost_basis,gain,currency,cost_basis_method"
+        );
+        assert!(lines[1].starts_with("1970-01-01 00:16:40,"));
+        assert!(lines[1].contains(",\"Salary, March\",10000.00,10000.00,,,USD,FIFO"));
+        assert!(lines[6].ends_with(",45000.00,20000.00,25000.00,USD,FIFO"));
+
+        let schema = json_schema();
+        assert_eq!(
+            schema["properties"].as_object().unwrap().len(),
+            COLUMNS.len()
+        );
+        assert!(Txid::from_str(lines[1].split(',').nth(1).unwrap()).is_ok());
+    }
+
+    #[test]
+    fn accounting_external_change() {
+        // We are paid 1 BTC by a transaction which also sends the payer's change back to them.
+        // Their change is not a disposal of our coins.
+        let receive = history_tx(
+            1_000,
+            Vec::new(),
+            &[(1, 100_000_000), (9, 50_000_000)],
+            1,
+            0,
+        );
+        let send = history_tx(
+            2_000,
+            vec![coin_of(&receive, 0)],
+            &[(3, 99_900_000)],
+            0,
+            100_000,
+        );
+        assert_eq!(classify(&receive), Classification::Receive);
+        let prices = HashMap::from([(receive.txid, 10_000.0), (send.txid, 20_000.0)]);
+
+        let rows = accounting_rows(&[receive, send], &prices, CostBasis::Fifo);
+        let external_change = &rows[1];
+        assert!(!external_change.is_mine);
+        assert_eq!(external_change.cost_basis, None);
+        assert_eq!(external_change.gain, None);
+        // Hence the whole received coin is still available to cost the payment we make.
+        let sent = &rows[3];
+        assert_eq!(sent.amount, Amount::from_sat(99_900_000));
+        assert_eq!(sent.cost_basis, Some(9_990.0));
+        assert_eq!(sent.gain, Some(9_990.0));
+        assert_eq!(rows[4].cost_basis, Some(10.0));
+    }
+}
@@ -11,7 +11,7 @@ use crate::{
     },
 };
 use coincube_core::miniscript::bitcoin::Network;
-use coincubed::commands::CoinStatus;
+use coincubed::{commands::CoinStatus, policy::SpendingPolicy};
 use std::sync::Arc;
 use std::time::Instant;
 
@@ -67,6 +67,10 @@ impl Cache {
     pub fn last_poll_timestamp(&self) -> Option<u32> {
         self.daemon_cache.last_poll_timestamp
     }
+
+    pub fn spending_policy(&self) -> Option<&SpendingPolicy> {
+        self.daemon_cache.spending_policy.as_ref()
+    }
 }
 
 /// The cache for dynamic daemon data.
@@ -78,6 +82,8 @@ pub struct DaemonCache {
     pub sync_progress: f64,
     /// The most recent `last_poll_timestamp`.
     pub last_poll_timestamp: Option<u32>,
+    /// The spending policy enforced by the daemon, if any.
+    pub spending_policy: Option<SpendingPolicy>,
     pub last_tick: std::time::Instant,
 }
 
@@ -90,6 +96,7 @@ impl std::default::Default for DaemonCache {
             rescan_progress: None,
             sync_progress: 1.0,
             last_poll_timestamp: None,
+            spending_policy: None,
             last_tick: Instant::now(),
         }
     }
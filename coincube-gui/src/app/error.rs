@@ -7,6 +7,7 @@ use coincubed::config::ConfigError;
 use crate::{
     app::{settings::SettingsError, wallet::WalletError},
     daemon::DaemonError,
+    drill::DrillError,
     export::{self, RestoreBackupError},
     services::fiat::api::PriceApiError,
 };
@@ -23,6 +24,7 @@ pub enum Error {
     ImportExport(export::Error),
     RestoreBackup(RestoreBackupError),
     FiatPrice(PriceApiError),
+    Drill(DrillError),
 }
 
 impl std::fmt::Display for Error {
@@ -66,6 +68,7 @@ impl std::fmt::Display for Error {
             Self::ImportExport(e) => write!(f, "{e}"),
             Self::RestoreBackup(e) => write!(f, "{e}"),
             Self::FiatPrice(e) => write!(f, "Fiat price error: {}", e),
+            Self::Drill(e) => write!(f, "Recovery drill: {}", e),
         }
     }
 }
@@ -111,3 +114,9 @@ impl From<PriceApiError> for Error {
         Error::FiatPrice(error)
     }
 }
+
+impl From<DrillError> for Error {
+    fn from(error: DrillError) -> Self {
+        Error::Drill(error)
+    }
+}
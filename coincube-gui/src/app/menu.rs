@@ -19,7 +19,6 @@ pub enum Menu {
     Coins,
     CreateSpendTx,
     Recovery,
-    RefreshCoins(Vec<OutPoint>),
     PsbtPreSelected(Txid),
 }
 
@@ -4,7 +4,7 @@ use std::sync::Arc;
 use coincube_core::miniscript::bitcoin::{
     bip32::{ChildNumber, Fingerprint},
     psbt::Psbt,
-    Address, Txid,
+    Address, OutPoint, Txid,
 };
 use coincubed::config::Config as DaemonConfig;
 
@@ -16,8 +16,11 @@ use crate::{
         wallet::Wallet,
     },
     daemon::model::*,
+    drill::Drill,
     export::ImportExportMessage,
+    heir::HeirPackage,
     hw::HardwareWalletMessage,
+    node::bitcoind::{Bitcoind, BitcoindUpgrade},
     services::fiat::{
         api::{ListCurrenciesResult, PriceApiError},
         PriceSource,
@@ -43,13 +46,26 @@ pub enum Message {
         Option<ChildNumber>, // start_index
     ),
     Coins(Result<Vec<Coin>, Error>),
+    /// All the coins of the wallet, spent ones included.
+    CoinsHistory(Result<Vec<Coin>, Error>),
     /// When we want both coins and tip height together.
     CoinsTipHeight(Result<Vec<Coin>, Error>, Result<i32, Error>),
     Labels(Result<HashMap<String, String>, Error>),
     SpendTxs(Result<Vec<SpendTx>, Error>),
     Psbt(Result<(Psbt, Vec<String>), Error>),
     RbfPsbt(Result<Txid, Error>),
+    /// The ID of the child transaction created to bump the fee of its parent.
+    CpfpPsbt(Result<Txid, Error>),
     Recovery(Result<SpendTx, Error>),
+    /// The BIP-0322 `to_sign` PSBT of a message to sign.
+    MessagePsbt(Result<Psbt, Error>),
+    /// An heir package, with its recovery transactions signed by the keys available.
+    HeirPackage(Result<Box<HeirPackage>, Error>),
+    /// A recovery drill, funded and past the timelock of its recovery path.
+    DrillStarted(Result<Arc<Drill>, Error>),
+    /// The recovery transaction of the drill to sign.
+    DrillPsbt(Result<Psbt, Error>),
+    DrillBroadcast(Result<(), Error>),
     Signed(Fingerprint, Result<Psbt, Error>),
     WalletUpdated(Result<Arc<Wallet>, Error>),
     Updated(Result<(), Error>),
@@ -60,6 +76,11 @@ pub enum Message {
     HistoryTransactionsExtension(Result<Vec<HistoryTransaction>, Error>),
     HistoryTransactions(Result<Vec<HistoryTransaction>, Error>),
     Payments(Result<Vec<Payment>, Error>),
+    BalanceHistory(Result<Vec<BalanceHistoryEntry>, Error>),
+    /// Current feerate estimate (in sats/kvb) to advise a consolidation of coins against, if
+    /// it could be fetched.
+    ConsolidationFeerate(Option<u64>),
+    ConsolidationAdvice(Result<Option<ConsolidationAdvice>, Error>),
     /// Extension of payments for pagination.
     /// Tuple contains (Vec<Payment>, u64) where the u64 is the actual page limit used
     /// for fetching payments. This limit may differ from HISTORY_EVENT_PAGE_SIZE when
@@ -67,9 +88,23 @@ pub enum Message {
     PaymentsExtension(Result<(Vec<Payment>, u64), Error>),
     Payment(Result<(HistoryTransaction, usize), Error>),
     LabelsUpdated(Result<HashMap<String, Option<String>>, Error>),
-    BroadcastModal(Result<HashSet<Txid>, Error>),
+    /// The directly conflicting transactions of the Spend transaction to broadcast and its
+    /// broadcast schedule, if any.
+    BroadcastModal(Result<(HashSet<Txid>, Option<ScheduledBroadcastEntry>), Error>),
+    /// The broadcast schedule of a Spend transaction was updated.
+    BroadcastScheduled(Result<Option<ScheduledBroadcastEntry>, Error>),
+    /// A Spend transaction was approved, at this time if known.
+    SpendApproved(Result<Option<u32>, Error>),
     RbfModal(Box<HistoryTransaction>, bool, Result<HashSet<Txid>, Error>),
+    /// The unspent outputs of ours of a transaction to bump the fee of using CPFP.
+    CpfpModal(Box<HistoryTransaction>, Result<Vec<OutPoint>, Error>),
     Export(ImportExportMessage),
+    /// Restart the managed bitcoind, upgrading it first if a new version is given.
+    RestartInternalBitcoind(Option<BitcoindUpgrade>),
+    /// The managed bitcoind was restarted. On error, the node running after the failure, if
+    /// any, is returned along with the error.
+    InternalBitcoindRestarted(Result<Bitcoind, (String, Option<Bitcoind>)>),
+    InternalBitcoindUpdated(Result<(), Error>),
 }
 
 impl From<ImportExportMessage> for Message {
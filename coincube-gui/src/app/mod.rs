@@ -274,7 +274,6 @@ impl Panels {
             Menu::Coins => self.coins.as_ref().map(|v| v as &dyn State),
             Menu::CreateSpendTx => self.create_spend.as_ref().map(|v| v as &dyn State),
             Menu::Recovery => self.recovery.as_ref().map(|v| v as &dyn State),
-            Menu::RefreshCoins(_) => self.create_spend.as_ref().map(|v| v as &dyn State),
             Menu::PsbtPreSelected(_) => self.psbts.as_ref().map(|v| v as &dyn State),
         }
     }
@@ -332,7 +331,6 @@ impl Panels {
             Menu::Coins => self.coins.as_mut().map(|v| v as &mut dyn State),
             Menu::CreateSpendTx => self.create_spend.as_mut().map(|v| v as &mut dyn State),
             Menu::Recovery => self.recovery.as_mut().map(|v| v as &mut dyn State),
-            Menu::RefreshCoins(_) => self.create_spend.as_mut().map(|v| v as &mut dyn State),
             Menu::PsbtPreSelected(_) => self.psbts.as_mut().map(|v| v as &mut dyn State),
         }
     }
@@ -522,11 +520,13 @@ impl App {
                                         .await
                                         .map(|txs| txs.first().cloned())
                                 }) {
-                                    if let Some(psbts) = &mut self.panels.psbts {
-                                        psbts.preselect(spend_tx);
-                                    }
+                                    let cmd = if let Some(psbts) = &mut self.panels.psbts {
+                                        psbts.preselect(daemon.clone(), spend_tx)
+                                    } else {
+                                        Task::none()
+                                    };
                                     self.panels.current = menu;
-                                    return Task::none();
+                                    return cmd;
                                 }
                             }
                         }
@@ -764,6 +764,7 @@ impl App {
                             rescan_progress: info.rescan_progress,
                             sync_progress: info.sync,
                             last_poll_timestamp: info.last_poll_timestamp,
+                            spending_policy: info.spending_policy,
                             last_tick: tick,
                         })
                     },
@@ -898,6 +899,48 @@ impl App {
                 }
             }
             Message::View(view::Message::Clipboard(text)) => return clipboard::write(text),
+            Message::RestartInternalBitcoind(upgrade) => {
+                let Some(bitcoind) = self.internal_bitcoind.take() else {
+                    return Task::done(Message::InternalBitcoindUpdated(Err(Error::Unexpected(
+                        "Bitcoind is not managed by Coincube".to_string(),
+                    ))));
+                };
+                let network = self.cache.network;
+                let datadir = self.datadir.clone();
+                return Task::perform(
+                    async move {
+                        // Stopping and starting bitcoind waits on the process, so don't block the
+                        // executor while doing so.
+                        tokio::task::spawn_blocking(move || match upgrade {
+                            None => bitcoind
+                                .restart(network, &datadir)
+                                .map_err(|e| (e.to_string(), None)),
+                            Some(upgrade) => bitcoind
+                                .upgrade(network, &datadir, upgrade)
+                                .map_err(|(e, bitcoind)| (e.to_string(), bitcoind)),
+                        })
+                        .await
+                        .unwrap_or_else(|e| Err((e.to_string(), None)))
+                    },
+                    Message::InternalBitcoindRestarted,
+                );
+            }
+            Message::InternalBitcoindRestarted(res) => {
+                let res = match res {
+                    Ok(bitcoind) => {
+                        self.internal_bitcoind = Some(bitcoind);
+                        Ok(())
+                    }
+                    Err((e, bitcoind)) => {
+                        if bitcoind.is_none() {
+                            error!("Managed bitcoind is not running: {}", e);
+                        }
+                        self.internal_bitcoind = bitcoind;
+                        Err(Error::Unexpected(e))
+                    }
+                };
+                return self.update(Message::InternalBitcoindUpdated(res));
+            }
 
             msg => {
                 if let (Some(daemon), Some(panel)) =
@@ -0,0 +1,256 @@
+//! Address book of the payees the wallet sends to.
+
+use std::str::FromStr;
+
+use coincube_core::miniscript::{
+    bitcoin::{Address, Network},
+    Descriptor, DescriptorPublicKey,
+};
+use serde::{Deserialize, Serialize};
+
+/// Number of addresses derived from a contact descriptor, past its next index, that are still
+/// recognized as belonging to the contact.
+pub const DESCRIPTOR_LOOKAHEAD: u32 = 20;
+
+/// Minimum number of identical leading and trailing characters (after the bech32 prefix) for
+/// an address to be considered as resembling another one.
+const RESEMBLANCE_CHARS: usize = 4;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Contact {
+    pub name: String,
+    /// Addresses of the payee, or descriptors the payee addresses are derived from.
+    pub destinations: Vec<String>,
+    #[serde(default)]
+    pub note: String,
+    /// Date, as `YYYY-MM-DD`, the destinations were last verified with the payee out of band.
+    #[serde(default)]
+    pub verified_on: Option<String>,
+    /// Derivation index of the next address to send to for descriptor destinations.
+    #[serde(default)]
+    pub next_index: u32,
+}
+
+/// A contact destination parsed for a network.
+#[derive(Debug, Clone)]
+pub enum Destination {
+    Address(Address),
+    Descriptor(Descriptor<DescriptorPublicKey>),
+}
+
+impl Destination {
+    /// Parse an address of the given network, or a ranged descriptor. For multipath
+    /// descriptors, only the first (receive) path is used.
+    pub fn parse(s: &str, network: Network) -> Option<Self> {
+        let s = s.trim();
+        if let Ok(address) = Address::from_str(s) {
+            return address.require_network(network).ok().map(Self::Address);
+        }
+        let descriptor = Descriptor::<DescriptorPublicKey>::from_str(s)
+            .ok()?
+            .into_single_descriptors()
+            .ok()?
+            .into_iter()
+            .next()?;
+        // The address must change at each payment.
+        if !descriptor.has_wildcard() {
+            return None;
+        }
+        descriptor
+            .at_derivation_index(0)
+            .ok()?
+            .address(network)
+            .ok()?;
+        Some(Self::Descriptor(descriptor))
+    }
+
+    /// The address of this destination at the given derivation index.
+    pub fn address(&self, network: Network, index: u32) -> Option<Address> {
+        match self {
+            Self::Address(address) => Some(address.clone()),
+            Self::Descriptor(descriptor) => descriptor
+                .at_derivation_index(index)
+                .ok()?
+                .address(network)
+                .ok(),
+        }
+    }
+}
+
+impl Contact {
+    pub fn parsed_destinations(&self, network: Network) -> Vec<Destination> {
+        self.destinations
+            .iter()
+            .filter_map(|d| Destination::parse(d, network))
+            .collect()
+    }
+
+    /// The addresses to pay this contact to: the plain addresses and the address at the next
+    /// index of each descriptor.
+    pub fn payment_addresses(&self, network: Network) -> Vec<Address> {
+        self.parsed_destinations(network)
+            .iter()
+            .filter_map(|d| d.address(network, self.next_index))
+            .collect()
+    }
+
+    /// Whether any of the contact destinations is a descriptor.
+    pub fn has_descriptor(&self, network: Network) -> bool {
+        self.parsed_destinations(network)
+            .iter()
+            .any(|d| matches!(d, Destination::Descriptor(_)))
+    }
+
+    /// All the addresses recognized as belonging to this contact.
+    pub fn known_addresses(&self, network: Network) -> Vec<Address> {
+        let mut addresses = Vec::new();
+        for destination in self.parsed_destinations(network) {
+            match destination {
+                Destination::Address(address) => addresses.push(address),
+                Destination::Descriptor(_) => addresses.extend(
+                    (0..self.next_index.saturating_add(DESCRIPTOR_LOOKAHEAD))
+                        .filter_map(|i| destination.address(network, i)),
+                ),
+            }
+        }
+        addresses
+    }
+}
+
+/// What the address book knows about an address the user is about to send to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ContactMatch<'a> {
+    /// The address belongs to this contact.
+    Known(&'a Contact),
+    /// The address looks like an address of this contact but is not one of them, which may be
+    /// an address poisoning attempt.
+    LookAlike(&'a Contact, Address),
+}
+
+pub fn match_contact<'a>(
+    contacts: &'a [Contact],
+    address: &Address,
+    network: Network,
+) -> Option<ContactMatch<'a>> {
+    let mut look_alike = None;
+    for contact in contacts {
+        for known in contact.known_addresses(network) {
+            if &known == address {
+                return Some(ContactMatch::Known(contact));
+            }
+            if look_alike.is_none() && resembles(&known.to_string(), &address.to_string()) {
+                look_alike = Some(ContactMatch::LookAlike(contact, known));
+            }
+        }
+    }
+    look_alike
+}
+
+/// Whether two different addresses share their first and last characters, as vanity addresses
+/// crafted for address poisoning do. The bech32 human readable part is not compared.
+fn resembles(a: &str, b: &str) -> bool {
+    fn data(address: &str) -> String {
+        let address = address.to_lowercase();
+        match address.rsplit_once('1') {
+            Some((hrp, data)) if ["bc", "tb", "bcrt"].contains(&hrp) => data.to_string(),
+            _ => address,
+        }
+    }
+    let (a, b) = (data(a), data(b));
+    if a == b {
+        return false;
+    }
+    let prefix = a.chars().zip(b.chars()).take_while(|(a, b)| a == b).count();
+    let suffix = a
+        .chars()
+        .rev()
+        .zip(b.chars().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    prefix >= RESEMBLANCE_CHARS && suffix >= RESEMBLANCE_CHARS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const XPUB: &str = "tpubD6NzVbkrYhZ4YgUx2ZLNt2rLYAMTdYysCRzKoLu2BeSHKvzqPaBDvf17GeBPnExUVPkuBpx4kniP964e2MxyzzazcXLptxLXModSVCVEV1T";
+
+    #[test]
+    fn contact_addresses() {
+        let network = Network::Testnet;
+        let contact = Contact {
+            name: "Supplier".to_string(),
+            destinations: vec![
+                "tb1qfufcrdyarcg5eph608c6l8vktrc9re6agu4se2".to_string(),
+                format!("wpkh({}/<0;1>/*)", XPUB),
+                // Wrong network, ignored.
+                "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4".to_string(),
+                // Not ranged, ignored.
+                format!("wpkh({})", XPUB),
+            ],
+            next_index: 2,
+            ..Default::default()
+        };
+        assert_eq!(contact.parsed_destinations(network).len(), 2);
+        assert!(contact.has_descriptor(network));
+
+        let payment = contact.payment_addresses(network);
+        assert_eq!(payment.len(), 2);
+        let known = contact.known_addresses(network);
+        assert_eq!(known.len(), 1 + 2 + DESCRIPTOR_LOOKAHEAD as usize);
+        // The address at the next index is the one to pay to, and it's a known one.
+        assert_eq!(known[3], payment[1]);
+
+        let contacts = [contact.clone()];
+        assert_eq!(
+            match_contact(&contacts, &known[5], network),
+            Some(ContactMatch::Known(&contacts[0]))
+        );
+    }
+
+    #[test]
+    fn address_poisoning() {
+        let network = Network::Testnet;
+        let saved = "tb1qfufcrdyarcg5eph608c6l8vktrc9re6agu4se2";
+        let contacts = [Contact {
+            name: "Supplier".to_string(),
+            destinations: vec![saved.to_string()],
+            ..Default::default()
+        }];
+        let address = |s: &str| {
+            Address::from_str(s)
+                .unwrap()
+                .require_network(network)
+                .unwrap()
+        };
+
+        assert_eq!(
+            match_contact(&contacts, &address(saved), network),
+            Some(ContactMatch::Known(&contacts[0]))
+        );
+        // Same first and last characters.
+        assert!(resembles(
+            saved,
+            "tb1qfufcr0000000000000000000000000000u4se2"
+        ));
+        assert!(!resembles(
+            saved,
+            "tb1qfufcr0000000000000000000000000000u4se3"
+        ));
+        assert!(!resembles(
+            saved,
+            "tb1qxufcr0000000000000000000000000000u4se2"
+        ));
+        // The human readable part doesn't count.
+        assert!(!resembles(
+            saved,
+            "tb1pzzzzrdyarcg5eph608c6l8vktrc9re6agu4zzz"
+        ));
+        assert!(!resembles(saved, saved));
+
+        // An unrelated address.
+        let other = address("tb1qqurswpc8qurswpc8qurswpc8qurswpc800zzll");
+        assert_eq!(match_contact(&contacts, &other, network), None);
+    }
+}
@@ -1,5 +1,6 @@
 //! Settings is the module to handle the GUI settings file.
 //! The settings file is used by the GUI to store useful information.
+pub mod contacts;
 pub mod fiat;
 
 use std::collections::HashMap;
@@ -18,6 +19,7 @@ use serde::{Deserialize, Serialize};
 use crate::{
     backup::{Key, KeyRole, KeyType},
     dir::NetworkDirectory,
+    heir::HeirPackageRecord,
     hw::HardwareWalletConfig,
     services::{self, connect::client::backend},
     utils::serde::ok_or_none,
@@ -252,6 +254,12 @@ pub struct WalletSettings {
     // setting will be set to None during deserialization and the user will need to reconfigure it.
     #[serde(default, deserialize_with = "ok_or_none")]
     pub fiat_price: Option<fiat::PriceSetting>,
+    /// Address book of the payees of this wallet.
+    #[serde(default)]
+    pub contacts: Vec<contacts::Contact>,
+    /// The last heir package exported for this wallet.
+    #[serde(default)]
+    pub heir_package: Option<HeirPackageRecord>,
 }
 
 impl WalletSettings {
@@ -504,6 +512,9 @@ pub mod global {
     pub struct GlobalSettings {
         pub bitbox: Option<BitboxSettings>,
         pub window_config: Option<WindowConfig>,
+        /// Minimum number of builder signatures required on a bitcoind release before
+        /// installing it.
+        pub bitcoind_min_signatures: Option<usize>,
     }
 
     impl GlobalSettings {
@@ -530,6 +541,21 @@ pub mod global {
             )
         }
 
+        pub fn load_bitcoind_min_signatures(path: &PathBuf) -> Option<usize> {
+            let mut ret = None;
+            if let Err(e) = Self::update(path, |s| ret = s.bitcoind_min_signatures, false) {
+                tracing::error!("Failed to load bitcoind minimum signatures: {e}");
+            }
+            ret
+        }
+
+        pub fn update_bitcoind_min_signatures(
+            path: &PathBuf,
+            min_signatures: Option<usize>,
+        ) -> Result<(), String> {
+            Self::update(path, |s| s.bitcoind_min_signatures = min_signatures, true)
+        }
+
         pub fn load_bitbox_settings(path: &PathBuf) -> Result<Option<BitboxSettings>, String> {
             let mut ret = None;
             Self::update(path, |s| ret = s.bitbox.clone(), false)?;
@@ -582,6 +608,7 @@ pub mod global {
             if !exists
                 && global_settings.bitbox.is_none()
                 && global_settings.window_config.is_none()
+                && global_settings.bitcoind_min_signatures.is_none()
             {
                 write = false;
             }
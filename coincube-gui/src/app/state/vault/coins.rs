@@ -4,8 +4,12 @@ use std::{cmp::Ordering, collections::HashSet};
 
 use iced::Task;
 
+use coincube_core::{
+    miniscript::bitcoin::OutPoint,
+    privacy::{assess_coins, PrivacyAssessment},
+};
 use coincube_ui::widget::Element;
-use coincubed::commands::CoinStatus;
+use coincubed::commands::{CoinStatus, SearchFilter};
 
 use crate::daemon::model::LabelsLoader;
 use crate::{
@@ -14,7 +18,10 @@ use crate::{
         error::Error,
         menu::Menu,
         message::Message,
-        state::{vault::label::LabelsEdited, State},
+        state::{
+            vault::{label::LabelsEdited, search::SearchForm},
+            State,
+        },
         view,
         wallet::Wallet,
     },
@@ -47,11 +54,15 @@ impl Labelled for Coins {
 
 pub struct CoinsPanel {
     coins: Coins,
+    /// The privacy assessment of the coins, against all the coins ever received by the wallet.
+    privacy: HashMap<OutPoint, PrivacyAssessment>,
     selected: Vec<usize>,
     labels_edited: LabelsEdited,
     warning: Option<Error>,
     /// timelock value to pass for the heir to consume a coin.
     timelock: u16,
+    /// Only set if the backend supports searching.
+    search: Option<SearchForm>,
 }
 
 impl CoinsPanel {
@@ -59,14 +70,52 @@ impl CoinsPanel {
         let mut panel = Self {
             labels_edited: LabelsEdited::default(),
             coins: Coins::default(),
+            privacy: HashMap::new(),
             selected: Vec::new(),
             warning: None,
             timelock,
+            search: None,
         };
         panel.update_coins(coins);
         panel
     }
 
+    fn load(&self, daemon: Arc<dyn Daemon + Sync + Send>) -> Task<Message> {
+        let filter = self.search.as_ref().and_then(|s| s.applied.clone());
+        Task::batch(vec![
+            Task::perform(
+                unspent_coins(daemon.clone(), filter.clone()),
+                Message::Coins,
+            ),
+            Task::perform(
+                {
+                    let daemon = daemon.clone();
+                    async move {
+                        daemon
+                            .list_coins(&[], &[])
+                            .await
+                            .map(|res| res.coins)
+                            .map_err(Error::from)
+                    }
+                },
+                Message::CoinsHistory,
+            ),
+            Task::perform(
+                async move {
+                    let coins = unspent_coins(daemon.clone(), filter).await?;
+                    let mut targets = HashSet::<LabelItem>::new();
+                    for coin in coins {
+                        targets.insert(LabelItem::OutPoint(coin.outpoint));
+                        targets.insert(LabelItem::Txid(coin.outpoint.txid));
+                        targets.insert(LabelItem::Address(coin.address));
+                    }
+                    daemon.get_labels(&targets).await.map_err(|e| e.into())
+                },
+                Message::Labels,
+            ),
+        ])
+    }
+
     fn update_coins(&mut self, coins: &[Coin]) {
         self.coins.list = coins
             .iter()
@@ -100,6 +149,8 @@ impl State for CoinsPanel {
             view::vault::coins::coins_view(
                 cache,
                 &self.coins.list,
+                &self.privacy,
+                self.search.as_ref(),
                 self.timelock,
                 &self.selected,
                 &self.coins.labels,
@@ -111,10 +162,17 @@ impl State for CoinsPanel {
     fn update(
         &mut self,
         daemon: Arc<dyn Daemon + Sync + Send>,
-        _cache: &Cache,
+        cache: &Cache,
         message: Message,
     ) -> Task<Message> {
         match message {
+            Message::View(view::Message::Search(msg)) => {
+                if let Some(search) = &mut self.search {
+                    if search.update(cache.network, msg) {
+                        return self.load(daemon);
+                    }
+                }
+            }
             Message::Coins(res) => match res {
                 Err(e) => self.warning = Some(e),
                 Ok(coins) => {
@@ -123,6 +181,17 @@ impl State for CoinsPanel {
                     self.update_coins(&coins);
                 }
             },
+            Message::CoinsHistory(res) => match res {
+                Err(e) => self.warning = Some(e),
+                Ok(coins) => {
+                    let scripts: Vec<_> = coins
+                        .iter()
+                        .map(|c| (c.outpoint, c.address.script_pubkey()))
+                        .collect();
+                    self.privacy =
+                        assess_coins(scripts.iter().map(|(op, spk)| (*op, spk.as_script())));
+                }
+            },
             Message::Labels(res) => match res {
                 Err(e) => self.warning = Some(e),
                 Ok(labels) => {
@@ -158,38 +227,32 @@ impl State for CoinsPanel {
         daemon: Arc<dyn Daemon + Sync + Send>,
         _wallet: Arc<Wallet>,
     ) -> Task<Message> {
-        let daemon1 = daemon.clone();
-        let daemon2 = daemon.clone();
-        Task::batch(vec![
-            Task::perform(
-                async move {
-                    daemon1
-                        .list_coins(&[CoinStatus::Unconfirmed, CoinStatus::Confirmed], &[])
-                        .await
-                        .map(|res| res.coins)
-                        .map_err(|e| e.into())
-                },
-                Message::Coins,
-            ),
-            Task::perform(
-                async move {
-                    let coins = daemon2
-                        .list_coins(&[CoinStatus::Unconfirmed, CoinStatus::Confirmed], &[])
-                        .await
-                        .map(|res| res.coins)
-                        .map_err(Error::from)?;
-                    let mut targets = HashSet::<LabelItem>::new();
-                    for coin in coins {
-                        targets.insert(LabelItem::OutPoint(coin.outpoint));
-                        targets.insert(LabelItem::Txid(coin.outpoint.txid));
-                        targets.insert(LabelItem::Address(coin.address));
-                    }
-                    daemon2.get_labels(&targets).await.map_err(|e| e.into())
-                },
-                Message::Labels,
-            ),
-        ])
+        if daemon.backend().is_coincubed() {
+            self.search.get_or_insert_with(SearchForm::default);
+        } else {
+            self.search = None;
+        }
+        self.load(daemon)
+    }
+}
+
+/// Fetch the unspent coins, restricted to the ones matching the filter if any.
+async fn unspent_coins(
+    daemon: Arc<dyn Daemon + Sync + Send>,
+    filter: Option<SearchFilter>,
+) -> Result<Vec<Coin>, Error> {
+    let statuses = [CoinStatus::Unconfirmed, CoinStatus::Confirmed];
+    match filter {
+        Some(mut filter) => {
+            if filter.statuses.is_empty() {
+                filter.statuses = statuses.to_vec();
+            }
+            daemon.search_coins(&filter).await
+        }
+        None => daemon.list_coins(&statuses, &[]).await,
     }
+    .map(|res| res.coins)
+    .map_err(Error::from)
 }
 
 impl From<CoinsPanel> for Box<dyn State> {
@@ -4,16 +4,21 @@ use std::{
 };
 
 use coincube_ui::{widget::modal::Modal, widget::Element};
+use coincubed::commands::LabelConflict;
 use iced::{Subscription, Task};
-use tokio::task::JoinHandle;
+use tokio::{sync::mpsc::Sender, task::JoinHandle};
 
 use crate::{
     app::{
         self,
         view::{vault::export::export_modal, Close},
     },
+    coldcard,
     daemon::Daemon,
-    export::{self, get_path, ImportExportMessage, ImportExportState, ImportExportType, Progress},
+    export::{
+        self, get_directory, get_path, ImportExportMessage, ImportExportState, ImportExportType,
+        Progress,
+    },
 };
 
 #[derive(Debug)]
@@ -22,6 +27,7 @@ pub struct VaultExportModal {
     handle: Option<Arc<Mutex<JoinHandle<()>>>>,
     state: ImportExportState,
     error: Option<export::Error>,
+    summary: Option<String>,
     daemon: Option<Arc<dyn Daemon + Sync + Send>>,
     import_export_type: ImportExportType,
 }
@@ -65,6 +71,7 @@ impl VaultExportModal {
             handle: None,
             state: ImportExportState::Init,
             error: None,
+            summary: None,
             daemon,
             import_export_type: export_type,
         }
@@ -73,6 +80,7 @@ impl VaultExportModal {
     pub fn modal_title(&self) -> &'static str {
         match self.import_export_type {
             ImportExportType::Transactions => "Export Transactions",
+            ImportExportType::AccountingTransactions(_) => "Export Accounting Report",
             ImportExportType::ExportPsbt(_) => "Export PSBT",
             ImportExportType::ExportXpub(_) => "Export Xpub",
             ImportExportType::ImportXpub(_) => "Import Xpub",
@@ -80,10 +88,17 @@ impl VaultExportModal {
             ImportExportType::ExportEncryptedDescriptor(_) => "Export Encrypted Descriptor",
             ImportExportType::Descriptor(_) => "Export Descriptor",
             ImportExportType::ExportLabels => "Export Labels",
+            ImportExportType::ImportLabels { .. } => "Import Labels",
             ImportExportType::ImportPsbt(_) => "Import PSBT",
             ImportExportType::ImportDescriptor => "Import Descriptor",
             ImportExportType::ImportBackup { .. } => "Restore Backup",
             ImportExportType::FromBackup => "Import existing wallet from backup",
+            ImportExportType::ExportColdcardRegistration(_) => "Export Coldcard Registration",
+            ImportExportType::ExportColdcardPsbt(_) => "Export PSBT to Coldcard",
+            ImportExportType::ImportColdcardSigned(_) => "Import PSBT from Coldcard",
+            ImportExportType::ExportReserveProof(_) => "Export Proof of Reserves",
+            ImportExportType::VerifyReserveProof => "Verify Proof of Reserves",
+            ImportExportType::ExportHeirPackage(_) => "Export Heir Package",
         }
     }
 
@@ -93,6 +108,9 @@ impl VaultExportModal {
             ImportExportType::Transactions => {
                 format!("coincube-txs-{date}.csv")
             }
+            ImportExportType::AccountingTransactions(_) => {
+                format!("coincube-accounting-{date}.csv")
+            }
             ImportExportType::ExportPsbt(_) => "psbt.psbt".into(),
             ImportExportType::ExportXpub(_) | ImportExportType::ImportXpub(_) => {
                 "coincube.pub".into()
@@ -110,16 +128,37 @@ impl VaultExportModal {
             ImportExportType::ImportPsbt(_) => "psbt.psbt".into(),
             ImportExportType::ImportDescriptor => "descriptor.txt".into(),
             ImportExportType::ExportLabels => format!("coincube-labels-{date}.jsonl"),
+            ImportExportType::ImportLabels { .. } => "labels.jsonl".into(),
             ImportExportType::ExportProcessBackup(..) => {
                 format!("coincube-backup-{date}.json")
             }
             ImportExportType::FromBackup | ImportExportType::ImportBackup { .. } => {
                 "coincube-backup.json".to_string()
             }
+            ImportExportType::ExportColdcardRegistration(descriptor) => {
+                format!("{}.txt", coldcard::wallet_name(descriptor))
+            }
+            ImportExportType::ExportColdcardPsbt(psbt) => {
+                coldcard::psbt_filename(&psbt.unsigned_tx.compute_txid())
+            }
+            ImportExportType::ImportColdcardSigned(psbt) => {
+                coldcard::signed_psbt_filename(&psbt.unsigned_tx.compute_txid())
+            }
+            ImportExportType::ExportReserveProof(_) => format!("coincube-reserves-{date}.json"),
+            ImportExportType::VerifyReserveProof => "coincube-reserves.json".into(),
+            ImportExportType::ExportHeirPackage(_) => format!("coincube-heir-package-{date}.bed"),
         }
     }
 
     pub fn launch<M: From<ImportExportMessage> + Send + 'static>(&self, write: bool) -> Task<M> {
+        // The Coldcard expects PSBTs named after its own convention, so we only let the user
+        // pick the directory (usually the SD card) to write it to.
+        if let ImportExportType::ExportColdcardPsbt(_) = self.import_export_type {
+            let filename = self.default_filename();
+            return Task::perform(get_directory(), move |dir| {
+                ImportExportMessage::Path(dir.map(|dir| dir.join(&filename))).into()
+            });
+        }
         Task::perform(get_path(self.default_filename(), write), move |m| {
             ImportExportMessage::Path(m).into()
         })
@@ -171,6 +210,16 @@ impl VaultExportModal {
                         };
                     }
                 }
+                Progress::LabelsImportConflict(sender) => {
+                    if let ImportExportType::ImportLabels { conflict, .. } =
+                        &mut self.import_export_type
+                    {
+                        *conflict = Some(sender);
+                    }
+                }
+                Progress::Summary(summary) => {
+                    self.summary = Some(summary);
+                }
                 Progress::Error(e) => {
                     self.error = Some(e.clone());
                 }
@@ -211,6 +260,11 @@ impl VaultExportModal {
             }
             ImportExportMessage::Close | ImportExportMessage::Open => { /* unreachable */ }
             ImportExportMessage::Overwrite => {
+                if let ImportExportType::ImportLabels { conflict, .. } =
+                    &mut self.import_export_type
+                {
+                    return resolve_labels_conflict(conflict, LabelConflict::Overwrite);
+                }
                 if let ImportExportType::ImportBackup {
                     overwrite_labels,
                     overwrite_aliases,
@@ -242,7 +296,19 @@ impl VaultExportModal {
                     }
                 }
             }
+            ImportExportMessage::Merge => {
+                if let ImportExportType::ImportLabels { conflict, .. } =
+                    &mut self.import_export_type
+                {
+                    return resolve_labels_conflict(conflict, LabelConflict::Merge);
+                }
+            }
             ImportExportMessage::Ignore => {
+                if let ImportExportType::ImportLabels { conflict, .. } =
+                    &mut self.import_export_type
+                {
+                    return resolve_labels_conflict(conflict, LabelConflict::Skip);
+                }
                 if let ImportExportType::ImportBackup {
                     overwrite_labels,
                     overwrite_aliases,
@@ -289,6 +355,7 @@ impl VaultExportModal {
             export_modal(
                 &self.state,
                 self.error.as_ref(),
+                self.summary.as_deref(),
                 self.modal_title(),
                 &self.import_export_type,
             ),
@@ -334,3 +401,24 @@ impl VaultExportModal {
         }
     }
 }
+
+/// Send the user choice to the labels import task waiting for it, if any.
+fn resolve_labels_conflict<M: From<ImportExportMessage> + Send + 'static>(
+    conflict: &mut Option<Sender<LabelConflict>>,
+    resolution: LabelConflict,
+) -> Task<M> {
+    if let Some(sender) = conflict.take() {
+        Task::perform(
+            async move {
+                if sender.send(resolution).await.is_err() {
+                    tracing::error!(
+                        "VaultExportModal.update(): fail to send labels conflict resolution"
+                    );
+                }
+            },
+            |_| ImportExportMessage::Ignore.into(),
+        )
+    } else {
+        Task::none()
+    }
+}
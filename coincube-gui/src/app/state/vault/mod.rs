@@ -5,6 +5,7 @@ pub mod overview;
 pub mod psbt;
 pub mod psbts;
 pub mod receive;
+pub mod search;
 pub mod settings;
 pub mod spend;
 pub mod transactions;
@@ -2,8 +2,11 @@ use std::convert::TryInto;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
-use coincube_core::miniscript::bitcoin::{Amount, OutPoint};
-use coincube_ui::widget::*;
+use coincube_core::{
+    miniscript::bitcoin::{Amount, OutPoint},
+    spend::{parse_feerate_vb, MAX_FEERATE_KVB},
+};
+use coincube_ui::{component::form, widget::*};
 use coincubed::commands::CoinStatus;
 use iced::Task;
 
@@ -17,17 +20,22 @@ use crate::{
     app::state::State,
     app::view,
     app::wallet::{sync_status, SyncStatus, Wallet},
+    services::feeestimation::fee_estimation::FeeEstimator,
 };
 
 pub const HISTORY_EVENT_PAGE_SIZE: u64 = 20;
 const HOME_RELOAD_MAX_TTL: Duration = Duration::from_secs(10);
 const HOME_RELOAD_MIN_TTL: Duration = Duration::from_secs(3);
+/// How long a feerate estimate is used to advise a consolidation before fetching a new one.
+const CONSOLIDATION_FEERATE_TTL: Duration = Duration::from_secs(600);
+/// Default feerate (in sats/vb) the consolidated coins are expected to be spent at.
+const DEFAULT_FUTURE_FEERATE_VB: &str = "50";
 
 use crate::app::state::vault::label::LabelsEdited;
 use crate::daemon::model::LabelsLoader;
 use crate::daemon::{
-    model::{Coin, HistoryTransaction, Payment},
-    Daemon,
+    model::{BalanceHistoryEntry, Coin, ConsolidationAdvice, HistoryTransaction, Payment},
+    Daemon, DaemonError,
 };
 use crate::utils::now;
 
@@ -47,11 +55,19 @@ pub struct VaultOverview {
     unconfirmed_balance: Amount,
     remaining_sequence: Option<u32>,
     expiring_coins: Vec<OutPoint>,
+    coins: Vec<Coin>,
+    balance_history: Vec<BalanceHistoryEntry>,
     payments: Payments,
     processing: bool,
     selected_event: Option<(HistoryTransaction, usize)>,
     labels_edited: LabelsEdited,
 
+    /// The current feerate estimate (in sats/kvb) and when it was fetched.
+    consolidation_feerate: Option<(u64, Instant)>,
+    future_feerate: form::Value<String>,
+    future_feerate_edited: bool,
+    consolidation: Option<ConsolidationAdvice>,
+
     warning: Option<Error>,
     show_rescan_warning: bool,
 }
@@ -77,15 +93,49 @@ impl VaultOverview {
             unconfirmed_balance,
             remaining_sequence: remaining_seq,
             expiring_coins,
+            coins: coins.to_vec(),
+            balance_history: Vec::new(),
             selected_event: None,
             payments: Payments::default(),
             labels_edited: LabelsEdited::default(),
+            consolidation_feerate: None,
+            future_feerate: form::Value {
+                value: DEFAULT_FUTURE_FEERATE_VB.to_string(),
+                warning: None,
+                valid: true,
+            },
+            future_feerate_edited: false,
+            consolidation: None,
             warning: None,
             processing: false,
             show_rescan_warning,
             last_reload: Instant::now(),
         }
     }
+
+    /// Ask the daemon which coins to consolidate at the current feerate estimate, if the
+    /// expected future feerate is higher.
+    fn consolidation_advice(&mut self, daemon: Arc<dyn Daemon + Sync + Send>) -> Task<Message> {
+        let Some((feerate_kvb, _)) = self.consolidation_feerate else {
+            return Task::none();
+        };
+        let future_feerate_kvb = parse_feerate_vb(&self.future_feerate.value)
+            .filter(|f| (feerate_kvb + 1..=MAX_FEERATE_KVB).contains(f));
+        self.future_feerate.valid = future_feerate_kvb.is_some();
+        let Some(future_feerate_kvb) = future_feerate_kvb else {
+            self.consolidation = None;
+            return Task::none();
+        };
+        Task::perform(
+            async move {
+                daemon
+                    .get_consolidation_advice(feerate_kvb, future_feerate_kvb)
+                    .await
+                    .map_err(|e| e.into())
+            },
+            Message::ConsolidationAdvice,
+        )
+    }
 }
 
 impl State for VaultOverview {
@@ -115,6 +165,22 @@ impl State for VaultOverview {
                     self.processing,
                     &self.sync_status,
                     self.show_rescan_warning,
+                    self.consolidation_feerate
+                        .filter(|_| self.consolidation.is_some() || self.future_feerate_edited)
+                        .map(|(feerate_kvb, _)| {
+                            view::vault::overview::consolidation_card(
+                                self.consolidation.as_ref(),
+                                feerate_kvb,
+                                &self.future_feerate,
+                            )
+                        }),
+                    view::vault::charts::analytics_view(
+                        &self.balance_history,
+                        &self.coins,
+                        cache.blockheight() as u32,
+                        self.wallet.main_descriptor.first_timelock_value(),
+                        converter,
+                    ),
                 ),
             )
         }
@@ -152,8 +218,25 @@ impl State for VaultOverview {
                         cache.blockheight() as u32,
                         self.wallet.main_descriptor.first_timelock_value(),
                     );
+                    self.coins = coins;
                 }
             },
+            Message::BalanceHistory(res) => match res {
+                // The history is not available with every backend.
+                Err(Error::Daemon(DaemonError::NotImplemented)) => {}
+                Err(e) => self.warning = Some(e),
+                Ok(history) => self.balance_history = history,
+            },
+            Message::ConsolidationFeerate(feerate) => {
+                self.consolidation_feerate = feerate.map(|f| (f, Instant::now()));
+                return self.consolidation_advice(daemon);
+            }
+            Message::ConsolidationAdvice(res) => match res {
+                // The advice is not available with every backend.
+                Err(Error::Daemon(DaemonError::NotImplemented)) => {}
+                Err(e) => self.warning = Some(e),
+                Ok(advice) => self.consolidation = advice,
+            },
             Message::Payments(res) => match res {
                 Err(e) => self.warning = Some(e),
                 Ok(events) => {
@@ -221,6 +304,11 @@ impl State for VaultOverview {
             Message::View(view::Message::HideRescanWarning) => {
                 self.show_rescan_warning = false;
             }
+            Message::View(view::Message::FutureFeerateEdited(value)) => {
+                self.future_feerate.value = value;
+                self.future_feerate_edited = true;
+                return self.consolidation_advice(daemon);
+            }
             Message::View(view::Message::SelectPayment(outpoint)) => {
                 return Task::perform(
                     async move {
@@ -337,6 +425,20 @@ impl State for VaultOverview {
         self.wallet = wallet;
         self.payments.loaded_page_count = 0;
         let daemon2 = daemon.clone();
+        let daemon3 = daemon.clone();
+        // The feerate estimate is fetched from external services, do not poll them on each
+        // reload.
+        let consolidation = if self
+            .consolidation_feerate
+            .is_some_and(|(_, fetched_at)| fetched_at.elapsed() < CONSOLIDATION_FEERATE_TTL)
+        {
+            self.consolidation_advice(daemon.clone())
+        } else {
+            Task::perform(
+                async move { FeeEstimator::new().get_low_priority_rate().await.ok() },
+                Message::ConsolidationFeerate,
+            )
+        };
         let now: u32 = now().as_secs().try_into().unwrap();
         self.last_reload = Instant::now();
         Task::batch(vec![
@@ -363,6 +465,11 @@ impl State for VaultOverview {
                 },
                 Message::Coins,
             ),
+            Task::perform(
+                async move { daemon3.get_balance_history().await.map_err(|e| e.into()) },
+                Message::BalanceHistory,
+            ),
+            consolidation,
         ])
     }
 }
@@ -1,20 +1,28 @@
 use std::collections::{HashMap, HashSet};
+use std::convert::TryInto;
 use std::sync::Arc;
 
+use chrono::{Local, NaiveDateTime};
 use iced::Subscription;
 
 use coincube_core::{
     descriptors::CoincubePolicy,
-    miniscript::bitcoin::{bip32::Fingerprint, psbt::Psbt, Network, Txid},
+    miniscript::bitcoin::{
+        bip32::Fingerprint, psbt::Psbt, secp256k1, Network, OutPoint, ScriptBuf, Txid,
+    },
+    privacy::{self, PrivacyAssessment},
+    psbtv2,
+    signer::{self, SighashPolicy},
 };
 use coincubed::commands::CoinStatus;
 use iced::Task;
 
-use coincube_ui::component::toast;
+use coincube_ui::component::{form, toast};
 use coincube_ui::{widget::modal, widget::Element};
 
+use crate::bcur::AnimatedQr;
 use crate::daemon::model::LabelsLoader;
-use crate::export::{ImportExportMessage, ImportExportType, Progress};
+use crate::export::{self, ImportExportMessage, ImportExportType, Progress};
 use crate::{
     app::{
         cache::Cache,
@@ -25,7 +33,7 @@ use crate::{
         wallet::{Wallet, WalletError},
     },
     daemon::{
-        model::{LabelItem, Labelled, SpendStatus, SpendTx},
+        model::{LabelItem, Labelled, ScheduledBroadcastEntry, SpendStatus, SpendTx},
         Daemon,
     },
     dir::CoincubeDirectory,
@@ -61,6 +69,7 @@ pub enum PsbtModal {
     Broadcast(BroadcastModal),
     Delete(DeleteModal),
     Export(VaultExportModal),
+    Qr(QrModal),
 }
 
 impl<'a> AsRef<dyn Modal + 'a> for PsbtModal {
@@ -71,6 +80,7 @@ impl<'a> AsRef<dyn Modal + 'a> for PsbtModal {
             Self::Broadcast(a) => a,
             Self::Delete(a) => a,
             Self::Export(a) => a,
+            Self::Qr(a) => a,
         }
     }
 }
@@ -83,6 +93,7 @@ impl<'a> AsMut<dyn Modal + 'a> for PsbtModal {
             Self::Broadcast(a) => a,
             Self::Delete(a) => a,
             Self::Export(a) => a,
+            Self::Qr(a) => a,
         }
     }
 }
@@ -95,6 +106,10 @@ pub struct PsbtState {
     pub warning: Option<Error>,
     pub labels_edited: LabelsEdited,
     pub modal: Option<PsbtModal>,
+    /// The Scripts the coins of the wallet were received on, with the coins left unspent on each.
+    wallet_scripts: HashMap<ScriptBuf, Vec<OutPoint>>,
+    /// The privacy assessment of the spend, if it is yet to be signed and broadcast.
+    privacy: Option<PrivacyAssessment>,
 }
 
 impl PsbtState {
@@ -105,11 +120,28 @@ impl PsbtState {
             labels_edited: LabelsEdited::default(),
             warning: None,
             modal: None,
+            wallet_scripts: HashMap::new(),
+            privacy: None,
             tx,
             saved,
         }
     }
 
+    /// Assess the privacy of the spend against the coins of the wallet. Only the spends yet to be
+    /// signed and broadcast are worth a warning.
+    fn assess_privacy(&mut self, network: Network) {
+        self.privacy = (self.tx.status == SpendStatus::Pending).then(|| {
+            privacy::assess_spend(
+                &self.wallet.main_descriptor,
+                &secp256k1::Secp256k1::verification_only(),
+                &self.tx.psbt,
+                &self.tx.labels,
+                &self.wallet_scripts,
+                network,
+            )
+        });
+    }
+
     pub fn interrupt(&mut self) {
         self.modal = None;
     }
@@ -123,10 +155,23 @@ impl PsbtState {
     }
 
     pub fn load(&self, daemon: Arc<dyn Daemon + Sync + Send>) -> Task<Message> {
+        let coins = {
+            let daemon = daemon.clone();
+            Task::perform(
+                async move {
+                    daemon
+                        .list_coins(&[], &[])
+                        .await
+                        .map(|res| res.coins)
+                        .map_err(|e| e.into())
+                },
+                Message::CoinsHistory,
+            )
+        };
         if let Some(modal) = &self.modal {
-            modal.as_ref().load(daemon)
+            Task::batch(vec![coins, modal.as_ref().load(daemon)])
         } else {
-            Task::none()
+            coins
         }
     }
 
@@ -146,6 +191,15 @@ impl PsbtState {
                     return launch;
                 }
             }
+            Message::View(view::Message::ExportPsbtV2) => {
+                if self.modal.is_none() {
+                    let psbt_str = psbtv2::to_base64_v2(&self.tx.psbt);
+                    let modal = VaultExportModal::new(None, ImportExportType::ExportPsbt(psbt_str));
+                    let launch = modal.launch(true);
+                    self.modal = Some(PsbtModal::Export(modal));
+                    return launch;
+                }
+            }
             Message::View(view::Message::ImportPsbt) => {
                 if self.modal.is_none() {
                     let modal = VaultExportModal::new(
@@ -157,6 +211,38 @@ impl PsbtState {
                     return launch;
                 }
             }
+            Message::View(view::Message::ExportPsbtColdcard) => {
+                if self.modal.is_none() {
+                    let modal = VaultExportModal::new(
+                        None,
+                        ImportExportType::ExportColdcardPsbt(Box::new(self.tx.psbt.clone())),
+                    );
+                    let launch = modal.launch(true);
+                    self.modal = Some(PsbtModal::Export(modal));
+                    return launch;
+                }
+            }
+            Message::View(view::Message::ImportPsbtColdcard) => {
+                if self.modal.is_none() {
+                    let modal = VaultExportModal::new(
+                        Some(daemon.clone()),
+                        ImportExportType::ImportColdcardSigned(Box::new(self.tx.psbt.clone())),
+                    );
+                    let launch = modal.launch(false);
+                    self.modal = Some(PsbtModal::Export(modal));
+                    return launch;
+                }
+            }
+            Message::View(view::Message::ShowPsbtQr) => {
+                if self.modal.is_none() {
+                    match AnimatedQr::psbt(&self.tx.psbt) {
+                        Ok(qr) => self.modal = Some(PsbtModal::Qr(QrModal { qr })),
+                        Err(e) => {
+                            self.warning = Some(Error::ImportExport(export::Error::Ur(e)));
+                        }
+                    }
+                }
+            }
             Message::View(view::Message::ImportExport(ImportExportMessage::Close)) => {
                 if matches!(self.modal, Some(PsbtModal::Export(_))) {
                     self.modal = None;
@@ -198,6 +284,7 @@ impl PsbtState {
                     cache.network,
                     self.saved,
                     self.tx.recovery_timelock(),
+                    !signer::non_standard_sighashes(&self.tx.psbt).is_empty(),
                 );
                 let cmd = modal.load(daemon);
                 self.modal = Some(PsbtModal::Sign(modal));
@@ -205,22 +292,70 @@ impl PsbtState {
             }
             Message::View(view::Message::Spend(view::SpendTxMessage::Broadcast)) => {
                 let outpoints: Vec<_> = self.tx.coins.keys().cloned().collect();
+                let txid = self.tx.psbt.unsigned_tx.compute_txid();
                 return Task::perform(
                     async move {
-                        daemon
+                        let conflicting_txids = daemon
                             .list_coins(&[CoinStatus::Spending], &outpoints)
+                            .await?
+                            .coins
+                            .iter()
+                            .filter_map(|c| c.spend_info.map(|info| info.txid))
+                            .collect();
+                        // Not all backends support scheduling broadcasts.
+                        let schedule = daemon
+                            .list_scheduled_broadcasts()
                             .await
-                            .map(|res| {
-                                res.coins
-                                    .iter()
-                                    .filter_map(|c| c.spend_info.map(|info| info.txid))
-                                    .collect()
-                            })
-                            .map_err(|e| e.into())
+                            .ok()
+                            .and_then(|res| res.broadcasts.into_iter().find(|b| b.txid == txid));
+                        Ok::<_, Error>((conflicting_txids, schedule))
                     },
                     Message::BroadcastModal,
                 );
             }
+            Message::View(view::Message::Spend(view::SpendTxMessage::Approve)) => {
+                let txid = self.tx.psbt.unsigned_tx.compute_txid();
+                return Task::perform(
+                    async move {
+                        daemon.approve_spend_tx(&txid).await?;
+                        let spend_txs = daemon.list_spend_txs().await?.spend_txs;
+                        Ok::<_, Error>(
+                            spend_txs
+                                .into_iter()
+                                .find(|tx| tx.psbt.unsigned_tx.compute_txid() == txid)
+                                .and_then(|tx| tx.approved_at),
+                        )
+                    },
+                    Message::SpendApproved,
+                );
+            }
+            Message::CoinsHistory(res) => match res {
+                Ok(coins) => {
+                    self.wallet_scripts = HashMap::new();
+                    for coin in coins {
+                        let unspent = self
+                            .wallet_scripts
+                            .entry(coin.address.script_pubkey())
+                            .or_default();
+                        if coin.spend_info.is_none() {
+                            unspent.push(coin.outpoint);
+                        }
+                    }
+                    self.assess_privacy(cache.network);
+                }
+                Err(e) => {
+                    self.warning = Some(e);
+                }
+            },
+            Message::SpendApproved(res) => match res {
+                Ok(approved_at) => {
+                    self.warning = None;
+                    self.tx.approved_at = approved_at;
+                }
+                Err(e) => {
+                    self.warning = Some(e);
+                }
+            },
             Message::View(view::Message::Spend(view::SpendTxMessage::Save)) => {
                 self.modal = Some(PsbtModal::Save(SaveModal::default()));
             }
@@ -231,6 +366,8 @@ impl PsbtState {
                     std::iter::once(&mut self.tx).map(|tx| tx as &mut dyn LabelsLoader),
                 ) {
                     Ok(cmd) => {
+                        // The sources of the coins spent may have been relabelled.
+                        self.assess_privacy(cache.network);
                         return cmd;
                     }
                     Err(e) => {
@@ -253,9 +390,10 @@ impl PsbtState {
                 }
             }
             Message::BroadcastModal(res) => match res {
-                Ok(conflicting_txids) => {
+                Ok((conflicting_txids, schedule)) => {
                     self.modal = Some(PsbtModal::Broadcast(BroadcastModal {
                         conflicting_txids,
+                        schedule,
                         ..Default::default()
                     }));
                 }
@@ -270,6 +408,7 @@ impl PsbtState {
                     .main_descriptor
                     .partial_spend_info(&self.tx.psbt)
                     .expect("already check in psbt import logic");
+                self.assess_privacy(cache.network);
             }
             _ => {
                 if let Some(modal) = self.modal.as_mut() {
@@ -294,6 +433,7 @@ impl PsbtState {
             } else {
                 false
             },
+            self.privacy.as_ref(),
             self.warning.as_ref(),
         );
         if let Some(modal) = &self.modal {
@@ -359,6 +499,43 @@ pub struct BroadcastModal {
     error: Option<Error>,
     /// IDs of any directly conflicting transactions.
     conflicting_txids: HashSet<Txid>,
+    /// The block height from which to broadcast the transaction later.
+    schedule_block_height: form::Value<String>,
+    /// The local date and time from which to broadcast the transaction later.
+    schedule_date: form::Value<String>,
+    /// The current broadcast schedule of the transaction, if any.
+    schedule: Option<ScheduledBroadcastEntry>,
+}
+
+/// Format of the date and time inputs to schedule a broadcast, in local time.
+const SCHEDULE_DATE_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+impl BroadcastModal {
+    /// Parse the schedule inputs, marking the invalid ones. Returns `None` if any input is
+    /// invalid or if both are empty.
+    fn parse_schedule(&mut self) -> Option<(Option<i32>, Option<u32>)> {
+        let height = Some(self.schedule_block_height.value.trim())
+            .filter(|v| !v.is_empty())
+            .map(|v| v.parse::<i32>().ok().filter(|h| *h > 0));
+        self.schedule_block_height.valid = !matches!(height, Some(None));
+        let timestamp = Some(self.schedule_date.value.trim())
+            .filter(|v| !v.is_empty())
+            .map(|v| {
+                NaiveDateTime::parse_from_str(v, SCHEDULE_DATE_FORMAT)
+                    .ok()
+                    .and_then(|dt| dt.and_local_timezone(Local).earliest())
+                    .and_then(|dt| dt.timestamp().try_into().ok())
+            });
+        self.schedule_date.valid = !matches!(timestamp, Some(None));
+        if !self.schedule_block_height.valid || !self.schedule_date.valid {
+            return None;
+        }
+        let (height, timestamp) = (height.flatten(), timestamp.flatten());
+        if height.is_none() && timestamp.is_none() {
+            return None;
+        }
+        Some((height, timestamp))
+    }
 }
 
 impl Modal for BroadcastModal {
@@ -390,6 +567,57 @@ impl Modal for BroadcastModal {
                 }
                 Err(e) => self.error = Some(e),
             },
+            Message::View(view::Message::Spend(
+                view::SpendTxMessage::ScheduleBlockHeightEdited(height),
+            )) => {
+                self.schedule_block_height.value = height;
+                self.schedule_block_height.valid = true;
+            }
+            Message::View(view::Message::Spend(view::SpendTxMessage::ScheduleDateEdited(date))) => {
+                self.schedule_date.value = date;
+                self.schedule_date.valid = true;
+            }
+            Message::View(view::Message::Spend(view::SpendTxMessage::ScheduleBroadcast)) => {
+                if let Some((block_height, timestamp)) = self.parse_schedule() {
+                    let txid = tx.psbt.unsigned_tx.compute_txid();
+                    self.error = None;
+                    return Task::perform(
+                        async move {
+                            daemon
+                                .schedule_broadcast_spend_tx(&txid, block_height, timestamp)
+                                .await?;
+                            Ok::<_, Error>(
+                                daemon
+                                    .list_scheduled_broadcasts()
+                                    .await?
+                                    .broadcasts
+                                    .into_iter()
+                                    .find(|b| b.txid == txid),
+                            )
+                        },
+                        Message::BroadcastScheduled,
+                    );
+                }
+            }
+            Message::View(view::Message::Spend(view::SpendTxMessage::CancelScheduledBroadcast)) => {
+                let txid = tx.psbt.unsigned_tx.compute_txid();
+                self.error = None;
+                return Task::perform(
+                    async move {
+                        daemon.cancel_scheduled_broadcast(&txid).await?;
+                        Ok::<_, Error>(None)
+                    },
+                    Message::BroadcastScheduled,
+                );
+            }
+            Message::BroadcastScheduled(res) => match res {
+                Ok(schedule) => {
+                    self.schedule = schedule;
+                    self.schedule_block_height = form::Value::default();
+                    self.schedule_date = form::Value::default();
+                }
+                Err(e) => self.error = Some(e),
+            },
             _ => {}
         }
         Task::none()
@@ -399,6 +627,9 @@ impl Modal for BroadcastModal {
             content,
             view::vault::psbt::broadcast_action(
                 &self.conflicting_txids,
+                &self.schedule_block_height,
+                &self.schedule_date,
+                self.schedule.as_ref(),
                 self.error.as_ref(),
                 self.broadcast,
             ),
@@ -408,6 +639,42 @@ impl Modal for BroadcastModal {
     }
 }
 
+pub struct QrModal {
+    qr: AnimatedQr,
+}
+
+impl Modal for QrModal {
+    fn subscription(&self) -> Subscription<Message> {
+        iced::time::every(std::time::Duration::from_millis(250))
+            .map(|_| Message::View(view::Message::NextQrFrame))
+    }
+
+    fn update(
+        &mut self,
+        _daemon: Arc<dyn Daemon + Sync + Send>,
+        message: Message,
+        _tx: &mut SpendTx,
+    ) -> Task<Message> {
+        if let Message::View(view::Message::NextQrFrame) = message {
+            self.qr.next_frame();
+        }
+        Task::none()
+    }
+
+    fn view<'a>(&'a self, content: Element<'a, view::Message>) -> Element<'a, view::Message> {
+        modal::Modal::new(
+            content,
+            view::vault::export::ur_qr_modal(
+                &self.qr,
+                "PSBT QR code",
+                view::Message::Spend(view::SpendTxMessage::Cancel),
+            ),
+        )
+        .on_blur(Some(view::Message::Spend(view::SpendTxMessage::Cancel)))
+        .into()
+    }
+}
+
 #[derive(Default)]
 pub struct DeleteModal {
     deleted: bool,
@@ -463,6 +730,8 @@ pub struct SignModal {
     is_saved: bool,
     display_modal: bool,
     recovery_timelock: Option<u16>,
+    non_standard_sighash: bool,
+    allow_non_standard_sighash: bool,
 }
 
 impl SignModal {
@@ -473,6 +742,7 @@ impl SignModal {
         network: Network,
         is_saved: bool,
         recovery_timelock: Option<u16>,
+        non_standard_sighash: bool,
     ) -> Self {
         Self {
             signing: HashSet::new(),
@@ -483,6 +753,8 @@ impl SignModal {
             is_saved,
             display_modal: true,
             recovery_timelock,
+            non_standard_sighash,
+            allow_non_standard_sighash: false,
         }
     }
 
@@ -521,11 +793,21 @@ impl Modal for SignModal {
                 }
             }
             Message::View(view::Message::Spend(view::SpendTxMessage::SelectHotSigner)) => {
+                let sighash_policy = if self.allow_non_standard_sighash {
+                    SighashPolicy::AllowNonStandard
+                } else {
+                    SighashPolicy::AllOnly
+                };
                 return Task::perform(
-                    sign_psbt_with_hot_signer(self.wallet.clone(), tx.psbt.clone()),
+                    sign_psbt_with_hot_signer(self.wallet.clone(), tx.psbt.clone(), sighash_policy),
                     |(fg, res)| Message::Signed(fg, res),
                 );
             }
+            Message::View(view::Message::Spend(view::SpendTxMessage::AllowNonStandardSighash(
+                allow,
+            ))) => {
+                self.allow_non_standard_sighash = allow;
+            }
             Message::Signed(fingerprint, res) => {
                 self.signing.remove(&fingerprint);
                 match res {
@@ -611,6 +893,8 @@ impl Modal for SignModal {
                     &self.signed,
                     &self.signing,
                     self.recovery_timelock,
+                    self.non_standard_sighash,
+                    self.allow_non_standard_sighash,
                 ),
             )
             .on_blur(Some(view::Message::Spend(view::SpendTxMessage::Cancel)))
@@ -621,7 +905,7 @@ impl Modal for SignModal {
     }
 }
 
-fn merge_signatures(psbt: &mut Psbt, signed_psbt: &Psbt) {
+pub fn merge_signatures(psbt: &mut Psbt, signed_psbt: &Psbt) {
     for i in 0..signed_psbt.inputs.len() {
         let psbtin = match psbt.inputs.get_mut(i) {
             Some(psbtin) => psbtin,
@@ -643,13 +927,14 @@ fn merge_signatures(psbt: &mut Psbt, signed_psbt: &Psbt) {
     }
 }
 
-async fn sign_psbt_with_hot_signer(
+pub async fn sign_psbt_with_hot_signer(
     wallet: Arc<Wallet>,
     psbt: Psbt,
+    sighash_policy: SighashPolicy,
 ) -> (Fingerprint, Result<Psbt, Error>) {
     if let Some(signer) = &wallet.signer {
         let res = signer
-            .sign_psbt(psbt)
+            .sign_psbt_with_policy(psbt, sighash_policy)
             .map_err(|e| WalletError::HotSigner(format!("Hot signer failed to sign psbt: {}", e)))
             .map_err(|e| e.into());
         (signer.fingerprint(), res)
@@ -661,7 +946,7 @@ async fn sign_psbt_with_hot_signer(
     }
 }
 
-async fn sign_psbt(
+pub async fn sign_psbt(
     wallet: Arc<Wallet>,
     hw: std::sync::Arc<dyn async_hwi::HWI + Send + Sync>,
     mut psbt: Psbt,
@@ -731,6 +1016,7 @@ mod tests {
                     "descriptors": { "main": CoincubeDescriptor::from_str(DESC).unwrap() },
                     "receive_index": 4,
                     "change_index": 3,
+                    "gap_limit": 200,
                     "timestamp": 1000,
                 })),
             ),
@@ -768,6 +1054,21 @@ mod tests {
                 ]]})),
                 Ok(json!({ "labels": {}})),
             ),
+            (
+                Some(
+                    json!({"method": "listcoins", "params": vec![Vec::<String>::new(), Vec::new()]}),
+                ),
+                Ok(json!({ "coins": [{
+                    "amount": 10000,
+                    "outpoint": "fa5bf9974ae9ab08b64da24f17e80a5689540a7e73335faea05bbc563bfe31cd:1",
+                    "address": "TB1QJEQFLW8E3RN60MC6X6C2ECE3EKUFV4XEYTJTU35HWPCTRMGN4W0S3DCXH5",
+                    "block_height": 200949,
+                    "derivation_index": 0,
+                    "is_immature": false,
+                    "is_change": false,
+                    "is_from_self": false,
+                }]})),
+            ),
             (
                 Some(json!({"method": "updatespend", "params": vec![vec![json!({})]]})),
                 Ok(json!({})),
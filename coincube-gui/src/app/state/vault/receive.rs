@@ -129,6 +129,7 @@ impl State for VaultReceivePanel {
                 self.labels_edited.cache(),
                 self.prev_continue_from.is_none(),
                 self.processing,
+                cache.coins(),
             ),
         );
 
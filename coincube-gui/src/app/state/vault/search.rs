@@ -0,0 +1,189 @@
+use std::convert::TryInto;
+use std::str::FromStr;
+
+use chrono::{NaiveDate, NaiveTime};
+use coincube_core::miniscript::bitcoin::{Address, Amount, Denomination, Network};
+use coincube_ui::component::form;
+use coincubed::commands::{CoinStatus, SearchFilter};
+
+use crate::app::view::SearchMessage;
+
+/// The search bar on top of the coins and transactions lists.
+#[derive(Debug, Default)]
+pub struct SearchForm {
+    pub open: bool,
+    pub label: form::Value<String>,
+    pub min_amount: form::Value<String>,
+    pub max_amount: form::Value<String>,
+    pub start: form::Value<String>,
+    pub end: form::Value<String>,
+    pub address: form::Value<String>,
+    pub txid_prefix: form::Value<String>,
+    pub status: Option<CoinStatus>,
+    /// The filter the list is currently restricted to, if any.
+    pub applied: Option<SearchFilter>,
+}
+
+impl SearchForm {
+    /// Update the form and return whether the applied filter changed, in which case the list
+    /// must be reloaded.
+    pub fn update(&mut self, network: Network, message: SearchMessage) -> bool {
+        match message {
+            SearchMessage::Toggle => {
+                self.open = !self.open;
+                false
+            }
+            SearchMessage::FieldEdited(field, value) => {
+                let value = form::Value {
+                    value,
+                    warning: None,
+                    valid: true,
+                };
+                match field {
+                    "label" => self.label = value,
+                    "min_amount" => self.min_amount = value,
+                    "max_amount" => self.max_amount = value,
+                    "start" => self.start = value,
+                    "end" => self.end = value,
+                    "address" => self.address = value,
+                    "txid_prefix" => self.txid_prefix = value,
+                    _ => {}
+                }
+                false
+            }
+            SearchMessage::StatusSelected(status) => {
+                self.status = status;
+                false
+            }
+            SearchMessage::Apply => match self.filter(network) {
+                Some(filter) => {
+                    let filter = (filter != SearchFilter::default()).then_some(filter);
+                    let changed = filter != self.applied;
+                    self.applied = filter;
+                    changed
+                }
+                None => false,
+            },
+            SearchMessage::Clear => {
+                let changed = self.applied.is_some();
+                *self = Self {
+                    open: self.open,
+                    ..Self::default()
+                };
+                changed
+            }
+        }
+    }
+
+    /// Parse the form into a filter, marking the invalid fields. Returns `None` if any is.
+    fn filter(&mut self, network: Network) -> Option<SearchFilter> {
+        fn non_empty(value: &form::Value<String>) -> Option<&str> {
+            Some(value.value.trim()).filter(|v| !v.is_empty())
+        }
+        fn amount(value: &mut form::Value<String>) -> Option<Amount> {
+            let amount =
+                non_empty(value).map(|v| Amount::from_str_in(v, Denomination::Bitcoin).ok());
+            value.valid = !matches!(amount, Some(None));
+            amount.flatten()
+        }
+        fn date(value: &mut form::Value<String>, time: NaiveTime) -> Option<u32> {
+            let timestamp = non_empty(value).map(|v| {
+                NaiveDate::parse_from_str(v, "%Y-%m-%d")
+                    .ok()
+                    .and_then(|d| d.and_time(time).and_utc().timestamp().try_into().ok())
+            });
+            value.valid = !matches!(timestamp, Some(None));
+            timestamp.flatten()
+        }
+
+        let min_amount = amount(&mut self.min_amount);
+        let max_amount = amount(&mut self.max_amount);
+        let start = date(&mut self.start, NaiveTime::MIN);
+        let end = date(
+            &mut self.end,
+            NaiveTime::from_hms_opt(23, 59, 59).expect("Valid time"),
+        );
+        let address = non_empty(&self.address).map(|a| {
+            Address::from_str(a)
+                .ok()
+                .and_then(|a| a.require_network(network).ok())
+        });
+        self.address.valid = !matches!(address, Some(None));
+        let txid_prefix = non_empty(&self.txid_prefix).map(|p| p.to_lowercase());
+        self.txid_prefix.valid = txid_prefix
+            .as_ref()
+            .map(|p| p.chars().all(|c| c.is_ascii_hexdigit()))
+            .unwrap_or(true);
+
+        if !(self.min_amount.valid
+            && self.max_amount.valid
+            && self.start.valid
+            && self.end.valid
+            && self.address.valid
+            && self.txid_prefix.valid)
+        {
+            return None;
+        }
+
+        Some(SearchFilter {
+            label: non_empty(&self.label).map(|l| l.to_string()),
+            min_amount,
+            max_amount,
+            start,
+            end,
+            statuses: self.status.into_iter().collect(),
+            address: address.flatten(),
+            txid_prefix,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edit(form: &mut SearchForm, field: &'static str, value: &str) {
+        form.update(
+            Network::Bitcoin,
+            SearchMessage::FieldEdited(field, value.to_string()),
+        );
+    }
+
+    #[test]
+    fn search_form() {
+        let mut form = SearchForm::default();
+        // An empty form doesn't filter anything.
+        assert!(!form.update(Network::Bitcoin, SearchMessage::Apply));
+        assert!(form.applied.is_none());
+
+        edit(&mut form, "label", " rent ");
+        edit(&mut form, "min_amount", "0.1");
+        edit(&mut form, "start", "2024-03-01");
+        edit(&mut form, "end", "2024-03-31");
+        edit(&mut form, "txid_prefix", "ABcd");
+        assert!(form.update(Network::Bitcoin, SearchMessage::Apply));
+        let filter = form.applied.clone().unwrap();
+        assert_eq!(filter.label.as_deref(), Some("rent"));
+        assert_eq!(filter.min_amount, Some(Amount::from_sat(10_000_000)));
+        assert_eq!(filter.max_amount, None);
+        assert_eq!(filter.start, Some(1_709_251_200));
+        assert_eq!(filter.end, Some(1_711_929_599));
+        assert_eq!(filter.txid_prefix.as_deref(), Some("abcd"));
+
+        // Invalid fields are flagged and the applied filter is kept.
+        edit(&mut form, "max_amount", "lots");
+        edit(
+            &mut form,
+            "address",
+            "tb1qnsexk3gnuyayu92fc3tczvc7k62u22a22ua2kv",
+        );
+        assert!(!form.update(Network::Bitcoin, SearchMessage::Apply));
+        assert!(!form.max_amount.valid);
+        assert!(!form.address.valid);
+        assert_eq!(form.applied, Some(filter));
+
+        assert!(form.update(Network::Bitcoin, SearchMessage::Clear));
+        assert!(form.applied.is_none());
+        assert!(form.label.value.is_empty());
+    }
+}
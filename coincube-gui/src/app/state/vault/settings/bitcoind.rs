@@ -5,8 +5,8 @@ use std::str::FromStr;
 use std::sync::Arc;
 
 use chrono::{NaiveDate, Utc};
-use iced::{clipboard, Task};
-use tracing::info;
+use iced::{clipboard, Subscription, Task};
+use tracing::{info, warn};
 
 use coincube_core::miniscript::bitcoin::Network;
 use coincubed::config::{
@@ -17,13 +17,19 @@ use coincube_ui::{component::form, widget::Element};
 
 use crate::{
     app::{
-        cache::Cache, error::Error, menu::Menu, message::Message, state::vault::settings::State,
-        view,
+        cache::Cache, error::Error, menu::Menu, message::Message, settings::global::GlobalSettings,
+        state::vault::settings::State, view,
     },
     daemon::Daemon,
-    help,
+    dir::CoincubeDirectory,
+    download, help,
     node::{
-        bitcoind::{RpcAuthType, RpcAuthValues},
+        bitcoind::{
+            self, installed_version, internal_bitcoind_config_path, internal_bitcoind_datadir,
+            upgradable_versions, BitcoindUpgrade, InternalBitcoindConfig,
+            InternalBitcoindNetworkConfig, RpcAuthType, RpcAuthValues,
+        },
+        release::{self, ReleaseChecksums},
         NodeType,
     },
 };
@@ -35,6 +41,7 @@ pub struct BitcoindSettingsState {
 
     bitcoind_settings: Option<BitcoindSettings>,
     electrum_settings: Option<ElectrumSettings>,
+    managed_bitcoind_settings: Option<ManagedBitcoindSettings>,
     rescan_settings: RescanSetting,
 }
 
@@ -83,6 +90,14 @@ impl BitcoindSettingsState {
                     daemon_is_external,
                 )
             }),
+            managed_bitcoind_settings: if bitcoind_is_internal && !daemon_is_external {
+                Some(ManagedBitcoindSettings::new(
+                    cache.datadir_path.clone(),
+                    cache.network,
+                ))
+            } else {
+                None
+            },
             rescan_settings: RescanSetting::new(cache.rescan_progress()),
         }
     }
@@ -160,11 +175,28 @@ impl State for BitcoindSettingsState {
             Message::View(view::Message::Settings(view::SettingsMessage::RescanSettings(msg))) => {
                 return self.rescan_settings.update(daemon, cache, msg);
             }
+            Message::View(view::Message::Settings(view::SettingsMessage::ManagedBitcoind(msg))) => {
+                if let Some(settings) = &mut self.managed_bitcoind_settings {
+                    return settings.update(msg);
+                }
+            }
+            Message::InternalBitcoindUpdated(res) => {
+                if let Some(settings) = &mut self.managed_bitcoind_settings {
+                    settings.updated(res);
+                }
+            }
             _ => {}
         };
         Task::none()
     }
 
+    fn subscription(&self) -> Subscription<Message> {
+        self.managed_bitcoind_settings
+            .as_ref()
+            .map(|settings| settings.subscription())
+            .unwrap_or_else(Subscription::none)
+    }
+
     fn view<'a>(&'a self, menu: &'a Menu, cache: &'a Cache) -> Element<'a, view::Message> {
         let can_edit_bitcoind_settings =
             self.bitcoind_settings.is_some() && !self.rescan_settings.processing;
@@ -201,6 +233,13 @@ impl State for BitcoindSettingsState {
                         },
                     ))
                 }
+                if let Some(settings) = self.managed_bitcoind_settings.as_ref() {
+                    setting_panels.push(settings.view(!self.rescan_settings.processing).map(
+                        move |msg| {
+                            view::Message::Settings(view::SettingsMessage::ManagedBitcoind(msg))
+                        },
+                    ))
+                }
                 setting_panels.push(view::vault::settings::link(
                     help::CHANGE_BACKEND_OR_NODE_URL,
                     "I want to change node type or use Liana Connect",
@@ -525,6 +564,335 @@ impl ElectrumSettings {
     }
 }
 
+/// Download of a new managed bitcoind version along with its release checksums.
+#[derive(Debug)]
+pub struct BitcoindUpgradeDownload {
+    pub version: &'static str,
+    pub progress: f32,
+    pub bytes: Option<Vec<u8>>,
+    pub checksums: Option<ReleaseChecksums>,
+}
+
+/// Settings of the bitcoind managed by Coincube, written to its `bitcoin.conf`.
+#[derive(Debug)]
+pub struct ManagedBitcoindSettings {
+    datadir: CoincubeDirectory,
+    network: Network,
+    config: Option<InternalBitcoindNetworkConfig>,
+    installed_version: Option<&'static str>,
+    edit: bool,
+    processing: bool,
+    prune: form::Value<String>,
+    dbcache: form::Value<String>,
+    max_connections: form::Value<String>,
+    listen: bool,
+    min_signatures: form::Value<String>,
+    selected_version: Option<&'static str>,
+    download: Option<BitcoindUpgradeDownload>,
+    error: Option<String>,
+}
+
+impl ManagedBitcoindSettings {
+    fn new(datadir: CoincubeDirectory, network: Network) -> Self {
+        let mut settings = Self {
+            datadir,
+            network,
+            config: None,
+            installed_version: None,
+            edit: false,
+            processing: false,
+            prune: form::Value::default(),
+            dbcache: form::Value::default(),
+            max_connections: form::Value::default(),
+            listen: true,
+            min_signatures: form::Value::default(),
+            selected_version: None,
+            download: None,
+            error: None,
+        };
+        settings.load();
+        settings
+    }
+
+    fn config_path(&self) -> PathBuf {
+        internal_bitcoind_config_path(&internal_bitcoind_datadir(&self.datadir))
+    }
+
+    /// Load the current values from the bitcoind configuration file.
+    fn load(&mut self) {
+        self.installed_version = installed_version(&self.datadir);
+        self.selected_version = self
+            .installed_version
+            .and_then(|v| upgradable_versions(v).first().copied());
+        match InternalBitcoindConfig::from_file(&self.config_path()) {
+            Ok(conf) => self.config = conf.networks.get(&self.network).cloned(),
+            Err(e) => {
+                warn!("Failed to read managed bitcoind configuration: {}", e);
+                self.error = Some(e.to_string());
+            }
+        }
+        self.reset_form();
+    }
+
+    fn reset_form(&mut self) {
+        let value = |v: String| form::Value {
+            value: v,
+            warning: None,
+            valid: true,
+        };
+        if let Some(conf) = &self.config {
+            self.prune = value(conf.prune.to_string());
+            self.dbcache = value(conf.dbcache.map(|v| v.to_string()).unwrap_or_default());
+            self.max_connections = value(
+                conf.max_connections
+                    .map(|v| v.to_string())
+                    .unwrap_or_default(),
+            );
+            self.listen = conf.listen.unwrap_or(true);
+        }
+        self.min_signatures = value(release::min_valid_signatures(&self.datadir).to_string());
+    }
+
+    fn updated(&mut self, res: Result<(), Error>) {
+        self.processing = false;
+        self.download = None;
+        match res {
+            Ok(()) => {
+                self.edit = false;
+                self.error = None;
+                self.load();
+            }
+            Err(e) => {
+                self.error = Some(e.to_string());
+                // The configuration file may have been written even though the restart failed.
+                self.load();
+            }
+        }
+    }
+
+    /// Parse the form into a new configuration, marking invalid fields.
+    fn new_config(&mut self) -> Option<InternalBitcoindNetworkConfig> {
+        let mut conf = self.config.clone()?;
+        fn parse_optional(value: &mut form::Value<String>) -> Result<Option<u32>, ()> {
+            if value.value.is_empty() {
+                return Ok(None);
+            }
+            let res = u32::from_str(&value.value).map(Some).map_err(|_| ());
+            value.valid = res.is_ok();
+            res
+        }
+        match u32::from_str(&self.prune.value) {
+            Ok(prune) => conf.prune = prune,
+            Err(_) => self.prune.valid = false,
+        }
+        conf.dbcache = parse_optional(&mut self.dbcache).unwrap_or(conf.dbcache);
+        conf.max_connections =
+            parse_optional(&mut self.max_connections).unwrap_or(conf.max_connections);
+        conf.listen = Some(self.listen);
+        if !self.prune.valid || !self.dbcache.valid || !self.max_connections.valid {
+            return None;
+        }
+        if let Err(e) = conf.validate() {
+            self.error = Some(e.to_string());
+            return None;
+        }
+        if self.config.as_ref().is_some_and(|c| c.prune != 0) && conf.prune == 0 {
+            self.error = Some(
+                "Pruning cannot be disabled on a pruned node without a full resync.".to_string(),
+            );
+            return None;
+        }
+        Some(conf)
+    }
+
+    /// Parse the minimum number of builder signatures required on a release, which can't be
+    /// more than the number of bundled builder keys.
+    fn new_min_signatures(&mut self) -> Option<usize> {
+        let max = release::builder_keyring().map(|keys| keys.len()).ok();
+        let res = usize::from_str(&self.min_signatures.value)
+            .ok()
+            .filter(|n| *n > 0 && max.is_none_or(|max| *n <= max));
+        self.min_signatures.valid = res.is_some();
+        res
+    }
+
+    fn update(&mut self, message: view::ManagedBitcoindMessage) -> Task<Message> {
+        match message {
+            view::ManagedBitcoindMessage::Edit => {
+                if !self.processing {
+                    self.edit = true;
+                }
+            }
+            view::ManagedBitcoindMessage::Cancel => {
+                if !self.processing {
+                    self.edit = false;
+                    self.error = None;
+                    self.reset_form();
+                }
+            }
+            view::ManagedBitcoindMessage::FieldEdited(field, value) => {
+                if !self.processing {
+                    match field {
+                        "prune" => self.prune.value = value,
+                        "dbcache" => self.dbcache.value = value,
+                        "max_connections" => self.max_connections.value = value,
+                        "min_signatures" => self.min_signatures.value = value,
+                        _ => {}
+                    }
+                    self.prune.valid = true;
+                    self.dbcache.valid = true;
+                    self.max_connections.valid = true;
+                    self.min_signatures.valid = true;
+                }
+            }
+            view::ManagedBitcoindMessage::ListenToggled(listen) => {
+                if !self.processing {
+                    self.listen = listen;
+                }
+            }
+            view::ManagedBitcoindMessage::Save => {
+                self.error = None;
+                let min_signatures = self.new_min_signatures();
+                let Some(new_config) = self.new_config() else {
+                    return Task::none();
+                };
+                let Some(min_signatures) = min_signatures else {
+                    return Task::none();
+                };
+                if let Err(e) = GlobalSettings::update_bitcoind_min_signatures(
+                    &GlobalSettings::path(&self.datadir),
+                    Some(min_signatures),
+                ) {
+                    self.error = Some(e);
+                    return Task::none();
+                }
+                // The signatures threshold only applies to the next installation, bitcoind only
+                // needs to be restarted if its own configuration changed.
+                if self.config.as_ref() == Some(&new_config) {
+                    self.edit = false;
+                    self.load();
+                    return Task::none();
+                }
+                let path = self.config_path();
+                let res = InternalBitcoindConfig::from_file(&path).and_then(|mut conf| {
+                    conf.networks.insert(self.network, new_config);
+                    conf.to_file(&path)
+                });
+                if let Err(e) = res {
+                    self.error = Some(e.to_string());
+                    return Task::none();
+                }
+                self.processing = true;
+                return Task::done(Message::RestartInternalBitcoind(None));
+            }
+            view::ManagedBitcoindMessage::UpgradeVersionSelected(version) => {
+                if !self.processing {
+                    self.selected_version = Some(version);
+                }
+            }
+            view::ManagedBitcoindMessage::Upgrade => {
+                if let (false, Some(version)) = (self.processing, self.selected_version) {
+                    info!("Downloading bitcoind version {}...", version);
+                    self.error = None;
+                    self.processing = true;
+                    self.download = Some(BitcoindUpgradeDownload {
+                        version,
+                        progress: 0.0,
+                        bytes: None,
+                        checksums: None,
+                    });
+                    return Task::perform(bitcoind::fetch_release_checksums(version), |res| {
+                        Message::View(view::Message::Settings(
+                            view::SettingsMessage::ManagedBitcoind(
+                                view::ManagedBitcoindMessage::ChecksumsFetched(res),
+                            ),
+                        ))
+                    });
+                }
+            }
+            view::ManagedBitcoindMessage::DownloadProgressed(progress) => {
+                if let Some(download) = &mut self.download {
+                    match progress {
+                        Ok(download::Progress::Downloading(p)) => download.progress = p,
+                        Ok(download::Progress::Finished(bytes)) => {
+                            download.progress = 100.0;
+                            download.bytes = Some(bytes);
+                        }
+                        Err(e) => {
+                            self.error = Some(format!("Download failed: {}", e));
+                            self.download = None;
+                            self.processing = false;
+                        }
+                    }
+                }
+                return self.maybe_upgrade();
+            }
+            view::ManagedBitcoindMessage::ChecksumsFetched(res) => {
+                if let Some(download) = &mut self.download {
+                    match res {
+                        Ok(checksums) => download.checksums = Some(checksums),
+                        Err(e) => {
+                            self.error = Some(e);
+                            self.download = None;
+                            self.processing = false;
+                        }
+                    }
+                }
+                return self.maybe_upgrade();
+            }
+        }
+        Task::none()
+    }
+
+    /// Once both the release and its checksums are downloaded, ask for the node to be upgraded.
+    fn maybe_upgrade(&mut self) -> Task<Message> {
+        if matches!(&self.download, Some(d) if d.bytes.is_some() && d.checksums.is_some()) {
+            let download = self.download.take().expect("checked above");
+            return Task::done(Message::RestartInternalBitcoind(Some(BitcoindUpgrade {
+                version: download.version,
+                checksums: download.checksums.expect("checked above"),
+                bytes: download.bytes.expect("checked above"),
+            })));
+        }
+        Task::none()
+    }
+
+    fn subscription(&self) -> Subscription<Message> {
+        match &self.download {
+            Some(download) if download.bytes.is_none() => {
+                download::file(download.version, bitcoind::download_url(download.version)).map(
+                    |(_, progress)| {
+                        Message::View(view::Message::Settings(
+                            view::SettingsMessage::ManagedBitcoind(
+                                view::ManagedBitcoindMessage::DownloadProgressed(progress),
+                            ),
+                        ))
+                    },
+                )
+            }
+            _ => Subscription::none(),
+        }
+    }
+
+    fn view(&self, can_edit: bool) -> Element<view::ManagedBitcoindMessage> {
+        view::vault::settings::managed_bitcoind(
+            self.installed_version,
+            self.config.as_ref(),
+            self.edit,
+            self.processing,
+            can_edit,
+            &self.prune,
+            &self.dbcache,
+            &self.max_connections,
+            self.listen,
+            &self.min_signatures,
+            self.selected_version,
+            self.download.as_ref().map(|d| (d.version, d.progress)),
+            self.error.as_ref(),
+        )
+    }
+}
+
 #[derive(Debug, Default)]
 pub struct RescanSetting {
     processing: bool,
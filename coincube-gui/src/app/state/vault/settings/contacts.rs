@@ -0,0 +1,254 @@
+use std::sync::Arc;
+
+use chrono::{Local, NaiveDate};
+use coincube_core::miniscript::bitcoin::Network;
+use coincube_ui::{component::form, widget::Element};
+use iced::Task;
+
+use crate::{
+    app::{
+        cache::Cache,
+        error::Error,
+        menu::Menu,
+        message::Message,
+        settings::{
+            contacts::{Contact, Destination},
+            update_settings_file,
+        },
+        state::State,
+        view::{self, ContactsMessage},
+        wallet::Wallet,
+    },
+    daemon::Daemon,
+    dir::CoincubeDirectory,
+};
+
+/// Save the address book of the wallet in the settings file.
+pub async fn update_contacts(
+    data_dir: CoincubeDirectory,
+    network: Network,
+    wallet: Arc<Wallet>,
+    contacts: Vec<Contact>,
+) -> Result<Arc<Wallet>, Error> {
+    let wallet = wallet.as_ref().clone().with_contacts(contacts.clone());
+    let network_dir = data_dir.network_directory(network);
+    let wallet_id = wallet.id();
+    update_settings_file(&network_dir, |mut settings| {
+        if let Some(wallet_setting) = settings
+            .wallets
+            .iter_mut()
+            .find(|w| w.wallet_id() == wallet_id)
+        {
+            wallet_setting.contacts = contacts;
+        }
+        Some(settings)
+    })
+    .await?;
+    Ok(Arc::new(wallet))
+}
+
+/// The form to add or edit a contact.
+#[derive(Debug, Default)]
+pub struct ContactForm {
+    /// Index of the edited contact, `None` for a new one.
+    pub index: Option<usize>,
+    pub name: form::Value<String>,
+    /// Addresses or descriptors, separated by spaces.
+    pub destinations: form::Value<String>,
+    pub note: form::Value<String>,
+    pub verified_on: form::Value<String>,
+    next_index: u32,
+}
+
+impl ContactForm {
+    fn edit(index: usize, contact: &Contact) -> Self {
+        let value = |value: &str| form::Value {
+            value: value.to_string(),
+            warning: None,
+            valid: true,
+        };
+        Self {
+            index: Some(index),
+            name: value(&contact.name),
+            destinations: value(&contact.destinations.join(" ")),
+            note: value(&contact.note),
+            verified_on: value(contact.verified_on.as_deref().unwrap_or_default()),
+            next_index: contact.next_index,
+        }
+    }
+
+    /// Parse the form into a contact, marking the invalid fields. Returns `None` if any is.
+    fn contact(&mut self, network: Network) -> Option<Contact> {
+        let name = self.name.value.trim();
+        self.name.valid = !name.is_empty();
+        let destinations: Vec<String> = self
+            .destinations
+            .value
+            .split_whitespace()
+            .map(|d| d.to_string())
+            .collect();
+        self.destinations.valid = !destinations.is_empty()
+            && destinations
+                .iter()
+                .all(|d| Destination::parse(d, network).is_some());
+        let verified_on = Some(self.verified_on.value.trim()).filter(|v| !v.is_empty());
+        self.verified_on.valid =
+            verified_on.is_none_or(|v| NaiveDate::parse_from_str(v, "%Y-%m-%d").is_ok());
+
+        (self.name.valid && self.destinations.valid && self.verified_on.valid).then(|| Contact {
+            name: name.to_string(),
+            destinations,
+            note: self.note.value.trim().to_string(),
+            verified_on: verified_on.map(|v| v.to_string()),
+            next_index: self.next_index,
+        })
+    }
+}
+
+pub struct ContactsSettingsState {
+    data_dir: CoincubeDirectory,
+    wallet: Arc<Wallet>,
+    form: Option<ContactForm>,
+    processing: bool,
+    warning: Option<Error>,
+}
+
+impl ContactsSettingsState {
+    pub fn new(data_dir: CoincubeDirectory, wallet: Arc<Wallet>) -> Self {
+        Self {
+            data_dir,
+            wallet,
+            form: None,
+            processing: false,
+            warning: None,
+        }
+    }
+
+    fn save(&mut self, cache: &Cache, contacts: Vec<Contact>) -> Task<Message> {
+        self.processing = true;
+        let data_dir = self.data_dir.clone();
+        let network = cache.network;
+        let wallet = self.wallet.clone();
+        Task::perform(
+            async move { update_contacts(data_dir, network, wallet, contacts).await },
+            Message::WalletUpdated,
+        )
+    }
+}
+
+impl From<ContactsSettingsState> for Box<dyn State> {
+    fn from(s: ContactsSettingsState) -> Box<dyn State> {
+        Box::new(s)
+    }
+}
+
+impl State for ContactsSettingsState {
+    fn view<'a>(&'a self, menu: &'a Menu, cache: &'a Cache) -> Element<'a, view::Message> {
+        view::vault::settings::contacts::contacts_section(
+            menu,
+            cache,
+            self.warning.as_ref(),
+            &self.wallet.contacts,
+            self.form.as_ref(),
+            self.processing,
+        )
+    }
+
+    fn reload(
+        &mut self,
+        _daemon: Arc<dyn Daemon + Sync + Send>,
+        wallet: Arc<Wallet>,
+    ) -> Task<Message> {
+        self.wallet = wallet;
+        self.form = None;
+        Task::none()
+    }
+
+    fn update(
+        &mut self,
+        _daemon: Arc<dyn Daemon + Sync + Send>,
+        cache: &Cache,
+        message: Message,
+    ) -> Task<Message> {
+        match message {
+            Message::WalletUpdated(res) => {
+                self.processing = false;
+                match res {
+                    Ok(wallet) => {
+                        self.warning = None;
+                        self.form = None;
+                        self.wallet = wallet;
+                    }
+                    Err(e) => self.warning = Some(e),
+                }
+            }
+            Message::View(view::Message::Settings(view::SettingsMessage::Contacts(msg))) => {
+                match msg {
+                    ContactsMessage::New => {
+                        self.form = Some(ContactForm::default());
+                    }
+                    ContactsMessage::Edit(i) => {
+                        if let Some(contact) = self.wallet.contacts.get(i) {
+                            self.form = Some(ContactForm::edit(i, contact));
+                        }
+                    }
+                    ContactsMessage::Delete(i) => {
+                        if !self.processing && i < self.wallet.contacts.len() {
+                            let mut contacts = self.wallet.contacts.clone();
+                            contacts.remove(i);
+                            return self.save(cache, contacts);
+                        }
+                    }
+                    ContactsMessage::FieldEdited(field, value) => {
+                        if let Some(form) = &mut self.form {
+                            let value = form::Value {
+                                value,
+                                warning: None,
+                                valid: true,
+                            };
+                            match field {
+                                "name" => form.name = value,
+                                "destinations" => form.destinations = value,
+                                "note" => form.note = value,
+                                "verified_on" => form.verified_on = value,
+                                _ => {}
+                            }
+                        }
+                    }
+                    ContactsMessage::VerifiedToday => {
+                        if let Some(form) = &mut self.form {
+                            form.verified_on = form::Value {
+                                value: Local::now().format("%Y-%m-%d").to_string(),
+                                warning: None,
+                                valid: true,
+                            };
+                        }
+                    }
+                    ContactsMessage::Cancel => {
+                        self.form = None;
+                    }
+                    ContactsMessage::Save => {
+                        if self.processing {
+                            return Task::none();
+                        }
+                        let Some(form) = &mut self.form else {
+                            return Task::none();
+                        };
+                        let index = form.index;
+                        if let Some(contact) = form.contact(cache.network) {
+                            let mut contacts = self.wallet.contacts.clone();
+                            match index.and_then(|i| contacts.get_mut(i)) {
+                                Some(c) => *c = contact,
+                                None => contacts.push(contact),
+                            }
+                            contacts.sort_by_key(|c| c.name.to_lowercase());
+                            return self.save(cache, contacts);
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+        Task::none()
+    }
+}
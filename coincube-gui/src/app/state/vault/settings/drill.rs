@@ -0,0 +1,400 @@
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use coincube_core::miniscript::bitcoin::{bip32::Fingerprint, psbt::Psbt, Network};
+use coincube_ui::{component::form, widget::Element};
+use iced::{Subscription, Task};
+
+use crate::{
+    app::{
+        cache::Cache,
+        error::Error,
+        menu::Menu,
+        message::Message,
+        state::{
+            vault::psbt::{merge_signatures, sign_psbt},
+            State,
+        },
+        view::{self, vault::settings::drill::KeyStatus, DrillMessage},
+        wallet::{Wallet, WalletError},
+    },
+    daemon::Daemon,
+    dir::CoincubeDirectory,
+    drill::{
+        drill_descriptor, start_drill, Drill, DrillKeys, DEFAULT_NODE_ADDRESS, DRILL_FEERATE_KVB,
+    },
+    hw::{HardwareWallet, HardwareWallets},
+};
+
+/// Rehearse the recovery of the wallet: clone its policy into a drill wallet on a regtest node,
+/// fund it and mine blocks past the timelock of a recovery path, then create, sign and broadcast
+/// the recovery transaction, reporting which keys could not sign.
+pub struct DrillSettingsState {
+    data_dir: CoincubeDirectory,
+    wallet: Arc<Wallet>,
+    keys: DrillKeys,
+    timelock: u16,
+    node_address: form::Value<String>,
+    cookie_path: form::Value<String>,
+    drill: Option<Arc<Drill>>,
+    /// The wallet of the drill descriptor, for the hardware wallets to sign with.
+    drill_wallet: Option<Arc<Wallet>>,
+    hws: HardwareWallets,
+    /// The recovery transaction of the drill being signed.
+    psbt: Option<Psbt>,
+    signing: HashSet<Fingerprint>,
+    signed: HashSet<Fingerprint>,
+    /// The errors of the keys which failed to sign.
+    key_errors: HashMap<Fingerprint, String>,
+    processing: bool,
+    /// Whether the recovery transaction of the drill was confirmed.
+    done: bool,
+    warning: Option<Error>,
+}
+
+impl DrillSettingsState {
+    pub fn new(data_dir: CoincubeDirectory, wallet: Arc<Wallet>) -> Self {
+        let timelock = wallet
+            .main_descriptor
+            .policy()
+            .recovery_paths()
+            .keys()
+            .next()
+            .copied()
+            .expect("A policy has at least one recovery path");
+        Self {
+            hws: HardwareWallets::new(data_dir.clone(), Network::Regtest),
+            data_dir,
+            wallet,
+            keys: DrillKeys::Same,
+            timelock,
+            node_address: form::Value {
+                value: DEFAULT_NODE_ADDRESS.to_string(),
+                warning: None,
+                valid: true,
+            },
+            cookie_path: form::Value::default(),
+            drill: None,
+            drill_wallet: None,
+            psbt: None,
+            signing: HashSet::new(),
+            signed: HashSet::new(),
+            key_errors: HashMap::new(),
+            processing: false,
+            done: false,
+            warning: None,
+        }
+    }
+
+    fn start(&mut self) -> Task<Message> {
+        let node_address = SocketAddr::from_str(self.node_address.value.trim()).ok();
+        self.node_address.valid = node_address.is_some();
+        let cookie_path = PathBuf::from(self.cookie_path.value.trim());
+        self.cookie_path.valid = cookie_path.is_file();
+        let Some(node_address) = node_address.filter(|_| self.cookie_path.valid) else {
+            return Task::none();
+        };
+
+        let (descriptor, signers) =
+            match drill_descriptor(&self.wallet.main_descriptor, self.keys, Network::Regtest) {
+                Ok(res) => res,
+                Err(e) => {
+                    self.warning = Some(e.into());
+                    return Task::none();
+                }
+            };
+        let mut signers: Vec<_> = signers.into_iter().map(Arc::new).collect();
+        if self.keys == DrillKeys::Same {
+            // The hot key of the wallet is part of the drill wallet.
+            signers.extend(self.wallet.signer.clone());
+        }
+        let drill_wallet = Arc::new(
+            Wallet::new(descriptor.clone()).with_key_aliases(self.wallet.keys_aliases.clone()),
+        );
+        self.hws = HardwareWallets::new(self.data_dir.clone(), Network::Regtest)
+            .with_wallet(drill_wallet.clone());
+        self.drill_wallet = Some(drill_wallet);
+
+        self.processing = true;
+        self.warning = None;
+        Task::perform(
+            start_drill(
+                self.data_dir.clone(),
+                descriptor,
+                signers,
+                self.timelock,
+                node_address,
+                cookie_path,
+            ),
+            |res| Message::DrillStarted(res.map(Arc::new).map_err(|e| e.into())),
+        )
+    }
+
+    fn end(&mut self) {
+        self.drill = None;
+        self.drill_wallet = None;
+        self.hws = HardwareWallets::new(self.data_dir.clone(), Network::Regtest);
+        self.psbt = None;
+        self.signing.clear();
+        self.signed.clear();
+        self.key_errors.clear();
+        self.processing = false;
+        self.done = false;
+    }
+
+    /// Whether enough keys signed the recovery transaction to broadcast it.
+    fn is_signed(&self) -> bool {
+        match (&self.drill, &self.psbt) {
+            (Some(drill), Some(psbt)) => drill
+                .descriptor
+                .partial_spend_info(psbt)
+                .ok()
+                .and_then(|info| {
+                    info.recovery_paths()
+                        .get(&drill.timelock)
+                        .map(|path| path.sigs_count >= path.threshold)
+                })
+                .unwrap_or(false),
+            _ => false,
+        }
+    }
+
+    /// The keys of the drilled recovery path, and whether they signed or could sign.
+    fn keys_report(&self) -> Vec<(Fingerprint, KeyStatus)> {
+        let Some(drill) = &self.drill else {
+            return Vec::new();
+        };
+        let (_, origins) =
+            drill.descriptor.policy().recovery_paths()[&drill.timelock].thresh_origins();
+        let mut keys: Vec<_> = origins.into_keys().collect();
+        keys.sort();
+        keys.into_iter()
+            .map(|fg| {
+                let status = if self.signed.contains(&fg) {
+                    KeyStatus::Signed
+                } else if let Some(e) = self.key_errors.get(&fg) {
+                    KeyStatus::Failed(e.clone())
+                } else if drill.signers.iter().any(|s| s.fingerprint() == fg) {
+                    KeyStatus::HotKey
+                } else if self.hws.list.iter().any(|hw| {
+                    matches!(hw, HardwareWallet::Supported { fingerprint, .. } if *fingerprint == fg)
+                }) {
+                    KeyStatus::DeviceConnected
+                } else {
+                    KeyStatus::Missing
+                };
+                (fg, status)
+            })
+            .collect()
+    }
+}
+
+impl From<DrillSettingsState> for Box<dyn State> {
+    fn from(s: DrillSettingsState) -> Box<dyn State> {
+        Box::new(s)
+    }
+}
+
+impl State for DrillSettingsState {
+    fn view<'a>(&'a self, menu: &'a Menu, cache: &'a Cache) -> Element<'a, view::Message> {
+        view::vault::settings::drill::drill_section(
+            menu,
+            cache,
+            self.warning.as_ref(),
+            &self.wallet,
+            self.keys,
+            self.timelock,
+            &self.node_address,
+            &self.cookie_path,
+            self.drill.as_deref(),
+            &self.hws.list,
+            self.keys_report(),
+            self.psbt.is_some(),
+            &self.signed,
+            &self.signing,
+            self.is_signed(),
+            self.done,
+            self.processing,
+        )
+    }
+
+    fn subscription(&self) -> Subscription<Message> {
+        if self.psbt.is_some() && !self.done {
+            return self.hws.refresh().map(Message::HardwareWallets);
+        }
+        Subscription::none()
+    }
+
+    fn update(
+        &mut self,
+        _daemon: Arc<dyn Daemon + Sync + Send>,
+        _cache: &Cache,
+        message: Message,
+    ) -> Task<Message> {
+        match message {
+            Message::DrillStarted(res) => {
+                self.processing = false;
+                match res {
+                    Ok(drill) => self.drill = Some(drill),
+                    Err(e) => self.warning = Some(e),
+                }
+            }
+            Message::DrillPsbt(res) => {
+                self.processing = false;
+                match res {
+                    Ok(psbt) => self.psbt = Some(psbt),
+                    Err(e) => self.warning = Some(e),
+                }
+            }
+            Message::DrillBroadcast(res) => {
+                self.processing = false;
+                match res {
+                    Ok(()) => self.done = true,
+                    Err(e) => self.warning = Some(e),
+                }
+            }
+            Message::HardwareWallets(msg) => match self.hws.update(msg) {
+                Ok(cmd) => return cmd.map(Message::HardwareWallets),
+                Err(e) => self.warning = Some(e.into()),
+            },
+            Message::View(view::Message::SelectHardwareWallet(i)) => {
+                if let (
+                    Some(HardwareWallet::Supported {
+                        fingerprint,
+                        device,
+                        ..
+                    }),
+                    Some(psbt),
+                    Some(wallet),
+                ) = (self.hws.list.get(i), &self.psbt, &self.drill_wallet)
+                {
+                    self.signing.insert(*fingerprint);
+                    self.key_errors.remove(fingerprint);
+                    let fingerprint = *fingerprint;
+                    return Task::perform(
+                        sign_psbt(wallet.clone(), device.clone(), psbt.clone()),
+                        move |res| Message::Signed(fingerprint, res),
+                    );
+                }
+            }
+            Message::Signed(fingerprint, res) => {
+                self.signing.remove(&fingerprint);
+                match res {
+                    Ok(signed_psbt) => {
+                        self.signed.insert(fingerprint);
+                        if let Some(psbt) = self.psbt.as_mut() {
+                            merge_signatures(psbt, &signed_psbt);
+                        }
+                    }
+                    Err(e) => {
+                        self.key_errors.insert(fingerprint, e.to_string());
+                    }
+                }
+            }
+            Message::View(view::Message::Settings(view::SettingsMessage::Drill(msg))) => {
+                match msg {
+                    DrillMessage::KeysSelected(keys) => {
+                        if self.drill.is_none() {
+                            self.keys = keys;
+                        }
+                    }
+                    DrillMessage::TimelockSelected(timelock) => {
+                        if self.drill.is_none() {
+                            self.timelock = timelock;
+                        }
+                    }
+                    DrillMessage::FieldEdited(field, value) => {
+                        let value = form::Value {
+                            value,
+                            warning: None,
+                            valid: true,
+                        };
+                        match field {
+                            "node_address" => self.node_address = value,
+                            "cookie_path" => self.cookie_path = value,
+                            _ => {}
+                        }
+                    }
+                    DrillMessage::Start => {
+                        if self.drill.is_none() && !self.processing {
+                            return self.start();
+                        }
+                    }
+                    DrillMessage::CreateRecovery => {
+                        if let Some(drill) = self.drill.clone() {
+                            self.processing = true;
+                            self.warning = None;
+                            return Task::perform(
+                                async move {
+                                    let address = drill
+                                        .with_node(|node| node.new_address())
+                                        .await
+                                        .map_err(Error::from)?
+                                        .as_unchecked()
+                                        .clone();
+                                    drill
+                                        .daemon
+                                        .create_recovery(
+                                            address,
+                                            &[],
+                                            DRILL_FEERATE_KVB,
+                                            Some(drill.timelock),
+                                        )
+                                        .await
+                                        .map_err(Error::from)
+                                },
+                                Message::DrillPsbt,
+                            );
+                        }
+                    }
+                    DrillMessage::SignWithHotKeys => {
+                        if let (Some(drill), Some(psbt)) = (&self.drill, &self.psbt) {
+                            match drill.sign_with_hot_signers(psbt.clone()) {
+                                Ok(signed_psbt) => {
+                                    for signer in &drill.signers {
+                                        if drill.descriptor.contains_fingerprint_in_path(
+                                            signer.fingerprint(),
+                                            Some(drill.timelock),
+                                        ) {
+                                            self.signed.insert(signer.fingerprint());
+                                        }
+                                    }
+                                    if let Some(psbt) = self.psbt.as_mut() {
+                                        merge_signatures(psbt, &signed_psbt);
+                                    }
+                                }
+                                Err(e) => {
+                                    self.warning = Some(
+                                        WalletError::HotSigner(format!(
+                                            "Hot signer failed to sign psbt: {}",
+                                            e
+                                        ))
+                                        .into(),
+                                    );
+                                }
+                            }
+                        }
+                    }
+                    DrillMessage::Broadcast => {
+                        if let (Some(drill), Some(psbt)) = (self.drill.clone(), self.psbt.clone()) {
+                            if self.is_signed() && !self.processing {
+                                self.processing = true;
+                                self.warning = None;
+                                return Task::perform(
+                                    async move { drill.broadcast(&psbt).await.map_err(Error::from) },
+                                    Message::DrillBroadcast,
+                                );
+                            }
+                        }
+                    }
+                    DrillMessage::End => self.end(),
+                }
+            }
+            _ => {}
+        }
+        Task::none()
+    }
+}
@@ -0,0 +1,317 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use coincube_core::{
+    miniscript::bitcoin::{Address, Network},
+    signer::SighashPolicy,
+    spend::{parse_feerate_vb, MAX_FEERATE_KVB, MIN_FEERATE_KVB},
+};
+use coincube_ui::{component::form, widget::Element};
+use coincubed::commands::CoinStatus;
+use iced::{Subscription, Task};
+
+use crate::{
+    app::{
+        cache::Cache,
+        error::Error,
+        menu::Menu,
+        message::Message,
+        settings::update_settings_file,
+        state::{vault::export::VaultExportModal, State},
+        view::{self, HeirMessage},
+        wallet::{Wallet, WalletError},
+    },
+    daemon::{model::Coin, Daemon},
+    dir::CoincubeDirectory,
+    export::{ImportExportMessage, ImportExportType, Progress},
+    heir::{HeirPackage, HeirPackageRecord},
+    utils::now,
+};
+
+/// Save the record of the last exported heir package in the settings file.
+pub async fn update_heir_package(
+    data_dir: CoincubeDirectory,
+    network: Network,
+    wallet: Arc<Wallet>,
+    record: HeirPackageRecord,
+) -> Result<Arc<Wallet>, Error> {
+    let wallet = wallet
+        .as_ref()
+        .clone()
+        .with_heir_package(Some(record.clone()));
+    let network_dir = data_dir.network_directory(network);
+    let wallet_id = wallet.id();
+    update_settings_file(&network_dir, |mut settings| {
+        if let Some(wallet_setting) = settings
+            .wallets
+            .iter_mut()
+            .find(|w| w.wallet_id() == wallet_id)
+        {
+            wallet_setting.heir_package = Some(record);
+        }
+        Some(settings)
+    })
+    .await?;
+    Ok(Arc::new(wallet))
+}
+
+/// Prepare an heir package: a recovery transaction per recovery path of the wallet, spending all
+/// its confirmed coins to the heir address, signed with the hot signer if any and exported in an
+/// encrypted backup along with the instructions for the heir.
+pub struct HeirSettingsState {
+    data_dir: CoincubeDirectory,
+    wallet: Arc<Wallet>,
+    address: form::Value<String>,
+    feerate: form::Value<String>,
+    instructions: form::Value<String>,
+    /// The coins of the wallet, to tell whether the last exported package went stale.
+    coins: Vec<Coin>,
+    /// The record of the package being exported, saved once the export ended.
+    exporting: Option<HeirPackageRecord>,
+    modal: Option<VaultExportModal>,
+    processing: bool,
+    warning: Option<Error>,
+}
+
+impl HeirSettingsState {
+    pub fn new(data_dir: CoincubeDirectory, wallet: Arc<Wallet>) -> Self {
+        Self {
+            data_dir,
+            wallet,
+            address: form::Value::default(),
+            feerate: form::Value::default(),
+            instructions: form::Value::default(),
+            coins: Vec::new(),
+            exporting: None,
+            modal: None,
+            processing: false,
+            warning: None,
+        }
+    }
+
+    fn create(&mut self, daemon: Arc<dyn Daemon + Sync + Send>, cache: &Cache) -> Task<Message> {
+        let address = Address::from_str(self.address.value.trim())
+            .ok()
+            .filter(|a| a.is_valid_for_network(cache.network));
+        self.address.valid = address.is_some();
+        let feerate_kvb = parse_feerate_vb(&self.feerate.value)
+            .filter(|f| (MIN_FEERATE_KVB..=MAX_FEERATE_KVB).contains(f));
+        self.feerate.valid = feerate_kvb.is_some();
+        let (Some(address), Some(feerate_kvb)) = (address, feerate_kvb) else {
+            return Task::none();
+        };
+
+        self.processing = true;
+        self.warning = None;
+        let wallet = self.wallet.clone();
+        let network = cache.network;
+        let tip_height = cache.blockheight();
+        let instructions = self.instructions.value.trim().to_string();
+        Task::perform(
+            async move {
+                let timelocks: Vec<u16> = wallet
+                    .main_descriptor
+                    .policy()
+                    .recovery_paths()
+                    .keys()
+                    .copied()
+                    .collect();
+                let mut psbts = Vec::with_capacity(timelocks.len());
+                for timelock in timelocks {
+                    let mut psbt = daemon
+                        .create_heir_recovery(address.clone(), feerate_kvb, timelock)
+                        .await?;
+                    if let Some(signer) = &wallet.signer {
+                        psbt = signer
+                            .sign_psbt_with_policy(psbt, SighashPolicy::AllOnly)
+                            .map_err(|e| {
+                                WalletError::HotSigner(format!(
+                                    "Hot signer failed to sign psbt: {}",
+                                    e
+                                ))
+                            })?;
+                    }
+                    psbts.push((timelock, psbt));
+                }
+                let coins = daemon
+                    .list_coins(&[CoinStatus::Confirmed], &[])
+                    .await?
+                    .coins;
+                let wallet_timestamp = daemon.get_info().await?.timestamp;
+                Ok::<_, Error>(Box::new(HeirPackage {
+                    descriptor: wallet.main_descriptor.clone(),
+                    network,
+                    keys: wallet
+                        .keys()
+                        .into_iter()
+                        .map(|(fg, setting)| (fg, setting.to_backup()))
+                        .collect::<BTreeMap<_, _>>(),
+                    instructions,
+                    psbts,
+                    coins,
+                    tip_height,
+                    wallet_timestamp,
+                    created_at: now().as_secs(),
+                }))
+            },
+            Message::HeirPackage,
+        )
+    }
+}
+
+impl From<HeirSettingsState> for Box<dyn State> {
+    fn from(s: HeirSettingsState) -> Box<dyn State> {
+        Box::new(s)
+    }
+}
+
+impl State for HeirSettingsState {
+    fn view<'a>(&'a self, menu: &'a Menu, cache: &'a Cache) -> Element<'a, view::Message> {
+        let record = self.wallet.heir_package.as_ref();
+        let content = view::vault::settings::heir::heir_section(
+            menu,
+            cache,
+            self.warning.as_ref(),
+            record,
+            record
+                .map(|r| r.moved_coins(&self.coins))
+                .unwrap_or_default(),
+            record
+                .map(|r| r.uncovered_coins(&self.coins))
+                .unwrap_or_default(),
+            &self.address,
+            &self.feerate,
+            &self.instructions,
+            self.processing,
+        );
+        if let Some(modal) = &self.modal {
+            modal.view(content)
+        } else {
+            content
+        }
+    }
+
+    fn subscription(&self) -> Subscription<Message> {
+        if let Some(modal) = &self.modal {
+            if let Some(sub) = modal.subscription() {
+                return sub.map(|m| {
+                    Message::View(view::Message::Settings(
+                        view::SettingsMessage::ImportExport(ImportExportMessage::Progress(m)),
+                    ))
+                });
+            }
+        }
+        Subscription::none()
+    }
+
+    fn reload(
+        &mut self,
+        daemon: Arc<dyn Daemon + Sync + Send>,
+        wallet: Arc<Wallet>,
+    ) -> Task<Message> {
+        self.wallet = wallet;
+        Task::perform(
+            async move {
+                daemon
+                    .list_coins(
+                        &[
+                            CoinStatus::Unconfirmed,
+                            CoinStatus::Confirmed,
+                            CoinStatus::Spending,
+                        ],
+                        &[],
+                    )
+                    .await
+                    .map(|res| res.coins)
+                    .map_err(|e| e.into())
+            },
+            Message::Coins,
+        )
+    }
+
+    fn update(
+        &mut self,
+        daemon: Arc<dyn Daemon + Sync + Send>,
+        cache: &Cache,
+        message: Message,
+    ) -> Task<Message> {
+        match message {
+            Message::Coins(res) => match res {
+                Ok(coins) => self.coins = coins,
+                Err(e) => self.warning = Some(e),
+            },
+            Message::HeirPackage(res) => {
+                self.processing = false;
+                match res {
+                    Ok(package) => {
+                        if self.modal.is_none() {
+                            self.exporting = Some(package.record());
+                            let modal = VaultExportModal::new(
+                                Some(daemon),
+                                ImportExportType::ExportHeirPackage(package),
+                            );
+                            let launch = modal.launch(true);
+                            self.modal = Some(modal);
+                            return launch;
+                        }
+                    }
+                    Err(e) => self.warning = Some(e),
+                }
+            }
+            Message::WalletUpdated(res) => match res {
+                Ok(wallet) => self.wallet = wallet,
+                Err(e) => self.warning = Some(e),
+            },
+            Message::View(view::Message::ImportExport(ImportExportMessage::Close)) => {
+                self.modal = None;
+                self.exporting = None;
+            }
+            Message::View(view::Message::ImportExport(m))
+            | Message::View(view::Message::Settings(view::SettingsMessage::ImportExport(m))) => {
+                if matches!(m, ImportExportMessage::Progress(Progress::Ended)) {
+                    if let Some(record) = self.exporting.take() {
+                        let save = Task::perform(
+                            update_heir_package(
+                                self.data_dir.clone(),
+                                cache.network,
+                                self.wallet.clone(),
+                                record,
+                            ),
+                            Message::WalletUpdated,
+                        );
+                        if let Some(modal) = self.modal.as_mut() {
+                            return Task::batch([modal.update(m), save]);
+                        }
+                        return save;
+                    }
+                }
+                if let Some(modal) = self.modal.as_mut() {
+                    return modal.update(m);
+                }
+            }
+            Message::View(view::Message::Settings(view::SettingsMessage::Heir(msg))) => match msg {
+                HeirMessage::FieldEdited(field, value) => {
+                    let value = form::Value {
+                        value,
+                        warning: None,
+                        valid: true,
+                    };
+                    match field {
+                        "address" => self.address = value,
+                        "feerate" => self.feerate = value,
+                        "instructions" => self.instructions = value,
+                        _ => {}
+                    }
+                }
+                HeirMessage::Create => {
+                    if !self.processing && self.modal.is_none() {
+                        return self.create(daemon, cache);
+                    }
+                }
+            },
+            _ => {}
+        }
+        Task::none()
+    }
+}
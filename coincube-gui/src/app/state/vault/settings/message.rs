@@ -0,0 +1,441 @@
+use std::collections::HashSet;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use coincube_core::{
+    bip322::{self, ReserveCoin, ReserveProof, SignatureFormat},
+    miniscript::bitcoin::{bip32::Fingerprint, psbt::Psbt, secp256k1, Address, TxOut},
+    signer::SighashPolicy,
+};
+use coincube_ui::{
+    component::{form, toast},
+    widget::{modal, Element},
+};
+use coincubed::commands::CoinStatus;
+use iced::{Subscription, Task};
+
+use crate::{
+    app::{
+        cache::Cache,
+        error::Error,
+        menu::Menu,
+        message::Message,
+        state::{
+            vault::{
+                export::VaultExportModal,
+                psbt::{merge_signatures, sign_psbt, sign_psbt_with_hot_signer},
+            },
+            State,
+        },
+        view::{self, MessageProofKind, SignMessageMessage},
+        wallet::Wallet,
+    },
+    daemon::{model::Coin, Daemon},
+    dir::CoincubeDirectory,
+    export::{ImportExportMessage, ImportExportType},
+    hw::{HardwareWallet, HardwareWallets},
+};
+
+/// Sign messages for our addresses following BIP-0322, produce proofs of reserves over a set of
+/// our coins and verify such signatures and proofs.
+pub struct MessageSettingsState {
+    wallet: Arc<Wallet>,
+    hws: HardwareWallets,
+    kind: MessageProofKind,
+    address: form::Value<String>,
+    message: form::Value<String>,
+    coins: Vec<Coin>,
+    selected_coins: HashSet<usize>,
+    /// The `to_sign` PSBT being signed.
+    psbt: Option<Psbt>,
+    signing: HashSet<Fingerprint>,
+    signed: HashSet<Fingerprint>,
+    signature: Option<String>,
+    verify_address: form::Value<String>,
+    verify_message: form::Value<String>,
+    verify_signature: form::Value<String>,
+    verified: Option<Result<SignatureFormat, bip322::Bip322Error>>,
+    modal: Option<VaultExportModal>,
+    warning: Option<Error>,
+}
+
+impl MessageSettingsState {
+    pub fn new(data_dir: CoincubeDirectory, wallet: Arc<Wallet>, cache: &Cache) -> Self {
+        Self {
+            hws: HardwareWallets::new(data_dir, cache.network).with_wallet(wallet.clone()),
+            wallet,
+            kind: MessageProofKind::Simple,
+            address: form::Value::default(),
+            message: form::Value::default(),
+            coins: Vec::new(),
+            selected_coins: HashSet::new(),
+            psbt: None,
+            signing: HashSet::new(),
+            signed: HashSet::new(),
+            signature: None,
+            verify_address: form::Value::default(),
+            verify_message: form::Value::default(),
+            verify_signature: form::Value::default(),
+            verified: None,
+            modal: None,
+            warning: None,
+        }
+    }
+
+    fn format(&self) -> SignatureFormat {
+        match self.kind {
+            MessageProofKind::Simple => SignatureFormat::Simple,
+            MessageProofKind::Full | MessageProofKind::Reserves => SignatureFormat::Full,
+        }
+    }
+
+    fn selected_coins(&self) -> Vec<&Coin> {
+        self.coins
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| self.selected_coins.contains(i))
+            .map(|(_, c)| c)
+            .collect()
+    }
+
+    fn reset_signature(&mut self) {
+        self.psbt = None;
+        self.signing.clear();
+        self.signed.clear();
+        self.signature = None;
+    }
+
+    // Try to finalize the signed PSBT. It may fail if more signatures are needed.
+    fn try_finalize(&mut self) {
+        if let Some(psbt) = &self.psbt {
+            let secp = secp256k1::Secp256k1::verification_only();
+            if let Ok(signature) = bip322::finalize(psbt.clone(), self.format(), &secp) {
+                self.signature = Some(signature);
+            }
+        }
+    }
+
+    fn reserve_proof(&self, cache: &Cache) -> Option<ReserveProof> {
+        let coins = self.selected_coins();
+        let first = coins.first()?;
+        Some(ReserveProof {
+            message: self.message.value.clone(),
+            address: first.address.as_unchecked().clone(),
+            network: cache.network,
+            coins: coins
+                .iter()
+                .map(|c| ReserveCoin {
+                    outpoint: c.outpoint,
+                    amount: c.amount,
+                    script_pubkey: c.address.script_pubkey(),
+                })
+                .collect(),
+            proof: self.signature.clone()?,
+        })
+    }
+
+    fn verify(&mut self, cache: &Cache) {
+        let address = match Address::from_str(&self.verify_address.value)
+            .ok()
+            .and_then(|a| a.require_network(cache.network).ok())
+        {
+            Some(address) => address,
+            None => {
+                self.verify_address.valid = false;
+                return;
+            }
+        };
+        let secp = secp256k1::Secp256k1::verification_only();
+        self.verified = Some(bip322::verify(
+            &address.script_pubkey(),
+            self.verify_message.value.as_bytes(),
+            &self.verify_signature.value,
+            &[] as &[TxOut],
+            &secp,
+        ));
+    }
+}
+
+impl State for MessageSettingsState {
+    fn view<'a>(&'a self, menu: &'a Menu, cache: &'a Cache) -> Element<'a, view::Message> {
+        let content = view::vault::settings::message::message_section(
+            menu,
+            cache,
+            self.warning.as_ref(),
+            self.kind,
+            &self.address,
+            &self.message,
+            &self.coins,
+            &self.selected_coins,
+            self.psbt.is_some(),
+            self.signature.as_ref(),
+            &self.verify_address,
+            &self.verify_message,
+            &self.verify_signature,
+            self.verified.as_ref(),
+        );
+        if let Some(modal) = &self.modal {
+            return modal.view(content);
+        }
+        if self.psbt.is_some() && self.signature.is_none() {
+            let content: Element<view::Message> = toast::Manager::new(
+                content,
+                view::vault::psbt::sign_action_toasts(None, &self.hws.list, &self.signing),
+            )
+            .into();
+            return modal::Modal::new(
+                content,
+                view::vault::psbt::sign_action(
+                    self.warning.as_ref(),
+                    &self.hws.list,
+                    &self.wallet.main_descriptor,
+                    self.wallet.signer.as_ref().map(|s| s.fingerprint()),
+                    self.wallet
+                        .signer
+                        .as_ref()
+                        .and_then(|signer| self.wallet.keys_aliases.get(&signer.fingerprint)),
+                    &self.signed,
+                    &self.signing,
+                    None,
+                    false,
+                    false,
+                ),
+            )
+            .on_blur(Some(view::Message::Settings(
+                view::SettingsMessage::SignMessage(SignMessageMessage::Cancel),
+            )))
+            .into();
+        }
+        content
+    }
+
+    fn subscription(&self) -> Subscription<Message> {
+        if let Some(modal) = &self.modal {
+            if let Some(sub) = modal.subscription() {
+                return sub.map(|m| {
+                    Message::View(view::Message::Settings(
+                        view::SettingsMessage::ImportExport(ImportExportMessage::Progress(m)),
+                    ))
+                });
+            }
+        }
+        if self.psbt.is_some() && self.signature.is_none() {
+            return self.hws.refresh().map(Message::HardwareWallets);
+        }
+        Subscription::none()
+    }
+
+    fn reload(
+        &mut self,
+        daemon: Arc<dyn Daemon + Sync + Send>,
+        wallet: Arc<Wallet>,
+    ) -> Task<Message> {
+        self.wallet = wallet;
+        Task::perform(
+            async move {
+                daemon
+                    .list_coins(&[CoinStatus::Unconfirmed, CoinStatus::Confirmed], &[])
+                    .await
+                    .map(|res| res.coins)
+                    .map_err(|e| e.into())
+            },
+            Message::Coins,
+        )
+    }
+
+    fn update(
+        &mut self,
+        daemon: Arc<dyn Daemon + Sync + Send>,
+        cache: &Cache,
+        message: Message,
+    ) -> Task<Message> {
+        match message {
+            Message::Coins(res) => match res {
+                Ok(coins) => {
+                    self.coins = coins;
+                    self.selected_coins.clear();
+                }
+                Err(e) => self.warning = Some(e),
+            },
+            Message::MessagePsbt(res) => match res {
+                Ok(psbt) => {
+                    self.warning = None;
+                    self.psbt = Some(psbt);
+                }
+                Err(e) => self.warning = Some(e),
+            },
+            Message::HardwareWallets(msg) => match self.hws.update(msg) {
+                Ok(cmd) => return cmd.map(Message::HardwareWallets),
+                Err(e) => self.warning = Some(e.into()),
+            },
+            Message::View(view::Message::SelectHardwareWallet(i)) => {
+                if let (
+                    Some(HardwareWallet::Supported {
+                        fingerprint,
+                        device,
+                        ..
+                    }),
+                    Some(psbt),
+                ) = (self.hws.list.get(i), &self.psbt)
+                {
+                    self.signing.insert(*fingerprint);
+                    let fingerprint = *fingerprint;
+                    return Task::perform(
+                        sign_psbt(self.wallet.clone(), device.clone(), psbt.clone()),
+                        move |res| Message::Signed(fingerprint, res),
+                    );
+                }
+            }
+            Message::View(view::Message::Spend(view::SpendTxMessage::SelectHotSigner)) => {
+                if let Some(psbt) = &self.psbt {
+                    return Task::perform(
+                        sign_psbt_with_hot_signer(
+                            self.wallet.clone(),
+                            psbt.clone(),
+                            SighashPolicy::AllOnly,
+                        ),
+                        |(fg, res)| Message::Signed(fg, res),
+                    );
+                }
+            }
+            Message::Signed(fingerprint, res) => {
+                self.signing.remove(&fingerprint);
+                match res {
+                    Ok(signed_psbt) => {
+                        self.warning = None;
+                        self.signed.insert(fingerprint);
+                        if let Some(psbt) = self.psbt.as_mut() {
+                            merge_signatures(psbt, &signed_psbt);
+                        }
+                        self.try_finalize();
+                    }
+                    Err(e) => {
+                        if !matches!(e, Error::HardwareWallet(async_hwi::Error::UserRefused)) {
+                            self.warning = Some(e);
+                        }
+                    }
+                }
+            }
+            Message::View(view::Message::ImportExport(ImportExportMessage::Close)) => {
+                self.modal = None;
+            }
+            Message::View(view::Message::ImportExport(m))
+            | Message::View(view::Message::Settings(view::SettingsMessage::ImportExport(m))) => {
+                if let Some(modal) = self.modal.as_mut() {
+                    return modal.update(m);
+                }
+            }
+            Message::View(view::Message::Settings(view::SettingsMessage::SignMessage(msg))) => {
+                match msg {
+                    SignMessageMessage::KindSelected(kind) => {
+                        self.kind = kind;
+                        self.reset_signature();
+                    }
+                    SignMessageMessage::AddressEdited(address) => {
+                        self.address.valid = true;
+                        self.address.value = address;
+                        self.reset_signature();
+                    }
+                    SignMessageMessage::MessageEdited(message) => {
+                        self.message.value = message;
+                        self.reset_signature();
+                    }
+                    SignMessageMessage::SelectCoin(i) => {
+                        if !self.selected_coins.remove(&i) {
+                            self.selected_coins.insert(i);
+                        }
+                        self.reset_signature();
+                    }
+                    SignMessageMessage::Sign => {
+                        self.warning = None;
+                        self.reset_signature();
+                        let (address, outpoints) = if self.kind == MessageProofKind::Reserves {
+                            let coins = self.selected_coins();
+                            match coins.first() {
+                                Some(first) => (
+                                    first.address.as_unchecked().clone(),
+                                    coins.iter().map(|c| c.outpoint).collect(),
+                                ),
+                                None => return Task::none(),
+                            }
+                        } else {
+                            match Address::from_str(&self.address.value) {
+                                Ok(address) if address.is_valid_for_network(cache.network) => {
+                                    (address, Vec::new())
+                                }
+                                _ => {
+                                    self.address.valid = false;
+                                    return Task::none();
+                                }
+                            }
+                        };
+                        let message = self.message.value.clone();
+                        return Task::perform(
+                            async move {
+                                daemon
+                                    .create_message_psbt(address, &message, &outpoints)
+                                    .await
+                                    .map_err(|e| e.into())
+                            },
+                            Message::MessagePsbt,
+                        );
+                    }
+                    SignMessageMessage::Cancel => {
+                        self.reset_signature();
+                    }
+                    SignMessageMessage::ExportProof => {
+                        if self.modal.is_none() {
+                            if let Some(proof) = self.reserve_proof(cache) {
+                                match serde_json::to_string_pretty(&proof) {
+                                    Ok(proof) => {
+                                        let modal = VaultExportModal::new(
+                                            Some(daemon),
+                                            ImportExportType::ExportReserveProof(proof),
+                                        );
+                                        let launch = modal.launch(true);
+                                        self.modal = Some(modal);
+                                        return launch;
+                                    }
+                                    Err(e) => self.warning = Some(Error::Unexpected(e.to_string())),
+                                }
+                            }
+                        }
+                    }
+                    SignMessageMessage::VerifyAddressEdited(address) => {
+                        self.verify_address.valid = true;
+                        self.verify_address.value = address;
+                        self.verified = None;
+                    }
+                    SignMessageMessage::VerifyMessageEdited(message) => {
+                        self.verify_message.value = message;
+                        self.verified = None;
+                    }
+                    SignMessageMessage::SignatureEdited(signature) => {
+                        self.verify_signature.value = signature;
+                        self.verified = None;
+                    }
+                    SignMessageMessage::Verify => self.verify(cache),
+                    SignMessageMessage::VerifyProofFile => {
+                        if self.modal.is_none() {
+                            let modal = VaultExportModal::new(
+                                Some(daemon),
+                                ImportExportType::VerifyReserveProof,
+                            );
+                            let launch = modal.launch(false);
+                            self.modal = Some(modal);
+                            return launch;
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+        Task::none()
+    }
+}
+
+impl From<MessageSettingsState> for Box<dyn State> {
+    fn from(s: MessageSettingsState) -> Box<dyn State> {
+        Box::new(s)
+    }
+}
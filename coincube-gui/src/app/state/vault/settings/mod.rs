@@ -1,5 +1,9 @@
 mod bitcoind;
+mod contacts;
+mod drill;
 mod general;
+mod heir;
+mod message;
 mod wallet;
 
 use std::convert::From;
@@ -7,9 +11,13 @@ use std::sync::Arc;
 
 use iced::Task;
 
-use coincube_ui::{component::form, widget::Element};
+use coincube_ui::{
+    component::form,
+    widget::{modal::Modal, Element},
+};
 
 use bitcoind::BitcoindSettingsState;
+pub use contacts::{update_contacts, ContactForm};
 use wallet::{update_aliases, WalletSettingsState};
 
 use crate::{
@@ -23,9 +31,10 @@ use crate::{
         wallet::Wallet,
         Config,
     },
+    bcur::AnimatedQr,
     daemon::{Daemon, DaemonBackend},
     dir::CoincubeDirectory,
-    export::{ImportExportMessage, ImportExportType},
+    export::{self, ImportExportMessage, ImportExportType},
 };
 
 use super::export::VaultExportModal;
@@ -102,6 +111,48 @@ impl State for SettingsState {
                 );
                 Task::none()
             }
+            Message::View(view::Message::Settings(view::SettingsMessage::MessageSection)) => {
+                self.setting = Some(
+                    message::MessageSettingsState::new(
+                        self.data_dir.clone(),
+                        self.wallet.clone(),
+                        cache,
+                    )
+                    .into(),
+                );
+                let wallet = self.wallet.clone();
+                self.setting
+                    .as_mut()
+                    .map(|s| s.reload(daemon, wallet))
+                    .unwrap_or_else(Task::none)
+            }
+            Message::View(view::Message::Settings(view::SettingsMessage::ContactsSection)) => {
+                self.setting = Some(
+                    contacts::ContactsSettingsState::new(
+                        self.data_dir.clone(),
+                        self.wallet.clone(),
+                    )
+                    .into(),
+                );
+                Task::none()
+            }
+            Message::View(view::Message::Settings(view::SettingsMessage::HeirSection)) => {
+                self.setting = Some(
+                    heir::HeirSettingsState::new(self.data_dir.clone(), self.wallet.clone()).into(),
+                );
+                let wallet = self.wallet.clone();
+                self.setting
+                    .as_mut()
+                    .map(|s| s.reload(daemon, wallet))
+                    .unwrap_or_else(Task::none)
+            }
+            Message::View(view::Message::Settings(view::SettingsMessage::DrillSection)) => {
+                self.setting = Some(
+                    drill::DrillSettingsState::new(self.data_dir.clone(), self.wallet.clone())
+                        .into(),
+                );
+                Task::none()
+            }
             Message::View(view::Message::Settings(view::SettingsMessage::AboutSection)) => {
                 self.setting = Some(AboutSettingsState::default().into());
                 let wallet = self.wallet.clone();
@@ -180,6 +231,7 @@ impl From<SettingsState> for Box<dyn State> {
 pub struct ImportExportSettingsState {
     warning: Option<Error>,
     modal: Option<VaultExportModal>,
+    qr: Option<AnimatedQr>,
     wallet: Arc<Wallet>,
     config: Arc<Config>,
 }
@@ -189,6 +241,7 @@ impl ImportExportSettingsState {
         Self {
             warning: None,
             modal: None,
+            qr: None,
             wallet,
             config,
         }
@@ -208,6 +261,14 @@ impl State for ImportExportSettingsState {
         let content = view::vault::settings::import_export(menu, cache, self.warning.as_ref());
         if let Some(modal) = &self.modal {
             modal.view(content)
+        } else if let Some(qr) = &self.qr {
+            let close = view::Message::ImportExport(ImportExportMessage::Close);
+            Modal::new(
+                content,
+                view::vault::export::ur_qr_modal(qr, "Descriptor QR code", close.clone()),
+            )
+            .on_blur(Some(close))
+            .into()
         } else {
             content
         }
@@ -223,6 +284,10 @@ impl State for ImportExportSettingsState {
                 });
             }
         }
+        if self.qr.is_some() {
+            return iced::time::every(std::time::Duration::from_millis(250))
+                .map(|_| Message::View(view::Message::NextQrFrame));
+        }
         iced::Subscription::none()
     }
 
@@ -235,6 +300,22 @@ impl State for ImportExportSettingsState {
         match message {
             Message::View(view::Message::ImportExport(ImportExportMessage::Close)) => {
                 self.modal = None;
+                self.qr = None;
+            }
+            Message::View(view::Message::NextQrFrame) => {
+                if let Some(qr) = self.qr.as_mut() {
+                    qr.next_frame();
+                }
+            }
+            Message::View(view::Message::Settings(view::SettingsMessage::ShowDescriptorQr)) => {
+                if self.modal.is_none() {
+                    match AnimatedQr::descriptor(self.wallet.main_descriptor.descriptor()) {
+                        Ok(qr) => self.qr = Some(qr),
+                        Err(e) => {
+                            self.warning = Some(Error::ImportExport(export::Error::Ur(e)));
+                        }
+                    }
+                }
             }
             Message::View(view::Message::ImportExport(m)) => {
                 if let ImportExportMessage::UpdateAliases(aliases) = m {
@@ -282,6 +363,19 @@ impl State for ImportExportSettingsState {
                     launch!(self, modal, true);
                 }
             }
+            Message::View(view::Message::Settings(
+                view::SettingsMessage::ExportColdcardRegistration,
+            )) => {
+                if self.modal.is_none() {
+                    let modal = VaultExportModal::new(
+                        Some(daemon),
+                        ImportExportType::ExportColdcardRegistration(Box::new(
+                            self.wallet.main_descriptor.clone(),
+                        )),
+                    );
+                    launch!(self, modal, true);
+                }
+            }
             Message::View(view::Message::Settings(view::SettingsMessage::ExportTransactions)) => {
                 if self.modal.is_none() {
                     let modal = VaultExportModal::new(Some(daemon), ImportExportType::Transactions);
@@ -294,6 +388,18 @@ impl State for ImportExportSettingsState {
                     launch!(self, modal, true);
                 }
             }
+            Message::View(view::Message::Settings(view::SettingsMessage::ImportLabels)) => {
+                if self.modal.is_none() {
+                    let modal = VaultExportModal::new(
+                        Some(daemon),
+                        ImportExportType::ImportLabels {
+                            wallet: self.wallet.clone(),
+                            conflict: None,
+                        },
+                    );
+                    launch!(self, modal, false);
+                }
+            }
             Message::View(view::Message::Settings(view::SettingsMessage::ExportWallet)) => {
                 if self.modal.is_none() {
                     let datadir = cache.datadir_path.clone();
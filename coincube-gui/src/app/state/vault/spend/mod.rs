@@ -165,6 +165,12 @@ impl State for CreateSpendPanel {
             }
         }
 
+        if let Message::WalletUpdated(Ok(wallet)) = &message {
+            for step in self.steps.iter_mut() {
+                step.reload_wallet(wallet.clone());
+            }
+        }
+
         if let Message::CoinsTipHeight(Ok(coins), Ok(tip)) = &message {
             // Save the coins and tip for use in the `load()` method.
             self.coins = coins.clone();
@@ -11,10 +11,14 @@ use coincube_core::{
     miniscript::bitcoin::{
         address,
         bip32::{DerivationPath, Fingerprint},
+        hashes::hex::FromHex,
         psbt::Psbt,
         secp256k1, Address, Amount, Denomination, Network, OutPoint,
     },
-    spend::{SpendCreationError, MAX_FEERATE},
+    spend::{
+        format_feerate_kvb, parse_feerate_vb, SpendCreationError, MAX_FEERATE_KVB,
+        MAX_OP_RETURN_DATA_SIZE, MIN_FEERATE_KVB,
+    },
 };
 use coincubed::commands::ListCoinsEntry;
 use iced::{Subscription, Task};
@@ -27,8 +31,16 @@ use crate::{
         error::Error,
         menu::Menu,
         message::Message,
-        state::{fiat_converter_for_wallet, vault::psbt},
-        view::{self, vault::fiat::FiatAmount, CreateSpendMessage},
+        settings::contacts::{match_contact, ContactMatch, Destination},
+        state::{
+            fiat_converter_for_wallet,
+            vault::{psbt, settings::update_contacts},
+        },
+        view::{
+            self,
+            vault::{fiat::FiatAmount, spend::RecipientContact},
+            ContactAddress, CreateSpendMessage,
+        },
         wallet::Wallet,
     },
     daemon::{
@@ -141,6 +153,8 @@ pub struct DefineSpend {
     /// If set, this is the index of a recipient that should
     /// receive the max amount.
     send_max_to_recipient: Option<usize>,
+    /// Data to commit to in an OP_RETURN output, if any.
+    op_return: Option<OpReturn>,
     /// Will be `true` if coins for spend were manually selected by user.
     /// Otherwise, will be `false` (including for self-send & recovery).
     is_user_coin_selection: bool,
@@ -200,6 +214,7 @@ impl DefineSpend {
             recipients: vec![Recipient::new(recovery_timelock.is_some())],
             // For recovery, send max to the (single) recipient.
             send_max_to_recipient: recovery_timelock.map(|_| 0),
+            op_return: None,
             is_user_coin_selection: false,
             is_valid: false,
             is_duplicate: false,
@@ -255,6 +270,49 @@ impl DefineSpend {
             .unwrap_or_else(|| self.wallet.main_descriptor.first_timelock_value())
     }
 
+    /// The payment addresses of the address book contacts.
+    fn contact_addresses(&self) -> Vec<ContactAddress> {
+        let mut addresses = Vec::new();
+        for (i, contact) in self.wallet.contacts.iter().enumerate() {
+            for destination in contact.parsed_destinations(self.network) {
+                if let Some(address) = destination.address(self.network, contact.next_index) {
+                    addresses.push(ContactAddress {
+                        contact: i,
+                        name: contact.name.clone(),
+                        address: address.to_string(),
+                        derived: matches!(destination, Destination::Descriptor(_)),
+                    });
+                }
+            }
+        }
+        addresses
+    }
+
+    /// What the address book says about the address of the given recipient.
+    fn update_recipient_contact(&mut self, i: usize) {
+        let network = self.network;
+        let contacts = &self.wallet.contacts;
+        if let Some(recipient) = self.recipients.get_mut(i) {
+            recipient.contact = Address::from_str(&recipient.address.value)
+                .ok()
+                .and_then(|a| a.require_network(network).ok())
+                .and_then(
+                    |address| match match_contact(contacts, &address, network)? {
+                        ContactMatch::Known(contact) => Some(RecipientContact::Known {
+                            name: contact.name.clone(),
+                            verified_on: contact.verified_on.clone(),
+                        }),
+                        ContactMatch::LookAlike(contact, address) => {
+                            Some(RecipientContact::LookAlike {
+                                name: contact.name.clone(),
+                                address: address.to_string(),
+                            })
+                        }
+                    },
+                );
+        }
+    }
+
     // If `is_redraft`, the validation of recipients will take into account
     // whether any should receive the max amount. Otherwise, all recipients
     // will be fully validated.
@@ -262,11 +320,23 @@ impl DefineSpend {
         self.feerate.valid
             && !self.feerate.value.is_empty()
             && (self.batch_label.valid || self.recipients.len() < 2)
+            && self.op_return.as_ref().is_none_or(OpReturn::valid)
             // Recipients will be empty for self-send.
             && self.recipients.iter().enumerate().all(|(i, r)|
             r.valid() || (is_redraft && self.send_max_to_recipient == Some(i) && r.address_valid()))
     }
 
+    // The addresses of the recipients the fee is deducted from. The recipient receiving the max
+    // already pays for the fee and is never part of them.
+    fn subtract_fee_from(&self) -> Vec<Address<address::NetworkUnchecked>> {
+        self.recipients
+            .iter()
+            .enumerate()
+            .filter(|(i, r)| r.subtract_fee && self.send_max_to_recipient != Some(*i))
+            .map(|(_, r)| Address::from_str(&r.address.value).expect("Checked before"))
+            .collect()
+    }
+
     fn exists_duplicate(&self) -> bool {
         for (i, recipient) in self.recipients.iter().enumerate() {
             if !recipient.address.value.is_empty()
@@ -334,6 +404,7 @@ impl DefineSpend {
             })
             .collect();
 
+        let subtract_fee_from = self.subtract_fee_from();
         let recipient_with_max = if let Some(i) = self.send_max_to_recipient {
             Some((
                 i,
@@ -411,13 +482,14 @@ impl DefineSpend {
                 .clone()
         };
 
-        let feerate_vb = self.feerate.value.parse::<u64>().expect("Checked before");
+        let feerate_kvb = parse_feerate_vb(&self.feerate.value).expect("Checked before");
         let recovery_timelock = self.recovery_timelock;
+        let op_return = self.op_return.as_ref().and_then(OpReturn::bytes);
         match tokio::runtime::Handle::current().block_on(async {
             // If recovery timelock is set, create a recovery transaction. Otherwise, a regular spend.
             if let Some(reco_tl) = recovery_timelock {
                 daemon
-                    .create_recovery(max_address.clone(), &outpoints, feerate_vb, Some(reco_tl))
+                    .create_recovery(max_address.clone(), &outpoints, feerate_kvb, Some(reco_tl))
                     .await
                     // Map the PSBT to `CreateSpendResult` result. We only need the PSBT below.
                     .map(|psbt| CreateSpendResult::Success {
@@ -429,7 +501,9 @@ impl DefineSpend {
                     .create_spend_tx(
                         &outpoints,
                         &destinations,
-                        feerate_vb,
+                        &subtract_fee_from,
+                        op_return.as_deref(),
+                        feerate_kvb,
                         Some(max_address.clone()),
                     )
                     .await
@@ -561,6 +635,7 @@ impl Step for DefineSpend {
     ) -> Task<Message> {
         match message {
             Message::View(view::Message::CreateSpend(msg)) => {
+                let mut task = Task::none();
                 match msg {
                     view::CreateSpendMessage::BatchLabelEdited(label) => {
                         self.batch_label.valid = label.len() <= 100;
@@ -605,17 +680,68 @@ impl Step for DefineSpend {
                             }
                         }
                     }
-                    view::CreateSpendMessage::RecipientEdited(i, _, _)
-                    | view::CreateSpendMessage::RecipientFiatAmountEdited(i, _, _) => {
+                    view::CreateSpendMessage::RecipientEdited(i, field, _) => {
                         self.recipients
                             .get_mut(i)
                             .unwrap()
                             .update(cache.network, msg);
+                        if field == "address" {
+                            self.update_recipient_contact(i);
+                        }
+                    }
+                    view::CreateSpendMessage::RecipientFiatAmountEdited(i, _, _) => {
+                        self.recipients
+                            .get_mut(i)
+                            .unwrap()
+                            .update(cache.network, msg);
+                    }
+                    view::CreateSpendMessage::RecipientContactSelected(i, choice) => {
+                        if let Some(recipient) = self.recipients.get_mut(i) {
+                            recipient.update(
+                                cache.network,
+                                view::CreateSpendMessage::RecipientEdited(
+                                    i,
+                                    "address",
+                                    choice.address.clone(),
+                                ),
+                            );
+                            if recipient.label.value.is_empty() {
+                                recipient.update(
+                                    cache.network,
+                                    view::CreateSpendMessage::RecipientEdited(
+                                        i,
+                                        "label",
+                                        choice.name.clone(),
+                                    ),
+                                );
+                            }
+                            self.update_recipient_contact(i);
+                        }
+                        // A derived address is not offered again, so that the payee gets a new
+                        // one for each payment.
+                        if choice.derived {
+                            let mut contacts = self.wallet.contacts.clone();
+                            if let Some(contact) = contacts.get_mut(choice.contact) {
+                                contact.next_index += 1;
+                                let data_dir = cache.datadir_path.clone();
+                                let network = cache.network;
+                                let wallet = self.wallet.clone();
+                                task = Task::perform(
+                                    async move {
+                                        update_contacts(data_dir, network, wallet, contacts).await
+                                    },
+                                    Message::WalletUpdated,
+                                );
+                            }
+                        }
                     }
                     view::CreateSpendMessage::FeerateEdited(s) => {
-                        if let Ok(value) = s.parse::<u64>() {
+                        // Save any number, even if not a valid feerate, for the user to be
+                        // able to type in a fractional value.
+                        if s.parse::<f64>().is_ok() {
+                            self.feerate.valid = parse_feerate_vb(&s)
+                                .is_some_and(|v| (MIN_FEERATE_KVB..=MAX_FEERATE_KVB).contains(&v));
                             self.feerate.value = s;
-                            self.feerate.valid = value != 0 && value <= MAX_FEERATE;
                         } else if s.is_empty() {
                             self.feerate.value = "".to_string();
                             self.feerate.valid = true;
@@ -643,7 +769,7 @@ impl Step for DefineSpend {
                             },
                             |feerate| match feerate {
                                 Ok(fee) => Message::View(view::Message::CreateSpend(
-                                    CreateSpendMessage::FeerateEdited(fee.to_string()),
+                                    CreateSpendMessage::FeerateEdited(format_feerate_kvb(fee)),
                                 )),
                                 Err(_) => Message::View(view::Message::CreateSpend(
                                     CreateSpendMessage::SessionError(
@@ -669,7 +795,7 @@ impl Step for DefineSpend {
                             .collect();
                         let mut outputs: HashMap<Address<address::NetworkUnchecked>, u64> =
                             HashMap::new();
-                        let feerate_vb = self.feerate.value.parse::<u64>().unwrap_or(0);
+                        let feerate_kvb = parse_feerate_vb(&self.feerate.value).unwrap_or(0);
                         self.warning = None;
                         if let Some(reco_tl) = self.recovery_timelock {
                             let recovery_address = Address::from_str(
@@ -687,7 +813,7 @@ impl Step for DefineSpend {
                                         .create_recovery(
                                             recovery_address,
                                             &inputs,
-                                            feerate_vb,
+                                            feerate_kvb,
                                             Some(reco_tl),
                                         )
                                         .await
@@ -703,10 +829,19 @@ impl Step for DefineSpend {
                                 outputs
                                     .insert(address, recipient.amount().expect("Checked before"));
                             }
+                            let subtract_fee_from = self.subtract_fee_from();
+                            let op_return = self.op_return.as_ref().and_then(OpReturn::bytes);
                             return Task::perform(
                                 async move {
                                     daemon
-                                        .create_spend_tx(&inputs, &outputs, feerate_vb, None)
+                                        .create_spend_tx(
+                                            &inputs,
+                                            &outputs,
+                                            &subtract_fee_from,
+                                            op_return.as_deref(),
+                                            feerate_kvb,
+                                            None,
+                                        )
                                         .await
                                         .map_err(|e| e.into())
                                         .and_then(|res| match res {
@@ -745,6 +880,29 @@ impl Step for DefineSpend {
                             };
                         }
                     }
+                    view::CreateSpendMessage::SubtractFeeFromRecipient(i) => {
+                        if let Some(recipient) = self.recipients.get_mut(i) {
+                            recipient.subtract_fee = !recipient.subtract_fee;
+                        }
+                    }
+                    view::CreateSpendMessage::AddOpReturn => {
+                        self.op_return = Some(OpReturn::default());
+                    }
+                    view::CreateSpendMessage::DeleteOpReturn => {
+                        self.op_return = None;
+                    }
+                    view::CreateSpendMessage::OpReturnEdited(data) => {
+                        if let Some(op_return) = &mut self.op_return {
+                            op_return.data.value = data;
+                            op_return.check_valid();
+                        }
+                    }
+                    view::CreateSpendMessage::OpReturnIsHex(is_hex) => {
+                        if let Some(op_return) = &mut self.op_return {
+                            op_return.is_hex = is_hex;
+                            op_return.check_valid();
+                        }
+                    }
                     _ => {}
                 }
 
@@ -754,6 +912,7 @@ impl Step for DefineSpend {
                 // - user has not yet selected coins manually
                 self.redraft(daemon);
                 self.check_valid();
+                return task;
             }
             Message::Psbt(res) => match res {
                 Ok(psbt) => {
@@ -851,10 +1010,18 @@ impl Step for DefineSpend {
                 .enumerate()
                 .map(|(i, recipient)| {
                     recipient
-                        .view(i, self.send_max_to_recipient == Some(i), converter.as_ref())
+                        .view(
+                            i,
+                            self.send_max_to_recipient == Some(i),
+                            converter.as_ref(),
+                            self.contact_addresses(),
+                        )
                         .map(view::Message::CreateSpend)
                 })
                 .collect(),
+            self.op_return
+                .as_ref()
+                .map(|op_return| (&op_return.data, op_return.is_hex)),
             self.is_valid,
             self.is_duplicate,
             self.timelock(),
@@ -885,6 +1052,10 @@ struct Recipient {
     fiat_amount: Option<form::Value<String>>,
     fiat_converter: Option<view::FiatAmountConverter>, // the converter at the time of entering the fiat amount
     is_recovery: bool,
+    // Whether the fee is deducted from this recipient's amount.
+    subtract_fee: bool,
+    // What the address book says about the address.
+    contact: Option<RecipientContact>,
 }
 
 impl Recipient {
@@ -1018,6 +1189,7 @@ impl Recipient {
         i: usize,
         is_max_selected: bool,
         fiat_converter: Option<&view::FiatAmountConverter>,
+        contacts: Vec<ContactAddress>,
     ) -> Element<view::CreateSpendMessage> {
         let mut fiat_form_value = self.fiat_amount.as_ref();
 
@@ -1040,16 +1212,47 @@ impl Recipient {
         view::vault::spend::recipient_view(
             i,
             &self.address,
+            contacts,
+            self.contact.as_ref(),
             &self.amount,
             fiat_form_value,
             fiat_converter,
             &self.label,
             is_max_selected,
+            self.subtract_fee,
             self.is_recovery,
         )
     }
 }
 
+#[derive(Default, Clone)]
+struct OpReturn {
+    data: form::Value<String>,
+    // Whether the data is entered hex encoded rather than as text.
+    is_hex: bool,
+}
+
+impl OpReturn {
+    fn bytes(&self) -> Option<Vec<u8>> {
+        if self.is_hex {
+            Vec::from_hex(&self.data.value).ok()
+        } else {
+            Some(self.data.value.as_bytes().to_vec())
+        }
+    }
+
+    fn check_valid(&mut self) {
+        self.data.valid = self.data.value.is_empty()
+            || self
+                .bytes()
+                .is_some_and(|data| data.len() <= MAX_OP_RETURN_DATA_SIZE);
+    }
+
+    fn valid(&self) -> bool {
+        !self.data.value.is_empty() && self.data.valid
+    }
+}
+
 pub struct SaveSpend {
     wallet: Arc<Wallet>,
     spend: Option<(psbt::PsbtState, Vec<String>)>,
@@ -6,18 +6,21 @@ use std::{
 
 use coincube_core::{
     miniscript::bitcoin::{OutPoint, Txid},
-    spend::{SpendCreationError, MAX_FEERATE},
+    spend::{
+        format_feerate_kvb, parse_feerate_vb, SpendCreationError, MAX_FEERATE_KVB, MIN_FEERATE_KVB,
+    },
 };
 use coincube_ui::{
     component::form,
     widget::{modal::Modal, Element},
 };
-use coincubed::commands::CoinStatus;
+use coincubed::commands::{CoinStatus, SearchFilter};
 use iced::Task;
 
 pub const HISTORY_EVENT_PAGE_SIZE: u64 = 20;
 
 use crate::{
+    accounting::{AccountingExport, CostBasis},
     app::{
         cache::Cache,
         error::Error,
@@ -29,6 +32,7 @@ use crate::{
     },
     daemon::model::{self, LabelsLoader},
     export::{ImportExportMessage, ImportExportType},
+    services::fiat::history::PRICE_HISTORY_FILENAME,
     utils::now,
 };
 
@@ -37,11 +41,12 @@ use crate::daemon::{
     Daemon,
 };
 
-use super::export::VaultExportModal;
+use super::{export::VaultExportModal, search::SearchForm};
 
 #[derive(Debug)]
 pub enum VaultTransactionsModal {
     CreateRbf(CreateRbfModal),
+    CreateCpfp(CreateCpfpModal),
     Export(VaultExportModal),
     None,
 }
@@ -55,6 +60,12 @@ pub struct VaultTransactionsPanel {
     modal: VaultTransactionsModal,
     is_last_page: bool,
     processing: bool,
+    /// Only set if the backend supports searching.
+    search: Option<SearchForm>,
+    /// Number of transactions requested for the applied search.
+    search_limit: u64,
+    /// Cost basis method of the accounting export.
+    cost_basis: CostBasis,
 }
 
 impl VaultTransactionsPanel {
@@ -68,9 +79,29 @@ impl VaultTransactionsPanel {
             modal: VaultTransactionsModal::None,
             is_last_page: false,
             processing: false,
+            search: None,
+            search_limit: HISTORY_EVENT_PAGE_SIZE,
+            cost_basis: CostBasis::default(),
         }
     }
 
+    fn search_txs(
+        &self,
+        daemon: Arc<dyn Daemon + Sync + Send>,
+        filter: SearchFilter,
+    ) -> Task<Message> {
+        let limit = self.search_limit;
+        Task::perform(
+            async move {
+                daemon
+                    .search_history_txs(&filter, limit)
+                    .await
+                    .map_err(|e| e.into())
+            },
+            Message::HistoryTransactions,
+        )
+    }
+
     pub fn preselect(&mut self, tx: HistoryTransaction) {
         self.selected_tx = Some(tx);
         self.warning = None;
@@ -90,6 +121,7 @@ impl State for VaultTransactionsPanel {
             );
             match &self.modal {
                 VaultTransactionsModal::CreateRbf(rbf) => rbf.view(content),
+                VaultTransactionsModal::CreateCpfp(cpfp) => cpfp.view(content),
                 _ => content,
             }
         } else {
@@ -97,6 +129,8 @@ impl State for VaultTransactionsPanel {
                 menu,
                 cache,
                 &self.txs,
+                self.search.as_ref(),
+                self.cost_basis,
                 self.warning.as_ref(),
                 self.is_last_page,
                 self.processing,
@@ -111,16 +145,22 @@ impl State for VaultTransactionsPanel {
     fn update(
         &mut self,
         daemon: Arc<dyn Daemon + Sync + Send>,
-        _cache: &Cache,
+        cache: &Cache,
         message: Message,
     ) -> Task<Message> {
         match message {
             Message::HistoryTransactions(res) => match res {
                 Err(e) => self.warning = Some(e),
                 Ok(txs) => {
+                    self.processing = false;
                     self.warning = None;
                     self.txs = txs;
-                    self.is_last_page = (self.txs.len() as u64) < HISTORY_EVENT_PAGE_SIZE;
+                    self.is_last_page = if self.search.as_ref().is_some_and(|s| s.applied.is_some())
+                    {
+                        (self.txs.len() as u64) < self.search_limit
+                    } else {
+                        (self.txs.len() as u64) < HISTORY_EVENT_PAGE_SIZE
+                    };
                 }
             },
             Message::HistoryTransactionsExtension(res) => match res {
@@ -156,21 +196,34 @@ impl State for VaultTransactionsPanel {
                     self.warning = e.into();
                 }
             },
+            Message::CpfpModal(tx, res) => match res {
+                Ok(outpoints) => {
+                    let modal = CreateCpfpModal::new(*tx, outpoints);
+                    self.modal = VaultTransactionsModal::CreateCpfp(modal);
+                }
+                Err(e) => {
+                    self.warning = e.into();
+                }
+            },
             Message::View(view::Message::Reload) | Message::View(view::Message::Close) => {
                 return self.reload(daemon, self.wallet.clone());
             }
             Message::View(view::Message::Select(i)) => {
                 self.selected_tx = self.txs.get(i).cloned();
                 // Clear modal if it's for a different tx.
-                if let VaultTransactionsModal::CreateRbf(modal) = &self.modal {
-                    if Some(modal.tx.tx.compute_txid())
+                let modal_txid = match &self.modal {
+                    VaultTransactionsModal::CreateRbf(modal) => Some(modal.tx.tx.compute_txid()),
+                    VaultTransactionsModal::CreateCpfp(modal) => Some(modal.tx.tx.compute_txid()),
+                    _ => None,
+                };
+                if modal_txid.is_some()
+                    && modal_txid
                         != self
                             .selected_tx
                             .as_ref()
                             .map(|selected| selected.tx.compute_txid())
-                    {
-                        self.modal = VaultTransactionsModal::None;
-                    }
+                {
+                    self.modal = VaultTransactionsModal::None;
                 }
             }
             Message::View(view::Message::CreateRbf(view::CreateRbfMessage::Cancel)) => {
@@ -208,6 +261,46 @@ impl State for VaultTransactionsPanel {
                     }
                 }
             }
+            Message::View(view::Message::CreateCpfp(view::CreateCpfpMessage::Cancel)) => {
+                self.modal = VaultTransactionsModal::None;
+            }
+            Message::View(view::Message::CreateCpfp(view::CreateCpfpMessage::New)) => {
+                if let Some(tx) = &self.selected_tx {
+                    let tx = tx.clone();
+                    let outpoints: Vec<_> = (0..tx.tx.output.len())
+                        .map(|vout| {
+                            OutPoint::new(
+                                tx.tx.compute_txid(),
+                                vout.try_into()
+                                    .expect("number of transaction outputs must fit in u32"),
+                            )
+                        })
+                        .collect();
+                    return Task::perform(
+                        async move {
+                            let res = daemon
+                                .list_coins(&[CoinStatus::Unconfirmed], &outpoints)
+                                .await
+                                .map_err(Error::from)
+                                .and_then(|res| {
+                                    let outpoints: Vec<_> =
+                                        res.coins.iter().map(|c| c.outpoint).collect();
+                                    if outpoints.is_empty() {
+                                        Err(Error::Unexpected(
+                                            "This transaction has no unspent output belonging \
+                                            to this wallet."
+                                                .to_string(),
+                                        ))
+                                    } else {
+                                        Ok(outpoints)
+                                    }
+                                });
+                            (Box::new(tx), res)
+                        },
+                        |(tx, res)| Message::CpfpModal(tx, res),
+                    );
+                }
+            }
             Message::View(view::Message::Label(_, _)) | Message::LabelsUpdated(_) => {
                 match self.labels_edited.update(
                     daemon,
@@ -229,7 +322,22 @@ impl State for VaultTransactionsPanel {
                     }
                 };
             }
+            Message::View(view::Message::Search(msg)) => {
+                if let Some(search) = &mut self.search {
+                    if search.update(cache.network, msg) {
+                        self.search_limit = HISTORY_EVENT_PAGE_SIZE;
+                        return self.reload(daemon, self.wallet.clone());
+                    }
+                }
+            }
             Message::View(view::Message::Next) => {
+                // Search results are sorted by relevance to the filter rather than paginated by
+                // date, so fetch a larger batch instead.
+                if let Some(filter) = self.search.as_ref().and_then(|s| s.applied.clone()) {
+                    self.processing = true;
+                    self.search_limit += HISTORY_EVENT_PAGE_SIZE;
+                    return self.search_txs(daemon, filter);
+                }
                 if let Some(last) = self.txs.last() {
                     let daemon = daemon.clone();
                     let last_tx_date = last.time.unwrap();
@@ -280,6 +388,38 @@ impl State for VaultTransactionsPanel {
                     }
                 }
             }
+            Message::View(view::Message::Accounting(
+                view::AccountingMessage::CostBasisSelected(cost_basis),
+            )) => {
+                self.cost_basis = cost_basis;
+            }
+            Message::View(view::Message::Accounting(view::AccountingMessage::Export)) => {
+                if let VaultTransactionsModal::None = &self.modal {
+                    let export = AccountingExport {
+                        fiat: self
+                            .wallet
+                            .fiat_price_setting
+                            .as_ref()
+                            .filter(|setting| setting.is_enabled)
+                            .map(|setting| (setting.source, setting.currency)),
+                        price_history: Some(
+                            cache
+                                .datadir_path
+                                .network_directory(cache.network)
+                                .path()
+                                .join(PRICE_HISTORY_FILENAME),
+                        ),
+                        cost_basis: self.cost_basis,
+                    };
+                    self.modal = VaultTransactionsModal::Export(VaultExportModal::new(
+                        Some(daemon),
+                        ImportExportType::AccountingTransactions(export),
+                    ));
+                    if let VaultTransactionsModal::Export(m) = &self.modal {
+                        return m.launch(true);
+                    }
+                }
+            }
             Message::View(view::Message::ImportExport(ImportExportMessage::Close)) => {
                 if let VaultTransactionsModal::Export(_) = &self.modal {
                     self.modal = VaultTransactionsModal::None;
@@ -288,8 +428,9 @@ impl State for VaultTransactionsPanel {
             ref msg => {
                 return match &mut self.modal {
                     VaultTransactionsModal::CreateRbf(modal) => {
-                        modal.update(daemon, _cache, message)
+                        modal.update(daemon, cache, message)
                     }
+                    VaultTransactionsModal::CreateCpfp(modal) => modal.update(daemon, message),
                     VaultTransactionsModal::Export(modal) => {
                         if let Message::View(view::Message::ImportExport(m)) = msg {
                             modal.update::<Message>(m.clone())
@@ -307,9 +448,18 @@ impl State for VaultTransactionsPanel {
     fn reload(
         &mut self,
         daemon: Arc<dyn Daemon + Sync + Send>,
-        _wallet: Arc<Wallet>,
+        wallet: Arc<Wallet>,
     ) -> Task<Message> {
         self.selected_tx = None;
+        self.wallet = wallet;
+        if daemon.backend().is_coincubed() {
+            self.search.get_or_insert_with(SearchForm::default);
+        } else {
+            self.search = None;
+        }
+        if let Some(filter) = self.search.as_ref().and_then(|s| s.applied.clone()) {
+            return self.search_txs(daemon, filter);
+        }
         let now: u32 = now().as_secs().try_into().unwrap();
         Task::batch(vec![Task::perform(
             async move {
@@ -352,15 +502,15 @@ pub struct CreateRbfModal {
     tx: model::HistoryTransaction,
     /// Whether to cancel or bump fee.
     is_cancel: bool,
-    /// Min feerate required for RBF.
-    min_feerate_vb: u64,
+    /// Min feerate (in sats/kvb) required for RBF.
+    min_feerate_kvb: u64,
     /// IDs of any transactions from this wallet that are direct descendants of
     /// the transaction to be replaced.
     descendant_txids: HashSet<Txid>,
     /// Feerate form value.
     feerate_val: form::Value<String>,
-    /// Parsed feerate.
-    feerate_vb: Option<u64>,
+    /// Parsed feerate, in sats/kvb.
+    feerate_kvb: Option<u64>,
     /// Replacement transaction ID.
     replacement_txid: Option<Txid>,
 
@@ -380,22 +530,25 @@ impl CreateRbfModal {
             .to_sat()
             .checked_div(tx.tx.vsize().try_into().expect("vsize must fit in u64"))
             .expect("transaction vsize must be positive");
-        let min_feerate_vb = prev_feerate_vb.checked_add(1).unwrap();
+        let min_feerate_kvb = prev_feerate_vb
+            .checked_add(1)
+            .and_then(|feerate_vb| feerate_vb.checked_mul(1_000))
+            .unwrap();
         Self {
             tx,
             is_cancel,
-            min_feerate_vb,
+            min_feerate_kvb,
             descendant_txids,
             feerate_val: form::Value {
                 valid: true,
                 warning: None,
-                value: min_feerate_vb.to_string(),
+                value: format_feerate_kvb(min_feerate_kvb),
             },
             // For cancel, we let `rbfpsbt` set the feerate.
-            feerate_vb: if is_cancel {
+            feerate_kvb: if is_cancel {
                 None
             } else {
-                Some(min_feerate_vb)
+                Some(min_feerate_kvb)
             },
             replacement_txid: None,
             warning: None,
@@ -412,16 +565,17 @@ impl CreateRbfModal {
         match message {
             Message::View(view::Message::CreateRbf(view::CreateRbfMessage::FeerateEdited(s))) => {
                 self.warning = None;
-                if let Ok(value) = s.parse::<u64>() {
-                    self.feerate_val.valid = value >= self.min_feerate_vb && value <= MAX_FEERATE;
+                if let Some(value) = parse_feerate_vb(&s) {
+                    self.feerate_val.valid =
+                        value >= self.min_feerate_kvb && value <= MAX_FEERATE_KVB;
                     if self.feerate_val.valid {
-                        self.feerate_vb = Some(value);
+                        self.feerate_kvb = Some(value);
                     }
                 } else {
                     self.feerate_val.valid = false;
                 }
                 if !self.feerate_val.valid {
-                    self.feerate_vb = None;
+                    self.feerate_kvb = None;
                 }
                 self.feerate_val.value = s; // save form value even if it cannot be parsed
             }
@@ -438,7 +592,7 @@ impl CreateRbfModal {
                 self.warning = None;
                 self.processing = true;
                 return Task::perform(
-                    rbf(daemon, self.tx.clone(), self.is_cancel, self.feerate_vb),
+                    rbf(daemon, self.tx.clone(), self.is_cancel, self.feerate_kvb),
                     Message::RbfPsbt,
                 );
             }
@@ -468,15 +622,118 @@ impl CreateRbfModal {
     }
 }
 
+#[derive(Debug)]
+pub struct CreateCpfpModal {
+    /// Parent transaction whose fee is to be bumped.
+    tx: model::HistoryTransaction,
+    /// Unconfirmed outputs of the parent belonging to this wallet.
+    outpoints: Vec<OutPoint>,
+    /// Feerate form value.
+    feerate_val: form::Value<String>,
+    /// Parsed target package feerate, in sats/kvb.
+    feerate_kvb: Option<u64>,
+    /// Child transaction ID.
+    child_txid: Option<Txid>,
+
+    processing: bool,
+    warning: Option<Error>,
+}
+
+impl CreateCpfpModal {
+    fn new(tx: model::HistoryTransaction, outpoints: Vec<OutPoint>) -> Self {
+        Self {
+            tx,
+            outpoints,
+            feerate_val: form::Value::default(),
+            feerate_kvb: None,
+            child_txid: None,
+            warning: None,
+            processing: false,
+        }
+    }
+
+    fn update(&mut self, daemon: Arc<dyn Daemon + Sync + Send>, message: Message) -> Task<Message> {
+        match message {
+            Message::View(view::Message::CreateCpfp(view::CreateCpfpMessage::FeerateEdited(s))) => {
+                self.warning = None;
+                self.feerate_kvb = parse_feerate_vb(&s)
+                    .filter(|value| (MIN_FEERATE_KVB..=MAX_FEERATE_KVB).contains(value));
+                self.feerate_val.valid = self.feerate_kvb.is_some();
+                self.feerate_val.value = s; // save form value even if it cannot be parsed
+            }
+            Message::CpfpPsbt(res) => {
+                self.processing = false;
+                match res {
+                    Ok(txid) => {
+                        self.child_txid = Some(txid);
+                    }
+                    Err(e) => self.warning = Some(e),
+                }
+            }
+            Message::View(view::Message::CreateCpfp(view::CreateCpfpMessage::Confirm)) => {
+                if let Some(feerate_kvb) = self.feerate_kvb {
+                    self.warning = None;
+                    self.processing = true;
+                    return Task::perform(
+                        cpfp(daemon, self.outpoints.clone(), feerate_kvb),
+                        Message::CpfpPsbt,
+                    );
+                }
+            }
+            _ => {}
+        }
+        Task::none()
+    }
+
+    fn view<'a>(&'a self, content: Element<'a, view::Message>) -> Element<'a, view::Message> {
+        let modal = Modal::new(
+            content,
+            view::vault::transactions::create_cpfp_modal(
+                &self.outpoints,
+                &self.feerate_val,
+                self.feerate_kvb.is_some(),
+                self.child_txid,
+                self.warning.as_ref(),
+            ),
+        );
+        if self.processing {
+            modal
+        } else {
+            modal.on_blur(Some(view::Message::CreateCpfp(
+                view::CreateCpfpMessage::Cancel,
+            )))
+        }
+        .into()
+    }
+}
+
+async fn cpfp(
+    daemon: Arc<dyn Daemon + Sync + Send>,
+    outpoints: Vec<OutPoint>,
+    feerate_kvb: u64,
+) -> Result<Txid, Error> {
+    let psbt = match daemon.cpfp(&outpoints, feerate_kvb).await? {
+        CreateSpendResult::Success { psbt, .. } => psbt,
+        CreateSpendResult::InsufficientFunds { missing } => {
+            return Err(SpendCreationError::CoinSelection(
+                coincube_core::spend::InsufficientFunds { missing },
+            )
+            .into());
+        }
+    };
+    daemon.update_spend_tx(&psbt).await?;
+    Ok(psbt.unsigned_tx.compute_txid())
+}
+
 async fn rbf(
     daemon: Arc<dyn Daemon + Sync + Send>,
     previous_tx: model::HistoryTransaction,
     is_cancel: bool,
-    feerate_vb: Option<u64>,
+    feerate_kvb: Option<u64>,
 ) -> Result<Txid, Error> {
     let previous_txid = previous_tx.tx.compute_txid();
     let psbt = match daemon
-        .rbf_psbt(&previous_txid, is_cancel, feerate_vb)
+        .rbf_psbt(&previous_txid, is_cancel, feerate_kvb)
         .await?
     {
         CreateSpendResult::Success { psbt, .. } => psbt,
@@ -515,11 +515,17 @@ fn confirm_transfer_view<'a>(
                             )
                             .push_maybe(receive_address.map(|addr| -> Element<'a, Message> {
                                 if address_expanded {
-                                    Button::new(address_card(0, addr, labels, labels_editing))
-                                        .padding(0)
-                                        .on_press(Message::SelectAddress(addr.clone()))
-                                        .style(theme::button::transparent_border)
-                                        .into()
+                                    Button::new(address_card(
+                                        0,
+                                        addr,
+                                        labels,
+                                        labels_editing,
+                                        false,
+                                    ))
+                                    .padding(0)
+                                    .on_press(Message::SelectAddress(addr.clone()))
+                                    .style(theme::button::transparent_border)
+                                    .into()
                                 } else {
                                     let addr_str = addr.to_string();
                                     let addr_len = addr_str.chars().count();
@@ -1,10 +1,13 @@
 use crate::{
+    accounting::CostBasis,
     app::{
         menu::Menu,
         view::{global_home::TransferDirection, FiatAmountConverter},
     },
+    download::{DownloadError, Progress},
+    drill::DrillKeys,
     export::ImportExportMessage,
-    node::bitcoind::RpcAuthType,
+    node::{bitcoind::RpcAuthType, release::ReleaseChecksums},
     services::fiat::{Currency, PriceSource},
 };
 
@@ -12,6 +15,7 @@ use crate::{
 use crate::services::mavapay::*;
 use coincube_core::miniscript::bitcoin::{bip32::Fingerprint, Address, OutPoint};
 use coincube_core::spend::SpendCreationError;
+use coincubed::commands::CoinStatus;
 
 pub trait Close {
     fn close() -> Self;
@@ -43,11 +47,20 @@ pub enum Message {
     Previous,
     SelectHardwareWallet(usize),
     CreateRbf(CreateRbfMessage),
+    CreateCpfp(CreateCpfpMessage),
     ShowQrCode(usize),
     ImportExport(ImportExportMessage),
+    Search(SearchMessage),
+    Accounting(AccountingMessage),
     HideRescanWarning,
+    FutureFeerateEdited(String),
     ExportPsbt,
+    ExportPsbtV2,
     ImportPsbt,
+    ExportPsbtColdcard,
+    ImportPsbtColdcard,
+    ShowPsbtQr,
+    NextQrFrame,
     OpenUrl(String),
     Home(HomeMessage),
 }
@@ -58,6 +71,21 @@ impl Close for Message {
     }
 }
 
+#[derive(Debug, Clone)]
+pub enum SearchMessage {
+    Toggle,
+    FieldEdited(&'static str, String),
+    StatusSelected(Option<CoinStatus>),
+    Apply,
+    Clear,
+}
+
+#[derive(Debug, Clone)]
+pub enum AccountingMessage {
+    CostBasisSelected(CostBasis),
+    Export,
+}
+
 #[derive(Debug, Clone)]
 pub enum LabelMessage {
     Edited(String),
@@ -73,15 +101,45 @@ pub enum CreateSpendMessage {
     SelectCoin(usize),
     RecipientEdited(usize, &'static str, String),
     RecipientFiatAmountEdited(usize, String, FiatAmountConverter),
+    RecipientContactSelected(usize, ContactAddress),
     FeerateEdited(String),
     SelectPath(usize),
     Generate,
     SendMaxToRecipient(usize),
+    SubtractFeeFromRecipient(usize),
+    AddOpReturn,
+    DeleteOpReturn,
+    OpReturnEdited(String),
+    OpReturnIsHex(bool),
     FetchFeeEstimate(usize),
     SessionError(SpendCreationError),
     Clear,
 }
 
+/// A payment address of a contact of the address book, to pick as a spend recipient.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContactAddress {
+    /// Index of the contact in the address book.
+    pub contact: usize,
+    pub name: String,
+    pub address: String,
+    /// Whether the address is derived from a descriptor of the contact.
+    pub derived: bool,
+}
+
+impl std::fmt::Display for ContactAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let chars: Vec<char> = self.address.chars().collect();
+        if chars.len() > 20 {
+            let start: String = chars[..10].iter().collect();
+            let end: String = chars[chars.len() - 8..].iter().collect();
+            write!(f, "{} ({}...{})", self.name, start, end)
+        } else {
+            write!(f, "{} ({})", self.name, self.address)
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum ImportSpendMessage {
     Import,
@@ -94,10 +152,16 @@ pub enum SpendTxMessage {
     Delete,
     Sign,
     Broadcast,
+    Approve,
     Save,
     Confirm,
     Cancel,
+    ScheduleBlockHeightEdited(String),
+    ScheduleDateEdited(String),
+    ScheduleBroadcast,
+    CancelScheduledBroadcast,
     SelectHotSigner,
+    AllowNonStandardSighash(bool),
     EditPsbt,
     PsbtEdited(String),
     Next,
@@ -109,6 +173,7 @@ pub enum SettingsMessage {
     BitcoindSettings(SettingsEditMessage),
     ElectrumSettings(SettingsEditMessage),
     RescanSettings(SettingsEditMessage),
+    ManagedBitcoind(ManagedBitcoindMessage),
     ImportExport(ImportExportMessage),
     EditRemoteBackendSettings,
     RemoteBackendSettings(RemoteBackendSettingsMessage),
@@ -116,8 +181,11 @@ pub enum SettingsMessage {
     ImportExportSection,
     ExportEncryptedDescriptor,
     ExportPlaintextDescriptor,
+    ExportColdcardRegistration,
+    ShowDescriptorQr,
     ExportTransactions,
     ExportLabels,
+    ImportLabels,
     ExportWallet,
     ImportWallet,
     AboutSection,
@@ -127,6 +195,70 @@ pub enum SettingsMessage {
     Save,
     GeneralSection,
     Fiat(FiatMessage),
+    MessageSection,
+    SignMessage(SignMessageMessage),
+    ContactsSection,
+    Contacts(ContactsMessage),
+    HeirSection,
+    Heir(HeirMessage),
+    DrillSection,
+    Drill(DrillMessage),
+}
+
+#[derive(Debug, Clone)]
+pub enum ContactsMessage {
+    New,
+    Edit(usize),
+    Delete(usize),
+    FieldEdited(&'static str, String),
+    VerifiedToday,
+    Save,
+    Cancel,
+}
+
+#[derive(Debug, Clone)]
+pub enum HeirMessage {
+    FieldEdited(&'static str, String),
+    Create,
+}
+
+#[derive(Debug, Clone)]
+pub enum DrillMessage {
+    KeysSelected(DrillKeys),
+    TimelockSelected(u16),
+    FieldEdited(&'static str, String),
+    Start,
+    CreateRecovery,
+    SignWithHotKeys,
+    Broadcast,
+    End,
+}
+
+#[derive(Debug, Clone)]
+pub enum SignMessageMessage {
+    KindSelected(MessageProofKind),
+    AddressEdited(String),
+    MessageEdited(String),
+    SelectCoin(usize),
+    Sign,
+    Cancel,
+    ExportProof,
+    VerifyAddressEdited(String),
+    VerifyMessageEdited(String),
+    SignatureEdited(String),
+    Verify,
+    VerifyProofFile,
+}
+
+/// What to prove control over with a BIP-0322 signed message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageProofKind {
+    /// An address, with a signature in the simple format.
+    Simple,
+    /// An address, with a signature in the full format.
+    Full,
+    /// A set of coins, as a proof of reserves file.
+    Reserves,
 }
 
 #[derive(Debug, Clone)]
@@ -146,6 +278,19 @@ pub enum SettingsEditMessage {
     Clipboard(String),
 }
 
+#[derive(Debug, Clone)]
+pub enum ManagedBitcoindMessage {
+    Edit,
+    Cancel,
+    FieldEdited(&'static str, String),
+    ListenToggled(bool),
+    Save,
+    UpgradeVersionSelected(&'static str),
+    Upgrade,
+    DownloadProgressed(Result<Progress, DownloadError>),
+    ChecksumsFetched(Result<ReleaseChecksums, String>),
+}
+
 #[derive(Debug, Clone)]
 pub enum CreateRbfMessage {
     New(bool),
@@ -154,6 +299,14 @@ pub enum CreateRbfMessage {
     Confirm,
 }
 
+#[derive(Debug, Clone)]
+pub enum CreateCpfpMessage {
+    New,
+    FeerateEdited(String),
+    Cancel,
+    Confirm,
+}
+
 #[cfg(feature = "buysell")]
 #[derive(Debug, Clone)]
 pub enum BuySellMessage {
@@ -236,6 +389,30 @@ impl From<FiatMessage> for Message {
     }
 }
 
+impl From<SignMessageMessage> for Message {
+    fn from(msg: SignMessageMessage) -> Self {
+        Message::Settings(SettingsMessage::SignMessage(msg))
+    }
+}
+
+impl From<ContactsMessage> for Message {
+    fn from(msg: ContactsMessage) -> Self {
+        Message::Settings(SettingsMessage::Contacts(msg))
+    }
+}
+
+impl From<HeirMessage> for Message {
+    fn from(msg: HeirMessage) -> Self {
+        Message::Settings(SettingsMessage::Heir(msg))
+    }
+}
+
+impl From<DrillMessage> for Message {
+    fn from(msg: DrillMessage) -> Self {
+        Message::Settings(SettingsMessage::Drill(msg))
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum HomeMessage {
     ToggleBalanceMask,
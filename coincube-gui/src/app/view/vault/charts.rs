@@ -0,0 +1,473 @@
+use chrono::{DateTime, Local, Utc};
+use iced::{
+    alignment, mouse,
+    widget::canvas::{self, Canvas, Frame, Geometry, Path, Stroke},
+    Color, Length, Pixels, Point, Rectangle, Size,
+};
+
+use coincube_core::miniscript::bitcoin::Amount;
+use coincube_ui::{
+    color,
+    component::{amount::format_f64_as_string, text::*},
+    theme::{self, Theme},
+    widget::*,
+};
+
+use crate::{
+    app::view::{message::Message, FiatAmountConverter},
+    daemon::model::{remaining_sequence, BalanceHistoryEntry, Coin},
+};
+
+const CHART_HEIGHT: f32 = 200.0;
+const LABEL_WIDTH: f32 = 110.0;
+const LABEL_SIZE: f32 = 12.0;
+
+/// Coins ages, in blocks, and the label of the bucket they fall in if younger.
+const AGE_BUCKETS: [(u32, &str); 5] = [
+    (144, "< 1 day"),
+    (1008, "< 1 week"),
+    (4320, "< 1 month"),
+    (26298, "< 6 months"),
+    (52596, "< 1 year"),
+];
+
+/// Blocks left before the recovery path of a coin becomes available, and the label of the
+/// bucket it falls in if fewer.
+const EXPIRY_BUCKETS: [(u32, &str); 5] = [
+    (1, "Available"),
+    (1008, "< 1 week"),
+    (4320, "< 1 month"),
+    (12960, "< 3 months"),
+    (26298, "< 6 months"),
+];
+
+/// The value and number of coins in a bucket of a bar chart.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bar {
+    pub label: &'static str,
+    pub value: Amount,
+    pub count: usize,
+}
+
+/// Balance history, UTXO age and recovery path expiry charts of the vault overview.
+pub fn analytics_view<'a>(
+    history: &[BalanceHistoryEntry],
+    coins: &[Coin],
+    tip_height: u32,
+    timelock: u16,
+    fiat_converter: Option<FiatAmountConverter>,
+) -> Element<'a, Message> {
+    if history.is_empty() && coins.is_empty() {
+        return Column::new().into();
+    }
+    Column::new()
+        .spacing(10)
+        .push(h4_bold("Analytics"))
+        .push_maybe((!history.is_empty()).then(|| {
+            chart_card(
+                "Balance history",
+                BalanceChart {
+                    points: history.iter().map(|e| (e.time, e.balance)).collect(),
+                    end: history
+                        .last()
+                        .map(|e| e.time)
+                        .unwrap_or_default()
+                        .max(crate::utils::now().as_secs() as u32),
+                    fiat_converter,
+                },
+            )
+        }))
+        .push(chart_card(
+            "Coins by age",
+            BarChart {
+                bars: utxo_age_bars(coins, tip_height),
+                color: color::BLUE,
+                fiat_converter,
+            },
+        ))
+        .push(chart_card(
+            "Coins by recovery path availability",
+            BarChart {
+                bars: expiry_bars(coins, tip_height, timelock),
+                color: color::ORANGE,
+                fiat_converter,
+            },
+        ))
+        .push_maybe(fiat_converter.map(|_| {
+            text("Fiat values are computed at the current exchange rate.")
+                .small()
+                .style(theme::text::secondary)
+        }))
+        .into()
+}
+
+fn chart_card<'a, P: canvas::Program<Message, Theme> + 'a>(
+    title: &'static str,
+    program: P,
+) -> Element<'a, Message> {
+    Container::new(
+        Column::new().spacing(10).push(text(title).bold()).push(
+            Canvas::new(program)
+                .width(Length::Fill)
+                .height(Length::Fixed(CHART_HEIGHT)),
+        ),
+    )
+    .padding(15)
+    .style(theme::card::simple)
+    .into()
+}
+
+/// Sum the coins by age, with the unconfirmed ones first.
+pub fn utxo_age_bars(coins: &[Coin], tip_height: u32) -> Vec<Bar> {
+    let mut bars = vec![Bar {
+        label: "Unconfirmed",
+        value: Amount::ZERO,
+        count: 0,
+    }];
+    bars.extend(buckets(
+        &AGE_BUCKETS,
+        "> 1 year",
+        coins.iter().filter_map(|coin| {
+            let height = coin.block_height? as u32;
+            Some((tip_height.saturating_sub(height), coin.amount))
+        }),
+    ));
+    for coin in coins.iter().filter(|c| c.block_height.is_none()) {
+        bars[0].value += coin.amount;
+        bars[0].count += 1;
+    }
+    bars
+}
+
+/// Sum the coins by the number of blocks left before their recovery path becomes available.
+pub fn expiry_bars(coins: &[Coin], tip_height: u32, timelock: u16) -> Vec<Bar> {
+    buckets(
+        &EXPIRY_BUCKETS,
+        "Later",
+        coins
+            .iter()
+            .map(|coin| (remaining_sequence(coin, tip_height, timelock), coin.amount)),
+    )
+}
+
+fn buckets(
+    thresholds: &[(u32, &'static str)],
+    last: &'static str,
+    values: impl Iterator<Item = (u32, Amount)>,
+) -> Vec<Bar> {
+    let mut bars: Vec<Bar> = thresholds
+        .iter()
+        .map(|(_, label)| *label)
+        .chain(std::iter::once(last))
+        .map(|label| Bar {
+            label,
+            value: Amount::ZERO,
+            count: 0,
+        })
+        .collect();
+    for (blocks, value) in values {
+        let i = thresholds
+            .iter()
+            .position(|(threshold, _)| blocks < *threshold)
+            .unwrap_or(thresholds.len());
+        bars[i].value += value;
+        bars[i].count += 1;
+    }
+    bars
+}
+
+fn btc_label(amount: Amount) -> String {
+    format!(
+        "{} BTC",
+        format_f64_as_string(amount.to_btc(), ",", 8, false)
+    )
+}
+
+fn fiat_label(converter: &FiatAmountConverter, amount: Amount) -> String {
+    let fiat = converter.convert(amount);
+    format!("~{} {}", fiat.to_rounded_string(), fiat.currency())
+}
+
+fn label(content: String, position: Point, color: Color) -> canvas::Text {
+    canvas::Text {
+        content,
+        position,
+        color,
+        size: Pixels(LABEL_SIZE),
+        horizontal_alignment: alignment::Horizontal::Center,
+        vertical_alignment: alignment::Vertical::Center,
+        ..Default::default()
+    }
+}
+
+/// Balance over time, as a step line from the first transaction to `end`.
+struct BalanceChart {
+    points: Vec<(u32, Amount)>,
+    end: u32,
+    fiat_converter: Option<FiatAmountConverter>,
+}
+
+impl canvas::Program<Message, Theme> for BalanceChart {
+    type State = ();
+
+    fn draw(
+        &self,
+        _state: &Self::State,
+        renderer: &Renderer,
+        _theme: &Theme,
+        bounds: Rectangle,
+        _cursor: mouse::Cursor,
+    ) -> Vec<Geometry> {
+        let Some((start, _)) = self.points.first() else {
+            return Vec::new();
+        };
+        let mut frame = Frame::new(renderer, bounds.size());
+        let right_margin = if self.fiat_converter.is_some() {
+            LABEL_WIDTH
+        } else {
+            0.0
+        };
+        let plot = Rectangle {
+            x: LABEL_WIDTH,
+            y: LABEL_SIZE,
+            width: (bounds.width - LABEL_WIDTH - right_margin).max(1.0),
+            height: (bounds.height - 3.0 * LABEL_SIZE).max(1.0),
+        };
+        let max = self
+            .points
+            .iter()
+            .map(|(_, balance)| *balance)
+            .max()
+            .unwrap_or_default()
+            .max(Amount::ONE_SAT);
+        let x = |time: u32| {
+            let span = self.end.saturating_sub(*start).max(1) as f32;
+            plot.x + plot.width * time.saturating_sub(*start) as f32 / span
+        };
+        let y = |balance: Amount| {
+            plot.y + plot.height * (1.0 - (balance.to_sat() as f64 / max.to_sat() as f64) as f32)
+        };
+
+        // Horizontal grid lines with the BTC values on the left and fiat ones on the right.
+        for amount in [Amount::ZERO, max / 2, max] {
+            let line_y = y(amount);
+            frame.stroke(
+                &Path::line(
+                    Point::new(plot.x, line_y),
+                    Point::new(plot.x + plot.width, line_y),
+                ),
+                Stroke::default().with_color(color::GREY_4).with_width(1.0),
+            );
+            frame.fill_text(label(
+                btc_label(amount),
+                Point::new(LABEL_WIDTH / 2.0, line_y),
+                color::GREY_3,
+            ));
+            if let Some(converter) = &self.fiat_converter {
+                frame.fill_text(label(
+                    fiat_label(converter, amount),
+                    Point::new(plot.x + plot.width + LABEL_WIDTH / 2.0, line_y),
+                    color::GREY_3,
+                ));
+            }
+        }
+
+        let line = Path::new(|builder| {
+            let mut previous: Option<Amount> = None;
+            for (time, balance) in &self.points {
+                match previous {
+                    None => builder.move_to(Point::new(x(*time), y(*balance))),
+                    Some(previous) => {
+                        builder.line_to(Point::new(x(*time), y(previous)));
+                        builder.line_to(Point::new(x(*time), y(*balance)));
+                    }
+                }
+                previous = Some(*balance);
+            }
+            if let Some(balance) = previous {
+                builder.line_to(Point::new(x(self.end), y(balance)));
+            }
+        });
+        frame.stroke(
+            &line,
+            Stroke::default().with_color(color::ORANGE).with_width(2.0),
+        );
+
+        let date = |time: u32| {
+            DateTime::<Utc>::from_timestamp(time as i64, 0)
+                .expect("Correct unix timestamp")
+                .with_timezone(&Local)
+                .format("%b. %d, %Y")
+                .to_string()
+        };
+        let dates_y = bounds.height - LABEL_SIZE / 2.0;
+        let mut first = label(date(*start), Point::new(plot.x, dates_y), color::GREY_3);
+        first.horizontal_alignment = alignment::Horizontal::Left;
+        frame.fill_text(first);
+        let mut last = label(
+            date(self.end),
+            Point::new(plot.x + plot.width, dates_y),
+            color::GREY_3,
+        );
+        last.horizontal_alignment = alignment::Horizontal::Right;
+        frame.fill_text(last);
+
+        vec![frame.into_geometry()]
+    }
+}
+
+/// Coins value by bucket, with the number of coins and their value above each bar.
+struct BarChart {
+    bars: Vec<Bar>,
+    color: Color,
+    fiat_converter: Option<FiatAmountConverter>,
+}
+
+impl canvas::Program<Message, Theme> for BarChart {
+    type State = ();
+
+    fn draw(
+        &self,
+        _state: &Self::State,
+        renderer: &Renderer,
+        _theme: &Theme,
+        bounds: Rectangle,
+        _cursor: mouse::Cursor,
+    ) -> Vec<Geometry> {
+        if self.bars.is_empty() {
+            return Vec::new();
+        }
+        let mut frame = Frame::new(renderer, bounds.size());
+        let max = self
+            .bars
+            .iter()
+            .map(|bar| bar.value)
+            .max()
+            .unwrap_or_default()
+            .max(Amount::ONE_SAT);
+        let slot = bounds.width / self.bars.len() as f32;
+        // Room for the captions above the bars and the labels below.
+        let caption_lines = if self.fiat_converter.is_some() {
+            3.0
+        } else {
+            2.0
+        };
+        let top = caption_lines * LABEL_SIZE * 1.5;
+        let bottom = bounds.height - LABEL_SIZE * 1.5;
+        let plot_height = (bottom - top).max(1.0);
+
+        for (i, bar) in self.bars.iter().enumerate() {
+            let center = slot * (i as f32 + 0.5);
+            let height = plot_height * (bar.value.to_sat() as f64 / max.to_sat() as f64) as f32;
+            frame.fill_rectangle(
+                Point::new(center - slot * 0.3, bottom - height),
+                Size::new(slot * 0.6, height),
+                self.color,
+            );
+            frame.fill_text(label(
+                bar.label.to_string(),
+                Point::new(center, bottom + LABEL_SIZE * 0.75),
+                color::GREY_3,
+            ));
+
+            let mut captions = vec![format!("{} coin(s)", bar.count), btc_label(bar.value)];
+            if let Some(converter) = &self.fiat_converter {
+                captions.push(fiat_label(converter, bar.value));
+            }
+            let captions_bottom = bottom - height - LABEL_SIZE * 0.75;
+            for (j, caption) in captions.iter().rev().enumerate() {
+                frame.fill_text(label(
+                    caption.clone(),
+                    Point::new(center, captions_bottom - j as f32 * LABEL_SIZE * 1.5),
+                    color::GREY_2,
+                ));
+            }
+        }
+
+        vec![frame.into_geometry()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use coincube_core::miniscript::bitcoin;
+    use std::str::FromStr;
+
+    fn coin(vout: u32, sats: u64, block_height: Option<i32>) -> Coin {
+        Coin {
+            outpoint: bitcoin::OutPoint::new(
+                bitcoin::Txid::from_str(
+                    "f7bd1b2a995b689d326e51eb742eb1088c4a8f110d9cb56128fd553acc9f88e5",
+                )
+                .unwrap(),
+                vout,
+            ),
+            amount: Amount::from_sat(sats),
+            address: bitcoin::Address::from_str("bc1qvrl2849aggm6qry9ea7xqp2kk39j8vaa8r3cwg")
+                .unwrap()
+                .assume_checked(),
+            derivation_index: bitcoin::bip32::ChildNumber::Normal { index: 0 },
+            block_height,
+            is_immature: false,
+            is_change: false,
+            is_from_self: false,
+            spend_info: None,
+        }
+    }
+
+    #[test]
+    fn test_coins_bars() {
+        let tip_height = 800_000;
+        let coins = [
+            coin(0, 1_000, None),
+            coin(1, 2_000, Some(800_000)),
+            coin(2, 3_000, Some(799_900)),
+            coin(3, 4_000, Some(790_000)),
+            coin(4, 5_000, Some(100_000)),
+        ];
+
+        let bars = utxo_age_bars(&coins, tip_height);
+        let values: Vec<_> = bars
+            .iter()
+            .map(|b| (b.label, b.count, b.value.to_sat()))
+            .collect();
+        assert_eq!(
+            values,
+            vec![
+                ("Unconfirmed", 1, 1_000),
+                ("< 1 day", 2, 5_000),
+                ("< 1 week", 0, 0),
+                ("< 1 month", 0, 0),
+                ("< 6 months", 1, 4_000),
+                ("< 1 year", 0, 0),
+                ("> 1 year", 1, 5_000),
+            ]
+        );
+
+        // With a timelock of 52596 blocks, the coin confirmed at height 100_000 is expired, the
+        // one at 790_000 has 42596 blocks left and the others more. The unconfirmed one has
+        // the whole timelock left.
+        let bars = expiry_bars(&coins, tip_height, 52596);
+        let values: Vec<_> = bars
+            .iter()
+            .map(|b| (b.label, b.count, b.value.to_sat()))
+            .collect();
+        assert_eq!(
+            values,
+            vec![
+                ("Available", 1, 5_000),
+                ("< 1 week", 0, 0),
+                ("< 1 month", 0, 0),
+                ("< 3 months", 0, 0),
+                ("< 6 months", 0, 0),
+                ("Later", 4, 10_000),
+            ]
+        );
+        // With a timelock of 1000 blocks, only the two most recent confirmed coins and the
+        // unconfirmed one are not yet expired.
+        let bars = expiry_bars(&coins, tip_height, 1000);
+        assert_eq!(bars[0].count, 2);
+        assert_eq!(bars[1].count, 3);
+    }
+}
@@ -2,25 +2,38 @@ use std::collections::HashMap;
 
 use iced::{widget::Space, Alignment, Length};
 
+use coincube_core::{
+    miniscript::bitcoin::OutPoint,
+    privacy::{PrivacyAssessment, PrivacyWarning},
+};
 use coincube_ui::{
     component::{amount::*, badge, button, form, text::*},
     icon::{self, coins_icon},
     theme,
     widget::*,
 };
+use coincubed::commands::CoinStatus;
 
 use crate::{
     app::{
         cache::Cache,
         menu::Menu,
-        view::{message::Message, placeholder, vault::label},
+        state::vault::search::SearchForm,
+        view::{
+            message::Message,
+            placeholder,
+            vault::{label, search::search_bar},
+        },
     },
     daemon::model::{remaining_sequence, Coin},
 };
 
+#[allow(clippy::too_many_arguments)]
 pub fn coins_view<'a>(
     cache: &Cache,
     coins: &'a [Coin],
+    privacy: &'a HashMap<OutPoint, PrivacyAssessment>,
+    search: Option<&'a SearchForm>,
     timelock: u16,
     selected: &[usize],
     labels: &'a HashMap<String, String>,
@@ -28,12 +41,23 @@ pub fn coins_view<'a>(
 ) -> Element<'a, Message> {
     Column::new()
         .push(Container::new(h3("Coins")).width(Length::Fill))
+        .push_maybe(
+            search.map(|s| search_bar(s, &[CoinStatus::Unconfirmed, CoinStatus::Confirmed])),
+        )
         .push_maybe(coins.is_empty().then(|| {
-            placeholder(
-                coins_icon().size(80),
-                "No coins yet",
-                "Your coins will appear here once you receive bitcoin.",
-            )
+            if search.is_some_and(|s| s.applied.is_some()) {
+                placeholder(
+                    coins_icon().size(80),
+                    "No matching coins",
+                    "No coin matches the current filters.",
+                )
+            } else {
+                placeholder(
+                    coins_icon().size(80),
+                    "No coins yet",
+                    "Your coins will appear here once you receive bitcoin.",
+                )
+            }
         }))
         .push(
             Column::new()
@@ -47,6 +71,7 @@ pub fn coins_view<'a>(
                             cache.blockheight() as u32,
                             i,
                             selected.contains(&i),
+                            privacy.get(&coin.outpoint),
                             labels,
                             labels_editing,
                         ))
@@ -59,12 +84,14 @@ pub fn coins_view<'a>(
 }
 
 #[allow(clippy::collapsible_else_if)]
+#[allow(clippy::too_many_arguments)]
 fn coin_list_view<'a>(
     coin: &'a Coin,
     timelock: u16,
     blockheight: u32,
     index: usize,
     collapsed: bool,
+    privacy: Option<&'a PrivacyAssessment>,
     labels: &'a HashMap<String, String>,
     labels_editing: &'a HashMap<String, form::Value<String>>,
 ) -> Container<'a, Message> {
@@ -72,6 +99,11 @@ fn coin_list_view<'a>(
     let address = coin.address.to_string();
     let txid = coin.outpoint.txid.to_string();
     let seq = remaining_sequence(coin, blockheight, timelock);
+    let reused = privacy.is_some_and(|p| {
+        p.warnings
+            .iter()
+            .any(|w| matches!(w, PrivacyWarning::ReusedCoinAddress { .. }))
+    });
     Container::new(
         Column::new()
             .push(
@@ -124,6 +156,7 @@ fn coin_list_view<'a>(
                                     Container::new(Space::with_width(Length::Fill))
                                         .width(Length::Fill)
                                 })
+                                .push_maybe(reused.then(badge::reused))
                                 .push(if coin.spend_info.is_some() {
                                     badge::spent()
                                 } else if coin.block_height.is_none() {
@@ -273,6 +306,30 @@ fn coin_list_view<'a>(
                                                 .style(theme::text::secondary),
                                         )
                                         .spacing(5)
+                                }))
+                                .push_maybe(privacy.map(|privacy| {
+                                    Row::new()
+                                        .push(
+                                            p2_regular("Privacy score:")
+                                                .bold()
+                                                .style(theme::text::secondary),
+                                        )
+                                        .push(
+                                            p2_regular(format!("{}/100", privacy.score()))
+                                                .style(theme::text::secondary),
+                                        )
+                                        .spacing(5)
+                                }))
+                                .push_maybe(privacy.map(|privacy| {
+                                    privacy.warnings.iter().fold(
+                                        Column::new().spacing(5),
+                                        |col, warning| {
+                                            col.push(
+                                                p2_regular(warning.to_string())
+                                                    .style(theme::text::secondary),
+                                            )
+                                        },
+                                    )
                                 })),
                         )
                         .push(if let Some(info) = coin.spend_info {
@@ -8,10 +8,11 @@ use coincube_ui::{
 };
 use iced::{
     alignment::{self, Horizontal},
-    widget::{progress_bar, Column, Container, Row, Space},
+    widget::{progress_bar, qr_code::QRCode, Column, Container, Row, Space},
     Length,
 };
 
+use crate::bcur::AnimatedQr;
 use crate::export::ImportExportState;
 use crate::export::{Error, ImportExportMessage, ImportExportType};
 
@@ -19,6 +20,7 @@ use crate::export::{Error, ImportExportMessage, ImportExportType};
 pub fn export_modal<'a, Message: From<ImportExportMessage> + Clone + 'static>(
     state: &ImportExportState,
     error: Option<&'a Error>,
+    summary: Option<&'a str>,
     title: &str,
     import_export_type: &ImportExportType,
 ) -> Element<'a, Message> {
@@ -53,7 +55,9 @@ pub fn export_modal<'a, Message: From<ImportExportMessage> + Clone + 'static>(
             ImportExportState::Progress(p) => format!("Progress: {}%", p.round()),
             ImportExportState::TimedOut => "Export failed: timeout".into(),
             ImportExportState::Aborted => "Export canceled".into(),
-            ImportExportState::Ended => import_export_type.end_message().into(),
+            ImportExportState::Ended => summary
+                .map(|s| format!("{}\n{}", import_export_type.end_message(), s))
+                .unwrap_or_else(|| import_export_type.end_message().into()),
             ImportExportState::Closed => "".into(),
         }
     };
@@ -85,6 +89,20 @@ pub fn export_modal<'a, Message: From<ImportExportMessage> + Clone + 'static>(
                 ),
         )),
     );
+    let import_labels_btn = (
+        "Some labels conflict with existing ones, what do you want to do?".to_string(),
+        Some(Container::new(
+            Row::new()
+                .push(button::secondary(None, "Merge").on_press(ImportExportMessage::Merge.into()))
+                .push(Space::with_width(30))
+                .push(
+                    button::secondary(None, "Overwrite")
+                        .on_press(ImportExportMessage::Overwrite.into()),
+                )
+                .push(Space::with_width(30))
+                .push(button::secondary(None, "Skip").on_press(ImportExportMessage::Ignore.into())),
+        )),
+    );
     let (msg, button) = match import_export_type {
         ImportExportType::ImportBackup {
             overwrite_labels,
@@ -96,6 +114,9 @@ pub fn export_modal<'a, Message: From<ImportExportMessage> + Clone + 'static>(
             (_, Some(_)) => aliases_btn,
             _ => (msg, cancel),
         },
+        ImportExportType::ImportLabels {
+            conflict: Some(_), ..
+        } => import_labels_btn,
         _ => (msg, cancel),
     };
     let button = button.map(|b| {
@@ -146,3 +167,38 @@ pub fn export_modal<'a, Message: From<ImportExportMessage> + Clone + 'static>(
     .height(Length::Fixed(300.0))
     .into()
 }
+
+/// Return the modal view displaying an animated BC-UR QR code to be scanned by a signing device
+pub fn ur_qr_modal<'a, Message: Clone + 'static>(
+    qr: &'a AnimatedQr,
+    title: &'static str,
+    close: Message,
+) -> Element<'a, Message> {
+    card::simple(
+        Column::new()
+            .spacing(10)
+            .push(
+                Row::new()
+                    .push(h4_bold(title))
+                    .push(Space::with_width(Length::Fill))
+                    .push(
+                        button::transparent(Some(icon::cross_icon().size(30)), "").on_press(close),
+                    )
+                    .align_y(alignment::Vertical::Center),
+            )
+            .push(
+                Container::new(QRCode::<coincube_ui::theme::Theme>::new(qr.data()).cell_size(5))
+                    .padding(10)
+                    .center_x(Length::Fill),
+            )
+            .push(
+                Container::new(text(format!(
+                    "Scan the animated QR code with your signing device ({} parts)",
+                    qr.fragment_count()
+                )))
+                .center_x(Length::Fill),
+            ),
+    )
+    .width(Length::Fixed(500.0))
+    .into()
+}
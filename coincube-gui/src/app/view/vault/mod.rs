@@ -1,5 +1,6 @@
 mod label;
 
+pub mod charts;
 pub mod coins;
 pub mod export;
 pub mod fiat;
@@ -9,6 +10,7 @@ pub mod psbt;
 pub mod psbts;
 pub mod receive;
 pub mod recovery;
+pub mod search;
 pub mod settings;
 pub mod spend;
 pub mod transactions;
@@ -8,7 +8,7 @@ use iced::{
     Length,
 };
 
-use coincube_core::miniscript::bitcoin;
+use coincube_core::{miniscript::bitcoin, spend::format_feerate_kvb};
 use coincube_ui::{
     color,
     component::{amount::*, button, card, event, form, spinner, text::*},
@@ -25,7 +25,9 @@ use crate::{
         view::{dashboard, message::Message, vault::coins, vault::label, FiatAmountConverter},
         wallet::SyncStatus,
     },
-    daemon::model::{HistoryTransaction, Payment, PaymentKind, TransactionKind},
+    daemon::model::{
+        ConsolidationAdvice, HistoryTransaction, Payment, PaymentKind, TransactionKind,
+    },
 };
 
 const RESCAN_WARNING: &str = "As this wallet was restored from a backup, you may need to rescan the blockchain to see past transactions.";
@@ -75,6 +77,8 @@ pub fn vault_overview_view<'a>(
     processing: bool,
     sync_status: &SyncStatus,
     show_rescan_warning: bool,
+    consolidation: Option<Element<'a, Message>>,
+    analytics: Element<'a, Message>,
 ) -> Element<'a, Message> {
     let fiat_balance = fiat_converter.as_ref().map(|c| c.convert(*balance));
     let fiat_unconfirmed = fiat_converter.map(|c| c.convert(*unconfirmed_balance));
@@ -209,6 +213,8 @@ pub fn vault_overview_view<'a>(
                 .style(theme::card::invalid),
             )
         })
+        .push_maybe(consolidation)
+        .push(analytics)
         .push(
             Column::new()
                 .spacing(10)
@@ -252,6 +258,77 @@ pub fn vault_overview_view<'a>(
         .into()
 }
 
+/// Advise to consolidate small coins while fees are low, before they become costly to spend.
+pub fn consolidation_card<'a>(
+    advice: Option<&'a ConsolidationAdvice>,
+    feerate_kvb: u64,
+    future_feerate: &'a form::Value<String>,
+) -> Element<'a, Message> {
+    Container::new(
+        Column::new()
+            .spacing(10)
+            .push(h4_bold("Coins consolidation"))
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .align_y(Alignment::Center)
+                    .push(
+                        p2_regular(format!(
+                            "Fees are currently around {} sats/vbyte. Expected feerate when \
+                             spending later (sats/vbyte):",
+                            format_feerate_kvb(feerate_kvb)
+                        ))
+                        .style(theme::text::secondary)
+                        .width(Length::Fill),
+                    )
+                    .push(
+                        Container::new(
+                            form::Form::new_trimmed(
+                                "50",
+                                future_feerate,
+                                Message::FutureFeerateEdited,
+                            )
+                            .warning("Must be higher than the current feerate")
+                            .size(P2_SIZE)
+                            .padding(5),
+                        )
+                        .width(Length::Fixed(120.0)),
+                    ),
+            )
+            .push(match advice {
+                Some(advice) => Row::new()
+                    .spacing(15)
+                    .align_y(Alignment::Center)
+                    .push(
+                        Container::new(
+                            Row::new()
+                                .spacing(5)
+                                .align_y(Alignment::Center)
+                                .push(p1_regular(format!(
+                                    "Consolidating {} small coins now costs",
+                                    advice.outpoints.len()
+                                )))
+                                .push(amount(&advice.fee))
+                                .push(p1_regular("and would save"))
+                                .push(amount(&advice.savings))
+                                .wrap(),
+                        )
+                        .width(Length::Fill),
+                    )
+                    .push(button::primary(None, "Consolidate").on_press(Message::Menu(
+                        Menu::Vault(menu::VaultSubMenu::Coins(Some(advice.outpoints.clone()))),
+                    ))),
+                None => Row::new().push(
+                    p1_regular("No coins are worth consolidating at these feerates.")
+                        .style(theme::text::secondary),
+                ),
+            }),
+    )
+    .padding(25)
+    .style(theme::card::border)
+    .into()
+}
+
 fn event_list_view(event: &Payment) -> Element<'_, Message> {
     let label = if let Some(label) = &event.label {
         Some(p1_regular(label))
@@ -1,7 +1,8 @@
 use std::collections::{HashMap, HashSet};
 
+use chrono::{DateTime, Local, Utc};
 use iced::{
-    widget::{scrollable, tooltip, Space},
+    widget::{checkbox, scrollable, tooltip, Space},
     Alignment, Length,
 };
 
@@ -9,10 +10,17 @@ use coincube_core::descriptors::CoincubeDescriptor;
 use coincube_core::{
     descriptors::{CoincubePolicy, PathInfo, PathSpendInfo},
     miniscript::bitcoin::{
-        bip32::Fingerprint, blockdata::transaction::TxOut, Address, Network, OutPoint, Transaction,
-        Txid,
+        bip32::Fingerprint,
+        blockdata::transaction::TxOut,
+        hex::DisplayHex,
+        psbt::{Input as PsbtIn, PsbtSighashType},
+        Address, Amount, Network, OutPoint, Transaction, Txid,
     },
+    privacy::PrivacyAssessment,
+    signer,
+    spend::{format_feerate_kvb, op_return_data},
 };
+use coincubed::policy::SpendingPolicy;
 
 use coincube_ui::{
     component::{
@@ -35,7 +43,7 @@ use crate::{
             dashboard, message::*, vault::hw::hw_list_view, vault::label, vault::warning::warn,
         },
     },
-    daemon::model::{Coin, SpendStatus, SpendTx},
+    daemon::model::{Coin, ScheduledBroadcastEntry, SpendStatus, SpendTx},
     hw::HardwareWallet,
 };
 
@@ -49,6 +57,7 @@ pub fn psbt_view<'a>(
     labels_editing: &'a HashMap<String, form::Value<String>>,
     network: Network,
     currently_signing: bool,
+    privacy: Option<&PrivacyAssessment>,
     warning: Option<&Error>,
 ) -> Element<'a, Message> {
     dashboard(
@@ -75,6 +84,9 @@ pub fn psbt_view<'a>(
                     }),
             )
             .push(spend_header(tx, labels_editing))
+            .push_maybe(spending_policy_status(tx, cache.spending_policy()))
+            .push_maybe(sighash_warning(&signer::non_standard_sighashes(&tx.psbt)))
+            .push_maybe(privacy.and_then(privacy_warning))
             .push(spend_overview_view(
                 tx,
                 desc_info,
@@ -87,6 +99,7 @@ pub fn psbt_view<'a>(
                     .push(inputs_view(
                         &tx.coins,
                         &tx.psbt.unsigned_tx,
+                        &tx.psbt.inputs,
                         &tx.labels,
                         labels_editing,
                     ))
@@ -129,6 +142,141 @@ pub fn psbt_view<'a>(
     )
 }
 
+/// Warn the user about the inputs requesting to be signed with a sighash type which would let
+/// anyone modify the transaction after it is signed.
+pub fn sighash_warning<'a>(
+    non_standard_sighashes: &[(usize, PsbtSighashType)],
+) -> Option<Element<'a, Message>> {
+    if non_standard_sighashes.is_empty() {
+        return None;
+    }
+    let inputs = non_standard_sighashes
+        .iter()
+        .map(|(i, sighash_type)| format!("input #{} ({})", i, sighash_type))
+        .collect::<Vec<_>>()
+        .join(", ");
+    Some(
+        card::simple(
+            Column::new()
+                .spacing(5)
+                .push(
+                    Row::new()
+                        .spacing(5)
+                        .align_y(Alignment::Center)
+                        .push(icon::warning_icon().style(theme::text::warning))
+                        .push(text("Non-standard signature hash types").bold()),
+                )
+                .push(
+                    p2_regular(format!(
+                        "Signatures for {} would not commit to all the inputs and outputs of this \
+                         transaction, which could be modified once signed. Only sign if you \
+                         know why they are requested.",
+                        inputs
+                    ))
+                    .style(theme::text::secondary),
+                ),
+        )
+        .into(),
+    )
+}
+
+/// Warn the user about what this transaction would leak about the wallet, before signing it.
+pub fn privacy_warning<'a>(privacy: &PrivacyAssessment) -> Option<Element<'a, Message>> {
+    if privacy.warnings.is_empty() {
+        return None;
+    }
+    Some(
+        card::simple(
+            Column::new()
+                .spacing(5)
+                .push(
+                    Row::new()
+                        .spacing(5)
+                        .align_y(Alignment::Center)
+                        .push(icon::warning_icon().style(theme::text::warning))
+                        .push(text(format!("Privacy score: {}/100", privacy.score())).bold()),
+                )
+                .push(
+                    privacy
+                        .warnings
+                        .iter()
+                        .fold(Column::new().spacing(5), |col, warning| {
+                            col.push(p2_regular(warning.to_string()).style(theme::text::secondary))
+                        }),
+                ),
+        )
+        .into(),
+    )
+}
+
+/// What the spending policy of the daemon requires before the pending transaction may be
+/// broadcast.
+pub fn spending_policy_status<'a>(
+    tx: &SpendTx,
+    policy: Option<&SpendingPolicy>,
+) -> Option<Element<'a, Message>> {
+    let policy = policy?;
+    if tx.status != SpendStatus::Pending {
+        return None;
+    }
+    let outflow = tx.spend_amount + tx.fee_amount.unwrap_or(Amount::ZERO);
+    let broadcast_at = policy
+        .delay_threshold
+        .filter(|threshold| outflow.to_sat() >= *threshold)
+        .map(|_| {
+            tx.updated_at
+                .unwrap_or_default()
+                .saturating_add(policy.broadcast_delay)
+        });
+    if !policy.require_approval && broadcast_at.is_none() {
+        return None;
+    }
+    Some(
+        card::simple(
+            Column::new()
+                .spacing(10)
+                .push(text("Spending policy").bold())
+                .push_maybe(broadcast_at.map(|t| {
+                    let date = DateTime::<Utc>::from_timestamp(t as i64, 0)
+                        .expect("Correct unix timestamp")
+                        .with_timezone(&Local)
+                        .format("%b. %d, %Y - %T");
+                    p2_regular(format!(
+                        "This transaction is above the delay threshold and may only be \
+                         broadcast from {}.",
+                        date
+                    ))
+                    .style(theme::text::secondary)
+                }))
+                .push_maybe(policy.require_approval.then(|| {
+                    Row::new()
+                        .spacing(10)
+                        .align_y(Alignment::Center)
+                        .push(match tx.approved_at {
+                            Some(t) => p2_regular(format!(
+                                "Approved on {}",
+                                DateTime::<Utc>::from_timestamp(t as i64, 0)
+                                    .expect("Correct unix timestamp")
+                                    .with_timezone(&Local)
+                                    .format("%b. %d, %Y - %T")
+                            ))
+                            .width(Length::Fill),
+                            None => p2_regular(
+                                "This transaction must be approved before being broadcast.",
+                            )
+                            .style(theme::text::warning)
+                            .width(Length::Fill),
+                        })
+                        .push_maybe(tx.approved_at.is_none().then(|| {
+                            button::secondary(Some(icon::check_icon()), "Approve")
+                                .on_press(Message::Spend(SpendTxMessage::Approve))
+                        }))
+                })),
+        )
+        .into(),
+    )
+}
+
 pub fn save_action<'a>(warning: Option<&Error>, saved: bool) -> Element<'a, Message> {
     if saved {
         card::simple(text("Transaction is saved"))
@@ -160,9 +308,13 @@ pub fn save_action<'a>(warning: Option<&Error>, saved: bool) -> Element<'a, Mess
 /// Return the modal view to broadcast a transaction.
 ///
 /// `conflicting_txids` contains the IDs of any directly conflicting transactions
-/// of the transaction to be broadcast.
+/// of the transaction to be broadcast. `schedule` is its current broadcast schedule, if any,
+/// otherwise the `schedule_block_height` and `schedule_date` inputs allow to broadcast it later.
 pub fn broadcast_action<'a>(
     conflicting_txids: &HashSet<Txid>,
+    schedule_block_height: &form::Value<String>,
+    schedule_date: &form::Value<String>,
+    schedule: Option<&ScheduledBroadcastEntry>,
     warning: Option<&Error>,
     saved: bool,
 ) -> Element<'a, Message> {
@@ -235,7 +387,13 @@ pub fn broadcast_action<'a>(
                         button::primary(None, "Broadcast")
                             .on_press(Message::Spend(SpendTxMessage::Confirm)),
                     ),
-                ),
+                )
+                .push(separation().width(Length::Fill))
+                .push(broadcast_later(
+                    schedule_block_height,
+                    schedule_date,
+                    schedule,
+                )),
         )
         .width(Length::Fixed(if conflicting_txids.is_empty() {
             400.0
@@ -246,6 +404,89 @@ pub fn broadcast_action<'a>(
     }
 }
 
+fn broadcast_later<'a>(
+    schedule_block_height: &form::Value<String>,
+    schedule_date: &form::Value<String>,
+    schedule: Option<&ScheduledBroadcastEntry>,
+) -> Element<'a, Message> {
+    let col = Column::new()
+        .spacing(10)
+        .push(text("Broadcast later").bold());
+    if let Some(schedule) = schedule {
+        let date = |t: u32| {
+            DateTime::<Utc>::from_timestamp(t as i64, 0)
+                .expect("Correct unix timestamp")
+                .with_timezone(&Local)
+                .format("%b. %d, %Y - %T")
+        };
+        col.push(p2_regular(
+            match (schedule.block_height, schedule.timestamp) {
+                (Some(h), Some(t)) => format!(
+                    "Scheduled for broadcast from block {} and from {}.",
+                    h,
+                    date(t)
+                ),
+                (Some(h), None) => format!("Scheduled for broadcast from block {}.", h),
+                (None, Some(t)) => format!("Scheduled for broadcast from {}.", date(t)),
+                (None, None) => "Scheduled for broadcast.".to_string(),
+            },
+        ))
+        .push_maybe(schedule.last_error.as_ref().map(|e| {
+            if schedule.failed_at.is_some() {
+                p2_regular(format!("It will not be broadcast: {}", e)).style(theme::text::error)
+            } else {
+                p2_regular(format!("Last broadcast attempt failed: {}", e))
+                    .style(theme::text::warning)
+            }
+        }))
+        .push(
+            Row::new().push(Column::new().width(Length::Fill)).push(
+                button::secondary(None, "Cancel schedule")
+                    .on_press(Message::Spend(SpendTxMessage::CancelScheduledBroadcast)),
+            ),
+        )
+        .into()
+    } else {
+        col.push(
+            p2_regular(
+                "Keep the signed transaction and broadcast it automatically once the \
+                 block height and/or the date (in local time) is reached.",
+            )
+            .style(theme::text::secondary),
+        )
+        .push(
+            Row::new()
+                .spacing(10)
+                .push(
+                    form::Form::new_trimmed("Block height", schedule_block_height, |msg| {
+                        Message::Spend(SpendTxMessage::ScheduleBlockHeightEdited(msg))
+                    })
+                    .warning("Block height must be a positive integer")
+                    .size(P1_SIZE)
+                    .padding(10),
+                )
+                .push(
+                    form::Form::new("YYYY-MM-DD HH:MM", schedule_date, |msg| {
+                        Message::Spend(SpendTxMessage::ScheduleDateEdited(msg))
+                    })
+                    .warning("Date must be of the form YYYY-MM-DD HH:MM")
+                    .size(P1_SIZE)
+                    .padding(10),
+                ),
+        )
+        .push(
+            Row::new().push(Column::new().width(Length::Fill)).push(
+                button::secondary(None, "Broadcast later").on_press_maybe(
+                    (!schedule_block_height.value.trim().is_empty()
+                        || !schedule_date.value.trim().is_empty())
+                    .then_some(Message::Spend(SpendTxMessage::ScheduleBroadcast)),
+                ),
+            ),
+        )
+        .into()
+    }
+}
+
 pub fn delete_action<'a>(warning: Option<&Error>, deleted: bool) -> Element<'a, Message> {
     if deleted {
         card::simple(
@@ -323,8 +564,8 @@ pub fn spend_header<'a>(
                         })
                         .push_maybe(tx.fee_amount.map(|fee| amount_with_size(&fee, H3_SIZE)))
                         .push(text(" ").size(H3_SIZE))
-                        .push_maybe(tx.min_feerate_vb().map(|rate| {
-                            text(format!("(~{} sats/vbyte)", &rate))
+                        .push_maybe(tx.min_feerate_kvb().map(|rate| {
+                            text(format!("(~{} sats/vbyte)", format_feerate_kvb(rate)))
                                 .size(H4_SIZE)
                                 .style(theme::text::secondary)
                         })),
@@ -366,6 +607,17 @@ pub fn spend_overview_view<'a>(
                                                     Some(Message::ExportPsbt)
                                                 }),
                                             )
+                                            .push(
+                                                button::secondary(
+                                                    Some(icon::backup_icon()),
+                                                    "Export v2",
+                                                )
+                                                .on_press_maybe(if currently_signing {
+                                                    None
+                                                } else {
+                                                    Some(Message::ExportPsbtV2)
+                                                }),
+                                            )
                                             .push(
                                                 button::secondary(
                                                     Some(icon::restore_icon()),
@@ -376,6 +628,36 @@ pub fn spend_overview_view<'a>(
                                                 } else {
                                                     Some(Message::ImportPsbt)
                                                 }),
+                                            )
+                                            .push(
+                                                button::secondary(
+                                                    Some(icon::backup_icon()),
+                                                    "Coldcard SD",
+                                                )
+                                                .on_press_maybe(if currently_signing {
+                                                    None
+                                                } else {
+                                                    Some(Message::ExportPsbtColdcard)
+                                                }),
+                                            )
+                                            .push(
+                                                button::secondary(
+                                                    Some(icon::restore_icon()),
+                                                    "Coldcard signed",
+                                                )
+                                                .on_press_maybe(if currently_signing {
+                                                    None
+                                                } else {
+                                                    Some(Message::ImportPsbtColdcard)
+                                                }),
+                                            )
+                                            .push(
+                                                button::secondary(Some(icon::qr_code_icon()), "QR")
+                                                    .on_press_maybe(if currently_signing {
+                                                        None
+                                                    } else {
+                                                        Some(Message::ShowPsbtQr)
+                                                    }),
                                             ),
                                     )
                                     .align_y(Alignment::Center),
@@ -631,6 +913,7 @@ pub fn path_view<'a>(
 pub fn inputs_view<'a>(
     coins: &'a HashMap<OutPoint, Coin>,
     tx: &'a Transaction,
+    psbt_inputs: &'a [PsbtIn],
     labels: &'a HashMap<String, String>,
     labels_editing: &'a HashMap<String, form::Value<String>>,
 ) -> Element<'a, Message> {
@@ -674,12 +957,14 @@ pub fn inputs_view<'a>(
         move || {
             tx.input
                 .iter()
+                .enumerate()
                 .fold(
                     Column::new().spacing(10).padding(20),
-                    |col: Column<'a, Message>, input| {
+                    |col: Column<'a, Message>, (i, input)| {
                         col.push(input_view(
                             &input.previous_output,
                             coins.get(&input.previous_output),
+                            psbt_inputs.get(i),
                             labels,
                             labels_editing,
                         ))
@@ -848,6 +1133,7 @@ pub fn outputs_view<'a>(
 fn input_view<'a>(
     outpoint: &'a OutPoint,
     coin: Option<&'a Coin>,
+    psbt_input: Option<&'a PsbtIn>,
     labels: &'a HashMap<String, String>,
     labels_editing: &'a HashMap<String, form::Value<String>>,
 ) -> Element<'a, Message> {
@@ -921,6 +1207,21 @@ fn input_view<'a>(
                                     .push(p2_regular(label).style(theme::text::secondary)),
                             )
                     })
+                }))
+                .push_maybe(psbt_input.and_then(|psbt_in| {
+                    psbt_in.sighash_type.map(|sighash_type| {
+                        Row::new()
+                            .align_y(Alignment::Center)
+                            .spacing(5)
+                            .push(p1_bold("Sighash type:").style(theme::text::secondary))
+                            .push(p2_regular(sighash_type.to_string()).style(
+                                if signer::requests_non_standard_sighash(psbt_in) {
+                                    theme::text::warning
+                                } else {
+                                    theme::text::secondary
+                                },
+                            ))
+                    })
                 })),
         )
         .spacing(5)
@@ -939,6 +1240,12 @@ fn payment_view<'a>(
     let addr = Address::from_script(&output.script_pubkey, network)
         .ok()
         .map(|a| a.to_string());
+    // Show the data of an OP_RETURN output as text if it is printable, hex encoded otherwise.
+    let data = op_return_data(&output.script_pubkey).map(|data| match String::from_utf8(data) {
+        Ok(text) if !text.chars().any(char::is_control) => ("Data (text):", text),
+        Ok(text) => ("Data (hex):", text.into_bytes().to_lower_hex_string()),
+        Err(e) => ("Data (hex):", e.into_bytes().to_lower_hex_string()),
+    });
     let outpoint = OutPoint {
         txid,
         vout: i as u32,
@@ -1004,6 +1311,19 @@ fn payment_view<'a>(
                         )
                 }))
         }))
+        .push_maybe(data.map(|(title, data)| {
+            Row::new()
+                .align_y(Alignment::Center)
+                .width(Length::Fill)
+                .spacing(5)
+                .push(p1_bold(title).style(theme::text::secondary))
+                .push(p2_regular(data.clone()).style(theme::text::secondary))
+                .push(
+                    Button::new(icon::clipboard_icon().style(theme::text::secondary))
+                        .on_press(Message::Clipboard(data))
+                        .style(theme::button::transparent_border),
+                )
+        }))
         .into()
 }
 
@@ -1050,11 +1370,34 @@ pub fn sign_action<'a>(
     signed: &HashSet<Fingerprint>,
     signing: &HashSet<Fingerprint>,
     recovery_timelock: Option<u16>,
+    non_standard_sighash: bool,
+    allow_non_standard_sighash: bool,
 ) -> Element<'a, Message> {
     Column::new()
         .push_maybe(warning.map(|w| warn(Some(w))))
         .push(card::simple(
             Column::new()
+                .push_maybe(non_standard_sighash.then(|| {
+                    Column::new()
+                        .spacing(10)
+                        .push(
+                            p2_regular(
+                                "This transaction requests signatures which do not commit to \
+                                 all of its inputs and outputs. The hot signer refuses to \
+                                 produce them unless allowed to.",
+                            )
+                            .style(theme::text::warning),
+                        )
+                        .push(
+                            checkbox(
+                                "Allow non-standard signature hash types",
+                                allow_non_standard_sighash,
+                            )
+                            .on_toggle(|allow| {
+                                Message::Spend(SpendTxMessage::AllowNonStandardSighash(allow))
+                            }),
+                        )
+                }))
                 .push(
                     Column::new()
                         .push(
@@ -1081,7 +1424,8 @@ pub fn sign_action<'a>(
                         .push_maybe({
                             signer.map(|fingerprint| {
                                 let can_sign = descriptor
-                                    .contains_fingerprint_in_path(fingerprint, recovery_timelock);
+                                    .contains_fingerprint_in_path(fingerprint, recovery_timelock)
+                                    && (!non_standard_sighash || allow_non_standard_sighash);
                                 let btn = Button::new(if signed.contains(&fingerprint) {
                                     hw::sign_success_hot_signer(fingerprint, signer_alias)
                                 } else {
@@ -17,7 +17,7 @@ use coincube_core::miniscript::bitcoin::{
 
 use coincube_ui::{
     component::{
-        button, card, form,
+        badge, button, card, form,
         text::{self, *},
     },
     icon, theme,
@@ -29,6 +29,7 @@ use crate::{
         error::Error,
         view::{placeholder, vault::hw, vault::label, vault::warning::warn},
     },
+    daemon::model::Coin,
     hw::HardwareWallet,
 };
 
@@ -39,15 +40,29 @@ pub fn address_card<'a>(
     address: &'a bitcoin::Address,
     labels: &'a HashMap<String, String>,
     labels_editing: &'a HashMap<String, form::Value<String>>,
+    reused: bool,
 ) -> Container<'a, Message> {
     let addr = address.to_string();
     card::simple(
         Column::new()
-            .push(if let Some(label) = labels_editing.get(&addr) {
-                label::label_editing(vec![addr.clone()], label, text::P1_SIZE)
-            } else {
-                label::label_editable(vec![addr.clone()], labels.get(&addr), text::P1_SIZE)
-            })
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .align_y(Alignment::Center)
+                    .push(
+                        Container::new(if let Some(label) = labels_editing.get(&addr) {
+                            label::label_editing(vec![addr.clone()], label, text::P1_SIZE)
+                        } else {
+                            label::label_editable(
+                                vec![addr.clone()],
+                                labels.get(&addr),
+                                text::P1_SIZE,
+                            )
+                        })
+                        .width(Length::Fill),
+                    )
+                    .push_maybe(reused.then(badge::reused)),
+            )
             .push(
                 Row::new()
                     .push(
@@ -101,11 +116,20 @@ pub fn receive<'a>(
     labels_editing: &'a HashMap<String, form::Value<String>>,
     is_last_page: bool,
     processing: bool,
+    coins: &'a [Coin],
 ) -> Element<'a, Message> {
     // Number of start and end address characters to show in collapsed view.
     const NUM_ADDR_CHARS: usize = 16;
     let mut addresses_count = 0; // for counting number of new addresses generated
 
+    // Addresses holding more than one of our unspent coins are flagged as reused.
+    let mut address_counts = HashMap::<&bitcoin::Address, usize>::new();
+    for coin in coins {
+        *address_counts.entry(&coin.address).or_insert(0) += 1;
+    }
+    let is_reused =
+        |address: &bitcoin::Address| address_counts.get(address).is_some_and(|c| *c > 1);
+
     Column::new()
         .push(
             Row::new()
@@ -136,7 +160,13 @@ pub fn receive<'a>(
                     Column::new().spacing(10).width(Length::Fill),
                     |col, (i, address)| {
                         addresses_count += 1;
-                        col.push(address_card(i, address, labels, labels_editing))
+                        col.push(address_card(
+                            i,
+                            address,
+                            labels,
+                            labels_editing,
+                            is_reused(address),
+                        ))
                     },
                 )),
         )
@@ -238,6 +268,7 @@ pub fn receive<'a>(
                             address,
                             prev_labels,
                             labels_editing,
+                            is_reused(address),
                         ))
                         .padding(0) // so that button & card borders match
                         .on_press(Message::SelectAddress(address.clone()))
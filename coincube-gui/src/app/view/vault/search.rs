@@ -0,0 +1,161 @@
+use iced::{widget::Space, Alignment, Length};
+
+use coincube_ui::{
+    component::{button, card, form, text::*},
+    widget::*,
+};
+use coincubed::commands::CoinStatus;
+
+use crate::app::{
+    state::vault::search::SearchForm,
+    view::message::{Message, SearchMessage},
+};
+
+/// Filter bar shown above the coins and transactions lists. `statuses` are the coin statuses
+/// the user can restrict the search to.
+pub fn search_bar<'a>(search: &'a SearchForm, statuses: &[CoinStatus]) -> Element<'a, Message> {
+    let header = Row::new()
+        .spacing(10)
+        .align_y(Alignment::Center)
+        .push_maybe(
+            search
+                .applied
+                .is_some()
+                .then(|| text("Filters applied").small()),
+        )
+        .push(Space::with_width(Length::Fill))
+        .push_maybe(search.applied.is_some().then(|| {
+            button::transparent(None, "Clear").on_press(search_message(SearchMessage::Clear))
+        }))
+        .push(
+            button::secondary(
+                None,
+                if search.open {
+                    "Hide filters"
+                } else {
+                    "Filter"
+                },
+            )
+            .on_press(search_message(SearchMessage::Toggle)),
+        );
+    if !search.open {
+        return header.into();
+    }
+
+    let status_row = std::iter::once(None)
+        .chain(statuses.iter().copied().map(Some))
+        .fold(
+            Row::new()
+                .spacing(10)
+                .align_y(Alignment::Center)
+                .push(text("Status:").small()),
+            |row, status| {
+                let label = match status {
+                    None => "All",
+                    Some(CoinStatus::Unconfirmed) => "Unconfirmed",
+                    Some(CoinStatus::Confirmed) => "Confirmed",
+                    Some(CoinStatus::Spending) => "Spending",
+                    Some(CoinStatus::Spent) => "Spent",
+                };
+                let btn = if search.status == status {
+                    button::primary(None, label)
+                } else {
+                    button::secondary(None, label)
+                };
+                row.push(btn.on_press(search_message(SearchMessage::StatusSelected(status))))
+            },
+        );
+
+    Column::new()
+        .spacing(10)
+        .push(header)
+        .push(card::simple(
+            Column::new()
+                .spacing(10)
+                .push(field("Label", "label", &search.label, "Label contains"))
+                .push(
+                    Row::new()
+                        .spacing(10)
+                        .push(
+                            field("Min amount", "min_amount", &search.min_amount, "0.001")
+                                .warning("Please enter an amount in BTC"),
+                        )
+                        .push(
+                            field("Max amount", "max_amount", &search.max_amount, "1.5")
+                                .warning("Please enter an amount in BTC"),
+                        ),
+                )
+                .push(
+                    Row::new()
+                        .spacing(10)
+                        .push(
+                            field("From", "start", &search.start, "YYYY-MM-DD")
+                                .warning("Please enter a date as YYYY-MM-DD"),
+                        )
+                        .push(
+                            field("To", "end", &search.end, "YYYY-MM-DD")
+                                .warning("Please enter a date as YYYY-MM-DD"),
+                        ),
+                )
+                .push(
+                    field("Address", "address", &search.address, "Address")
+                        .warning("Please enter an address of this network"),
+                )
+                .push(
+                    field(
+                        "Transaction ID",
+                        "txid_prefix",
+                        &search.txid_prefix,
+                        "Txid starts with",
+                    )
+                    .warning("Please enter hexadecimal characters"),
+                )
+                .push(status_row)
+                .push(Row::new().push(Space::with_width(Length::Fill)).push(
+                    button::primary(None, "Apply").on_press(search_message(SearchMessage::Apply)),
+                )),
+        ))
+        .into()
+}
+
+fn search_message(msg: SearchMessage) -> Message {
+    Message::Search(msg)
+}
+
+fn field<'a>(
+    title: &'static str,
+    name: &'static str,
+    value: &'a form::Value<String>,
+    placeholder: &'static str,
+) -> Field<'a> {
+    Field {
+        title,
+        form: form::Form::new(placeholder, value, move |v| {
+            search_message(SearchMessage::FieldEdited(name, v))
+        }),
+    }
+}
+
+/// A titled form input of the filter bar.
+struct Field<'a> {
+    title: &'static str,
+    form: form::Form<'a, Message>,
+}
+
+impl<'a> Field<'a> {
+    fn warning(mut self, warning: &'a str) -> Self {
+        self.form = self.form.warning(warning);
+        self
+    }
+}
+
+impl<'a> From<Field<'a>> for Element<'a, Message> {
+    fn from(field: Field<'a>) -> Self {
+        Column::new()
+            .spacing(5)
+            .width(Length::Fill)
+            .push(text(field.title).bold().small())
+            .push(field.form.size(P1_SIZE).padding(10))
+            .into()
+    }
+}
@@ -0,0 +1,223 @@
+use iced::widget::{Column, Row, Space};
+use iced::{Alignment, Length};
+
+use coincube_ui::{
+    component::{button, card, form, text::*},
+    icon, theme,
+    widget::*,
+};
+
+use super::header;
+
+use crate::app::cache::Cache;
+use crate::app::error::Error;
+use crate::app::menu::Menu;
+use crate::app::settings::contacts::Contact;
+use crate::app::state::vault::settings::ContactForm;
+use crate::app::view::dashboard;
+use crate::app::view::message::*;
+
+pub fn contacts_section<'a>(
+    menu: &'a Menu,
+    cache: &'a Cache,
+    warning: Option<&Error>,
+    contacts: &'a [Contact],
+    form: Option<&'a ContactForm>,
+    processing: bool,
+) -> Element<'a, Message> {
+    let header = header("Address book", SettingsMessage::ContactsSection);
+
+    let list = contacts
+        .iter()
+        .enumerate()
+        .fold(Column::new().spacing(10), |col, (i, contact)| {
+            if form.is_some_and(|f| f.index == Some(i)) {
+                col
+            } else {
+                col.push(contact_card(i, contact, processing))
+            }
+        });
+
+    dashboard(
+        menu,
+        cache,
+        warning,
+        Column::new()
+            .spacing(20)
+            .push(header)
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .align_y(Alignment::Center)
+                    .push(
+                        p2_regular(
+                            "Save the addresses of the payees you send to, after verifying them \
+                             with the payee. You will be warned when sending to an address that \
+                             looks like one of them but is not.",
+                        )
+                        .style(theme::text::secondary)
+                        .width(Length::Fill),
+                    )
+                    .push(
+                        button::secondary(Some(icon::plus_icon()), "Add contact")
+                            .on_press_maybe(form.is_none().then_some(ContactsMessage::New.into())),
+                    ),
+            )
+            .push_maybe(form.map(|f| contact_form(f, processing)))
+            .push_maybe(
+                (contacts.is_empty() && form.is_none())
+                    .then(|| text("No contact yet.").style(theme::text::secondary)),
+            )
+            .push(list),
+    )
+}
+
+fn contact_card<'a>(index: usize, contact: &'a Contact, processing: bool) -> Element<'a, Message> {
+    card::simple(
+        Column::new()
+            .spacing(10)
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .align_y(Alignment::Center)
+                    .push(text(&contact.name).bold().width(Length::Fill))
+                    .push(
+                        button::secondary(Some(icon::pencil_icon()), "Edit")
+                            .on_press(ContactsMessage::Edit(index).into()),
+                    )
+                    .push(
+                        button::secondary(Some(icon::trash_icon()), "Delete").on_press_maybe(
+                            (!processing).then_some(ContactsMessage::Delete(index).into()),
+                        ),
+                    ),
+            )
+            .push(match &contact.verified_on {
+                Some(date) => p2_regular(format!("Verified on {}", date)),
+                None => p2_regular("Not verified").style(theme::text::warning),
+            })
+            .push_maybe(
+                (!contact.note.is_empty())
+                    .then(|| p2_regular(&contact.note).style(theme::text::secondary)),
+            )
+            .push(contact.destinations.iter().fold(
+                Column::new().spacing(5),
+                |col, destination| {
+                    col.push(
+                        Row::new()
+                            .spacing(10)
+                            .align_y(Alignment::Center)
+                            .push(
+                                Container::new(
+                                    p2_regular(destination).style(theme::text::secondary),
+                                )
+                                .width(Length::Fill),
+                            )
+                            .push(
+                                Button::new(icon::clipboard_icon().style(theme::text::secondary))
+                                    .on_press(Message::Clipboard(destination.clone()))
+                                    .style(theme::button::transparent_border),
+                            ),
+                    )
+                },
+            )),
+    )
+    .into()
+}
+
+fn contact_form(form: &ContactForm, processing: bool) -> Element<Message> {
+    card::simple(
+        Column::new()
+            .spacing(10)
+            .push(
+                text(if form.index.is_some() {
+                    "Edit contact"
+                } else {
+                    "New contact"
+                })
+                .bold(),
+            )
+            .push(field("Name", "name", &form.name, "Name").warning("Please enter a name"))
+            .push(
+                field(
+                    "Addresses",
+                    "destinations",
+                    &form.destinations,
+                    "Addresses or descriptors (e.g. wpkh(xpub.../0/*)), separated by spaces",
+                )
+                .warning(
+                    "Please enter addresses of this network, or ranged descriptors, separated \
+                     by spaces",
+                ),
+            )
+            .push(field("Note", "note", &form.note, "Note"))
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .align_y(Alignment::End)
+                    .push(
+                        field(
+                            "Verified on",
+                            "verified_on",
+                            &form.verified_on,
+                            "YYYY-MM-DD",
+                        )
+                        .warning("Please enter a date as YYYY-MM-DD"),
+                    )
+                    .push(Element::from(
+                        button::secondary(Some(icon::check_icon()), "Verified today")
+                            .on_press(ContactsMessage::VerifiedToday.into()),
+                    )),
+            )
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .push(Space::with_width(Length::Fill))
+                    .push(
+                        button::secondary(None, "Cancel").on_press(ContactsMessage::Cancel.into()),
+                    )
+                    .push(
+                        button::primary(None, "Save")
+                            .on_press_maybe((!processing).then_some(ContactsMessage::Save.into())),
+                    ),
+            ),
+    )
+    .into()
+}
+
+fn field<'a>(
+    title: &'static str,
+    name: &'static str,
+    value: &'a form::Value<String>,
+    placeholder: &'static str,
+) -> Field<'a> {
+    Field {
+        title,
+        form: form::Form::new(placeholder, value, move |v| {
+            ContactsMessage::FieldEdited(name, v).into()
+        }),
+    }
+}
+
+/// A titled form input of the contact form.
+struct Field<'a> {
+    title: &'static str,
+    form: form::Form<'a, Message>,
+}
+
+impl<'a> Field<'a> {
+    fn warning(mut self, warning: &'a str) -> Self {
+        self.form = self.form.warning(warning);
+        self
+    }
+}
+
+impl<'a> From<Field<'a>> for Element<'a, Message> {
+    fn from(field: Field<'a>) -> Self {
+        Column::new()
+            .spacing(5)
+            .width(Length::Fill)
+            .push(text(field.title).bold().small())
+            .push(field.form.size(P1_SIZE).padding(10))
+            .into()
+    }
+}
@@ -0,0 +1,311 @@
+use std::collections::HashSet;
+
+use iced::widget::{radio, Column, Row, Space};
+use iced::{Alignment, Length};
+
+use coincube_core::miniscript::bitcoin::bip32::Fingerprint;
+use coincube_ui::{
+    component::{button, card, form, text::*},
+    theme,
+    widget::*,
+};
+
+use super::header;
+
+use crate::app::cache::Cache;
+use crate::app::error::Error;
+use crate::app::menu::Menu;
+use crate::app::view::{dashboard, message::*, vault::hw::hw_list_view};
+use crate::app::wallet::Wallet;
+use crate::drill::{Drill, DrillKeys};
+use crate::hw::HardwareWallet;
+
+/// Whether a key of the drilled recovery path signed, or could sign, the recovery transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyStatus {
+    Signed,
+    /// The device of the key failed to sign.
+    Failed(String),
+    HotKey,
+    DeviceConnected,
+    /// Neither a hot key nor a connected device can sign for this key.
+    Missing,
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn drill_section<'a>(
+    menu: &'a Menu,
+    cache: &'a Cache,
+    warning: Option<&Error>,
+    wallet: &'a Wallet,
+    keys: DrillKeys,
+    timelock: u16,
+    node_address: &'a form::Value<String>,
+    cookie_path: &'a form::Value<String>,
+    drill: Option<&'a Drill>,
+    hws: &'a [HardwareWallet],
+    keys_report: Vec<(Fingerprint, KeyStatus)>,
+    has_psbt: bool,
+    signed: &'a HashSet<Fingerprint>,
+    signing: &'a HashSet<Fingerprint>,
+    can_broadcast: bool,
+    done: bool,
+    processing: bool,
+) -> Element<'a, Message> {
+    let header = header("Recovery drill", SettingsMessage::DrillSection);
+
+    dashboard(
+        menu,
+        cache,
+        warning,
+        Column::new()
+            .spacing(20)
+            .push(header)
+            .push(
+                p2_regular(
+                    "Rehearse the recovery of your coins before you need it. The policy of the \
+                     wallet is cloned into a drill wallet on a local regtest node, either with the \
+                     same keys to check they and their devices are still available, or with fresh \
+                     keys to go through the process only. The drill wallet is funded from the \
+                     node, which mines blocks until the recovery path is available.",
+                )
+                .style(theme::text::secondary),
+            )
+            .push(match drill {
+                None => drill_form(
+                    wallet,
+                    keys,
+                    timelock,
+                    node_address,
+                    cookie_path,
+                    processing,
+                ),
+                Some(drill) => drill_card(
+                    wallet,
+                    drill,
+                    hws,
+                    keys_report,
+                    has_psbt,
+                    signed,
+                    signing,
+                    can_broadcast,
+                    done,
+                    processing,
+                ),
+            }),
+    )
+}
+
+fn drill_form<'a>(
+    wallet: &'a Wallet,
+    keys: DrillKeys,
+    timelock: u16,
+    node_address: &'a form::Value<String>,
+    cookie_path: &'a form::Value<String>,
+    processing: bool,
+) -> Element<'a, Message> {
+    let key_modes = [
+        (DrillKeys::Same, "Same keys"),
+        (DrillKeys::Fresh, "Fresh test keys"),
+    ];
+    card::simple(
+        Column::new()
+            .spacing(10)
+            .push(text("New drill").bold())
+            .push(
+                key_modes
+                    .iter()
+                    .fold(Row::new().spacing(30), |row, (k, label)| {
+                        row.push(radio(*label, *k, Some(keys), |k| {
+                            DrillMessage::KeysSelected(k).into()
+                        }))
+                    })
+                    .align_y(Alignment::Center),
+            )
+            .push(text("Recovery path").bold().small())
+            .push(
+                wallet
+                    .main_descriptor
+                    .policy()
+                    .recovery_paths()
+                    .keys()
+                    .fold(Row::new().spacing(30), |row, t| {
+                        row.push(radio(
+                            format!("After {} blocks", t),
+                            *t,
+                            Some(timelock),
+                            |t| DrillMessage::TimelockSelected(t).into(),
+                        ))
+                    })
+                    .align_y(Alignment::Center),
+            )
+            .push(field(
+                "Regtest node address",
+                form::Form::new_trimmed("127.0.0.1:18443", node_address, |v| {
+                    DrillMessage::FieldEdited("node_address", v).into()
+                })
+                .warning("Please enter a valid address"),
+            ))
+            .push(field(
+                "Cookie file of the node",
+                form::Form::new_trimmed("~/.bitcoin/regtest/.cookie", cookie_path, |v| {
+                    DrillMessage::FieldEdited("cookie_path", v).into()
+                })
+                .warning("Please enter the path of an existing cookie file"),
+            ))
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .align_y(Alignment::Center)
+                    .push_maybe(processing.then(|| {
+                        p2_regular("Funding the drill wallet and mining blocks...")
+                            .style(theme::text::secondary)
+                    }))
+                    .push(Space::with_width(Length::Fill))
+                    .push(
+                        button::primary(None, "Start drill")
+                            .on_press_maybe((!processing).then_some(DrillMessage::Start.into())),
+                    ),
+            ),
+    )
+    .into()
+}
+
+#[allow(clippy::too_many_arguments)]
+fn drill_card<'a>(
+    wallet: &'a Wallet,
+    drill: &'a Drill,
+    hws: &'a [HardwareWallet],
+    keys_report: Vec<(Fingerprint, KeyStatus)>,
+    has_psbt: bool,
+    signed: &'a HashSet<Fingerprint>,
+    signing: &'a HashSet<Fingerprint>,
+    can_broadcast: bool,
+    done: bool,
+    processing: bool,
+) -> Element<'a, Message> {
+    let mut col = Column::new()
+        .spacing(10)
+        .push(
+            text(format!(
+                "Drill of the recovery after {} blocks",
+                drill.timelock
+            ))
+            .bold(),
+        )
+        .push(p2_regular(format!(
+            "The drill wallet was funded by the node at {}, the recovery path is available.",
+            drill.node.addr()
+        )));
+
+    if !has_psbt {
+        col = col.push(
+            Row::new().push(Space::with_width(Length::Fill)).push(
+                button::primary(None, "Create recovery transaction")
+                    .on_press_maybe((!processing).then_some(DrillMessage::CreateRecovery.into())),
+            ),
+        );
+    } else {
+        let report = keys_report
+            .into_iter()
+            .fold(Column::new().spacing(5), |col, (fg, status)| {
+                col.push(key_status(fg, wallet.keys_aliases.get(&fg), status))
+            });
+        col = col
+            .push(text("Keys of the recovery path").bold().small())
+            .push(report);
+        if !done {
+            col = col
+                .push_maybe((!drill.signers.is_empty()).then(|| {
+                    Row::new().push(Space::with_width(Length::Fill)).push(
+                        button::secondary(None, "Sign with hot keys")
+                            .on_press(DrillMessage::SignWithHotKeys.into()),
+                    )
+                }))
+                .push_maybe((!hws.is_empty()).then(|| {
+                    hws.iter()
+                        .enumerate()
+                        .fold(Column::new().spacing(10), |col, (i, hw)| {
+                            let (signed, signing, can_sign) =
+                                hw.fingerprint().map_or((false, false, false), |f| {
+                                    (
+                                        signed.contains(&f),
+                                        signing.contains(&f),
+                                        drill
+                                            .descriptor
+                                            .contains_fingerprint_in_path(f, Some(drill.timelock)),
+                                    )
+                                });
+                            col.push(hw_list_view(i, hw, signed, signing, can_sign))
+                        })
+                }))
+                .push(
+                    Row::new()
+                        .spacing(10)
+                        .align_y(Alignment::Center)
+                        .push_maybe(processing.then(|| {
+                            p2_regular("Broadcasting and confirming the recovery transaction...")
+                                .style(theme::text::secondary)
+                        }))
+                        .push(Space::with_width(Length::Fill))
+                        .push(
+                            button::primary(None, "Broadcast").on_press_maybe(
+                                (can_broadcast && !processing)
+                                    .then_some(DrillMessage::Broadcast.into()),
+                            ),
+                        ),
+                );
+        } else {
+            col = col.push(
+                p2_regular("The recovery transaction was confirmed, the drill succeeded.")
+                    .style(theme::text::success),
+            );
+        }
+    }
+
+    card::simple(
+        col.push(
+            Row::new().push(Space::with_width(Length::Fill)).push(
+                button::secondary(None, "End drill")
+                    .on_press_maybe((!processing).then_some(DrillMessage::End.into())),
+            ),
+        ),
+    )
+    .into()
+}
+
+fn key_status<'a>(
+    fingerprint: Fingerprint,
+    alias: Option<&'a String>,
+    status: KeyStatus,
+) -> Element<'a, Message> {
+    let name = match alias {
+        Some(alias) => format!("{} ({})", alias, fingerprint),
+        None => fingerprint.to_string(),
+    };
+    let status = match status {
+        KeyStatus::Signed => p2_regular("Signed").style(theme::text::success),
+        KeyStatus::Failed(e) => {
+            p2_regular(format!("Failed to sign: {}", e)).style(theme::text::warning)
+        }
+        KeyStatus::HotKey => p2_regular("Hot key available"),
+        KeyStatus::DeviceConnected => p2_regular("Device connected"),
+        KeyStatus::Missing => {
+            p2_regular("Missing: no hot key nor connected device").style(theme::text::warning)
+        }
+    };
+    Row::new()
+        .spacing(10)
+        .push(p2_regular(name).width(Length::Fill))
+        .push(status)
+        .into()
+}
+
+fn field<'a>(title: &'static str, form: form::Form<'a, Message>) -> Element<'a, Message> {
+    Column::new()
+        .spacing(5)
+        .width(Length::Fill)
+        .push(text(title).bold().small())
+        .push(form.size(P1_SIZE).padding(10))
+        .into()
+}
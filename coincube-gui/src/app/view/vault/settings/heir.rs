@@ -0,0 +1,191 @@
+use chrono::{DateTime, Local, Utc};
+use iced::widget::{Column, Row, Space};
+use iced::{Alignment, Length};
+
+use coincube_core::miniscript::bitcoin::OutPoint;
+use coincube_ui::{
+    component::{button, card, form, text::*},
+    theme,
+    widget::*,
+};
+
+use super::header;
+
+use crate::app::cache::Cache;
+use crate::app::error::Error;
+use crate::app::menu::Menu;
+use crate::app::view::dashboard;
+use crate::app::view::message::*;
+use crate::heir::HeirPackageRecord;
+
+#[allow(clippy::too_many_arguments)]
+pub fn heir_section<'a>(
+    menu: &'a Menu,
+    cache: &'a Cache,
+    warning: Option<&Error>,
+    record: Option<&'a HeirPackageRecord>,
+    moved_coins: Vec<OutPoint>,
+    uncovered_coins: Vec<OutPoint>,
+    address: &'a form::Value<String>,
+    feerate: &'a form::Value<String>,
+    instructions: &'a form::Value<String>,
+    processing: bool,
+) -> Element<'a, Message> {
+    let header = header("Heir package", SettingsMessage::HeirSection);
+
+    dashboard(
+        menu,
+        cache,
+        warning,
+        Column::new()
+            .spacing(20)
+            .push(header)
+            .push(
+                p2_regular(
+                    "Prepare, for your heir, the transactions recovering your coins through the \
+                     recovery paths of the wallet. They are signed with the hot key of this \
+                     device, if any, and exported along with the wallet descriptor and your \
+                     instructions in a backup file encrypted to the keys of the wallet. Your heir \
+                     restores the wallet from it with their key, and broadcasts the transactions \
+                     once their timelock expired.",
+                )
+                .style(theme::text::secondary),
+            )
+            .push_maybe(
+                record.map(|r| record_card(r, cache.blockheight(), &moved_coins, &uncovered_coins)),
+            )
+            .push(package_form(address, feerate, instructions, processing)),
+    )
+}
+
+fn record_card<'a>(
+    record: &'a HeirPackageRecord,
+    tip_height: i32,
+    moved_coins: &[OutPoint],
+    uncovered_coins: &[OutPoint],
+) -> Element<'a, Message> {
+    let date = DateTime::<Utc>::from_timestamp(record.created_at as i64, 0)
+        .expect("Correct unix timestamp")
+        .with_timezone(&Local)
+        .format("%b. %d, %Y - %T");
+    card::simple(
+        Column::new()
+            .spacing(10)
+            .push(text(format!("Last package exported on {}", date)).bold())
+            .push(if !moved_coins.is_empty() {
+                p2_regular(format!(
+                    "This package is stale: {} of its coins moved since it was exported, its \
+                     recovery transactions are no longer valid. Please export a new package.",
+                    moved_coins.len()
+                ))
+                .style(theme::text::warning)
+            } else if !uncovered_coins.is_empty() {
+                p2_regular(format!(
+                    "{} coins received since the package was exported are not recovered by it. \
+                     Please export a new package.",
+                    uncovered_coins.len()
+                ))
+                .style(theme::text::warning)
+            } else {
+                p2_regular("The package recovers all the coins of the wallet.")
+                    .style(theme::text::success)
+            })
+            .push(
+                record
+                    .schedule
+                    .iter()
+                    .fold(Column::new().spacing(5), |col, entry| {
+                        col.push(p2_regular(if entry.valid_from <= tip_height {
+                            format!(
+                                "Recovery after {} blocks: valid since block {}",
+                                entry.timelock, entry.valid_from
+                            )
+                        } else {
+                            format!(
+                                "Recovery after {} blocks: valid from block {} (in about {} days)",
+                                entry.timelock,
+                                entry.valid_from,
+                                (entry.valid_from - tip_height) / 144 + 1
+                            )
+                        }))
+                    }),
+            ),
+    )
+    .into()
+}
+
+fn package_form<'a>(
+    address: &'a form::Value<String>,
+    feerate: &'a form::Value<String>,
+    instructions: &'a form::Value<String>,
+    processing: bool,
+) -> Element<'a, Message> {
+    card::simple(
+        Column::new()
+            .spacing(10)
+            .push(text("New package").bold())
+            .push(
+                field("Heir address", "address", address, "Address")
+                    .warning("Please enter an address of this network"),
+            )
+            .push(
+                field("Feerate", "feerate", feerate, "Feerate (sats/vbyte)")
+                    .warning("Please enter a valid feerate in sats/vbyte"),
+            )
+            .push(field(
+                "Instructions",
+                "instructions",
+                instructions,
+                "Instructions for your heir",
+            ))
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .align_y(Alignment::Center)
+                    .push(Space::with_width(Length::Fill))
+                    .push(
+                        button::primary(None, "Create and export")
+                            .on_press_maybe((!processing).then_some(HeirMessage::Create.into())),
+                    ),
+            ),
+    )
+    .into()
+}
+
+fn field<'a>(
+    title: &'static str,
+    name: &'static str,
+    value: &'a form::Value<String>,
+    placeholder: &'static str,
+) -> Field<'a> {
+    Field {
+        title,
+        form: form::Form::new(placeholder, value, move |v| {
+            HeirMessage::FieldEdited(name, v).into()
+        }),
+    }
+}
+
+/// A titled form input of the package form.
+struct Field<'a> {
+    title: &'static str,
+    form: form::Form<'a, Message>,
+}
+
+impl<'a> Field<'a> {
+    fn warning(mut self, warning: &'a str) -> Self {
+        self.form = self.form.warning(warning);
+        self
+    }
+}
+
+impl<'a> From<Field<'a>> for Element<'a, Message> {
+    fn from(field: Field<'a>) -> Self {
+        Column::new()
+            .spacing(5)
+            .width(Length::Fill)
+            .push(text(field.title).bold().small())
+            .push(field.form.size(P1_SIZE).padding(10))
+            .into()
+    }
+}
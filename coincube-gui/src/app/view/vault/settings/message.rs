@@ -0,0 +1,240 @@
+use std::collections::HashSet;
+
+use iced::widget::{checkbox, radio, Column, Row, Space};
+use iced::{Alignment, Length};
+
+use coincube_core::bip322::{Bip322Error, SignatureFormat};
+use coincube_ui::{
+    component::{amount::*, button, card, form, text::*},
+    icon, theme,
+    widget::*,
+};
+
+use super::header;
+
+use crate::app::cache::Cache;
+use crate::app::error::Error;
+use crate::app::menu::Menu;
+use crate::app::view::dashboard;
+use crate::app::view::message::*;
+use crate::daemon::model::Coin;
+
+#[allow(clippy::too_many_arguments)]
+pub fn message_section<'a>(
+    menu: &'a Menu,
+    cache: &'a Cache,
+    warning: Option<&Error>,
+    kind: MessageProofKind,
+    address: &'a form::Value<String>,
+    message: &'a form::Value<String>,
+    coins: &'a [Coin],
+    selected_coins: &'a HashSet<usize>,
+    processing: bool,
+    signature: Option<&'a String>,
+    verify_address: &'a form::Value<String>,
+    verify_message: &'a form::Value<String>,
+    verify_signature: &'a form::Value<String>,
+    verified: Option<&'a Result<SignatureFormat, Bip322Error>>,
+) -> Element<'a, Message> {
+    let header = header("Sign message", SettingsMessage::MessageSection);
+
+    dashboard(
+        menu,
+        cache,
+        warning,
+        Column::new()
+            .spacing(20)
+            .push(header)
+            .push(sign_card(
+                kind,
+                address,
+                message,
+                coins,
+                selected_coins,
+                processing,
+                signature,
+            ))
+            .push(verify_card(
+                verify_address,
+                verify_message,
+                verify_signature,
+                verified,
+            )),
+    )
+}
+
+fn sign_card<'a>(
+    kind: MessageProofKind,
+    address: &'a form::Value<String>,
+    message: &'a form::Value<String>,
+    coins: &'a [Coin],
+    selected_coins: &'a HashSet<usize>,
+    processing: bool,
+    signature: Option<&'a String>,
+) -> Element<'a, Message> {
+    let kinds = [
+        (MessageProofKind::Simple, "Address"),
+        (MessageProofKind::Full, "Address (full format)"),
+        (MessageProofKind::Reserves, "Proof of reserves"),
+    ];
+    let can_sign = !processing
+        && match kind {
+            MessageProofKind::Reserves => !selected_coins.is_empty(),
+            MessageProofKind::Simple | MessageProofKind::Full => !address.value.is_empty(),
+        };
+    card::simple(
+        Column::new()
+            .spacing(20)
+            .push(text("Sign a message (BIP 322)").bold())
+            .push(
+                p2_regular(
+                    "Prove control over one of your addresses, or over a set of your coins, \
+                     by signing a message with the keys of this wallet.",
+                )
+                .style(theme::text::secondary),
+            )
+            .push(
+                kinds
+                    .iter()
+                    .fold(Row::new().spacing(30), |row, (k, label)| {
+                        row.push(radio(*label, *k, Some(kind), |k| {
+                            SignMessageMessage::KindSelected(k).into()
+                        }))
+                    })
+                    .align_y(Alignment::Center),
+            )
+            .push(if kind == MessageProofKind::Reserves {
+                coins_list(coins, selected_coins)
+            } else {
+                form::Form::new_trimmed("Address", address, |a| {
+                    SignMessageMessage::AddressEdited(a).into()
+                })
+                .warning("Invalid address (maybe it is for another network?)")
+                .size(P1_SIZE)
+                .padding(10)
+                .into()
+            })
+            .push(
+                form::Form::new("Message", message, |m| {
+                    SignMessageMessage::MessageEdited(m).into()
+                })
+                .size(P1_SIZE)
+                .padding(10),
+            )
+            .push_maybe(signature.map(|signature| {
+                Column::new()
+                    .spacing(10)
+                    .push(text("Signature:").bold())
+                    .push(
+                        Row::new()
+                            .spacing(10)
+                            .align_y(Alignment::Center)
+                            .push(
+                                Container::new(p2_regular(signature).style(theme::text::secondary))
+                                    .width(Length::Fill),
+                            )
+                            .push(
+                                Button::new(icon::clipboard_icon().style(theme::text::secondary))
+                                    .on_press(Message::Clipboard(signature.clone()))
+                                    .style(theme::button::transparent_border),
+                            ),
+                    )
+            }))
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .push(Space::with_width(Length::Fill))
+                    .push_maybe(
+                        (kind == MessageProofKind::Reserves && signature.is_some()).then(|| {
+                            button::secondary(Some(icon::backup_icon()), "Export proof")
+                                .on_press(SignMessageMessage::ExportProof.into())
+                        }),
+                    )
+                    .push(
+                        button::primary(None, "Sign")
+                            .on_press_maybe(can_sign.then_some(SignMessageMessage::Sign.into())),
+                    ),
+            ),
+    )
+    .into()
+}
+
+fn coins_list<'a>(coins: &'a [Coin], selected_coins: &'a HashSet<usize>) -> Element<'a, Message> {
+    if coins.is_empty() {
+        return p2_regular("No unspent coins in this wallet.")
+            .style(theme::text::secondary)
+            .into();
+    }
+    coins
+        .iter()
+        .enumerate()
+        .fold(Column::new().spacing(10), |col, (i, coin)| {
+            col.push(
+                Row::new()
+                    .spacing(20)
+                    .align_y(Alignment::Center)
+                    .push(
+                        checkbox("", selected_coins.contains(&i))
+                            .on_toggle(move |_| SignMessageMessage::SelectCoin(i).into()),
+                    )
+                    .push(Container::new(p2_regular(coin.outpoint.to_string())).width(Length::Fill))
+                    .push(amount(&coin.amount)),
+            )
+        })
+        .into()
+}
+
+fn verify_card<'a>(
+    address: &'a form::Value<String>,
+    message: &'a form::Value<String>,
+    signature: &'a form::Value<String>,
+    verified: Option<&'a Result<SignatureFormat, Bip322Error>>,
+) -> Element<'a, Message> {
+    card::simple(
+        Column::new()
+            .spacing(20)
+            .push(text("Verify a signed message").bold())
+            .push(
+                form::Form::new_trimmed("Address", address, |a| {
+                    SignMessageMessage::VerifyAddressEdited(a).into()
+                })
+                .warning("Invalid address (maybe it is for another network?)")
+                .size(P1_SIZE)
+                .padding(10),
+            )
+            .push(
+                form::Form::new("Message", message, |m| {
+                    SignMessageMessage::VerifyMessageEdited(m).into()
+                })
+                .size(P1_SIZE)
+                .padding(10),
+            )
+            .push(
+                form::Form::new_trimmed("Signature", signature, |s| {
+                    SignMessageMessage::SignatureEdited(s).into()
+                })
+                .size(P1_SIZE)
+                .padding(10),
+            )
+            .push_maybe(verified.map(|res| match res {
+                Ok(_) => p1_regular("The signature is valid.").style(theme::text::success),
+                Err(e) => p1_regular(format!("Invalid signature: {}", e)).style(theme::text::error),
+            }))
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .push(Space::with_width(Length::Fill))
+                    .push(
+                        button::secondary(Some(icon::restore_icon()), "Verify proof of reserves")
+                            .on_press(SignMessageMessage::VerifyProofFile.into()),
+                    )
+                    .push(
+                        button::primary(None, "Verify").on_press_maybe(
+                            (!address.value.is_empty() && !signature.value.is_empty())
+                                .then_some(SignMessageMessage::Verify.into()),
+                        ),
+                    ),
+            ),
+    )
+    .into()
+}
@@ -1,4 +1,8 @@
+pub mod contacts;
+pub mod drill;
 pub mod general;
+pub mod heir;
+pub mod message;
 
 use std::collections::{HashMap, HashSet};
 use std::str::FromStr;
@@ -7,7 +11,7 @@ use iced::alignment::{Horizontal, Vertical};
 use iced::widget::{container, Column, Rule};
 use iced::{
     alignment,
-    widget::{radio, scrollable, tooltip as iced_tooltip, Space},
+    widget::{pick_list, progress_bar, radio, scrollable, tooltip as iced_tooltip, Space, Toggler},
     Alignment, Length,
 };
 
@@ -37,7 +41,9 @@ use crate::{
     help,
     hw::HardwareWallet,
     node::{
-        bitcoind::{RpcAuthType, RpcAuthValues},
+        bitcoind::{
+            upgradable_versions, InternalBitcoindNetworkConfig, RpcAuthType, RpcAuthValues,
+        },
         electrum::{self, validate_domain_checkbox},
     },
 };
@@ -153,6 +159,34 @@ pub fn list<'a>(menu: &'a Menu, cache: &'a Cache, is_remote_backend: bool) -> El
         Message::Settings(SettingsMessage::ImportExportSection),
     );
 
+    let sign_message = settings_section(
+        "Sign message",
+        Some("Sign a message or a proof of reserves with your keys (BIP 322)"),
+        icon::key_icon(),
+        Message::Settings(SettingsMessage::MessageSection),
+    );
+
+    let contacts = settings_section(
+        "Address book",
+        Some("The payees you send to, with their verified addresses"),
+        icon::person_icon(),
+        Message::Settings(SettingsMessage::ContactsSection),
+    );
+
+    let heir = settings_section(
+        "Heir package",
+        Some("Recovery transactions for your heir, signed in advance"),
+        icon::recovery_icon(),
+        Message::Settings(SettingsMessage::HeirSection),
+    );
+
+    let drill = settings_section(
+        "Recovery drill",
+        Some("Rehearse the recovery of the wallet on a regtest node"),
+        icon::clock_icon(),
+        Message::Settings(SettingsMessage::DrillSection),
+    );
+
     let about = settings_section(
         "About",
         None,
@@ -172,6 +206,10 @@ pub fn list<'a>(menu: &'a Menu, cache: &'a Cache, is_remote_backend: bool) -> El
             .push(if !is_remote_backend { node } else { backend })
             .push(wallet)
             .push(import_export)
+            .push(sign_message)
+            .push(contacts)
+            .push(heir)
+            .push(drill)
             .push(about),
     )
 }
@@ -235,6 +273,20 @@ pub fn import_export<'a>(
         Message::Settings(SettingsMessage::ExportPlaintextDescriptor),
     );
 
+    let export_coldcard_registration = export_section(
+        "Coldcard registration file",
+        "Miniscript wallet file to register the descriptor on an air-gapped Coldcard via SD card.",
+        icon::backup_icon(),
+        Message::Settings(SettingsMessage::ExportColdcardRegistration),
+    );
+
+    let export_descriptor_qr = export_section(
+        "Descriptor - animated QR code",
+        "BC-UR animated QR code of the descriptor, to register it on air-gapped signing devices.",
+        icon::qr_code_icon(),
+        Message::Settings(SettingsMessage::ShowDescriptorQr),
+    );
+
     let export_transactions = export_section(
         "Transactions table",
         ".CSV file of past transactions, for accounting purposes.",
@@ -249,6 +301,13 @@ pub fn import_export<'a>(
         Message::Settings(SettingsMessage::ExportLabels),
     );
 
+    let import_labels = export_section(
+        "Import BIP 329 labels",
+        "Import labels from another wallet, such as Sparrow.",
+        icon::restore_icon(),
+        Message::Settings(SettingsMessage::ImportLabels),
+    );
+
     let export_wallet = export_section(
         "Export wallet",
         "File (not encrypted) with wallet info useful to sync labels and data on other devices.",
@@ -284,8 +343,11 @@ pub fn import_export<'a>(
             .push(import_wallet)
             .push(separator)
             .push(export_labels)
+            .push(import_labels)
             .push(export_transactions)
             .push(export_descriptor)
+            .push(export_descriptor_qr)
+            .push(export_coldcard_registration)
             .width(Length::Fill),
     )
 }
@@ -856,6 +918,236 @@ pub fn electrum<'a>(
     .into()
 }
 
+#[allow(clippy::too_many_arguments)]
+pub fn managed_bitcoind<'a>(
+    installed_version: Option<&'static str>,
+    config: Option<&InternalBitcoindNetworkConfig>,
+    edit: bool,
+    processing: bool,
+    can_edit: bool,
+    prune: &form::Value<String>,
+    dbcache: &form::Value<String>,
+    max_connections: &form::Value<String>,
+    listen: bool,
+    min_signatures: &form::Value<String>,
+    selected_version: Option<&'static str>,
+    download: Option<(&'static str, f32)>,
+    error: Option<&String>,
+) -> Element<'a, ManagedBitcoindMessage> {
+    let mut col = Column::new().spacing(20);
+
+    let field_row = |label: &'static str, value: String| {
+        Row::new()
+            .push(Container::new(text(label).bold().small()).width(Length::FillPortion(1)))
+            .push(
+                Container::new(text(value).small())
+                    .align_x(alignment::Horizontal::Right)
+                    .width(Length::FillPortion(1)),
+            )
+            .align_y(Alignment::Center)
+    };
+
+    if let Some(conf) = config {
+        if edit {
+            col = col
+                .push(
+                    Column::new()
+                        .push(text("Prune (MiB, 0 to disable):").bold().small())
+                        .push(
+                            form::Form::new_trimmed("Prune", prune, |value| {
+                                ManagedBitcoindMessage::FieldEdited("prune", value)
+                            })
+                            .warning("Please enter a valid number")
+                            .size(P1_SIZE)
+                            .padding(5),
+                        )
+                        .spacing(5),
+                )
+                .push(
+                    Column::new()
+                        .push(text("Database cache (MiB):").bold().small())
+                        .push(
+                            form::Form::new_trimmed("Default", dbcache, |value| {
+                                ManagedBitcoindMessage::FieldEdited("dbcache", value)
+                            })
+                            .warning("Please enter a valid number")
+                            .size(P1_SIZE)
+                            .padding(5),
+                        )
+                        .spacing(5),
+                )
+                .push(
+                    Column::new()
+                        .push(text("Maximum peer connections:").bold().small())
+                        .push(
+                            form::Form::new_trimmed("Default", max_connections, |value| {
+                                ManagedBitcoindMessage::FieldEdited("max_connections", value)
+                            })
+                            .warning("Please enter a valid number")
+                            .size(P1_SIZE)
+                            .padding(5),
+                        )
+                        .spacing(5),
+                )
+                .push(
+                    Row::new()
+                        .push(text("Accept incoming connections:").bold().small())
+                        .push(Space::with_width(Length::Fill))
+                        .push(
+                            Toggler::new(listen)
+                                .on_toggle_maybe(
+                                    (!processing).then_some(ManagedBitcoindMessage::ListenToggled),
+                                )
+                                .style(theme::toggler::primary),
+                        )
+                        .align_y(Alignment::Center),
+                )
+                .push(
+                    Column::new()
+                        .push(
+                            text("Builder signatures required to install a release:")
+                                .bold()
+                                .small(),
+                        )
+                        .push(
+                            form::Form::new_trimmed("Signatures", min_signatures, |value| {
+                                ManagedBitcoindMessage::FieldEdited("min_signatures", value)
+                            })
+                            .warning("Please enter a valid number of signatures")
+                            .size(P1_SIZE)
+                            .padding(5),
+                        )
+                        .spacing(5),
+                );
+            let mut cancel_button = button::transparent(None, " Cancel ").padding(5);
+            let mut confirm_button = button::secondary(None, " Save and restart ").padding(5);
+            if !processing {
+                cancel_button = cancel_button.on_press(ManagedBitcoindMessage::Cancel);
+                confirm_button = confirm_button.on_press(ManagedBitcoindMessage::Save);
+            }
+            col = col.push(
+                Container::new(
+                    Row::new()
+                        .push(cancel_button)
+                        .push(confirm_button)
+                        .spacing(10)
+                        .align_y(Alignment::Center),
+                )
+                .width(Length::Fill)
+                .align_x(alignment::Horizontal::Right),
+            );
+        } else {
+            col = col
+                .push(field_row(
+                    "Prune:",
+                    if conf.prune == 0 {
+                        "Disabled".to_string()
+                    } else {
+                        format!("{} MiB", conf.prune)
+                    },
+                ))
+                .push(field_row(
+                    "Database cache:",
+                    conf.dbcache
+                        .map(|v| format!("{} MiB", v))
+                        .unwrap_or_else(|| "Default".to_string()),
+                ))
+                .push(field_row(
+                    "Maximum peer connections:",
+                    conf.max_connections
+                        .map(|v| v.to_string())
+                        .unwrap_or_else(|| "Default".to_string()),
+                ))
+                .push(field_row(
+                    "Accept incoming connections:",
+                    if conf.listen.unwrap_or(true) {
+                        "Yes".to_string()
+                    } else {
+                        "No".to_string()
+                    },
+                ))
+                .push(field_row(
+                    "Builder signatures required:",
+                    min_signatures.value.clone(),
+                ));
+        }
+    }
+
+    if let Some(version) = installed_version {
+        col = col
+            .push(separation().width(Length::Fill))
+            .push(field_row("Version:", version.to_string()));
+        let versions = upgradable_versions(version);
+        if let Some((version, progress)) = download {
+            col = col.push(
+                Column::new()
+                    .spacing(10)
+                    .push(text(format!(
+                        "Downloading Bitcoin Core {version}... {progress:.2}%"
+                    )))
+                    .push(progress_bar(0.0..=100.0, progress)),
+            );
+        } else if processing && !edit {
+            col = col.push(text("Upgrading and restarting bitcoind..."));
+        } else if !versions.is_empty() {
+            col = col.push(
+                Row::new()
+                    .spacing(10)
+                    .align_y(Alignment::Center)
+                    .push(text("Upgrade to:").bold().small())
+                    .push(Space::with_width(Length::Fill))
+                    .push(
+                        pick_list(
+                            versions,
+                            selected_version,
+                            ManagedBitcoindMessage::UpgradeVersionSelected,
+                        )
+                        .style(theme::pick_list::primary)
+                        .padding(5),
+                    )
+                    .push(
+                        button::secondary(None, " Upgrade ")
+                            .padding(5)
+                            .on_press_maybe(
+                                (can_edit && !processing && !edit && selected_version.is_some())
+                                    .then_some(ManagedBitcoindMessage::Upgrade),
+                            ),
+                    ),
+            );
+        }
+    }
+
+    card::simple(Container::new(
+        Column::new()
+            .push(
+                Row::new()
+                    .push(
+                        Row::new()
+                            .push(badge::badge(icon::bitcoin_icon()))
+                            .push(text("Managed Bitcoin Core").bold())
+                            .spacing(20)
+                            .align_y(Alignment::Center)
+                            .width(Length::Fill),
+                    )
+                    .push(
+                        Button::new(icon::pencil_icon())
+                            .style(theme::button::transparent_border)
+                            .on_press_maybe(
+                                (can_edit && !edit && !processing && config.is_some())
+                                    .then_some(ManagedBitcoindMessage::Edit),
+                            ),
+                    )
+                    .align_y(Alignment::Center),
+            )
+            .push(separation().width(Length::Fill))
+            .push(col)
+            .push_maybe(error.map(|e| text(e.to_string()).style(theme::text::error)))
+            .spacing(20),
+    ))
+    .width(Length::Fill)
+    .into()
+}
+
 pub fn is_running_label<'a, T: 'a>(is_running: Option<bool>) -> Container<'a, T> {
     if let Some(running) = is_running {
         if running {
@@ -2,7 +2,7 @@ use std::collections::HashMap;
 
 use iced::{
     alignment,
-    widget::{checkbox, scrollable, tooltip, Column, Container, Row, Space},
+    widget::{checkbox, pick_list, scrollable, tooltip, Column, Container, Row, Space},
     Alignment, Length,
 };
 
@@ -26,6 +26,7 @@ use crate::{
     },
     daemon::model::{remaining_sequence, Coin, SpendTx},
 };
+use coincubed::policy::SpendingPolicy;
 
 #[allow(clippy::too_many_arguments)]
 pub fn spend_view<'a>(
@@ -58,6 +59,11 @@ pub fn spend_view<'a>(
                     .width(Length::Fill),
             )
             .push(psbt::spend_header(tx, labels_editing))
+            .push_maybe(if saved {
+                psbt::spending_policy_status(tx, cache.spending_policy())
+            } else {
+                None
+            })
             .push_maybe(if spend_warnings.is_empty() || saved {
                 None
             } else {
@@ -85,6 +91,7 @@ pub fn spend_view<'a>(
                     .push(psbt::inputs_view(
                         &tx.coins,
                         &tx.psbt.unsigned_tx,
+                        &tx.psbt.inputs,
                         &tx.labels,
                         labels_editing,
                     ))
@@ -141,6 +148,7 @@ pub fn create_spend_tx<'a>(
     cache: &'a Cache,
     fiat_converter: Option<&FiatAmountConverter>,
     recipients: Vec<Element<'a, Message>>,
+    op_return: Option<(&'a form::Value<String>, bool)>,
     is_valid: bool,
     duplicate: bool,
     timelock: u16,
@@ -168,6 +176,7 @@ pub fn create_spend_tx<'a>(
             } else {
                 "Send"
             }))
+            .push_maybe(cache.spending_policy().and_then(spending_policy_summary))
             .push_maybe(if recipients.len() > 1 {
                 Some(
                     form::Form::new("Batch label", batch_label, |s| {
@@ -183,6 +192,9 @@ pub fn create_spend_tx<'a>(
             .push(
                 Column::new()
                     .push(Column::with_children(recipients).spacing(10))
+                    .push_maybe(op_return.map(|(data, is_hex)| {
+                        op_return_view(data, is_hex).map(Message::CreateSpend)
+                    }))
                     .push(
                         Row::new()
                             .push_maybe(if duplicate {
@@ -197,6 +209,13 @@ pub fn create_spend_tx<'a>(
                                 None
                             })
                             .push(Space::with_width(Length::Fill))
+                            .push_maybe((op_return.is_none() && recovery_timelock.is_none()).then(
+                                || {
+                                    button::secondary(Some(icon::plus_icon()), "Add data").on_press(
+                                        Message::CreateSpend(CreateSpendMessage::AddOpReturn),
+                                    )
+                                },
+                            ))
                             .push_maybe(if is_self_send || recovery_timelock.is_some() {
                                 // Recipients cannot be added for self-send (zero recipients) and recovery (exactly one recipient).
                                 None
@@ -242,9 +261,7 @@ pub fn create_spend_tx<'a>(
                             form::Form::new_trimmed("42 (in sats/vbyte)", feerate, move |msg| {
                                 Message::CreateSpend(CreateSpendMessage::FeerateEdited(msg))
                             })
-                            .warning(
-                                "Feerate must be an integer less than or equal to 1000 sats/vbyte",
-                            )
+                            .warning("Feerate must be between 0.1 and 1000 sats/vbyte")
                             .size(P1_SIZE)
                             .padding(10),
                         )
@@ -392,15 +409,88 @@ pub fn create_spend_tx<'a>(
     )
 }
 
+/// The rules of the spending policy of the daemon, if it has any.
+fn spending_policy_summary<'a>(policy: &SpendingPolicy) -> Option<Element<'a, Message>> {
+    let mut rules = Vec::new();
+    if let Some(limit) = policy.daily_limit {
+        rules.push(format!(
+            "At most {} may be spent over 24 hours.",
+            Amount::from_sat(limit)
+        ));
+    }
+    if let Some(limit) = policy.weekly_limit {
+        rules.push(format!(
+            "At most {} may be spent over 7 days.",
+            Amount::from_sat(limit)
+        ));
+    }
+    if !policy.allowed_destinations.is_empty() {
+        rules.push(format!(
+            "Payments may only be sent to {} allowed address{}.",
+            policy.allowed_destinations.len(),
+            if policy.allowed_destinations.len() > 1 {
+                "es"
+            } else {
+                ""
+            }
+        ));
+    }
+    if let Some(threshold) = policy.delay_threshold {
+        rules.push(format!(
+            "Spends of at least {} may only be broadcast {} after they are saved.",
+            Amount::from_sat(threshold),
+            if policy.broadcast_delay >= 3600 {
+                format!("{} hour(s)", policy.broadcast_delay / 3600)
+            } else {
+                format!("{} minute(s)", policy.broadcast_delay / 60)
+            }
+        ));
+    }
+    if policy.require_approval {
+        rules.push("Spends must be approved before being broadcast.".to_string());
+    }
+    if rules.is_empty() {
+        return None;
+    }
+    Some(
+        Container::new(
+            rules.into_iter().fold(
+                Column::new()
+                    .spacing(5)
+                    .push(text("Spending policy").bold()),
+                |col, rule| col.push(p2_regular(rule).style(theme::text::secondary)),
+            ),
+        )
+        .padding(15)
+        .style(theme::card::simple)
+        .into(),
+    )
+}
+
+/// What the address book says about the address of a recipient.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecipientContact {
+    /// The address belongs to this contact.
+    Known {
+        name: String,
+        verified_on: Option<String>,
+    },
+    /// The address looks like this address of the contact but is not one of them.
+    LookAlike { name: String, address: String },
+}
+
 #[allow(clippy::too_many_arguments)]
 pub fn recipient_view<'a>(
     index: usize,
     address: &'a form::Value<String>,
+    contacts: Vec<ContactAddress>,
+    contact: Option<&'a RecipientContact>,
     amount: &'a form::Value<String>,
     fiat_form_value: Option<&'a form::Value<String>>,
     fiat_converter: Option<&FiatAmountConverter>,
     label: &'a form::Value<String>,
     is_max_selected: bool,
+    is_subtract_fee_selected: bool,
     is_recovery: bool,
 ) -> Element<'a, CreateSpendMessage> {
     let btc_amt = Amount::from_str_in(&amount.value, Denomination::Bitcoin).ok();
@@ -438,6 +528,52 @@ pub fn recipient_view<'a>(
                         .padding(10),
                     ),
             )
+            .push_maybe((!contacts.is_empty()).then(|| {
+                Row::new()
+                    .spacing(10)
+                    .push(Space::with_width(Length::Fixed(130.0)))
+                    .push(
+                        pick_list(contacts, None::<ContactAddress>, move |c| {
+                            CreateSpendMessage::RecipientContactSelected(index, c)
+                        })
+                        .placeholder("Select a contact")
+                        .style(theme::pick_list::primary)
+                        .padding(10),
+                    )
+            }))
+            .push_maybe(contact.map(|contact| {
+                Row::new()
+                    .spacing(10)
+                    .align_y(Alignment::Center)
+                    .push(Space::with_width(Length::Fixed(130.0)))
+                    .push(match contact {
+                        RecipientContact::Known { name, verified_on } => Row::new()
+                            .spacing(5)
+                            .align_y(Alignment::Center)
+                            .push(icon::person_icon().style(theme::text::secondary))
+                            .push(
+                                p2_regular(match verified_on {
+                                    Some(date) => format!("{}, verified on {}", name, date),
+                                    None => format!("{}, not verified", name),
+                                })
+                                .style(theme::text::secondary),
+                            ),
+                        RecipientContact::LookAlike { name, address } => Row::new()
+                            .spacing(5)
+                            .align_y(Alignment::Center)
+                            .push(icon::warning_icon().style(theme::text::warning))
+                            .push(
+                                p2_regular(format!(
+                                    "This address looks like the address {} of {} but is \
+                                     different. It may be an address poisoning attempt: check \
+                                     it with the payee before sending.",
+                                    address, name
+                                ))
+                                .style(theme::text::warning)
+                                .width(Length::Fill),
+                            ),
+                    })
+            }))
             .push(
                 Row::new()
                     .align_y(Alignment::Start)
@@ -556,6 +692,68 @@ pub fn recipient_view<'a>(
                         )),
                     )
                     .width(Length::Fill),
+            )
+            .push_maybe(
+                // The recipient receiving the max already pays for the fee.
+                (!is_recovery && !is_max_selected).then_some(
+                    Row::new()
+                        .spacing(10)
+                        .push(Space::with_width(Length::Fixed(130.0)))
+                        .push(tooltip::Tooltip::new(
+                            checkbox("Subtract fee from amount", is_subtract_fee_selected)
+                                .on_toggle(move |_| {
+                                    CreateSpendMessage::SubtractFeeFromRecipient(index)
+                                }),
+                            // Add spaces at end so that text is padded at screen edge.
+                            "The recipient receives the amount minus the fee, split with any other \
+                             recipient doing so     ",
+                            tooltip::Position::Bottom,
+                        )),
+                ),
+            ),
+    )
+    .padding(20)
+    .style(theme::card::simple)
+    .into()
+}
+
+fn op_return_view(data: &form::Value<String>, is_hex: bool) -> Element<CreateSpendMessage> {
+    Container::new(
+        Column::new()
+            .spacing(10)
+            .push(
+                Row::new()
+                    .align_y(Alignment::Center)
+                    .push(p1_bold("Data (OP_RETURN)").width(Length::Fill))
+                    .push(
+                        Button::new(icon::cross_icon())
+                            .style(theme::button::transparent)
+                            .on_press(CreateSpendMessage::DeleteOpReturn)
+                            .width(Length::Shrink),
+                    ),
+            )
+            .push(
+                p2_regular(
+                    "Commit to some data in the transaction, for instance to timestamp a \
+                     document. It is public and can't be removed from the blockchain.",
+                )
+                .style(theme::text::secondary),
+            )
+            .push(
+                Row::new()
+                    .align_y(Alignment::Center)
+                    .spacing(10)
+                    .push(
+                        form::Form::new(
+                            if is_hex { "Hex encoded data" } else { "Text" },
+                            data,
+                            CreateSpendMessage::OpReturnEdited,
+                        )
+                        .warning("Invalid data. (Note data is limited to 80 bytes.)")
+                        .size(P1_SIZE)
+                        .padding(10),
+                    )
+                    .push(checkbox("Hex", is_hex).on_toggle(CreateSpendMessage::OpReturnIsHex)),
             ),
     )
     .padding(20)
@@ -3,7 +3,7 @@ use std::collections::{HashMap, HashSet};
 use chrono::{DateTime, Local, Utc};
 use iced::{
     alignment,
-    widget::{tooltip, Space},
+    widget::{pick_list, tooltip, Space},
     Alignment, Length,
 };
 
@@ -13,31 +13,38 @@ use coincube_ui::{
     theme,
     widget::*,
 };
+use coincubed::commands::CoinStatus;
 
 use crate::{
+    accounting::{CostBasis, ALL_COST_BASES},
     app::{
         cache::Cache,
         error::Error,
         menu::Menu,
+        state::vault::search::SearchForm,
         view::{
             dashboard,
-            message::{CreateRbfMessage, Message},
+            message::{AccountingMessage, CreateCpfpMessage, CreateRbfMessage, Message},
             placeholder,
-            vault::{label, warning::warn},
+            vault::{label, search::search_bar, warning::warn},
         },
     },
-    daemon::model::{HistoryTransaction, Txid},
+    daemon::model::{HistoryTransaction, OutPoint, Txid},
     export::ImportExportMessage,
 };
 
+#[allow(clippy::too_many_arguments)]
 pub fn transactions_view<'a>(
     menu: &'a Menu,
     cache: &'a Cache,
     txs: &'a [HistoryTransaction],
+    search: Option<&'a SearchForm>,
+    cost_basis: CostBasis,
     warning: Option<&'a Error>,
     is_last_page: bool,
     processing: bool,
 ) -> Element<'a, Message> {
+    let filtered = search.is_some_and(|s| s.applied.is_some());
     dashboard(
         menu,
         cache,
@@ -46,19 +53,51 @@ pub fn transactions_view<'a>(
             .push(
                 Row::new()
                     .push(Container::new(h3("Transactions")))
+                    .spacing(10)
+                    .align_y(Alignment::Center)
                     .push(Space::with_width(Length::Fill))
+                    .push(
+                        pick_list(ALL_COST_BASES, Some(cost_basis), |c| {
+                            Message::Accounting(AccountingMessage::CostBasisSelected(c))
+                        })
+                        .style(theme::pick_list::primary)
+                        .padding(10),
+                    )
+                    .push(
+                        button::secondary(Some(icon::backup_icon()), "Accounting export")
+                            .on_press(Message::Accounting(AccountingMessage::Export)),
+                    )
                     .push(
                         button::secondary(Some(icon::backup_icon()), "Export")
                             .on_press(ImportExportMessage::Open.into()),
                     ),
             )
-            .push_maybe(txs.is_empty().then(|| {
-                placeholder(
-                    receipt_icon().size(80),
-                    "No transactions yet",
-                    "Your transaction history will appear here once you send or receive coins.",
+            .push_maybe(search.map(|s| {
+                search_bar(
+                    s,
+                    &[
+                        CoinStatus::Unconfirmed,
+                        CoinStatus::Confirmed,
+                        CoinStatus::Spending,
+                        CoinStatus::Spent,
+                    ],
                 )
             }))
+            .push_maybe(txs.is_empty().then(|| {
+                if filtered {
+                    placeholder(
+                        receipt_icon().size(80),
+                        "No matching transactions",
+                        "No transaction matches the current filters.",
+                    )
+                } else {
+                    placeholder(
+                        receipt_icon().size(80),
+                        "No transactions yet",
+                        "Your transaction history will appear here once you send or receive coins.",
+                    )
+                }
+            }))
             .push(
                 Column::new()
                     .spacing(10)
@@ -311,6 +350,79 @@ pub fn create_rbf_modal<'a>(
     .into()
 }
 
+/// Return the modal view for a new CPFP transaction.
+///
+/// `outpoints` are the unconfirmed outputs of the parent transaction belonging to this
+/// wallet that will be spent by the child.
+pub fn create_cpfp_modal<'a>(
+    outpoints: &'a [OutPoint],
+    feerate: &form::Value<String>,
+    can_confirm: bool,
+    child_txid: Option<Txid>,
+    warning: Option<&'a Error>,
+) -> Element<'a, Message> {
+    let mut confirm_button = button::secondary(None, "Confirm").width(Length::Fixed(200.0));
+    if can_confirm {
+        confirm_button = confirm_button.on_press(Message::CreateCpfp(CreateCpfpMessage::Confirm));
+    }
+    card::simple(
+        Column::new()
+            .spacing(10)
+            .push(Container::new(h4_bold("Child pays for parent")).width(Length::Fill))
+            .push(Row::new().push(text(
+                "Create a transaction spending the outputs of this transaction \
+                back to your wallet, paying a fee high enough for both of them \
+                to reach the target feerate. Other coins may be added to cover the fee.",
+            )))
+            .push(outpoints.iter().fold(
+                Column::new().spacing(5).push(text("Outputs to spend:")),
+                |col, outpoint| {
+                    col.push(Row::new().padding([0, 30]).push(text(outpoint.to_string())))
+                },
+            ))
+            .push(
+                Row::new()
+                    .push(Container::new(p1_bold("Package feerate")).padding(10))
+                    .spacing(10)
+                    .push(
+                        if child_txid.is_none() {
+                            form::Form::new_trimmed("", feerate, move |msg| {
+                                Message::CreateCpfp(CreateCpfpMessage::FeerateEdited(msg))
+                            })
+                            .warning("Feerate must be between 0.1 and 1000 sats/vbyte")
+                        } else {
+                            form::Form::new_disabled("", feerate)
+                        }
+                        .size(P1_SIZE)
+                        .padding(10),
+                    )
+                    .width(Length::Fill),
+            )
+            .push(warn(warning))
+            .push(Row::new().push(if child_txid.is_none() {
+                Row::new().push(confirm_button)
+            } else {
+                Row::new()
+                    .spacing(10)
+                    .align_y(Alignment::Center)
+                    .push(icon::square_check_icon().style(theme::text::secondary))
+                    .push(
+                        text("Child PSBT created successfully and ready to be signed")
+                            .style(theme::text::success),
+                    )
+            }))
+            .push_maybe(child_txid.map(|id| {
+                Row::new().push(
+                    button::primary(None, "Go to child transaction")
+                        .width(Length::Fixed(200.0))
+                        .on_press(Message::Menu(Menu::PsbtPreSelected(id))),
+                )
+            })),
+    )
+    .width(Length::Fixed(800.0))
+    .into()
+}
+
 pub fn tx_view<'a>(
     menu: &'a Menu,
     cache: &'a Cache,
@@ -376,17 +488,17 @@ pub fn tx_view<'a>(
                         })),
                 ),
             )
-            // If unconfirmed, give option to use RBF.
-            // Check fee amount is some as otherwise we may be missing coins for this transaction.
-            .push_maybe(if tx.time.is_none() && tx.fee_amount.is_some() {
+            // If unconfirmed, give option to use RBF and CPFP.
+            // Check fee amount is some for RBF as otherwise we may be missing coins for this transaction.
+            .push_maybe(if tx.time.is_none() {
                 Some(
                     Row::new()
-                        .push(
+                        .push_maybe(tx.fee_amount.is_some().then(|| {
                             button::secondary(None, "Bump fee")
                                 .width(Length::Fixed(200.0))
-                                .on_press(Message::CreateRbf(super::super::CreateRbfMessage::New(false))),
-                        )
-                        .push(
+                                .on_press(Message::CreateRbf(super::super::CreateRbfMessage::New(false)))
+                        }))
+                        .push_maybe(tx.fee_amount.is_some().then(|| {
                             tooltip::Tooltip::new(
                                 button::secondary(None, "Cancel transaction")
                                 .width(Length::Fixed(200.0))
@@ -394,6 +506,15 @@ pub fn tx_view<'a>(
                                 "Best effort attempt at double spending an unconfirmed outgoing transaction",
                                 tooltip::Position::Top,
                             )
+                        }))
+                        .push(
+                            tooltip::Tooltip::new(
+                                button::secondary(None, "Bump fee (CPFP)")
+                                .width(Length::Fixed(200.0))
+                                .on_press(Message::CreateCpfp(CreateCpfpMessage::New)),
+                                "Spend an output of this transaction to pay for its confirmation",
+                                tooltip::Position::Top,
+                            )
                         )
                         .spacing(10),
                 )
@@ -441,6 +562,7 @@ pub fn tx_view<'a>(
                         Some(super::psbt::inputs_view(
                             &tx.coins,
                             &tx.tx,
+                            &[],
                             &tx.labels,
                             labels_editing,
                         ))
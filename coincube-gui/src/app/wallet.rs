@@ -3,6 +3,7 @@ use std::sync::Arc;
 
 use crate::app::cache::FiatPrice;
 use crate::dir::CoincubeDirectory;
+use crate::heir::HeirPackageRecord;
 use crate::{
     app::settings, daemon::DaemonBackend, hw::HardwareWalletConfig, node::NodeType, signer::Signer,
 };
@@ -12,7 +13,7 @@ use coincube_core::{miniscript::bitcoin, signer::HotSigner};
 use coincube_core::descriptors::CoincubeDescriptor;
 use coincube_core::miniscript::bitcoin::bip32::Fingerprint;
 
-use super::settings::{fiat, WalletId, WalletSettings};
+use super::settings::{contacts::Contact, fiat, WalletId, WalletSettings};
 
 const DEFAULT_WALLET_NAME: &str = "Coincube";
 
@@ -43,6 +44,8 @@ pub struct Wallet {
     pub hardware_wallets: Vec<HardwareWalletConfig>,
     pub signer: Option<Arc<Signer>>,
     pub fiat_price_setting: Option<fiat::PriceSetting>,
+    pub contacts: Vec<Contact>,
+    pub heir_package: Option<HeirPackageRecord>,
 }
 
 impl Wallet {
@@ -63,6 +66,8 @@ impl Wallet {
             hardware_wallets: Vec::new(),
             signer: None,
             fiat_price_setting: None,
+            contacts: Vec::new(),
+            heir_package: None,
         }
     }
 
@@ -117,6 +122,16 @@ impl Wallet {
         self
     }
 
+    pub fn with_contacts(mut self, contacts: Vec<Contact>) -> Self {
+        self.contacts = contacts;
+        self
+    }
+
+    pub fn with_heir_package(mut self, heir_package: Option<HeirPackageRecord>) -> Self {
+        self.heir_package = heir_package;
+        self
+    }
+
     pub fn descriptor_keys(&self) -> HashSet<Fingerprint> {
         let info = self.main_descriptor.policy();
         let mut descriptor_keys = HashSet::new();
@@ -142,7 +157,9 @@ impl Wallet {
                 .with_name(wallet_settings.name)
                 .with_pinned_at(wallet_settings.pinned_at)
                 .with_hardware_wallets(wallet_settings.hardware_wallets)
-                .with_fiat_price_setting(wallet_settings.fiat_price))
+                .with_fiat_price_setting(wallet_settings.fiat_price)
+                .with_contacts(wallet_settings.contacts)
+                .with_heir_package(wallet_settings.heir_package))
         }
     }
 
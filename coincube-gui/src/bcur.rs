@@ -0,0 +1,985 @@
+//! BC-UR encoding and decoding, used to exchange PSBTs and descriptors with air-gapped signing
+//! devices through animated QR codes.
+//!
+//! Supported registry types are `crypto-psbt`, `crypto-output` and `crypto-account` as
+//! specified in BCR-2020-006, BCR-2020-010 and BCR-2020-015, along with plain `bytes`.
+use std::convert::TryFrom;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use coincube_core::{
+    miniscript::{
+        bitcoin::{
+            bip32::{ChainCode, ChildNumber, DerivationPath, Fingerprint, Xpub},
+            psbt::Psbt,
+            secp256k1, NetworkKind,
+        },
+        descriptor::{DescriptorXKey, ShInner, Wildcard, WshInner},
+        Descriptor, DescriptorPublicKey, ForEachKey, Terminal,
+    },
+    psbtv2,
+};
+use iced::widget::qr_code;
+
+/// Maximum length of the fragments of a multi-part UR, small enough for the frames to be read
+/// by the cameras of most signing devices.
+pub const MAX_FRAGMENT_LEN: usize = 200;
+
+/// Maximum nesting of CBOR arrays, maps and tags. The registry types we support don't go
+/// beyond a handful of levels, this protects from a crafted payload exhausting the stack.
+const MAX_CBOR_DEPTH: usize = 32;
+
+const TAG_HDKEY: u64 = 303;
+const TAG_KEYPATH: u64 = 304;
+const TAG_COIN_INFO: u64 = 305;
+const TAG_ECKEY: u64 = 306;
+const TAG_OUTPUT: u64 = 308;
+const TAG_SH: u64 = 400;
+const TAG_WSH: u64 = 401;
+const TAG_PK: u64 = 402;
+const TAG_PKH: u64 = 403;
+const TAG_WPKH: u64 = 404;
+const TAG_MULTI: u64 = 406;
+const TAG_SORTEDMULTI: u64 = 407;
+const TAG_TR: u64 = 409;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    Ur(String),
+    Cbor(String),
+    UnknownType(String),
+    Incomplete { received: usize },
+    UnsupportedDescriptor,
+    Image(String),
+    NoQrCode,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Ur(e) => write!(f, "Invalid UR: {e}"),
+            Error::Cbor(e) => write!(f, "Invalid UR payload: {e}"),
+            Error::UnknownType(t) => write!(f, "Unsupported UR type '{t}'"),
+            Error::Incomplete { received } => write!(
+                f,
+                "Only {received} part(s) received, scan more frames of the animated QR code"
+            ),
+            Error::UnsupportedDescriptor => {
+                write!(f, "Descriptor cannot be expressed as a crypto-output")
+            }
+            Error::Image(e) => write!(f, "Failed to read image: {e}"),
+            Error::NoQrCode => write!(f, "No QR code found in image"),
+        }
+    }
+}
+
+impl From<ur::ur::Error> for Error {
+    fn from(e: ur::ur::Error) -> Self {
+        Self::Ur(e.to_string())
+    }
+}
+
+/// The registry types this module knows how to handle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UrType {
+    CryptoPsbt,
+    CryptoOutput,
+    CryptoAccount,
+    Bytes,
+}
+
+impl UrType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::CryptoPsbt => "crypto-psbt",
+            Self::CryptoOutput => "crypto-output",
+            Self::CryptoAccount => "crypto-account",
+            Self::Bytes => "bytes",
+        }
+    }
+}
+
+impl FromStr for UrType {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "crypto-psbt" | "psbt" => Ok(Self::CryptoPsbt),
+            "crypto-output" | "output-descriptor" => Ok(Self::CryptoOutput),
+            "crypto-account" | "account-descriptor" => Ok(Self::CryptoAccount),
+            "bytes" => Ok(Self::Bytes),
+            _ => Err(Error::UnknownType(s.to_string())),
+        }
+    }
+}
+
+/// The content of a decoded UR.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Decoded {
+    Psbt(Box<Psbt>),
+    /// Output descriptors, a `crypto-account` may hold several.
+    Descriptors(Vec<String>),
+    Bytes(Vec<u8>),
+}
+
+/// Minimal CBOR data model, covering what the UR registry types above make use of.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cbor {
+    Uint(u64),
+    Bytes(Vec<u8>),
+    Text(String),
+    Array(Vec<Cbor>),
+    Map(Vec<(Cbor, Cbor)>),
+    Tag(u64, Box<Cbor>),
+    Bool(bool),
+}
+
+impl Cbor {
+    fn write_header(out: &mut Vec<u8>, major: u8, value: u64) {
+        let major = major << 5;
+        if value < 24 {
+            out.push(major | value as u8);
+        } else if value <= u8::MAX as u64 {
+            out.push(major | 24);
+            out.push(value as u8);
+        } else if value <= u16::MAX as u64 {
+            out.push(major | 25);
+            out.extend_from_slice(&(value as u16).to_be_bytes());
+        } else if value <= u32::MAX as u64 {
+            out.push(major | 26);
+            out.extend_from_slice(&(value as u32).to_be_bytes());
+        } else {
+            out.push(major | 27);
+            out.extend_from_slice(&value.to_be_bytes());
+        }
+    }
+
+    pub fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            Self::Uint(v) => Self::write_header(out, 0, *v),
+            Self::Bytes(b) => {
+                Self::write_header(out, 2, b.len() as u64);
+                out.extend_from_slice(b);
+            }
+            Self::Text(t) => {
+                Self::write_header(out, 3, t.len() as u64);
+                out.extend_from_slice(t.as_bytes());
+            }
+            Self::Array(items) => {
+                Self::write_header(out, 4, items.len() as u64);
+                for item in items {
+                    item.encode(out);
+                }
+            }
+            Self::Map(entries) => {
+                Self::write_header(out, 5, entries.len() as u64);
+                for (k, v) in entries {
+                    k.encode(out);
+                    v.encode(out);
+                }
+            }
+            Self::Tag(tag, item) => {
+                Self::write_header(out, 6, *tag);
+                item.encode(out);
+            }
+            Self::Bool(b) => out.push(if *b { 0xf5 } else { 0xf4 }),
+        }
+    }
+
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.encode(&mut out);
+        out
+    }
+
+    /// Decode a single CBOR item, which must span the whole of `bytes`.
+    pub fn decode(bytes: &[u8]) -> Result<Self, Error> {
+        let (item, rest) = Self::decode_item(bytes, 0)?;
+        if !rest.is_empty() {
+            return Err(Error::Cbor("trailing bytes".to_string()));
+        }
+        Ok(item)
+    }
+
+    fn decode_item(bytes: &[u8], depth: usize) -> Result<(Self, &[u8]), Error> {
+        if depth > MAX_CBOR_DEPTH {
+            return Err(Error::Cbor("nesting too deep".to_string()));
+        }
+        let eof = || Error::Cbor("unexpected end of data".to_string());
+        let (first, mut rest) = bytes.split_first().ok_or_else(eof)?;
+        let major = first >> 5;
+        let info = first & 0x1f;
+        if major == 7 {
+            return match info {
+                20 => Ok((Self::Bool(false), rest)),
+                21 => Ok((Self::Bool(true), rest)),
+                _ => Err(Error::Cbor(format!("unsupported simple value {info}"))),
+            };
+        }
+        let len = match info {
+            0..=23 => 0,
+            24 => 1,
+            25 => 2,
+            26 => 4,
+            27 => 8,
+            _ => {
+                return Err(Error::Cbor(
+                    "indefinite lengths are not supported".to_string(),
+                ))
+            }
+        };
+        let value = if len == 0 {
+            info as u64
+        } else {
+            if rest.len() < len {
+                return Err(eof());
+            }
+            let (value, r) = rest.split_at(len);
+            rest = r;
+            value.iter().fold(0u64, |acc, b| (acc << 8) | *b as u64)
+        };
+        match major {
+            0 => Ok((Self::Uint(value), rest)),
+            2 | 3 => {
+                let len = value as usize;
+                if rest.len() < len {
+                    return Err(eof());
+                }
+                let (data, rest) = rest.split_at(len);
+                if major == 2 {
+                    Ok((Self::Bytes(data.to_vec()), rest))
+                } else {
+                    let text =
+                        String::from_utf8(data.to_vec()).map_err(|e| Error::Cbor(e.to_string()))?;
+                    Ok((Self::Text(text), rest))
+                }
+            }
+            4 => {
+                let mut items = Vec::new();
+                for _ in 0..value {
+                    let (item, r) = Self::decode_item(rest, depth + 1)?;
+                    items.push(item);
+                    rest = r;
+                }
+                Ok((Self::Array(items), rest))
+            }
+            5 => {
+                let mut entries = Vec::new();
+                for _ in 0..value {
+                    let (k, r) = Self::decode_item(rest, depth + 1)?;
+                    let (v, r) = Self::decode_item(r, depth + 1)?;
+                    entries.push((k, v));
+                    rest = r;
+                }
+                Ok((Self::Map(entries), rest))
+            }
+            6 => {
+                let (item, rest) = Self::decode_item(rest, depth + 1)?;
+                Ok((Self::Tag(value, Box::new(item)), rest))
+            }
+            _ => Err(Error::Cbor(format!("unsupported major type {major}"))),
+        }
+    }
+
+    fn get(&self, key: u64) -> Option<&Cbor> {
+        if let Self::Map(entries) = self {
+            entries
+                .iter()
+                .find(|(k, _)| *k == Self::Uint(key))
+                .map(|(_, v)| v)
+        } else {
+            None
+        }
+    }
+
+    fn untag(&self, tag: u64) -> &Cbor {
+        match self {
+            Self::Tag(t, item) if *t == tag => item,
+            _ => self,
+        }
+    }
+
+    fn as_uint(&self) -> Option<u64> {
+        match self {
+            Self::Uint(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Self::Bytes(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    fn as_array(&self) -> Option<&[Cbor]> {
+        match self {
+            Self::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+}
+
+fn invalid(what: &str) -> Error {
+    Error::Cbor(format!("invalid {what}"))
+}
+
+/// The CBOR payload of a `crypto-psbt`.
+pub fn psbt_to_cbor(psbt: &Psbt) -> Vec<u8> {
+    Cbor::Bytes(psbt.serialize()).to_vec()
+}
+
+fn keypath_to_cbor(
+    path: &DerivationPath,
+    wildcard: bool,
+    fingerprint: Option<Fingerprint>,
+) -> Cbor {
+    let mut components = Vec::new();
+    for child in path {
+        let (index, hardened) = match child {
+            ChildNumber::Normal { index } => (*index, false),
+            ChildNumber::Hardened { index } => (*index, true),
+        };
+        components.push(Cbor::Uint(index as u64));
+        components.push(Cbor::Bool(hardened));
+    }
+    if wildcard {
+        components.push(Cbor::Array(Vec::new()));
+        components.push(Cbor::Bool(false));
+    }
+    let mut entries = vec![(Cbor::Uint(1), Cbor::Array(components))];
+    if let Some(fingerprint) = fingerprint {
+        entries.push((
+            Cbor::Uint(2),
+            Cbor::Uint(u32::from_be_bytes(fingerprint.to_bytes()) as u64),
+        ));
+    }
+    Cbor::Tag(TAG_KEYPATH, Box::new(Cbor::Map(entries)))
+}
+
+fn key_to_cbor(key: &DescriptorPublicKey) -> Result<Cbor, Error> {
+    match key {
+        DescriptorPublicKey::Single(single) => {
+            let data = match single.key {
+                coincube_core::miniscript::descriptor::SinglePubKey::FullKey(pk) => pk.to_bytes(),
+                coincube_core::miniscript::descriptor::SinglePubKey::XOnly(pk) => {
+                    pk.serialize().to_vec()
+                }
+            };
+            Ok(Cbor::Tag(
+                TAG_ECKEY,
+                Box::new(Cbor::Map(vec![(Cbor::Uint(3), Cbor::Bytes(data))])),
+            ))
+        }
+        DescriptorPublicKey::XPub(DescriptorXKey {
+            origin,
+            xkey,
+            derivation_path,
+            wildcard,
+        }) => {
+            let network = if xkey.network == NetworkKind::Main {
+                0
+            } else {
+                1
+            };
+            let mut entries = vec![
+                (
+                    Cbor::Uint(3),
+                    Cbor::Bytes(xkey.public_key.serialize().to_vec()),
+                ),
+                (
+                    Cbor::Uint(4),
+                    Cbor::Bytes(xkey.chain_code.to_bytes().to_vec()),
+                ),
+                (
+                    Cbor::Uint(5),
+                    Cbor::Tag(
+                        TAG_COIN_INFO,
+                        Box::new(Cbor::Map(vec![(Cbor::Uint(2), Cbor::Uint(network))])),
+                    ),
+                ),
+            ];
+            if let Some((fingerprint, path)) = origin {
+                entries.push((
+                    Cbor::Uint(6),
+                    keypath_to_cbor(path, false, Some(*fingerprint)),
+                ));
+            }
+            if !derivation_path.is_empty() || *wildcard != Wildcard::None {
+                if *wildcard == Wildcard::Hardened {
+                    return Err(Error::UnsupportedDescriptor);
+                }
+                entries.push((
+                    Cbor::Uint(7),
+                    keypath_to_cbor(derivation_path, *wildcard == Wildcard::Unhardened, None),
+                ));
+            }
+            entries.push((
+                Cbor::Uint(8),
+                Cbor::Uint(u32::from_be_bytes(xkey.parent_fingerprint.to_bytes()) as u64),
+            ));
+            Ok(Cbor::Tag(TAG_HDKEY, Box::new(Cbor::Map(entries))))
+        }
+        _ => Err(Error::UnsupportedDescriptor),
+    }
+}
+
+fn multi_to_cbor(tag: u64, k: usize, keys: &[DescriptorPublicKey]) -> Result<Cbor, Error> {
+    let keys = keys
+        .iter()
+        .map(key_to_cbor)
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Cbor::Tag(
+        tag,
+        Box::new(Cbor::Map(vec![
+            (Cbor::Uint(1), Cbor::Uint(k as u64)),
+            (Cbor::Uint(2), Cbor::Array(keys)),
+        ])),
+    ))
+}
+
+fn wsh_inner_to_cbor(inner: &WshInner<DescriptorPublicKey>) -> Result<Cbor, Error> {
+    match inner {
+        WshInner::SortedMulti(smv) => multi_to_cbor(TAG_SORTEDMULTI, smv.k(), smv.pks()),
+        WshInner::Ms(ms) => match &ms.node {
+            Terminal::Multi(thresh) => multi_to_cbor(TAG_MULTI, thresh.k(), thresh.data()),
+            _ => Err(Error::UnsupportedDescriptor),
+        },
+    }
+}
+
+/// The CBOR payload of a `crypto-output`. Only descriptors made of a single key or of a
+/// multisig can be expressed, arbitrary miniscript is not part of the specification.
+fn output_cbor(descriptor: &Descriptor<DescriptorPublicKey>) -> Result<Cbor, Error> {
+    let tagged = |tag: u64, item: Cbor| Cbor::Tag(tag, Box::new(item));
+    let output = match descriptor {
+        Descriptor::Wpkh(wpkh) => tagged(TAG_WPKH, key_to_cbor(wpkh.as_inner())?),
+        Descriptor::Pkh(pkh) => tagged(TAG_PKH, key_to_cbor(pkh.as_inner())?),
+        Descriptor::Wsh(wsh) => tagged(TAG_WSH, wsh_inner_to_cbor(wsh.as_inner())?),
+        Descriptor::Sh(sh) => match sh.as_inner() {
+            ShInner::Wsh(wsh) => {
+                tagged(TAG_SH, tagged(TAG_WSH, wsh_inner_to_cbor(wsh.as_inner())?))
+            }
+            ShInner::Wpkh(wpkh) => tagged(TAG_SH, tagged(TAG_WPKH, key_to_cbor(wpkh.as_inner())?)),
+            _ => return Err(Error::UnsupportedDescriptor),
+        },
+        Descriptor::Tr(tr) if tr.tap_tree().is_none() => {
+            tagged(TAG_TR, key_to_cbor(tr.internal_key())?)
+        }
+        _ => return Err(Error::UnsupportedDescriptor),
+    };
+    Ok(output)
+}
+
+/// The CBOR payload of a `crypto-output`.
+pub fn output_to_cbor(descriptor: &Descriptor<DescriptorPublicKey>) -> Result<Vec<u8>, Error> {
+    output_cbor(descriptor).map(|output| output.to_vec())
+}
+
+/// The CBOR payload of a `crypto-account`: the outputs derived from the keys of the device with
+/// the given master fingerprint.
+pub fn account_to_cbor(
+    master_fingerprint: Fingerprint,
+    descriptors: &[Descriptor<DescriptorPublicKey>],
+) -> Result<Vec<u8>, Error> {
+    let outputs = descriptors
+        .iter()
+        .map(output_cbor)
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Cbor::Map(vec![
+        (
+            Cbor::Uint(1),
+            Cbor::Uint(u32::from_be_bytes(master_fingerprint.to_bytes()) as u64),
+        ),
+        (Cbor::Uint(2), Cbor::Array(outputs)),
+    ])
+    .to_vec())
+}
+
+fn keypath_from_cbor(cbor: &Cbor) -> Result<(String, Option<Fingerprint>, Option<u64>), Error> {
+    let cbor = cbor.untag(TAG_KEYPATH);
+    let components = cbor
+        .get(1)
+        .and_then(Cbor::as_array)
+        .ok_or_else(|| invalid("keypath"))?;
+    let mut path = String::new();
+    for pair in components.chunks(2) {
+        let hardened = matches!(pair.get(1), Some(Cbor::Bool(true)));
+        match &pair[0] {
+            Cbor::Uint(index) => path.push_str(&format!("/{index}")),
+            Cbor::Array(a) if a.is_empty() => path.push_str("/*"),
+            _ => return Err(invalid("keypath component")),
+        }
+        if hardened {
+            path.push('\'');
+        }
+    }
+    let fingerprint = cbor
+        .get(2)
+        .and_then(Cbor::as_uint)
+        .map(|fg| Fingerprint::from((fg as u32).to_be_bytes()));
+    let depth = cbor.get(3).and_then(Cbor::as_uint);
+    Ok((path, fingerprint, depth))
+}
+
+fn key_from_cbor(cbor: &Cbor) -> Result<String, Error> {
+    match cbor {
+        Cbor::Tag(TAG_ECKEY, key) => {
+            let data = key
+                .get(3)
+                .and_then(Cbor::as_bytes)
+                .ok_or_else(|| invalid("eckey"))?;
+            Ok(hex::encode(data))
+        }
+        Cbor::Tag(TAG_HDKEY, key) => {
+            let public_key = key
+                .get(3)
+                .and_then(Cbor::as_bytes)
+                .and_then(|b| secp256k1::PublicKey::from_slice(b).ok())
+                .ok_or_else(|| invalid("hdkey key data"))?;
+            let chain_code = key
+                .get(4)
+                .and_then(Cbor::as_bytes)
+                .and_then(|b| <[u8; 32]>::try_from(b).ok())
+                .map(ChainCode::from)
+                .ok_or_else(|| invalid("hdkey chain code"))?;
+            let network = match key
+                .get(5)
+                .map(|info| info.untag(TAG_COIN_INFO))
+                .and_then(|info| info.get(2))
+                .and_then(Cbor::as_uint)
+            {
+                Some(1) => NetworkKind::Test,
+                _ => NetworkKind::Main,
+            };
+            let origin = key.get(6).map(keypath_from_cbor).transpose()?;
+            let parent_fingerprint = key
+                .get(8)
+                .and_then(Cbor::as_uint)
+                .map(|fg| Fingerprint::from((fg as u32).to_be_bytes()))
+                .unwrap_or_default();
+            let (depth, child_number) = match &origin {
+                Some((path, _, depth)) => {
+                    let path = DerivationPath::from_str(&format!("m{path}"))
+                        .map_err(|_| invalid("hdkey origin"))?;
+                    (
+                        depth.unwrap_or(path.len() as u64) as u8,
+                        path.as_ref()
+                            .last()
+                            .copied()
+                            .unwrap_or(ChildNumber::Normal { index: 0 }),
+                    )
+                }
+                None => (0, ChildNumber::Normal { index: 0 }),
+            };
+            let xpub = Xpub {
+                network,
+                depth,
+                parent_fingerprint,
+                child_number,
+                public_key,
+                chain_code,
+            };
+            let mut key_str = String::new();
+            if let Some((path, Some(fingerprint), _)) = &origin {
+                key_str.push_str(&format!("[{fingerprint}{path}]"));
+            }
+            key_str.push_str(&xpub.to_string());
+            if let Some(children) = key.get(7) {
+                key_str.push_str(&keypath_from_cbor(children)?.0);
+            }
+            Ok(key_str)
+        }
+        _ => Err(invalid("key")),
+    }
+}
+
+fn script_from_cbor(cbor: &Cbor) -> Result<String, Error> {
+    let Cbor::Tag(tag, inner) = cbor else {
+        return Err(invalid("script expression"));
+    };
+    match *tag {
+        TAG_OUTPUT => script_from_cbor(inner),
+        TAG_SH => Ok(format!("sh({})", script_from_cbor(inner)?)),
+        TAG_WSH => Ok(format!("wsh({})", script_from_cbor(inner)?)),
+        TAG_PK => Ok(format!("pk({})", key_from_cbor(inner)?)),
+        TAG_PKH => Ok(format!("pkh({})", key_from_cbor(inner)?)),
+        TAG_WPKH => Ok(format!("wpkh({})", key_from_cbor(inner)?)),
+        TAG_TR => Ok(format!("tr({})", key_from_cbor(inner)?)),
+        TAG_MULTI | TAG_SORTEDMULTI => {
+            let k = inner
+                .get(1)
+                .and_then(Cbor::as_uint)
+                .ok_or_else(|| invalid("multi"))?;
+            let keys = inner
+                .get(2)
+                .and_then(Cbor::as_array)
+                .ok_or_else(|| invalid("multi"))?
+                .iter()
+                .map(key_from_cbor)
+                .collect::<Result<Vec<_>, _>>()?;
+            let name = if *tag == TAG_MULTI {
+                "multi"
+            } else {
+                "sortedmulti"
+            };
+            Ok(format!("{name}({k},{})", keys.join(",")))
+        }
+        TAG_HDKEY | TAG_ECKEY => key_from_cbor(cbor),
+        _ => Err(Error::Cbor(format!(
+            "unsupported script expression tag {tag}"
+        ))),
+    }
+}
+
+/// Decode the CBOR payload of a UR of the given type.
+pub fn decode_payload(ur_type: UrType, payload: &[u8]) -> Result<Decoded, Error> {
+    let cbor = Cbor::decode(payload)?;
+    match ur_type {
+        UrType::CryptoPsbt => {
+            let bytes = cbor.as_bytes().ok_or_else(|| invalid("crypto-psbt"))?;
+            psbtv2::deserialize(bytes)
+                .map(|psbt| Decoded::Psbt(Box::new(psbt)))
+                .map_err(|e| Error::Cbor(e.to_string()))
+        }
+        UrType::CryptoOutput => Ok(Decoded::Descriptors(vec![script_from_cbor(&cbor)?])),
+        UrType::CryptoAccount => {
+            let outputs = cbor
+                .get(2)
+                .and_then(Cbor::as_array)
+                .ok_or_else(|| invalid("crypto-account"))?;
+            Ok(Decoded::Descriptors(
+                outputs
+                    .iter()
+                    .map(script_from_cbor)
+                    .collect::<Result<Vec<_>, _>>()?,
+            ))
+        }
+        UrType::Bytes => cbor
+            .as_bytes()
+            .map(|b| Decoded::Bytes(b.to_vec()))
+            .ok_or_else(|| invalid("bytes")),
+    }
+}
+
+fn parse_type(part: &str) -> Result<UrType, Error> {
+    part.strip_prefix("ur:")
+        .and_then(|rest| rest.split('/').next())
+        .ok_or_else(|| Error::Ur(format!("'{part}' is not a UR")))?
+        .parse()
+}
+
+/// Decode a UR from its parts, given in any order. Parts may be repeated and, for animated
+/// QR codes, not all of them are required thanks to the fountain encoding.
+pub fn decode<'a>(parts: impl IntoIterator<Item = &'a str>) -> Result<Decoded, Error> {
+    let mut decoder = ur::Decoder::default();
+    let mut ur_type = None;
+    let mut received = 0;
+    for part in parts {
+        let part = part.trim().to_lowercase();
+        if part.is_empty() {
+            continue;
+        }
+        let t = parse_type(&part)?;
+        if *ur_type.get_or_insert(t) != t {
+            return Err(Error::Ur("parts of different types".to_string()));
+        }
+        received += 1;
+        if let (ur::ur::Kind::SinglePart, payload) = ur::decode(&part)? {
+            return decode_payload(t, &payload);
+        }
+        decoder.receive(&part)?;
+        if decoder.complete() {
+            break;
+        }
+    }
+    match (ur_type, decoder.message()?) {
+        (Some(t), Some(payload)) => decode_payload(t, &payload),
+        _ => Err(Error::Incomplete { received }),
+    }
+}
+
+/// Read the content of all the QR codes found in an image. Every frame of an animated GIF is
+/// read, so that a recording of an animated QR code can be imported at once.
+pub fn qr_codes_from_image(bytes: &[u8]) -> Result<Vec<String>, Error> {
+    let image_error = |e: image::ImageError| Error::Image(e.to_string());
+    let frames = if image::guess_format(bytes).map_err(image_error)? == image::ImageFormat::Gif {
+        let decoder = image::codecs::gif::GifDecoder::new(std::io::Cursor::new(bytes))
+            .map_err(image_error)?;
+        image::AnimationDecoder::into_frames(decoder)
+            .map(|frame| {
+                frame.map(|frame| image::DynamicImage::ImageRgba8(frame.into_buffer()).to_luma8())
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(image_error)?
+    } else {
+        vec![image::load_from_memory(bytes)
+            .map_err(image_error)?
+            .to_luma8()]
+    };
+    let mut contents: Vec<String> = Vec::new();
+    for frame in frames {
+        let mut prepared = rqrr::PreparedImage::prepare(frame);
+        for grid in prepared.detect_grids() {
+            if let Ok((_, content)) = grid.decode() {
+                if !contents.contains(&content) {
+                    contents.push(content);
+                }
+            }
+        }
+    }
+    if contents.is_empty() {
+        Err(Error::NoQrCode)
+    } else {
+        Ok(contents)
+    }
+}
+
+/// Returns true if the content looks like an image the QR codes can be read from.
+pub fn is_image(bytes: &[u8]) -> bool {
+    image::guess_format(bytes).is_ok()
+}
+
+/// Returns true if the text is made of UR parts.
+pub fn is_ur(text: &str) -> bool {
+    text.trim_start().to_lowercase().starts_with("ur:")
+}
+
+/// An animated QR code cycling through the parts of a multi-part UR.
+pub struct AnimatedQr {
+    encoder: ur::Encoder<'static>,
+    frame: qr_code::Data,
+}
+
+impl std::fmt::Debug for AnimatedQr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AnimatedQr")
+            .field("fragment_count", &self.encoder.fragment_count())
+            .field("current_index", &self.encoder.current_index())
+            .finish()
+    }
+}
+
+impl AnimatedQr {
+    pub fn new(ur_type: UrType, payload: &[u8]) -> Result<Self, Error> {
+        let mut encoder = ur::Encoder::new(payload, MAX_FRAGMENT_LEN, ur_type.as_str())?;
+        let frame = Self::frame(&encoder.next_part()?)?;
+        Ok(Self { encoder, frame })
+    }
+
+    pub fn psbt(psbt: &Psbt) -> Result<Self, Error> {
+        Self::new(UrType::CryptoPsbt, &psbt_to_cbor(psbt))
+    }
+
+    pub fn account(
+        master_fingerprint: Fingerprint,
+        descriptors: &[Descriptor<DescriptorPublicKey>],
+    ) -> Result<Self, Error> {
+        Self::new(
+            UrType::CryptoAccount,
+            &account_to_cbor(master_fingerprint, descriptors)?,
+        )
+    }
+
+    /// A `crypto-account` of the receive and change descriptors if the descriptor is a
+    /// multipath one whose keys all come from the same device, a `crypto-output` of the
+    /// descriptor if it can be expressed as such, otherwise the descriptor string as `bytes`,
+    /// which most signing devices accept too.
+    pub fn descriptor(descriptor: &Descriptor<DescriptorPublicKey>) -> Result<Self, Error> {
+        if let Some(qr) = Self::single_device_account(descriptor) {
+            return Ok(qr);
+        }
+        match output_to_cbor(descriptor) {
+            Ok(payload) => Self::new(UrType::CryptoOutput, &payload),
+            Err(Error::UnsupportedDescriptor) => Self::new(
+                UrType::Bytes,
+                &Cbor::Bytes(descriptor.to_string().into_bytes()).to_vec(),
+            ),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn single_device_account(descriptor: &Descriptor<DescriptorPublicKey>) -> Option<Self> {
+        let mut fingerprint = None;
+        let single_device = descriptor.for_each_key(|pk| {
+            *fingerprint.get_or_insert(pk.master_fingerprint()) == pk.master_fingerprint()
+        });
+        if !descriptor.is_multipath() || !single_device {
+            return None;
+        }
+        let descriptors = descriptor.clone().into_single_descriptors().ok()?;
+        Self::account(fingerprint?, &descriptors).ok()
+    }
+
+    fn frame(part: &str) -> Result<qr_code::Data, Error> {
+        // Uppercase parts fit in the more compact alphanumeric mode of QR codes.
+        qr_code::Data::new(part.to_uppercase()).map_err(|e| Error::Ur(e.to_string()))
+    }
+
+    /// Move on to the next part. Once all fragments were shown, the encoder goes on with
+    /// fountain parts mixing several fragments so a missed frame does not need a full cycle.
+    pub fn next_frame(&mut self) {
+        match self.encoder.next_part().map_err(Error::from) {
+            Ok(part) => match Self::frame(&part) {
+                Ok(frame) => self.frame = frame,
+                Err(e) => tracing::error!("Failed to render UR part: {}", e),
+            },
+            Err(e) => tracing::error!("Failed to encode next UR part: {}", e),
+        }
+    }
+
+    pub fn data(&self) -> &qr_code::Data {
+        &self.frame
+    }
+
+    pub fn fragment_count(&self) -> usize {
+        self.encoder.fragment_count()
+    }
+
+    /// Index of the part currently displayed, starting at 1.
+    pub fn current_index(&self) -> usize {
+        self.encoder.current_index()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PSBT: &str = "cHNidP8BAHECAAAAAUSHuliRtuCX1S6JxRuDRqDCKkWfKmWL5sV9ukZ/wzvfAAAAAAD9////AogTAAAAAAAAFgAUIxe7UY6LJ6y5mFBoWTOoVispDmdwFwAAAAAAABYAFKqO83TK+t/KdpAt21z2HGC7/Z2FAAAAAAABASsQJwAAAAAAACIAIIIySQjGCTeyx/rKUQx8qobjhJeNCiVCliBJPdyRX6XKAQVBIQI2cqWpc9UAW2gZt2WkKjvi8KoMCui00pRlL6wG32uKDKxzZHapFNYASzIYkEdH9bJz6nnqUG3uBB8kiK1asmgiBgI2cqWpc9UAW2gZt2WkKjvi8KoMCui00pRlL6wG32uKDAz1rML9AAAAAG8AAAAiBgMLcbOxsfLe6+3r1UcjQo77HY0As8OKE4l37yj0/qhIyQyKZPKpAAAAAG8AAAAAAAA=";
+
+    #[test]
+    fn cbor_roundtrip() {
+        let cbor = Cbor::Tag(
+            TAG_OUTPUT,
+            Box::new(Cbor::Map(vec![
+                (Cbor::Uint(1), Cbor::Bool(true)),
+                (Cbor::Uint(2), Cbor::Bytes(vec![0; 300])),
+                (Cbor::Uint(70_000), Cbor::Text("coincube".to_string())),
+                (
+                    Cbor::Uint(3),
+                    Cbor::Array(vec![Cbor::Uint(u32::MAX as u64 + 1)]),
+                ),
+            ])),
+        );
+        assert_eq!(Cbor::decode(&cbor.to_vec()).unwrap(), cbor);
+        // Byte string of length 3 with only 2 bytes given.
+        assert!(Cbor::decode(&[0x43, 0x01, 0x02]).is_err());
+
+        // Arrays nested beyond the limit are rejected instead of exhausting the stack.
+        let mut nested = vec![0x81; MAX_CBOR_DEPTH];
+        nested.push(0x80);
+        assert!(Cbor::decode(&nested).is_ok());
+        let nested = [vec![0x81; 100_000], vec![0x80]].concat();
+        assert_eq!(
+            Cbor::decode(&nested).unwrap_err(),
+            Error::Cbor("nesting too deep".to_string())
+        );
+    }
+
+    #[test]
+    fn psbt_roundtrip() {
+        let psbt = Psbt::from_str(PSBT).unwrap();
+        let mut qr = AnimatedQr::psbt(&psbt).unwrap();
+        assert!(qr.fragment_count() > 1);
+
+        let mut encoder =
+            ur::Encoder::new(&psbt_to_cbor(&psbt), MAX_FRAGMENT_LEN, "crypto-psbt").unwrap();
+        // Skip the first fragment, the fountain parts coming after the pure fragments make up
+        // for it.
+        let parts: Vec<String> = (0..qr.fragment_count() * 3)
+            .map(|_| encoder.next_part().unwrap())
+            .skip(1)
+            .collect();
+        assert_eq!(
+            decode(parts.iter().map(|p| p.as_str())).unwrap(),
+            Decoded::Psbt(Box::new(psbt.clone()))
+        );
+        // Parts read from a QR code are upper case.
+        assert_eq!(
+            decode(
+                parts
+                    .iter()
+                    .map(|p| p.to_uppercase())
+                    .collect::<Vec<_>>()
+                    .iter()
+                    .map(|p| p.as_str())
+            )
+            .unwrap(),
+            Decoded::Psbt(Box::new(psbt.clone()))
+        );
+        assert_eq!(
+            decode(parts.iter().take(1).map(|p| p.as_str())).unwrap_err(),
+            Error::Incomplete { received: 1 }
+        );
+
+        qr.next_frame();
+        assert_eq!(qr.current_index(), 2);
+    }
+
+    #[test]
+    fn crypto_output() {
+        let descriptor = Descriptor::<DescriptorPublicKey>::from_str("wsh(sortedmulti(2,[8a550171/48'/1'/0'/2']tpubDFnCs5ZaCqopaNhgLCiXAwbkaBdcnuMt1VFoPsRpUrpidyvzG67MYjkfxw6HnTBhHqeU3xw2ioNBVcWY3jXwGhSyppEQvtn38GsL7RH1eef/0/*,02fc9e5af0ac8d9b3cecfe2a888e2117ba3d089d8585886c9c826b6b22a98d12ea))").unwrap();
+        let payload = output_to_cbor(&descriptor).unwrap();
+        let Decoded::Descriptors(decoded) = decode_payload(UrType::CryptoOutput, &payload).unwrap()
+        else {
+            panic!("expected descriptors");
+        };
+        assert_eq!(
+            Descriptor::<DescriptorPublicKey>::from_str(&decoded[0]).unwrap(),
+            descriptor
+        );
+
+        // A crypto-account is a list of outputs along with the master fingerprint.
+        let account = Cbor::Map(vec![
+            (Cbor::Uint(1), Cbor::Uint(0x8a550171)),
+            (
+                Cbor::Uint(2),
+                Cbor::Array(vec![Cbor::decode(&payload).unwrap()]),
+            ),
+        ]);
+        assert_eq!(
+            decode_payload(UrType::CryptoAccount, &account.to_vec()).unwrap(),
+            Decoded::Descriptors(decoded)
+        );
+
+        // Coincube descriptors are arbitrary miniscript.
+        let descriptor = Descriptor::<DescriptorPublicKey>::from_str("wsh(or_d(pk([8a550171/48'/1'/0'/2']tpubDFnCs5ZaCqopaNhgLCiXAwbkaBdcnuMt1VFoPsRpUrpidyvzG67MYjkfxw6HnTBhHqeU3xw2ioNBVcWY3jXwGhSyppEQvtn38GsL7RH1eef/0/*),and_v(v:pkh([8a550171/48'/1'/0'/2']tpubDFnCs5ZaCqopaNhgLCiXAwbkaBdcnuMt1VFoPsRpUrpidyvzG67MYjkfxw6HnTBhHqeU3xw2ioNBVcWY3jXwGhSyppEQvtn38GsL7RH1eef/2/*),older(52596))))").unwrap();
+        assert_eq!(
+            output_to_cbor(&descriptor).unwrap_err(),
+            Error::UnsupportedDescriptor
+        );
+        assert!(AnimatedQr::descriptor(&descriptor).is_ok());
+    }
+
+    #[test]
+    fn crypto_account() {
+        let descriptor = Descriptor::<DescriptorPublicKey>::from_str("wpkh([8a550171/48'/1'/0'/2']tpubDFnCs5ZaCqopaNhgLCiXAwbkaBdcnuMt1VFoPsRpUrpidyvzG67MYjkfxw6HnTBhHqeU3xw2ioNBVcWY3jXwGhSyppEQvtn38GsL7RH1eef/<0;1>/*)").unwrap();
+        let descriptors = descriptor.clone().into_single_descriptors().unwrap();
+        let payload =
+            account_to_cbor(Fingerprint::from_str("8a550171").unwrap(), &descriptors).unwrap();
+        assert_eq!(
+            Cbor::decode(&payload).unwrap().get(1),
+            Some(&Cbor::Uint(0x8a550171))
+        );
+        let Decoded::Descriptors(decoded) =
+            decode_payload(UrType::CryptoAccount, &payload).unwrap()
+        else {
+            panic!("expected descriptors");
+        };
+        assert_eq!(
+            decoded
+                .iter()
+                .map(|d| Descriptor::<DescriptorPublicKey>::from_str(d).unwrap())
+                .collect::<Vec<_>>(),
+            descriptors
+        );
+
+        // The multipath descriptor of a single device is exported as an account.
+        let mut qr = AnimatedQr::descriptor(&descriptor).unwrap();
+        assert_eq!(
+            qr.encoder.next_part().unwrap().split('/').next(),
+            Some("ur:crypto-account")
+        );
+    }
+}
@@ -0,0 +1,325 @@
+//! File based flows for air-gapped Coldcard signing devices.
+//!
+//! An air-gapped Coldcard exchanges data with the wallet through its SD card:
+//!    - the wallet policy is registered from a miniscript registration file,
+//!    - PSBTs are written as `<name>.psbt` and the device writes back either a
+//!      `<name>-signed.psbt` or, once the transaction is complete, a `<name>-final.txn`
+//!      containing the hex encoded finalized transaction.
+use std::fmt::Display;
+
+use coincube_core::{
+    descriptors::CoincubeDescriptor,
+    miniscript::bitcoin::{
+        consensus, ecdsa,
+        hashes::Hash,
+        key::TapTweak,
+        psbt::Psbt,
+        secp256k1::{self, Secp256k1, VerifyOnly},
+        sighash::{Prevouts, SighashCache},
+        taproot, PublicKey, Transaction, TxOut, Txid,
+    },
+    psbtv2,
+};
+
+/// Maximum length of a wallet name on the Coldcard.
+pub const MAX_NAME_LEN: usize = 20;
+
+const PSBT_MAGIC: &[u8] = b"psbt\xff";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    UnknownFormat,
+    TxidNotMatch,
+    MissingPrevout(usize),
+    Sighash(String),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::UnknownFormat => write!(f, "File is neither a PSBT nor a transaction"),
+            Error::TxidNotMatch => write!(f, "The signed transaction doesn't match this PSBT"),
+            Error::MissingPrevout(i) => write!(f, "PSBT input #{i} is missing its witness UTXO"),
+            Error::Sighash(e) => write!(f, "Failed to compute signature hash: {e}"),
+        }
+    }
+}
+
+/// The wallet name registered on the Coldcard, derived from the descriptor checksum so the
+/// same wallet always gets the same name.
+pub fn wallet_name(descriptor: &CoincubeDescriptor) -> String {
+    let descriptor = descriptor.to_string();
+    let checksum = descriptor
+        .split_once('#')
+        .map(|(_, checksum)| checksum)
+        .unwrap_or_default();
+    let mut name = format!("coincube-{checksum}");
+    name.truncate(MAX_NAME_LEN);
+    name
+}
+
+/// Content of the file to import on the Coldcard to register the wallet policy.
+///
+/// Coincube descriptors always carry recovery paths, so they are registered as miniscript
+/// wallets rather than with the plain `Policy: M of N` multisig format.
+pub fn registration_file(descriptor: &CoincubeDescriptor) -> String {
+    serde_json::json!({
+        "name": wallet_name(descriptor),
+        "desc": descriptor.to_string(),
+    })
+    .to_string()
+}
+
+/// Base name of the files exchanged with the Coldcard for the transaction `txid`.
+pub fn psbt_basename(txid: &Txid) -> String {
+    format!("coincube-{}", &txid.to_string()[..8])
+}
+
+/// Name of the PSBT file to write on the SD card.
+pub fn psbt_filename(txid: &Txid) -> String {
+    format!("{}.psbt", psbt_basename(txid))
+}
+
+/// Name of the PSBT file written back by the Coldcard after signing.
+pub fn signed_psbt_filename(txid: &Txid) -> String {
+    format!("{}-signed.psbt", psbt_basename(txid))
+}
+
+/// A file written by the Coldcard after signing.
+#[derive(Debug, Clone)]
+pub enum SignedFile {
+    Psbt(Box<Psbt>),
+    Transaction(Transaction),
+}
+
+impl SignedFile {
+    /// Parse the content of a file written by the Coldcard. PSBTs may be binary, base64 or hex
+    /// encoded and transactions binary or hex encoded.
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.starts_with(PSBT_MAGIC) {
+            return psbtv2::deserialize(bytes)
+                .map(|psbt| Self::Psbt(Box::new(psbt)))
+                .map_err(|_| Error::UnknownFormat);
+        }
+        if let Ok(text) = std::str::from_utf8(bytes) {
+            let text = text.trim();
+            if let Ok(psbt) = psbtv2::from_base64(text) {
+                return Ok(Self::Psbt(Box::new(psbt)));
+            }
+            if let Ok(raw) = hex::decode(text) {
+                if raw.starts_with(PSBT_MAGIC) {
+                    return psbtv2::deserialize(&raw)
+                        .map(|psbt| Self::Psbt(Box::new(psbt)))
+                        .map_err(|_| Error::UnknownFormat);
+                }
+                return consensus::deserialize::<Transaction>(&raw)
+                    .map(Self::Transaction)
+                    .map_err(|_| Error::UnknownFormat);
+            }
+        }
+        consensus::deserialize::<Transaction>(bytes)
+            .map(Self::Transaction)
+            .map_err(|_| Error::UnknownFormat)
+    }
+
+    /// Returns a PSBT carrying the signatures of this file, to be merged into `psbt`.
+    pub fn into_psbt(self, psbt: &Psbt, secp: &Secp256k1<VerifyOnly>) -> Result<Psbt, Error> {
+        match self {
+            Self::Psbt(signed) => Ok(*signed),
+            Self::Transaction(tx) => signatures_from_tx(psbt, &tx, secp),
+        }
+    }
+}
+
+/// Recover the signatures contained in the witnesses of the finalized transaction `tx` and
+/// set them in a copy of `psbt`, as if the signing device had returned a signed PSBT.
+///
+/// Every witness element parsing as a signature is checked against the keys of the PSBT
+/// input it belongs to, unmatched elements are ignored.
+pub fn signatures_from_tx(
+    psbt: &Psbt,
+    tx: &Transaction,
+    secp: &Secp256k1<VerifyOnly>,
+) -> Result<Psbt, Error> {
+    if tx.compute_txid() != psbt.unsigned_tx.compute_txid() {
+        return Err(Error::TxidNotMatch);
+    }
+
+    let prevouts = psbt
+        .inputs
+        .iter()
+        .enumerate()
+        .map(|(i, psbtin)| psbtin.witness_utxo.clone().ok_or(Error::MissingPrevout(i)))
+        .collect::<Result<Vec<TxOut>, Error>>()?;
+    let mut cache = SighashCache::new(&psbt.unsigned_tx);
+    let mut signed = psbt.clone();
+
+    for (i, (psbtin, txin)) in signed.inputs.iter_mut().zip(tx.input.iter()).enumerate() {
+        if let Some(witness_script) = &psbtin.witness_script {
+            for sig in txin
+                .witness
+                .iter()
+                .filter_map(|elem| ecdsa::Signature::from_slice(elem).ok())
+            {
+                let sighash = cache
+                    .p2wsh_signature_hash(i, witness_script, prevouts[i].value, sig.sighash_type)
+                    .map_err(|e| Error::Sighash(e.to_string()))?;
+                let msg = secp256k1::Message::from_digest(sighash.to_byte_array());
+                if let Some(key) = psbtin
+                    .bip32_derivation
+                    .keys()
+                    .find(|key| secp.verify_ecdsa(&msg, &sig.signature, key).is_ok())
+                {
+                    psbtin.partial_sigs.insert(PublicKey::new(*key), sig);
+                }
+            }
+        } else if let Some(internal_key) = psbtin.tap_internal_key {
+            for sig in txin
+                .witness
+                .iter()
+                .filter_map(|elem| taproot::Signature::from_slice(elem).ok())
+            {
+                let key_spend_sighash = cache
+                    .taproot_key_spend_signature_hash(
+                        i,
+                        &Prevouts::All(&prevouts),
+                        sig.sighash_type,
+                    )
+                    .map_err(|e| Error::Sighash(e.to_string()))?;
+                let (output_key, _) = internal_key.tap_tweak(secp, psbtin.tap_merkle_root);
+                let msg = secp256k1::Message::from_digest(key_spend_sighash.to_byte_array());
+                if secp
+                    .verify_schnorr(&sig.signature, &msg, &output_key.to_x_only_public_key())
+                    .is_ok()
+                {
+                    psbtin.tap_key_sig = Some(sig);
+                    continue;
+                }
+                for (key, (leaf_hashes, _)) in &psbtin.tap_key_origins {
+                    for leaf_hash in leaf_hashes {
+                        let sighash = cache
+                            .taproot_script_spend_signature_hash(
+                                i,
+                                &Prevouts::All(&prevouts),
+                                *leaf_hash,
+                                sig.sighash_type,
+                            )
+                            .map_err(|e| Error::Sighash(e.to_string()))?;
+                        let msg = secp256k1::Message::from_digest(sighash.to_byte_array());
+                        if secp.verify_schnorr(&sig.signature, &msg, key).is_ok() {
+                            psbtin.tap_script_sigs.insert((*key, *leaf_hash), sig);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(signed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    use coincube_core::miniscript::bitcoin::{
+        absolute, bip32, script::Builder, transaction, Amount, EcdsaSighashType, OutPoint,
+        ScriptBuf, Sequence, TxIn, Witness,
+    };
+
+    #[test]
+    fn registration() {
+        let descriptor = CoincubeDescriptor::from_str("wsh(or_d(pk([8a550171/48'/1'/0'/2']tpubDFnCs5ZaCqopaNhgLCiXAwbkaBdcnuMt1VFoPsRpUrpidyvzG67MYjkfxw6HnTBhHqeU3xw2ioNBVcWY3jXwGhSyppEQvtn38GsL7RH1eef/<0;1>/*),and_v(v:pkh([8a550171/48'/1'/0'/2']tpubDFnCs5ZaCqopaNhgLCiXAwbkaBdcnuMt1VFoPsRpUrpidyvzG67MYjkfxw6HnTBhHqeU3xw2ioNBVcWY3jXwGhSyppEQvtn38GsL7RH1eef/<2;3>/*),older(52596))))#jz5sm0xn").unwrap();
+        assert_eq!(wallet_name(&descriptor), "coincube-jz5sm0xn");
+        let file: serde_json::Value =
+            serde_json::from_str(&registration_file(&descriptor)).unwrap();
+        assert_eq!(file["name"], "coincube-jz5sm0xn");
+        assert_eq!(file["desc"], descriptor.to_string());
+    }
+
+    #[test]
+    fn signed_tx_signatures() {
+        let secp = Secp256k1::new();
+        let sk = secp256k1::SecretKey::from_slice(&[42; 32]).unwrap();
+        let pk = secp256k1::PublicKey::from_secret_key(&secp, &sk);
+        let witness_script = Builder::new()
+            .push_key(&PublicKey::new(pk))
+            .push_opcode(coincube_core::miniscript::bitcoin::opcodes::all::OP_CHECKSIG)
+            .into_script();
+        let prevout = TxOut {
+            value: Amount::from_sat(100_000),
+            script_pubkey: ScriptBuf::new_p2wsh(&witness_script.wscript_hash()),
+        };
+        let unsigned_tx = Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::from_str(
+                    "4b9a8d1b9a5d5b5a9b0f5c4a5c4a4d3e2f1a0b9c8d7e6f5a4b3c2d1e0f9a8b7c:0",
+                )
+                .unwrap(),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                ..Default::default()
+            }],
+            output: vec![TxOut {
+                value: Amount::from_sat(90_000),
+                script_pubkey: prevout.script_pubkey.clone(),
+            }],
+        };
+        let mut psbt = Psbt::from_unsigned_tx(unsigned_tx.clone()).unwrap();
+        psbt.inputs[0].witness_utxo = Some(prevout.clone());
+        psbt.inputs[0].witness_script = Some(witness_script.clone());
+        psbt.inputs[0].bip32_derivation.insert(
+            pk,
+            (
+                bip32::Fingerprint::default(),
+                bip32::DerivationPath::from_str("m/0/0").unwrap(),
+            ),
+        );
+
+        let sighash = SighashCache::new(&unsigned_tx)
+            .p2wsh_signature_hash(0, &witness_script, prevout.value, EcdsaSighashType::All)
+            .unwrap();
+        let sig = ecdsa::Signature::sighash_all(secp.sign_ecdsa(
+            &secp256k1::Message::from_digest(sighash.to_byte_array()),
+            &sk,
+        ));
+        let mut tx = unsigned_tx.clone();
+        tx.input[0].witness = Witness::from_slice(&[sig.to_vec(), witness_script.to_bytes()]);
+
+        let hex_tx = consensus::encode::serialize_hex(&tx);
+        let signed = SignedFile::parse(hex_tx.as_bytes())
+            .unwrap()
+            .into_psbt(&psbt, &Secp256k1::verification_only())
+            .unwrap();
+        assert_eq!(
+            signed.inputs[0].partial_sigs.get(&PublicKey::new(pk)),
+            Some(&sig)
+        );
+
+        // A transaction spending other coins is refused.
+        let mut other = tx.clone();
+        other.output[0].value = Amount::from_sat(80_000);
+        assert_eq!(
+            signatures_from_tx(&psbt, &other, &Secp256k1::verification_only()).unwrap_err(),
+            Error::TxidNotMatch
+        );
+
+        // PSBTs are read whatever their encoding.
+        for bytes in [
+            psbt.serialize(),
+            psbt.to_string().into_bytes(),
+            hex::encode(psbt.serialize()).into_bytes(),
+        ] {
+            assert!(matches!(
+                SignedFile::parse(&bytes).unwrap(),
+                SignedFile::Psbt(p) if *p == psbt
+            ));
+        }
+        assert_eq!(
+            SignedFile::parse(b"not a psbt").unwrap_err(),
+            Error::UnknownFormat
+        );
+    }
+}
@@ -4,7 +4,10 @@ use std::iter::FromIterator;
 
 use async_trait::async_trait;
 use coincubed::bip329::Labels;
-use coincubed::commands::{GetLabelsBip329Result, UpdateDerivIndexesResult};
+use coincubed::commands::{
+    GetBalanceHistoryResult, GetConsolidationAdviceResult, GetLabelsBip329Result,
+    ImportLabelsResult, LabelConflict, UpdateDerivIndexesResult,
+};
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
@@ -14,10 +17,12 @@ pub mod error;
 pub mod jsonrpc;
 
 use coincube_core::miniscript::bitcoin::{
-    address, bip32::ChildNumber, psbt::Psbt, Address, Network, OutPoint, Txid,
+    address, bip32::ChildNumber, hex::DisplayHex, psbt::Psbt, Address, Network, OutPoint, Txid,
 };
 use coincubed::{
-    commands::{CoinStatus, CreateRecoveryResult, LabelItem},
+    commands::{
+        CoinStatus, CreateMessagePsbtResult, CreateRecoveryResult, LabelItem, SearchFilter,
+    },
     config::Config,
 };
 
@@ -57,6 +62,53 @@ impl<C: Client> Coincubed<C> {
     }
 }
 
+/// The daemon takes feerates in sats/vb. Whole values are passed as integers for compatibility
+/// with daemons which don't support fractional feerates.
+fn feerate_param(feerate_kvb: u64) -> serde_json::Value {
+    if feerate_kvb.is_multiple_of(1_000) {
+        json!(feerate_kvb / 1_000)
+    } else {
+        json!(feerate_kvb as f64 / 1_000.0)
+    }
+}
+
+// The search criteria, passed by name.
+fn search_filter_param(filter: &SearchFilter) -> serde_json::Map<String, serde_json::Value> {
+    let mut params = serde_json::Map::new();
+    if let Some(label) = &filter.label {
+        params.insert("label".to_string(), json!(label));
+    }
+    if let Some(min) = filter.min_amount {
+        params.insert("min_amount".to_string(), json!(min.to_sat()));
+    }
+    if let Some(max) = filter.max_amount {
+        params.insert("max_amount".to_string(), json!(max.to_sat()));
+    }
+    if let Some(start) = filter.start {
+        params.insert("start".to_string(), json!(start));
+    }
+    if let Some(end) = filter.end {
+        params.insert("end".to_string(), json!(end));
+    }
+    if !filter.statuses.is_empty() {
+        params.insert(
+            "statuses".to_string(),
+            json!(filter
+                .statuses
+                .iter()
+                .map(|s| s.to_arg())
+                .collect::<Vec<_>>()),
+        );
+    }
+    if let Some(address) = &filter.address {
+        params.insert("address".to_string(), json!(address.to_string()));
+    }
+    if let Some(prefix) = &filter.txid_prefix {
+        params.insert("txid_prefix".to_string(), json!(prefix));
+    }
+    params
+}
+
 #[async_trait]
 impl<C: Client + Send + Sync + Debug> Daemon for Coincubed<C> {
     fn backend(&self) -> DaemonBackend {
@@ -89,6 +141,28 @@ impl<C: Client + Send + Sync + Debug> Daemon for Coincubed<C> {
         self.call("getnewaddress", Option::<Request>::None)
     }
 
+    async fn get_balance_history(&self) -> Result<Vec<BalanceHistoryEntry>, DaemonError> {
+        let res: GetBalanceHistoryResult =
+            self.call("getbalancehistory", Option::<Request>::None)?;
+        Ok(res.history)
+    }
+
+    async fn get_consolidation_advice(
+        &self,
+        feerate_kvb: u64,
+        future_feerate_kvb: u64,
+    ) -> Result<Option<ConsolidationAdvice>, DaemonError> {
+        let mut params = serde_json::Map::new();
+        params.insert("feerate".to_string(), feerate_param(feerate_kvb));
+        params.insert(
+            "future_feerate".to_string(),
+            feerate_param(future_feerate_kvb),
+        );
+        let res: GetConsolidationAdviceResult =
+            self.call("getconsolidationadvice", Some(params))?;
+        Ok(res.advice)
+    }
+
     async fn list_revealed_addresses(
         &self,
         is_change: bool,
@@ -129,6 +203,10 @@ impl<C: Client + Send + Sync + Debug> Daemon for Coincubed<C> {
         )
     }
 
+    async fn search_coins(&self, filter: &SearchFilter) -> Result<ListCoinsResult, DaemonError> {
+        self.call("searchcoins", Some(search_filter_param(filter)))
+    }
+
     async fn list_spend_txs(&self) -> Result<ListSpendResult, DaemonError> {
         self.call("listspendtxs", Option::<Request>::None)
     }
@@ -137,16 +215,28 @@ impl<C: Client + Send + Sync + Debug> Daemon for Coincubed<C> {
         &self,
         coins_outpoints: &[OutPoint],
         destinations: &HashMap<Address<address::NetworkUnchecked>, u64>,
-        feerate_vb: u64,
+        subtract_fee_from: &[Address<address::NetworkUnchecked>],
+        op_return: Option<&[u8]>,
+        feerate_kvb: u64,
         change_address: Option<Address<address::NetworkUnchecked>>,
     ) -> Result<CreateSpendResult, DaemonError> {
         let mut input = vec![
             json!(destinations),
             json!(coins_outpoints),
-            json!(feerate_vb),
+            feerate_param(feerate_kvb),
         ];
-        if let Some(change_address) = change_address {
-            input.push(json!(change_address));
+        // Optional parameters are positional, fill in the ones before the last one given.
+        let optionals = [
+            change_address.map(|addr| json!(addr)),
+            (!subtract_fee_from.is_empty()).then(|| json!(subtract_fee_from)),
+            op_return.map(|data| json!({ "hex": data.to_lower_hex_string() })),
+        ];
+        if let Some(last) = optionals.iter().rposition(Option::is_some) {
+            input.extend(
+                optionals[..=last]
+                    .iter()
+                    .map(|param| param.clone().unwrap_or(json!(null))),
+            );
         }
         self.call("createspend", Some(input))
     }
@@ -155,15 +245,26 @@ impl<C: Client + Send + Sync + Debug> Daemon for Coincubed<C> {
         &self,
         txid: &Txid,
         is_cancel: bool,
-        feerate_vb: Option<u64>,
+        feerate_kvb: Option<u64>,
     ) -> Result<CreateSpendResult, DaemonError> {
         let mut input = vec![json!(txid.to_string()), json!(is_cancel)];
-        if let Some(feerate_vb) = feerate_vb {
-            input.push(json!(feerate_vb));
+        if let Some(feerate_kvb) = feerate_kvb {
+            input.push(feerate_param(feerate_kvb));
         }
         self.call("rbfpsbt", Some(input))
     }
 
+    async fn cpfp(
+        &self,
+        outpoints: &[OutPoint],
+        feerate_kvb: u64,
+    ) -> Result<CreateSpendResult, DaemonError> {
+        self.call(
+            "cpfp",
+            Some(vec![json!(outpoints), feerate_param(feerate_kvb)]),
+        )
+    }
+
     async fn update_spend_tx(&self, psbt: &Psbt) -> Result<(), DaemonError> {
         let spend_tx = psbt.to_string();
         let _res: serde_json::value::Value = self.call("updatespend", Some(vec![spend_tx]))?;
@@ -182,6 +283,37 @@ impl<C: Client + Send + Sync + Debug> Daemon for Coincubed<C> {
         Ok(())
     }
 
+    async fn approve_spend_tx(&self, txid: &Txid) -> Result<(), DaemonError> {
+        let _res: serde_json::value::Value =
+            self.call("approvespend", Some(vec![txid.to_string()]))?;
+        Ok(())
+    }
+
+    async fn schedule_broadcast_spend_tx(
+        &self,
+        txid: &Txid,
+        block_height: Option<i32>,
+        timestamp: Option<u32>,
+    ) -> Result<(), DaemonError> {
+        let _res: serde_json::value::Value = self.call(
+            "schedulebroadcast",
+            Some(vec![json!(txid), json!(block_height), json!(timestamp)]),
+        )?;
+        Ok(())
+    }
+
+    async fn list_scheduled_broadcasts(
+        &self,
+    ) -> Result<ListScheduledBroadcastsResult, DaemonError> {
+        self.call("listscheduledbroadcasts", Option::<Request>::None)
+    }
+
+    async fn cancel_scheduled_broadcast(&self, txid: &Txid) -> Result<(), DaemonError> {
+        let _res: serde_json::value::Value =
+            self.call("cancelscheduledbroadcast", Some(vec![txid.to_string()]))?;
+        Ok(())
+    }
+
     async fn start_rescan(&self, t: u32) -> Result<(), DaemonError> {
         let _res: serde_json::value::Value = self.call("startrescan", Some(vec![t]))?;
         Ok(())
@@ -203,17 +335,27 @@ impl<C: Client + Send + Sync + Debug> Daemon for Coincubed<C> {
         self.call("listtransactions", Some(vec![txids]))
     }
 
+    async fn search_txs(
+        &self,
+        filter: &SearchFilter,
+        limit: u64,
+    ) -> Result<ListTransactionsResult, DaemonError> {
+        let mut params = search_filter_param(filter);
+        params.insert("limit".to_string(), json!(limit));
+        self.call("searchtransactions", Some(params))
+    }
+
     async fn create_recovery(
         &self,
         address: Address<address::NetworkUnchecked>,
         coins_outpoints: &[OutPoint],
-        feerate_vb: u64,
+        feerate_kvb: u64,
         sequence: Option<u16>,
     ) -> Result<Psbt, DaemonError> {
         let mut params = serde_json::Map::new();
         params.insert("address".to_string(), json!(address));
         params.insert("outpoints".to_string(), json!(coins_outpoints));
-        params.insert("feerate".to_string(), json!(feerate_vb));
+        params.insert("feerate".to_string(), feerate_param(feerate_kvb));
         if let Some(sequence) = sequence {
             params.insert("timelock".to_string(), json!(sequence));
         }
@@ -221,6 +363,34 @@ impl<C: Client + Send + Sync + Debug> Daemon for Coincubed<C> {
         Ok(res.psbt)
     }
 
+    async fn create_heir_recovery(
+        &self,
+        address: Address<address::NetworkUnchecked>,
+        feerate_kvb: u64,
+        timelock: u16,
+    ) -> Result<Psbt, DaemonError> {
+        let mut params = serde_json::Map::new();
+        params.insert("address".to_string(), json!(address));
+        params.insert("feerate".to_string(), feerate_param(feerate_kvb));
+        params.insert("timelock".to_string(), json!(timelock));
+        let res: CreateRecoveryResult = self.call("createheirrecovery", Some(params))?;
+        Ok(res.psbt)
+    }
+
+    async fn create_message_psbt(
+        &self,
+        address: Address<address::NetworkUnchecked>,
+        message: &str,
+        coins_outpoints: &[OutPoint],
+    ) -> Result<Psbt, DaemonError> {
+        let mut params = serde_json::Map::new();
+        params.insert("address".to_string(), json!(address));
+        params.insert("message".to_string(), json!(message));
+        params.insert("outpoints".to_string(), json!(coins_outpoints));
+        let res: CreateMessagePsbtResult = self.call("createmessagepsbt", Some(params))?;
+        Ok(res.psbt)
+    }
+
     async fn get_labels(
         &self,
         items: &HashSet<LabelItem>,
@@ -250,6 +420,17 @@ impl<C: Client + Send + Sync + Debug> Daemon for Coincubed<C> {
             self.call("getlabelsbip329", Some(vec![json!(offset), json!(limit)]))?;
         Ok(res.labels)
     }
+
+    async fn import_labels(
+        &self,
+        labels: &[coincubed::bip329::Label],
+        conflict: LabelConflict,
+    ) -> Result<ImportLabelsResult, DaemonError> {
+        self.call(
+            "importlabels",
+            Some(vec![json!(labels), json!(conflict.to_arg())]),
+        )
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
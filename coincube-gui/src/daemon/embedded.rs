@@ -1,5 +1,5 @@
 use coincubed::bip329::Labels;
-use coincubed::commands::UpdateDerivIndexesResult;
+use coincubed::commands::{ImportLabelsResult, LabelConflict, UpdateDerivIndexesResult};
 use std::collections::{HashMap, HashSet};
 use tokio::sync::Mutex;
 
@@ -10,7 +10,7 @@ use coincube_core::miniscript::bitcoin::{
     address, bip32::ChildNumber, psbt::Psbt, Address, Network, OutPoint, Txid,
 };
 use coincubed::{
-    commands::{CoinStatus, LabelItem},
+    commands::{CoinStatus, LabelItem, SearchFilter},
     config::Config,
     DaemonControl, DaemonHandle,
 };
@@ -105,6 +105,25 @@ impl Daemon for EmbeddedDaemon {
         self.command(|daemon| Ok(daemon.get_new_address())).await
     }
 
+    async fn get_balance_history(&self) -> Result<Vec<BalanceHistoryEntry>, DaemonError> {
+        self.command(|daemon| Ok(daemon.get_balance_history().history))
+            .await
+    }
+
+    async fn get_consolidation_advice(
+        &self,
+        feerate_kvb: u64,
+        future_feerate_kvb: u64,
+    ) -> Result<Option<ConsolidationAdvice>, DaemonError> {
+        self.command(|daemon| {
+            daemon
+                .get_consolidation_advice(feerate_kvb, future_feerate_kvb)
+                .map(|res| res.advice)
+                .map_err(|e| DaemonError::Unexpected(e.to_string()))
+        })
+        .await
+    }
+
     async fn list_revealed_addresses(
         &self,
         is_change: bool,
@@ -142,6 +161,10 @@ impl Daemon for EmbeddedDaemon {
             .await
     }
 
+    async fn search_coins(&self, filter: &SearchFilter) -> Result<ListCoinsResult, DaemonError> {
+        self.command(|daemon| Ok(daemon.search_coins(filter))).await
+    }
+
     async fn list_spend_txs(&self) -> Result<ListSpendResult, DaemonError> {
         self.command(|daemon| {
             daemon
@@ -166,16 +189,34 @@ impl Daemon for EmbeddedDaemon {
             .await
     }
 
+    async fn search_txs(
+        &self,
+        filter: &SearchFilter,
+        limit: u64,
+    ) -> Result<ListTransactionsResult, DaemonError> {
+        self.command(|daemon| Ok(daemon.search_transactions(filter, limit)))
+            .await
+    }
+
     async fn create_spend_tx(
         &self,
         coins_outpoints: &[OutPoint],
         destinations: &HashMap<Address<address::NetworkUnchecked>, u64>,
-        feerate_vb: u64,
+        subtract_fee_from: &[Address<address::NetworkUnchecked>],
+        op_return: Option<&[u8]>,
+        feerate_kvb: u64,
         change_address: Option<Address<address::NetworkUnchecked>>,
     ) -> Result<CreateSpendResult, DaemonError> {
         self.command(|daemon| {
             daemon
-                .create_spend(destinations, coins_outpoints, feerate_vb, change_address)
+                .create_spend(
+                    destinations,
+                    subtract_fee_from,
+                    op_return,
+                    coins_outpoints,
+                    feerate_kvb,
+                    change_address,
+                )
                 .map_err(|e| DaemonError::Unexpected(e.to_string()))
         })
         .await
@@ -185,11 +226,24 @@ impl Daemon for EmbeddedDaemon {
         &self,
         txid: &Txid,
         is_cancel: bool,
-        feerate_vb: Option<u64>,
+        feerate_kvb: Option<u64>,
+    ) -> Result<CreateSpendResult, DaemonError> {
+        self.command(|daemon| {
+            daemon
+                .rbf_psbt(txid, is_cancel, feerate_kvb)
+                .map_err(|e| DaemonError::Unexpected(e.to_string()))
+        })
+        .await
+    }
+
+    async fn cpfp(
+        &self,
+        outpoints: &[OutPoint],
+        feerate_kvb: u64,
     ) -> Result<CreateSpendResult, DaemonError> {
         self.command(|daemon| {
             daemon
-                .rbf_psbt(txid, is_cancel, feerate_vb)
+                .cpfp(outpoints, feerate_kvb)
                 .map_err(|e| DaemonError::Unexpected(e.to_string()))
         })
         .await
@@ -221,6 +275,45 @@ impl Daemon for EmbeddedDaemon {
         .await
     }
 
+    async fn approve_spend_tx(&self, txid: &Txid) -> Result<(), DaemonError> {
+        self.command(|daemon| {
+            daemon
+                .approve_spend(txid)
+                .map_err(|e| DaemonError::Unexpected(e.to_string()))
+        })
+        .await
+    }
+
+    async fn schedule_broadcast_spend_tx(
+        &self,
+        txid: &Txid,
+        block_height: Option<i32>,
+        timestamp: Option<u32>,
+    ) -> Result<(), DaemonError> {
+        self.command(|daemon| {
+            daemon
+                .schedule_broadcast(txid, block_height, timestamp)
+                .map_err(|e| DaemonError::Unexpected(e.to_string()))
+        })
+        .await
+    }
+
+    async fn list_scheduled_broadcasts(
+        &self,
+    ) -> Result<ListScheduledBroadcastsResult, DaemonError> {
+        self.command(|daemon| Ok(daemon.list_scheduled_broadcasts()))
+            .await
+    }
+
+    async fn cancel_scheduled_broadcast(&self, txid: &Txid) -> Result<(), DaemonError> {
+        self.command(|daemon| {
+            daemon
+                .cancel_scheduled_broadcast(txid)
+                .map_err(|e| DaemonError::Unexpected(e.to_string()))
+        })
+        .await
+    }
+
     async fn start_rescan(&self, t: u32) -> Result<(), DaemonError> {
         self.command(|daemon| {
             daemon
@@ -234,12 +327,42 @@ impl Daemon for EmbeddedDaemon {
         &self,
         address: Address<address::NetworkUnchecked>,
         coins_outpoints: &[OutPoint],
-        feerate_vb: u64,
+        feerate_kvb: u64,
         sequence: Option<u16>,
     ) -> Result<Psbt, DaemonError> {
         self.command(|daemon| {
             daemon
-                .create_recovery(address, coins_outpoints, feerate_vb, sequence)
+                .create_recovery(address, coins_outpoints, feerate_kvb, sequence)
+                .map(|res| res.psbt)
+                .map_err(|e| DaemonError::Unexpected(e.to_string()))
+        })
+        .await
+    }
+
+    async fn create_heir_recovery(
+        &self,
+        address: Address<address::NetworkUnchecked>,
+        feerate_kvb: u64,
+        timelock: u16,
+    ) -> Result<Psbt, DaemonError> {
+        self.command(|daemon| {
+            daemon
+                .create_heir_recovery(address, feerate_kvb, timelock)
+                .map(|res| res.psbt)
+                .map_err(|e| DaemonError::Unexpected(e.to_string()))
+        })
+        .await
+    }
+
+    async fn create_message_psbt(
+        &self,
+        address: Address<address::NetworkUnchecked>,
+        message: &str,
+        coins_outpoints: &[OutPoint],
+    ) -> Result<Psbt, DaemonError> {
+        self.command(|daemon| {
+            daemon
+                .create_message_psbt(address, message, coins_outpoints)
                 .map(|res| res.psbt)
                 .map_err(|e| DaemonError::Unexpected(e.to_string()))
         })
@@ -269,4 +392,13 @@ impl Daemon for EmbeddedDaemon {
         self.command(|daemon| Ok(daemon.get_labels_bip329(offset, limit).labels))
             .await
     }
+
+    async fn import_labels(
+        &self,
+        labels: &[coincubed::bip329::Label],
+        conflict: LabelConflict,
+    ) -> Result<ImportLabelsResult, DaemonError> {
+        self.command(|daemon| Ok(daemon.import_labels(labels, conflict)))
+            .await
+    }
 }
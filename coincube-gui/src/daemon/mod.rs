@@ -15,9 +15,9 @@ use coincube_core::miniscript::bitcoin::{
     secp256k1, Address, Network, OutPoint, Txid,
 };
 use coincubed::bip329::Labels;
-use coincubed::commands::UpdateDerivIndexesResult;
+use coincubed::commands::{ImportLabelsResult, LabelConflict, UpdateDerivIndexesResult};
 use coincubed::{
-    commands::{CoinStatus, LabelItem, TransactionInfo},
+    commands::{CoinStatus, LabelItem, SearchFilter, TransactionInfo},
     config::Config,
     StartupError,
 };
@@ -108,6 +108,16 @@ pub trait Daemon: Debug {
     ) -> Result<(), DaemonError>;
     async fn stop(&self) -> Result<(), DaemonError>;
     async fn get_info(&self) -> Result<model::GetInfoResult, DaemonError>;
+    async fn get_balance_history(&self) -> Result<Vec<model::BalanceHistoryEntry>, DaemonError> {
+        Err(DaemonError::NotImplemented)
+    }
+    async fn get_consolidation_advice(
+        &self,
+        _feerate_kvb: u64,
+        _future_feerate_kvb: u64,
+    ) -> Result<Option<model::ConsolidationAdvice>, DaemonError> {
+        Err(DaemonError::NotImplemented)
+    }
     async fn get_new_address(&self) -> Result<model::GetAddressResult, DaemonError>;
     async fn list_revealed_addresses(
         &self,
@@ -126,23 +136,57 @@ pub trait Daemon: Debug {
         statuses: &[CoinStatus],
         outpoints: &[OutPoint],
     ) -> Result<model::ListCoinsResult, DaemonError>;
+    async fn search_coins(
+        &self,
+        _filter: &SearchFilter,
+    ) -> Result<model::ListCoinsResult, DaemonError> {
+        Err(DaemonError::NotImplemented)
+    }
     async fn list_spend_txs(&self) -> Result<model::ListSpendResult, DaemonError>;
     async fn create_spend_tx(
         &self,
         coins_outpoints: &[OutPoint],
         destinations: &HashMap<Address<address::NetworkUnchecked>, u64>,
-        feerate_vb: u64,
+        subtract_fee_from: &[Address<address::NetworkUnchecked>],
+        op_return: Option<&[u8]>,
+        feerate_kvb: u64,
         change_address: Option<Address<address::NetworkUnchecked>>,
     ) -> Result<model::CreateSpendResult, DaemonError>;
     async fn rbf_psbt(
         &self,
         txid: &Txid,
         is_cancel: bool,
-        feerate_vb: Option<u64>,
+        feerate_kvb: Option<u64>,
     ) -> Result<model::CreateSpendResult, DaemonError>;
+    async fn cpfp(
+        &self,
+        _outpoints: &[OutPoint],
+        _feerate_kvb: u64,
+    ) -> Result<model::CreateSpendResult, DaemonError> {
+        Err(DaemonError::NotImplemented)
+    }
     async fn update_spend_tx(&self, psbt: &Psbt) -> Result<(), DaemonError>;
     async fn delete_spend_tx(&self, txid: &Txid) -> Result<(), DaemonError>;
     async fn broadcast_spend_tx(&self, txid: &Txid) -> Result<(), DaemonError>;
+    async fn approve_spend_tx(&self, _txid: &Txid) -> Result<(), DaemonError> {
+        Err(DaemonError::NotImplemented)
+    }
+    async fn schedule_broadcast_spend_tx(
+        &self,
+        _txid: &Txid,
+        _block_height: Option<i32>,
+        _timestamp: Option<u32>,
+    ) -> Result<(), DaemonError> {
+        Err(DaemonError::NotImplemented)
+    }
+    async fn list_scheduled_broadcasts(
+        &self,
+    ) -> Result<model::ListScheduledBroadcastsResult, DaemonError> {
+        Err(DaemonError::NotImplemented)
+    }
+    async fn cancel_scheduled_broadcast(&self, _txid: &Txid) -> Result<(), DaemonError> {
+        Err(DaemonError::NotImplemented)
+    }
     async fn start_rescan(&self, t: u32) -> Result<(), DaemonError>;
     async fn list_confirmed_txs(
         &self,
@@ -154,10 +198,33 @@ pub trait Daemon: Debug {
         &self,
         address: Address<address::NetworkUnchecked>,
         coins_outpoints: &[OutPoint],
-        feerate_vb: u64,
+        feerate_kvb: u64,
         sequence: Option<u16>,
     ) -> Result<Psbt, DaemonError>;
+    async fn create_heir_recovery(
+        &self,
+        _address: Address<address::NetworkUnchecked>,
+        _feerate_kvb: u64,
+        _timelock: u16,
+    ) -> Result<Psbt, DaemonError> {
+        Err(DaemonError::NotImplemented)
+    }
     async fn list_txs(&self, txid: &[Txid]) -> Result<model::ListTransactionsResult, DaemonError>;
+    async fn search_txs(
+        &self,
+        _filter: &SearchFilter,
+        _limit: u64,
+    ) -> Result<model::ListTransactionsResult, DaemonError> {
+        Err(DaemonError::NotImplemented)
+    }
+    async fn create_message_psbt(
+        &self,
+        _address: Address<address::NetworkUnchecked>,
+        _message: &str,
+        _coins_outpoints: &[OutPoint],
+    ) -> Result<Psbt, DaemonError> {
+        Err(DaemonError::NotImplemented)
+    }
     async fn get_labels(
         &self,
         labels: &HashSet<LabelItem>,
@@ -167,6 +234,13 @@ pub trait Daemon: Debug {
         labels: &HashMap<LabelItem, Option<String>>,
     ) -> Result<(), DaemonError>;
     async fn get_labels_bip329(&self, offset: u32, limit: u32) -> Result<Labels, DaemonError>;
+    async fn import_labels(
+        &self,
+        _labels: &[coincubed::bip329::Label],
+        _conflict: LabelConflict,
+    ) -> Result<ImportLabelsResult, DaemonError> {
+        Err(DaemonError::NotImplemented)
+    }
     async fn send_wallet_invitation(&self, _email: &str) -> Result<(), DaemonError> {
         Ok(())
     }
@@ -210,14 +284,16 @@ pub trait Daemon: Debug {
                 .cloned()
                 .collect();
 
-            spend_txs.push(model::SpendTx::new(
+            let mut spend_tx = model::SpendTx::new(
                 tx.updated_at,
                 tx.psbt,
                 coins,
                 &info.descriptors.main,
                 &curve,
                 info.network,
-            ));
+            );
+            spend_tx.approved_at = tx.approved_at;
+            spend_txs.push(spend_tx);
         }
         load_labels(self, &mut spend_txs).await?;
         spend_txs.sort_by(|a, b| {
@@ -300,6 +376,15 @@ pub trait Daemon: Debug {
         self.txs_to_historytxs(txs).await
     }
 
+    async fn search_history_txs(
+        &self,
+        filter: &SearchFilter,
+        limit: u64,
+    ) -> Result<Vec<model::HistoryTransaction>, DaemonError> {
+        let txs = self.search_txs(filter, limit).await?.transactions;
+        self.txs_to_historytxs(txs).await
+    }
+
     async fn get_history_txs(
         &self,
         txids: &[Txid],
@@ -11,9 +11,10 @@ pub use coincube_core::{
     },
 };
 pub use coincubed::commands::{
-    CreateSpendResult, GetAddressResult, GetInfoResult, GetLabelsResult, LabelItem, ListCoinsEntry,
-    ListCoinsResult, ListRevealedAddressesEntry, ListRevealedAddressesResult, ListSpendEntry,
-    ListSpendResult, ListTransactionsResult, TransactionInfo,
+    BalanceHistoryEntry, ConsolidationAdvice, CreateSpendResult, GetAddressResult, GetInfoResult,
+    GetLabelsResult, LabelItem, ListCoinsEntry, ListCoinsResult, ListRevealedAddressesEntry,
+    ListRevealedAddressesResult, ListScheduledBroadcastsResult, ListSpendEntry, ListSpendResult,
+    ListTransactionsResult, ScheduledBroadcastEntry, TransactionInfo,
 };
 
 pub type Coin = ListCoinsEntry;
@@ -48,6 +49,8 @@ pub struct SpendTx {
     pub status: SpendStatus,
     pub sigs: PartialSpendInfo,
     pub updated_at: Option<u32>,
+    /// When the spend was approved, per the spending policy of the daemon.
+    pub approved_at: Option<u32>,
     pub kind: TransactionKind,
 }
 
@@ -177,6 +180,7 @@ impl SpendTx {
                 }
             },
             updated_at,
+            approved_at: None,
             coins: coins_map,
             psbt,
             change_indexes,
@@ -220,10 +224,11 @@ impl SpendTx {
         signers
     }
 
-    /// Feerate obtained if all transaction inputs have the maximum satisfaction size.
-    pub fn min_feerate_vb(&self) -> Option<u64> {
+    /// Feerate (in sats/kvb) obtained if all transaction inputs have the maximum satisfaction size.
+    pub fn min_feerate_kvb(&self) -> Option<u64> {
         self.fee_amount.map(|a| {
             a.to_sat()
+                .saturating_mul(1_000)
                 .checked_div(self.max_vbytes)
                 .expect("a descriptor's satisfaction size is never 0")
         })
@@ -268,9 +273,10 @@ impl Labelled for SpendTx {
                 txid,
                 vout: vout as u32,
             }));
-            items.push(LabelItem::Address(
-                Address::from_script(&output.script_pubkey, self.network).unwrap(),
-            ));
+            // OP_RETURN outputs have no address.
+            if let Ok(addr) = Address::from_script(&output.script_pubkey, self.network) {
+                items.push(LabelItem::Address(addr));
+            }
         }
         items
     }
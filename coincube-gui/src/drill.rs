@@ -0,0 +1,477 @@
+//! Recovery drills: rehearse the recovery of a wallet before it is needed. The policy of the wallet
+//! is cloned, with the same keys or fresh test keys, into a drill wallet on a local regtest node.
+//! The drill wallet is funded from the node, which then mines blocks until the recovery path is
+//! available, so the user can go through creating, signing and broadcasting the recovery
+//! transaction.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use coincube_core::{
+    descriptors::{CoincubeDescriptor, CoincubePolicyError},
+    miniscript::{
+        bitcoin::{bip32::Fingerprint, psbt::Psbt, Address, Amount, Network, NetworkKind},
+        descriptor::{DescriptorMultiXKey, DescriptorPublicKey},
+    },
+};
+use coincubed::{
+    commands::CoinStatus,
+    config::{BitcoinBackend, BitcoinConfig, BitcoindConfig, BitcoindRpcAuth, Config},
+    datadir::DataDirectory,
+};
+use jsonrpc::{client::Client, simple_http::SimpleHttpTransport};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::{
+    daemon::{embedded::EmbeddedDaemon, Daemon, DaemonError},
+    dir::CoincubeDirectory,
+    signer::{Signer, SignerError},
+    utils::now,
+};
+
+/// The default RPC address of a regtest node.
+pub const DEFAULT_NODE_ADDRESS: &str = "127.0.0.1:18443";
+
+/// The name of the wallet funding the drills on the regtest node.
+const NODE_WALLET: &str = "coincube-drill";
+
+/// The amount sent to the drill wallet.
+const DRILL_AMOUNT: Amount = Amount::from_sat(1_000_000);
+
+/// The feerate of the drill recovery transaction.
+pub const DRILL_FEERATE_KVB: u64 = 2_000;
+
+/// How long to wait for the drill wallet to catch up with the node.
+const SYNC_TIMEOUT: Duration = Duration::from_secs(600);
+
+#[derive(Debug)]
+pub enum DrillError {
+    /// The policy of the wallet could not be cloned with the drill keys.
+    Policy(CoincubePolicyError),
+    Signer(SignerError),
+    Node(String),
+    Daemon(DaemonError),
+    /// The drill wallet did not catch up with the node in time.
+    Timeout(&'static str),
+}
+
+impl std::fmt::Display for DrillError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Policy(e) => write!(f, "Failed to clone the wallet policy: {}", e),
+            Self::Signer(e) => write!(f, "Failed to generate a drill key: {}", e),
+            Self::Node(e) => write!(f, "Regtest node error: {}", e),
+            Self::Daemon(e) => write!(f, "Drill wallet error: {}", e),
+            Self::Timeout(what) => write!(f, "Timed out waiting for the drill wallet {}", what),
+        }
+    }
+}
+
+impl From<DaemonError> for DrillError {
+    fn from(e: DaemonError) -> Self {
+        Self::Daemon(e)
+    }
+}
+
+impl From<jsonrpc::Error> for DrillError {
+    fn from(e: jsonrpc::Error) -> Self {
+        Self::Node(e.to_string())
+    }
+}
+
+impl From<jsonrpc::simple_http::Error> for DrillError {
+    fn from(e: jsonrpc::simple_http::Error) -> Self {
+        Self::Node(e.to_string())
+    }
+}
+
+/// The keys of the drill wallet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DrillKeys {
+    /// The keys of the wallet, to check they are all still available and the devices work.
+    Same,
+    /// Fresh hot keys at the same places in the policy, to rehearse the process only.
+    Fresh,
+}
+
+/// Clone the policy of the descriptor for a test network. With fresh keys, the hot signers of the
+/// generated keys are returned along with the descriptor, in the order of the keys they replace.
+pub fn drill_descriptor(
+    descriptor: &CoincubeDescriptor,
+    keys: DrillKeys,
+    network: Network,
+) -> Result<(CoincubeDescriptor, Vec<Signer>), DrillError> {
+    let mut signers: HashMap<Fingerprint, Signer> = HashMap::new();
+    if keys == DrillKeys::Fresh {
+        for key in descriptor.spendable_keys() {
+            signers.insert(
+                key.master_fingerprint(),
+                Signer::generate(network).map_err(DrillError::Signer)?,
+            );
+        }
+    }
+
+    let policy = descriptor
+        .policy()
+        .map_keys(|key| match key {
+            DescriptorPublicKey::MultiXPub(xpub) => {
+                let mut xpub = xpub.clone();
+                match (keys, xpub.origin.clone()) {
+                    (DrillKeys::Fresh, Some((fingerprint, path))) => {
+                        let signer = &signers[&fingerprint];
+                        xpub = DescriptorMultiXKey {
+                            xkey: signer.get_extended_pubkey(&path),
+                            origin: Some((signer.fingerprint(), path)),
+                            ..xpub
+                        };
+                    }
+                    _ => xpub.xkey.network = NetworkKind::Test,
+                }
+                DescriptorPublicKey::MultiXPub(xpub)
+            }
+            // A Coincube policy only contains multipath extended keys.
+            key => key.clone(),
+        })
+        .map_err(DrillError::Policy)?;
+
+    let mut signers: Vec<_> = signers.into_iter().collect();
+    signers.sort_by_key(|(fingerprint, _)| *fingerprint);
+    Ok((
+        CoincubeDescriptor::new(policy),
+        signers.into_iter().map(|(_, signer)| signer).collect(),
+    ))
+}
+
+/// The regtest node funding the drill wallet and mining its blocks.
+pub struct DrillNode {
+    addr: SocketAddr,
+    node: Client,
+    wallet: Client,
+}
+
+impl std::fmt::Debug for DrillNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DrillNode")
+            .field("addr", &self.addr)
+            .finish()
+    }
+}
+
+impl DrillNode {
+    /// Connect to the regtest node, and create or load the wallet funding the drills.
+    pub fn new(addr: SocketAddr, cookie_path: &Path) -> Result<Self, DrillError> {
+        let cookie = std::fs::read_to_string(cookie_path)
+            .map_err(|e| DrillError::Node(format!("Failed to read cookie file: {}", e)))?;
+        let client = |url: String| -> Result<Client, DrillError> {
+            Ok(Client::with_transport(
+                SimpleHttpTransport::builder()
+                    .url(&url)?
+                    .cookie_auth(cookie.clone())
+                    .timeout(Duration::from_secs(120))
+                    .build(),
+            ))
+        };
+        let node = Self {
+            addr,
+            node: client(format!("http://{}", addr))?,
+            wallet: client(format!("http://{}/wallet/{}", addr, NODE_WALLET))?,
+        };
+
+        let info: Value = node.call("getblockchaininfo", &[])?;
+        let chain = info["chain"].as_str().unwrap_or_default();
+        if chain != "regtest" {
+            return Err(DrillError::Node(format!(
+                "The node is on '{}', a regtest node is required to mine blocks.",
+                chain
+            )));
+        }
+        let wallets: Vec<String> = node.call("listwallets", &[])?;
+        if !wallets.iter().any(|w| w == NODE_WALLET) {
+            let created: Result<Value, _> = node.call("createwallet", &[NODE_WALLET.into()]);
+            if created.is_err() {
+                node.call::<Value>("loadwallet", &[NODE_WALLET.into()])?;
+            }
+        }
+        Ok(node)
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    fn call<T: DeserializeOwned>(&self, method: &str, params: &[Value]) -> Result<T, DrillError> {
+        self.client(method)
+            .call(method, Some(&*jsonrpc::arg(params)))
+            .map_err(DrillError::from)
+    }
+
+    fn client(&self, method: &str) -> &Client {
+        match method {
+            "getnewaddress" | "getbalance" | "sendtoaddress" => &self.wallet,
+            _ => &self.node,
+        }
+    }
+
+    pub fn block_count(&self) -> Result<i32, DrillError> {
+        self.call("getblockcount", &[])
+    }
+
+    pub fn new_address(&self) -> Result<Address, DrillError> {
+        let address: String = self.call("getnewaddress", &[])?;
+        Address::from_str(&address)
+            .map(|a| a.assume_checked())
+            .map_err(|e| DrillError::Node(e.to_string()))
+    }
+
+    /// Mine blocks to the node wallet, by batches to not time out.
+    pub fn mine(&self, mut count: u32) -> Result<(), DrillError> {
+        let address = self.new_address()?.to_string();
+        while count > 0 {
+            let batch = count.min(1_000);
+            self.call::<Value>("generatetoaddress", &[batch.into(), address.clone().into()])?;
+            count -= batch;
+        }
+        Ok(())
+    }
+
+    /// Send the drill amount to this address and confirm it.
+    pub fn fund(&self, address: &Address) -> Result<(), DrillError> {
+        let balance: f64 = self.call("getbalance", &[])?;
+        if Amount::from_btc(balance).unwrap_or(Amount::ZERO) < DRILL_AMOUNT * 2 {
+            // Coinbase outputs mature after 100 blocks.
+            self.mine(101)?;
+        }
+        self.call::<Value>(
+            "sendtoaddress",
+            &[address.to_string().into(), DRILL_AMOUNT.to_btc().into()],
+        )?;
+        self.mine(1)
+    }
+}
+
+/// Run blocking work, such as calls to the regtest node, off the async executor.
+async fn blocking<T, F>(f: F) -> Result<T, DrillError>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, DrillError> + Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| DrillError::Node(e.to_string()))?
+}
+
+/// A running drill: the drill wallet, funded and past the timelock of its drilled recovery path.
+#[derive(Debug)]
+pub struct Drill {
+    pub descriptor: CoincubeDescriptor,
+    /// The hot signers of the drill keys, if fresh keys were generated.
+    pub signers: Vec<Arc<Signer>>,
+    pub timelock: u16,
+    pub daemon: Arc<dyn Daemon + Sync + Send>,
+    pub node: Arc<DrillNode>,
+    /// The data directory of the drill wallet, removed once the drill is over.
+    data_dir: PathBuf,
+}
+
+impl Drop for Drill {
+    fn drop(&mut self) {
+        // The drill wallet must not keep polling the node once the drill is over, and its data is
+        // not needed anymore.
+        let data_dir = self.data_dir.clone();
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            let daemon = self.daemon.clone();
+            runtime.spawn(async move {
+                if let Err(e) = daemon.stop().await {
+                    log::error!("Failed to stop the drill wallet: {}", e);
+                }
+                if let Err(e) = tokio::fs::remove_dir_all(&data_dir).await {
+                    log::error!("Failed to remove the drill wallet data directory: {}", e);
+                }
+            });
+        } else if let Err(e) = std::fs::remove_dir_all(&data_dir) {
+            log::error!("Failed to remove the drill wallet data directory: {}", e);
+        }
+    }
+}
+
+/// The data directory of a new drill wallet.
+pub fn drill_data_directory(datadir: &CoincubeDirectory) -> PathBuf {
+    let mut path = datadir
+        .network_directory(Network::Regtest)
+        .path()
+        .to_path_buf();
+    path.push("drills");
+    path.push(now().as_secs().to_string());
+    path
+}
+
+/// Start a drill for the given recovery path: start the drill wallet, fund it from the node and
+/// mine blocks until its coin can be spent through the recovery path.
+pub async fn start_drill(
+    datadir: CoincubeDirectory,
+    descriptor: CoincubeDescriptor,
+    signers: Vec<Arc<Signer>>,
+    timelock: u16,
+    node_addr: SocketAddr,
+    cookie_path: PathBuf,
+) -> Result<Drill, DrillError> {
+    let node = {
+        let cookie_path = cookie_path.clone();
+        blocking(move || DrillNode::new(node_addr, &cookie_path)).await?
+    };
+    let data_dir = drill_data_directory(&datadir);
+    let config = Config::new(
+        BitcoinConfig {
+            network: Network::Regtest,
+            poll_interval_secs: Duration::from_secs(1),
+        },
+        Some(BitcoinBackend::Bitcoind(BitcoindConfig {
+            rpc_auth: BitcoindRpcAuth::CookieFile(cookie_path),
+            addr: node_addr,
+        })),
+        log::LevelFilter::Info,
+        descriptor.clone(),
+        DataDirectory::new(data_dir.clone()),
+    );
+    let daemon: Arc<dyn Daemon + Sync + Send> =
+        match blocking(move || Ok(EmbeddedDaemon::start(config)?)).await {
+            Ok(daemon) => Arc::new(daemon),
+            Err(e) => {
+                if data_dir.exists() {
+                    if let Err(e) = tokio::fs::remove_dir_all(&data_dir).await {
+                        log::error!("Failed to remove the drill wallet data directory: {}", e);
+                    }
+                }
+                return Err(e);
+            }
+        };
+    let drill = Drill {
+        descriptor,
+        signers,
+        timelock,
+        daemon,
+        node: Arc::new(node),
+        data_dir,
+    };
+
+    let address = drill.daemon.get_new_address().await?.address;
+    drill
+        .with_node(move |node| {
+            node.fund(&address)?;
+            // The coin is confirmed in the tip block, it can be spent through the recovery path
+            // in the block after the timelock expired.
+            node.mine(u32::from(timelock) - 1)
+        })
+        .await?;
+    drill.wait_for_sync().await?;
+    let coins = drill
+        .daemon
+        .list_coins(&[CoinStatus::Confirmed], &[])
+        .await?
+        .coins;
+    if coins.is_empty() {
+        return Err(DrillError::Timeout("to receive the drill coin"));
+    }
+    Ok(drill)
+}
+
+impl Drill {
+    /// Make blocking calls to the regtest node, off the async executor.
+    pub async fn with_node<T, F>(&self, f: F) -> Result<T, DrillError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&DrillNode) -> Result<T, DrillError> + Send + 'static,
+    {
+        let node = self.node.clone();
+        blocking(move || f(&node)).await
+    }
+
+    /// Wait for the drill wallet to process all the blocks of the node.
+    pub async fn wait_for_sync(&self) -> Result<(), DrillError> {
+        let height = self.with_node(|node| node.block_count()).await?;
+        let start = now();
+        while self.daemon.get_info().await?.block_height < height {
+            if now() - start > SYNC_TIMEOUT {
+                return Err(DrillError::Timeout("to sync with the node"));
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+        Ok(())
+    }
+
+    /// Sign the recovery transaction with the hot signers of the drill.
+    pub fn sign_with_hot_signers(&self, mut psbt: Psbt) -> Result<Psbt, SignerError> {
+        for signer in &self.signers {
+            if self
+                .descriptor
+                .contains_fingerprint_in_path(signer.fingerprint(), Some(self.timelock))
+            {
+                psbt = signer.sign_psbt(psbt)?;
+            }
+        }
+        Ok(psbt)
+    }
+
+    /// Broadcast the signed recovery transaction, confirm it and wait for the drill wallet to see
+    /// its coin spent.
+    pub async fn broadcast(&self, psbt: &Psbt) -> Result<(), DrillError> {
+        let txid = psbt.unsigned_tx.compute_txid();
+        self.daemon.update_spend_tx(psbt).await?;
+        self.daemon.broadcast_spend_tx(&txid).await?;
+        self.with_node(|node| node.mine(1)).await?;
+        self.wait_for_sync().await?;
+        let unspent = self
+            .daemon
+            .list_coins(&[CoinStatus::Confirmed, CoinStatus::Spending], &[])
+            .await?
+            .coins;
+        if !unspent.is_empty() {
+            return Err(DrillError::Timeout(
+                "to see the recovery transaction confirmed",
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DESCRIPTOR: &str = "wsh(or_d(pk([8a550171/48'/1'/0'/2']tpubDFnCs5ZaCqopaNhgLCiXAwbkaBdcnuMt1VFoPsRpUrpidyvzG67MYjkfxw6HnTBhHqeU3xw2ioNBVcWY3jXwGhSyppEQvtn38GsL7RH1eef/<0;1>/*),and_v(v:pkh([8a550171/48'/1'/0'/2']tpubDFnCs5ZaCqopaNhgLCiXAwbkaBdcnuMt1VFoPsRpUrpidyvzG67MYjkfxw6HnTBhHqeU3xw2ioNBVcWY3jXwGhSyppEQvtn38GsL7RH1eef/<2;3>/*),older(52596))))#jz5sm0xn";
+
+    #[test]
+    fn drill_descriptors() {
+        let descriptor = CoincubeDescriptor::from_str(DESCRIPTOR).unwrap();
+        let timelocks = |d: &CoincubeDescriptor| {
+            d.policy()
+                .recovery_paths()
+                .keys()
+                .copied()
+                .collect::<Vec<_>>()
+        };
+
+        // The same keys, on a test network.
+        let (drill, signers) =
+            drill_descriptor(&descriptor, DrillKeys::Same, Network::Regtest).unwrap();
+        assert!(signers.is_empty());
+        assert!(drill.all_xpubs_net_is(Network::Regtest));
+        assert_eq!(drill.spendable_keys(), descriptor.spendable_keys());
+        assert_eq!(timelocks(&drill), vec![52596]);
+
+        // Fresh keys for the same policy, which the generated signers can sign for.
+        let (drill, signers) =
+            drill_descriptor(&descriptor, DrillKeys::Fresh, Network::Regtest).unwrap();
+        assert_eq!(signers.len(), 1);
+        assert!(drill.all_xpubs_net_is(Network::Regtest));
+        assert!(!drill.contains_fingerprint(Fingerprint::from_str("8a550171").unwrap()));
+        assert!(drill.contains_fingerprint_in_path(signers[0].fingerprint(), None));
+        assert!(drill.contains_fingerprint_in_path(signers[0].fingerprint(), Some(52596)));
+        assert_eq!(timelocks(&drill), vec![52596]);
+    }
+}
@@ -1,5 +1,5 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fmt::Display,
     fs::{self, File},
     io::{Read, Write},
@@ -9,21 +9,23 @@ use std::{
     time,
 };
 
-use encrypted_backup::{descriptor::dpk_to_pk, Decrypted, EncryptedBackup};
+use encrypted_backup::{descriptor::dpk_to_pk, Content, Decrypted, EncryptedBackup};
 use tokio::sync::mpsc::{channel, unbounded_channel, Sender, UnboundedReceiver, UnboundedSender};
 
 use async_hwi::bitbox::api::btc::Fingerprint;
 use chrono::{DateTime, Duration, Utc};
 use coincube_core::{
+    bip322::{self, ReserveProof},
     descriptors::{bip341_nums, CoincubeDescriptor},
     miniscript::{
-        bitcoin::{Amount, Network, Psbt, Txid},
+        bitcoin::{secp256k1, Amount, Network, Psbt, Txid},
         DescriptorPublicKey,
     },
+    psbtv2,
 };
 use coincubed::{
-    bip329::{error::ExportError, Labels},
-    commands::LabelItem,
+    bip329::{self, error::ExportError, Labels},
+    commands::{ImportLabelsResult, LabelConflict, LabelItem},
 };
 use tokio::{
     task::{JoinError, JoinHandle},
@@ -33,6 +35,7 @@ use tokio::{
 use iced::futures::{SinkExt, Stream};
 
 use crate::{
+    accounting::{self, AccountingExport},
     app::{
         cache::Cache,
         settings::{self, update_settings_file, KeySetting, WalletSettings},
@@ -41,13 +44,18 @@ use crate::{
         Config,
     },
     backup::{self, Backup},
+    bcur, coldcard,
     daemon::{
         model::{HistoryTransaction, Labelled},
         Daemon, DaemonBackend, DaemonError,
     },
     dir::{CoincubeDirectory, NetworkDirectory},
+    heir::HeirPackage,
     node::bitcoind::Bitcoind,
-    services::connect::client::backend::api::DEFAULT_LIMIT,
+    services::{
+        connect::client::backend::api::DEFAULT_LIMIT,
+        fiat::{PriceClient, PriceHistory},
+    },
 };
 
 const DUMP_LABELS_LIMIT: u32 = 100;
@@ -83,6 +91,7 @@ pub enum ImportExportMessage {
     Path(Option<PathBuf>),
     Close,
     Overwrite,
+    Merge,
     Ignore,
     UpdateAliases(HashMap<Fingerprint, settings::KeySetting>),
     Xpub(String),
@@ -122,6 +131,7 @@ pub enum Error {
     ParseDescriptor,
     Bip329Export(String),
     BackupImport(String),
+    LabelsImport(String),
     Backup(backup::Error),
     EncryptedBackup(encrypted_backup::Error),
     EncryptionFailed,
@@ -131,6 +141,9 @@ pub enum Error {
     InsanePsbt,
     OutpointNotOwned,
     UnknownFormat,
+    Coldcard(coldcard::Error),
+    Ur(bcur::Error),
+    Bip322(bip322::Bip322Error),
 }
 
 impl Display for Error {
@@ -149,6 +162,7 @@ impl Display for Error {
             Error::ParseDescriptor => write!(f, "ImportExport: fail to parse descriptor"),
             Error::Bip329Export(e) => write!(f, "Bip329Export: {e}"),
             Error::BackupImport(e) => write!(f, "BackupImport: {e}"),
+            Error::LabelsImport(e) => write!(f, "LabelsImport: {e}"),
             Error::Backup(e) => write!(f, "Backup: {e}"),
             Error::ParseXpub => write!(f, "Failed to parse Xpub from file"),
             Error::XpubNetwork => write!(f, "Xpub is for another network"),
@@ -160,6 +174,9 @@ impl Display for Error {
             ),
             Error::EncryptedBackup(e) => write!(f, "Failed to encrypt backup: {e:?}"),
             Error::UnknownFormat => write!(f, "Format of the file unknown"),
+            Error::Coldcard(e) => write!(f, "Coldcard: {e}"),
+            Error::Ur(e) => write!(f, "{e}"),
+            Error::Bip322(e) => write!(f, "{e}"),
             Error::EncryptionFailed => write!(f, "Encryption failed, please contact Wizarsardine team.")
         }
     }
@@ -168,6 +185,7 @@ impl Display for Error {
 #[derive(Debug, Clone)]
 pub enum ImportExportType {
     Transactions,
+    AccountingTransactions(AccountingExport),
     ExportPsbt(String),
     ExportXpub(String),
     ExportEncryptedDescriptor(Box<CoincubeDescriptor>),
@@ -181,26 +199,44 @@ pub enum ImportExportType {
     FromBackup,
     Descriptor(CoincubeDescriptor),
     ExportLabels,
+    ImportLabels {
+        wallet: Arc<Wallet>,
+        conflict: Option<Sender<LabelConflict>>,
+    },
     ImportPsbt(Option<Txid>),
     ImportXpub(Network),
     ImportDescriptor,
+    ExportColdcardRegistration(Box<CoincubeDescriptor>),
+    ExportColdcardPsbt(Box<Psbt>),
+    ImportColdcardSigned(Box<Psbt>),
+    ExportReserveProof(String),
+    VerifyReserveProof,
+    ExportHeirPackage(Box<HeirPackage>),
 }
 
 impl ImportExportType {
     pub fn end_message(&self) -> &str {
         match self {
             ImportExportType::Transactions
+            | ImportExportType::AccountingTransactions(_)
             | ImportExportType::ExportPsbt(_)
             | ImportExportType::Descriptor(_)
             | ImportExportType::ExportProcessBackup(..)
             | ImportExportType::ExportXpub(_)
             | ImportExportType::ExportEncryptedDescriptor(_)
-            | ImportExportType::ExportLabels => "Export successful!",
+            | ImportExportType::ExportLabels
+            | ImportExportType::ExportColdcardRegistration(_)
+            | ImportExportType::ExportColdcardPsbt(_)
+            | ImportExportType::ExportReserveProof(_)
+            | ImportExportType::ExportHeirPackage(_) => "Export successful!",
             ImportExportType::ImportBackup { .. }
+            | ImportExportType::ImportLabels { .. }
             | ImportExportType::ImportPsbt(_)
+            | ImportExportType::ImportColdcardSigned(_)
             | ImportExportType::ImportXpub(_)
             | ImportExportType::FromBackup
             | ImportExportType::ImportDescriptor => "Import successful",
+            ImportExportType::VerifyReserveProof => "The proof of reserves is valid.",
         }
     }
 }
@@ -254,6 +290,8 @@ pub enum Progress {
     Descriptor(CoincubeDescriptor),
     Xpub(String),
     LabelsConflict(Sender<bool>),
+    LabelsImportConflict(Sender<LabelConflict>),
+    Summary(String),
     KeyAliasesConflict(Sender<bool>),
     UpdateAliases(HashMap<Fingerprint, settings::KeySetting>),
     WalletFromBackup(
@@ -301,11 +339,17 @@ impl Export {
     ) {
         if let Err(e) = match export_type {
             ImportExportType::Transactions => export_transactions(&sender, daemon, path).await,
+            ImportExportType::AccountingTransactions(export) => {
+                export_accounting(&sender, daemon, path, export).await
+            }
             ImportExportType::ExportPsbt(str) => export_string(&sender, path, str).await,
             ImportExportType::Descriptor(descriptor) => {
                 export_descriptor(&sender, path, descriptor).await
             }
             ImportExportType::ExportLabels => export_labels(&sender, daemon, path).await,
+            ImportExportType::ImportLabels { wallet, .. } => {
+                import_labels(&sender, daemon, path, wallet).await
+            }
             ImportExportType::ImportPsbt(txid) => import_psbt(daemon, &sender, path, txid).await,
             ImportExportType::ImportXpub(network) => import_xpub(&sender, path, network).await,
             ImportExportType::ImportDescriptor => import_descriptor(&sender, path).await,
@@ -331,6 +375,22 @@ impl Export {
                 ..
             } => import_backup(&network_dir, wallet, &sender, path, daemon).await,
             ImportExportType::FromBackup => from_backup(&sender, path).await,
+            ImportExportType::ExportColdcardRegistration(descriptor) => {
+                export_string(&sender, path, coldcard::registration_file(&descriptor)).await
+            }
+            ImportExportType::ExportColdcardPsbt(psbt) => {
+                export_bytes(&sender, path, psbt.serialize()).await
+            }
+            ImportExportType::ImportColdcardSigned(psbt) => {
+                import_coldcard_signed(daemon, &sender, path, *psbt).await
+            }
+            ImportExportType::ExportReserveProof(proof) => {
+                export_string(&sender, path, proof).await
+            }
+            ImportExportType::VerifyReserveProof => verify_reserve_proof(&sender, path).await,
+            ImportExportType::ExportHeirPackage(package) => {
+                export_heir_package(&sender, path, *package).await
+            }
         } {
             if let Err(e) = sender.send(Progress::Error(e)) {
                 tracing::error!("Import/Export fail to send msg: {}", e);
@@ -432,17 +492,11 @@ pub fn export_subscription(
     })
 }
 
-pub async fn export_transactions(
+/// Fetch all the wallet transactions, reporting the progress up to 80%.
+async fn fetch_history_txs(
     sender: &UnboundedSender<Progress>,
-    daemon: Option<Arc<dyn Daemon + Sync + Send>>,
-    path: PathBuf,
-) -> Result<(), Error> {
-    let daemon = daemon.ok_or(Error::DaemonMissing)?;
-    let mut file = open_file_write(&path).await?;
-
-    let header = "Date,Label,Value,Fee,Txid,Block\n".to_string();
-    file.write_all(header.as_bytes())?;
-
+    daemon: &Arc<dyn Daemon + Sync + Send>,
+) -> Result<Vec<HistoryTransaction>, Error> {
     // look 2 hour forward
     // https://github.com/bitcoin/bitcoin/blob/62bd61de110b057cbfd6e31e4d0b727d93119c72/src/chain.h#L29
     let mut end = ((Utc::now() + Duration::hours(2)).timestamp()) as u32;
@@ -516,7 +570,21 @@ pub async fn export_transactions(
         }
     }
 
-    let mut txs: Vec<_> = map.into_values().collect();
+    Ok(map.into_values().collect())
+}
+
+pub async fn export_transactions(
+    sender: &UnboundedSender<Progress>,
+    daemon: Option<Arc<dyn Daemon + Sync + Send>>,
+    path: PathBuf,
+) -> Result<(), Error> {
+    let daemon = daemon.ok_or(Error::DaemonMissing)?;
+    let mut file = open_file_write(&path).await?;
+
+    let header = "Date,Label,Value,Fee,Txid,Block\n".to_string();
+    file.write_all(header.as_bytes())?;
+
+    let mut txs = fetch_history_txs(sender, &daemon).await?;
     txs.sort_by(|a, b| b.compare(a));
 
     for mut tx in txs {
@@ -571,6 +639,63 @@ pub async fn export_transactions(
     Ok(())
 }
 
+/// Export the accounting report of the wallet transactions as CSV, along with the JSON schema
+/// of its rows in a `.schema.json` file next to it.
+pub async fn export_accounting(
+    sender: &UnboundedSender<Progress>,
+    daemon: Option<Arc<dyn Daemon + Sync + Send>>,
+    path: PathBuf,
+    export: AccountingExport,
+) -> Result<(), Error> {
+    let daemon = daemon.ok_or(Error::DaemonMissing)?;
+    let mut file = open_file_write(&path).await?;
+
+    let txs = fetch_history_txs(sender, &daemon).await?;
+
+    let mut prices = HashMap::new();
+    if let Some((source, currency)) = export.fiat {
+        let mut history = export
+            .price_history
+            .map(PriceHistory::load)
+            .unwrap_or_default();
+        let client = PriceClient::<reqwest::Client>::default_from_source(source);
+        let confirmed: Vec<_> = txs
+            .iter()
+            .filter_map(|tx| tx.time.map(|time| (tx.txid, time)))
+            .collect();
+        for (i, (txid, time)) in confirmed.iter().enumerate() {
+            if let Some(price) = history
+                .price_at(source, Some(&client), currency, *time as u64)
+                .await
+            {
+                prices.insert(*txid, price);
+            }
+            send_progress!(
+                sender,
+                Progress(80.0 + (i + 1) as f32 / confirmed.len() as f32 * 15.0)
+            );
+        }
+        if let Err(e) = history.save() {
+            tracing::error!("Failed to save the price history: {}", e);
+        }
+    }
+
+    let rows = accounting::accounting_rows(&txs, &prices, export.cost_basis);
+    accounting::write_csv(
+        &mut file,
+        &rows,
+        export.fiat.map(|(_, currency)| currency),
+        export.cost_basis,
+    )?;
+    let schema = serde_json::to_string_pretty(&accounting::json_schema())
+        .map_err(|e| Error::Io(e.to_string()))?;
+    fs::write(path.with_extension("schema.json"), schema)?;
+
+    send_progress!(sender, Progress(100.0));
+    send_progress!(sender, Ended);
+    Ok(())
+}
+
 pub async fn export_descriptor(
     sender: &UnboundedSender<Progress>,
     path: PathBuf,
@@ -598,6 +723,18 @@ pub async fn export_string(
     Ok(())
 }
 
+pub async fn export_bytes(
+    sender: &UnboundedSender<Progress>,
+    path: PathBuf,
+    bytes: Vec<u8>,
+) -> Result<(), Error> {
+    let mut file = open_file_write(&path).await?;
+    file.write_all(&bytes)?;
+    send_progress!(sender, Progress(100.0));
+    send_progress!(sender, Ended);
+    Ok(())
+}
+
 pub async fn export_encrypted_descriptor(
     sender: &UnboundedSender<Progress>,
     path: PathBuf,
@@ -651,22 +788,75 @@ pub async fn export_encrypted_descriptor(
     Ok(())
 }
 
+/// Export the heir package as a wallet backup encrypted to the keys of its descriptor, for the
+/// heir to restore the wallet and its pre-signed recovery transactions with their own key.
+pub async fn export_heir_package(
+    sender: &UnboundedSender<Progress>,
+    path: PathBuf,
+    package: HeirPackage,
+) -> Result<(), Error> {
+    let backup = serde_json::to_vec(&package.backup()).map_err(|_| Error::EncryptionFailed)?;
+    // The descriptor gives the keys and derivation paths to encrypt to, the payload is then
+    // replaced by the backup which has no content type of its own.
+    let bytes = EncryptedBackup::new()
+        .set_payload(package.descriptor.descriptor())?
+        .set_payload(&backup)?
+        .set_content_type(Content::None)
+        .encrypt()?;
+
+    send_progress!(sender, Progress(50.0));
+    // verify we can decrypt the backup with any key of the descriptor
+    for key in package.descriptor.spendable_keys() {
+        let decrypted = EncryptedBackup::new()
+            .set_encrypted_payload(&bytes)
+            .map_err(|_| Error::EncryptionFailed)?
+            .set_keys(vec![dpk_to_pk(&key)])
+            .decrypt()
+            .map_err(|_| Error::EncryptionFailed)?;
+        match decrypted {
+            Decrypted::Raw(b) if b == backup => {}
+            _ => return Err(Error::EncryptionFailed),
+        }
+    }
+
+    let mut file = open_file_write(&path).await?;
+    file.write_all(&bytes)?;
+    send_progress!(sender, Progress(100.0));
+    send_progress!(sender, Ended);
+    Ok(())
+}
+
+/// Parse a PSBT from the content of a file: either the base64 encoded PSBT, the parts of a
+/// `crypto-psbt` UR or an image of the scanned frames of its animated QR code. Version 2 PSBTs
+/// are converted to version 0.
+pub fn parse_psbt(bytes: &[u8]) -> Result<Psbt, Error> {
+    let parts = if bcur::is_image(bytes) {
+        bcur::qr_codes_from_image(bytes).map_err(Error::Ur)?
+    } else {
+        let text = std::str::from_utf8(bytes).map_err(|_| Error::ParsePsbt)?;
+        if !bcur::is_ur(text) {
+            return psbtv2::from_base64(text.trim()).map_err(|_| Error::ParsePsbt);
+        }
+        text.split_whitespace().map(|s| s.to_string()).collect()
+    };
+    match bcur::decode(parts.iter().map(|p| p.as_str())).map_err(Error::Ur)? {
+        bcur::Decoded::Psbt(psbt) => Ok(*psbt),
+        _ => Err(Error::ParsePsbt),
+    }
+}
+
 pub async fn import_psbt(
     daemon: Option<Arc<dyn Daemon + Sync + Send>>,
     sender: &UnboundedSender<Progress>,
     path: PathBuf,
     txid: Option<Txid>,
 ) -> Result<(), Error> {
-    let mut file = File::open(&path)?;
+    let bytes = fs::read(&path)?;
     let daemon = daemon.ok_or(Error::DaemonMissing)?;
 
     let descr = daemon.get_info().await?.descriptors.main;
 
-    let mut psbt_str = String::new();
-    file.read_to_string(&mut psbt_str)?;
-    psbt_str = psbt_str.trim().to_string();
-
-    let psbt = Psbt::from_str(&psbt_str).map_err(|_| Error::ParsePsbt)?;
+    let psbt = parse_psbt(&bytes)?;
     send_progress!(sender, Progress(50.0));
     descr
         .partial_spend_info(&psbt)
@@ -696,6 +886,36 @@ pub async fn import_psbt(
     Ok(())
 }
 
+/// Import a `-signed.psbt` or `-final.txn` file written by an air-gapped Coldcard for `psbt`.
+pub async fn import_coldcard_signed(
+    daemon: Option<Arc<dyn Daemon + Sync + Send>>,
+    sender: &UnboundedSender<Progress>,
+    path: PathBuf,
+    psbt: Psbt,
+) -> Result<(), Error> {
+    let bytes = fs::read(&path)?;
+    let daemon = daemon.ok_or(Error::DaemonMissing)?;
+
+    let descr = daemon.get_info().await?.descriptors.main;
+
+    let signed = coldcard::SignedFile::parse(&bytes)
+        .and_then(|file| file.into_psbt(&psbt, &secp256k1::Secp256k1::verification_only()))
+        .map_err(Error::Coldcard)?;
+    send_progress!(sender, Progress(50.0));
+    if signed.unsigned_tx.compute_txid() != psbt.unsigned_tx.compute_txid() {
+        return Err(Error::TxidNotMatch);
+    }
+    descr
+        .partial_spend_info(&signed)
+        .map_err(|_| Error::InsanePsbt)?;
+
+    daemon.update_spend_tx(&signed).await?;
+    send_progress!(sender, Psbt(signed));
+
+    send_progress!(sender, Progress(100.0));
+    Ok(())
+}
+
 pub async fn import_descriptor(
     sender: &UnboundedSender<Progress>,
     path: PathBuf,
@@ -1341,6 +1561,172 @@ pub async fn export_labels(
     Ok(())
 }
 
+/// Import labels from a BIP-0329 JSONL file:
+///    - parse the file line by line, skipping invalid records
+///    - check if labels or key aliases conflict with the existing ones, if so ask the user how
+///      to resolve the conflict
+///    - import the labels in the daemon
+///    - update the key aliases from the labels of the wallet xpubs
+pub async fn import_labels(
+    sender: &UnboundedSender<Progress>,
+    daemon: Option<Arc<dyn Daemon + Sync + Send>>,
+    path: PathBuf,
+    wallet: Arc<Wallet>,
+) -> Result<(), Error> {
+    let daemon = daemon.ok_or(Error::DaemonMissing)?;
+
+    let content = fs::read_to_string(&path)?;
+    let mut labels = Vec::new();
+    let mut invalid = 0;
+    for line in content.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        match serde_json::from_str::<bip329::Label>(line) {
+            Ok(label) => labels.push(label),
+            Err(e) => {
+                tracing::warn!("Skipping invalid BIP-0329 record: {}", e);
+                invalid += 1;
+            }
+        }
+    }
+    if labels.is_empty() {
+        return Err(Error::LabelsImport(
+            "The file does not contain any valid BIP-0329 label".into(),
+        ));
+    }
+    send_progress!(sender, Progress(20.0));
+
+    // check if labels or aliases can be imported w/o conflict
+    let network = daemon.get_info().await?.network;
+    let new_labels: HashMap<LabelItem, String> = labels
+        .iter()
+        .filter_map(|l| LabelItem::from_bip329(l, network))
+        .collect();
+    let items: HashSet<LabelItem> = new_labels.keys().cloned().collect();
+    let db_labels = daemon.get_labels(&items).await?;
+    let labels_conflict = new_labels.iter().any(|(item, label)| {
+        db_labels
+            .get(&item.to_string())
+            .is_some_and(|current| current != label)
+    });
+    send_progress!(sender, Progress(40.0));
+
+    let conflict = if labels_conflict {
+        let (conflict_sender, mut conflict_receiver) = channel(1);
+        send_progress!(sender, LabelsImportConflict(conflict_sender));
+        conflict_receiver.recv().await.ok_or_else(|| {
+            Error::LabelsImport("Failed to receive the conflict resolution".into())
+        })?
+    } else {
+        LabelConflict::Skip
+    };
+
+    let res = daemon.import_labels(&labels, conflict).await?;
+    send_progress!(sender, Progress(80.0));
+
+    // The labels of our xpubs become key aliases.
+    let mut aliases = wallet.keys_aliases.clone();
+    let mut aliases_updated = false;
+    for key in &res.keys {
+        let alias = match aliases.get(&key.fingerprint) {
+            None => key.label.clone(),
+            Some(current) if current.is_empty() => key.label.clone(),
+            Some(current) if *current == key.label => continue,
+            Some(current) => match conflict {
+                LabelConflict::Skip => continue,
+                LabelConflict::Overwrite => key.label.clone(),
+                LabelConflict::Merge if current.contains(&key.label) => continue,
+                LabelConflict::Merge => format!("{}; {}", current, key.label),
+            },
+        };
+        aliases.insert(key.fingerprint, alias);
+        aliases_updated = true;
+    }
+    if aliases_updated {
+        let aliases = aliases
+            .into_iter()
+            .map(|(fg, name)| {
+                (
+                    fg,
+                    KeySetting {
+                        name,
+                        master_fingerprint: fg,
+                        provider_key: wallet.provider_keys.get(&fg).cloned(),
+                    },
+                )
+            })
+            .collect();
+        send_progress!(sender, UpdateAliases(aliases));
+    }
+
+    send_progress!(sender, Summary(labels_import_summary(&res, invalid)));
+    send_progress!(sender, Progress(100.0));
+    send_progress!(sender, Ended);
+    Ok(())
+}
+
+/// Human readable report of a labels import.
+pub fn labels_import_summary(res: &ImportLabelsResult, invalid: usize) -> String {
+    let mut summary = format!(
+        "{} label(s) imported, {} updated, {} skipped, {} unchanged.",
+        res.imported, res.updated, res.skipped, res.unchanged
+    );
+    if !res.keys.is_empty() {
+        summary.push_str(&format!(
+            "\n{} key label(s) used as key aliases.",
+            res.keys.len()
+        ));
+    }
+    if !res.foreign.is_empty() {
+        summary.push_str(&format!(
+            "\n{} label(s) ignored, not belonging to this wallet.",
+            res.foreign.len()
+        ));
+    }
+    if !res.unsupported.is_empty() {
+        summary.push_str(&format!(
+            "\n{} label(s) of an unsupported type ignored.",
+            res.unsupported.len()
+        ));
+    }
+    if !res.unspendable.is_empty() {
+        summary.push_str(&format!(
+            "\n{} coin(s) marked as not spendable: coin freezing is not supported.",
+            res.unspendable.len()
+        ));
+    }
+    if invalid > 0 {
+        summary.push_str(&format!("\n{} invalid line(s) ignored.", invalid));
+    }
+    summary
+}
+
+/// Check the signatures of a proof of reserves file. Whether the coins are still unspent is left
+/// to the user to check against their own node.
+pub async fn verify_reserve_proof(
+    sender: &UnboundedSender<Progress>,
+    path: PathBuf,
+) -> Result<(), Error> {
+    let content = fs::read_to_string(&path)?;
+    let proof: ReserveProof = serde_json::from_str(&content).map_err(|_| Error::UnknownFormat)?;
+    send_progress!(sender, Progress(50.0));
+
+    let secp = secp256k1::Secp256k1::verification_only();
+    let total = proof.verify(&secp).map_err(Error::Bip322)?;
+    send_progress!(
+        sender,
+        Summary(format!(
+            "{} coin(s) for a total of {} ({} address {}).\nMessage: \"{}\"\nCheck with your own node that the coins are unspent.",
+            proof.coins.len(),
+            total,
+            proof.network,
+            proof.address.assume_checked_ref(),
+            proof.message,
+        ))
+    );
+    send_progress!(sender, Progress(100.0));
+    send_progress!(sender, Ended);
+    Ok(())
+}
+
 pub async fn get_path(filename: String, write: bool) -> Option<PathBuf> {
     if write {
         rfd::AsyncFileDialog::new()
@@ -1359,6 +1745,14 @@ pub async fn get_path(filename: String, write: bool) -> Option<PathBuf> {
     }
 }
 
+pub async fn get_directory() -> Option<PathBuf> {
+    rfd::AsyncFileDialog::new()
+        .set_title("Choose a directory to export to...")
+        .pick_folder()
+        .await
+        .map(|fh| fh.path().to_path_buf())
+}
+
 pub async fn app_backup(
     datadir: CoincubeDirectory,
     network: Network,
@@ -1394,6 +1788,28 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn test_parse_psbt_from_ur() {
+        let psbt_str = "cHNidP8BAHECAAAAAUSHuliRtuCX1S6JxRuDRqDCKkWfKmWL5sV9ukZ/wzvfAAAAAAD9////AogTAAAAAAAAFgAUIxe7UY6LJ6y5mFBoWTOoVispDmdwFwAAAAAAABYAFKqO83TK+t/KdpAt21z2HGC7/Z2FAAAAAAABASsQJwAAAAAAACIAIIIySQjGCTeyx/rKUQx8qobjhJeNCiVCliBJPdyRX6XKAQVBIQI2cqWpc9UAW2gZt2WkKjvi8KoMCui00pRlL6wG32uKDKxzZHapFNYASzIYkEdH9bJz6nnqUG3uBB8kiK1asmgiBgI2cqWpc9UAW2gZt2WkKjvi8KoMCui00pRlL6wG32uKDAz1rML9AAAAAG8AAAAiBgMLcbOxsfLe6+3r1UcjQo77HY0As8OKE4l37yj0/qhIyQyKZPKpAAAAAG8AAAAAAAA=";
+        let psbt = Psbt::from_str(psbt_str).unwrap();
+        assert_eq!(parse_psbt(psbt_str.as_bytes()).unwrap(), psbt);
+
+        let mut encoder = ur::Encoder::new(
+            &bcur::psbt_to_cbor(&psbt),
+            bcur::MAX_FRAGMENT_LEN,
+            "crypto-psbt",
+        )
+        .unwrap();
+        let parts: Vec<String> = (0..encoder.fragment_count())
+            .map(|_| encoder.next_part().unwrap().to_uppercase())
+            .collect();
+        assert_eq!(parse_psbt(parts.join("\n").as_bytes()).unwrap(), psbt);
+        assert!(matches!(
+            parse_psbt(parts[0].as_bytes()),
+            Err(Error::Ur(bcur::Error::Incomplete { .. }))
+        ));
+    }
+
     #[tokio::test]
     async fn test_import_descriptor_from_file() {
         let (sender, mut receiver) = unbounded_channel();
@@ -773,6 +773,7 @@ pub fn create_app_with_remote_backend(
                 blockheight: wallet.tip_height.unwrap_or(0),
                 // We ignore last poll fields for remote backend.
                 last_poll_timestamp: None,
+                spending_policy: None,
                 last_tick: Instant::now(),
             },
             fiat_price: None,
@@ -789,6 +790,8 @@ pub fn create_app_with_remote_backend(
                 .with_provider_keys(provider_keys)
                 .with_hardware_wallets(hws)
                 .with_fiat_price_setting(wallet_settings.fiat_price)
+                .with_contacts(wallet_settings.contacts)
+                .with_heir_package(wallet_settings.heir_package)
                 .load_hotsigners(&coincube_dir, network)
                 .expect("Datadir should be conform"),
         ),
@@ -0,0 +1,282 @@
+//! Heir packages: recovery transactions prepared while the owner is still around, signed by the
+//! keys available at that time and handed over to an heir in an encrypted wallet backup, along
+//! with the descriptor, instructions and the schedule at which they become valid.
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+use coincube_core::{
+    descriptors::CoincubeDescriptor,
+    miniscript::bitcoin::{bip32::Fingerprint, Network, OutPoint, Psbt, Txid},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    backup::{self, Backup, ChainTip},
+    daemon::model::Coin,
+};
+
+/// Key of the heir package data in the proprietary metadata of the backup account.
+pub const PROPRIETARY_KEY: &str = "heir_package";
+
+/// When a recovery transaction of the package becomes valid.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExpiryEntry {
+    pub txid: Txid,
+    /// Timelock of the recovery path, in blocks.
+    pub timelock: u16,
+    /// Block height from which the transaction can be mined, once the timelock of all its coins
+    /// expired.
+    pub valid_from: i32,
+}
+
+/// What the wallet settings keep of the last exported heir package, to tell when it goes stale.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HeirPackageRecord {
+    /// Timestamp of the export.
+    pub created_at: u64,
+    /// The coins spent by the recovery transactions of the package.
+    pub outpoints: Vec<OutPoint>,
+    pub schedule: Vec<ExpiryEntry>,
+}
+
+impl HeirPackageRecord {
+    /// The coins of the package that were spent, or are being spent, since it was exported. Any
+    /// of them invalidates the recovery transactions of the package.
+    pub fn moved_coins(&self, coins: &[Coin]) -> Vec<OutPoint> {
+        let unspent: HashSet<_> = coins
+            .iter()
+            .filter(|c| c.spend_info.is_none())
+            .map(|c| c.outpoint)
+            .collect();
+        self.outpoints
+            .iter()
+            .filter(|op| !unspent.contains(op))
+            .copied()
+            .collect()
+    }
+
+    /// The confirmed unspent coins of the wallet the package does not recover.
+    pub fn uncovered_coins(&self, coins: &[Coin]) -> Vec<OutPoint> {
+        coins
+            .iter()
+            .filter(|c| {
+                c.spend_info.is_none()
+                    && c.block_height.is_some()
+                    && !self.outpoints.contains(&c.outpoint)
+            })
+            .map(|c| c.outpoint)
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HeirPackage {
+    pub descriptor: CoincubeDescriptor,
+    pub network: Network,
+    /// Aliases of the keys of the descriptor, to help the heir tell them apart.
+    pub keys: BTreeMap<Fingerprint, backup::Key>,
+    pub instructions: String,
+    /// The recovery transactions, along with the timelock of their recovery path.
+    pub psbts: Vec<(u16, Psbt)>,
+    /// The coins spent by the recovery transactions.
+    pub coins: Vec<Coin>,
+    pub tip_height: i32,
+    /// Timestamp from which the heir wallet must scan the block chain.
+    pub wallet_timestamp: u32,
+    pub created_at: u64,
+}
+
+impl HeirPackage {
+    pub fn schedule(&self) -> Vec<ExpiryEntry> {
+        self.psbts
+            .iter()
+            .map(|(timelock, psbt)| {
+                let last_confirmation = psbt
+                    .unsigned_tx
+                    .input
+                    .iter()
+                    .filter_map(|txin| {
+                        self.coins
+                            .iter()
+                            .find(|c| c.outpoint == txin.previous_output)
+                            .and_then(|c| c.block_height)
+                    })
+                    .max()
+                    .unwrap_or(self.tip_height);
+                ExpiryEntry {
+                    txid: psbt.unsigned_tx.compute_txid(),
+                    timelock: *timelock,
+                    valid_from: last_confirmation + i32::from(*timelock),
+                }
+            })
+            .collect()
+    }
+
+    pub fn record(&self) -> HeirPackageRecord {
+        let outpoints: BTreeSet<_> = self
+            .psbts
+            .iter()
+            .flat_map(|(_, psbt)| psbt.unsigned_tx.input.iter().map(|i| i.previous_output))
+            .collect();
+        HeirPackageRecord {
+            created_at: self.created_at,
+            outpoints: outpoints.into_iter().collect(),
+            schedule: self.schedule(),
+        }
+    }
+
+    /// The package as a wallet backup, which the heir can restore a wallet from. The recovery
+    /// transactions are then imported along with the wallet.
+    pub fn backup(&self) -> Backup {
+        let mut backup = Backup::from_descriptor(self.descriptor.clone(), self.network);
+        backup.date = Some(self.created_at);
+        let account = backup.accounts.first_mut().expect("single account");
+        account.timestamp = Some(self.wallet_timestamp as u64);
+        account.keys = self.keys.clone();
+        account.psbts = self
+            .psbts
+            .iter()
+            .map(|(_, psbt)| psbt.to_string())
+            .collect();
+        account.coins = self
+            .coins
+            .iter()
+            .map(|c| (c.outpoint.to_string(), backup::Coin::from(c.clone())))
+            .collect();
+        account.chain_tip = Some(ChainTip {
+            block_height: self.tip_height,
+            block_hash: None,
+        });
+        account.proprietary.insert(
+            PROPRIETARY_KEY.to_string(),
+            serde_json::json!({
+                "instructions": self.instructions,
+                "schedule": self.schedule(),
+            }),
+        );
+        backup
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use coincube_core::miniscript::bitcoin::{
+        self, absolute, transaction, Amount, Sequence, Transaction, TxIn,
+    };
+    use coincubed::commands::LCSpendInfo;
+    use std::str::FromStr;
+
+    const DESCRIPTOR: &str = "wsh(or_d(pk([8a550171/48'/1'/0'/2']tpubDFnCs5ZaCqopaNhgLCiXAwbkaBdcnuMt1VFoPsRpUrpidyvzG67MYjkfxw6HnTBhHqeU3xw2ioNBVcWY3jXwGhSyppEQvtn38GsL7RH1eef/<0;1>/*),and_v(v:pkh([8a550171/48'/1'/0'/2']tpubDFnCs5ZaCqopaNhgLCiXAwbkaBdcnuMt1VFoPsRpUrpidyvzG67MYjkfxw6HnTBhHqeU3xw2ioNBVcWY3jXwGhSyppEQvtn38GsL7RH1eef/<2;3>/*),older(52596))))#jz5sm0xn";
+
+    fn coin(vout: u32, block_height: Option<i32>, spent: bool) -> Coin {
+        let txid =
+            Txid::from_str("f7bd1b2a995b689d326e51eb742eb1088c4a8f110d9cb56128fd553acc9f88e5")
+                .unwrap();
+        Coin {
+            outpoint: OutPoint::new(txid, vout),
+            amount: Amount::from_sat(100_000),
+            address: bitcoin::Address::from_str("bc1qvrl2849aggm6qry9ea7xqp2kk39j8vaa8r3cwg")
+                .unwrap()
+                .assume_checked(),
+            derivation_index: bitcoin::bip32::ChildNumber::Normal { index: vout },
+            block_height,
+            is_immature: false,
+            is_change: false,
+            is_from_self: false,
+            spend_info: spent.then_some(LCSpendInfo { txid, height: None }),
+        }
+    }
+
+    fn recovery_psbt(coins: &[&Coin], timelock: u16) -> Psbt {
+        Psbt::from_unsigned_tx(Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: coins
+                .iter()
+                .map(|c| TxIn {
+                    previous_output: c.outpoint,
+                    sequence: Sequence::from_height(timelock),
+                    ..Default::default()
+                })
+                .collect(),
+            output: Vec::new(),
+        })
+        .unwrap()
+    }
+
+    fn package(coins: Vec<Coin>, psbts: Vec<(u16, Psbt)>) -> HeirPackage {
+        HeirPackage {
+            descriptor: CoincubeDescriptor::from_str(DESCRIPTOR).unwrap(),
+            network: Network::Testnet,
+            keys: BTreeMap::new(),
+            instructions: "Call the notary first.".to_string(),
+            psbts,
+            coins,
+            tip_height: 800_010,
+            wallet_timestamp: 1_700_000_000,
+            created_at: 1_700_000_100,
+        }
+    }
+
+    #[test]
+    fn heir_package_schedule() {
+        let (a, b) = (coin(0, Some(800_000), false), coin(1, Some(800_005), false));
+        let psbt = recovery_psbt(&[&a, &b], 10);
+        let txid = psbt.unsigned_tx.compute_txid();
+        let package = package(vec![a.clone(), b.clone()], vec![(10, psbt)]);
+
+        // The transaction is valid once the timelock of the last confirmed coin expired.
+        let schedule = package.schedule();
+        assert_eq!(
+            schedule,
+            vec![ExpiryEntry {
+                txid,
+                timelock: 10,
+                valid_from: 800_015,
+            }]
+        );
+
+        let record = package.record();
+        assert_eq!(record.outpoints, vec![a.outpoint, b.outpoint]);
+        assert_eq!(record.schedule, schedule);
+        assert_eq!(record.created_at, 1_700_000_100);
+
+        let backup = package.backup();
+        assert_eq!(backup.network, Network::Testnet);
+        assert_eq!(backup.date, Some(1_700_000_100));
+        let account = &backup.accounts[0];
+        assert_eq!(account.timestamp, Some(1_700_000_000));
+        assert_eq!(account.psbts.len(), 1);
+        assert_eq!(account.coins.len(), 2);
+        assert_eq!(
+            account.chain_tip.as_ref().map(|t| t.block_height),
+            Some(800_010)
+        );
+        let data = &account.proprietary[PROPRIETARY_KEY];
+        assert_eq!(data["instructions"], "Call the notary first.");
+        assert_eq!(data["schedule"][0]["valid_from"], 800_015);
+    }
+
+    #[test]
+    fn heir_package_staleness() {
+        let (a, b) = (coin(0, Some(800_000), false), coin(1, Some(800_005), false));
+        let record = package(
+            vec![a.clone(), b.clone()],
+            vec![(10, recovery_psbt(&[&a, &b], 10))],
+        )
+        .record();
+
+        // Nothing moved.
+        assert!(record.moved_coins(&[a.clone(), b.clone()]).is_empty());
+        assert!(record.uncovered_coins(&[a.clone(), b.clone()]).is_empty());
+
+        // A coin is being spent, another one was spent and is no longer listed. A new coin is
+        // not covered by the package, unless it's unconfirmed.
+        let spending = coin(0, Some(800_000), true);
+        let (new, unconfirmed) = (coin(2, Some(800_020), false), coin(3, None, false));
+        let coins = [spending, new.clone(), unconfirmed];
+        assert_eq!(record.moved_coins(&coins), vec![a.outpoint, b.outpoint]);
+        assert_eq!(record.uncovered_coins(&coins), vec![new.outpoint]);
+    }
+}
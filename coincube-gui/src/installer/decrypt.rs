@@ -149,7 +149,8 @@ pub fn decrypt_descriptor_with_pk(bytes: &[u8], pk: secp256k1::PublicKey) -> Opt
                 };
                 Some(Decrypt::Backup(Backup::from_descriptor(descr, network)))
             }
-            Decrypted::WalletBackup(backup_bytes) => {
+            // Heir packages are exported as a raw wallet backup.
+            Decrypted::WalletBackup(backup_bytes) | Decrypted::Raw(backup_bytes) => {
                 let backup_str = String::from_utf8(backup_bytes.clone()).ok()?;
                 let backup: Backup = serde_json::from_str(&backup_str).ok()?;
                 if backup.accounts.len() != 1 {
@@ -27,7 +27,9 @@ use crate::{
     installer::{decrypt::Decrypt, descriptor::PathKind},
     node::{
         bitcoind::{Bitcoind, ConfigField, RpcAuthType},
-        electrum, NodeType,
+        electrum,
+        release::ReleaseChecksums,
+        NodeType,
     },
     services::{
         self,
@@ -164,6 +166,7 @@ pub enum InternalBitcoindMsg {
     DefineConfig,
     Download,
     DownloadProgressed(Result<Progress, DownloadError>),
+    ChecksumsFetched(Result<ReleaseChecksums, String>),
     Install,
     Start,
 }
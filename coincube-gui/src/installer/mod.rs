@@ -464,6 +464,8 @@ pub async fn install_local_wallet(
         remote_backend_auth: None,
         start_internal_bitcoind: Some(ctx.internal_bitcoind.is_some()),
         fiat_price: None,
+        contacts: Vec::new(),
+        heir_package: None,
     };
 
     let cfg: coincubed::config::Config = extract_daemon_config(&ctx, &wallet_settings)?;
@@ -684,6 +686,8 @@ pub async fn create_remote_wallet(
         )),
         start_internal_bitcoind: None,
         fiat_price: None,
+        contacts: Vec::new(),
+        heir_package: None,
     };
     update_settings_file(&network_datadir, |mut settings| {
         settings.wallets.push(wallet_settings.clone());
@@ -764,6 +768,8 @@ pub async fn import_remote_wallet(
         )),
         start_internal_bitcoind: None,
         fiat_price: None,
+        contacts: Vec::new(),
+        heir_package: None,
     };
     update_settings_file(&network_datadir, |mut settings| {
         settings.wallets.push(wallet_settings.clone());
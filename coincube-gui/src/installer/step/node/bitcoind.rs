@@ -1,18 +1,11 @@
-#[cfg(target_os = "windows")]
-use std::io::{self, Cursor};
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener};
 use std::path::PathBuf;
 use std::str::FromStr;
 
-use bitcoin_hashes::sha256;
 use coincube_core::miniscript::bitcoin::Network;
 use coincubed::config::{BitcoinBackend, BitcoindConfig, BitcoindRpcAuth};
-#[cfg(any(target_os = "macos", target_os = "linux"))]
-use flate2::read::GzDecoder;
 use iced::{Subscription, Task};
-#[cfg(any(target_os = "macos", target_os = "linux"))]
-use tar::Archive;
-use tracing::info;
+use tracing::{info, warn};
 
 use jsonrpc::{client::Client, simple_http::SimpleHttpTransport};
 
@@ -29,11 +22,13 @@ use crate::{
         view, Error,
     },
     node::bitcoind::{
-        self, bitcoind_network_dir, internal_bitcoind_cookie_path, internal_bitcoind_datadir,
-        internal_bitcoind_directory, Bitcoind, ConfigField, InternalBitcoindConfig,
-        InternalBitcoindConfigError, InternalBitcoindNetworkConfig, RpcAuthType, RpcAuthValues,
-        StartInternalBitcoindError, VERSION,
+        self, bitcoind_network_dir, install_bitcoind, internal_bitcoind_cookie_path,
+        internal_bitcoind_datadir, internal_bitcoind_directory, Bitcoind, ConfigField,
+        InstallBitcoindError, InternalBitcoindConfig, InternalBitcoindConfigError,
+        InternalBitcoindNetworkConfig, RpcAuthType, RpcAuthValues, StartInternalBitcoindError,
+        PRUNE_DEFAULT, VERSION,
     },
+    node::release::{self, ReleaseChecksums},
 };
 
 // The approach for tracking download progress is taken from
@@ -89,19 +84,18 @@ impl Download {
 
     pub fn subscription(&self) -> Subscription<Message> {
         match self.state {
-            DownloadState::Downloading { .. } => download::file(self.id, bitcoind::download_url())
-                .map(|(_, progress)| {
+            DownloadState::Downloading { .. } => {
+                download::file(self.id, bitcoind::download_url(VERSION)).map(|(_, progress)| {
                     Message::InternalBitcoind(message::InternalBitcoindMsg::DownloadProgressed(
                         progress,
                     ))
-                }),
+                })
+            }
             _ => Subscription::none(),
         }
     }
 }
 
-/// Default prune value used by internal bitcoind.
-pub const PRUNE_DEFAULT: u32 = 15_000;
 /// Default ports used by bitcoind across all networks.
 pub const BITCOIND_DEFAULT_PORTS: [u16; 10] = [
     8332, 8333, 18332, 18333, 18443, 18444, 48332, 48333, 38332, 38333,
@@ -114,98 +108,6 @@ pub enum InstallState {
     Errored(InstallBitcoindError),
 }
 
-/// Possible errors when installing bitcoind.
-#[derive(PartialEq, Eq, Debug, Clone)]
-pub enum InstallBitcoindError {
-    HashMismatch,
-    UnpackingError(String),
-}
-
-impl std::fmt::Display for InstallBitcoindError {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        match self {
-            Self::HashMismatch => {
-                write!(f, "Hashes do not match.")
-            }
-            Self::UnpackingError(e) => {
-                write!(f, "Error unpacking: '{}'.", e)
-            }
-        }
-    }
-}
-
-// The functions below for unpacking the bitcoin download and verifying its hash are based on
-// https://github.com/RCasatta/bitcoind/blob/bada7ebb7197b89fd67e607f815ce1e43e76da7f/build.rs#L73.
-
-/// Unpack the downloaded bytes in the specified directory.
-fn unpack_bitcoind(install_dir: &PathBuf, bytes: &[u8]) -> Result<(), InstallBitcoindError> {
-    #[cfg(any(target_os = "macos", target_os = "linux"))]
-    {
-        let d = GzDecoder::new(bytes);
-
-        let mut archive = Archive::new(d);
-        for mut entry in archive
-            .entries()
-            .map_err(|e| InstallBitcoindError::UnpackingError(e.to_string()))?
-            .flatten()
-        {
-            if let Ok(file) = entry.path() {
-                if file.ends_with("bitcoind") {
-                    if let Err(e) = entry.unpack_in(install_dir) {
-                        return Err(InstallBitcoindError::UnpackingError(e.to_string()));
-                    }
-                }
-            }
-        }
-    }
-    #[cfg(target_os = "windows")]
-    {
-        let cursor = Cursor::new(bytes);
-        let mut archive = zip::ZipArchive::new(cursor)
-            .map_err(|e| InstallBitcoindError::UnpackingError(e.to_string()))?;
-        for i in 0..zip::ZipArchive::len(&archive) {
-            let mut file = archive
-                .by_index(i)
-                .map_err(|e| InstallBitcoindError::UnpackingError(e.to_string()))?;
-            let outpath = match file.enclosed_name() {
-                Some(path) => path.to_owned(),
-                None => continue,
-            };
-            if outpath.file_name().map(|s| s.to_str()) == Some(Some("bitcoind.exe")) {
-                let mut exe_path = PathBuf::from(install_dir);
-                for d in outpath.iter() {
-                    exe_path.push(d);
-                }
-                let parent = exe_path.parent().expect("bitcoind.exe should have parent.");
-                std::fs::create_dir_all(parent)
-                    .map_err(|e| InstallBitcoindError::UnpackingError(e.to_string()))?;
-                let mut outfile = std::fs::File::create(&exe_path)
-                    .map_err(|e| InstallBitcoindError::UnpackingError(e.to_string()))?;
-                io::copy(&mut file, &mut outfile)
-                    .map_err(|e| InstallBitcoindError::UnpackingError(e.to_string()))?;
-                break;
-            }
-        }
-    }
-    Ok(())
-}
-
-/// Verify the download hash against the expected value.
-fn verify_hash(bytes: &[u8]) -> bool {
-    let bytes_hash = sha256::Hash::hash(bytes);
-    info!("Download hash: '{}'.", bytes_hash);
-    let expected_hash = sha256::Hash::from_str(bitcoind::SHA256SUM).expect("This cannot fail.");
-    expected_hash == bytes_hash
-}
-
-/// Install bitcoind by verifying the download hash and unpacking in the specified directory.
-fn install_bitcoind(install_dir: &PathBuf, bytes: &[u8]) -> Result<(), InstallBitcoindError> {
-    if !verify_hash(bytes) {
-        return Err(InstallBitcoindError::HashMismatch);
-    };
-    unpack_bitcoind(install_dir, bytes)
-}
-
 /// RPC address for internal bitcoind.
 fn internal_bitcoind_address(rpc_port: u16) -> SocketAddr {
     SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), rpc_port)
@@ -500,6 +402,7 @@ pub struct InternalBitcoindStep {
     internal_bitcoind_config: Option<InternalBitcoindConfig>,
     error: Option<String>,
     exe_download: Option<Download>,
+    checksums: Option<Result<ReleaseChecksums, String>>,
     install_state: Option<InstallState>,
     internal_bitcoind: Option<Bitcoind>,
 }
@@ -522,6 +425,7 @@ impl InternalBitcoindStep {
             internal_bitcoind_config: None,
             error: None,
             exe_download: None,
+            checksums: None,
             install_state: None,
             internal_bitcoind: None,
         }
@@ -625,15 +529,14 @@ impl Step for InternalBitcoindStep {
                     };
                     // Use existing network conf if it exists as it may have rpc_auth field set.
                     // This ensures an existing wallet using username/password authentication will continue to work.
-                    let network_conf =
-                        network_conf
-                            .cloned()
-                            .unwrap_or(InternalBitcoindNetworkConfig {
-                                rpc_port,
-                                p2p_port,
-                                prune: PRUNE_DEFAULT,
-                                rpc_auth: None, // can be omitted for new bitcoin.conf entries
-                            });
+                    let network_conf = network_conf
+                        .cloned()
+                        // rpc_auth can be omitted for new bitcoin.conf entries
+                        .unwrap_or(InternalBitcoindNetworkConfig::new(
+                            rpc_port,
+                            p2p_port,
+                            PRUNE_DEFAULT,
+                        ));
                     conf.networks.insert(self.network, network_conf);
                     if let Err(e) = conf.to_file(&bitcoind::internal_bitcoind_config_path(
                         &self.bitcoind_datadir,
@@ -653,9 +556,31 @@ impl Step for InternalBitcoindStep {
                         if let DownloadState::Idle = download.state {
                             info!("Downloading bitcoind version {}...", &bitcoind::VERSION);
                             download.start();
+                            self.checksums = None;
+                            return Task::perform(
+                                bitcoind::fetch_release_checksums(VERSION),
+                                |res| {
+                                    Message::InternalBitcoind(
+                                        message::InternalBitcoindMsg::ChecksumsFetched(res),
+                                    )
+                                },
+                            );
                         }
                     }
                 }
+                message::InternalBitcoindMsg::ChecksumsFetched(res) => {
+                    if let Err(e) = &res {
+                        warn!("Failed to fetch bitcoind release checksums: {}", e);
+                    }
+                    self.checksums = Some(res);
+                    if let Some(DownloadState::Finished(_)) =
+                        self.exe_download.as_ref().map(|d| &d.state)
+                    {
+                        return Task::perform(async {}, |_| {
+                            Message::InternalBitcoind(message::InternalBitcoindMsg::Install)
+                        });
+                    }
+                }
                 message::InternalBitcoindMsg::DownloadProgressed(progress) => {
                     if let Some(download) = self.exe_download.as_mut() {
                         download.progress(progress);
@@ -670,11 +595,25 @@ impl Step for InternalBitcoindStep {
                 message::InternalBitcoindMsg::Install => {
                     if let Some(download) = &self.exe_download {
                         if let DownloadState::Finished(bytes) = &download.state {
+                            // Wait for the release checksums to be fetched.
+                            let checksums = match &self.checksums {
+                                None => return Task::none(),
+                                Some(Ok(checksums)) => checksums,
+                                Some(Err(e)) => {
+                                    let e = InstallBitcoindError::ChecksumsUnavailable(e.clone());
+                                    self.install_state = Some(InstallState::Errored(e.clone()));
+                                    self.error = Some(e.to_string());
+                                    return Task::none();
+                                }
+                            };
                             info!("Installing bitcoind...");
                             self.install_state = Some(InstallState::InProgress);
                             match install_bitcoind(
                                 &internal_bitcoind_directory(&self.coincube_datadir),
+                                VERSION,
+                                checksums,
                                 bytes,
+                                release::min_valid_signatures(&self.coincube_datadir),
                             ) {
                                 Ok(_) => {
                                     info!("Installation of bitcoind complete.");
@@ -804,14 +743,3 @@ impl Step for InternalBitcoindStep {
         ctx.bitcoind_is_external || ctx.remote_backend.is_some()
     }
 }
-
-#[cfg(test)]
-mod tests {
-    use super::verify_hash;
-
-    #[test]
-    fn hash() {
-        let bytes = "this is not bitcoin".as_bytes().to_vec();
-        assert!(!verify_hash(&bytes));
-    }
-}
@@ -1,11 +1,16 @@
+pub mod accounting;
 pub mod app;
 pub mod backup;
+pub mod bcur;
+pub mod coldcard;
 pub mod daemon;
 pub mod delete;
 pub mod dir;
 pub mod download;
+pub mod drill;
 pub mod export;
 pub mod gui;
+pub mod heir;
 pub mod help;
 pub mod hw;
 pub mod installer;
@@ -474,6 +474,7 @@ pub async fn load_application(
             coins,
             sync_progress: info.sync,
             last_poll_timestamp: info.last_poll_timestamp,
+            spending_policy: info.spending_policy,
             ..Default::default()
         },
         fiat_price: None,
@@ -15,10 +15,18 @@ use std::time;
 
 use tracing::{info, warn};
 
+#[cfg(target_os = "windows")]
+use std::io::{self, Cursor};
 #[cfg(target_os = "windows")]
 use std::os::windows::process::CommandExt;
 
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+use flate2::read::GzDecoder;
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+use tar::Archive;
+
 use crate::dir::{BitcoindDirectory, CoincubeDirectory};
+use crate::node::release::{self, ReleaseChecksums, VerificationError};
 use crate::utils::now_fallible;
 
 #[cfg(target_os = "windows")]
@@ -34,43 +42,66 @@ pub const VERSIONS: [&str; 7] = ["29.0", "28.0", "27.1", "26.1", "26.0", "25.1",
 pub const VERSION: &str = VERSIONS[0];
 
 #[cfg(all(target_os = "macos", target_arch = "x86_64"))]
-pub const SHA256SUM: &str = "5bb824fc86a15318d6a83a1b821ff4cd4b3d3d0e1ec3d162b805ccf7cae6fca8";
+pub fn download_filename(version: &str) -> String {
+    format!("bitcoin-{}-x86_64-apple-darwin.tar.gz", version)
+}
 
 #[cfg(all(target_os = "macos", target_arch = "aarch64"))]
-pub const SHA256SUM: &str = "34431c582a0399dd42e1276d87d25306cbdde0217f6744bd55a2945986645dda";
+pub fn download_filename(version: &str) -> String {
+    format!("bitcoin-{}-arm64-apple-darwin.tar.gz", version)
+}
 
 #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
-pub const SHA256SUM: &str = "a681e4f6ce524c338a105f214613605bac6c33d58c31dc5135bbc02bc458bb6c";
+pub fn download_filename(version: &str) -> String {
+    format!("bitcoin-{}-x86_64-linux-gnu.tar.gz", version)
+}
 
 #[cfg(all(target_os = "windows", target_arch = "x86_64"))]
-pub const SHA256SUM: &str = "4c1780532031129fcacfc0e393c8430b3cea414c9f8c5e0c0c87ebe59a5ada1b";
+pub fn download_filename(version: &str) -> String {
+    format!("bitcoin-{}-win64.zip", version)
+}
 
-#[cfg(all(target_os = "macos", target_arch = "x86_64"))]
-pub fn download_filename() -> String {
-    format!("bitcoin-{}-x86_64-apple-darwin.tar.gz", &VERSION)
+fn release_url(version: &str) -> String {
+    format!("https://bitcoincore.org/bin/bitcoin-core-{}", version)
 }
 
-#[cfg(all(target_os = "macos", target_arch = "aarch64"))]
-pub fn download_filename() -> String {
-    format!("bitcoin-{}-arm64-apple-darwin.tar.gz", &VERSION)
+pub fn download_url(version: &str) -> String {
+    format!("{}/{}", release_url(version), download_filename(version))
 }
 
-#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
-fn download_filename() -> String {
-    format!("bitcoin-{}-x86_64-linux-gnu.tar.gz", &VERSION)
+/// URL of the `SHA256SUMS` file of the given release.
+pub fn sha256sums_url(version: &str) -> String {
+    format!("{}/SHA256SUMS", release_url(version))
 }
 
-#[cfg(all(target_os = "windows", target_arch = "x86_64"))]
-fn download_filename() -> String {
-    format!("bitcoin-{}-win64.zip", &VERSION)
+/// URL of the detached signatures of the `SHA256SUMS` file of the given release.
+pub fn sha256sums_signatures_url(version: &str) -> String {
+    format!("{}/SHA256SUMS.asc", release_url(version))
 }
 
-pub fn download_url() -> String {
-    format!(
-        "https://bitcoincore.org/bin/bitcoin-core-{}/{}",
-        &VERSION,
-        download_filename()
-    )
+/// Fetch the `SHA256SUMS` file of the given release and its signatures.
+pub async fn fetch_release_checksums(version: &str) -> Result<ReleaseChecksums, String> {
+    let get = |url: String| async move {
+        reqwest::get(&url)
+            .await
+            .and_then(|res| res.error_for_status())
+            .map_err(|e| format!("Failed to fetch '{}': {}", url, e))
+    };
+    let sha256sums = get(sha256sums_url(version))
+        .await?
+        .bytes()
+        .await
+        .map_err(|e| e.to_string())?
+        .to_vec();
+    let signatures = get(sha256sums_signatures_url(version))
+        .await?
+        .text()
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(ReleaseChecksums {
+        sha256sums,
+        signatures,
+    })
 }
 
 pub fn internal_bitcoind_directory(coincube_datadir: &CoincubeDirectory) -> PathBuf {
@@ -219,6 +250,17 @@ impl std::str::FromStr for RpcAuth {
     }
 }
 
+/// Default prune value used by internal bitcoind.
+pub const PRUNE_DEFAULT: u32 = 15_000;
+/// Minimum prune value accepted by bitcoind, in MiB. A value of 0 disables pruning.
+pub const PRUNE_MIN: u32 = 550;
+/// Bounds of the database cache size accepted by bitcoind, in MiB.
+pub const DBCACHE_MIN: u32 = 4;
+pub const DBCACHE_MAX: u32 = 16_384;
+
+/// Maximum number of entries in the section for a single network.
+const MAX_NETWORK_SECTION_ELEMENTS: usize = 7;
+
 /// Represents section for a single network in `bitcoin.conf` file.
 #[derive(PartialEq, Eq, Debug, Clone, Serialize, Deserialize)]
 pub struct InternalBitcoindNetworkConfig {
@@ -226,6 +268,48 @@ pub struct InternalBitcoindNetworkConfig {
     pub p2p_port: u16,
     pub prune: u32,
     pub rpc_auth: Option<RpcAuth>,
+    /// Database cache size in MiB. Bitcoind's default is used if not set.
+    #[serde(default)]
+    pub dbcache: Option<u32>,
+    /// Whether to accept incoming connections. Bitcoind's default is used if not set.
+    #[serde(default)]
+    pub listen: Option<bool>,
+    /// Maximum number of peer connections. Bitcoind's default is used if not set.
+    #[serde(default)]
+    pub max_connections: Option<u32>,
+}
+
+impl InternalBitcoindNetworkConfig {
+    pub fn new(rpc_port: u16, p2p_port: u16, prune: u32) -> Self {
+        Self {
+            rpc_port,
+            p2p_port,
+            prune,
+            rpc_auth: None,
+            dbcache: None,
+            listen: None,
+            max_connections: None,
+        }
+    }
+
+    /// Check the values are accepted by bitcoind.
+    pub fn validate(&self) -> Result<(), InternalBitcoindConfigError> {
+        if self.prune != 0 && self.prune < PRUNE_MIN {
+            return Err(InternalBitcoindConfigError::InvalidValue(format!(
+                "prune must be 0 (disabled) or at least {} MiB",
+                PRUNE_MIN
+            )));
+        }
+        if let Some(dbcache) = self.dbcache {
+            if !(DBCACHE_MIN..=DBCACHE_MAX).contains(&dbcache) {
+                return Err(InternalBitcoindConfigError::InvalidValue(format!(
+                    "dbcache must be between {} and {} MiB",
+                    DBCACHE_MIN, DBCACHE_MAX
+                )));
+            }
+        }
+        Ok(())
+    }
 }
 
 /// Represents the `bitcoin.conf` file to be used by internal bitcoind.
@@ -240,6 +324,7 @@ pub enum InternalBitcoindConfigError {
     CouldNotParseValue(String),
     UnexpectedSection(String),
     TooManyElements(String),
+    InvalidValue(String),
     FileNotFound,
     ReadingFile(String),
     WritingFile(String),
@@ -255,6 +340,7 @@ impl std::fmt::Display for InternalBitcoindConfigError {
             Self::TooManyElements(section) => {
                 write!(f, "Section in file contains too many elements: {}", section)
             }
+            Self::InvalidValue(e) => write!(f, "Invalid value: {}", e),
             Self::FileNotFound => write!(f, "File not found"),
             Self::ReadingFile(e) => write!(f, "Error while reading file: {}", e),
             Self::WritingFile(e) => write!(f, "Error while writing file: {}", e),
@@ -282,7 +368,7 @@ impl InternalBitcoindConfig {
             if let Some(sec) = maybe_sec {
                 let network = Network::from_core_arg(sec)
                     .map_err(|e| InternalBitcoindConfigError::UnexpectedSection(e.to_string()))?;
-                if prop.len() > 4 {
+                if prop.len() > MAX_NETWORK_SECTION_ELEMENTS {
                     return Err(InternalBitcoindConfigError::TooManyElements(
                         sec.to_string(),
                     ));
@@ -310,6 +396,33 @@ impl InternalBitcoindConfig {
                         })
                     })
                     .transpose()?;
+                let dbcache = prop
+                    .get("dbcache")
+                    .map(|v| {
+                        v.parse::<u32>().map_err(|e| {
+                            InternalBitcoindConfigError::CouldNotParseValue(e.to_string())
+                        })
+                    })
+                    .transpose()?;
+                let listen = prop
+                    .get("listen")
+                    .map(|v| match v {
+                        "0" => Ok(false),
+                        "1" => Ok(true),
+                        _ => Err(InternalBitcoindConfigError::CouldNotParseValue(format!(
+                            "listen must be 0 or 1, got '{}'",
+                            v
+                        ))),
+                    })
+                    .transpose()?;
+                let max_connections = prop
+                    .get("maxconnections")
+                    .map(|v| {
+                        v.parse::<u32>().map_err(|e| {
+                            InternalBitcoindConfigError::CouldNotParseValue(e.to_string())
+                        })
+                    })
+                    .transpose()?;
 
                 networks.insert(
                     network,
@@ -318,6 +431,9 @@ impl InternalBitcoindConfig {
                         p2p_port,
                         prune,
                         rpc_auth,
+                        dbcache,
+                        listen,
+                        max_connections,
                     },
                 );
             } else if !prop.is_empty() {
@@ -353,6 +469,21 @@ impl InternalBitcoindConfig {
                     .with_section(Some(network.to_core_arg()))
                     .set("rpcauth", rpc_auth.to_string());
             }
+            if let Some(dbcache) = network_conf.dbcache {
+                conf_ini
+                    .with_section(Some(network.to_core_arg()))
+                    .set("dbcache", dbcache.to_string());
+            }
+            if let Some(listen) = network_conf.listen {
+                conf_ini
+                    .with_section(Some(network.to_core_arg()))
+                    .set("listen", if listen { "1" } else { "0" });
+            }
+            if let Some(max_connections) = network_conf.max_connections {
+                conf_ini
+                    .with_section(Some(network.to_core_arg()))
+                    .set("maxconnections", max_connections.to_string());
+            }
         }
         conf_ini
     }
@@ -372,6 +503,113 @@ impl InternalBitcoindConfig {
     }
 }
 
+/// Possible errors when installing bitcoind.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum InstallBitcoindError {
+    ChecksumsUnavailable(String),
+    Verification(VerificationError),
+    UnpackingError(String),
+}
+
+impl std::fmt::Display for InstallBitcoindError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::ChecksumsUnavailable(e) => {
+                write!(f, "Release checksums unavailable: {}", e)
+            }
+            Self::Verification(e) => {
+                write!(f, "Verification failed: {}", e)
+            }
+            Self::UnpackingError(e) => {
+                write!(f, "Error unpacking: '{}'.", e)
+            }
+        }
+    }
+}
+
+// The function below for unpacking the bitcoin download is based on
+// https://github.com/RCasatta/bitcoind/blob/bada7ebb7197b89fd67e607f815ce1e43e76da7f/build.rs#L73.
+
+/// Unpack the downloaded bytes in the specified directory.
+fn unpack_bitcoind(install_dir: &PathBuf, bytes: &[u8]) -> Result<(), InstallBitcoindError> {
+    #[cfg(any(target_os = "macos", target_os = "linux"))]
+    {
+        let d = GzDecoder::new(bytes);
+
+        let mut archive = Archive::new(d);
+        for mut entry in archive
+            .entries()
+            .map_err(|e| InstallBitcoindError::UnpackingError(e.to_string()))?
+            .flatten()
+        {
+            if let Ok(file) = entry.path() {
+                if file.ends_with("bitcoind") {
+                    if let Err(e) = entry.unpack_in(install_dir) {
+                        return Err(InstallBitcoindError::UnpackingError(e.to_string()));
+                    }
+                }
+            }
+        }
+    }
+    #[cfg(target_os = "windows")]
+    {
+        let cursor = Cursor::new(bytes);
+        let mut archive = zip::ZipArchive::new(cursor)
+            .map_err(|e| InstallBitcoindError::UnpackingError(e.to_string()))?;
+        for i in 0..zip::ZipArchive::len(&archive) {
+            let mut file = archive
+                .by_index(i)
+                .map_err(|e| InstallBitcoindError::UnpackingError(e.to_string()))?;
+            let outpath = match file.enclosed_name() {
+                Some(path) => path.to_owned(),
+                None => continue,
+            };
+            if outpath.file_name().map(|s| s.to_str()) == Some(Some("bitcoind.exe")) {
+                let mut exe_path = PathBuf::from(install_dir);
+                for d in outpath.iter() {
+                    exe_path.push(d);
+                }
+                let parent = exe_path.parent().expect("bitcoind.exe should have parent.");
+                std::fs::create_dir_all(parent)
+                    .map_err(|e| InstallBitcoindError::UnpackingError(e.to_string()))?;
+                let mut outfile = std::fs::File::create(&exe_path)
+                    .map_err(|e| InstallBitcoindError::UnpackingError(e.to_string()))?;
+                io::copy(&mut file, &mut outfile)
+                    .map_err(|e| InstallBitcoindError::UnpackingError(e.to_string()))?;
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Verify the download of the given bitcoind version against the signed release checksums,
+/// using the bundled builder keyring and requiring at least `min_signatures` of them to have
+/// signed.
+pub fn verify_download(
+    version: &str,
+    checksums: &ReleaseChecksums,
+    bytes: &[u8],
+    min_signatures: usize,
+) -> Result<(), InstallBitcoindError> {
+    let keyring = release::builder_keyring().map_err(InstallBitcoindError::Verification)?;
+    checksums
+        .verify(&download_filename(version), bytes, &keyring, min_signatures)
+        .map_err(InstallBitcoindError::Verification)
+}
+
+/// Install bitcoind by verifying the download and unpacking in the specified directory.
+pub fn install_bitcoind(
+    install_dir: &PathBuf,
+    version: &str,
+    checksums: &ReleaseChecksums,
+    bytes: &[u8],
+    min_signatures: usize,
+) -> Result<(), InstallBitcoindError> {
+    verify_download(version, checksums, bytes, min_signatures)?;
+    unpack_bitcoind(install_dir, bytes)
+}
+
 /// Possible errors when starting bitcoind.
 #[derive(PartialEq, Eq, Debug, Clone)]
 pub enum StartInternalBitcoindError {
@@ -381,6 +619,7 @@ pub enum StartInternalBitcoindError {
     BitcoinDError(String),
     ExecutableNotFound,
     ProcessExited(std::process::ExitStatus),
+    InUse,
 }
 
 impl std::fmt::Display for StartInternalBitcoindError {
@@ -400,6 +639,7 @@ impl std::fmt::Display for StartInternalBitcoindError {
             Self::ProcessExited(status) => {
                 write!(f, "bitcoind process exited with status '{}'.", status)
             }
+            Self::InUse => write!(f, "bitcoind is in use by another wallet."),
         }
     }
 }
@@ -426,17 +666,8 @@ impl Bitcoind {
         }
         let bitcoind_datadir = internal_bitcoind_datadir(coincube_datadir);
         // Find most recent bitcoind version available.
-        let bitcoind_exe_path = VERSIONS
-            .iter()
-            .filter_map(|v| {
-                let path = internal_bitcoind_exe_path(coincube_datadir, v);
-                if path.exists() {
-                    Some(path)
-                } else {
-                    None
-                }
-            })
-            .next()
+        let bitcoind_exe_path = installed_version(coincube_datadir)
+            .map(|v| internal_bitcoind_exe_path(coincube_datadir, v))
             .ok_or(StartInternalBitcoindError::ExecutableNotFound)?;
         info!(
             "Found bitcoind executable at '{}'.",
@@ -556,6 +787,154 @@ impl Bitcoind {
     }
 }
 
+impl Bitcoind {
+    /// Whether other processes are also using this managed bitcoind.
+    pub fn is_shared(&self) -> bool {
+        self.lock.is_shared()
+    }
+
+    /// Stop the managed bitcoind and wait for its RPC interface to go away.
+    fn stop_and_wait(self) -> Result<(), StartInternalBitcoindError> {
+        let config = self.config.clone();
+        self.stop();
+        for _ in 0..SHUTDOWN_TIMEOUT_TRIES {
+            if coincubed::BitcoinD::new(&config, "internal_bitcoind_stop".to_string()).is_err() {
+                return Ok(());
+            }
+            log::info!("Waiting for bitcoind to stop.");
+            thread::sleep(time::Duration::from_millis(500));
+        }
+        Err(StartInternalBitcoindError::CommandError(
+            "Timed out waiting for bitcoind to stop.".to_string(),
+        ))
+    }
+
+    /// Restart the managed bitcoind, for instance to apply changes to its configuration file.
+    pub fn restart(
+        self,
+        network: Network,
+        coincube_datadir: &CoincubeDirectory,
+    ) -> Result<Self, StartInternalBitcoindError> {
+        if self.is_shared() {
+            return Err(StartInternalBitcoindError::InUse);
+        }
+        let config = self.config.clone();
+        self.stop_and_wait()?;
+        Self::maybe_start(network, config, coincube_datadir)
+    }
+
+    /// Upgrade the managed bitcoind to a newer version and restart it.
+    ///
+    /// The download is verified before the running node is stopped. If the new version
+    /// fails to be installed or to start, it is removed and the previous version is started
+    /// again. The node running after the operation, if any, is always returned.
+    pub fn upgrade(
+        self,
+        network: Network,
+        coincube_datadir: &CoincubeDirectory,
+        upgrade: BitcoindUpgrade,
+    ) -> Result<Self, (UpgradeInternalBitcoindError, Option<Self>)> {
+        if self.is_shared() {
+            return Err((
+                UpgradeInternalBitcoindError::Start(StartInternalBitcoindError::InUse),
+                Some(self),
+            ));
+        }
+        if let Err(e) = verify_download(
+            upgrade.version,
+            &upgrade.checksums,
+            &upgrade.bytes,
+            release::min_valid_signatures(coincube_datadir),
+        ) {
+            return Err((UpgradeInternalBitcoindError::Install(e), Some(self)));
+        }
+        let config = self.config.clone();
+        if let Err(e) = self.stop_and_wait() {
+            return Err((UpgradeInternalBitcoindError::Start(e), None));
+        }
+
+        let version_dir = internal_bitcoind_directory(coincube_datadir)
+            .join(format!("bitcoin-{}", upgrade.version));
+        let error = match unpack_bitcoind(
+            &internal_bitcoind_directory(coincube_datadir),
+            &upgrade.bytes,
+        ) {
+            Err(e) => UpgradeInternalBitcoindError::Install(e),
+            Ok(()) => match Self::maybe_start(network, config.clone(), coincube_datadir) {
+                Ok(bitcoind) => {
+                    info!("Managed bitcoind upgraded to version {}.", upgrade.version);
+                    return Ok(bitcoind);
+                }
+                Err(e) => UpgradeInternalBitcoindError::Start(e),
+            },
+        };
+
+        warn!(
+            "Failed to upgrade managed bitcoind to version {}: {}. Rolling back.",
+            upgrade.version, error
+        );
+        if let Err(e) = std::fs::remove_dir_all(&version_dir) {
+            tracing::error!(
+                "Failed to remove '{}': {}",
+                version_dir.to_string_lossy(),
+                e
+            );
+        }
+        match Self::maybe_start(network, config, coincube_datadir) {
+            Ok(bitcoind) => Err((error, Some(bitcoind))),
+            Err(e) => Err((UpgradeInternalBitcoindError::RollbackFailed(e), None)),
+        }
+    }
+}
+
+/// A downloaded bitcoind release to upgrade the managed bitcoind to.
+#[derive(Debug, Clone)]
+pub struct BitcoindUpgrade {
+    pub version: &'static str,
+    pub checksums: ReleaseChecksums,
+    pub bytes: Vec<u8>,
+}
+
+/// Possible errors when upgrading the managed bitcoind.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum UpgradeInternalBitcoindError {
+    Install(InstallBitcoindError),
+    Start(StartInternalBitcoindError),
+    RollbackFailed(StartInternalBitcoindError),
+}
+
+impl std::fmt::Display for UpgradeInternalBitcoindError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Install(e) => write!(f, "Failed to install new version: {}", e),
+            Self::Start(e) => write!(f, "Failed to start new version: {}", e),
+            Self::RollbackFailed(e) => {
+                write!(f, "Failed to start previous version after rollback: {}", e)
+            }
+        }
+    }
+}
+
+/// The most recent managed bitcoind version installed, if any.
+pub fn installed_version(coincube_datadir: &CoincubeDirectory) -> Option<&'static str> {
+    VERSIONS
+        .iter()
+        .find(|v| internal_bitcoind_exe_path(coincube_datadir, v).exists())
+        .copied()
+}
+
+/// Managed bitcoind versions more recent than the given one, in order of descending version.
+pub fn upgradable_versions(installed_version: &str) -> Vec<&'static str> {
+    VERSIONS
+        .iter()
+        .take_while(|v| **v != installed_version)
+        .copied()
+        .collect()
+}
+
+/// Number of 500ms intervals to wait for bitcoind to stop.
+const SHUTDOWN_TIMEOUT_TRIES: usize = 120;
+
 const LOCK_DIRECTORY_NAME: &str = "locks";
 
 #[derive(Debug, Clone)]
@@ -589,6 +968,18 @@ impl LockFile {
         })
     }
 
+    // returns true if other processes hold a lock for the same network.
+    fn is_shared(&self) -> bool {
+        std::fs::read_dir(
+            self.directory
+                .path()
+                .join(LOCK_DIRECTORY_NAME)
+                .join(self.network.to_string()),
+        )
+        .map(|entries| entries.count() > 1)
+        .unwrap_or(false)
+    }
+
     // returns true if the lock directory is removed because empty.
     fn delete(self) -> Result<bool, Box<dyn std::error::Error>> {
         std::fs::remove_file(self.path)?;
@@ -717,21 +1108,14 @@ mod tests {
             .set("prune", "2043")
             .set("rpcauth", "my_user:my_salt$my_pw_hmac");
         let conf = InternalBitcoindConfig::from_ini(&conf_ini).expect("Loading conf from ini");
-        let main_conf = InternalBitcoindNetworkConfig {
-            rpc_port: 43345,
-            p2p_port: 42355,
-            prune: 15246,
-            rpc_auth: None,
-        };
+        let main_conf = InternalBitcoindNetworkConfig::new(43345, 42355, 15246);
         let regtest_conf = InternalBitcoindNetworkConfig {
-            rpc_port: 34067,
-            p2p_port: 45175,
-            prune: 2043,
             rpc_auth: Some(RpcAuth {
                 user: "my_user".to_string(),
                 salt: "my_salt".to_string(),
                 password_hmac: "my_pw_hmac".to_string(),
             }),
+            ..InternalBitcoindNetworkConfig::new(34067, 45175, 2043)
         };
         assert_eq!(conf.networks.len(), 2);
         assert_eq!(
@@ -777,4 +1161,65 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn internal_bitcoind_config_optional_settings() {
+        let mut conf_ini = Ini::new();
+        conf_ini
+            .with_section(Some("signet"))
+            .set("rpcport", "34067")
+            .set("port", "45175")
+            .set("prune", "0")
+            .set("dbcache", "2048")
+            .set("listen", "0")
+            .set("maxconnections", "12");
+        let conf = InternalBitcoindConfig::from_ini(&conf_ini).expect("Loading conf from ini");
+        let signet_conf = conf.networks.get(&Network::Signet).expect("Missing signet");
+        assert_eq!(
+            signet_conf,
+            &InternalBitcoindNetworkConfig {
+                dbcache: Some(2048),
+                listen: Some(false),
+                max_connections: Some(12),
+                ..InternalBitcoindNetworkConfig::new(34067, 45175, 0)
+            }
+        );
+        signet_conf.validate().expect("Pruning disabled");
+
+        // Round trip.
+        let conf_ini = conf.to_ini();
+        let conf = InternalBitcoindConfig::from_ini(&conf_ini).expect("Loading conf from ini");
+        assert_eq!(conf.networks.get(&Network::Signet), Some(signet_conf));
+
+        // Invalid values.
+        let mut conf_ini = Ini::new();
+        conf_ini
+            .with_section(Some("main"))
+            .set("rpcport", "43345")
+            .set("port", "42355")
+            .set("prune", "15246")
+            .set("listen", "yes");
+        assert!(matches!(
+            InternalBitcoindConfig::from_ini(&conf_ini),
+            Err(InternalBitcoindConfigError::CouldNotParseValue(_))
+        ));
+        assert!(InternalBitcoindNetworkConfig::new(1, 2, 549)
+            .validate()
+            .is_err());
+        assert!(InternalBitcoindNetworkConfig::new(1, 2, 550)
+            .validate()
+            .is_ok());
+        let mut conf = InternalBitcoindNetworkConfig::new(1, 2, 550);
+        conf.dbcache = Some(DBCACHE_MAX + 1);
+        assert!(conf.validate().is_err());
+    }
+
+    #[test]
+    fn upgradable_versions() {
+        assert_eq!(super::upgradable_versions(VERSIONS[0]), Vec::<&str>::new());
+        assert_eq!(
+            super::upgradable_versions(VERSIONS[2]),
+            VERSIONS[..2].to_vec()
+        );
+    }
 }
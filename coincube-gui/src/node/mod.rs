@@ -2,6 +2,7 @@ use coincubed::config::BitcoinBackend;
 
 pub mod bitcoind;
 pub mod electrum;
+pub mod release;
 
 #[derive(Debug, Clone, PartialEq, Eq, Copy)]
 pub enum NodeType {
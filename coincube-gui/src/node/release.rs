@@ -0,0 +1,309 @@
+//! Verification of Bitcoin Core release downloads.
+//!
+//! A release is verified by checking the downloaded archive against the upstream `SHA256SUMS`
+//! file, which itself must carry enough valid detached signatures (`SHA256SUMS.asc`) from the
+//! bundled builder keyring.
+use std::collections::{BTreeMap, BTreeSet};
+use std::str::FromStr;
+
+use bitcoin_hashes::sha256;
+use pgp::{types::PublicKeyTrait, Deserializable, SignedPublicKey, StandaloneSignature};
+use tracing::{info, warn};
+
+use crate::{app::settings::global::GlobalSettings, dir::CoincubeDirectory};
+
+/// Armored public keys of the Bitcoin Core builders whose signatures we accept.
+/// Taken from https://github.com/bitcoin-core/guix.sigs/tree/main/builder-keys.
+pub const BUILDER_KEYS: &str = include_str!("../../static/bitcoind_builder_keys.asc");
+
+/// Default minimum number of distinct builders that must have signed a `SHA256SUMS` file.
+pub const MIN_VALID_SIGNATURES: usize = 3;
+
+const PUBLIC_KEY_HEADER: &str = "-----BEGIN PGP PUBLIC KEY BLOCK-----";
+const SIGNATURE_HEADER: &str = "-----BEGIN PGP SIGNATURE-----";
+
+/// The minimum number of valid signatures to require, as set in the global settings of the
+/// given data directory or [`MIN_VALID_SIGNATURES`] if none is set.
+pub fn min_valid_signatures(global_datadir: &CoincubeDirectory) -> usize {
+    GlobalSettings::load_bitcoind_min_signatures(&GlobalSettings::path(global_datadir))
+        .filter(|n| *n > 0)
+        .unwrap_or(MIN_VALID_SIGNATURES)
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum VerificationError {
+    Keyring(String),
+    NoBuilderKeys,
+    NotEnoughSignatures { valid: usize, required: usize },
+    InvalidSha256Sums(String),
+    FileNotListed(String),
+    HashMismatch,
+}
+
+impl std::fmt::Display for VerificationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Keyring(e) => write!(f, "Failed to load builder keys: {}", e),
+            Self::NoBuilderKeys => write!(f, "No builder key available to verify the release."),
+            Self::NotEnoughSignatures { valid, required } => write!(
+                f,
+                "Release checksums have {} valid signature(s), {} required.",
+                valid, required
+            ),
+            Self::InvalidSha256Sums(e) => write!(f, "Invalid SHA256SUMS file: {}", e),
+            Self::FileNotListed(name) => write!(f, "'{}' is not listed in SHA256SUMS.", name),
+            Self::HashMismatch => write!(f, "Hashes do not match."),
+        }
+    }
+}
+
+/// The `SHA256SUMS` file of a release along with its detached signatures.
+#[derive(Debug, Clone)]
+pub struct ReleaseChecksums {
+    pub sha256sums: Vec<u8>,
+    pub signatures: String,
+}
+
+impl ReleaseChecksums {
+    /// Verify `bytes` are the content of the file `filename` as listed in these checksums,
+    /// with the checksums signed by at least `min_signatures` distinct keys of the `keyring`.
+    pub fn verify(
+        &self,
+        filename: &str,
+        bytes: &[u8],
+        keyring: &[SignedPublicKey],
+        min_signatures: usize,
+    ) -> Result<(), VerificationError> {
+        if keyring.is_empty() {
+            return Err(VerificationError::NoBuilderKeys);
+        }
+        let valid = count_valid_signatures(&self.sha256sums, &self.signatures, keyring);
+        info!(
+            "SHA256SUMS has {} valid signature(s) from builder keys, {} required.",
+            valid, min_signatures
+        );
+        if valid < min_signatures {
+            return Err(VerificationError::NotEnoughSignatures {
+                valid,
+                required: min_signatures,
+            });
+        }
+
+        let sums = parse_sha256sums(&self.sha256sums)?;
+        let expected_hash = sums
+            .get(filename)
+            .ok_or_else(|| VerificationError::FileNotListed(filename.to_string()))?;
+        let bytes_hash = sha256::Hash::hash(bytes);
+        info!("Download hash: '{}'.", bytes_hash);
+        if *expected_hash != bytes_hash {
+            return Err(VerificationError::HashMismatch);
+        }
+        Ok(())
+    }
+}
+
+/// Split a file made of concatenated armored blocks into the individual blocks.
+fn armored_blocks<'a>(armored: &'a str, header: &str) -> Vec<&'a str> {
+    let starts: Vec<usize> = armored.match_indices(header).map(|(i, _)| i).collect();
+    starts
+        .iter()
+        .enumerate()
+        .map(|(n, start)| &armored[*start..starts.get(n + 1).copied().unwrap_or(armored.len())])
+        .collect()
+}
+
+/// Parse a keyring made of concatenated armored public keys. Keys whose self-signatures are
+/// invalid are ignored.
+pub fn parse_keyring(armored: &str) -> Result<Vec<SignedPublicKey>, VerificationError> {
+    let mut keys = Vec::new();
+    for block in armored_blocks(armored, PUBLIC_KEY_HEADER) {
+        let (key, _) = SignedPublicKey::from_string(block)
+            .map_err(|e| VerificationError::Keyring(e.to_string()))?;
+        if let Err(e) = key.verify() {
+            warn!(
+                "Ignoring builder key {} with invalid self-signature: {}",
+                hex::encode(key.fingerprint().as_bytes()),
+                e
+            );
+            continue;
+        }
+        keys.push(key);
+    }
+    Ok(keys)
+}
+
+/// The bundled builder keyring.
+pub fn builder_keyring() -> Result<Vec<SignedPublicKey>, VerificationError> {
+    parse_keyring(BUILDER_KEYS)
+}
+
+/// Returns the number of distinct keys from the `keyring` that produced a valid signature over
+/// `data` among the armored detached `signatures`. Signatures from unknown keys, and those which
+/// can't be parsed, are ignored.
+pub fn count_valid_signatures(data: &[u8], signatures: &str, keyring: &[SignedPublicKey]) -> usize {
+    let mut signers = BTreeSet::new();
+    for block in armored_blocks(signatures, SIGNATURE_HEADER) {
+        let sig = match StandaloneSignature::from_string(block) {
+            Ok((sig, _)) => sig,
+            Err(e) => {
+                warn!("Ignoring release signature which can't be parsed: {}", e);
+                continue;
+            }
+        };
+        for (i, key) in keyring.iter().enumerate() {
+            if signers.contains(&i) {
+                continue;
+            }
+            let valid = sig.verify(key, data).is_ok()
+                || key
+                    .public_subkeys
+                    .iter()
+                    .any(|sub| sig.verify(&sub.key, data).is_ok());
+            if valid {
+                signers.insert(i);
+                break;
+            }
+        }
+    }
+    signers.len()
+}
+
+/// Parse the content of a `SHA256SUMS` file into a map from file name to hash.
+pub fn parse_sha256sums(
+    content: &[u8],
+) -> Result<BTreeMap<String, sha256::Hash>, VerificationError> {
+    let content = std::str::from_utf8(content)
+        .map_err(|e| VerificationError::InvalidSha256Sums(e.to_string()))?;
+    let mut sums = BTreeMap::new();
+    for line in content.lines().filter(|l| !l.trim().is_empty()) {
+        let (hash, filename) = line
+            .split_once(char::is_whitespace)
+            .ok_or_else(|| VerificationError::InvalidSha256Sums(line.to_string()))?;
+        let hash = sha256::Hash::from_str(hash)
+            .map_err(|e| VerificationError::InvalidSha256Sums(e.to_string()))?;
+        // A leading '*' denotes binary mode in the sha256sum output format.
+        let filename = filename.trim().trim_start_matches('*');
+        sums.insert(filename.to_string(), hash);
+    }
+    Ok(sums)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::path::PathBuf;
+
+    fn asset(name: &str) -> PathBuf {
+        env::current_dir()
+            .unwrap()
+            .join("test_assets")
+            .join("bitcoind_release")
+            .join(name)
+    }
+
+    fn checksums() -> ReleaseChecksums {
+        ReleaseChecksums {
+            sha256sums: std::fs::read(asset("SHA256SUMS")).unwrap(),
+            signatures: std::fs::read_to_string(asset("SHA256SUMS.asc")).unwrap(),
+        }
+    }
+
+    fn keyring() -> Vec<SignedPublicKey> {
+        parse_keyring(&std::fs::read_to_string(asset("builder-keys.asc")).unwrap()).unwrap()
+    }
+
+    const FILENAME: &str = "bitcoin-29.0-x86_64-linux-gnu.tar.gz";
+
+    #[test]
+    fn sha256sums_parsing() {
+        let sums = parse_sha256sums(&checksums().sha256sums).unwrap();
+        assert_eq!(sums.len(), 2);
+        assert_eq!(sums[FILENAME], sha256::Hash::hash(b"this is not bitcoin"));
+        assert!(parse_sha256sums(b"not a hash  bitcoin.tar.gz").is_err());
+    }
+
+    #[test]
+    fn signatures() {
+        let checksums = checksums();
+        let keyring = keyring();
+        assert_eq!(keyring.len(), 2);
+        // The signatures file is signed by both keys of the keyring and by an unknown key.
+        assert_eq!(
+            count_valid_signatures(&checksums.sha256sums, &checksums.signatures, &keyring),
+            2
+        );
+        assert_eq!(
+            count_valid_signatures(&checksums.sha256sums, &checksums.signatures, &keyring[..1]),
+            1
+        );
+        // A signature block which can't be parsed doesn't prevent counting the others.
+        let malformed = std::fs::read_to_string(asset("SHA256SUMS-malformed.asc")).unwrap();
+        assert_eq!(
+            count_valid_signatures(&checksums.sha256sums, &malformed, &keyring),
+            2
+        );
+        // Tampering with the checksums invalidates all signatures.
+        let mut tampered = checksums.sha256sums.clone();
+        tampered[0] ^= 1;
+        assert_eq!(
+            count_valid_signatures(&tampered, &checksums.signatures, &keyring),
+            0
+        );
+    }
+
+    #[test]
+    fn verify_release() {
+        let checksums = checksums();
+        let keyring = keyring();
+        let bytes = b"this is not bitcoin";
+        checksums.verify(FILENAME, bytes, &keyring, 2).unwrap();
+        assert_eq!(
+            checksums.verify(FILENAME, bytes, &keyring, 3),
+            Err(VerificationError::NotEnoughSignatures {
+                valid: 2,
+                required: 3
+            })
+        );
+        assert_eq!(
+            checksums.verify(FILENAME, b"this is bitcoin", &keyring, 2),
+            Err(VerificationError::HashMismatch)
+        );
+        assert_eq!(
+            checksums.verify("bitcoin-29.0-arm64-apple-darwin.tar.gz", bytes, &keyring, 2),
+            Err(VerificationError::FileNotListed(
+                "bitcoin-29.0-arm64-apple-darwin.tar.gz".to_string()
+            ))
+        );
+        assert_eq!(
+            checksums.verify(FILENAME, bytes, &[], 1),
+            Err(VerificationError::NoBuilderKeys)
+        );
+    }
+
+    #[test]
+    fn shipped_keyring() {
+        let keyring = builder_keyring().unwrap();
+        assert!(
+            keyring.len() >= MIN_VALID_SIGNATURES,
+            "the bundled keyring has {} builder keys, at least {} are needed",
+            keyring.len(),
+            MIN_VALID_SIGNATURES
+        );
+    }
+
+    #[test]
+    fn min_signatures_setting() {
+        let datadir = CoincubeDirectory::new(
+            env::temp_dir().join(format!("coincube-min-signatures-{}", std::process::id())),
+        );
+        std::fs::create_dir_all(datadir.path()).unwrap();
+        assert_eq!(min_valid_signatures(&datadir), MIN_VALID_SIGNATURES);
+        let path = GlobalSettings::path(&datadir);
+        GlobalSettings::update_bitcoind_min_signatures(&path, Some(5)).unwrap();
+        assert_eq!(min_valid_signatures(&datadir), 5);
+        GlobalSettings::update_bitcoind_min_signatures(&path, Some(0)).unwrap();
+        assert_eq!(min_valid_signatures(&datadir), MIN_VALID_SIGNATURES);
+        std::fs::remove_dir_all(datadir.path()).unwrap();
+    }
+}
@@ -586,6 +586,11 @@ impl Daemon for BackendWalletClient {
             last_poll_timestamp: None,
             receive_index: wallet.deposit_derivation_index,
             change_index: wallet.change_derivation_index,
+            // The gap limit is managed by the remote backend.
+            gap_limit: coincubed::commands::DEFAULT_GAP_LIMIT,
+            warnings: Vec::new(),
+            // Spending policies are only enforced by a local daemon.
+            spending_policy: None,
         })
     }
 
@@ -654,6 +659,7 @@ impl Daemon for BackendWalletClient {
                     address: addr.address,
                     label: addr.label,
                     used_count: addr.used_count,
+                    reused: addr.used_count > 1,
                 })
                 .collect(),
             continue_from: res.continue_from,
@@ -713,6 +719,7 @@ impl Daemon for BackendWalletClient {
                 .map(|psbt| ListSpendEntry {
                     psbt: psbt.raw,
                     updated_at: Some(psbt.updated_at as u32),
+                    approved_at: None,
                 })
                 .collect(),
         })
@@ -761,9 +768,16 @@ impl Daemon for BackendWalletClient {
         &self,
         coins_outpoints: &[OutPoint],
         destinations: &HashMap<Address<address::NetworkUnchecked>, u64>,
-        feerate_vb: u64,
+        subtract_fee_from: &[Address<address::NetworkUnchecked>],
+        op_return: Option<&[u8]>,
+        feerate_kvb: u64,
         change_address: Option<Address<address::NetworkUnchecked>>,
     ) -> Result<CreateSpendResult, DaemonError> {
+        // The remote backend can't deduct the fee from the recipients' amounts nor create
+        // OP_RETURN outputs.
+        if !subtract_fee_from.is_empty() || op_return.is_some() {
+            return Err(DaemonError::NotImplemented);
+        }
         let mut recipients: Vec<api::payload::Recipient> = destinations
             .iter()
             .map(|(addr, amt)| api::payload::Recipient {
@@ -791,7 +805,8 @@ impl Daemon for BackendWalletClient {
             .await
             .json(&api::payload::GeneratePsbt {
                 save: false,
-                feerate: feerate_vb,
+                // The remote backend only takes whole sats/vb, round up so as to never underpay.
+                feerate: feerate_kvb.div_ceil(1_000),
                 inputs: coins_outpoints,
                 recipients,
             })
@@ -818,7 +833,7 @@ impl Daemon for BackendWalletClient {
         &self,
         txid: &Txid,
         is_cancel: bool,
-        feerate_vb: Option<u64>,
+        feerate_kvb: Option<u64>,
     ) -> Result<CreateSpendResult, DaemonError> {
         let res: api::DraftPsbtResult = self
             .inner
@@ -833,7 +848,7 @@ impl Daemon for BackendWalletClient {
             .json(&api::payload::GenerateRbfPsbt {
                 txid: *txid,
                 is_cancel,
-                feerate: feerate_vb,
+                feerate: feerate_kvb.map(|f| f.div_ceil(1_000)),
                 save: false,
             })
             .send()
@@ -930,7 +945,7 @@ impl Daemon for BackendWalletClient {
         &self,
         address: Address<address::NetworkUnchecked>,
         coins_outpoints: &[OutPoint],
-        feerate_vb: u64,
+        feerate_kvb: u64,
         sequence: Option<u16>,
     ) -> Result<Psbt, DaemonError> {
         let res: api::DraftPsbt = self
@@ -945,7 +960,7 @@ impl Daemon for BackendWalletClient {
             .await
             .json(&api::payload::GenerateRecoveryPsbt {
                 save: false,
-                feerate: feerate_vb,
+                feerate: feerate_kvb.div_ceil(1_000),
                 timelock: sequence
                     .ok_or(DaemonError::Unexpected("Missing sequence".to_string()))?,
                 address,
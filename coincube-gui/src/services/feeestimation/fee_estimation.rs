@@ -1,3 +1,4 @@
+use coincube_core::spend::MIN_FEERATE_KVB;
 use reqwest::Client;
 use serde::Deserialize;
 use std::collections::HashMap;
@@ -16,19 +17,22 @@ impl FeeEstimator {
         Self
     }
 
-    pub async fn get_high_priority_rate(&self) -> Result<usize, FeeEstimatorError> {
+    /// Feerate (in sats/kvb) to get confirmed in the next block.
+    pub async fn get_high_priority_rate(&self) -> Result<u64, FeeEstimatorError> {
         let fee = self.get_fee_rate(BlockTarget::Fastest).await?;
-        Ok(fee.round().max(1.0) as usize)
+        Ok(to_sats_kvb(fee))
     }
 
-    pub async fn get_mid_priority_rate(&self) -> Result<usize, FeeEstimatorError> {
+    /// Feerate (in sats/kvb) to get confirmed within the hour.
+    pub async fn get_mid_priority_rate(&self) -> Result<u64, FeeEstimatorError> {
         let fee = self.get_fee_rate(BlockTarget::Standard).await?;
-        Ok(fee.round().max(1.0) as usize)
+        Ok(to_sats_kvb(fee))
     }
 
-    pub async fn get_low_priority_rate(&self) -> Result<usize, FeeEstimatorError> {
+    /// Feerate (in sats/kvb) to get confirmed within a day.
+    pub async fn get_low_priority_rate(&self) -> Result<u64, FeeEstimatorError> {
         let fee = self.get_fee_rate(BlockTarget::Economy).await?;
-        Ok(fee.round().max(1.0) as usize)
+        Ok(to_sats_kvb(fee))
     }
 
     async fn get_fee_rate(&self, target: BlockTarget) -> Result<f64, FeeEstimatorError> {
@@ -117,6 +121,11 @@ impl FeeEstimator {
     }
 }
 
+// Convert an estimate in sats/vb to sats/kvb, keeping it above the minimum relay feerate.
+fn to_sats_kvb(fee_vb: f64) -> u64 {
+    ((fee_vb * 1_000.0).round() as u64).max(MIN_FEERATE_KVB)
+}
+
 #[derive(Debug, Deserialize)]
 struct MempoolFeeResponse {
     #[serde(rename = "fastestFee")]
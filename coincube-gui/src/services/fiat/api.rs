@@ -38,5 +38,12 @@ impl std::fmt::Display for PriceApiError {
 pub trait PriceApi {
     async fn get_price(&self, currency: Currency) -> Result<GetPriceResult, PriceApiError>;
 
+    /// Get the price at the given unix timestamp (in seconds).
+    async fn get_historical_price(
+        &self,
+        currency: Currency,
+        timestamp: u64,
+    ) -> Result<GetPriceResult, PriceApiError>;
+
     async fn list_currencies(&self) -> Result<ListCurrenciesResult, PriceApiError>;
 }
@@ -0,0 +1,146 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use chrono::DateTime;
+
+use super::{api::PriceApi, currency::Currency, source::PriceSource};
+
+/// Name of the file caching historical prices in the network directory.
+pub const PRICE_HISTORY_FILENAME: &str = "fiat_price_history.json";
+
+/// Daily historical prices, cached in a local file so that they can be used offline.
+///
+/// Prices are stored per source, currency and UTC day, e.g. `mempool.space:USD:2024-03-01`.
+#[derive(Debug, Default)]
+pub struct PriceHistory {
+    path: Option<PathBuf>,
+    prices: BTreeMap<String, f64>,
+}
+
+impl PriceHistory {
+    /// Load the cache from the given file. A missing or unreadable file gives an empty cache.
+    pub fn load(path: PathBuf) -> Self {
+        let prices = match fs::read(&path) {
+            Ok(content) => serde_json::from_slice(&content).unwrap_or_else(|e| {
+                tracing::warn!("Ignoring invalid price history file {:?}: {}", path, e);
+                BTreeMap::new()
+            }),
+            Err(_) => BTreeMap::new(),
+        };
+        Self {
+            path: Some(path),
+            prices,
+        }
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    fn key(source: PriceSource, currency: Currency, timestamp: u64) -> String {
+        let day = DateTime::from_timestamp(timestamp as i64, 0)
+            .unwrap_or_default()
+            .format("%Y-%m-%d");
+        format!("{}:{}:{}", source, currency, day)
+    }
+
+    pub fn get(&self, source: PriceSource, currency: Currency, timestamp: u64) -> Option<f64> {
+        self.prices
+            .get(&Self::key(source, currency, timestamp))
+            .copied()
+    }
+
+    pub fn insert(&mut self, source: PriceSource, currency: Currency, timestamp: u64, price: f64) {
+        self.prices
+            .insert(Self::key(source, currency, timestamp), price);
+    }
+
+    /// Get the price of the day of `timestamp`, from the cache or else from the API if given.
+    /// Returns `None` if the price is unknown, for instance when offline.
+    pub async fn price_at(
+        &mut self,
+        source: PriceSource,
+        api: Option<&(dyn PriceApi + Send + Sync)>,
+        currency: Currency,
+        timestamp: u64,
+    ) -> Option<f64> {
+        if let Some(price) = self.get(source, currency, timestamp) {
+            return Some(price);
+        }
+        match api?.get_historical_price(currency, timestamp).await {
+            Ok(res) => {
+                self.insert(source, currency, timestamp, res.value);
+                Some(res.value)
+            }
+            Err(e) => {
+                tracing::warn!(
+                    "Failed to get the {} price of {} from {}: {}",
+                    currency,
+                    timestamp,
+                    source,
+                    e
+                );
+                None
+            }
+        }
+    }
+
+    /// Write the cache to its file, if any.
+    pub fn save(&self) -> Result<(), std::io::Error> {
+        if let Some(path) = &self.path {
+            fs::write(path, serde_json::to_vec_pretty(&self.prices)?)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn price_history_cache() {
+        let dir =
+            std::env::temp_dir().join(format!("coincube-price-history-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(PRICE_HISTORY_FILENAME);
+
+        let mut history = PriceHistory::load(path.clone());
+        assert_eq!(
+            history.get(PriceSource::MempoolSpace, Currency::USD, 1_709_251_200),
+            None
+        );
+        // 2024-03-01 00:00:00 UTC.
+        history.insert(
+            PriceSource::MempoolSpace,
+            Currency::USD,
+            1_709_251_200,
+            61_000.0,
+        );
+        history.save().unwrap();
+
+        // Prices are daily, and per source and currency.
+        let history = PriceHistory::load(path);
+        assert_eq!(
+            history.get(PriceSource::MempoolSpace, Currency::USD, 1_709_337_599),
+            Some(61_000.0)
+        );
+        assert_eq!(
+            history.get(PriceSource::MempoolSpace, Currency::USD, 1_709_337_600),
+            None
+        );
+        assert_eq!(
+            history.get(PriceSource::CoinGecko, Currency::USD, 1_709_251_200),
+            None
+        );
+        assert_eq!(
+            history.get(PriceSource::MempoolSpace, Currency::EUR, 1_709_251_200),
+            None
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,8 +1,10 @@
 pub mod api;
 pub mod client;
 pub mod currency;
+pub mod history;
 pub mod source;
 
 pub use client::PriceClient;
 pub use currency::Currency;
+pub use history::PriceHistory;
 pub use source::{PriceSource, ALL_PRICE_SOURCES};
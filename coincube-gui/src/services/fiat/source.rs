@@ -53,6 +53,23 @@ impl PriceSource {
         }
     }
 
+    /// Returns the URL to fetch the price for a given currency at a given unix timestamp.
+    pub fn get_historical_price_url(&self, currency: Currency, timestamp: u64) -> String {
+        match self {
+            // The CoinGecko history is daily, in UTC.
+            Self::CoinGecko => format!(
+                "https://api.coingecko.com/api/v3/coins/bitcoin/history?date={}&localization=false",
+                chrono::DateTime::from_timestamp(timestamp as i64, 0)
+                    .unwrap_or_default()
+                    .format("%d-%m-%Y")
+            ),
+            Self::MempoolSpace => format!(
+                "https://mempool.space/api/v1/historical-price?currency={}&timestamp={}",
+                currency, timestamp
+            ),
+        }
+    }
+
     /// Returns the URL to fetch the list of supported currencies.
     pub fn list_currencies_url(&self) -> String {
         match self {
@@ -90,6 +107,47 @@ impl PriceSource {
         Ok(GetPriceResult { value, updated_at })
     }
 
+    /// Parses the price data in the API response from the `get_historical_price_url` endpoint.
+    pub fn parse_historical_price_data(
+        &self,
+        currency: Currency,
+        timestamp: u64,
+        data: &serde_json::Value,
+    ) -> Result<GetPriceResult, PriceApiError> {
+        let value = match self {
+            Self::CoinGecko => data
+                .get("market_data")
+                .and_then(|market| market.get("current_price"))
+                .and_then(|prices| prices.get(currency.to_string().to_lowercase()))
+                .and_then(|num| num.as_f64()),
+            Self::MempoolSpace => {
+                let price = data
+                    .get("prices")
+                    .and_then(|prices| prices.as_array())
+                    .and_then(|prices| prices.first());
+                // Only a few currencies have their own history, the others are derived from
+                // the USD price with the current exchange rate.
+                price
+                    .and_then(|p| p.get(currency.to_string()))
+                    .and_then(|num| num.as_f64())
+                    .or_else(|| {
+                        let usd = price?.get("USD")?.as_f64()?;
+                        let rate = data
+                            .get("exchangeRates")?
+                            .get(format!("USD{}", currency))?
+                            .as_f64()?;
+                        Some(usd * rate)
+                    })
+            }
+        }
+        .filter(|value| *value > 0.0)
+        .ok_or(PriceApiError::CannotParseData("price".to_string()))?;
+        Ok(GetPriceResult {
+            value,
+            updated_at: Some(timestamp),
+        })
+    }
+
     /// Parses the currencies data in the API response from the `list_currencies_url` endpoint.
     pub fn parse_currencies_data(
         &self,
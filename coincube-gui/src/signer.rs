@@ -7,7 +7,7 @@ use coincube_core::{
         psbt::Psbt,
         secp256k1, Network,
     },
-    signer::{self, HotSigner},
+    signer::{self, HotSigner, SighashPolicy},
 };
 
 use crate::dir::{CoincubeDirectory, NetworkDirectory};
@@ -59,6 +59,15 @@ impl Signer {
         self.key.sign_psbt(psbt, &self.curve)
     }
 
+    pub fn sign_psbt_with_policy(
+        &self,
+        psbt: Psbt,
+        sighash_policy: SighashPolicy,
+    ) -> Result<Psbt, SignerError> {
+        self.key
+            .sign_psbt_with_policy(psbt, &self.curve, sighash_policy)
+    }
+
     pub fn store(
         &self,
         datadir_root: &CoincubeDirectory,
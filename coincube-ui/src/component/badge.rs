@@ -71,6 +71,13 @@ pub fn spent<'a, T: 'a>() -> Container<'a, T> {
     )
 }
 
+pub fn reused<'a, T: 'a>() -> Container<'a, T> {
+    badge_pill(
+        "  Reused  ",
+        "This address received coins more than once, which harms your privacy.",
+    )
+}
+
 pub fn badge_pill<'a, T: 'a>(label: &'a str, tooltip: &'a str) -> Container<'a, T> {
     Container::new({
         tooltip::Tooltip::new(
@@ -1166,6 +1166,56 @@ impl BitcoinD {
         }
     }
 
+    /// Make sure the receive and change descriptors imported on the watchonly wallet are watched
+    /// at least up to this derivation index.
+    pub fn maybe_extend_descriptors_range(&self, range_end: u32) -> Result<(), BitcoindError> {
+        // The descriptors are imported with bitcoind's default range at first, [0, 999], and
+        // their range is never decreased.
+        if range_end < 1_000 {
+            return Ok(());
+        }
+        let descriptors: Vec<Json> = self
+            .list_descriptors()
+            .into_iter()
+            .filter(|entry| entry.range.is_some_and(|r| r[1] < range_end))
+            .map(|entry| {
+                // Do not trigger a rescan. Only future transactions paying to the newly watched
+                // addresses will be noticed.
+                serde_json::json!({
+                    "desc": entry.desc,
+                    "timestamp": "now",
+                    "active": false,
+                    "range": [0, range_end],
+                })
+            })
+            .collect();
+        if descriptors.is_empty() {
+            return Ok(());
+        }
+
+        log::info!(
+            "Extending the range of the descriptors on the watchonly wallet up to index {}.",
+            range_end
+        );
+        let res = self.make_wallet_request("importdescriptors", params!(Json::Array(descriptors)));
+        let all_succeeded = res
+            .as_array()
+            .map(|results| {
+                results
+                    .iter()
+                    .all(|res| res.get("success").and_then(Json::as_bool).unwrap_or(false))
+            })
+            .unwrap_or(false);
+        if all_succeeded {
+            Ok(())
+        } else {
+            Err(BitcoindError::Wallet(
+                self.watchonly_wallet_path.clone(),
+                WalletError::ImportingDescriptor(res.to_string()),
+            ))
+        }
+    }
+
     /// Get the progress of the ongoing rescan, if there is any.
     pub fn rescan_progress(&self) -> Option<f64> {
         self.make_wallet_request("getwalletinfo", None)
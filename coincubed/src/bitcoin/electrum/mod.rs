@@ -120,12 +120,18 @@ impl Electrum {
 
     /// Sync the wallet with the Electrum server. If there was any reorg since the last poll, this
     /// returns the first common ancestor between the previous and the new chain.
+    ///
+    /// `gap_limit` is the number of addresses watched past the last used derivation indexes. When
+    /// performing a full scan, it is also the number of consecutive unused addresses after which
+    /// we stop looking for transactions.
     pub fn sync_wallet(
         &mut self,
         receive_index: ChildNumber,
         change_index: ChildNumber,
+        gap_limit: u32,
     ) -> Result<Option<BlockChainTip>, ElectrumError> {
-        self.bdk_wallet.reveal_spks(receive_index, change_index);
+        self.bdk_wallet
+            .reveal_spks(receive_index, change_index, gap_limit);
         let local_chain_tip = self.local_chain().tip();
         log::debug!(
             "local chain tip height before sync with electrum: {}",
@@ -135,7 +141,6 @@ impl Electrum {
         // We'll only need to calculate fees of mempool transactions and this will be done separately from our graph
         // so we don't need to fetch prev txouts. In any case, we'll already have these for our own transactions.
         const FETCH_PREV_TXOUTS: bool = false;
-        const STOP_GAP: usize = 200;
 
         // TODO: See if this caching can be done in a more optimal way, e.g. only new txs after syncing.
         self.client
@@ -174,7 +179,7 @@ impl Electrum {
                 .client
                 .full_scan_with_confirmation_time_height_anchor(
                     request,
-                    STOP_GAP,
+                    gap_limit as usize,
                     FETCH_PREV_TXOUTS,
                 )
                 .map_err(ElectrumError::Client)?;
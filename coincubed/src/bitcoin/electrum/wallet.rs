@@ -20,9 +20,6 @@ use super::utils::{
 use crate::bitcoin::{Block, BlockChainTip, Coin, COINBASE_MATURITY};
 use coincube_core::descriptors::CoincubeDescriptor;
 
-// We don't want to overload the server (each SPK is separate call).
-const LOOK_AHEAD_LIMIT: u32 = 30;
-
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum KeychainType {
     Receive,
@@ -45,6 +42,10 @@ impl BdkWallet {
     ///
     /// `receive_index` and `change_index` are the last used derivation
     /// indices for the receive and change descriptors, respectively.
+    ///
+    /// `gap_limit` is the number of SPKs past the last used derivation
+    /// index that we watch for each descriptor.
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         main_descriptor: &CoincubeDescriptor,
         genesis_hash: BlockHash,
@@ -53,6 +54,7 @@ impl BdkWallet {
         txs: &[bitcoin::Transaction],
         receive_index: ChildNumber,
         change_index: ChildNumber,
+        gap_limit: u32,
     ) -> Self {
         let local_chain = LocalChain::from_genesis_hash(genesis_hash).0;
         let receive_desc = main_descriptor
@@ -64,7 +66,7 @@ impl BdkWallet {
 
         let mut bdk_wallet = BdkWallet {
             graph: {
-                let mut indexer = KeychainTxOutIndex::<KeychainType>::new(LOOK_AHEAD_LIMIT);
+                let mut indexer = KeychainTxOutIndex::<KeychainType>::new(gap_limit);
                 let _ = indexer.insert_descriptor(KeychainType::Receive, receive_desc.clone());
                 let _ = indexer.insert_descriptor(KeychainType::Change, change_desc.clone());
                 IndexedTxGraph::new(indexer)
@@ -87,7 +89,7 @@ impl BdkWallet {
             log::debug!(
                 "revealing SPKs up to receive index {receive_index} and change index {change_index}"
             );
-            bdk_wallet.reveal_spks(receive_index, change_index);
+            bdk_wallet.reveal_spks(receive_index, change_index, gap_limit);
 
             // Update the existing coins and transactions information using a TxGraph changeset.
             log::debug!("Number of coins to load: {}.", coins.len());
@@ -161,11 +163,22 @@ impl BdkWallet {
         &self.graph.index
     }
 
-    /// Reveal SPKs based on derivation indices set in DB.
-    pub fn reveal_spks(&mut self, receive_index: ChildNumber, change_index: ChildNumber) {
-        let mut keychain_update = BTreeMap::new();
+    /// Reveal SPKs based on derivation indices set in DB, and make sure we watch `gap_limit` SPKs
+    /// past each of them. The gap limit may have been increased since the wallet was created.
+    pub fn reveal_spks(
+        &mut self,
+        receive_index: ChildNumber,
+        change_index: ChildNumber,
+        gap_limit: u32,
+    ) {
+        let mut keychain_update = BTreeMap::<_, u32>::new();
         keychain_update.insert(KeychainType::Receive, receive_index.into());
         keychain_update.insert(KeychainType::Change, change_index.into());
+        for (keychain, index) in &keychain_update {
+            self.graph
+                .index
+                .lookahead_to_target(keychain, index.saturating_add(gap_limit));
+        }
         self.apply_keychain_update(keychain_update)
     }
 
@@ -10,7 +10,7 @@ use crate::bitcoin::d::{BitcoindError, CachedTxGetter, LSBlockEntry};
 use coincube_core::descriptors;
 pub use d::{MempoolEntry, MempoolEntryFees, SyncProgress};
 
-use std::{fmt, sync};
+use std::{cmp, fmt, sync};
 
 use miniscript::bitcoin::{self, address, bip32::ChildNumber};
 
@@ -62,13 +62,15 @@ pub trait BitcoinInterface: Send {
 
     /// Sync the wallet with the current best chain.
     /// `receive_index` and `change_index` are the last derivation indices
-    /// that are expected to have been used by the wallet.
+    /// that are expected to have been used by the wallet. `gap_limit` is the
+    /// number of addresses to watch past the highest of them.
     /// In case there has been a reorg, returns the common ancestor between
     /// the wallet and the reorged chain.
     fn sync_wallet(
         &mut self,
         receive_index: ChildNumber,
         change_index: ChildNumber,
+        gap_limit: u32,
     ) -> Result<Option<BlockChainTip>, String>;
 
     /// Get coins received since the specified tip.
@@ -167,12 +169,24 @@ impl BitcoinInterface for d::BitcoinD {
             .unwrap_or(false)
     }
 
-    // The watchonly wallet handles this for us.
+    // The watchonly wallet handles this for us. We only need to make sure it watches enough
+    // addresses.
     fn sync_wallet(
         &mut self,
-        _receive_index: ChildNumber,
-        _change_index: ChildNumber,
+        receive_index: ChildNumber,
+        change_index: ChildNumber,
+        gap_limit: u32,
     ) -> Result<Option<BlockChainTip>, String> {
+        let highest_index: u32 = cmp::max(receive_index, change_index).into();
+        // This may fail if the watchonly wallet is rescanning. Don't hold the poller, we'll
+        // retry at the next poll.
+        if let Err(e) = self.maybe_extend_descriptors_range(highest_index.saturating_add(gap_limit))
+        {
+            log::error!(
+                "Error extending the range of the watched descriptors: '{}'.",
+                e
+            );
+        }
         Ok(None)
     }
 
@@ -409,8 +423,9 @@ impl BitcoinInterface for electrum::Electrum {
         &mut self,
         receive_index: ChildNumber,
         change_index: ChildNumber,
+        gap_limit: u32,
     ) -> Result<Option<BlockChainTip>, String> {
-        self.sync_wallet(receive_index, change_index)
+        self.sync_wallet(receive_index, change_index, gap_limit)
             .map_err(|e| e.to_string())
     }
 
@@ -617,10 +632,11 @@ impl BitcoinInterface for sync::Arc<sync::Mutex<dyn BitcoinInterface + 'static>>
         &mut self,
         receive_index: ChildNumber,
         change_index: ChildNumber,
+        gap_limit: u32,
     ) -> Result<Option<BlockChainTip>, String> {
         self.lock()
             .unwrap()
-            .sync_wallet(receive_index, change_index)
+            .sync_wallet(receive_index, change_index, gap_limit)
     }
 
     fn received_coins(
@@ -1,12 +1,17 @@
 use crate::{
     bitcoin::{BitcoinInterface, BlockChainTip, UTxO, UTxOAddress},
+    commands,
     database::{Coin, DatabaseConnection, DatabaseInterface},
+    policy::SpendingPolicy,
 };
 
 use std::{collections::HashSet, convert::TryInto, sync, thread, time};
 
 use coincube_core::descriptors;
-use miniscript::bitcoin::{self, secp256k1};
+use miniscript::{
+    bitcoin::{self, secp256k1},
+    psbt::PsbtExt,
+};
 
 #[derive(Debug, Clone)]
 struct UpdatedCoins {
@@ -254,7 +259,7 @@ fn updates(
     // an Electrum backend with the bitcoind-specific backend interface.
     let current_tip = db_conn.chain_tip().expect("Always set at first startup");
     let (receive_index, change_index) = (db_conn.receive_index(), db_conn.change_index());
-    let latest_tip = match bit.sync_wallet(receive_index, change_index) {
+    let latest_tip = match bit.sync_wallet(receive_index, change_index, db_conn.gap_limit()) {
         Ok(None) => {
             match new_tip(bit, &current_tip) {
                 TipUpdate::Same => current_tip,
@@ -374,6 +379,133 @@ fn rescan_check(
     }
 }
 
+/// Broadcast the Spend transactions from the queue which are due at our current tip and the
+/// `now` timestamp. Returns whether any of them was broadcast.
+///
+/// The spending policy, if any, is checked again right before broadcasting: it may forbid a
+/// broadcast that it allowed when it was scheduled. The transaction is then kept in the queue and
+/// attempted again on the next poll. Transactions which can never be broadcast, for instance
+/// because their coins were spent by another transaction, are marked as failed instead.
+fn process_broadcast_queue(
+    bit: &impl BitcoinInterface,
+    db_conn: &mut Box<dyn DatabaseConnection>,
+    secp: &secp256k1::Secp256k1<secp256k1::VerifyOnly>,
+    main_descriptor: &descriptors::CoincubeDescriptor,
+    spending_policy: Option<&SpendingPolicy>,
+    now: u32,
+) -> bool {
+    let tip_height = match db_conn.chain_tip() {
+        Some(tip) => tip.height,
+        None => return false,
+    };
+    let network = db_conn.network();
+    let mut broadcast = false;
+
+    for entry in db_conn.scheduled_broadcasts() {
+        if !entry.is_due(tip_height, now) {
+            continue;
+        }
+        let mut psbt = match db_conn.spend_tx(&entry.txid) {
+            Some(psbt) => psbt,
+            None => {
+                log::error!(
+                    "Spend transaction '{}' scheduled for broadcast is not in database.",
+                    entry.txid
+                );
+                db_conn.fail_broadcast(&entry.txid, "The spend transaction is not in database.");
+                continue;
+            }
+        };
+
+        // If any of our coins was spent already, either this transaction was broadcast by other
+        // means or it was double spent.
+        let outpoints: Vec<_> = psbt
+            .unsigned_tx
+            .input
+            .iter()
+            .map(|txin| txin.previous_output)
+            .collect();
+        let spend_txids: HashSet<_> = db_conn
+            .coins(&[], &outpoints)
+            .into_values()
+            .filter_map(|coin| coin.spend_txid)
+            .collect();
+        if spend_txids.contains(&entry.txid) {
+            log::info!(
+                "Scheduled Spend transaction '{}' was already broadcast.",
+                entry.txid
+            );
+            db_conn.record_broadcast_attempt(&entry.txid, Ok(()));
+            continue;
+        }
+        if let Some(conflict) = spend_txids.iter().next() {
+            let error = format!(
+                "Coins it spends were already spent by transaction '{}'.",
+                conflict
+            );
+            log::warn!(
+                "Not broadcasting scheduled Spend transaction '{}': {}",
+                entry.txid,
+                error
+            );
+            db_conn.fail_broadcast(&entry.txid, &error);
+            continue;
+        }
+
+        if let Some(policy) = spending_policy {
+            let (created_at, approved_at) =
+                db_conn.spend_tx_timestamps(&entry.txid).unwrap_or_default();
+            let mut ctx = commands::spend_context(db_conn, main_descriptor, network, secp, &psbt);
+            ctx.now = now;
+            if let Err(e) = policy.check_broadcast(network, &ctx, created_at, approved_at) {
+                let error = format!("Spending policy violation: {}", e);
+                log::warn!(
+                    "Not broadcasting scheduled Spend transaction '{}': {}",
+                    entry.txid,
+                    error
+                );
+                db_conn.record_broadcast_attempt(&entry.txid, Err(&error));
+                continue;
+            }
+        }
+
+        if let Err(e) = psbt.finalize_mut(secp) {
+            let error = format!(
+                "Failed to finalize the spend transaction PSBT: '{}'.",
+                e.into_iter()
+                    .next()
+                    .map(|e| e.to_string())
+                    .unwrap_or_default()
+            );
+            log::error!(
+                "Not broadcasting scheduled Spend transaction '{}': {}",
+                entry.txid,
+                error
+            );
+            db_conn.fail_broadcast(&entry.txid, &error);
+            continue;
+        }
+        let res = bit.broadcast_tx(&psbt.extract_tx_unchecked_fee_rate());
+        match &res {
+            Ok(()) => {
+                log::info!("Broadcast scheduled Spend transaction '{}'.", entry.txid);
+                broadcast = true;
+            }
+            Err(e) => log::warn!(
+                "Failed to broadcast scheduled Spend transaction '{}': {}",
+                entry.txid,
+                e
+            ),
+        }
+        db_conn.record_broadcast_attempt(
+            &entry.txid,
+            res.as_ref().map(|_| ()).map_err(|e| e.as_str()),
+        );
+    }
+
+    broadcast
+}
+
 /// If the database chain tip is NULL (first startup), initialize it.
 pub fn maybe_initialize_tip(bit: &impl BitcoinInterface, db: &impl DatabaseInterface) {
     let mut db_conn = db.connection();
@@ -400,6 +532,8 @@ pub fn poll(
     db: &sync::Arc<sync::Mutex<dyn DatabaseInterface>>,
     secp: &secp256k1::Secp256k1<secp256k1::VerifyOnly>,
     descs: &[descriptors::SinglePathCoincubeDesc],
+    main_descriptor: &descriptors::CoincubeDescriptor,
+    spending_policy: Option<&SpendingPolicy>,
 ) {
     let mut db_conn = db.connection();
     updates(&mut db_conn, bit, descs, secp);
@@ -410,5 +544,16 @@ pub fn poll(
         .as_secs()
         .try_into()
         .expect("system clock year is earlier than 2106");
+    // Update our state with the transactions we broadcast right away.
+    if process_broadcast_queue(
+        bit,
+        &mut db_conn,
+        secp,
+        main_descriptor,
+        spending_policy,
+        now,
+    ) {
+        updates(&mut db_conn, bit, descs, secp);
+    }
     db_conn.set_last_poll(now);
 }
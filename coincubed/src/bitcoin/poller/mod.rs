@@ -1,6 +1,6 @@
 mod looper;
 
-use crate::{bitcoin::BitcoinInterface, database::DatabaseInterface};
+use crate::{bitcoin::BitcoinInterface, database::DatabaseInterface, policy::SpendingPolicy};
 use coincube_core::descriptors;
 
 use std::{
@@ -25,6 +25,9 @@ pub struct Poller {
     secp: secp256k1::Secp256k1<secp256k1::VerifyOnly>,
     // The receive and change descriptors (in this order).
     descs: [descriptors::SinglePathCoincubeDesc; 2],
+    main_descriptor: descriptors::CoincubeDescriptor,
+    // Checked again before broadcasting the scheduled Spend transactions.
+    spending_policy: Option<SpendingPolicy>,
 }
 
 impl Poller {
@@ -32,6 +35,7 @@ impl Poller {
         bit: sync::Arc<sync::Mutex<dyn BitcoinInterface>>,
         db: sync::Arc<sync::Mutex<dyn DatabaseInterface>>,
         desc: descriptors::CoincubeDescriptor,
+        spending_policy: Option<SpendingPolicy>,
     ) -> Poller {
         let secp = secp256k1::Secp256k1::verification_only();
         let descs = [
@@ -47,6 +51,8 @@ impl Poller {
             db,
             secp,
             descs,
+            main_descriptor: desc,
+            spending_policy,
         }
     }
 
@@ -108,7 +114,14 @@ impl Poller {
                     // poll too soon.
                     last_poll = Some(time::Instant::now());
                     if synced {
-                        looper::poll(&mut self.bit, &self.db, &self.secp, &self.descs);
+                        looper::poll(
+                            &mut self.bit,
+                            &self.db,
+                            &self.secp,
+                            &self.descs,
+                            &self.main_descriptor,
+                            self.spending_policy.as_ref(),
+                        );
                     } else {
                         log::warn!("Skipped poll as block chain is still synchronizing.");
                     }
@@ -142,7 +155,14 @@ impl Poller {
                 }
             }
 
-            looper::poll(&mut self.bit, &self.db, &self.secp, &self.descs);
+            looper::poll(
+                &mut self.bit,
+                &self.db,
+                &self.secp,
+                &self.descs,
+                &self.main_descriptor,
+                self.spending_policy.as_ref(),
+            );
         }
     }
 }
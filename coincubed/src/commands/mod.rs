@@ -8,17 +8,18 @@ use crate::{
     bitcoin::BitcoinInterface,
     database::{Coin, DatabaseConnection, DatabaseInterface},
     miniscript::bitcoin::absolute::LockTime,
+    policy::{PolicyViolation, SpendContext, SpendingPolicy},
     poller::PollerMessage,
     DaemonControl, VERSION,
 };
 
-pub use crate::database::{CoinStatus, LabelItem};
+pub use crate::database::{sqlite::DEFAULT_GAP_LIMIT, CoinStatus, LabelItem, SearchFilter};
 
-use coincube_core::descriptors;
 use coincube_core::spend::{
     self, create_spend, AddrInfo, AncestorInfo, CandidateCoin, CreateSpendRes, SpendCreationError,
     SpendOutputAddress, SpendTxFees, TxGetter,
 };
+use coincube_core::{bip322, descriptors, signer};
 
 use utils::{
     deser_addr_assume_checked, deser_amount_from_sats, deser_fromstr, deser_hex, ser_amount,
@@ -26,7 +27,7 @@ use utils::{
 };
 
 use std::{
-    collections::{hash_map, HashMap, HashSet},
+    collections::{hash_map, BTreeMap, HashMap, HashSet},
     convert::TryInto,
     fmt,
     sync::{self, mpsc},
@@ -38,15 +39,18 @@ use miniscript::{
         self, address,
         bip32::{self, ChildNumber},
         psbt::Psbt,
+        secp256k1,
+        taproot::TapLeafHash,
     },
     psbt::PsbtExt,
+    DescriptorPublicKey, ForEachKey,
 };
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum CommandError {
     NoOutpointForSelfSend,
-    InvalidFeerate(/* sats/vb */ u64),
+    InvalidFeerate(/* sats/kvb */ u64),
     UnknownOutpoint(bitcoin::OutPoint),
     AlreadySpent(bitcoin::OutPoint),
     ImmatureCoinbase(bitcoin::OutPoint),
@@ -55,7 +59,7 @@ pub enum CommandError {
     InsufficientFunds(
         /* in value */ bitcoin::Amount,
         /* out value */ Option<bitcoin::Amount>,
-        /* target feerate */ u64,
+        /* target feerate (sats/kvb) */ u64,
     ),
     UnknownSpend(bitcoin::Txid),
     // FIXME: when upgrading Miniscript put the actual error there
@@ -72,6 +76,15 @@ pub enum CommandError {
     InvalidDerivationIndex,
     RbfError(RbfErrorInfo),
     EmptyFilterList,
+    UnknownAddress(bitcoin::Address),
+    NotADestination(bitcoin::Address),
+    CoinAlreadyConfirmed(bitcoin::OutPoint),
+    NotInMempool(bitcoin::Txid),
+    InvalidGapLimit(u32),
+    PolicyViolation(PolicyViolation),
+    EmptyBroadcastSchedule,
+    NotScheduled(bitcoin::Txid),
+    UnknownRecoveryPath(/* timelock */ u16),
 }
 
 impl fmt::Display for CommandError {
@@ -80,7 +93,11 @@ impl fmt::Display for CommandError {
             Self::NoOutpointForSelfSend => {
                 write!(f, "No provided outpoint for self-send. Need at least one.")
             }
-            Self::InvalidFeerate(sats_vb) => write!(f, "Invalid feerate: {} sats/vb.", sats_vb),
+            Self::InvalidFeerate(sats_kvb) => write!(
+                f,
+                "Invalid feerate: {} sats/vb.",
+                spend::format_feerate_kvb(*sats_kvb)
+            ),
             Self::AlreadySpent(op) => write!(f, "Coin at '{}' is already spent.", op),
             Self::ImmatureCoinbase(op) => write!(
                 f,
@@ -95,13 +112,14 @@ impl fmt::Display for CommandError {
                     write!(
                     f,
                     "Cannot create a {} sat/vb transaction with input value {} and output value {}",
-                    feerate, in_val, out_val
+                    spend::format_feerate_kvb(*feerate), in_val, out_val
                 )
                 } else {
                     write!(
                         f,
                         "Not enough fund to create a {} sat/vb transaction with input value {}",
-                        feerate, in_val
+                        spend::format_feerate_kvb(*feerate),
+                        in_val
                     )
                 }
             }
@@ -120,6 +138,13 @@ impl fmt::Display for CommandError {
                 f,
                 "No coin currently spendable through this timelocked recovery path."
             ),
+            Self::UnknownRecoveryPath(t) => {
+                write!(
+                    f,
+                    "No recovery path with timelock '{}' in the descriptor.",
+                    t
+                )
+            }
             Self::OutpointNotRecoverable(op, t) => write!(
                 f,
                 "Coin at '{}' is not recoverable with timelock '{}'",
@@ -130,6 +155,31 @@ impl fmt::Display for CommandError {
             }
             Self::RbfError(e) => write!(f, "RBF error: '{}'.", e),
             Self::EmptyFilterList => write!(f, "Filter list is empty, should supply None instead."),
+            Self::UnknownAddress(addr) => {
+                write!(f, "Address '{}' does not belong to this wallet.", addr)
+            }
+            Self::NotADestination(addr) => {
+                write!(f, "Address '{}' is not one of the destinations.", addr)
+            }
+            Self::CoinAlreadyConfirmed(op) => write!(f, "Coin at '{}' is already confirmed.", op),
+            Self::NotInMempool(txid) => {
+                write!(f, "Transaction '{}' is not in the mempool.", txid)
+            }
+            Self::InvalidGapLimit(gap_limit) => write!(
+                f,
+                "Invalid gap limit '{}'. Must be between 1 and {}.",
+                gap_limit, MAX_GAP_LIMIT
+            ),
+            Self::PolicyViolation(e) => write!(f, "Spending policy violation: {}", e),
+            Self::EmptyBroadcastSchedule => write!(
+                f,
+                "A block height or a timestamp to broadcast the transaction at must be given."
+            ),
+            Self::NotScheduled(txid) => write!(
+                f,
+                "Spend transaction '{}' is not scheduled for broadcast.",
+                txid
+            ),
         }
     }
 }
@@ -142,11 +192,17 @@ impl From<SpendCreationError> for CommandError {
     }
 }
 
+impl From<PolicyViolation> for CommandError {
+    fn from(e: PolicyViolation) -> Self {
+        CommandError::PolicyViolation(e)
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum RbfErrorInfo {
     MissingFeerate,
     SuperfluousFeerate,
-    TooLowFeerate(u64, u64),
+    TooLowFeerate(/* sats/kvb */ u64, /* sats/kvb */ u64),
     NotSignaling,
 }
 
@@ -160,7 +216,12 @@ impl fmt::Display for RbfErrorInfo {
                 write!(f, "A feerate must not be provided if creating a cancel. We'll always use the smallest one which satisfies the RBF rules.")
             }
             Self::TooLowFeerate(r, m) => {
-                write!(f, "Feerate {} too low for minimum feerate {}.", r, m)
+                write!(
+                    f,
+                    "Feerate {} too low for minimum feerate {}.",
+                    spend::format_feerate_kvb(r),
+                    spend::format_feerate_kvb(m)
+                )
             }
             Self::NotSignaling => write!(f, "Replacement candidate does not signal for RBF."),
         }
@@ -216,6 +277,292 @@ fn coin_to_candidate(
     }
 }
 
+// All the BIP32 key sources, for either Segwit v0 or Taproot keys, of a PSBT input or output.
+fn key_sources<'a>(
+    bip32_derivation: &'a BTreeMap<secp256k1::PublicKey, bip32::KeySource>,
+    tap_key_origins: &'a BTreeMap<secp256k1::XOnlyPublicKey, (Vec<TapLeafHash>, bip32::KeySource)>,
+) -> Vec<&'a bip32::KeySource> {
+    bip32_derivation
+        .values()
+        .chain(tap_key_origins.values().map(|(_, source)| source))
+        .collect()
+}
+
+// Warnings for the destinations of a spend which are our own addresses that already received coins.
+fn reused_destinations(
+    db_conn: &mut Box<dyn DatabaseConnection>,
+    destinations: &[(SpendOutputAddress, bitcoin::Amount)],
+) -> Vec<spend::CreateSpendWarning> {
+    let mut use_counts: HashMap<bool, HashMap<ChildNumber, u32>> = HashMap::new();
+    destinations
+        .iter()
+        .filter_map(|(dest, _)| {
+            let info = dest.info.as_ref()?;
+            let counts = use_counts
+                .entry(info.is_change)
+                .or_insert_with(|| db_conn.address_use_counts(info.is_change));
+            counts
+                .contains_key(&info.index)
+                .then(|| spend::CreateSpendWarning::AddressReuse(dest.addr.to_string()))
+        })
+        .collect()
+}
+
+// Get the derivation index of this Script and whether it is for the change keychain, if it is
+// ours. If we don't know about it, try the derivation paths it may have been derived at.
+fn spk_derivation<'a>(
+    db_conn: &mut Box<dyn DatabaseConnection>,
+    desc: &descriptors::CoincubeDescriptor,
+    network: bitcoin::Network,
+    secp: &secp256k1::Secp256k1<secp256k1::VerifyOnly>,
+    spk: &bitcoin::Script,
+    der_paths: impl IntoIterator<Item = &'a bip32::DerivationPath>,
+) -> Option<(bip32::ChildNumber, bool)> {
+    bitcoin::Address::from_script(spk, network)
+        .ok()
+        .and_then(|addr| db_conn.derivation_index_by_address(&addr))
+        .or_else(|| desc.spk_derivation(spk, der_paths, secp))
+}
+
+/// Analyze a PSBT against the wallet descriptor `desc`. See [`DaemonControl::analyze_psbt`].
+pub fn analyze_psbt(
+    db_conn: &mut Box<dyn DatabaseConnection>,
+    desc: &descriptors::CoincubeDescriptor,
+    network: bitcoin::Network,
+    secp: &secp256k1::Secp256k1<secp256k1::VerifyOnly>,
+    psbt: &Psbt,
+) -> AnalyzePsbtResult {
+    let tx = &psbt.unsigned_tx;
+    let mut anomalies = Vec::new();
+
+    let policy = desc.policy();
+    let our_fingerprints: HashSet<bip32::Fingerprint> = std::iter::once(policy.primary_path())
+        .chain(policy.recovery_paths().values())
+        .flat_map(|path_info| path_info.thresh_origins().1.into_keys())
+        .collect();
+
+    let outpoints: Vec<_> = tx.input.iter().map(|txin| txin.previous_output).collect();
+    let coins = db_conn.coins(&[], &outpoints);
+    let mut inputs = Vec::with_capacity(tx.input.len());
+    for (i, (txin, psbt_in)) in tx.input.iter().zip(psbt.inputs.iter()).enumerate() {
+        let prev_txo = psbt_in.witness_utxo.clone().or_else(|| {
+            psbt_in
+                .non_witness_utxo
+                .as_ref()
+                .filter(|prev_tx| prev_tx.compute_txid() == txin.previous_output.txid)
+                .and_then(|prev_tx| {
+                    prev_tx
+                        .output
+                        .get(txin.previous_output.vout as usize)
+                        .cloned()
+                })
+        });
+        let coin = coins.get(&txin.previous_output);
+        let derivation = if let Some(coin) = coin {
+            if prev_txo
+                .as_ref()
+                .is_some_and(|txo| txo.value != coin.amount)
+            {
+                anomalies.push(format!(
+                    "The value of the coin spent by input #{} does not match our records.",
+                    i
+                ));
+            }
+            Some((coin.derivation_index, coin.is_change))
+        } else {
+            prev_txo.as_ref().and_then(|txo| {
+                let key_sources = key_sources(&psbt_in.bip32_derivation, &psbt_in.tap_key_origins);
+                spk_derivation(
+                    db_conn,
+                    desc,
+                    network,
+                    secp,
+                    &txo.script_pubkey,
+                    key_sources.iter().map(|(_, der_path)| der_path),
+                )
+            })
+        };
+        // Trust our own records over the PSBT for the value of our coins.
+        let value = coin
+            .map(|c| c.amount)
+            .or_else(|| prev_txo.as_ref().map(|txo| txo.value));
+        if value.is_none() {
+            anomalies.push(format!(
+                "The coin spent by input #{} is unknown, the fee can't be computed.",
+                i
+            ));
+        }
+        if let Some(sighash_type) = psbt_in.sighash_type {
+            if !signer::is_standard_sighash(sighash_type, desc.is_taproot()) {
+                anomalies.push(format!(
+                    "Input #{} uses the non-standard sighash type {}.",
+                    i, sighash_type
+                ));
+            }
+        }
+        inputs.push(AnalyzePsbtInput {
+            outpoint: txin.previous_output,
+            value: value.map(|v| v.to_sat()),
+            ownership: Ownership::new(derivation, prev_txo.is_some()),
+            derivation_index: derivation.map(|(index, _)| index),
+            sighash_type: psbt_in.sighash_type.map(|s| s.to_string()),
+            spend_info: derivation
+                .is_some()
+                .then(|| InputSpendInfo::new(&desc.partial_spend_info_txin(psbt_in, txin))),
+        });
+    }
+
+    let mut outputs = Vec::with_capacity(tx.output.len());
+    for (i, (txo, psbt_out)) in tx.output.iter().zip(psbt.outputs.iter()).enumerate() {
+        let key_sources = key_sources(&psbt_out.bip32_derivation, &psbt_out.tap_key_origins);
+        let derivation = spk_derivation(
+            db_conn,
+            desc,
+            network,
+            secp,
+            &txo.script_pubkey,
+            key_sources.iter().map(|(_, der_path)| der_path),
+        );
+        if derivation.is_none() && !key_sources.is_empty() {
+            if key_sources
+                .iter()
+                .any(|(fg, _)| our_fingerprints.contains(fg))
+            {
+                anomalies.push(format!(
+                    "Output #{} has derivation paths for our keys but does not pay to this wallet.",
+                    i
+                ));
+            } else {
+                anomalies.push(format!(
+                    "Output #{} is marked as change to keys which are not part of this wallet.",
+                    i
+                ));
+            }
+        }
+        outputs.push(AnalyzePsbtOutput {
+            address: bitcoin::Address::from_script(&txo.script_pubkey, network)
+                .ok()
+                .map(|addr| addr.into_unchecked()),
+            value: txo.value.to_sat(),
+            ownership: Ownership::new(derivation, true),
+            derivation_index: derivation.map(|(index, _)| index),
+        });
+    }
+
+    let out_value: u64 = outputs.iter().map(|o| o.value).sum();
+    let fee = inputs
+        .iter()
+        .map(|i| i.value)
+        .sum::<Option<u64>>()
+        .and_then(|in_value| {
+            let fee = in_value.checked_sub(out_value);
+            if fee.is_none() {
+                anomalies.push("The outputs are worth more than the inputs.".to_string());
+            }
+            fee
+        });
+    // If all the inputs are ours assume the largest possible satisfaction for them. Otherwise
+    // we can only give a lower bound.
+    let vsize = if inputs.iter().all(|i| i.spend_info.is_some()) {
+        let use_primary_path = inputs
+            .iter()
+            .filter_map(|i| i.spend_info.as_ref())
+            .all(|info| info.recovery_paths.is_empty());
+        desc.unsigned_tx_max_vbytes(tx, use_primary_path)
+    } else {
+        tx.vsize() as u64
+    };
+    let feerate_kvb = fee.map(|fee| fee * 1_000 / vsize);
+    if fee.is_some_and(|fee| fee > spend::MAX_FEE.to_sat()) {
+        anomalies.push("The fee is higher than the maximum we would set.".to_string());
+    }
+    if feerate_kvb.is_some_and(|feerate| feerate > spend::MAX_FEERATE_KVB) {
+        anomalies.push("The feerate is higher than the maximum we would set.".to_string());
+    }
+
+    AnalyzePsbtResult {
+        txid: tx.compute_txid(),
+        inputs,
+        outputs,
+        fee,
+        vsize,
+        feerate_kvb,
+        anomalies,
+    }
+}
+
+/// What the spending policy is evaluated against for this PSBT: the value it sends out of the
+/// wallet and the value previous transactions did. Transactions it conflicts with, such as the
+/// one it replaces, are not accounted for.
+pub fn spend_context(
+    db_conn: &mut Box<dyn DatabaseConnection>,
+    desc: &descriptors::CoincubeDescriptor,
+    network: bitcoin::Network,
+    secp: &secp256k1::Secp256k1<secp256k1::VerifyOnly>,
+    psbt: &Psbt,
+) -> SpendContext {
+    let is_ours = |ownership: Ownership| matches!(ownership, Ownership::Ours | Ownership::Change);
+    let analysis = analyze_psbt(db_conn, desc, network, secp, psbt);
+    let spent: u64 = analysis
+        .inputs
+        .iter()
+        .filter(|txin| is_ours(txin.ownership))
+        .filter_map(|txin| txin.value)
+        .sum();
+    let kept: u64 = analysis
+        .outputs
+        .iter()
+        .filter(|txout| is_ours(txout.ownership))
+        .map(|txout| txout.value)
+        .sum();
+    let external_outputs = analysis
+        .outputs
+        .iter()
+        .zip(psbt.unsigned_tx.output.iter())
+        .filter(|(txout, _)| !is_ours(txout.ownership))
+        .map(|(_, txo)| txo.clone())
+        .collect();
+
+    let coins = db_conn.coins(&[], &[]);
+    let mut excluded: HashSet<bitcoin::Txid> = psbt
+        .unsigned_tx
+        .input
+        .iter()
+        .filter_map(|txin| coins.get(&txin.previous_output)?.spend_txid)
+        .collect();
+    excluded.insert(psbt.unsigned_tx.compute_txid());
+    // The net value each previous transaction spending our coins sent out of the wallet.
+    let mut past_outflows = HashMap::<bitcoin::Txid, (Option<u32>, i64)>::new();
+    for coin in coins.values() {
+        if let Some(txid) = coin.spend_txid.filter(|txid| !excluded.contains(txid)) {
+            let entry = past_outflows
+                .entry(txid)
+                .or_insert((coin.spend_block.map(|block| block.time), 0));
+            entry.1 += coin.amount.to_sat() as i64;
+        }
+    }
+    for coin in coins.values() {
+        if let Some(entry) = past_outflows.get_mut(&coin.outpoint.txid) {
+            entry.1 -= coin.amount.to_sat() as i64;
+        }
+    }
+
+    SpendContext {
+        external_outputs,
+        outflow: spent.saturating_sub(kept),
+        past_outflows: past_outflows
+            .into_values()
+            .map(|(time, value)| (time, value.max(0) as u64))
+            .collect(),
+        now: SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .expect("time measured now cannot be before unix epoch")
+            .as_secs()
+            .try_into()
+            .expect("timestamp must fit in u32"),
+    }
+}
+
 impl DaemonControl {
     // Get the derived descriptor for this coin
     fn derived_desc(&self, coin: &Coin) -> descriptors::DerivedSinglePathCoincubeDesc {
@@ -313,6 +660,21 @@ impl DaemonControl {
             .expect("block height must fit in u32");
         spend::anti_fee_sniping_locktime(now, tip_height, tip_time)
     }
+
+    // What the spending policy is evaluated against for this PSBT.
+    fn spend_context(
+        &self,
+        db_conn: &mut Box<dyn DatabaseConnection>,
+        psbt: &Psbt,
+    ) -> SpendContext {
+        spend_context(
+            db_conn,
+            &self.config.main_descriptor,
+            self.config.bitcoin_config.network,
+            &self.secp,
+            psbt,
+        )
+    }
 }
 
 impl DaemonControl {
@@ -326,6 +688,28 @@ impl DaemonControl {
         let rescan_progress = wallet
             .rescan_timestamp
             .map(|_| self.bitcoin.rescan_progress().unwrap_or(1.0));
+
+        // Warn if the number of addresses revealed since the last one to be used gets close to
+        // the gap limit. Another wallet restored from the same descriptor would not find coins
+        // received past the gap limit.
+        let mut warnings = Vec::new();
+        for (kind, index, change) in [
+            ("receive", receive_index, false),
+            ("change", change_index, true),
+        ] {
+            let unused = db_conn
+                .highest_used_index(change)
+                .map(|used| index.saturating_sub(used.into()))
+                .unwrap_or(index);
+            if unused >= wallet.gap_limit.saturating_mul(GAP_LIMIT_WARNING_PERCENT) / 100 {
+                warnings.push(format!(
+                    "{} {} addresses were revealed since the last one to receive coins, \
+                     close to the gap limit of {}. Consider increasing the gap limit.",
+                    unused, kind, wallet.gap_limit
+                ));
+            }
+        }
+
         GetInfoResult {
             version: VERSION.to_string(),
             network: self.config.bitcoin_config.network,
@@ -339,7 +723,20 @@ impl DaemonControl {
             last_poll_timestamp: wallet.last_poll_timestamp,
             receive_index,
             change_index,
+            gap_limit: wallet.gap_limit,
+            warnings,
+            spending_policy: self.config.spending_policy.clone(),
+        }
+    }
+
+    /// Set the number of addresses to watch past the highest of our derivation indexes.
+    pub fn set_gap_limit(&self, gap_limit: u32) -> Result<(), CommandError> {
+        if gap_limit == 0 || gap_limit > MAX_GAP_LIMIT {
+            return Err(CommandError::InvalidGapLimit(gap_limit));
         }
+        let mut db_conn = self.db.connection();
+        db_conn.set_gap_limit(gap_limit, &self.secp);
+        Ok(())
     }
 
     /// Get a new deposit address. This will always generate a new deposit address, regardless of
@@ -423,6 +820,8 @@ impl DaemonControl {
         let mut db_conn = self.db.connection();
         let receive_index: u32 = db_conn.receive_index().into();
         let change_index: u32 = db_conn.change_index().into();
+        let receive_use_counts = db_conn.address_use_counts(false);
+        let change_use_counts = db_conn.address_use_counts(true);
 
         // If a start index isn't provided, we derive from index 0. Make sure the provided index is
         // unhardened.
@@ -469,6 +868,8 @@ impl DaemonControl {
                     index,
                     receive,
                     change,
+                    receive_reused: receive_use_counts.get(&child).is_some_and(|c| *c > 1),
+                    change_reused: change_use_counts.get(&child).is_some_and(|c| *c > 1),
                 })
             })
             .collect();
@@ -517,13 +918,7 @@ impl DaemonControl {
         let start_index = start_index.unwrap_or(last_revealed).min(last_revealed);
 
         // Count how many times each (used) address has been used.
-        let mut used_counts = HashMap::<ChildNumber, u32>::new();
-        // TODO: consider adding DB method to get coins or used indices by index range.
-        for coin in db_conn.coins(&[], &[]).values() {
-            if coin.is_change == is_change && coin.derivation_index <= start_index {
-                *used_counts.entry(coin.derivation_index).or_insert(0) += 1;
-            }
-        }
+        let used_counts = db_conn.address_use_counts(is_change);
 
         let mut addresses = Vec::<_>::with_capacity(limit);
         let mut continue_from = None;
@@ -556,11 +951,13 @@ impl DaemonControl {
         }
         for (index, address) in derived_addresses {
             let label = labels.get(&address.to_string()).cloned();
+            let used_count = *used_counts.get(&index).unwrap_or(&0);
             addresses.push(ListRevealedAddressesEntry {
                 index,
                 address,
                 label,
-                used_count: *used_counts.get(&index).unwrap_or(&0),
+                used_count,
+                reused: used_count > 1,
             });
         }
         Ok(ListRevealedAddressesResult {
@@ -579,48 +976,64 @@ impl DaemonControl {
         let coins: Vec<ListCoinsEntry> = db_conn
             .coins(statuses, outpoints)
             .into_values()
-            .map(|coin| {
-                let Coin {
-                    amount,
-                    outpoint,
-                    block_info,
-                    spend_txid,
-                    spend_block,
-                    is_immature,
-                    is_change,
-                    is_from_self,
-                    derivation_index,
-                    ..
-                } = coin;
-                let spend_info = spend_txid.map(|txid| LCSpendInfo {
-                    txid,
-                    height: spend_block.map(|b| b.height),
-                });
-                let block_height = block_info.map(|b| b.height);
-                let address = self
-                    .derived_desc(&coin)
-                    .address(self.config.bitcoin_config.network);
-                ListCoinsEntry {
-                    address,
-                    amount,
-                    derivation_index,
-                    outpoint,
-                    block_height,
-                    spend_info,
-                    is_immature,
-                    is_change,
-                    is_from_self,
-                }
-            })
+            .map(|coin| self.list_coins_entry(coin))
             .collect();
         ListCoinsResult { coins }
     }
 
+    /// Get the coins matching all the criteria of the given filter, whether spent or not.
+    pub fn search_coins(&self, filter: &SearchFilter) -> ListCoinsResult {
+        let mut db_conn = self.db.connection();
+        let coins: Vec<ListCoinsEntry> = db_conn
+            .search_coins(filter)
+            .into_values()
+            .map(|coin| self.list_coins_entry(coin))
+            .collect();
+        ListCoinsResult { coins }
+    }
+
+    fn list_coins_entry(&self, coin: Coin) -> ListCoinsEntry {
+        let Coin {
+            amount,
+            outpoint,
+            block_info,
+            spend_txid,
+            spend_block,
+            is_immature,
+            is_change,
+            is_from_self,
+            derivation_index,
+            ..
+        } = coin;
+        let spend_info = spend_txid.map(|txid| LCSpendInfo {
+            txid,
+            height: spend_block.map(|b| b.height),
+        });
+        let block_height = block_info.map(|b| b.height);
+        let address = self
+            .derived_desc(&coin)
+            .address(self.config.bitcoin_config.network);
+        ListCoinsEntry {
+            address,
+            amount,
+            derivation_index,
+            outpoint,
+            block_height,
+            spend_info,
+            is_immature,
+            is_change,
+            is_from_self,
+        }
+    }
+
+    /// Create a transaction paying to `destinations`, at the given feerate in sats/kvb.
     pub fn create_spend(
         &self,
         destinations: &HashMap<bitcoin::Address<bitcoin::address::NetworkUnchecked>, u64>,
+        subtract_fee_from: &[bitcoin::Address<bitcoin::address::NetworkUnchecked>],
+        op_return: Option<&[u8]>,
         coins_outpoints: &[bitcoin::OutPoint],
-        feerate_vb: u64,
+        feerate_kvb: u64,
         change_address: Option<bitcoin::Address<bitcoin::address::NetworkUnchecked>>,
     ) -> Result<CreateSpendResult, CommandError> {
         let is_self_send = destinations.is_empty();
@@ -628,8 +1041,8 @@ impl DaemonControl {
         if is_self_send && coins_outpoints.is_empty() {
             return Err(CommandError::NoOutpointForSelfSend);
         }
-        if feerate_vb < 1 {
-            return Err(CommandError::InvalidFeerate(feerate_vb));
+        if feerate_kvb < spend::MIN_FEERATE_KVB {
+            return Err(CommandError::InvalidFeerate(feerate_kvb));
         }
         let mut db_conn = self.db.connection();
         let mut tx_getter = DbTxGetter::new(&self.db);
@@ -643,6 +1056,19 @@ impl DaemonControl {
             destinations_checked.push((address, amount));
         }
 
+        // The destinations whose amount the fee should be deducted from.
+        let mut subtract_fee_from_indexes = Vec::with_capacity(subtract_fee_from.len());
+        for address in subtract_fee_from {
+            let address = self.validate_address(address.clone())?;
+            let index = destinations_checked
+                .iter()
+                .position(|(dest, _)| dest.addr == address)
+                .ok_or(CommandError::NotADestination(address))?;
+            if !subtract_fee_from_indexes.contains(&index) {
+                subtract_fee_from_indexes.push(index);
+            }
+        }
+
         // The change address to be used if a change output needs to be created. It may be
         // specified by the caller (for instance for the purpose of a sweep, or to avoid us
         // creating a new change address on every call).
@@ -748,14 +1174,16 @@ impl DaemonControl {
         let CreateSpendRes {
             psbt,
             has_change,
-            warnings,
+            mut warnings,
         } = match create_spend(
             &self.config.main_descriptor,
             &self.secp,
             &mut tx_getter,
             &destinations_checked,
+            &subtract_fee_from_indexes,
+            op_return,
             &candidate_coins,
-            SpendTxFees::Regular(feerate_vb),
+            SpendTxFees::Regular(feerate_kvb),
             change_address,
             locktime,
         ) {
@@ -767,6 +1195,11 @@ impl DaemonControl {
                 return Err(e.into());
             }
         };
+        if let Some(policy) = &self.config.spending_policy {
+            let ctx = self.spend_context(&mut db_conn, &psbt);
+            policy.check_spend(self.config.bitcoin_config.network, &ctx)?;
+        }
+        warnings.extend(reused_destinations(&mut db_conn, &destinations_checked));
         for (addr, _) in destinations_checked {
             self.maybe_increase_last_deriv_index(&mut db_conn, &addr.info);
         }
@@ -857,6 +1290,155 @@ impl DaemonControl {
         }
     }
 
+    /// Import labels in BIP-0329 format, typically exported from another wallet.
+    ///
+    /// Labels for addresses, coins and transactions which are not ours are not imported and
+    /// reported as foreign. Labels on our own xpubs are reported along with the fingerprint of
+    /// the key so they can be used as key aliases. Record types we cannot store (public keys,
+    /// inputs) are reported as unsupported. Since we have no notion of frozen coins, outputs
+    /// marked as not spendable are reported too.
+    pub fn import_labels(
+        &self,
+        labels: &[bip329::Label],
+        conflict: LabelConflict,
+    ) -> ImportLabelsResult {
+        let network = self.config.bitcoin_config.network;
+        let mut db_conn = self.db.connection();
+        let mut res = ImportLabelsResult::default();
+
+        let mut wallet_keys = Vec::new();
+        self.config
+            .main_descriptor
+            .descriptor()
+            .for_each_key(|key| {
+                if let DescriptorPublicKey::MultiXPub(xkey) = key {
+                    wallet_keys.push((key.master_fingerprint(), xkey.xkey.to_string()));
+                }
+                true
+            });
+
+        // Later entries for the same item take precedence, as when applied in order.
+        let mut candidates: HashMap<LabelItem, String> = HashMap::new();
+        for label in labels {
+            let (kind, ref_, spendable) = match serde_json::to_value(label) {
+                Ok(value) => (
+                    value["type"].as_str().unwrap_or_default().to_string(),
+                    value["ref"].as_str().unwrap_or_default().to_string(),
+                    value["spendable"].as_bool().unwrap_or(true),
+                ),
+                Err(e) => {
+                    log::error!("Failed to serialize BIP-0329 label: {}", e);
+                    continue;
+                }
+            };
+            match kind.as_str() {
+                "xpub" => {
+                    // Tolerate key origins and derivation steps around the xpub.
+                    let xpub = ref_.rsplit(']').next().unwrap_or_default();
+                    let xpub = xpub.split('/').next().unwrap_or_default();
+                    match (
+                        wallet_keys.iter().find(|(_, key)| key == xpub),
+                        label_value(&ref_, label),
+                    ) {
+                        (Some((fingerprint, _)), Some(value)) => res.keys.push(KeyLabel {
+                            fingerprint: *fingerprint,
+                            label: value,
+                        }),
+                        (Some(_), None) => {}
+                        (None, _) => res.foreign.push(ref_),
+                    }
+                }
+                "tx" | "addr" | "output" => {
+                    if !spendable {
+                        res.unspendable.push(ref_.clone());
+                    }
+                    match LabelItem::from_bip329(label, network) {
+                        Some((item, value)) => {
+                            candidates.insert(item, value);
+                        }
+                        None => {
+                            // Entries without a label only carry the spendable flag.
+                            if label_value(&ref_, label).is_some() {
+                                res.foreign.push(ref_);
+                            }
+                        }
+                    }
+                }
+                _ => res.unsupported.push(ref_),
+            }
+        }
+
+        // Only keep the items belonging to this wallet.
+        let outpoints: Vec<bitcoin::OutPoint> = candidates
+            .keys()
+            .filter_map(|item| match item {
+                LabelItem::OutPoint(op) => Some(*op),
+                _ => None,
+            })
+            .collect();
+        let coins = db_conn.coins_by_outpoints(&outpoints);
+        let txids: Vec<bitcoin::Txid> = candidates
+            .keys()
+            .filter_map(|item| match item {
+                LabelItem::Txid(txid) => Some(*txid),
+                _ => None,
+            })
+            .collect();
+        let wallet_txids: HashSet<bitcoin::Txid> = db_conn
+            .list_wallet_transactions(&txids)
+            .into_iter()
+            .map(|(tx, _, _)| tx.compute_txid())
+            .collect();
+        let mut owned = HashMap::new();
+        for (item, value) in candidates {
+            let is_ours = match &item {
+                LabelItem::Address(addr) => db_conn.derivation_index_by_address(addr).is_some(),
+                LabelItem::OutPoint(op) => coins.contains_key(op),
+                LabelItem::Txid(txid) => {
+                    wallet_txids.contains(txid) || db_conn.spend_tx(txid).is_some()
+                }
+            };
+            if is_ours {
+                owned.insert(item, value);
+            } else {
+                res.foreign.push(item.to_string());
+            }
+        }
+
+        let existing = db_conn.labels(&owned.keys().cloned().collect());
+        let mut updates = HashMap::new();
+        for (item, value) in owned {
+            let value = truncate_label(value);
+            match existing.get(&item.to_string()) {
+                None => {
+                    res.imported += 1;
+                    updates.insert(item, Some(value));
+                }
+                Some(current) if *current == value => res.unchanged += 1,
+                Some(current) => match conflict {
+                    LabelConflict::Skip => res.skipped += 1,
+                    LabelConflict::Overwrite => {
+                        res.updated += 1;
+                        updates.insert(item, Some(value));
+                    }
+                    LabelConflict::Merge => {
+                        if current.contains(&value) {
+                            res.unchanged += 1;
+                        } else {
+                            res.updated += 1;
+                            updates
+                                .insert(item, Some(truncate_label(format!("{current}; {value}"))));
+                        }
+                    }
+                },
+            }
+        }
+        db_conn.update_labels(&updates);
+
+        res.foreign.sort();
+        res
+    }
+
     pub fn list_spend(
         &self,
         txids: Option<Vec<bitcoin::Txid>>,
@@ -873,13 +1455,17 @@ impl DaemonControl {
         let txids_set: Option<HashSet<_>> = txids.as_ref().map(|list| list.iter().collect());
         let spend_txs = spend_psbts
             .into_iter()
-            .filter_map(|(psbt, updated_at)| {
+            .filter_map(|(psbt, updated_at, approved_at)| {
                 if let Some(set) = &txids_set {
                     if !set.contains(&psbt.unsigned_tx.compute_txid()) {
                         return None;
                     }
                 }
-                Some(ListSpendEntry { psbt, updated_at })
+                Some(ListSpendEntry {
+                    psbt,
+                    updated_at,
+                    approved_at,
+                })
             })
             .collect();
         Ok(ListSpendResult { spend_txs })
@@ -890,6 +1476,34 @@ impl DaemonControl {
         db_conn.delete_spend(txid);
     }
 
+    /// Approve this stored Spend transaction, as the spending policy may require before it is
+    /// broadcast. The daemon has no notion of operators: it is up to the deployment to restrict
+    /// this command to another one than the spend creator.
+    pub fn approve_spend(&self, txid: &bitcoin::Txid) -> Result<(), CommandError> {
+        let mut db_conn = self.db.connection();
+        if db_conn.spend_tx(txid).is_none() {
+            return Err(CommandError::UnknownSpend(*txid));
+        }
+        db_conn.approve_spend(txid);
+        Ok(())
+    }
+
+    /// Analyze a PSBT against our descriptor. The PSBT does not need to be stored in database.
+    ///
+    /// Each input and output is classified as ours (with its derivation index), change, external
+    /// or unknown. For our inputs, the signatures present and missing for each available spending
+    /// path are reported. Anything suspicious about the PSBT is reported as an anomaly.
+    pub fn analyze_psbt(&self, psbt: &Psbt) -> AnalyzePsbtResult {
+        let mut db_conn = self.db.connection();
+        analyze_psbt(
+            &mut db_conn,
+            &self.config.main_descriptor,
+            self.config.bitcoin_config.network,
+            &self.secp,
+            psbt,
+        )
+    }
+
     /// Finalize and broadcast this stored Spend transaction.
     pub fn broadcast_spend(&self, txid: &bitcoin::Txid) -> Result<(), CommandError> {
         let mut db_conn = self.db.connection();
@@ -899,6 +1513,16 @@ impl DaemonControl {
         let mut spend_psbt = db_conn
             .spend_tx(txid)
             .ok_or(CommandError::UnknownSpend(*txid))?;
+        if let Some(policy) = &self.config.spending_policy {
+            let (created_at, approved_at) = db_conn.spend_tx_timestamps(txid).unwrap_or_default();
+            let ctx = self.spend_context(&mut db_conn, &spend_psbt);
+            policy.check_broadcast(
+                self.config.bitcoin_config.network,
+                &ctx,
+                created_at,
+                approved_at,
+            )?;
+        }
         spend_psbt.finalize_mut(&self.secp).map_err(|e| {
             CommandError::SpendFinalization(
                 e.into_iter()
@@ -928,6 +1552,86 @@ impl DaemonControl {
         Ok(())
     }
 
+    /// Schedule the broadcast of a fully signed Spend transaction once the block chain reaches
+    /// `block_height` and the clock `timestamp`, whichever of the two are given. The poller
+    /// attempts to broadcast it on each poll from then on, until it succeeds.
+    ///
+    /// The spending policy is checked now, and again by the poller before broadcasting. A
+    /// broadcast delay imposed by the policy postpones the scheduled time rather than failing.
+    pub fn schedule_broadcast(
+        &self,
+        txid: &bitcoin::Txid,
+        block_height: Option<i32>,
+        mut timestamp: Option<u32>,
+    ) -> Result<(), CommandError> {
+        if block_height.is_none() && timestamp.is_none() {
+            return Err(CommandError::EmptyBroadcastSchedule);
+        }
+        let mut db_conn = self.db.connection();
+
+        let mut spend_psbt = db_conn
+            .spend_tx(txid)
+            .ok_or(CommandError::UnknownSpend(*txid))?;
+        if let Some(policy) = &self.config.spending_policy {
+            let (created_at, approved_at) = db_conn.spend_tx_timestamps(txid).unwrap_or_default();
+            let ctx = self.spend_context(&mut db_conn, &spend_psbt);
+            let broadcast_at = policy.check_schedule(
+                self.config.bitcoin_config.network,
+                &ctx,
+                created_at,
+                approved_at,
+            )?;
+            if let Some(broadcast_at) = broadcast_at.filter(|t| *t > ctx.now) {
+                timestamp = Some(timestamp.unwrap_or(0).max(broadcast_at));
+            }
+        }
+        // Make sure it can be broadcast as is.
+        spend_psbt.finalize_mut(&self.secp).map_err(|e| {
+            CommandError::SpendFinalization(
+                e.into_iter()
+                    .next()
+                    .map(|e| e.to_string())
+                    .unwrap_or_default(),
+            )
+        })?;
+
+        db_conn.schedule_broadcast(txid, block_height, timestamp);
+        Ok(())
+    }
+
+    /// List the Spend transactions scheduled for broadcast, including those which were already.
+    pub fn list_scheduled_broadcasts(&self) -> ListScheduledBroadcastsResult {
+        let mut db_conn = self.db.connection();
+        let broadcasts = db_conn
+            .scheduled_broadcasts()
+            .into_iter()
+            .map(|entry| ScheduledBroadcastEntry {
+                txid: entry.txid,
+                block_height: entry.block_height,
+                timestamp: entry.timestamp,
+                created_at: entry.created_at,
+                broadcast_at: entry.broadcast_at,
+                last_error: entry.last_error,
+                failed_at: entry.failed_at,
+            })
+            .collect();
+        ListScheduledBroadcastsResult { broadcasts }
+    }
+
+    /// Remove a Spend transaction from the broadcast queue.
+    pub fn cancel_scheduled_broadcast(&self, txid: &bitcoin::Txid) -> Result<(), CommandError> {
+        let mut db_conn = self.db.connection();
+        if !db_conn
+            .scheduled_broadcasts()
+            .iter()
+            .any(|entry| &entry.txid == txid)
+        {
+            return Err(CommandError::NotScheduled(*txid));
+        }
+        db_conn.cancel_broadcast(txid);
+        Ok(())
+    }
+
     /// Create PSBT to replace the given transaction using RBF.
     ///
     /// `txid` must either point to a PSBT in our database (not necessarily broadcast) or an
@@ -947,19 +1651,19 @@ impl DaemonControl {
     /// - the RBF transaction may include additional confirmed coins as inputs if required
     ///   in order to pay the higher fee (this applies also when replacing a self-send).
     ///
-    /// `feerate_vb` is the target feerate for the RBF transaction (in sat/vb). If `None`, it will be set
-    /// to 1 sat/vb larger than the feerate of the previous transaction, which is the minimum value allowed
-    /// when using RBF.
+    /// `feerate_kvb` is the target feerate for the RBF transaction (in sats/kvb). If `None`, it will be
+    /// set to 1 sat/vb larger than the feerate of the previous transaction, which is the minimum value
+    /// allowed when using RBF.
     pub fn rbf_psbt(
         &self,
         txid: &bitcoin::Txid,
         is_cancel: bool,
-        feerate_vb: Option<u64>,
+        feerate_kvb: Option<u64>,
     ) -> Result<CreateSpendResult, CommandError> {
         let mut db_conn = self.db.connection();
         let mut tx_getter = DbTxGetter::new(&self.db);
 
-        if is_cancel && feerate_vb.is_some() {
+        if is_cancel && feerate_kvb.is_some() {
             return Err(CommandError::RbfError(RbfErrorInfo::SuperfluousFeerate));
         }
 
@@ -1002,14 +1706,16 @@ impl DaemonControl {
         // rules #3, #4 and #6 (see
         // https://github.com/bitcoin/bitcoin/blob/master/doc/policy/mempool-replacements.md). By
         // default (ie if the transaction we are replacing was dropped from the mempool) there is
-        // no minimum absolute fee and the minimum feerate is 1, the minimum relay feerate.
-        let (min_feerate_vb, descendant_fees) = self
+        // no minimum absolute fee and the minimum feerate is the minimum relay feerate.
+        let (min_feerate_kvb, descendant_fees) = self
             .bitcoin
             .mempool_spenders(&prev_outpoints)
             .into_iter()
             .fold(
-                (1, bitcoin::Amount::from_sat(0)),
+                (spend::MIN_FEERATE_KVB, bitcoin::Amount::from_sat(0)),
                 |(min_feerate, descendant_fee), entry| {
+                    // Replacements are required to pay at least 1 sat/vb more than the
+                    // (truncated) feerate of the transactions they replace.
                     let entry_feerate = entry
                         .fees
                         .base
@@ -1017,6 +1723,7 @@ impl DaemonControl {
                         .expect("Can't have a null vsize or tx would be invalid")
                         .to_sat()
                         .checked_add(1)
+                        .and_then(|feerate_vb| feerate_vb.checked_mul(1_000))
                         .expect("Can't overflow or tx would be invalid");
                     (
                         std::cmp::max(min_feerate, entry_feerate),
@@ -1026,21 +1733,22 @@ impl DaemonControl {
             );
         // Check replacement transaction's target feerate, if set, is high enough,
         // and otherwise set it to the min feerate found above.
-        let feerate_vb = if is_cancel {
-            min_feerate_vb
+        let feerate_kvb = if is_cancel {
+            min_feerate_kvb
         } else {
-            feerate_vb.ok_or(CommandError::RbfError(RbfErrorInfo::MissingFeerate))?
+            feerate_kvb.ok_or(CommandError::RbfError(RbfErrorInfo::MissingFeerate))?
         };
-        if feerate_vb < min_feerate_vb {
+        if feerate_kvb < min_feerate_kvb {
             return Err(CommandError::RbfError(RbfErrorInfo::TooLowFeerate(
-                feerate_vb,
-                min_feerate_vb,
+                feerate_kvb,
+                min_feerate_kvb,
             )));
         }
         // Get info about prev outputs to determine replacement outputs.
         let prev_derivs: Vec<_> = prev_tx
             .output
             .iter()
+            .filter(|txo| !txo.script_pubkey.is_op_return())
             .map(|txo| {
                 let address = bitcoin::Address::from_script(
                     &txo.script_pubkey,
@@ -1054,6 +1762,15 @@ impl DaemonControl {
                 )
             })
             .collect();
+        // If not cancel, the replacement carries the same data as the previous transaction.
+        let prev_op_return = if !is_cancel {
+            prev_tx
+                .output
+                .iter()
+                .find_map(|txo| spend::op_return_data(&txo.script_pubkey))
+        } else {
+            None
+        };
         // Set the previous change address to that of the change output with the largest value
         // and then largest index.
         let prev_change_address = prev_derivs
@@ -1140,8 +1857,10 @@ impl DaemonControl {
                 &self.secp,
                 &mut tx_getter,
                 &destinations,
+                &[],
+                prev_op_return.as_deref(),
                 &candidate_coins,
-                SpendTxFees::Rbf(feerate_vb, replaced_fee),
+                SpendTxFees::Rbf(feerate_kvb, replaced_fee),
                 change_address.clone(),
                 locktime,
             ) {
@@ -1185,30 +1904,142 @@ impl DaemonControl {
         }
     }
 
-    /// Trigger a rescan of the block chain for transactions involving our main descriptor between
-    /// the given date and the current tip.
-    /// The date must be after the genesis block time and before the current tip blocktime.
-    pub fn start_rescan(&mut self, timestamp: u32) -> Result<(), CommandError> {
+    /// Create a transaction spending the given unconfirmed coins back to the wallet, paying a fee
+    /// such that the package made of it and its unconfirmed ancestors reaches the `feerate_kvb`
+    /// (in sats/kvb) target. This "Child Pays For Parent" allows bumping the fee of transactions
+    /// that can't be replaced, such as incoming payments.
+    ///
+    /// Confirmed coins may be added as inputs if the given ones are not sufficient to pay for the
+    /// package.
+    pub fn cpfp(
+        &self,
+        outpoints: &[bitcoin::OutPoint],
+        feerate_kvb: u64,
+    ) -> Result<CreateSpendResult, CommandError> {
+        if outpoints.is_empty() {
+            return Err(CommandError::NoOutpointForSelfSend);
+        }
+        if feerate_kvb < spend::MIN_FEERATE_KVB {
+            return Err(CommandError::InvalidFeerate(feerate_kvb));
+        }
         let mut db_conn = self.db.connection();
-        let genesis_timestamp = self.bitcoin.genesis_block_timestamp();
+        let mut tx_getter = DbTxGetter::new(&self.db);
 
-        let future_timestamp = self
-            .bitcoin
-            .tip_time()
-            .map(|t| timestamp >= t)
-            .unwrap_or(false);
-        if timestamp < genesis_timestamp || future_timestamp {
-            return Err(CommandError::InsaneRescanTimestamp(timestamp));
-        }
-        if db_conn.rescan_timestamp().is_some() || self.bitcoin.rescan_progress().is_some() {
-            return Err(CommandError::AlreadyRescanning);
+        // The coins to bump must all be unconfirmed outputs of transactions in the mempool. They
+        // are all mandatory candidates, along with information about their ancestors for coin
+        // selection to pay for them.
+        let coins = db_conn.coins(&[], outpoints);
+        let mut parent_txids = HashSet::new();
+        let mut candidate_coins = Vec::with_capacity(outpoints.len());
+        for op in outpoints {
+            let coin = coins.get(op).ok_or(CommandError::UnknownOutpoint(*op))?;
+            if coin.is_spent() {
+                return Err(CommandError::AlreadySpent(*op));
+            }
+            if coin.is_immature {
+                return Err(CommandError::ImmatureCoinbase(*op));
+            }
+            if coin.block_info.is_some() {
+                return Err(CommandError::CoinAlreadyConfirmed(*op));
+            }
+            let entry = self
+                .bitcoin
+                .mempool_entry(&op.txid)
+                .ok_or(CommandError::NotInMempool(op.txid))?;
+            // Only account for the ancestors once if several coins share them.
+            let ancestor_info = parent_txids.insert(op.txid).then(|| AncestorInfo {
+                vsize: entry.ancestor_vsize,
+                fee: entry
+                    .fees
+                    .ancestor
+                    .to_sat()
+                    .try_into()
+                    .expect("fee in sat should fit in u32"),
+            });
+            candidate_coins.push(coin_to_candidate(
+                coin,
+                /*must_select=*/ true,
+                /*sequence=*/ None,
+                ancestor_info,
+            ));
         }
+        candidate_coins.extend(
+            db_conn
+                .coins(&[CoinStatus::Confirmed], &[])
+                .values()
+                .map(|c| {
+                    coin_to_candidate(
+                        c, /*must_select=*/ false, /*sequence=*/ None,
+                        /*ancestor_info=*/ None,
+                    )
+                }),
+        );
 
-        // TODO: there is a race with the above check for whether the backend is already
-        // rescanning. This could make us crash with the bitcoind backend if someone triggered a
-        // rescan of the wallet just after we checked above and did now.
-        self.bitcoin
-            .start_rescan(&self.config.main_descriptor, timestamp)
+        let change_address = self.next_change_addr(&mut db_conn);
+        let change_info = change_address.info;
+        let locktime = self.anti_fee_sniping_locktime();
+        let CreateSpendRes {
+            psbt,
+            has_change,
+            warnings,
+        } = match create_spend(
+            &self.config.main_descriptor,
+            &self.secp,
+            &mut tx_getter,
+            &[], // No destination, only the change address.
+            &[],
+            None,
+            &candidate_coins,
+            SpendTxFees::Regular(feerate_kvb),
+            change_address,
+            locktime,
+        ) {
+            Ok(res) => res,
+            Err(SpendCreationError::CoinSelection(e)) => {
+                return Ok(CreateSpendResult::InsufficientFunds { missing: e.missing });
+            }
+            Err(e) => {
+                return Err(e.into());
+            }
+        };
+        if let Some(policy) = &self.config.spending_policy {
+            let ctx = self.spend_context(&mut db_conn, &psbt);
+            policy.check_spend(self.config.bitcoin_config.network, &ctx)?;
+        }
+        if has_change {
+            self.maybe_increase_last_deriv_index(&mut db_conn, &change_info);
+        }
+
+        Ok(CreateSpendResult::Success {
+            psbt,
+            warnings: warnings.iter().map(|w| w.to_string()).collect(),
+        })
+    }
+
+    /// Trigger a rescan of the block chain for transactions involving our main descriptor between
+    /// the given date and the current tip.
+    /// The date must be after the genesis block time and before the current tip blocktime.
+    pub fn start_rescan(&mut self, timestamp: u32) -> Result<(), CommandError> {
+        let mut db_conn = self.db.connection();
+        let genesis_timestamp = self.bitcoin.genesis_block_timestamp();
+
+        let future_timestamp = self
+            .bitcoin
+            .tip_time()
+            .map(|t| timestamp >= t)
+            .unwrap_or(false);
+        if timestamp < genesis_timestamp || future_timestamp {
+            return Err(CommandError::InsaneRescanTimestamp(timestamp));
+        }
+        if db_conn.rescan_timestamp().is_some() || self.bitcoin.rescan_progress().is_some() {
+            return Err(CommandError::AlreadyRescanning);
+        }
+
+        // TODO: there is a race with the above check for whether the backend is already
+        // rescanning. This could make us crash with the bitcoind backend if someone triggered a
+        // rescan of the wallet just after we checked above and did now.
+        self.bitcoin
+            .start_rescan(&self.config.main_descriptor, timestamp)
             .map_err(CommandError::RescanTrigger)?;
         db_conn.set_rescan(timestamp);
 
@@ -1228,6 +2059,20 @@ impl DaemonControl {
         self.list_transactions(&txids)
     }
 
+    /// search_transactions retrieves a limited list of the transactions creating or spending one
+    /// of our coins which match the given filter. Unconfirmed ones come first, then the most
+    /// recent ones.
+    pub fn search_transactions(&self, filter: &SearchFilter, limit: u64) -> ListTransactionsResult {
+        let txids = self.db.connection().search_txids(filter, limit);
+        let mut res = self.list_transactions(&txids);
+        // Keep the order of the search.
+        res.transactions.sort_by_key(|tx| {
+            let txid = tx.tx.compute_txid();
+            txids.iter().position(|id| *id == txid)
+        });
+        res
+    }
+
     /// list_transactions retrieves the transactions with the given txids.
     pub fn list_transactions(&self, txids: &[bitcoin::Txid]) -> ListTransactionsResult {
         let transactions = self
@@ -1240,8 +2085,84 @@ impl DaemonControl {
         ListTransactionsResult { transactions }
     }
 
+    /// Get the wallet balance after each confirmed transaction creating or spending one of
+    /// our coins, from the oldest to the most recent one.
+    pub fn get_balance_history(&self) -> GetBalanceHistoryResult {
+        let mut db_conn = self.db.connection();
+        // The net change of the balance for every transaction involving our coins.
+        let mut deltas = HashMap::<bitcoin::Txid, i64>::new();
+        for coin in db_conn.coins(&[], &[]).into_values() {
+            let value = coin.amount.to_sat() as i64;
+            *deltas.entry(coin.outpoint.txid).or_default() += value;
+            if let Some(txid) = coin.spend_txid {
+                *deltas.entry(txid).or_default() -= value;
+            }
+        }
+        let txids: Vec<_> = deltas.keys().copied().collect();
+        let mut confirmed: Vec<_> = db_conn
+            .list_wallet_transactions(&txids)
+            .into_iter()
+            .filter_map(|(tx, height, time)| Some((height?, time?, tx.compute_txid())))
+            .collect();
+        confirmed.sort();
+
+        let mut balance: i64 = 0;
+        let history = confirmed
+            .into_iter()
+            .map(|(height, time, txid)| {
+                let delta = deltas[&txid];
+                balance += delta;
+                BalanceHistoryEntry {
+                    txid,
+                    height,
+                    time,
+                    delta,
+                    balance: bitcoin::Amount::from_sat(balance.max(0) as u64),
+                }
+            })
+            .collect();
+        GetBalanceHistoryResult { history }
+    }
+
+    /// Recommend which of our confirmed coins to consolidate now, at the given feerate (in
+    /// sats/kvb), for spending them later at the expected future feerate (in sats/kvb) to cost
+    /// less. See [`spend::consolidation_advice`]. No advice is returned if it isn't worth it.
+    pub fn get_consolidation_advice(
+        &self,
+        feerate_kvb: u64,
+        future_feerate_kvb: u64,
+    ) -> Result<GetConsolidationAdviceResult, CommandError> {
+        for feerate in [feerate_kvb, future_feerate_kvb] {
+            if !(spend::MIN_FEERATE_KVB..=spend::MAX_FEERATE_KVB).contains(&feerate) {
+                return Err(CommandError::InvalidFeerate(feerate));
+            }
+        }
+        let coins: Vec<_> = self
+            .db
+            .connection()
+            .coins(&[CoinStatus::Confirmed], &[])
+            .into_values()
+            .filter(|c| !c.is_immature)
+            .map(|c| (c.outpoint, c.amount))
+            .collect();
+        let advice = spend::consolidation_advice(
+            &self.config.main_descriptor,
+            &self.secp,
+            &coins,
+            feerate_kvb,
+            future_feerate_kvb,
+        )
+        .map(|advice| ConsolidationAdvice {
+            outpoints: advice.coins,
+            amount: advice.amount,
+            fee: advice.fee,
+            savings: advice.savings,
+        });
+        Ok(GetConsolidationAdviceResult { advice })
+    }
+
     /// Create a transaction that sweeps coins using a timelocked recovery path to a
-    /// provided address with the provided feerate.
+    /// provided address with the provided feerate (in sats/kvb).
     ///
     /// The `timelock` parameter can be used to specify which recovery path to use. By default,
     /// we'll use the first recovery path available.
@@ -1256,21 +2177,56 @@ impl DaemonControl {
         &self,
         address: bitcoin::Address<address::NetworkUnchecked>,
         coins_outpoints: &[bitcoin::OutPoint],
-        feerate_vb: u64,
+        feerate_kvb: u64,
         timelock: Option<u16>,
     ) -> Result<CreateRecoveryResult, CommandError> {
-        if feerate_vb < 1 {
-            return Err(CommandError::InvalidFeerate(feerate_vb));
+        // If no recovery path is specified, use the first available one.
+        let timelock =
+            timelock.unwrap_or_else(|| self.config.main_descriptor.first_timelock_value());
+        self.recovery_psbt(address, coins_outpoints, feerate_kvb, timelock, true)
+    }
+
+    /// Create a transaction sweeping all our confirmed coins through the recovery path with the
+    /// given timelock, whether or not it is already available. It is meant to be signed ahead of
+    /// time and handed over to an heir, who may only broadcast it once the timelock of all its
+    /// coins has matured.
+    pub fn create_heir_recovery(
+        &self,
+        address: bitcoin::Address<address::NetworkUnchecked>,
+        feerate_kvb: u64,
+        timelock: u16,
+    ) -> Result<CreateRecoveryResult, CommandError> {
+        if !self
+            .config
+            .main_descriptor
+            .policy()
+            .recovery_paths()
+            .contains_key(&timelock)
+        {
+            return Err(CommandError::UnknownRecoveryPath(timelock));
+        }
+        self.recovery_psbt(address, &[], feerate_kvb, timelock, false)
+    }
+
+    fn recovery_psbt(
+        &self,
+        address: bitcoin::Address<address::NetworkUnchecked>,
+        coins_outpoints: &[bitcoin::OutPoint],
+        feerate_kvb: u64,
+        timelock: u16,
+        // Whether to only spend the coins for which the recovery path is available.
+        mature_only: bool,
+    ) -> Result<CreateRecoveryResult, CommandError> {
+        if feerate_kvb < spend::MIN_FEERATE_KVB {
+            return Err(CommandError::InvalidFeerate(feerate_kvb));
         }
         let mut tx_getter = DbTxGetter::new(&self.db);
         let mut db_conn = self.db.connection();
         let sweep_addr = self.spend_addr(&mut db_conn, self.validate_address(address)?);
 
-        // Query the coins that we can spend through the specified recovery path (if no recovery
-        // path specified, use the first available one) from the database.
+        // Query the coins that we can spend through the specified recovery path from the
+        // database.
         let current_height = self.bitcoin.chain_tip().height;
-        let timelock =
-            timelock.unwrap_or_else(|| self.config.main_descriptor.first_timelock_value());
         let height_delta: i32 = timelock.into();
         let coins = if coins_outpoints.is_empty() {
             db_conn.coins(&[CoinStatus::Confirmed], &[])
@@ -1295,7 +2251,7 @@ impl DaemonControl {
         for (op, c) in coins {
             // We are interested in coins available at the *next* block
             if c.block_info
-                .map(|b| current_height + 1 >= b.height + height_delta)
+                .map(|b| !mature_only || current_height + 1 >= b.height + height_delta)
                 .unwrap_or(false)
             {
                 sweepable_coins.push(coin_to_candidate(
@@ -1321,8 +2277,10 @@ impl DaemonControl {
             &self.secp,
             &mut tx_getter,
             &[], // No destination, only the change address.
+            &[],
+            None,
             &sweepable_coins,
-            SpendTxFees::Regular(feerate_vb),
+            SpendTxFees::Regular(feerate_kvb),
             sweep_addr,
             locktime,
         )?;
@@ -1332,6 +2290,65 @@ impl DaemonControl {
 
         Ok(CreateRecoveryResult { psbt })
     }
+
+    /// Create the PSBT of a BIP-0322 `to_sign` transaction, to sign a message for one of our
+    /// addresses.
+    ///
+    /// If `coins_outpoints` is not empty the transaction also spends these coins, for the
+    /// signature to prove control over them (proof of reserves). An error will be returned if any
+    /// of these coins is unknown or already spent.
+    pub fn create_message_psbt(
+        &self,
+        address: bitcoin::Address<address::NetworkUnchecked>,
+        message: &str,
+        coins_outpoints: &[bitcoin::OutPoint],
+    ) -> Result<CreateMessagePsbtResult, CommandError> {
+        let address = self.validate_address(address)?;
+        let mut tx_getter = DbTxGetter::new(&self.db);
+        let mut db_conn = self.db.connection();
+
+        let addr_info = self
+            .addr_info(&mut db_conn, &address)
+            .ok_or(CommandError::UnknownAddress(address))?;
+        let addr_desc = if addr_info.is_change {
+            self.config.main_descriptor.change_descriptor()
+        } else {
+            self.config.main_descriptor.receive_descriptor()
+        }
+        .derive(addr_info.index, &self.secp);
+
+        let coins = db_conn.coins(&[], coins_outpoints);
+        let mut spent = Vec::with_capacity(coins_outpoints.len());
+        for op in coins_outpoints {
+            let coin = coins.get(op).ok_or(CommandError::UnknownOutpoint(*op))?;
+            if coin.is_spent() {
+                return Err(CommandError::AlreadySpent(*op));
+            }
+            spent.push((coin, self.derived_desc(coin)));
+        }
+
+        let prevouts: Vec<_> = spent
+            .iter()
+            .map(|(coin, desc)| {
+                let txout = bitcoin::TxOut {
+                    value: coin.amount,
+                    script_pubkey: desc.script_pubkey(),
+                };
+                (coin.outpoint, txout)
+            })
+            .collect();
+        let mut psbt =
+            bip322::to_sign_psbt(&addr_desc.script_pubkey(), message.as_bytes(), &prevouts);
+        addr_desc.update_psbt_in(&mut psbt.inputs[0]);
+        for (psbt_in, (coin, desc)) in psbt.inputs.iter_mut().skip(1).zip(spent.iter()) {
+            desc.update_psbt_in(psbt_in);
+            if !self.config.main_descriptor.is_taproot() {
+                psbt_in.non_witness_utxo = tx_getter.get_tx(&coin.outpoint.txid);
+            }
+        }
+
+        Ok(CreateMessagePsbtResult { psbt })
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -1357,6 +2374,14 @@ pub struct GetInfoResult {
     pub receive_index: u32,
     /// Last index used to generate a change address
     pub change_index: u32,
+    /// Number of addresses watched past the highest derivation index
+    pub gap_limit: u32,
+    /// Warnings about the state of the wallet, if any
+    #[serde(default)]
+    pub warnings: Vec<String>,
+    /// The rules enforced when creating and broadcasting spends, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spending_policy: Option<SpendingPolicy>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -1391,11 +2416,95 @@ pub struct GetLabelsBip329Result {
     pub labels: crate::bip329::Labels,
 }
 
+/// Maximum length of a label, in characters.
+pub const MAX_LABEL_LEN: usize = 100;
+
+/// Maximum value for the address gap limit.
+pub const MAX_GAP_LIMIT: u32 = 100_000;
+
+/// Warn in `getinfo` when this percentage of the gap limit is revealed but unused.
+const GAP_LIMIT_WARNING_PERCENT: u32 = 80;
+
+fn truncate_label(mut label: String) -> String {
+    if let Some((i, _)) = label.char_indices().nth(MAX_LABEL_LEN) {
+        label.truncate(i);
+    }
+    label
+}
+
+fn label_value(ref_: &str, label: &bip329::Label) -> Option<String> {
+    serde_json::to_value(label)
+        .ok()
+        .and_then(|v| v["label"].as_str().map(|s| s.to_string()))
+        .filter(|s| !s.is_empty() && !ref_.is_empty())
+}
+
+/// How to handle an imported label for an item which already has a different one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LabelConflict {
+    /// Append the imported label to the existing one.
+    Merge,
+    /// Replace the existing label.
+    Overwrite,
+    /// Keep the existing label.
+    Skip,
+}
+
+impl LabelConflict {
+    pub fn from_arg(arg: &str) -> Option<Self> {
+        match arg {
+            "merge" => Some(Self::Merge),
+            "overwrite" => Some(Self::Overwrite),
+            "skip" => Some(Self::Skip),
+            _ => None,
+        }
+    }
+
+    pub fn to_arg(&self) -> &str {
+        match self {
+            Self::Merge => "merge",
+            Self::Overwrite => "overwrite",
+            Self::Skip => "skip",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyLabel {
+    pub fingerprint: bip32::Fingerprint,
+    pub label: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportLabelsResult {
+    /// Number of items which were not labelled before.
+    pub imported: usize,
+    /// Number of existing labels which were overwritten or merged.
+    pub updated: usize,
+    /// Number of existing labels kept as is because of a conflict.
+    pub skipped: usize,
+    /// Number of labels identical to the existing ones.
+    pub unchanged: usize,
+    /// References of the entries which do not belong to this wallet.
+    pub foreign: Vec<String>,
+    /// References of the entries of a type which cannot be imported.
+    pub unsupported: Vec<String>,
+    /// References of the outputs marked as not spendable.
+    pub unspendable: Vec<String>,
+    /// Labels of the wallet's own xpubs.
+    pub keys: Vec<KeyLabel>,
+}
+
 #[derive(Debug, Clone, Serialize, PartialEq, Eq)]
 pub struct AddressInfo {
     index: u32,
     receive: bitcoin::Address,
     change: bitcoin::Address,
+    /// Whether more than one coin was received on the receive address.
+    receive_reused: bool,
+    /// Whether more than one coin was received on the change address.
+    change_reused: bool,
 }
 
 #[derive(Debug, Clone, Serialize, PartialEq, Eq)]
@@ -1424,6 +2533,9 @@ pub struct ListRevealedAddressesEntry {
     /// This count does not include any coins that may have been replaced or otherwise dropped
     /// from the mempool.
     pub used_count: u32,
+    /// Whether more than one coin was received on this address.
+    #[serde(default)]
+    pub reused: bool,
 }
 
 /// Result of a [`DaemonControl::list_revealed_addresses`] request.
@@ -1500,6 +2612,9 @@ pub struct ListSpendEntry {
     #[serde(serialize_with = "ser_to_string", deserialize_with = "deser_fromstr")]
     pub psbt: Psbt,
     pub updated_at: Option<u32>,
+    /// When the spend was approved, if it was.
+    #[serde(default)]
+    pub approved_at: Option<u32>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -1507,6 +2622,27 @@ pub struct ListSpendResult {
     pub spend_txs: Vec<ListSpendEntry>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ScheduledBroadcastEntry {
+    pub txid: bitcoin::Txid,
+    /// Block height from which to broadcast the transaction, if any.
+    pub block_height: Option<i32>,
+    /// Timestamp from which to broadcast the transaction, if any.
+    pub timestamp: Option<u32>,
+    pub created_at: u32,
+    /// When the transaction was broadcast, if it was.
+    pub broadcast_at: Option<u32>,
+    /// The error of the last failed attempt to broadcast the transaction, if any.
+    pub last_error: Option<String>,
+    /// When the transaction was found impossible to ever broadcast, if it was.
+    pub failed_at: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListScheduledBroadcastsResult {
+    pub broadcasts: Vec<ScheduledBroadcastEntry>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ListTransactionsResult {
     pub transactions: Vec<TransactionInfo>,
@@ -1520,16 +2656,183 @@ pub struct TransactionInfo {
     pub time: Option<u32>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BalanceHistoryEntry {
+    pub txid: bitcoin::Txid,
+    pub height: i32,
+    pub time: u32,
+    /// Net change of the balance with this transaction, in satoshis.
+    pub delta: i64,
+    /// Balance after this transaction.
+    #[serde(
+        serialize_with = "ser_amount",
+        deserialize_with = "deser_amount_from_sats"
+    )]
+    pub balance: bitcoin::Amount,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct GetBalanceHistoryResult {
+    pub history: Vec<BalanceHistoryEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ConsolidationAdvice {
+    /// The coins to consolidate, smallest first.
+    pub outpoints: Vec<bitcoin::OutPoint>,
+    /// The total value of the coins to consolidate.
+    #[serde(
+        serialize_with = "ser_amount",
+        deserialize_with = "deser_amount_from_sats"
+    )]
+    pub amount: bitcoin::Amount,
+    /// The fee of the consolidation transaction at the current feerate.
+    #[serde(
+        serialize_with = "ser_amount",
+        deserialize_with = "deser_amount_from_sats"
+    )]
+    pub fee: bitcoin::Amount,
+    /// The fees saved by spending the consolidated coin at the future feerate instead of each of
+    /// the coins, net of the consolidation fee.
+    #[serde(
+        serialize_with = "ser_amount",
+        deserialize_with = "deser_amount_from_sats"
+    )]
+    pub savings: bitcoin::Amount,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct GetConsolidationAdviceResult {
+    pub advice: Option<ConsolidationAdvice>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct CreateRecoveryResult {
     #[serde(serialize_with = "ser_to_string", deserialize_with = "deser_fromstr")]
     pub psbt: Psbt,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CreateMessagePsbtResult {
+    #[serde(serialize_with = "ser_to_string", deserialize_with = "deser_fromstr")]
+    pub psbt: Psbt,
+}
+
+/// Who an input or output of an analyzed PSBT belongs to.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Ownership {
+    /// Derived from our receive keychain.
+    Ours,
+    /// Derived from our change keychain.
+    Change,
+    /// Known not to be ours.
+    External,
+    /// The coin spent by an input is not ours and its Script is not in the PSBT.
+    Unknown,
+}
+
+impl Ownership {
+    fn new(derivation: Option<(bip32::ChildNumber, bool)>, is_known: bool) -> Self {
+        match derivation {
+            Some((_, true)) => Self::Change,
+            Some((_, false)) => Self::Ours,
+            None if is_known => Self::External,
+            None => Self::Unknown,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PathSpendStatus {
+    /// The number of signatures required to spend through this path.
+    pub threshold: usize,
+    /// The number of signatures provided.
+    pub sigs_count: usize,
+    /// The number of signatures still needed to spend through this path.
+    pub missing_sigs: usize,
+    /// The fingerprints of the master keys for which a signature was provided.
+    pub signed_fingerprints: Vec<bip32::Fingerprint>,
+}
+
+impl PathSpendStatus {
+    fn new(info: &descriptors::PathSpendInfo) -> Self {
+        let mut signed_fingerprints: Vec<_> = info.signed_pubkeys.keys().copied().collect();
+        signed_fingerprints.sort();
+        Self {
+            threshold: info.threshold,
+            sigs_count: info.sigs_count,
+            missing_sigs: info.threshold.saturating_sub(info.sigs_count),
+            signed_fingerprints,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct InputSpendInfo {
+    pub primary_path: PathSpendStatus,
+    /// Only the recovery paths available given the nSequence of the input, by timelock.
+    pub recovery_paths: BTreeMap<u16, PathSpendStatus>,
+}
+
+impl InputSpendInfo {
+    fn new(info: &descriptors::PartialSpendInfo) -> Self {
+        Self {
+            primary_path: PathSpendStatus::new(info.primary_path()),
+            recovery_paths: info
+                .recovery_paths()
+                .iter()
+                .map(|(timelock, path_info)| (*timelock, PathSpendStatus::new(path_info)))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AnalyzePsbtInput {
+    pub outpoint: bitcoin::OutPoint,
+    /// Value of the coin spent, in sats, if known.
+    pub value: Option<u64>,
+    pub ownership: Ownership,
+    pub derivation_index: Option<bip32::ChildNumber>,
+    pub sighash_type: Option<String>,
+    /// The signatures for each spending path, for our inputs only.
+    pub spend_info: Option<InputSpendInfo>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AnalyzePsbtOutput {
+    /// Not set if the Script is not one of a standard address.
+    pub address: Option<bitcoin::Address<address::NetworkUnchecked>>,
+    /// Value in sats.
+    pub value: u64,
+    pub ownership: Ownership,
+    pub derivation_index: Option<bip32::ChildNumber>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AnalyzePsbtResult {
+    pub txid: bitcoin::Txid,
+    pub inputs: Vec<AnalyzePsbtInput>,
+    pub outputs: Vec<AnalyzePsbtOutput>,
+    /// Fee in sats, if the value of all the coins spent is known.
+    pub fee: Option<u64>,
+    /// Size in virtual bytes of the transaction once signed. If not all inputs are ours, it is
+    /// only a lower bound.
+    pub vsize: u64,
+    /// Feerate in sats/kvb, if the fee is known.
+    pub feerate_kvb: Option<u64>,
+    pub anomalies: Vec<String>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{bitcoin::Block, database::BlockInfo, testutils::*};
+    use crate::{
+        bitcoin::{Block, MempoolEntry, MempoolEntryFees},
+        database::BlockInfo,
+        testutils::*,
+    };
     use coincube_core::spend::InsaneFeeInfo;
 
     use bitcoin::{
@@ -1549,6 +2852,42 @@ mod tests {
         ms.shutdown();
     }
 
+    #[test]
+    fn gap_limit() {
+        let ms = DummyCoincube::new(DummyBitcoind::new(), DummyDatabase::new());
+        let control = &ms.control();
+
+        let info = control.get_info();
+        assert_eq!(info.gap_limit, DEFAULT_GAP_LIMIT);
+        assert!(info.warnings.is_empty());
+
+        // The gap limit must be within bounds.
+        assert_eq!(
+            control.set_gap_limit(0),
+            Err(CommandError::InvalidGapLimit(0))
+        );
+        assert_eq!(
+            control.set_gap_limit(MAX_GAP_LIMIT + 1),
+            Err(CommandError::InvalidGapLimit(MAX_GAP_LIMIT + 1))
+        );
+        control.set_gap_limit(10).unwrap();
+        assert_eq!(control.get_info().gap_limit, 10);
+
+        // Revealing too many unused receive addresses triggers a warning.
+        control.update_deriv_indexes(Some(7), None).unwrap();
+        assert!(control.get_info().warnings.is_empty());
+        control.update_deriv_indexes(Some(8), None).unwrap();
+        let warnings = control.get_info().warnings;
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("receive"));
+
+        // Increasing the gap limit makes it go away.
+        control.set_gap_limit(20).unwrap();
+        assert!(control.get_info().warnings.is_empty());
+
+        ms.shutdown();
+    }
+
     #[test]
     fn getnewaddress() {
         let ms = DummyCoincube::new(DummyBitcoind::new(), DummyDatabase::new());
@@ -2019,7 +3358,7 @@ mod tests {
         let dummy_value = 10_000;
         let mut destinations = <HashMap<bitcoin::Address<address::NetworkUnchecked>, u64>>::new();
         assert_eq!(
-            control.create_spend(&destinations, &[], 1, None),
+            control.create_spend(&destinations, &[], None, &[], 1_000, None),
             Err(CommandError::NoOutpointForSelfSend)
         );
         destinations = [(dummy_addr.clone(), dummy_value)]
@@ -2028,18 +3367,24 @@ mod tests {
             .collect();
         // Insufficient funds for coin selection.
         assert!(matches!(
-            control.create_spend(&destinations, &[], 1, None),
+            control.create_spend(&destinations, &[], None, &[], 1_000, None),
             Ok(CreateSpendResult::InsufficientFunds { .. }),
         ));
         assert_eq!(
-            control.create_spend(&destinations, &[dummy_op], 0, None),
+            control.create_spend(&destinations, &[], None, &[dummy_op], 0, None),
             Err(CommandError::InvalidFeerate(0))
         );
+        // Feerates are in sats/kvb and may be lower than 1 sat/vb, down to the minimum relay
+        // feerate.
+        assert_eq!(
+            control.create_spend(&destinations, &[], None, &[dummy_op], 99, None),
+            Err(CommandError::InvalidFeerate(99))
+        );
 
         // The coin doesn't exist. If we create a new unspent one at this outpoint with a much
         // higher value, we'll get a Spend transaction with a change output.
         assert_eq!(
-            control.create_spend(&destinations, &[dummy_op], 1, None),
+            control.create_spend(&destinations, &[], None, &[dummy_op], 1_000, None),
             Err(CommandError::UnknownOutpoint(dummy_op))
         );
         db_conn.new_unspent_coins(&[Coin {
@@ -2056,11 +3401,11 @@ mod tests {
         // If we try to use coin selection, the unconfirmed not-from-self coin will not be used
         // as a candidate and so we get a coin selection error due to insufficient funds.
         assert!(matches!(
-            control.create_spend(&destinations, &[], 1, None),
+            control.create_spend(&destinations, &[], None, &[], 1_000, None),
             Ok(CreateSpendResult::InsufficientFunds { .. }),
         ));
         let (psbt, warnings) = if let CreateSpendResult::Success { psbt, warnings } = control
-            .create_spend(&destinations, &[dummy_op], 1, None)
+            .create_spend(&destinations, &[], None, &[dummy_op], 1_000, None)
             .unwrap()
         {
             (psbt, warnings)
@@ -2090,7 +3435,7 @@ mod tests {
         // At 2sats/vb, it's twice that.
         assert_eq!(tx.output[1].value.to_sat(), 89_839);
         let psbt = if let CreateSpendResult::Success { psbt, .. } = control
-            .create_spend(&destinations, &[dummy_op], 2, None)
+            .create_spend(&destinations, &[], None, &[dummy_op], 2_000, None)
             .unwrap()
         {
             psbt
@@ -2100,25 +3445,152 @@ mod tests {
         let tx = psbt.unsigned_tx;
         assert_eq!(tx.output[1].value.to_sat(), 89_678);
 
-        // A feerate of 555 won't trigger the sanity checks (they were previously not taking the
-        // satisfaction size into account and overestimating the feerate).
-        control
-            .create_spend(&destinations, &[dummy_op], 555, None)
-            .unwrap();
-
-        // If we ask for a too high feerate, or a too large/too small output, it'll fail.
-        assert!(matches!(
-            control.create_spend(&destinations, &[dummy_op], 10_000, None),
-            Ok(CreateSpendResult::InsufficientFunds { .. }),
-        ));
-        *destinations.get_mut(&dummy_addr).unwrap() = 100_001;
-        assert!(matches!(
-            control.create_spend(&destinations, &[dummy_op], 1, None),
-            Ok(CreateSpendResult::InsufficientFunds { .. }),
+        // Sending to our own address which already received the coin is flagged as reuse.
+        let reused_addr = control
+            .config
+            .main_descriptor
+            .receive_descriptor()
+            .derive(bip32::ChildNumber::from(13), &control.secp)
+            .address(control.config.bitcoin_config.network);
+        let fresh_addr = control
+            .config
+            .main_descriptor
+            .receive_descriptor()
+            .derive(bip32::ChildNumber::from(14), &control.secp)
+            .address(control.config.bitcoin_config.network);
+        let own_destinations = [(13, reused_addr.clone()), (14, fresh_addr)].map(|(i, addr)| {
+            (
+                SpendOutputAddress {
+                    addr,
+                    info: Some(AddrInfo {
+                        index: bip32::ChildNumber::from(i),
+                        is_change: false,
+                    }),
+                },
+                bitcoin::Amount::from_sat(dummy_value),
+            )
+        });
+        assert_eq!(
+            reused_destinations(&mut db_conn, &own_destinations),
+            vec![spend::CreateSpendWarning::AddressReuse(
+                reused_addr.to_string()
+            )]
+        );
+
+        // A feerate of 555 won't trigger the sanity checks (they were previously not taking the
+        // satisfaction size into account and overestimating the feerate).
+        control
+            .create_spend(&destinations, &[], None, &[dummy_op], 555_000, None)
+            .unwrap();
+        // So won't a fractional feerate below 1 sat/vb.
+        control
+            .create_spend(&destinations, &[], None, &[dummy_op], 250, None)
+            .unwrap();
+
+        // The fee may be deducted from the destination instead of being paid on top of it. The
+        // change output then receives as much as the destination would have paid in fees.
+        let psbt = if let CreateSpendResult::Success { psbt, .. } = control
+            .create_spend(
+                &destinations,
+                std::slice::from_ref(&dummy_addr),
+                None,
+                &[dummy_op],
+                1_000,
+                None,
+            )
+            .unwrap()
+        {
+            psbt
+        } else {
+            panic!("expect successful spend creation")
+        };
+        let tx = psbt.unsigned_tx;
+        assert_eq!(tx.output[0].value.to_sat(), dummy_value - 161);
+        assert_eq!(tx.output[1].value.to_sat(), 90_000);
+        // Only destinations may be given to deduct the fee from.
+        let other_addr =
+            bitcoin::Address::from_str("bc1q39srgatmkp6k2ne3l52yhkjprdvunvspqydmkx").unwrap();
+        assert_eq!(
+            control.create_spend(
+                &destinations,
+                std::slice::from_ref(&other_addr),
+                None,
+                &[dummy_op],
+                1_000,
+                None
+            ),
+            Err(CommandError::NotADestination(other_addr.assume_checked()))
+        );
+        // And the destination must remain above dust once the fee is deducted.
+        *destinations.get_mut(&dummy_addr).unwrap() = 5_100;
+        assert_eq!(
+            control.create_spend(
+                &destinations,
+                std::slice::from_ref(&dummy_addr),
+                None,
+                &[dummy_op],
+                1_000,
+                None
+            ),
+            Err(CommandError::SpendCreation(
+                SpendCreationError::InvalidOutputValue(bitcoin::Amount::from_sat(4_939))
+            ))
+        );
+        *destinations.get_mut(&dummy_addr).unwrap() = dummy_value;
+
+        // Some data may be committed to in an OP_RETURN output. Its size is accounted for in the
+        // fee: 19 more vbytes at 1 sat/vb.
+        let psbt = if let CreateSpendResult::Success { psbt, .. } = control
+            .create_spend(
+                &destinations,
+                &[],
+                Some(b"coincube".as_slice()),
+                &[dummy_op],
+                1_000,
+                None,
+            )
+            .unwrap()
+        {
+            psbt
+        } else {
+            panic!("expect successful spend creation")
+        };
+        let tx = psbt.unsigned_tx;
+        assert_eq!(tx.output.len(), 3);
+        assert_eq!(tx.output[1].value.to_sat(), 0);
+        assert_eq!(
+            spend::op_return_data(&tx.output[1].script_pubkey),
+            Some(b"coincube".to_vec())
+        );
+        assert_eq!(tx.output[2].value.to_sat(), 89_839 - 19);
+        // But it must be standard.
+        assert_eq!(
+            control.create_spend(
+                &destinations,
+                &[],
+                Some([0; 81].as_slice()),
+                &[dummy_op],
+                1_000,
+                None
+            ),
+            Err(CommandError::SpendCreation(
+                SpendCreationError::OpReturnDataTooLarge(81)
+            ))
+        );
+
+        // If we ask for a too high feerate, or a too large/too small output, it'll fail.
+        assert!(matches!(
+            control.create_spend(&destinations, &[], None, &[dummy_op], 10_000_000, None),
+            Ok(CreateSpendResult::InsufficientFunds { .. }),
+        ));
+        *destinations.get_mut(&dummy_addr).unwrap() = 100_001;
+        assert!(matches!(
+            control.create_spend(&destinations, &[], None, &[dummy_op], 1_000, None),
+            Ok(CreateSpendResult::InsufficientFunds { .. }),
         ));
         *destinations.get_mut(&dummy_addr).unwrap() = 4_500;
         assert_eq!(
-            control.create_spend(&destinations, &[dummy_op], 1, None),
+            control.create_spend(&destinations, &[], None, &[dummy_op], 1_000, None),
             Err(CommandError::SpendCreation(
                 SpendCreationError::InvalidOutputValue(bitcoin::Amount::from_sat(4_500))
             ))
@@ -2130,7 +3602,7 @@ mod tests {
         let invalid_destinations: HashMap<bitcoin::Address<address::NetworkUnchecked>, u64> =
             [(invalid_addr, dummy_value)].iter().cloned().collect();
         assert!(matches!(
-            control.create_spend(&invalid_destinations, &[dummy_op], 1, None),
+            control.create_spend(&invalid_destinations, &[], None, &[dummy_op], 1_000, None),
             Err(CommandError::Address(
                 address::error::ParseError::NetworkValidation { .. }
             ))
@@ -2140,7 +3612,7 @@ mod tests {
         // won't create an output lower than 5k sats.
         *destinations.get_mut(&dummy_addr).unwrap() = 95_000;
         let (psbt, warnings) = if let CreateSpendResult::Success { psbt, warnings } = control
-            .create_spend(&destinations, &[dummy_op], 1, None)
+            .create_spend(&destinations, &[], None, &[dummy_op], 1_000, None)
             .unwrap()
         {
             (psbt, warnings)
@@ -2169,7 +3641,7 @@ mod tests {
         // Increase the target value by the change amount and the warning will disappear.
         *destinations.get_mut(&dummy_addr).unwrap() = 95_000 + 4_839;
         let (psbt, warnings) = if let CreateSpendResult::Success { psbt, warnings } = control
-            .create_spend(&destinations, &[dummy_op], 1, None)
+            .create_spend(&destinations, &[], None, &[dummy_op], 1_000, None)
             .unwrap()
         {
             (psbt, warnings)
@@ -2184,7 +3656,7 @@ mod tests {
         *destinations.get_mut(&dummy_addr).unwrap() =
             95_000 + 4_830 + /* fee for change output */ 43;
         let (psbt, warnings) = if let CreateSpendResult::Success { psbt, warnings } = control
-            .create_spend(&destinations, &[dummy_op], 1, None)
+            .create_spend(&destinations, &[], None, &[dummy_op], 1_000, None)
             .unwrap()
         {
             (psbt, warnings)
@@ -2199,7 +3671,7 @@ mod tests {
         *destinations.get_mut(&dummy_addr).unwrap() =
             95_000 + 4_839 + /* fee for change output */ 43 + 1;
         assert_eq!(
-            control.create_spend(&destinations, &[dummy_op], 1, None),
+            control.create_spend(&destinations, &[], None, &[dummy_op], 1_000, None),
             Ok(CreateSpendResult::InsufficientFunds { missing: 1 }),
         );
 
@@ -2207,7 +3679,7 @@ mod tests {
         *destinations.get_mut(&dummy_addr).unwrap() =
             100_000 - /* fee without change */ 118 - /* extra fee for change output */ 43 - 1;
         let warnings = if let CreateSpendResult::Success { warnings, .. } = control
-            .create_spend(&destinations, &[dummy_op], 1, None)
+            .create_spend(&destinations, &[], None, &[dummy_op], 1_000, None)
             .unwrap()
         {
             warnings
@@ -2228,7 +3700,7 @@ mod tests {
         *destinations.get_mut(&dummy_addr).unwrap() =
             95_000 - /* fee without change */ 118 - /* extra fee for change output */ 43;
         let (psbt, warnings) = if let CreateSpendResult::Success { psbt, warnings } = control
-            .create_spend(&destinations, &[dummy_op], 1, None)
+            .create_spend(&destinations, &[], None, &[dummy_op], 1_000, None)
             .unwrap()
         {
             (psbt, warnings)
@@ -2244,7 +3716,7 @@ mod tests {
         *destinations.get_mut(&dummy_addr).unwrap() =
             95_000 - /* fee without change */ 118 - /* extra fee for change output */ 43 + 1;
         let warnings = if let CreateSpendResult::Success { warnings, .. } = control
-            .create_spend(&destinations, &[dummy_op], 1, None)
+            .create_spend(&destinations, &[], None, &[dummy_op], 1_000, None)
             .unwrap()
         {
             warnings
@@ -2270,13 +3742,13 @@ mod tests {
             .unwrap(),
         )]);
         assert_eq!(
-            control.create_spend(&destinations, &[dummy_op], 1, None),
+            control.create_spend(&destinations, &[], None, &[dummy_op], 1_000, None),
             Err(CommandError::AlreadySpent(dummy_op))
         );
         // If we try to use coin selection, the spent coin will not be used as a candidate
         // and so we get a coin selection error due to insufficient funds.
         assert!(matches!(
-            control.create_spend(&destinations, &[], 1, None),
+            control.create_spend(&destinations, &[], None, &[], 1_000, None),
             Ok(CreateSpendResult::InsufficientFunds { .. }),
         ));
 
@@ -2296,12 +3768,12 @@ mod tests {
             spend_block: None,
             is_from_self: false,
         }]);
-        assert_eq!(
-            control.create_spend(&destinations, &[dummy_op_dup], 1_001, None),
+        assert!(matches!(
+            control.create_spend(&destinations, &[], None, &[dummy_op_dup], 1_001_000, None),
             Err(CommandError::SpendCreation(SpendCreationError::InsaneFees(
-                InsaneFeeInfo::TooHighFeerate(1_001)
-            )))
-        );
+                InsaneFeeInfo::TooHighFeerate(r)
+            ))) if (1_001_000..1_002_000).contains(&r)
+        ));
 
         // Add an unconfirmed coin from self to be used for coin selection.
         let confirmed_op_1 = bitcoin::OutPoint {
@@ -2322,14 +3794,14 @@ mod tests {
         db_conn.new_unspent_coins(&[unconfirmed_coin]);
         // Coin selection error due to insufficient funds.
         assert!(matches!(
-            control.create_spend(&destinations, &[], 1, None),
+            control.create_spend(&destinations, &[], None, &[], 1_000, None),
             Ok(CreateSpendResult::InsufficientFunds { .. }),
         ));
         // Set destination amount equal to value of confirmed coins.
         *destinations.get_mut(&dummy_addr).unwrap() = 80_000;
         // Coin selection error occurs due to insufficient funds to pay fee.
         assert!(matches!(
-            control.create_spend(&destinations, &[], 1, None),
+            control.create_spend(&destinations, &[], None, &[], 1_000, None),
             Ok(CreateSpendResult::InsufficientFunds { .. }),
         ));
         let confirmed_op_2 = bitcoin::OutPoint {
@@ -2352,8 +3824,9 @@ mod tests {
             is_from_self: false,
         }]);
         // First, create a transaction using auto coin selection.
-        let psbt = if let CreateSpendResult::Success { psbt, .. } =
-            control.create_spend(&destinations, &[], 1, None).unwrap()
+        let psbt = if let CreateSpendResult::Success { psbt, .. } = control
+            .create_spend(&destinations, &[], None, &[], 1_000, None)
+            .unwrap()
         {
             psbt
         } else {
@@ -2378,7 +3851,14 @@ mod tests {
 
         // Create a second transaction using manual coin selection.
         let psbt = if let CreateSpendResult::Success { psbt, .. } = control
-            .create_spend(&destinations, &[confirmed_op_1, confirmed_op_2], 1, None)
+            .create_spend(
+                &destinations,
+                &[],
+                None,
+                &[confirmed_op_1, confirmed_op_2],
+                1_000,
+                None,
+            )
             .unwrap()
         {
             psbt
@@ -2411,7 +3891,7 @@ mod tests {
         unconfirmed_coin_2.is_change = false;
         db_conn.new_unspent_coins(&[unconfirmed_coin_2]);
         assert!(matches!(
-            control.create_spend(&destinations, &[], 1, None),
+            control.create_spend(&destinations, &[], None, &[], 1_000, None),
             Ok(CreateSpendResult::InsufficientFunds { .. }),
         ));
         // 2. not from self and change
@@ -2420,7 +3900,7 @@ mod tests {
         unconfirmed_coin_2.is_change = true;
         db_conn.new_unspent_coins(&[unconfirmed_coin_2]);
         assert!(matches!(
-            control.create_spend(&destinations, &[], 1, None),
+            control.create_spend(&destinations, &[], None, &[], 1_000, None),
             Ok(CreateSpendResult::InsufficientFunds { .. }),
         ));
 
@@ -2438,8 +3918,10 @@ mod tests {
         let psbt = if let CreateSpendResult::Success { psbt, .. } = control
             .create_spend(
                 &destinations,
+                &[],
+                None,
                 &[confirmed_op_1, confirmed_op_2],
-                1,
+                1_000,
                 Some(change_address.as_unchecked().clone()),
             )
             .unwrap()
@@ -2480,12 +3962,12 @@ mod tests {
         }]);
         let empty_dest = &HashMap::<bitcoin::Address<address::NetworkUnchecked>, u64>::new();
         assert!(matches!(
-            control.create_spend(empty_dest, &[confirmed_op_3], 5, None),
+            control.create_spend(empty_dest, &[], None, &[confirmed_op_3], 5_000, None),
             Ok(CreateSpendResult::InsufficientFunds { .. }),
         ));
         // If we use a lower fee, the self-send will succeed.
         let psbt = if let CreateSpendResult::Success { psbt, .. } = control
-            .create_spend(empty_dest, &[confirmed_op_3], 1, None)
+            .create_spend(empty_dest, &[], None, &[confirmed_op_3], 1_000, None)
             .unwrap()
         {
             psbt
@@ -2519,13 +4001,147 @@ mod tests {
             is_from_self: false,
         }]);
         assert_eq!(
-            control.create_spend(&destinations, &[imma_op], 1_001, None),
+            control.create_spend(&destinations, &[], None, &[imma_op], 1_001_000, None),
             Err(CommandError::ImmatureCoinbase(imma_op))
         );
 
         ms.shutdown();
     }
 
+    #[test]
+    fn spending_policy() {
+        let dummy_tx = bitcoin::Transaction {
+            version: TxVersion::TWO,
+            lock_time: absolute::LockTime::Blocks(absolute::Height::ZERO),
+            input: vec![],
+            output: vec![],
+        };
+        let dummy_op = bitcoin::OutPoint::new(dummy_tx.compute_txid(), 0);
+        let allowed_addr =
+            bitcoin::Address::from_str("bc1qnsexk3gnuyayu92fc3tczvc7k62u22a22ua2kv").unwrap();
+        let other_addr =
+            bitcoin::Address::from_str("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4").unwrap();
+        let policy = SpendingPolicy {
+            daily_limit: Some(50_000),
+            allowed_destinations: vec![allowed_addr.clone()],
+            require_approval: true,
+            ..Default::default()
+        };
+        let ms = DummyCoincube::new_policy(DummyBitcoind::new(), DummyDatabase::new(), policy);
+        let control = &ms.control();
+        let mut db_conn = control.db().lock().unwrap().connection();
+        db_conn.new_txs(&[dummy_tx]);
+        db_conn.new_unspent_coins(&[Coin {
+            outpoint: dummy_op,
+            is_immature: false,
+            block_info: None,
+            amount: bitcoin::Amount::from_sat(100_000),
+            derivation_index: bip32::ChildNumber::from(13),
+            is_change: false,
+            spend_txid: None,
+            spend_block: None,
+            is_from_self: false,
+        }]);
+        let destinations = |addr: &bitcoin::Address<address::NetworkUnchecked>, value: u64| {
+            <HashMap<bitcoin::Address<address::NetworkUnchecked>, u64>>::from([(
+                addr.clone(),
+                value,
+            )])
+        };
+
+        // Only the allowed destinations may be paid, up to the limit.
+        assert_eq!(
+            control.create_spend(
+                &destinations(&other_addr, 10_000),
+                &[],
+                None,
+                &[dummy_op],
+                1_000,
+                None
+            ),
+            Err(CommandError::PolicyViolation(
+                PolicyViolation::DestinationNotAllowed(
+                    other_addr.clone().assume_checked().to_string()
+                )
+            ))
+        );
+        assert!(matches!(
+            control.create_spend(
+                &destinations(&allowed_addr, 50_000),
+                &[],
+                None,
+                &[dummy_op],
+                1_000,
+                None
+            ),
+            // The fee is part of the outflow.
+            Err(CommandError::PolicyViolation(PolicyViolation::DailyLimit {
+                limit: 50_000,
+                outflow,
+            })) if outflow > 50_000
+        ));
+        let psbt = match control
+            .create_spend(
+                &destinations(&allowed_addr, 10_000),
+                &[],
+                None,
+                &[dummy_op],
+                1_000,
+                None,
+            )
+            .unwrap()
+        {
+            CreateSpendResult::Success { psbt, .. } => psbt,
+            CreateSpendResult::InsufficientFunds { .. } => {
+                panic!("expect successful spend creation")
+            }
+        };
+
+        // It must be approved before being broadcast.
+        let txid = psbt.unsigned_tx.compute_txid();
+        control.update_spend(psbt).unwrap();
+        assert_eq!(
+            control.broadcast_spend(&txid),
+            Err(CommandError::PolicyViolation(
+                PolicyViolation::ApprovalRequired
+            ))
+        );
+        // Nor may it be scheduled for broadcast.
+        assert_eq!(
+            control.schedule_broadcast(&txid, Some(800_000), None),
+            Err(CommandError::PolicyViolation(
+                PolicyViolation::ApprovalRequired
+            ))
+        );
+        assert_eq!(
+            control.approve_spend(&dummy_op.txid),
+            Err(CommandError::UnknownSpend(dummy_op.txid))
+        );
+        control.approve_spend(&txid).unwrap();
+        let spend_txs = control.list_spend(None).unwrap().spend_txs;
+        assert!(spend_txs[0].approved_at.is_some());
+        // Now the policy is followed, but the spend isn't signed.
+        assert!(matches!(
+            control.broadcast_spend(&txid),
+            Err(CommandError::SpendFinalization(..))
+        ));
+        assert!(matches!(
+            control.schedule_broadcast(&txid, Some(800_000), None),
+            Err(CommandError::SpendFinalization(..))
+        ));
+        assert_eq!(
+            control.schedule_broadcast(&txid, None, None),
+            Err(CommandError::EmptyBroadcastSchedule)
+        );
+        assert!(control.list_scheduled_broadcasts().broadcasts.is_empty());
+        assert_eq!(
+            control.cancel_scheduled_broadcast(&txid),
+            Err(CommandError::NotScheduled(txid))
+        );
+
+        ms.shutdown();
+    }
+
     #[test]
     fn update_spend() {
         let dummy_op_a = bitcoin::OutPoint::from_str(
@@ -2600,7 +4216,7 @@ mod tests {
                 .cloned()
                 .collect();
         let mut psbt_a = if let CreateSpendResult::Success { psbt, .. } = control
-            .create_spend(&destinations_a, &[dummy_op_a], 1, None)
+            .create_spend(&destinations_a, &[], None, &[dummy_op_a], 1_000, None)
             .unwrap()
         {
             psbt
@@ -2609,7 +4225,7 @@ mod tests {
         };
         let txid_a = psbt_a.unsigned_tx.compute_txid();
         let psbt_b = if let CreateSpendResult::Success { psbt, .. } = control
-            .create_spend(&destinations_b, &[dummy_op_b], 10, None)
+            .create_spend(&destinations_b, &[], None, &[dummy_op_b], 10_000, None)
             .unwrap()
         {
             psbt
@@ -2618,7 +4234,14 @@ mod tests {
         };
         let txid_b = psbt_b.unsigned_tx.compute_txid();
         let psbt_c = if let CreateSpendResult::Success { psbt, .. } = control
-            .create_spend(&destinations_c, &[dummy_op_a, dummy_op_b], 100, None)
+            .create_spend(
+                &destinations_c,
+                &[],
+                None,
+                &[dummy_op_a, dummy_op_b],
+                100_000,
+                None,
+            )
             .unwrap()
         {
             psbt
@@ -2741,6 +4364,256 @@ mod tests {
         ms.shutdown();
     }
 
+    #[test]
+    fn cpfp() {
+        let dummy_op = bitcoin::OutPoint::from_str(
+            "3753a1d74c0af8dd0a0f3b763c14faf3bd9ed03cbdf33337a074fb0e9f6c7810:0",
+        )
+        .unwrap();
+        let ms = DummyCoincube::new(DummyBitcoind::new(), DummyDatabase::new());
+        let control = &ms.control();
+        let mut db_conn = control.db().lock().unwrap().connection();
+
+        assert_eq!(
+            control.cpfp(&[], 2_000),
+            Err(CommandError::NoOutpointForSelfSend)
+        );
+        assert_eq!(
+            control.cpfp(&[dummy_op], 99),
+            Err(CommandError::InvalidFeerate(99))
+        );
+        assert_eq!(
+            control.cpfp(&[dummy_op], 2_000),
+            Err(CommandError::UnknownOutpoint(dummy_op))
+        );
+        // A confirmed coin doesn't need a bump.
+        let coin = Coin {
+            outpoint: dummy_op,
+            is_immature: false,
+            block_info: Some(BlockInfo {
+                height: 174500,
+                time: 174500,
+            }),
+            amount: bitcoin::Amount::from_sat(300_000),
+            derivation_index: bip32::ChildNumber::from(11),
+            is_change: false,
+            spend_txid: None,
+            spend_block: None,
+            is_from_self: false,
+        };
+        db_conn.new_unspent_coins(&[coin]);
+        assert_eq!(
+            control.cpfp(&[dummy_op], 2_000),
+            Err(CommandError::CoinAlreadyConfirmed(dummy_op))
+        );
+        // An unconfirmed coin whose transaction isn't in the mempool can't be bumped either.
+        db_conn.remove_coins(&[dummy_op]);
+        db_conn.new_unspent_coins(&[Coin {
+            block_info: None,
+            ..coin
+        }]);
+        assert_eq!(
+            control.cpfp(&[dummy_op], 2_000),
+            Err(CommandError::NotInMempool(dummy_op.txid))
+        );
+
+        ms.shutdown();
+    }
+
+    #[test]
+    fn cpfp_mempool() {
+        let parent_tx = bitcoin::Transaction {
+            version: TxVersion::TWO,
+            lock_time: absolute::LockTime::Blocks(absolute::Height::ZERO),
+            input: vec![],
+            output: vec![],
+        };
+        let parent_op = bitcoin::OutPoint::new(parent_tx.compute_txid(), 0);
+        let coin = Coin {
+            outpoint: parent_op,
+            is_immature: false,
+            block_info: None,
+            amount: bitcoin::Amount::from_sat(100_000),
+            derivation_index: bip32::ChildNumber::from(11),
+            is_change: false,
+            spend_txid: None,
+            spend_block: None,
+            is_from_self: false,
+        };
+        // The parent pays 1 sat/vb.
+        let parent_entry = MempoolEntry {
+            vsize: 150,
+            ancestor_vsize: 150,
+            fees: MempoolEntryFees {
+                base: bitcoin::Amount::from_sat(150),
+                ancestor: bitcoin::Amount::from_sat(150),
+                descendant: bitcoin::Amount::from_sat(150),
+            },
+        };
+        let bitcoind = || {
+            let mut bitcoind = DummyBitcoind::new();
+            bitcoind
+                .mempool_entries
+                .insert(parent_op.txid, parent_entry.clone());
+            bitcoind
+        };
+
+        // The child spends the coin back to us and pays for its parent to reach the target.
+        let ms = DummyCoincube::new(bitcoind(), DummyDatabase::new());
+        let control = &ms.control();
+        let mut db_conn = control.db().lock().unwrap().connection();
+        db_conn.new_txs(std::slice::from_ref(&parent_tx));
+        db_conn.new_unspent_coins(&[coin]);
+        let psbt = match control.cpfp(&[parent_op], 10_000).unwrap() {
+            CreateSpendResult::Success { psbt, .. } => psbt,
+            res => panic!("Unexpected result: {:?}", res),
+        };
+        let tx = &psbt.unsigned_tx;
+        assert_eq!(
+            tx.input
+                .iter()
+                .map(|txin| txin.previous_output)
+                .collect::<Vec<_>>(),
+            vec![parent_op]
+        );
+        assert_eq!(tx.output.len(), 1);
+        let fee = coin.amount.to_sat() - tx.output[0].value.to_sat();
+        let vsize = control
+            .config
+            .main_descriptor
+            .unsigned_tx_max_vbytes(tx, true);
+        assert!(fee * 1_000 / vsize > 10_000);
+        assert!(
+            (fee + parent_entry.fees.ancestor.to_sat()) * 1_000
+                >= 10_000 * (vsize + parent_entry.ancestor_vsize)
+        );
+        ms.shutdown();
+
+        // The fee of the child counts toward the daily limit of the spending policy.
+        let policy = SpendingPolicy {
+            daily_limit: Some(1_000),
+            ..Default::default()
+        };
+        let ms = DummyCoincube::new_policy(bitcoind(), DummyDatabase::new(), policy);
+        let control = &ms.control();
+        let mut db_conn = control.db().lock().unwrap().connection();
+        db_conn.new_txs(&[parent_tx]);
+        db_conn.new_unspent_coins(&[coin]);
+        assert!(matches!(
+            control.cpfp(&[parent_op], 10_000),
+            Err(CommandError::PolicyViolation(PolicyViolation::DailyLimit {
+                limit: 1_000,
+                outflow,
+            })) if outflow == fee
+        ));
+        ms.shutdown();
+    }
+
+    #[test]
+    fn analyze_psbt() {
+        let dummy_op = bitcoin::OutPoint::from_str(
+            "3753a1d74c0af8dd0a0f3b763c14faf3bd9ed03cbdf33337a074fb0e9f6c7810:0",
+        )
+        .unwrap();
+        let ms = DummyCoincube::new(DummyBitcoind::new(), DummyDatabase::new());
+        let control = &ms.control();
+        let mut db_conn = control.db().lock().unwrap().connection();
+        db_conn.new_unspent_coins(&[Coin {
+            outpoint: dummy_op,
+            is_immature: false,
+            block_info: Some(BlockInfo {
+                height: 174500,
+                time: 174500,
+            }),
+            amount: bitcoin::Amount::from_sat(100_000),
+            derivation_index: bip32::ChildNumber::from(13),
+            is_change: false,
+            spend_txid: None,
+            spend_block: None,
+            is_from_self: false,
+        }]);
+        let dummy_addr =
+            bitcoin::Address::from_str("bc1qnsexk3gnuyayu92fc3tczvc7k62u22a22ua2kv").unwrap();
+        let destinations = vec![(dummy_addr.clone(), 10_000)].into_iter().collect();
+        let mut psbt = if let CreateSpendResult::Success { psbt, .. } = control
+            .create_spend(&destinations, &[], None, &[dummy_op], 1_000, None)
+            .unwrap()
+        {
+            psbt
+        } else {
+            panic!("expect successful spend creation")
+        };
+
+        // The spend we created is analyzed as such: our coin, an external output and change.
+        let res = control.analyze_psbt(&psbt);
+        assert_eq!(res.txid, psbt.unsigned_tx.compute_txid());
+        assert_eq!(res.inputs.len(), 1);
+        assert_eq!(res.inputs[0].outpoint, dummy_op);
+        assert_eq!(res.inputs[0].value, Some(100_000));
+        assert_eq!(res.inputs[0].ownership, Ownership::Ours);
+        assert_eq!(res.inputs[0].derivation_index, Some(13.into()));
+        let spend_info = res.inputs[0].spend_info.as_ref().unwrap();
+        assert_eq!(spend_info.primary_path.sigs_count, 0);
+        assert_eq!(
+            spend_info.primary_path.missing_sigs,
+            spend_info.primary_path.threshold
+        );
+        assert!(spend_info.recovery_paths.is_empty());
+        assert_eq!(res.outputs.len(), 2);
+        assert_eq!(res.outputs[0].address, Some(dummy_addr));
+        assert_eq!(res.outputs[0].value, 10_000);
+        assert_eq!(res.outputs[0].ownership, Ownership::External);
+        assert_eq!(res.outputs[0].derivation_index, None);
+        assert_eq!(res.outputs[1].ownership, Ownership::Change);
+        assert!(res.outputs[1].derivation_index.is_some());
+        assert_eq!(res.fee, Some(161));
+        assert!(res.anomalies.is_empty());
+
+        // Our records are trusted over the PSBT for the value of our coins.
+        let spent_spk = psbt.inputs[0]
+            .witness_utxo
+            .as_ref()
+            .unwrap()
+            .script_pubkey
+            .clone();
+        psbt.inputs[0].witness_utxo = Some(bitcoin::TxOut {
+            value: bitcoin::Amount::from_sat(200_000),
+            script_pubkey: spent_spk,
+        });
+        let res = control.analyze_psbt(&psbt);
+        assert_eq!(res.fee, Some(161));
+        assert_eq!(res.anomalies.len(), 1);
+        assert!(res.anomalies[0].contains("does not match our records"));
+
+        // Non-standard sighash types and outputs pretending to be change are flagged.
+        psbt.inputs[0].sighash_type = Some(bitcoin::EcdsaSighashType::None.into());
+        psbt.outputs[0].bip32_derivation = psbt.outputs[1].bip32_derivation.clone();
+        let res = control.analyze_psbt(&psbt);
+        assert_eq!(res.outputs[0].ownership, Ownership::External);
+        assert_eq!(res.inputs[0].sighash_type.as_deref(), Some("SIGHASH_NONE"));
+        assert_eq!(res.anomalies.len(), 3);
+        assert!(res.anomalies[1].contains("non-standard sighash type"));
+        assert!(res.anomalies[2].contains("Output #0 has derivation paths for our keys"));
+
+        // An input spending an unknown coin.
+        psbt.unsigned_tx.input.push(TxIn {
+            previous_output: OutPoint::from_str(
+                "617eab1fc0b03ee7f82ba70166725291783461f1a0e7975eaf8b5f8f674234f3:1",
+            )
+            .unwrap(),
+            ..Default::default()
+        });
+        psbt.inputs.push(Default::default());
+        let res = control.analyze_psbt(&psbt);
+        assert_eq!(res.inputs[1].ownership, Ownership::Unknown);
+        assert_eq!(res.inputs[1].value, None);
+        assert!(res.inputs[1].spend_info.is_none());
+        assert_eq!(res.fee, None);
+        assert_eq!(res.feerate_kvb, None);
+
+        ms.shutdown();
+    }
+
     #[test]
     fn list_confirmed_transactions() {
         let outpoint = OutPoint::new(
@@ -3124,6 +4997,267 @@ mod tests {
         ms.shutdown();
     }
 
+    #[test]
+    fn get_balance_history() {
+        let txs: Vec<Transaction> = (1..4)
+            .map(|i| Transaction {
+                version: TxVersion::ONE,
+                lock_time: absolute::LockTime::Blocks(absolute::Height::from_consensus(i).unwrap()),
+                input: vec![TxIn::default()],
+                output: vec![TxOut {
+                    script_pubkey: ScriptBuf::new(),
+                    value: Amount::from_sat(1_000),
+                }],
+            })
+            .collect();
+        let txids: Vec<_> = txs.iter().map(|tx| tx.compute_txid()).collect();
+
+        let ms = DummyCoincube::new(DummyBitcoind::new(), DummyDatabase::new());
+        let control = &ms.control();
+        let mut db_conn = control.db.connection();
+        db_conn.new_txs(&txs);
+        // The first transaction receives 100k sats, the second one spends them and receives
+        // 50k sats of change. The third one receives 10k sats but is unconfirmed.
+        let coin = |txid: Txid,
+                    sats: u64,
+                    block_info: Option<BlockInfo>,
+                    spend: Option<(Txid, BlockInfo)>| Coin {
+            outpoint: bitcoin::OutPoint::new(txid, 0),
+            is_immature: false,
+            block_info,
+            amount: bitcoin::Amount::from_sat(sats),
+            derivation_index: bip32::ChildNumber::from(13),
+            is_change: false,
+            spend_txid: spend.map(|(txid, _)| txid),
+            spend_block: spend.map(|(_, block)| block),
+            is_from_self: false,
+        };
+        let block = |height| BlockInfo {
+            height,
+            time: 1_000 * height as u32,
+        };
+        db_conn.new_unspent_coins(&[
+            coin(
+                txids[0],
+                100_000,
+                Some(block(1)),
+                Some((txids[1], block(2))),
+            ),
+            coin(txids[1], 50_000, Some(block(2)), None),
+            coin(txids[2], 10_000, None, None),
+        ]);
+
+        let history = control.get_balance_history().history;
+        assert_eq!(
+            history,
+            vec![
+                BalanceHistoryEntry {
+                    txid: txids[0],
+                    height: 1,
+                    time: 1_000,
+                    delta: 100_000,
+                    balance: Amount::from_sat(100_000),
+                },
+                BalanceHistoryEntry {
+                    txid: txids[1],
+                    height: 2,
+                    time: 2_000,
+                    delta: -50_000,
+                    balance: Amount::from_sat(50_000),
+                },
+            ]
+        );
+
+        ms.shutdown();
+    }
+
+    #[test]
+    fn get_consolidation_advice() {
+        let ms = DummyCoincube::new(DummyBitcoind::new(), DummyDatabase::new());
+        let control = &ms.control();
+
+        assert_eq!(
+            control.get_consolidation_advice(50, 50_000),
+            Err(CommandError::InvalidFeerate(50))
+        );
+        assert_eq!(
+            control.get_consolidation_advice(2_000, 2_000_000),
+            Err(CommandError::InvalidFeerate(2_000_000))
+        );
+        // Nothing to consolidate.
+        assert_eq!(
+            control.get_consolidation_advice(2_000, 50_000),
+            Ok(GetConsolidationAdviceResult { advice: None })
+        );
+
+        // Only the small confirmed and mature coins are worth consolidating.
+        let txid =
+            Txid::from_str("f7bd1b2a995b689d326e51eb742eb1088c4a8f110d9cb56128fd553acc9f88e5")
+                .unwrap();
+        let coin = |vout: u32, sats: u64, confirmed: bool, is_immature: bool| Coin {
+            outpoint: bitcoin::OutPoint::new(txid, vout),
+            is_immature,
+            block_info: confirmed.then_some(BlockInfo {
+                height: 1,
+                time: 1_000,
+            }),
+            amount: bitcoin::Amount::from_sat(sats),
+            derivation_index: bip32::ChildNumber::from(vout),
+            is_change: false,
+            spend_txid: None,
+            spend_block: None,
+            is_from_self: false,
+        };
+        let coins = [
+            coin(0, 20_000, true, false),
+            coin(1, 10_000, true, false),
+            coin(2, 1_000_000_000, true, false),
+            coin(3, 10_000, false, false),
+            coin(4, 10_000, true, true),
+        ];
+        control.db.connection().new_unspent_coins(&coins);
+        let advice = control
+            .get_consolidation_advice(2_000, 50_000)
+            .unwrap()
+            .advice
+            .unwrap();
+        assert_eq!(advice.outpoints, vec![coins[1].outpoint, coins[0].outpoint]);
+        assert_eq!(advice.amount, bitcoin::Amount::from_sat(30_000));
+        assert!(advice.savings > bitcoin::Amount::ZERO);
+
+        // Not worth it if the feerate isn't expected to rise.
+        assert_eq!(
+            control.get_consolidation_advice(50_000, 2_000),
+            Ok(GetConsolidationAdviceResult { advice: None })
+        );
+
+        ms.shutdown();
+    }
+
+    #[test]
+    fn import_labels() {
+        let outpoint = OutPoint::from_str(
+            "617eab1fc0b03ee7f82ba70166725291783461f1a0e7975eaf8b5f8f674234f3:0",
+        )
+        .unwrap();
+        let foreign_outpoint = OutPoint::from_str(
+            "617eab1fc0b03ee7f82ba70166725291783461f1a0e7975eaf8b5f8f674234f3:1",
+        )
+        .unwrap();
+        let mut db = DummyDatabase::new();
+        db.insert_coins(vec![Coin {
+            is_change: false,
+            is_immature: false,
+            outpoint,
+            block_info: None,
+            spend_block: None,
+            derivation_index: ChildNumber::from(0),
+            amount: bitcoin::Amount::from_sat(100_000),
+            spend_txid: None,
+            is_from_self: false,
+        }]);
+        let ms = DummyCoincube::new(DummyBitcoind::new(), db);
+        let control = &ms.control();
+
+        let labels = |value: &str| -> Vec<bip329::Label> {
+            vec![
+                serde_json::json!({"type": "output", "ref": outpoint.to_string(), "label": value, "spendable": false}),
+                serde_json::json!({"type": "output", "ref": foreign_outpoint.to_string(), "label": "not ours", "spendable": true}),
+                serde_json::json!({"type": "xpub", "ref": "xpub68JJTXc1MWK8KLW4HGLXZBJknja7kDUJuFHnM424LbziEXsfkh1WQCiEjjHw4zLqSUm4rvhgyGkkuRowE9tCJSgt3TQB5J3SKAbZ2SdcKST", "label": "primary key"}),
+                serde_json::json!({"type": "pubkey", "ref": "0283409659355b6d1cc3c32decd5d561abaac86c37a353b52895a5e6c196d6f448", "label": "some key"}),
+            ]
+            .into_iter()
+            .map(|l| serde_json::from_value(l).unwrap())
+            .collect()
+        };
+        let items: HashSet<LabelItem> = vec![LabelItem::OutPoint(outpoint)].into_iter().collect();
+        let coin_label = || {
+            control
+                .get_labels(&items)
+                .labels
+                .get(&outpoint.to_string())
+                .cloned()
+        };
+
+        let res = control.import_labels(&labels("coin"), LabelConflict::Skip);
+        assert_eq!(res.imported, 1);
+        assert_eq!(res.foreign, vec![foreign_outpoint.to_string()]);
+        assert_eq!(res.unspendable, vec![outpoint.to_string()]);
+        assert_eq!(res.unsupported.len(), 1);
+        assert_eq!(
+            res.keys,
+            vec![KeyLabel {
+                fingerprint: bip32::Fingerprint::from_str("aabbccdd").unwrap(),
+                label: "primary key".to_string()
+            }]
+        );
+        assert_eq!(coin_label(), Some("coin".to_string()));
+
+        // Conflicting labels are kept, merged or replaced depending on the strategy.
+        let res = control.import_labels(&labels("coin"), LabelConflict::Overwrite);
+        assert_eq!((res.imported, res.updated, res.unchanged), (0, 0, 1));
+        let res = control.import_labels(&labels("savings"), LabelConflict::Skip);
+        assert_eq!(res.skipped, 1);
+        assert_eq!(coin_label(), Some("coin".to_string()));
+        let res = control.import_labels(&labels("savings"), LabelConflict::Merge);
+        assert_eq!(res.updated, 1);
+        assert_eq!(coin_label(), Some("coin; savings".to_string()));
+        let res = control.import_labels(&labels("savings"), LabelConflict::Merge);
+        assert_eq!(res.unchanged, 1);
+        control.import_labels(&labels(&"a".repeat(150)), LabelConflict::Overwrite);
+        assert_eq!(coin_label(), Some("a".repeat(MAX_LABEL_LEN)));
+
+        ms.shutdown();
+    }
+
+    #[test]
+    fn create_message_psbt() {
+        let mut db = DummyDatabase::new();
+        let ms = DummyCoincube::new(DummyBitcoind::new(), db.clone());
+        let control = &ms.control();
+
+        // The address must be for our network and belong to the wallet.
+        let invalid_addr =
+            bitcoin::Address::from_str("tb1qfufcrdyarcg5eph608c6l8vktrc9re6agu4se2").unwrap();
+        assert!(matches!(
+            control.create_message_psbt(invalid_addr, "Hello World", &[]),
+            Err(CommandError::Address(
+                address::error::ParseError::NetworkValidation { .. }
+            ))
+        ));
+        let foreign_addr =
+            bitcoin::Address::from_str("bc1qnsexk3gnuyayu92fc3tczvc7k62u22a22ua2kv").unwrap();
+        assert_eq!(
+            control.create_message_psbt(foreign_addr.clone(), "Hello World", &[]),
+            Err(CommandError::UnknownAddress(foreign_addr.assume_checked()))
+        );
+
+        // Without coins, the to_sign transaction only spends the to_spend one and has a single
+        // OP_RETURN output.
+        let our_addr = control
+            .config
+            .main_descriptor
+            .receive_descriptor()
+            .derive(bip32::ChildNumber::from(3), &control.secp)
+            .address(control.config.bitcoin_config.network);
+        db.insert_address(our_addr.clone(), bip32::ChildNumber::from(3), false);
+        let psbt = control
+            .create_message_psbt(our_addr.as_unchecked().clone(), "Hello World", &[])
+            .unwrap()
+            .psbt;
+        let to_spend = bip322::to_spend(&our_addr.script_pubkey(), b"Hello World");
+        let tx = &psbt.unsigned_tx;
+        assert_eq!(tx.input.len(), 1);
+        assert_eq!(
+            tx.input[0].previous_output,
+            bitcoin::OutPoint::new(to_spend.compute_txid(), 0)
+        );
+        assert_eq!(tx.output.len(), 1);
+        assert!(tx.output[0].script_pubkey.is_op_return());
+
+        ms.shutdown();
+    }
+
     #[test]
     fn create_recovery() {
         let dummy_tx = bitcoin::Transaction {
@@ -3151,7 +5285,7 @@ mod tests {
         let invalid_addr =
             bitcoin::Address::from_str("tb1qfufcrdyarcg5eph608c6l8vktrc9re6agu4se2").unwrap();
         assert!(matches!(
-            control.create_recovery(invalid_addr, &[], 1, None),
+            control.create_recovery(invalid_addr, &[], 1_000, None),
             Err(CommandError::Address(
                 address::error::ParseError::NetworkValidation { .. }
             ))
@@ -3159,12 +5293,12 @@ mod tests {
 
         // We have no coins to create recovery.
         assert!(matches!(
-            control.create_recovery(dummy_addr.clone(), &[], 1, None),
+            control.create_recovery(dummy_addr.clone(), &[], 1_000, None),
             Err(CommandError::RecoveryNotAvailable),
         ));
         // Coin is unknown.
         assert_eq!(
-            control.create_recovery(dummy_addr.clone(), &[dummy_op], 1, None),
+            control.create_recovery(dummy_addr.clone(), &[dummy_op], 1_000, None),
             Err(CommandError::UnknownOutpoint(dummy_op)),
         );
 
@@ -3183,39 +5317,60 @@ mod tests {
         db_conn.new_unspent_coins(&[dummy_coin]);
         // Recovery not available for unconfirmed coins.
         assert!(matches!(
-            control.create_recovery(dummy_addr.clone(), &[], 1, None),
+            control.create_recovery(dummy_addr.clone(), &[], 1_000, None),
             Err(CommandError::RecoveryNotAvailable),
         ));
         assert_eq!(
-            control.create_recovery(dummy_addr.clone(), &[dummy_op], 1, None),
+            control.create_recovery(dummy_addr.clone(), &[dummy_op], 1_000, None),
             Err(CommandError::OutpointNotRecoverable(dummy_op, 10)),
         );
 
         // Confirm coin such that timelock (10) has not expired at next block (101).
         db_conn.confirm_coins(&[(dummy_op, 92, 100_000)]);
         assert!(matches!(
-            control.create_recovery(dummy_addr.clone(), &[], 1, None),
+            control.create_recovery(dummy_addr.clone(), &[], 1_000, None),
             Err(CommandError::RecoveryNotAvailable),
         ));
         assert_eq!(
-            control.create_recovery(dummy_addr.clone(), &[dummy_op], 1, None),
+            control.create_recovery(dummy_addr.clone(), &[dummy_op], 1_000, None),
             Err(CommandError::OutpointNotRecoverable(dummy_op, 10)),
         );
 
         // If we use a smaller timelock value it works, even though we don't have any such
         // recovery timelock (see https://github.com/wizardsardine/coincube/issues/1089).
         assert!(control
-            .create_recovery(dummy_addr.clone(), &[], 1, Some(9))
+            .create_recovery(dummy_addr.clone(), &[], 1_000, Some(9))
             .is_ok());
         assert!(control
-            .create_recovery(dummy_addr.clone(), &[dummy_op], 1, Some(9))
+            .create_recovery(dummy_addr.clone(), &[dummy_op], 1_000, Some(9))
             .is_ok());
 
+        // A recovery to hand over to an heir can be created before the timelock matured, but
+        // only for a recovery path of the descriptor.
+        assert_eq!(
+            control.create_heir_recovery(dummy_addr.clone(), 1_000, 9),
+            Err(CommandError::UnknownRecoveryPath(9)),
+        );
+        assert_eq!(
+            control.create_heir_recovery(dummy_addr.clone(), 0, 10),
+            Err(CommandError::InvalidFeerate(0)),
+        );
+        let psbt = control
+            .create_heir_recovery(dummy_addr.clone(), 1_000, 10)
+            .unwrap()
+            .psbt;
+        assert_eq!(psbt.unsigned_tx.input.len(), 1);
+        assert_eq!(psbt.unsigned_tx.input[0].previous_output, dummy_op);
+        assert_eq!(
+            psbt.unsigned_tx.input[0].sequence,
+            bitcoin::Sequence::from_height(10)
+        );
+
         // Remove coin, re-add and confirm such that recovery available at next block.
         db_conn.remove_coins(&[dummy_op]);
         db_conn.new_unspent_coins(&[dummy_coin]);
         db_conn.confirm_coins(&[(dummy_op, 91, 100_000)]);
-        let res = control.create_recovery(dummy_addr.clone(), &[], 1, None);
+        let res = control.create_recovery(dummy_addr.clone(), &[], 1_000, None);
         assert!(res.is_ok());
         let psbt = res.unwrap().psbt;
         assert_eq!(psbt.outputs.len(), 1);
@@ -3232,11 +5387,11 @@ mod tests {
 
         // If we pass a larger timelock, it no longer works:
         assert!(matches!(
-            control.create_recovery(dummy_addr.clone(), &[], 1, Some(11)),
+            control.create_recovery(dummy_addr.clone(), &[], 1_000, Some(11)),
             Err(CommandError::RecoveryNotAvailable),
         ));
         assert_eq!(
-            control.create_recovery(dummy_addr.clone(), &[dummy_op], 1, Some(11)),
+            control.create_recovery(dummy_addr.clone(), &[dummy_op], 1_000, Some(11)),
             Err(CommandError::OutpointNotRecoverable(dummy_op, 11)),
         );
 
@@ -3247,11 +5402,11 @@ mod tests {
                 .unwrap(),
         )]);
         assert!(matches!(
-            control.create_recovery(dummy_addr.clone(), &[], 1, None),
+            control.create_recovery(dummy_addr.clone(), &[], 1_000, None),
             Err(CommandError::RecoveryNotAvailable),
         ));
         assert_eq!(
-            control.create_recovery(dummy_addr.clone(), &[dummy_op], 1, None),
+            control.create_recovery(dummy_addr.clone(), &[dummy_op], 1_000, None),
             Err(CommandError::AlreadySpent(dummy_op)),
         );
 
@@ -3263,13 +5418,13 @@ mod tests {
         db_conn.new_unspent_coins(&[dummy_coin]);
         db_conn.confirm_coins(&[(dummy_op, 91, 100_000)]);
         assert_eq!(
-            control.create_recovery(dummy_addr.clone(), &[], 1, None),
+            control.create_recovery(dummy_addr.clone(), &[], 1_000, None),
             Err(CommandError::SpendCreation(
                 SpendCreationError::CoinSelection(InsufficientFunds { missing: 1 })
             )),
         );
         assert_eq!(
-            control.create_recovery(dummy_addr.clone(), &[dummy_op], 1, None),
+            control.create_recovery(dummy_addr.clone(), &[dummy_op], 1_000, None),
             Err(CommandError::SpendCreation(
                 SpendCreationError::CoinSelection(InsufficientFunds { missing: 1 })
             )),
@@ -3292,22 +5447,22 @@ mod tests {
         db_conn.confirm_coins(&[(dummy_op_2, 92, 200_000)]);
         // Coin cannot be used as the timelock will still be in place at the next block.
         assert_eq!(
-            control.create_recovery(dummy_addr.clone(), &[], 1, None),
+            control.create_recovery(dummy_addr.clone(), &[], 1_000, None),
             Err(CommandError::SpendCreation(
                 SpendCreationError::CoinSelection(InsufficientFunds { missing: 1 })
             )),
         );
         // If we try to specify the new coin, we'll get an error that the coin is not recoverable.
         assert_eq!(
-            control.create_recovery(dummy_addr.clone(), &[dummy_op, dummy_op_2], 1, None),
+            control.create_recovery(dummy_addr.clone(), &[dummy_op, dummy_op_2], 1_000, None),
             Err(CommandError::OutpointNotRecoverable(dummy_op_2, 10)),
         );
         // Using a shorter timelock parameter works:
         assert!(control
-            .create_recovery(dummy_addr.clone(), &[], 1, Some(9))
+            .create_recovery(dummy_addr.clone(), &[], 1_000, Some(9))
             .is_ok());
         assert!(control
-            .create_recovery(dummy_addr.clone(), &[dummy_op, dummy_op_2], 1, Some(9))
+            .create_recovery(dummy_addr.clone(), &[dummy_op, dummy_op_2], 1_000, Some(9))
             .is_ok());
 
         // Now re-add the coin with a confirmation one block earlier.
@@ -3316,7 +5471,7 @@ mod tests {
         db_conn.confirm_coins(&[(dummy_op_2, 91, 200_000)]);
 
         // Now both coins are used in the recovery and we have enough funds.
-        let res = control.create_recovery(dummy_addr.clone(), &[], 1, None);
+        let res = control.create_recovery(dummy_addr.clone(), &[], 1_000, None);
         assert!(res.is_ok());
         let psbt = res.unwrap().psbt;
         assert_eq!(psbt.outputs.len(), 1);
@@ -3332,7 +5487,7 @@ mod tests {
         );
 
         // Do the same again, now specifying the outpoints explicitly.
-        let res = control.create_recovery(dummy_addr.clone(), &[dummy_op, dummy_op_2], 1, None);
+        let res = control.create_recovery(dummy_addr.clone(), &[dummy_op, dummy_op_2], 1_000, None);
         assert!(res.is_ok());
         let psbt = res.unwrap().psbt;
         assert_eq!(psbt.outputs.len(), 1);
@@ -3347,7 +5502,7 @@ mod tests {
         );
 
         // Now check that increasing the feerate increases the fee.
-        let res = control.create_recovery(dummy_addr.clone(), &[], 2, None);
+        let res = control.create_recovery(dummy_addr.clone(), &[], 2_000, None);
         assert!(res.is_ok());
         let psbt = res.unwrap().psbt;
         assert_eq!(
@@ -2,7 +2,7 @@ use coincube_core::descriptors::CoincubeDescriptor;
 
 use std::{fmt, net::SocketAddr, path::PathBuf, str::FromStr, time::Duration};
 
-use crate::datadir::DataDirectory;
+use crate::{datadir::DataDirectory, policy::SpendingPolicy};
 use miniscript::bitcoin::Network;
 
 use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
@@ -177,6 +177,9 @@ pub struct Config {
     /// Settings specific to the Bitcoin backend.
     #[serde(flatten)]
     pub bitcoin_backend: Option<BitcoinBackend>,
+    /// Rules enforced by the daemon when creating and broadcasting spends.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spending_policy: Option<SpendingPolicy>,
 }
 
 impl Config {
@@ -194,6 +197,7 @@ impl Config {
             main_descriptor,
             data_directory: Some(data_directory.path().to_path_buf()),
             data_dir: None,
+            spending_policy: None,
         }
     }
 
@@ -314,6 +318,19 @@ impl Config {
             )));
         }
 
+        if let Some(addr) = self.spending_policy.as_ref().and_then(|policy| {
+            policy
+                .allowed_destinations
+                .iter()
+                .find(|addr| !addr.is_valid_for_network(self.bitcoin_config.network))
+        }) {
+            return Err(ConfigError::Unexpected(format!(
+                "Our bitcoin network is {} but allowed destination '{}' is not for this network",
+                self.bitcoin_config.network,
+                addr.assume_checked_ref()
+            )));
+        }
+
         // TODO: check the semantics of the main descriptor
 
         Ok(())
@@ -405,6 +422,49 @@ mod tests {
             assert_eq!(toml_str, serialized);
         }
 
+        // A valid config with a spending policy
+        {
+            let toml_str = r#"
+            log_level = "debug"
+            main_descriptor = "wsh(andor(pk([aabbccdd]tpubDEN9WSToTyy9ZQfaYqSKfmVqmq1VVLNtYfj3Vkqh67et57eJ5sTKZQBkHqSwPUsoSskJeaYnPttHe2VrkCsKA27kUaN9SDc5zhqeLzKa1rr/<0;1>/*),older(10000),pk([aabbccdd]tpubD8LYfn6njiA2inCoxwM7EuN3cuLVcaHAwLYeups13dpevd3nHLRdK9NdQksWXrhLQVxcUZRpnp5CkJ1FhE61WRAsHxDNAkvGkoQkAeWDYjV/<0;1>/*)))#dw4ulnrs"
+
+            [bitcoin_config]
+            network = "testnet"
+            poll_interval_secs = 18
+
+            [bitcoind_config]
+            cookie_path = "/home/user/.bitcoin/.cookie"
+            addr = "127.0.0.1:8332"
+
+            [spending_policy]
+            daily_limit = 1000000
+            allowed_destinations = ["tb1qfufcrdyarcg5eph608c6l8vktrc9re6agu4se2"]
+            delay_threshold = 500000
+            broadcast_delay = 86400
+            require_approval = true
+            "#;
+            let mut config = toml::from_str::<Config>(toml_str).expect("Deserializing toml_str");
+            let policy = config.spending_policy.clone().unwrap();
+            assert_eq!(policy.daily_limit, Some(1_000_000));
+            assert_eq!(policy.weekly_limit, None);
+            assert_eq!(policy.allowed_destinations.len(), 1);
+            assert_eq!(policy.delay_threshold, Some(500_000));
+            assert_eq!(policy.broadcast_delay, 86_400);
+            assert!(policy.require_approval);
+            config.check().unwrap();
+
+            // The allowed destinations must be for our network.
+            config
+                .spending_policy
+                .as_mut()
+                .unwrap()
+                .allowed_destinations = vec![miniscript::bitcoin::Address::from_str(
+                "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4",
+            )
+            .unwrap()];
+            config.check().unwrap_err();
+        }
+
         // Invalid desc checksum
         let toml_str = r#"
             log_level = "trace"
@@ -7,7 +7,7 @@ pub mod sqlite;
 use crate::{
     bitcoin::BlockChainTip,
     database::sqlite::{
-        schema::{DbBlockInfo, DbCoin, DbTip},
+        schema::{DbBlockInfo, DbCoin, DbScheduledBroadcast, DbTip},
         SqliteConn, SqliteDb,
     },
 };
@@ -38,6 +38,8 @@ pub struct Wallet {
     pub rescan_timestamp: Option<u32>,
     /// Timestamp at which the last poll of the blockchain completed, if any,
     pub last_poll_timestamp: Option<u32>,
+    /// Number of addresses watched past the highest derivation index.
+    pub gap_limit: u32,
 }
 
 pub trait DatabaseInterface: Send {
@@ -93,6 +95,20 @@ pub trait DatabaseConnection {
         secp: &secp256k1::Secp256k1<secp256k1::VerifyOnly>,
     );
 
+    /// Get the number of addresses watched past the highest derivation index.
+    fn gap_limit(&mut self) -> u32;
+
+    /// Set the number of addresses watched past the highest derivation index.
+    fn set_gap_limit(&mut self, gap_limit: u32, secp: &secp256k1::Secp256k1<secp256k1::VerifyOnly>);
+
+    /// Get the highest derivation index of a receive or change address which received a coin,
+    /// if any.
+    fn highest_used_index(&mut self, change: bool) -> Option<bip32::ChildNumber>;
+
+    /// Get the number of coins, past or present, received on each of our receive or change
+    /// addresses. Addresses which never received a coin are not included.
+    fn address_use_counts(&mut self, change: bool) -> HashMap<bip32::ChildNumber, u32>;
+
     /// Get the timestamp at which to start rescaning from, if any.
     fn rescan_timestamp(&mut self) -> Option<u32>;
 
@@ -123,6 +139,13 @@ pub trait DatabaseConnection {
         outpoints: &[bitcoin::OutPoint],
     ) -> HashMap<bitcoin::OutPoint, Coin>;
 
+    /// Get all our coins, past or present, which match this filter.
+    fn search_coins(&mut self, filter: &SearchFilter) -> HashMap<bitcoin::OutPoint, Coin>;
+
+    /// Get the txids of the transactions which created or spent one of our coins and match this
+    /// filter, most recent first with unconfirmed transactions at the top.
+    fn search_txids(&mut self, filter: &SearchFilter, limit: u64) -> Vec<bitcoin::Txid>;
+
     /// List coins that are being spent and whose spending transaction is still unconfirmed.
     fn list_spending_coins(&mut self) -> HashMap<bitcoin::OutPoint, Coin>;
 
@@ -158,12 +181,41 @@ pub trait DatabaseConnection {
     /// Insert a new Spend transaction or replace an existing one.
     fn store_spend(&mut self, psbt: &Psbt);
 
-    /// List all existing Spend transactions, along with an optional last update timestamp.
-    fn list_spend(&mut self) -> Vec<(Psbt, Option<u32>)>;
+    /// List all existing Spend transactions, along with an optional last update timestamp and
+    /// an optional approval timestamp.
+    fn list_spend(&mut self) -> Vec<(Psbt, Option<u32>, Option<u32>)>;
+
+    /// Get the optional last update and approval timestamps of a Spend transaction, if it exists.
+    fn spend_tx_timestamps(&mut self, txid: &bitcoin::Txid) -> Option<(Option<u32>, Option<u32>)>;
 
-    /// Delete a Spend transaction from database.
+    /// Mark a Spend transaction as approved now. Keeps the time of any previous approval.
+    fn approve_spend(&mut self, txid: &bitcoin::Txid);
+
+    /// Delete a Spend transaction from database, along with any scheduled broadcast of it.
     fn delete_spend(&mut self, txid: &bitcoin::Txid);
 
+    /// Schedule the broadcast of a Spend transaction once the chain reaches the block height and
+    /// the clock the timestamp, whichever of the two are set. Replaces any previous schedule.
+    fn schedule_broadcast(
+        &mut self,
+        txid: &bitcoin::Txid,
+        block_height: Option<i32>,
+        timestamp: Option<u32>,
+    );
+
+    /// List the Spend transactions scheduled for broadcast, whether they were broadcast already
+    /// or not.
+    fn scheduled_broadcasts(&mut self) -> Vec<ScheduledBroadcast>;
+
+    /// Remove a Spend transaction from the broadcast queue.
+    fn cancel_broadcast(&mut self, txid: &bitcoin::Txid);
+
+    /// Record the outcome of an attempt to broadcast a scheduled Spend transaction.
+    fn record_broadcast_attempt(&mut self, txid: &bitcoin::Txid, result: Result<(), &str>);
+
+    /// Mark a scheduled Spend transaction as impossible to broadcast, for the given reason.
+    fn fail_broadcast(&mut self, txid: &bitcoin::Txid, error: &str);
+
     /// Update, for a set of items (as key), their label (as value). A `None` value deletes the
     /// label.
     fn update_labels(&mut self, items: &HashMap<LabelItem, Option<String>>);
@@ -220,6 +272,7 @@ impl DatabaseConnection for SqliteConn {
             change_index: db_wallet.change_derivation_index,
             rescan_timestamp: db_wallet.rescan_timestamp,
             last_poll_timestamp: db_wallet.last_poll_timestamp,
+            gap_limit: db_wallet.gap_limit,
         }
     }
 
@@ -255,6 +308,26 @@ impl DatabaseConnection for SqliteConn {
         self.set_derivation_index(index, true, secp)
     }
 
+    fn gap_limit(&mut self) -> u32 {
+        self.wallet().gap_limit
+    }
+
+    fn set_gap_limit(
+        &mut self,
+        gap_limit: u32,
+        secp: &secp256k1::Secp256k1<secp256k1::VerifyOnly>,
+    ) {
+        self.set_gap_limit(gap_limit, secp)
+    }
+
+    fn highest_used_index(&mut self, change: bool) -> Option<bip32::ChildNumber> {
+        self.highest_used_index(change)
+    }
+
+    fn address_use_counts(&mut self, change: bool) -> HashMap<bip32::ChildNumber, u32> {
+        self.address_use_counts(change)
+    }
+
     fn rescan_timestamp(&mut self) -> Option<u32> {
         self.wallet().rescan_timestamp
     }
@@ -287,6 +360,17 @@ impl DatabaseConnection for SqliteConn {
             .collect()
     }
 
+    fn search_coins(&mut self, filter: &SearchFilter) -> HashMap<bitcoin::OutPoint, Coin> {
+        self.search_coins(filter)
+            .into_iter()
+            .map(|db_coin| (db_coin.outpoint, db_coin.into()))
+            .collect()
+    }
+
+    fn search_txids(&mut self, filter: &SearchFilter, limit: u64) -> Vec<bitcoin::Txid> {
+        self.search_txids(filter, limit)
+    }
+
     fn list_spending_coins(&mut self) -> HashMap<bitcoin::OutPoint, Coin> {
         self.list_spending_coins()
             .into_iter()
@@ -351,17 +435,54 @@ impl DatabaseConnection for SqliteConn {
         self.store_spend(psbt)
     }
 
-    fn list_spend(&mut self) -> Vec<(Psbt, Option<u32>)> {
+    fn list_spend(&mut self) -> Vec<(Psbt, Option<u32>, Option<u32>)> {
         self.list_spend()
             .into_iter()
-            .map(|db_spend| (db_spend.psbt, db_spend.updated_at))
+            .map(|db_spend| (db_spend.psbt, db_spend.updated_at, db_spend.approved_at))
             .collect()
     }
 
+    fn spend_tx_timestamps(&mut self, txid: &bitcoin::Txid) -> Option<(Option<u32>, Option<u32>)> {
+        self.db_spend(txid)
+            .map(|db_spend| (db_spend.updated_at, db_spend.approved_at))
+    }
+
+    fn approve_spend(&mut self, txid: &bitcoin::Txid) {
+        self.approve_spend(txid)
+    }
+
     fn delete_spend(&mut self, txid: &bitcoin::Txid) {
         self.delete_spend(txid)
     }
 
+    fn schedule_broadcast(
+        &mut self,
+        txid: &bitcoin::Txid,
+        block_height: Option<i32>,
+        timestamp: Option<u32>,
+    ) {
+        self.schedule_broadcast(txid, block_height, timestamp)
+    }
+
+    fn scheduled_broadcasts(&mut self) -> Vec<ScheduledBroadcast> {
+        self.scheduled_broadcasts()
+            .into_iter()
+            .map(ScheduledBroadcast::from)
+            .collect()
+    }
+
+    fn cancel_broadcast(&mut self, txid: &bitcoin::Txid) {
+        self.cancel_broadcast(txid)
+    }
+
+    fn record_broadcast_attempt(&mut self, txid: &bitcoin::Txid, result: Result<(), &str>) {
+        self.record_broadcast_attempt(txid, result)
+    }
+
+    fn fail_broadcast(&mut self, txid: &bitcoin::Txid, error: &str) {
+        self.fail_broadcast(txid, error)
+    }
+
     fn update_labels(&mut self, items: &HashMap<LabelItem, Option<String>>) {
         self.update_labels(items)
     }
@@ -484,6 +605,58 @@ impl Coin {
     }
 }
 
+/// A Spend transaction scheduled for broadcast.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScheduledBroadcast {
+    pub txid: bitcoin::Txid,
+    /// The block height from which to broadcast it, if any.
+    pub block_height: Option<i32>,
+    /// The timestamp from which to broadcast it, if any.
+    pub timestamp: Option<u32>,
+    /// When it was scheduled.
+    pub created_at: u32,
+    /// When it was broadcast, if it was.
+    pub broadcast_at: Option<u32>,
+    /// The error of the last failed attempt to broadcast it, if any.
+    pub last_error: Option<String>,
+    /// When it was found impossible to ever broadcast, if it was.
+    pub failed_at: Option<u32>,
+}
+
+impl From<DbScheduledBroadcast> for ScheduledBroadcast {
+    fn from(db_broadcast: DbScheduledBroadcast) -> ScheduledBroadcast {
+        let DbScheduledBroadcast {
+            txid,
+            block_height,
+            timestamp,
+            created_at,
+            broadcast_at,
+            last_error,
+            failed_at,
+            ..
+        } = db_broadcast;
+        ScheduledBroadcast {
+            txid,
+            block_height,
+            timestamp,
+            created_at,
+            broadcast_at,
+            last_error,
+            failed_at,
+        }
+    }
+}
+
+impl ScheduledBroadcast {
+    /// Whether it is yet to be broadcast and may be at this block height and timestamp.
+    pub fn is_due(&self, block_height: i32, now: u32) -> bool {
+        self.broadcast_at.is_none()
+            && self.failed_at.is_none()
+            && self.block_height.is_none_or(|h| block_height >= h)
+            && self.timestamp.is_none_or(|t| now >= t)
+    }
+}
+
 /// Possible (mutually exclusive) status of a coin.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum CoinStatus {
@@ -520,6 +693,55 @@ impl CoinStatus {
     }
 }
 
+/// Criteria to search our coins and transactions with. Only the criteria which are set are
+/// checked, and all of them must match.
+///
+/// A coin matches if its own data does, where its date is the time of the block it was
+/// confirmed in. A transaction matches if it created or spent a coin with a matching amount,
+/// status and address, and if its own txid and block time match. The label criterion is checked
+/// against the labels of the transaction, the coin and its address.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchFilter {
+    /// A text the label must contain, case-insensitively.
+    pub label: Option<String>,
+    /// The minimum value of the coin, inclusive.
+    pub min_amount: Option<bitcoin::Amount>,
+    /// The maximum value of the coin, inclusive.
+    pub max_amount: Option<bitcoin::Amount>,
+    /// The earliest block time as a UNIX timestamp, inclusive. Excludes unconfirmed transactions.
+    pub start: Option<u32>,
+    /// The latest block time as a UNIX timestamp, inclusive. Excludes unconfirmed transactions.
+    pub end: Option<u32>,
+    /// The statuses the coin may be in. Any status if empty.
+    pub statuses: Vec<CoinStatus>,
+    /// The address the coin was received on.
+    pub address: Option<bitcoin::Address>,
+    /// The beginning of the txid, as hex.
+    pub txid_prefix: Option<String>,
+}
+
+impl SearchFilter {
+    /// Whether a transaction or coin confirmed at this block time, if any, is within the date
+    /// range.
+    pub fn matches_time(&self, time: Option<u32>) -> bool {
+        match time {
+            Some(time) => {
+                self.start.map(|start| time >= start).unwrap_or(true)
+                    && self.end.map(|end| time <= end).unwrap_or(true)
+            }
+            None => self.start.is_none() && self.end.is_none(),
+        }
+    }
+
+    /// Whether this txid starts with the requested prefix.
+    pub fn matches_txid(&self, txid: &bitcoin::Txid) -> bool {
+        self.txid_prefix
+            .as_ref()
+            .map(|prefix| txid.to_string().starts_with(&prefix.to_lowercase()))
+            .unwrap_or(true)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum LabelItem {
     Address(bitcoin::Address),
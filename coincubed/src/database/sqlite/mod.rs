@@ -10,20 +10,22 @@
 pub mod schema;
 mod utils;
 
+pub use utils::DEFAULT_GAP_LIMIT;
+
 use crate::{
     bitcoin::BlockChainTip,
     database::{
         sqlite::{
             schema::{
-                DbAddress, DbCoin, DbLabel, DbLabelledKind, DbSpendTransaction, DbTip, DbWallet,
-                DbWalletTransaction, SCHEMA,
+                DbAddress, DbCoin, DbLabel, DbLabelledKind, DbScheduledBroadcast,
+                DbSpendTransaction, DbTip, DbWallet, DbWalletTransaction, SCHEMA,
             },
             utils::{
                 create_fresh_db, curr_timestamp, db_exec, db_query, db_tx_query, db_version,
-                maybe_apply_migration, LOOK_AHEAD_LIMIT,
+                extend_addresses, maybe_apply_migration,
             },
         },
-        Coin, CoinStatus, LabelItem,
+        Coin, CoinStatus, LabelItem, SearchFilter,
     },
 };
 use coincube_core::descriptors::CoincubeDescriptor;
@@ -43,7 +45,7 @@ use miniscript::bitcoin::{
     secp256k1,
 };
 
-const DB_VERSION: i64 = 8;
+const DB_VERSION: i64 = 11;
 
 /// Last database version for which Bitcoin transactions were not stored in database. In practice
 /// this meant we relied on the bitcoind watchonly wallet to store them for us.
@@ -220,6 +222,31 @@ pub struct SqliteConn {
     conn: rusqlite::Connection,
 }
 
+// The SQL condition for a coin to be in any of these statuses.
+fn status_condition(statuses: &[CoinStatus]) -> String {
+    statuses
+        .iter()
+        .map(|c| {
+            format!(
+                "({})",
+                match c {
+                    CoinStatus::Unconfirmed => {
+                        "blocktime IS NULL AND spend_txid IS NULL"
+                    }
+                    CoinStatus::Confirmed => {
+                        "blocktime IS NOT NULL AND spend_txid IS NULL"
+                    }
+                    CoinStatus::Spending => {
+                        "spend_txid IS NOT NULL AND spend_block_time IS NULL"
+                    }
+                    CoinStatus::Spent => "spend_block_time IS NOT NULL",
+                }
+            )
+        })
+        .collect::<Vec<String>>()
+        .join(" OR ")
+}
+
 impl SqliteConn {
     pub fn db_version(&mut self) -> i64 {
         db_version(&mut self.conn).expect("db must not fail")
@@ -266,8 +293,8 @@ impl SqliteConn {
 
     /// Set the derivation index for receiving or change addresses.
     ///
-    /// This will populate the address->deriv_index mapping with all the new entries between the
-    /// former and new gap limit indexes.
+    /// This will populate the address->deriv_index mapping with all the new entries up to the gap
+    /// limit past the new index.
     pub fn set_derivation_index(
         &mut self,
         index: bip32::ChildNumber,
@@ -287,7 +314,9 @@ impl SqliteConn {
             // Make sure we don't set a lower derivation index. This can happen since the
             // derivation is set outside the atomic transaction. So there may be a race between say
             // the Bitcoin poller thread and the JSONRPC commands thread.
-            if (change && index <= db_wallet.change_derivation_index) || (!change && index <= db_wallet.deposit_derivation_index) {
+            if (change && index <= db_wallet.change_derivation_index)
+                || (!change && index <= db_wallet.deposit_derivation_index)
+            {
                 // It was already set at a higher index.
                 return Ok(());
             }
@@ -306,34 +335,94 @@ impl SqliteConn {
                 )?;
             }
 
-            // Now if this new index is higher than the highest of our current derivation indexes,
-            // populate the addresses mapping for derivation indexes between our previous "gap
-            // limit index" and the new one.
-            let curr_highest_index = cmp::max(
-                db_wallet.deposit_derivation_index,
-                db_wallet.change_derivation_index,
-            ).into();
-            if index_u32 > curr_highest_index {
-                let receive_desc = db_wallet.main_descriptor.receive_descriptor();
-                let change_desc = db_wallet.main_descriptor.change_descriptor();
-
-                for index in curr_highest_index + 1..=index_u32 {
-                    let la_index = index + LOOK_AHEAD_LIMIT - 1;
-                    let receive_addr = receive_desc.derive(la_index.into(), secp).address(network);
-                    let change_addr = change_desc.derive(la_index.into(), secp).address(network);
-                    db_tx.execute(
-                        "INSERT INTO addresses (receive_address, change_address, derivation_index) VALUES (?1, ?2, ?3)",
-                        rusqlite::params![receive_addr.to_string(), change_addr.to_string(), la_index],
-                    )?;
-                }
+            // Now make sure the addresses mapping covers the gap limit past this new index. This is
+            // a no-op if it is not higher than the highest of our current derivation indexes.
+            extend_addresses(
+                db_tx,
+                &db_wallet.main_descriptor,
+                network,
+                index_u32,
+                db_wallet.gap_limit,
+                secp,
+            )
+        })
+        .expect("Database must be available")
+    }
 
-            }
+    /// Set the number of addresses to watch past the highest of our derivation indexes.
+    ///
+    /// This will populate the address->deriv_index mapping with the missing entries if the gap
+    /// limit is increased. Entries are never removed if it is decreased.
+    pub fn set_gap_limit(
+        &mut self,
+        gap_limit: u32,
+        secp: &secp256k1::Secp256k1<secp256k1::VerifyOnly>,
+    ) {
+        let network = self.db_tip().network;
 
-            Ok(())
+        db_exec(&mut self.conn, |db_tx| {
+            let db_wallet: DbWallet =
+                db_tx_query(db_tx, "SELECT * FROM wallets", rusqlite::params![], |row| {
+                    row.try_into()
+                })?
+                .pop()
+                .expect("There is always a row in the wallet table");
+            db_tx.execute(
+                "UPDATE wallets SET gap_limit = (?1) WHERE id = (?2)",
+                rusqlite::params![gap_limit, WALLET_ID],
+            )?;
+            let highest_index = cmp::max(
+                db_wallet.deposit_derivation_index,
+                db_wallet.change_derivation_index,
+            );
+            extend_addresses(
+                db_tx,
+                &db_wallet.main_descriptor,
+                network,
+                highest_index.into(),
+                gap_limit,
+                secp,
+            )
         })
         .expect("Database must be available")
     }
 
+    /// Get the highest derivation index of a receive or change address which received a coin,
+    /// if any.
+    pub fn highest_used_index(&mut self, change: bool) -> Option<bip32::ChildNumber> {
+        db_query(
+            &mut self.conn,
+            "SELECT MAX(derivation_index) FROM coins WHERE is_change = ?1",
+            rusqlite::params![change],
+            |row| {
+                let index: Option<u32> = row.get(0)?;
+                Ok(index.map(bip32::ChildNumber::from))
+            },
+        )
+        .expect("Db must not fail")
+        .pop()
+        .flatten()
+    }
+
+    /// Get the number of coins received on each of our receive or change addresses which
+    /// received at least one.
+    pub fn address_use_counts(&mut self, change: bool) -> HashMap<bip32::ChildNumber, u32> {
+        db_query(
+            &mut self.conn,
+            "SELECT derivation_index, COUNT(*) FROM coins WHERE is_change = ?1 \
+             GROUP BY derivation_index",
+            rusqlite::params![change],
+            |row| {
+                let index: u32 = row.get(0)?;
+                let count: u32 = row.get(1)?;
+                Ok((bip32::ChildNumber::from(index), count))
+            },
+        )
+        .expect("Db must not fail")
+        .into_iter()
+        .collect()
+    }
+
     pub fn set_wallet_rescan_timestamp(&mut self, timestamp: u32) {
         db_exec(&mut self.conn, |db_tx| {
             // NOTE: this will need to be updated if we ever implement multi-wallet support
@@ -391,27 +480,7 @@ impl SqliteConn {
         statuses: &[CoinStatus],
         outpoints: &[bitcoin::OutPoint],
     ) -> Vec<DbCoin> {
-        let status_condition = statuses
-            .iter()
-            .map(|c| {
-                format!(
-                    "({})",
-                    match c {
-                        CoinStatus::Unconfirmed => {
-                            "blocktime IS NULL AND spend_txid IS NULL"
-                        }
-                        CoinStatus::Confirmed => {
-                            "blocktime IS NOT NULL AND spend_txid IS NULL"
-                        }
-                        CoinStatus::Spending => {
-                            "spend_txid IS NOT NULL AND spend_block_time IS NULL"
-                        }
-                        CoinStatus::Spent => "spend_block_time IS NOT NULL",
-                    }
-                )
-            })
-            .collect::<Vec<String>>()
-            .join(" OR ");
+        let status_condition = status_condition(statuses);
         // SELECT * FROM coins WHERE (txid, vout) IN ((txidA, voutA), (txidB, voutB));
         let op_condition = if !outpoints.is_empty() {
             let mut cond = "(txid, vout) IN (VALUES ".to_string();
@@ -448,6 +517,129 @@ impl SqliteConn {
         .expect("Db must not fail")
     }
 
+    // Get the coins matching the amount, status, address and date criteria of this filter,
+    // along with the address they were received on.
+    fn search_coin_rows(&mut self, filter: &SearchFilter) -> Vec<(DbCoin, Option<String>)> {
+        const ADDRESS: &str = "CASE WHEN coins.is_change THEN addresses.change_address \
+                               ELSE addresses.receive_address END";
+        let mut conditions = Vec::new();
+        let mut params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+        if !filter.statuses.is_empty() {
+            conditions.push(format!("({})", status_condition(&filter.statuses)));
+        }
+        if let Some(min) = filter.min_amount {
+            params.push(Box::new(min.to_sat()));
+            conditions.push(format!("coins.amount_sat >= ?{}", params.len()));
+        }
+        if let Some(max) = filter.max_amount {
+            params.push(Box::new(max.to_sat()));
+            conditions.push(format!("coins.amount_sat <= ?{}", params.len()));
+        }
+        if let Some(start) = filter.start {
+            params.push(Box::new(start));
+            conditions.push(format!("coins.blocktime >= ?{}", params.len()));
+        }
+        if let Some(end) = filter.end {
+            params.push(Box::new(end));
+            conditions.push(format!("coins.blocktime <= ?{}", params.len()));
+        }
+        if let Some(address) = &filter.address {
+            params.push(Box::new(address.to_string()));
+            conditions.push(format!("{} = ?{}", ADDRESS, params.len()));
+        }
+        let where_clause = if conditions.is_empty() {
+            String::new()
+        } else {
+            format!(" WHERE {}", conditions.join(" AND "))
+        };
+        let query = format!(
+            "SELECT coins.*, {} AS address FROM coins \
+             LEFT JOIN addresses ON addresses.derivation_index = coins.derivation_index{}",
+            ADDRESS, where_clause
+        );
+        let params: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
+        db_query(&mut self.conn, &query, params.as_slice(), |row| {
+            let coin: DbCoin = row.try_into()?;
+            let address: Option<String> = row.get("address")?;
+            Ok((coin, address))
+        })
+        .expect("Db must not fail")
+    }
+
+    // Get the items whose label contains this text, case-insensitively.
+    fn labelled_items(&mut self, text: &str) -> HashSet<String> {
+        let pattern = format!(
+            "%{}%",
+            text.replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_")
+        );
+        db_query(
+            &mut self.conn,
+            "SELECT item FROM labels WHERE value LIKE ?1 ESCAPE '\\'",
+            rusqlite::params![pattern],
+            |row| row.get(0),
+        )
+        .expect("Db must not fail")
+        .into_iter()
+        .collect()
+    }
+
+    /// Get all the coins matching this filter.
+    pub fn search_coins(&mut self, filter: &SearchFilter) -> Vec<DbCoin> {
+        let labelled = filter.label.as_ref().map(|text| self.labelled_items(text));
+        self.search_coin_rows(filter)
+            .into_iter()
+            .filter(|(coin, address)| {
+                filter.matches_txid(&coin.outpoint.txid)
+                    && labelled.as_ref().is_none_or(|items| {
+                        items.contains(&coin.outpoint.to_string())
+                            || items.contains(&coin.outpoint.txid.to_string())
+                            || address.as_ref().is_some_and(|a| items.contains(a))
+                    })
+            })
+            .map(|(coin, _)| coin)
+            .collect()
+    }
+
+    /// Get the txids of the transactions creating or spending a coin which match this filter.
+    /// Unconfirmed transactions come first, then the most recent ones.
+    pub fn search_txids(&mut self, filter: &SearchFilter, limit: u64) -> Vec<bitcoin::Txid> {
+        let labelled = filter.label.as_ref().map(|text| self.labelled_items(text));
+        // The date criteria apply to the transactions, not the coins.
+        let coins_filter = SearchFilter {
+            start: None,
+            end: None,
+            ..filter.clone()
+        };
+        let mut txs = HashMap::<bitcoin::Txid, Option<u32>>::new();
+        for (coin, address) in self.search_coin_rows(&coins_filter) {
+            let coin_labelled = labelled.as_ref().is_none_or(|items| {
+                items.contains(&coin.outpoint.to_string())
+                    || address.as_ref().is_some_and(|a| items.contains(a))
+            });
+            let created = (coin.outpoint.txid, coin.block_info.map(|b| b.time));
+            let spent = coin
+                .spend_txid
+                .map(|txid| (txid, coin.spend_block.map(|b| b.time)));
+            for (txid, time) in std::iter::once(created).chain(spent) {
+                let tx_labelled = coin_labelled
+                    || labelled
+                        .as_ref()
+                        .is_some_and(|items| items.contains(&txid.to_string()));
+                if tx_labelled && filter.matches_time(time) && filter.matches_txid(&txid) {
+                    txs.insert(txid, time);
+                }
+            }
+        }
+        let mut txs: Vec<_> = txs.into_iter().collect();
+        txs.sort_by_key(|(txid, time)| (cmp::Reverse(time.unwrap_or(u32::MAX)), *txid));
+        txs.into_iter()
+            .take(limit.try_into().unwrap_or(usize::MAX))
+            .map(|(txid, _)| txid)
+            .collect()
+    }
+
     /// List coins that are being spent and whose spending transaction is still unconfirmed.
     pub fn list_spending_coins(&mut self) -> Vec<DbCoin> {
         self.coins(&[CoinStatus::Spending], &[])
@@ -624,6 +816,19 @@ impl SqliteConn {
         .expect("Db must not fail");
     }
 
+    /// Mark a Spend transaction as approved now. Keeps the time of any previous approval.
+    pub fn approve_spend(&mut self, txid: &bitcoin::Txid) {
+        db_exec(&mut self.conn, |db_tx| {
+            db_tx.execute(
+                "UPDATE spend_transactions SET approved_at = ?1 \
+                 WHERE txid = ?2 AND approved_at IS NULL",
+                rusqlite::params![curr_timestamp(), txid[..].to_vec()],
+            )?;
+            Ok(())
+        })
+        .expect("Db must not fail");
+    }
+
     pub fn list_spend(&mut self) -> Vec<DbSpendTransaction> {
         db_query(
             &mut self.conn,
@@ -913,12 +1118,94 @@ impl SqliteConn {
         w_txs
     }
 
+    /// Delete a Spend transaction, along with any scheduled broadcast of it.
     pub fn delete_spend(&mut self, txid: &bitcoin::Txid) {
         db_exec(&mut self.conn, |db_tx| {
             db_tx.execute(
                 "DELETE FROM spend_transactions WHERE txid = ?1",
                 rusqlite::params![txid[..].to_vec()],
             )?;
+            db_tx.execute(
+                "DELETE FROM broadcast_queue WHERE txid = ?1",
+                rusqlite::params![txid[..].to_vec()],
+            )?;
+            Ok(())
+        })
+        .expect("Db must not fail");
+    }
+
+    /// Schedule the broadcast of a Spend transaction, replacing any previous schedule for it.
+    pub fn schedule_broadcast(
+        &mut self,
+        txid: &bitcoin::Txid,
+        block_height: Option<i32>,
+        timestamp: Option<u32>,
+    ) {
+        assert!(block_height.is_some() || timestamp.is_some());
+        db_exec(&mut self.conn, |db_tx| {
+            db_tx.execute(
+                "INSERT INTO broadcast_queue (txid, block_height, timestamp, created_at) \
+                 VALUES (?1, ?2, ?3, ?4) \
+                 ON CONFLICT DO UPDATE SET block_height=excluded.block_height, \
+                 timestamp=excluded.timestamp, created_at=excluded.created_at, \
+                 broadcast_at=NULL, last_error=NULL, failed_at=NULL",
+                rusqlite::params![txid[..].to_vec(), block_height, timestamp, curr_timestamp()],
+            )?;
+            Ok(())
+        })
+        .expect("Db must not fail");
+    }
+
+    /// All the Spend transactions scheduled for broadcast, in the order they were scheduled.
+    pub fn scheduled_broadcasts(&mut self) -> Vec<DbScheduledBroadcast> {
+        db_query(
+            &mut self.conn,
+            "SELECT * FROM broadcast_queue ORDER BY id",
+            rusqlite::params![],
+            |row| row.try_into(),
+        )
+        .expect("Db must not fail")
+    }
+
+    /// Remove a Spend transaction from the broadcast queue.
+    pub fn cancel_broadcast(&mut self, txid: &bitcoin::Txid) {
+        db_exec(&mut self.conn, |db_tx| {
+            db_tx.execute(
+                "DELETE FROM broadcast_queue WHERE txid = ?1",
+                rusqlite::params![txid[..].to_vec()],
+            )?;
+            Ok(())
+        })
+        .expect("Db must not fail");
+    }
+
+    /// Record the outcome of an attempt to broadcast a scheduled Spend transaction.
+    pub fn record_broadcast_attempt(&mut self, txid: &bitcoin::Txid, result: Result<(), &str>) {
+        db_exec(&mut self.conn, |db_tx| {
+            match result {
+                Ok(()) => db_tx.execute(
+                    "UPDATE broadcast_queue SET broadcast_at = ?1, last_error = NULL \
+                     WHERE txid = ?2",
+                    rusqlite::params![curr_timestamp(), txid[..].to_vec()],
+                )?,
+                Err(e) => db_tx.execute(
+                    "UPDATE broadcast_queue SET last_error = ?1 WHERE txid = ?2",
+                    rusqlite::params![e, txid[..].to_vec()],
+                )?,
+            };
+            Ok(())
+        })
+        .expect("Db must not fail");
+    }
+
+    /// Mark a scheduled Spend transaction as impossible to broadcast, for the given reason. It
+    /// won't be attempted anymore.
+    pub fn fail_broadcast(&mut self, txid: &bitcoin::Txid, error: &str) {
+        db_exec(&mut self.conn, |db_tx| {
+            db_tx.execute(
+                "UPDATE broadcast_queue SET failed_at = ?1, last_error = ?2 WHERE txid = ?3",
+                rusqlite::params![curr_timestamp(), error, txid[..].to_vec()],
+            )?;
             Ok(())
         })
         .expect("Db must not fail");
@@ -2063,6 +2350,237 @@ CREATE TABLE labels (
         fs::remove_dir_all(tmp_dir).unwrap();
     }
 
+    #[test]
+    fn sqlite_gap_limit() {
+        let (tmp_dir, options, secp, db) = dummy_db();
+
+        {
+            let mut conn = db.connection().unwrap();
+            assert_eq!(conn.db_wallet().gap_limit, 200);
+            let addr_at = |index: u32| {
+                options
+                    .main_descriptor
+                    .receive_descriptor()
+                    .derive(index.into(), &secp)
+                    .address(options.bitcoind_network)
+            };
+
+            // Increasing the gap limit populates the missing addresses.
+            assert!(conn.db_address(&addr_at(200)).is_none());
+            conn.set_gap_limit(500, &secp);
+            assert_eq!(conn.db_wallet().gap_limit, 500);
+            let db_addr = conn.db_address(&addr_at(499)).unwrap();
+            assert_eq!(db_addr.derivation_index, 499.into());
+            assert!(conn.db_address(&addr_at(500)).is_none());
+
+            // Bumping a derivation index takes the new gap limit into account.
+            conn.set_derivation_index(10.into(), false, &secp);
+            let db_addr = conn.db_address(&addr_at(509)).unwrap();
+            assert_eq!(db_addr.derivation_index, 509.into());
+            assert!(conn.db_address(&addr_at(510)).is_none());
+
+            // Decreasing the gap limit doesn't remove any address, and doesn't add any until a
+            // derivation index exceeds the addresses we already have.
+            conn.set_gap_limit(50, &secp);
+            assert_eq!(conn.db_wallet().gap_limit, 50);
+            assert!(conn.db_address(&addr_at(509)).is_some());
+            conn.set_derivation_index(459.into(), false, &secp);
+            assert!(conn.db_address(&addr_at(510)).is_none());
+            conn.set_derivation_index(461.into(), false, &secp);
+            let db_addr = conn.db_address(&addr_at(510)).unwrap();
+            assert_eq!(db_addr.derivation_index, 510.into());
+            assert!(conn.db_address(&addr_at(511)).is_none());
+        }
+
+        fs::remove_dir_all(tmp_dir).unwrap();
+    }
+
+    #[test]
+    fn sqlite_address_use_counts() {
+        let (tmp_dir, _, _, db) = dummy_db();
+
+        {
+            let mut conn = db.connection().unwrap();
+            assert!(conn.address_use_counts(false).is_empty());
+
+            let txs: Vec<_> = (0..4)
+                .map(|i| bitcoin::Transaction {
+                    version: bitcoin::transaction::Version::TWO,
+                    lock_time: bitcoin::absolute::LockTime::from_height(i).unwrap(),
+                    input: vec![bitcoin::TxIn::default()],
+                    output: vec![bitcoin::TxOut::minimal_non_dust(ScriptBuf::default())],
+                })
+                .collect();
+            conn.new_txs(&txs);
+            // Two coins on receive index 5, one on receive index 6 and one on change index 5.
+            let coins: Vec<_> = vec![(0, 5, false), (1, 5, false), (2, 6, false), (3, 5, true)]
+                .into_iter()
+                .map(|(i, index, is_change)| Coin {
+                    outpoint: bitcoin::OutPoint::new(txs[i].compute_txid(), 0),
+                    is_immature: false,
+                    block_info: None,
+                    amount: bitcoin::Amount::from_sat(98765),
+                    derivation_index: bip32::ChildNumber::from_normal_idx(index).unwrap(),
+                    is_change,
+                    spend_txid: None,
+                    spend_block: None,
+                    is_from_self: false,
+                })
+                .collect();
+            conn.new_unspent_coins(&coins);
+
+            let receive_counts = conn.address_use_counts(false);
+            assert_eq!(receive_counts.len(), 2);
+            assert_eq!(receive_counts[&bip32::ChildNumber::from(5)], 2);
+            assert_eq!(receive_counts[&bip32::ChildNumber::from(6)], 1);
+            let change_counts = conn.address_use_counts(true);
+            assert_eq!(change_counts.len(), 1);
+            assert_eq!(change_counts[&bip32::ChildNumber::from(5)], 1);
+
+            // Spent coins still count.
+            conn.spend_coins(&[(coins[0].outpoint, txs[3].compute_txid())]);
+            assert_eq!(
+                conn.address_use_counts(false)[&bip32::ChildNumber::from(5)],
+                2
+            );
+        }
+
+        fs::remove_dir_all(tmp_dir).unwrap();
+    }
+
+    #[test]
+    fn sqlite_search() {
+        let (tmp_dir, _, _, db) = dummy_db();
+
+        {
+            let mut conn = db.connection().unwrap();
+
+            let txs: Vec<_> = (0..4)
+                .map(|i| bitcoin::Transaction {
+                    version: bitcoin::transaction::Version::TWO,
+                    lock_time: bitcoin::absolute::LockTime::from_height(i).unwrap(),
+                    input: vec![bitcoin::TxIn::default()],
+                    output: vec![bitcoin::TxOut::minimal_non_dust(ScriptBuf::default())],
+                })
+                .collect();
+            conn.new_txs(&txs);
+            // Three coins of 10k, 20k and 30k sats. The first two are confirmed, the first one
+            // is spent by the fourth transaction.
+            let coins: Vec<_> = (0..3)
+                .map(|i| Coin {
+                    outpoint: bitcoin::OutPoint::new(txs[i].compute_txid(), 0),
+                    is_immature: false,
+                    block_info: None,
+                    amount: bitcoin::Amount::from_sat(10_000 * (i as u64 + 1)),
+                    derivation_index: bip32::ChildNumber::from_normal_idx(i as u32).unwrap(),
+                    is_change: false,
+                    spend_txid: None,
+                    spend_block: None,
+                    is_from_self: false,
+                })
+                .collect();
+            conn.new_unspent_coins(&coins);
+            conn.confirm_coins(&[
+                (coins[0].outpoint, 100, 1_000),
+                (coins[1].outpoint, 200, 2_000),
+            ]);
+            let spend_txid = txs[3].compute_txid();
+            conn.spend_coins(&[(coins[0].outpoint, spend_txid)]);
+            conn.confirm_spend(&[(coins[0].outpoint, spend_txid, 300, 3_000)]);
+            conn.update_labels(&HashMap::from([(
+                LabelItem::Txid(coins[1].outpoint.txid),
+                Some("Rent for March".to_string()),
+            )]));
+
+            let outpoints = |coins: Vec<DbCoin>| {
+                let mut outpoints: Vec<_> = coins.into_iter().map(|c| c.outpoint).collect();
+                outpoints.sort();
+                outpoints
+            };
+            let sorted = |mut outpoints: Vec<bitcoin::OutPoint>| {
+                outpoints.sort();
+                outpoints
+            };
+
+            // An empty filter matches everything.
+            assert_eq!(conn.search_coins(&SearchFilter::default()).len(), 3);
+
+            let filter = SearchFilter {
+                min_amount: Some(bitcoin::Amount::from_sat(15_000)),
+                ..Default::default()
+            };
+            assert_eq!(
+                outpoints(conn.search_coins(&filter)),
+                sorted(vec![coins[1].outpoint, coins[2].outpoint])
+            );
+            let filter = SearchFilter {
+                max_amount: Some(bitcoin::Amount::from_sat(25_000)),
+                statuses: vec![CoinStatus::Confirmed, CoinStatus::Unconfirmed],
+                ..Default::default()
+            };
+            assert_eq!(
+                outpoints(conn.search_coins(&filter)),
+                vec![coins[1].outpoint]
+            );
+
+            // The label search is case-insensitive and matches on the transaction's label.
+            let filter = SearchFilter {
+                label: Some("rent".to_string()),
+                ..Default::default()
+            };
+            assert_eq!(
+                outpoints(conn.search_coins(&filter)),
+                vec![coins[1].outpoint]
+            );
+            assert_eq!(conn.search_txids(&filter, 10), vec![coins[1].outpoint.txid]);
+            let filter = SearchFilter {
+                label: Some("100%".to_string()),
+                ..Default::default()
+            };
+            assert!(conn.search_coins(&filter).is_empty());
+
+            // Unconfirmed coins don't match a date range.
+            let filter = SearchFilter {
+                start: Some(1_500),
+                ..Default::default()
+            };
+            assert_eq!(
+                outpoints(conn.search_coins(&filter)),
+                vec![coins[1].outpoint]
+            );
+            // The date range of transactions applies to the spending transaction too.
+            assert_eq!(
+                conn.search_txids(&filter, 10),
+                vec![spend_txid, coins[1].outpoint.txid]
+            );
+
+            let txid = coins[2].outpoint.txid.to_string();
+            let filter = SearchFilter {
+                txid_prefix: Some(txid[..6].to_uppercase()),
+                ..Default::default()
+            };
+            assert_eq!(
+                outpoints(conn.search_coins(&filter)),
+                vec![coins[2].outpoint]
+            );
+
+            // Unconfirmed transactions come first, then the most recent ones.
+            let all = SearchFilter::default();
+            assert_eq!(
+                conn.search_txids(&all, 10),
+                vec![
+                    coins[2].outpoint.txid,
+                    spend_txid,
+                    coins[1].outpoint.txid,
+                    coins[0].outpoint.txid
+                ]
+            );
+            assert_eq!(conn.search_txids(&all, 2).len(), 2);
+        }
+
+        fs::remove_dir_all(tmp_dir).unwrap();
+    }
+
     #[test]
     fn sqlite_tip_rollback() {
         let (tmp_dir, _, _, db) = dummy_db();
@@ -3079,7 +3597,7 @@ CREATE TABLE labels (
     }
 
     #[test]
-    fn v0_to_v8_migration() {
+    fn v0_to_v11_migration() {
         let secp = secp256k1::Secp256k1::verification_only();
 
         // Create a database with version 0, using the old schema.
@@ -3185,7 +3703,9 @@ CREATE TABLE labels (
         {
             let mut conn = db.connection().unwrap();
             let version = conn.db_version();
-            assert_eq!(version, 8);
+            assert_eq!(version, 11);
+            // Existing wallets keep the previously hardcoded gap limit.
+            assert_eq!(conn.db_wallet().gap_limit, 200);
         }
         // We should now be able to insert another PSBT, to query both, and the first PSBT must
         // have no associated timestamp.
@@ -3203,6 +3723,66 @@ CREATE TABLE labels (
                 .find(|db_spend| db_spend.psbt == second_psbt)
                 .unwrap();
             assert!(second_spend.updated_at.is_some());
+            // Existing Spend transactions are not approved.
+            assert!(db_spends
+                .iter()
+                .all(|db_spend| db_spend.approved_at.is_none()));
+            // They can be approved, once.
+            let second_txid = second_psbt.unsigned_tx.compute_txid();
+            conn.approve_spend(&second_txid);
+            let approved_at = conn.db_spend(&second_txid).unwrap().approved_at;
+            assert!(approved_at.is_some());
+            conn.store_spend(&second_psbt);
+            conn.approve_spend(&second_txid);
+            assert_eq!(
+                conn.db_spend(&second_txid).unwrap().approved_at,
+                approved_at
+            );
+        }
+
+        // In v11, Spend transactions can be scheduled for broadcast.
+        {
+            let mut conn = db.connection().unwrap();
+            let first_txid = first_psbt.unsigned_tx.compute_txid();
+            let second_txid = second_psbt.unsigned_tx.compute_txid();
+            assert!(conn.scheduled_broadcasts().is_empty());
+            conn.schedule_broadcast(&first_txid, Some(800_000), None);
+            conn.schedule_broadcast(&second_txid, Some(800_000), None);
+            // Scheduling again replaces the previous schedule.
+            conn.schedule_broadcast(&second_txid, None, Some(1_700_000_000));
+            let queue = conn.scheduled_broadcasts();
+            assert_eq!(queue.len(), 2);
+            assert_eq!(queue[0].txid, first_txid);
+            assert_eq!(queue[1].txid, second_txid);
+            assert_eq!(
+                (queue[1].block_height, queue[1].timestamp),
+                (None, Some(1_700_000_000))
+            );
+
+            // The outcome of broadcast attempts is recorded.
+            conn.record_broadcast_attempt(&second_txid, Err("non-final"));
+            let entry = conn.scheduled_broadcasts().pop().unwrap();
+            assert_eq!(entry.last_error.as_deref(), Some("non-final"));
+            assert!(entry.broadcast_at.is_none());
+            conn.record_broadcast_attempt(&second_txid, Ok(()));
+            let entry = conn.scheduled_broadcasts().pop().unwrap();
+            assert!(entry.last_error.is_none());
+            assert!(entry.broadcast_at.is_some());
+            conn.fail_broadcast(&first_txid, "conflicting spend");
+            let entry = conn.scheduled_broadcasts().remove(0);
+            assert_eq!(entry.last_error.as_deref(), Some("conflicting spend"));
+            assert!(entry.failed_at.is_some());
+            // Scheduling it again resets it.
+            conn.schedule_broadcast(&first_txid, Some(800_000), None);
+            let entry = conn.scheduled_broadcasts().remove(0);
+            assert!(entry.last_error.is_none() && entry.failed_at.is_none());
+
+            // Entries can be cancelled, and are removed along with their Spend transaction.
+            conn.cancel_broadcast(&second_txid);
+            assert_eq!(conn.scheduled_broadcasts().len(), 1);
+            conn.delete_spend(&first_txid);
+            assert!(conn.scheduled_broadcasts().is_empty());
+            conn.store_spend(&first_psbt);
         }
 
         // We should now be able to store an immature coin, query all of them, and the first two
@@ -3260,7 +3840,7 @@ CREATE TABLE labels (
     }
 
     #[test]
-    fn v3_to_v8_migration() {
+    fn v3_to_v11_migration() {
         let secp = secp256k1::Secp256k1::verification_only();
 
         // Create a database with version 3, using the old schema.
@@ -3412,10 +3992,10 @@ CREATE TABLE labels (
 
             // Migrate the DB.
             maybe_apply_migration(&db_path, &bitcoin_txs).unwrap();
-            assert_eq!(conn.db_version(), 8);
+            assert_eq!(conn.db_version(), 11);
             // Migrating twice will be a no-op. No need to pass `bitcoin_txs` second time.
             maybe_apply_migration(&db_path, &[]).unwrap();
-            assert!(conn.db_version() == 8);
+            assert!(conn.db_version() == 11);
 
             // Compare the `DbCoin`s with the expected values.
             let coins_post = conn.coins(&[], &[]);
@@ -36,6 +36,8 @@ CREATE TABLE tip (
  * information related to our descriptor(s) that occurred after this date.
  * The optional 'rescan_timestamp' field is a the timestamp we need to rescan the chain
  * for events related to our descriptor(s) from.
+ * The 'gap_limit' field is the number of addresses we watch past the highest of our
+ * derivation indexes.
  */
 CREATE TABLE wallets (
     id INTEGER PRIMARY KEY NOT NULL,
@@ -44,7 +46,8 @@ CREATE TABLE wallets (
     deposit_derivation_index INTEGER NOT NULL,
     change_derivation_index INTEGER NOT NULL,
     rescan_timestamp INTEGER,
-    last_poll_timestamp INTEGER
+    last_poll_timestamp INTEGER,
+    gap_limit INTEGER NOT NULL DEFAULT 200 CHECK (gap_limit > 0)
 );
 
 /* Our (U)TxOs.
@@ -111,7 +114,27 @@ CREATE TABLE spend_transactions (
     id INTEGER PRIMARY KEY NOT NULL,
     psbt BLOB UNIQUE NOT NULL,
     txid BLOB UNIQUE NOT NULL,
-    updated_at INTEGER
+    updated_at INTEGER,
+    approved_at INTEGER
+);
+
+/* Spend transactions to broadcast once the chain reaches 'block_height' and the clock
+ * 'timestamp', whichever of the two are set.
+ *
+ * The 'broadcast_at' field is set once the transaction was broadcast. The 'last_error' field
+ * is the error of the last failed attempt to broadcast it, if any. The 'failed_at' field is set
+ * if it can't ever be broadcast, for instance if its coins were spent by another transaction.
+ */
+CREATE TABLE broadcast_queue (
+    id INTEGER PRIMARY KEY NOT NULL,
+    txid BLOB UNIQUE NOT NULL,
+    block_height INTEGER,
+    timestamp INTEGER,
+    created_at INTEGER NOT NULL,
+    broadcast_at INTEGER,
+    last_error TEXT,
+    failed_at INTEGER,
+    CHECK (block_height IS NOT NULL OR timestamp IS NOT NULL)
 );
 
 /* Labels applied on addresses (0), outpoints (1), txids (2) */
@@ -164,6 +187,7 @@ pub struct DbWallet {
     pub change_derivation_index: bip32::ChildNumber,
     pub rescan_timestamp: Option<u32>,
     pub last_poll_timestamp: Option<u32>,
+    pub gap_limit: u32,
 }
 
 impl TryFrom<&rusqlite::Row<'_>> for DbWallet {
@@ -184,6 +208,7 @@ impl TryFrom<&rusqlite::Row<'_>> for DbWallet {
 
         let rescan_timestamp = row.get(5)?;
         let last_poll_timestamp = row.get(6)?;
+        let gap_limit = row.get(7)?;
 
         Ok(DbWallet {
             id,
@@ -193,6 +218,7 @@ impl TryFrom<&rusqlite::Row<'_>> for DbWallet {
             change_derivation_index,
             rescan_timestamp,
             last_poll_timestamp,
+            gap_limit,
         })
     }
 }
@@ -317,6 +343,7 @@ pub struct DbSpendTransaction {
     pub psbt: Psbt,
     pub txid: bitcoin::Txid,
     pub updated_at: Option<u32>,
+    pub approved_at: Option<u32>,
 }
 
 impl TryFrom<&rusqlite::Row<'_>> for DbSpendTransaction {
@@ -333,12 +360,57 @@ impl TryFrom<&rusqlite::Row<'_>> for DbSpendTransaction {
         assert_eq!(txid, psbt.unsigned_tx.compute_txid());
 
         let updated_at = row.get(3)?;
+        let approved_at = row.get(4)?;
 
         Ok(DbSpendTransaction {
             id,
             psbt,
             txid,
             updated_at,
+            approved_at,
+        })
+    }
+}
+
+/// A row in the "broadcast_queue" table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DbScheduledBroadcast {
+    pub id: i64,
+    pub txid: bitcoin::Txid,
+    pub block_height: Option<i32>,
+    pub timestamp: Option<u32>,
+    pub created_at: u32,
+    pub broadcast_at: Option<u32>,
+    pub last_error: Option<String>,
+    pub failed_at: Option<u32>,
+}
+
+impl TryFrom<&rusqlite::Row<'_>> for DbScheduledBroadcast {
+    type Error = rusqlite::Error;
+
+    fn try_from(row: &rusqlite::Row) -> Result<Self, Self::Error> {
+        let id: i64 = row.get(0)?;
+
+        let txid: Vec<u8> = row.get(1)?;
+        let txid: bitcoin::Txid = encode::deserialize(&txid).expect("We only store valid txids");
+
+        let block_height: Option<i32> = row.get(2)?;
+        let timestamp: Option<u32> = row.get(3)?;
+        assert!(block_height.is_some() || timestamp.is_some());
+        let created_at = row.get(4)?;
+        let broadcast_at = row.get(5)?;
+        let last_error = row.get(6)?;
+        let failed_at = row.get(7)?;
+
+        Ok(DbScheduledBroadcast {
+            id,
+            txid,
+            block_height,
+            timestamp,
+            created_at,
+            broadcast_at,
+            last_error,
+            failed_at,
         })
     }
 }
@@ -1,10 +1,13 @@
 use crate::database::sqlite::{FreshDbOptions, SqliteDbError, DB_VERSION};
+use coincube_core::descriptors::CoincubeDescriptor;
 
-use std::{convert::TryInto, fs, path, time};
+use std::{cmp, convert::TryInto, fs, path, time};
 
 use miniscript::bitcoin::{self, secp256k1};
 
-pub const LOOK_AHEAD_LIMIT: u32 = 200;
+/// The number of addresses we watch past the highest derivation index, unless configured
+/// otherwise.
+pub const DEFAULT_GAP_LIMIT: u32 = 200;
 
 /// Perform a set of modifications to the database inside a single transaction
 pub fn db_exec<F>(conn: &mut rusqlite::Connection, modifications: F) -> Result<(), rusqlite::Error>
@@ -109,8 +112,8 @@ pub fn create_fresh_db(
 
     // Fill the initial addresses. On a fresh database, the deposit_derivation_index is
     // necessarily 0.
-    let mut query = String::with_capacity(100 * LOOK_AHEAD_LIMIT as usize);
-    for index in 0..LOOK_AHEAD_LIMIT {
+    let mut query = String::with_capacity(100 * DEFAULT_GAP_LIMIT as usize);
+    for index in 0..DEFAULT_GAP_LIMIT {
         let receive_address = options
             .main_descriptor
             .receive_descriptor()
@@ -151,6 +154,36 @@ pub fn create_fresh_db(
     Ok(())
 }
 
+/// Populate the address->deriv_index mapping for all the derivation indexes up to `gap_limit`
+/// past `highest_index`. Only the entries which are missing are derived.
+pub fn extend_addresses(
+    db_tx: &rusqlite::Transaction,
+    main_descriptor: &CoincubeDescriptor,
+    network: bitcoin::Network,
+    highest_index: u32,
+    gap_limit: u32,
+    secp: &secp256k1::Secp256k1<secp256k1::VerifyOnly>,
+) -> Result<(), rusqlite::Error> {
+    let next_index: u32 = db_tx.query_row(
+        "SELECT COALESCE(MAX(derivation_index) + 1, 0) FROM addresses",
+        rusqlite::params![],
+        |row| row.get(0),
+    )?;
+    // Never populate hardened derivation indexes.
+    let end_index = cmp::min(highest_index.saturating_add(gap_limit), 1 << 31);
+    let receive_desc = main_descriptor.receive_descriptor();
+    let change_desc = main_descriptor.change_descriptor();
+    for index in next_index..end_index {
+        let receive_addr = receive_desc.derive(index.into(), secp).address(network);
+        let change_addr = change_desc.derive(index.into(), secp).address(network);
+        db_tx.execute(
+            "INSERT INTO addresses (receive_address, change_address, derivation_index) VALUES (?1, ?2, ?3)",
+            rusqlite::params![receive_addr.to_string(), change_addr.to_string(), index],
+        )?;
+    }
+    Ok(())
+}
+
 pub fn db_version(conn: &mut rusqlite::Connection) -> Result<i64, SqliteDbError> {
     Ok(db_query(
         conn,
@@ -482,6 +515,59 @@ fn migrate_v7_to_v8(conn: &mut rusqlite::Connection) -> Result<(), SqliteDbError
     Ok(())
 }
 
+// Make the number of addresses we watch past the highest derivation index configurable. Existing
+// wallets keep the limit which was previously hardcoded.
+fn migrate_v8_to_v9(conn: &mut rusqlite::Connection) -> Result<(), SqliteDbError> {
+    db_exec(conn, |tx| {
+        tx.execute(
+            "ALTER TABLE wallets ADD COLUMN gap_limit INTEGER NOT NULL DEFAULT 200 CHECK (gap_limit > 0)",
+            rusqlite::params![],
+        )?;
+        tx.execute("UPDATE version SET version = 9", rusqlite::params![])?;
+        Ok(())
+    })?;
+
+    Ok(())
+}
+
+// Record the approval of Spend transactions, for the spending policy.
+fn migrate_v9_to_v10(conn: &mut rusqlite::Connection) -> Result<(), SqliteDbError> {
+    db_exec(conn, |tx| {
+        tx.execute(
+            "ALTER TABLE spend_transactions ADD COLUMN approved_at INTEGER",
+            rusqlite::params![],
+        )?;
+        tx.execute("UPDATE version SET version = 10", rusqlite::params![])?;
+        Ok(())
+    })?;
+
+    Ok(())
+}
+
+// Add the queue of Spend transactions scheduled for broadcast.
+fn migrate_v10_to_v11(conn: &mut rusqlite::Connection) -> Result<(), SqliteDbError> {
+    db_exec(conn, |tx| {
+        tx.execute(
+            "CREATE TABLE broadcast_queue (
+                id INTEGER PRIMARY KEY NOT NULL,
+                txid BLOB UNIQUE NOT NULL,
+                block_height INTEGER,
+                timestamp INTEGER,
+                created_at INTEGER NOT NULL,
+                broadcast_at INTEGER,
+                last_error TEXT,
+                failed_at INTEGER,
+                CHECK (block_height IS NOT NULL OR timestamp IS NOT NULL)
+            )",
+            rusqlite::params![],
+        )?;
+        tx.execute("UPDATE version SET version = 11", rusqlite::params![])?;
+        Ok(())
+    })?;
+
+    Ok(())
+}
+
 /// Check the database version and if necessary apply the migrations to upgrade it to the current
 /// one. The `bitcoin_txs` parameter is here for the migration from versions 4 and earlier, which
 /// did not store the Bitcoin transactions in database, to versions 5 and later, which do. For a
@@ -545,6 +631,21 @@ pub fn maybe_apply_migration(
                 migrate_v7_to_v8(&mut conn)?;
                 log::warn!("Migration from database version 7 to version 8 successful.");
             }
+            8 => {
+                log::warn!("Upgrading database from version 8 to version 9.");
+                migrate_v8_to_v9(&mut conn)?;
+                log::warn!("Migration from database version 8 to version 9 successful.");
+            }
+            9 => {
+                log::warn!("Upgrading database from version 9 to version 10.");
+                migrate_v9_to_v10(&mut conn)?;
+                log::warn!("Migration from database version 9 to version 10 successful.");
+            }
+            10 => {
+                log::warn!("Upgrading database from version 10 to version 11.");
+                migrate_v10_to_v11(&mut conn)?;
+                log::warn!("Migration from database version 10 to version 11 successful.");
+            }
             _ => return Err(SqliteDbError::UnsupportedVersion(version)),
         }
     }
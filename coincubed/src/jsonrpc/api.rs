@@ -1,5 +1,5 @@
 use crate::{
-    commands::{CoinStatus, LabelItem},
+    commands::{CoinStatus, LabelConflict, LabelItem, SearchFilter},
     jsonrpc::rpc::{Error, Params, Request, Response},
     DaemonControl,
 };
@@ -10,7 +10,27 @@ use std::{
     str::FromStr,
 };
 
-use miniscript::bitcoin::{self, psbt::Psbt, Txid};
+use coincube_core::{psbtv2, spend};
+use miniscript::bitcoin::{self, hashes::hex::FromHex, psbt::Psbt, Txid};
+
+// Parse a feerate parameter, in sats/vb, to sats/kvb. Integers are still accepted as they were
+// before fractional feerates were supported.
+fn feerate_kvb(feerate: &serde_json::Value) -> Option<u64> {
+    feerate
+        .as_u64()
+        .and_then(|feerate_vb| feerate_vb.checked_mul(1_000))
+        .or_else(|| feerate.as_f64().and_then(spend::feerate_kvb_from_vb))
+}
+
+// Parse the data of an OP_RETURN output, given either hex encoded or as UTF-8 text.
+fn op_return_data(data: &serde_json::Value) -> Option<Vec<u8>> {
+    let data = data.as_object()?;
+    match (data.get("hex"), data.get("text")) {
+        (Some(hex), None) => Vec::from_hex(hex.as_str()?).ok(),
+        (None, Some(text)) => Some(text.as_str()?.as_bytes().to_vec()),
+        _ => None,
+    }
+}
 
 fn create_spend(control: &DaemonControl, params: Params) -> Result<serde_json::Value, Error> {
     let destinations = params
@@ -41,13 +61,15 @@ fn create_spend(control: &DaemonControl, params: Params) -> Result<serde_json::V
                 .collect::<Option<Vec<bitcoin::OutPoint>>>()
         })
         .ok_or_else(|| Error::invalid_params("Invalid 'outpoints' parameter."))?;
-    let feerate: u64 = params
+    let feerate = params
         .get(2, "feerate")
-        .ok_or_else(|| Error::invalid_params("Missing 'feerate' parameter."))?
-        .as_u64()
-        .ok_or_else(|| Error::invalid_params("Invalid 'feerate' parameter."))?;
+        .ok_or_else(|| Error::invalid_params("Missing 'feerate' parameter."))
+        .and_then(|f| {
+            feerate_kvb(f).ok_or_else(|| Error::invalid_params("Invalid 'feerate' parameter."))
+        })?;
     let change_address: Option<bitcoin::Address<bitcoin::address::NetworkUnchecked>> = params
         .get(3, "change_address")
+        .filter(|addr| !addr.is_null())
         .map(|addr| {
             let addr_str = addr.as_str().ok_or_else(|| {
                 Error::invalid_params("Invalid 'change_address' parameter: must be a string.")
@@ -57,8 +79,41 @@ fn create_spend(control: &DaemonControl, params: Params) -> Result<serde_json::V
             })
         })
         .transpose()?;
+    let subtract_fee_from = params
+        .get(4, "subtract_fee_from")
+        .filter(|addrs| !addrs.is_null())
+        .map(|addrs| {
+            addrs
+                .as_array()
+                .and_then(|arr| {
+                    arr.iter()
+                        .map(|addr| {
+                            addr.as_str()
+                                .and_then(|a| bitcoin::Address::from_str(a).ok())
+                        })
+                        .collect::<Option<Vec<bitcoin::Address<bitcoin::address::NetworkUnchecked>>>>()
+                })
+                .ok_or_else(|| Error::invalid_params("Invalid 'subtract_fee_from' parameter."))
+        })
+        .transpose()?
+        .unwrap_or_default();
+    let op_return = params
+        .get(5, "op_return")
+        .filter(|data| !data.is_null())
+        .map(|data| {
+            op_return_data(data)
+                .ok_or_else(|| Error::invalid_params("Invalid 'op_return' parameter."))
+        })
+        .transpose()?;
 
-    let res = control.create_spend(&destinations, &outpoints, feerate, change_address)?;
+    let res = control.create_spend(
+        &destinations,
+        &subtract_fee_from,
+        op_return.as_deref(),
+        &outpoints,
+        feerate,
+        change_address,
+    )?;
     Ok(serde_json::json!(&res))
 }
 
@@ -67,13 +122,23 @@ fn update_spend(control: &DaemonControl, params: Params) -> Result<serde_json::V
         .get(0, "psbt")
         .ok_or_else(|| Error::invalid_params("Missing 'psbt' parameter."))?
         .as_str()
-        .and_then(|s| Psbt::from_str(s).ok())
+        .and_then(|s| psbtv2::from_base64(s).ok())
         .ok_or_else(|| Error::invalid_params("Invalid 'psbt' parameter."))?;
     control.update_spend(psbt)?;
 
     Ok(serde_json::json!({}))
 }
 
+fn analyze_psbt(control: &DaemonControl, params: Params) -> Result<serde_json::Value, Error> {
+    let psbt = params
+        .get(0, "psbt")
+        .ok_or_else(|| Error::invalid_params("Missing 'psbt' parameter."))?
+        .as_str()
+        .and_then(|s| psbtv2::from_base64(s).ok())
+        .ok_or_else(|| Error::invalid_params("Invalid 'psbt' parameter."))?;
+    Ok(serde_json::json!(control.analyze_psbt(&psbt)))
+}
+
 fn delete_spend(control: &DaemonControl, params: Params) -> Result<serde_json::Value, Error> {
     let txid = params
         .get(0, "txid")
@@ -98,6 +163,66 @@ fn broadcast_spend(control: &DaemonControl, params: Params) -> Result<serde_json
     Ok(serde_json::json!({}))
 }
 
+// The block height and the timestamp are both optional, `null` can be used to skip the former
+// when passing parameters by position.
+fn schedule_broadcast(control: &DaemonControl, params: Params) -> Result<serde_json::Value, Error> {
+    let txid = params
+        .get(0, "txid")
+        .ok_or_else(|| Error::invalid_params("Missing 'txid' parameter."))?
+        .as_str()
+        .and_then(|s| bitcoin::Txid::from_str(s).ok())
+        .ok_or_else(|| Error::invalid_params("Invalid 'txid' parameter."))?;
+    let block_height: Option<i32> = params
+        .get(1, "block_height")
+        .filter(|v| !v.is_null())
+        .map(|v| {
+            v.as_i64()
+                .and_then(|h| h.try_into().ok())
+                .filter(|h: &i32| *h > 0)
+                .ok_or_else(|| Error::invalid_params("Invalid 'block_height' parameter."))
+        })
+        .transpose()?;
+    let timestamp: Option<u32> = params
+        .get(2, "timestamp")
+        .filter(|v| !v.is_null())
+        .map(|v| {
+            v.as_u64()
+                .and_then(|t| t.try_into().ok())
+                .ok_or_else(|| Error::invalid_params("Invalid 'timestamp' parameter."))
+        })
+        .transpose()?;
+    control.schedule_broadcast(&txid, block_height, timestamp)?;
+
+    Ok(serde_json::json!({}))
+}
+
+fn cancel_scheduled_broadcast(
+    control: &DaemonControl,
+    params: Params,
+) -> Result<serde_json::Value, Error> {
+    let txid = params
+        .get(0, "txid")
+        .ok_or_else(|| Error::invalid_params("Missing 'txid' parameter."))?
+        .as_str()
+        .and_then(|s| bitcoin::Txid::from_str(s).ok())
+        .ok_or_else(|| Error::invalid_params("Invalid 'txid' parameter."))?;
+    control.cancel_scheduled_broadcast(&txid)?;
+
+    Ok(serde_json::json!({}))
+}
+
+fn approve_spend(control: &DaemonControl, params: Params) -> Result<serde_json::Value, Error> {
+    let txid = params
+        .get(0, "txid")
+        .ok_or_else(|| Error::invalid_params("Missing 'txid' parameter."))?
+        .as_str()
+        .and_then(|s| bitcoin::Txid::from_str(s).ok())
+        .ok_or_else(|| Error::invalid_params("Invalid 'txid' parameter."))?;
+    control.approve_spend(&txid)?;
+
+    Ok(serde_json::json!({}))
+}
+
 fn rbf_psbt(control: &DaemonControl, params: Params) -> Result<serde_json::Value, Error> {
     let txid = params
         .get(0, "txid")
@@ -110,42 +235,45 @@ fn rbf_psbt(control: &DaemonControl, params: Params) -> Result<serde_json::Value
         .ok_or_else(|| Error::invalid_params("Missing 'is_cancel' parameter."))?
         .as_bool()
         .ok_or_else(|| Error::invalid_params("Invalid 'is_cancel' parameter."))?;
-    let feerate_vb: Option<u64> = if let Some(feerate) = params.get(2, "feerate") {
+    let feerate_kvb: Option<u64> = if let Some(feerate) = params.get(2, "feerate") {
         Some(
-            feerate
-                .as_u64()
+            feerate_kvb(feerate)
                 .ok_or_else(|| Error::invalid_params("Invalid 'feerate' parameter."))?,
         )
     } else {
         None
     };
-    let res = control.rbf_psbt(&txid, is_cancel, feerate_vb)?;
+    let res = control.rbf_psbt(&txid, is_cancel, feerate_kvb)?;
+    Ok(serde_json::json!(&res))
+}
+
+fn cpfp(control: &DaemonControl, params: Params) -> Result<serde_json::Value, Error> {
+    let outpoints = params
+        .get(0, "outpoints")
+        .ok_or_else(|| Error::invalid_params("Missing 'outpoints' parameter."))?
+        .as_array()
+        .and_then(|arr| {
+            arr.iter()
+                .map(|entry| {
+                    entry
+                        .as_str()
+                        .and_then(|e| bitcoin::OutPoint::from_str(e).ok())
+                })
+                .collect::<Option<Vec<bitcoin::OutPoint>>>()
+        })
+        .ok_or_else(|| Error::invalid_params("Invalid 'outpoints' parameter."))?;
+    let feerate = params
+        .get(1, "feerate")
+        .ok_or_else(|| Error::invalid_params("Missing 'feerate' parameter."))
+        .and_then(|f| {
+            feerate_kvb(f).ok_or_else(|| Error::invalid_params("Invalid 'feerate' parameter."))
+        })?;
+    let res = control.cpfp(&outpoints, feerate)?;
     Ok(serde_json::json!(&res))
 }
 
 fn list_coins(control: &DaemonControl, params: Option<Params>) -> Result<serde_json::Value, Error> {
-    let statuses_arg = params
-        .as_ref()
-        .and_then(|p| p.get(0, "statuses"))
-        .and_then(|statuses| statuses.as_array());
-    let statuses: Vec<CoinStatus> = if let Some(statuses_arg) = statuses_arg {
-        statuses_arg
-            .iter()
-            .map(|status_arg| {
-                status_arg
-                    .as_str()
-                    .and_then(CoinStatus::from_arg)
-                    .ok_or_else(|| {
-                        Error::invalid_params(format!(
-                            "Invalid value {} in 'statuses' parameter.",
-                            status_arg
-                        ))
-                    })
-            })
-            .collect::<Result<Vec<CoinStatus>, Error>>()?
-    } else {
-        Vec::new()
-    };
+    let statuses = coin_statuses(params.as_ref().and_then(|p| p.get(0, "statuses")))?;
     let outpoints_arg = params
         .as_ref()
         .and_then(|p| p.get(1, "outpoints"))
@@ -172,6 +300,114 @@ fn list_coins(control: &DaemonControl, params: Option<Params>) -> Result<serde_j
     Ok(serde_json::json!(&res))
 }
 
+fn coin_statuses(statuses: Option<&serde_json::Value>) -> Result<Vec<CoinStatus>, Error> {
+    if let Some(statuses_arg) = statuses.and_then(|statuses| statuses.as_array()) {
+        statuses_arg
+            .iter()
+            .map(|status_arg| {
+                status_arg
+                    .as_str()
+                    .and_then(CoinStatus::from_arg)
+                    .ok_or_else(|| {
+                        Error::invalid_params(format!(
+                            "Invalid value {} in 'statuses' parameter.",
+                            status_arg
+                        ))
+                    })
+            })
+            .collect()
+    } else {
+        Ok(Vec::new())
+    }
+}
+
+// Parse the search criteria, which are all optional. They may be passed by position starting
+// at `offset`, in which case `null` can be used to skip one.
+fn search_filter(params: &Option<Params>, offset: usize) -> Result<SearchFilter, Error> {
+    let get = |index: usize, name: &str| {
+        params
+            .as_ref()
+            .and_then(|p| p.get(offset + index, name))
+            .filter(|v| !v.is_null())
+    };
+    let get_str = |index: usize, name: &str| {
+        get(index, name)
+            .map(|v| {
+                v.as_str()
+                    .map(|s| s.to_string())
+                    .ok_or_else(|| Error::invalid_params(format!("Invalid '{}' parameter.", name)))
+            })
+            .transpose()
+    };
+    let get_u64 = |index: usize, name: &str| {
+        get(index, name)
+            .map(|v| {
+                v.as_u64()
+                    .ok_or_else(|| Error::invalid_params(format!("Invalid '{}' parameter.", name)))
+            })
+            .transpose()
+    };
+    let get_u32 = |index: usize, name: &str| {
+        get_u64(index, name)?
+            .map(|v| {
+                v.try_into()
+                    .map_err(|_| Error::invalid_params(format!("Invalid '{}' parameter.", name)))
+            })
+            .transpose()
+    };
+
+    let address = get_str(6, "address")?
+        .map(|addr| {
+            bitcoin::Address::from_str(&addr)
+                // An address for another network will simply not match any coin.
+                .map(|addr| addr.assume_checked())
+                .map_err(|_| Error::invalid_params("Invalid 'address' parameter."))
+        })
+        .transpose()?;
+    let txid_prefix = get_str(7, "txid_prefix")?;
+    if txid_prefix
+        .as_ref()
+        .is_some_and(|p| !p.chars().all(|c| c.is_ascii_hexdigit()))
+    {
+        return Err(Error::invalid_params("Invalid 'txid_prefix' parameter."));
+    }
+
+    Ok(SearchFilter {
+        label: get_str(0, "label")?,
+        min_amount: get_u64(1, "min_amount")?.map(bitcoin::Amount::from_sat),
+        max_amount: get_u64(2, "max_amount")?.map(bitcoin::Amount::from_sat),
+        start: get_u32(3, "start")?,
+        end: get_u32(4, "end")?,
+        statuses: coin_statuses(get(5, "statuses"))?,
+        address,
+        txid_prefix,
+    })
+}
+
+fn search_coins(
+    control: &DaemonControl,
+    params: Option<Params>,
+) -> Result<serde_json::Value, Error> {
+    let filter = search_filter(&params, 0)?;
+    Ok(serde_json::json!(&control.search_coins(&filter)))
+}
+
+fn search_transactions(
+    control: &DaemonControl,
+    params: Option<Params>,
+) -> Result<serde_json::Value, Error> {
+    let limit: u64 = params
+        .as_ref()
+        .and_then(|p| p.get(0, "limit"))
+        .ok_or_else(|| Error::invalid_params("Missing 'limit' parameter."))?
+        .as_u64()
+        .ok_or_else(|| Error::invalid_params("Invalid 'limit' parameter."))?;
+    let filter = search_filter(&params, 1)?;
+    Ok(serde_json::json!(
+        &control.search_transactions(&filter, limit)
+    ))
+}
+
 fn get_opt_u32<Q>(params: &Option<Params>, index: usize, name: &Q) -> Result<Option<u32>, Error>
 where
     String: std::borrow::Borrow<Q>,
@@ -287,6 +523,18 @@ fn update_deriv_indexes(
     ))
 }
 
+fn set_gap_limit(control: &DaemonControl, params: Params) -> Result<serde_json::Value, Error> {
+    let gap_limit = params
+        .get(0, "gap_limit")
+        .ok_or_else(|| Error::invalid_params("Missing 'gap_limit' parameter."))?
+        .as_u64()
+        .and_then(|i| i.try_into().ok())
+        .ok_or_else(|| Error::invalid_params("Invalid 'gap_limit' parameter."))?;
+    control.set_gap_limit(gap_limit)?;
+
+    Ok(serde_json::json!({}))
+}
+
 fn list_confirmed(control: &DaemonControl, params: Params) -> Result<serde_json::Value, Error> {
     let start: u32 = params
         .get(0, "start")
@@ -366,6 +614,28 @@ fn start_rescan(control: &mut DaemonControl, params: Params) -> Result<serde_jso
     Ok(serde_json::json!({}))
 }
 
+fn get_consolidation_advice(
+    control: &DaemonControl,
+    params: Params,
+) -> Result<serde_json::Value, Error> {
+    let feerate = params
+        .get(0, "feerate")
+        .ok_or_else(|| Error::invalid_params("Missing 'feerate' parameter."))
+        .and_then(|f| {
+            feerate_kvb(f).ok_or_else(|| Error::invalid_params("Invalid 'feerate' parameter."))
+        })?;
+    let future_feerate = params
+        .get(1, "future_feerate")
+        .ok_or_else(|| Error::invalid_params("Missing 'future_feerate' parameter."))
+        .and_then(|f| {
+            feerate_kvb(f)
+                .ok_or_else(|| Error::invalid_params("Invalid 'future_feerate' parameter."))
+        })?;
+
+    let res = control.get_consolidation_advice(feerate, future_feerate)?;
+    Ok(serde_json::json!(&res))
+}
+
 fn create_recovery(control: &DaemonControl, params: Params) -> Result<serde_json::Value, Error> {
     let address = params
         .get(0, "address")
@@ -373,11 +643,12 @@ fn create_recovery(control: &DaemonControl, params: Params) -> Result<serde_json
         .as_str()
         .and_then(|s| bitcoin::Address::from_str(s).ok())
         .ok_or_else(|| Error::invalid_params("Invalid 'address' parameter."))?;
-    let feerate: u64 = params
+    let feerate = params
         .get(1, "feerate")
-        .ok_or_else(|| Error::invalid_params("Missing 'feerate' parameter."))?
-        .as_u64()
-        .ok_or_else(|| Error::invalid_params("Invalid 'feerate' parameter."))?;
+        .ok_or_else(|| Error::invalid_params("Missing 'feerate' parameter."))
+        .and_then(|f| {
+            feerate_kvb(f).ok_or_else(|| Error::invalid_params("Invalid 'feerate' parameter."))
+        })?;
     let timelock: Option<u16> = params
         .get(2, "timelock")
         .map(|tl| {
@@ -409,6 +680,71 @@ fn create_recovery(control: &DaemonControl, params: Params) -> Result<serde_json
     Ok(serde_json::json!(&res))
 }
 
+fn create_heir_recovery(
+    control: &DaemonControl,
+    params: Params,
+) -> Result<serde_json::Value, Error> {
+    let address = params
+        .get(0, "address")
+        .ok_or_else(|| Error::invalid_params("Missing 'address' parameter."))?
+        .as_str()
+        .and_then(|s| bitcoin::Address::from_str(s).ok())
+        .ok_or_else(|| Error::invalid_params("Invalid 'address' parameter."))?;
+    let feerate = params
+        .get(1, "feerate")
+        .ok_or_else(|| Error::invalid_params("Missing 'feerate' parameter."))
+        .and_then(|f| {
+            feerate_kvb(f).ok_or_else(|| Error::invalid_params("Invalid 'feerate' parameter."))
+        })?;
+    let timelock: u16 = params
+        .get(2, "timelock")
+        .ok_or_else(|| Error::invalid_params("Missing 'timelock' parameter."))?
+        .as_u64()
+        .and_then(|tl| tl.try_into().ok())
+        .ok_or_else(|| Error::invalid_params("Invalid 'timelock' parameter."))?;
+
+    let res = control.create_heir_recovery(address, feerate, timelock)?;
+    Ok(serde_json::json!(&res))
+}
+
+fn create_message_psbt(
+    control: &DaemonControl,
+    params: Params,
+) -> Result<serde_json::Value, Error> {
+    let address = params
+        .get(0, "address")
+        .ok_or_else(|| Error::invalid_params("Missing 'address' parameter."))?
+        .as_str()
+        .and_then(|s| bitcoin::Address::from_str(s).ok())
+        .ok_or_else(|| Error::invalid_params("Invalid 'address' parameter."))?;
+    let message = params
+        .get(1, "message")
+        .ok_or_else(|| Error::invalid_params("Missing 'message' parameter."))?
+        .as_str()
+        .ok_or_else(|| Error::invalid_params("Invalid 'message' parameter."))?;
+    let outpoints = params
+        .get(2, "outpoints")
+        .map(|param| {
+            param
+                .as_array()
+                .and_then(|arr| {
+                    arr.iter()
+                        .map(|entry| {
+                            entry
+                                .as_str()
+                                .and_then(|e| bitcoin::OutPoint::from_str(e).ok())
+                        })
+                        .collect::<Option<Vec<_>>>()
+                })
+                .ok_or_else(|| Error::invalid_params("Invalid 'outpoints' parameter."))
+        })
+        .transpose()?
+        .unwrap_or_default(); // missing is same as empty array
+
+    let res = control.create_message_psbt(address, message, &outpoints)?;
+    Ok(serde_json::json!(&res))
+}
+
 fn update_labels(control: &DaemonControl, params: Params) -> Result<serde_json::Value, Error> {
     let mut items = HashMap::new();
     for (item, value) in params
@@ -486,21 +822,79 @@ fn get_labels_bip329(control: &DaemonControl, params: Params) -> Result<serde_js
     Ok(serde_json::json!(control.get_labels_bip329(offset, limit)))
 }
 
+fn import_labels(control: &DaemonControl, params: Params) -> Result<serde_json::Value, Error> {
+    let labels = params
+        .get(0, "labels")
+        .ok_or_else(|| Error::invalid_params("Missing 'labels' parameter."))?
+        .as_array()
+        .ok_or_else(|| Error::invalid_params("Invalid 'labels' parameter."))?
+        .iter()
+        .enumerate()
+        .map(|(i, label)| {
+            serde_json::from_value::<crate::bip329::Label>(label.clone()).map_err(|e| {
+                Error::invalid_params(format!("Invalid 'labels.{}' parameter: {}", i, e))
+            })
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    let conflict = match params.get(1, "conflict") {
+        Some(conflict) => conflict
+            .as_str()
+            .and_then(LabelConflict::from_arg)
+            .ok_or_else(|| {
+                Error::invalid_params(
+                    "Invalid 'conflict' parameter: must be 'merge', 'overwrite' or 'skip'.",
+                )
+            })?,
+        None => LabelConflict::Skip,
+    };
+    Ok(serde_json::json!(control.import_labels(&labels, conflict)))
+}
+
 /// Handle an incoming JSONRPC2 request.
 pub fn handle_request(control: &mut DaemonControl, req: Request) -> Result<Response, Error> {
     let result = match req.method.as_str() {
+        "analyzepsbt" => {
+            let params = req
+                .params
+                .ok_or_else(|| Error::invalid_params("Missing 'psbt' parameter."))?;
+            analyze_psbt(control, params)?
+        }
+        "approvespend" => {
+            let params = req
+                .params
+                .ok_or_else(|| Error::invalid_params("Missing 'txid' parameter."))?;
+            approve_spend(control, params)?
+        }
         "broadcastspend" => {
             let params = req
                 .params
                 .ok_or_else(|| Error::invalid_params("Missing 'txid' parameter."))?;
             broadcast_spend(control, params)?
         }
+        "cancelscheduledbroadcast" => {
+            let params = req
+                .params
+                .ok_or_else(|| Error::invalid_params("Missing 'txid' parameter."))?;
+            cancel_scheduled_broadcast(control, params)?
+        }
+        "createheirrecovery" => {
+            let params = req.params.ok_or_else(|| {
+                Error::invalid_params("Missing 'address', 'feerate' and 'timelock' parameters.")
+            })?;
+            create_heir_recovery(control, params)?
+        }
         "createrecovery" => {
             let params = req.params.ok_or_else(|| {
                 Error::invalid_params("Missing 'address' and 'feerate' parameters.")
             })?;
             create_recovery(control, params)?
         }
+        "createmessagepsbt" => {
+            let params = req.params.ok_or_else(|| {
+                Error::invalid_params("Missing 'address' and 'message' parameters.")
+            })?;
+            create_message_psbt(control, params)?
+        }
         "createspend" => {
             let params = req.params.ok_or_else(|| {
                 Error::invalid_params(
@@ -521,6 +915,19 @@ pub fn handle_request(control: &mut DaemonControl, req: Request) -> Result<Respo
             })?;
             rbf_psbt(control, params)?
         }
+        "cpfp" => {
+            let params = req.params.ok_or_else(|| {
+                Error::invalid_params("Missing 'outpoints' and 'feerate' parameters.")
+            })?;
+            cpfp(control, params)?
+        }
+        "getbalancehistory" => serde_json::json!(&control.get_balance_history()),
+        "getconsolidationadvice" => {
+            let params = req.params.ok_or_else(|| {
+                Error::invalid_params("Missing 'feerate' and 'future_feerate' parameters.")
+            })?;
+            get_consolidation_advice(control, params)?
+        }
         "getinfo" => serde_json::json!(&control.get_info()),
         "getnewaddress" => serde_json::json!(&control.get_new_address()),
         "updatederivationindexes" => {
@@ -545,6 +952,8 @@ pub fn handle_request(control: &mut DaemonControl, req: Request) -> Result<Respo
             })?;
             list_revealed_addresses(control, params)?
         }
+        "searchcoins" => search_coins(control, req.params)?,
+        "searchtransactions" => search_transactions(control, req.params)?,
         "listconfirmed" => {
             let params = req.params.ok_or_else(|| {
                 Error::invalid_params(
@@ -553,6 +962,7 @@ pub fn handle_request(control: &mut DaemonControl, req: Request) -> Result<Respo
             })?;
             list_confirmed(control, params)?
         }
+        "listscheduledbroadcasts" => serde_json::json!(&control.list_scheduled_broadcasts()),
         "listspendtxs" => list_spendtxs(control, req.params)?,
         "listtransactions" => {
             let params = req.params.ok_or_else(|| {
@@ -562,6 +972,18 @@ pub fn handle_request(control: &mut DaemonControl, req: Request) -> Result<Respo
             })?;
             list_transactions(control, params)?
         }
+        "schedulebroadcast" => {
+            let params = req
+                .params
+                .ok_or_else(|| Error::invalid_params("Missing 'txid' parameter."))?;
+            schedule_broadcast(control, params)?
+        }
+        "setgaplimit" => {
+            let params = req
+                .params
+                .ok_or_else(|| Error::invalid_params("Missing 'gap_limit' parameter."))?;
+            set_gap_limit(control, params)?
+        }
         "startrescan" => {
             let params = req
                 .params
@@ -593,6 +1015,12 @@ pub fn handle_request(control: &mut DaemonControl, req: Request) -> Result<Respo
                 .ok_or_else(|| Error::invalid_params("Missing 'offset' and 'limit' parameters."))?;
             get_labels_bip329(control, params)?
         }
+        "importlabels" => {
+            let params = req
+                .params
+                .ok_or_else(|| Error::invalid_params("Missing 'labels' parameter."))?;
+            import_labels(control, params)?
+        }
         _ => {
             return Err(Error::method_not_found());
         }
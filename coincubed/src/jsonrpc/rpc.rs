@@ -51,6 +51,9 @@ pub struct Request {
 /// A failure to broadcast a transaction to the P2P network.
 const BROADCAST_ERROR: i64 = 1_000;
 
+/// A Spend transaction does not follow the local spending policy.
+const POLICY_VIOLATION_ERROR: i64 = 1_001;
+
 /// JSONRPC2 error codes. See https://www.jsonrpc.org/specification#error_object.
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum ErrorCode {
@@ -163,8 +166,16 @@ impl From<commands::CommandError> for Error {
             | commands::CommandError::InvalidDerivationIndex
             | commands::CommandError::RbfError(..)
             | commands::CommandError::EmptyFilterList
+            | commands::CommandError::UnknownAddress(..)
+            | commands::CommandError::NotADestination(..)
+            | commands::CommandError::CoinAlreadyConfirmed(..)
+            | commands::CommandError::NotInMempool(..)
+            | commands::CommandError::InvalidGapLimit(..)
             | commands::CommandError::RecoveryNotAvailable
-            | commands::CommandError::OutpointNotRecoverable(..) => {
+            | commands::CommandError::OutpointNotRecoverable(..)
+            | commands::CommandError::EmptyBroadcastSchedule
+            | commands::CommandError::NotScheduled(..)
+            | commands::CommandError::UnknownRecoveryPath(..) => {
                 Error::new(ErrorCode::InvalidParams, e.to_string())
             }
             commands::CommandError::RescanTrigger(..) => {
@@ -173,6 +184,10 @@ impl From<commands::CommandError> for Error {
             commands::CommandError::TxBroadcast(_) => {
                 Error::new(ErrorCode::ServerError(BROADCAST_ERROR), e.to_string())
             }
+            commands::CommandError::PolicyViolation(_) => Error::new(
+                ErrorCode::ServerError(POLICY_VIOLATION_ERROR),
+                e.to_string(),
+            ),
         }
     }
 }
@@ -4,6 +4,7 @@ pub mod config;
 mod database;
 pub mod datadir;
 mod jsonrpc;
+pub mod policy;
 #[cfg(test)]
 mod testutils;
 
@@ -322,6 +323,7 @@ fn setup_electrum(
         &txs,
         receive_index,
         change_index,
+        db_conn.gap_limit(),
     );
     let full_scan = db_conn.rescan_timestamp().is_some();
     let electrum = Electrum::new(client, bdk_wallet, full_scan).map_err(StartupError::Electrum)?;
@@ -455,8 +457,12 @@ impl DaemonHandle {
 
         // Start the poller thread. Keep the thread handle to be able to check if it crashed. Store
         // an atomic to be able to stop it.
-        let mut bitcoin_poller =
-            poller::Poller::new(bit.clone(), db.clone(), config.main_descriptor.clone());
+        let mut bitcoin_poller = poller::Poller::new(
+            bit.clone(),
+            db.clone(),
+            config.main_descriptor.clone(),
+            config.spending_policy.clone(),
+        );
         let (poller_sender, poller_receiver) = mpsc::sync_channel(0);
         let poller_handle = thread::Builder::new()
             .name("Bitcoin Network poller".to_string())
@@ -0,0 +1,320 @@
+//! Local spending policy.
+//!
+//! Rules enforced by the daemon, on top of the on-chain policy of the descriptor, when creating
+//! and broadcasting a Spend transaction. They are not enforced by the signing devices: they guard
+//! against operator mistakes and a compromised frontend, not against a compromised daemon.
+
+use std::fmt;
+
+use miniscript::bitcoin::{self, address::NetworkUnchecked};
+use serde::{Deserialize, Serialize};
+
+/// One day, in seconds.
+pub const DAY_SECS: u32 = 24 * 60 * 60;
+/// One week, in seconds.
+pub const WEEK_SECS: u32 = 7 * DAY_SECS;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SpendingPolicy {
+    /// Maximum value, in sats, leaving the wallet over any 24 hours.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub daily_limit: Option<u64>,
+    /// Maximum value, in sats, leaving the wallet over any 7 days.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weekly_limit: Option<u64>,
+    /// If not empty, the only addresses the wallet may send to (besides itself).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_destinations: Vec<bitcoin::Address<NetworkUnchecked>>,
+    /// Spends of at least this value, in sats, may only be broadcast `broadcast_delay` seconds
+    /// after they were created.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delay_threshold: Option<u64>,
+    #[serde(default)]
+    pub broadcast_delay: u32,
+    /// Whether Spend transactions must be approved, typically by a second operator, before
+    /// being broadcast.
+    #[serde(default)]
+    pub require_approval: bool,
+}
+
+/// A rule of the spending policy a Spend transaction does not follow.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PolicyViolation {
+    DailyLimit {
+        limit: u64,
+        /// Value which would leave the wallet over the last 24 hours, including this spend.
+        outflow: u64,
+    },
+    WeeklyLimit {
+        limit: u64,
+        /// Value which would leave the wallet over the last 7 days, including this spend.
+        outflow: u64,
+    },
+    /// The address, or the Script if it is not one of a standard address, of an output.
+    DestinationNotAllowed(String),
+    /// The spend may only be broadcast at this timestamp.
+    BroadcastDelay(u32),
+    ApprovalRequired,
+}
+
+impl fmt::Display for PolicyViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::DailyLimit { limit, outflow } => write!(
+                f,
+                "Daily outflow limit of {} sats exceeded: {} sats would be spent over the last 24 hours.",
+                limit, outflow
+            ),
+            Self::WeeklyLimit { limit, outflow } => write!(
+                f,
+                "Weekly outflow limit of {} sats exceeded: {} sats would be spent over the last 7 days.",
+                limit, outflow
+            ),
+            Self::DestinationNotAllowed(dest) => {
+                write!(f, "Destination '{}' is not in the allowlist.", dest)
+            }
+            Self::BroadcastDelay(at) => write!(
+                f,
+                "This spend is above the delay threshold and may only be broadcast from timestamp {}.",
+                at
+            ),
+            Self::ApprovalRequired => {
+                write!(f, "This spend must be approved before being broadcast.")
+            }
+        }
+    }
+}
+
+/// What the policy is evaluated against.
+#[derive(Debug, Clone, Default)]
+pub struct SpendContext {
+    /// The outputs of the spend which are not ours.
+    pub external_outputs: Vec<bitcoin::TxOut>,
+    /// Value leaving the wallet with this spend, fee included.
+    pub outflow: u64,
+    /// Value which left the wallet with previous transactions, along with their confirmation
+    /// time (`None` if unconfirmed).
+    pub past_outflows: Vec<(Option<u32>, u64)>,
+    /// The current timestamp.
+    pub now: u32,
+}
+
+impl SpendContext {
+    /// Value leaving the wallet since this timestamp, including this spend. Unconfirmed
+    /// transactions are counted as recent.
+    fn outflow_since(&self, since: u32) -> u64 {
+        self.past_outflows
+            .iter()
+            .filter(|(time, _)| time.is_none_or(|t| t >= since))
+            .fold(self.outflow, |sum, (_, value)| sum.saturating_add(*value))
+    }
+}
+
+impl SpendingPolicy {
+    /// The rules to follow for a Spend transaction to be created.
+    pub fn check_spend(
+        &self,
+        network: bitcoin::Network,
+        ctx: &SpendContext,
+    ) -> Result<(), PolicyViolation> {
+        if !self.allowed_destinations.is_empty() {
+            for txo in &ctx.external_outputs {
+                // Data carrier outputs don't send any value.
+                if txo.value == bitcoin::Amount::ZERO && txo.script_pubkey.is_op_return() {
+                    continue;
+                }
+                if !self
+                    .allowed_destinations
+                    .iter()
+                    .any(|addr| addr.assume_checked_ref().script_pubkey() == txo.script_pubkey)
+                {
+                    return Err(PolicyViolation::DestinationNotAllowed(
+                        bitcoin::Address::from_script(&txo.script_pubkey, network)
+                            .map(|addr| addr.to_string())
+                            .unwrap_or_else(|_| txo.script_pubkey.to_asm_string()),
+                    ));
+                }
+            }
+        }
+
+        if let Some(limit) = self.daily_limit {
+            let outflow = ctx.outflow_since(ctx.now.saturating_sub(DAY_SECS));
+            if outflow > limit {
+                return Err(PolicyViolation::DailyLimit { limit, outflow });
+            }
+        }
+        if let Some(limit) = self.weekly_limit {
+            let outflow = ctx.outflow_since(ctx.now.saturating_sub(WEEK_SECS));
+            if outflow > limit {
+                return Err(PolicyViolation::WeeklyLimit { limit, outflow });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The rules to follow for a Spend transaction to be broadcast. This includes the rules for
+    /// its creation, as it may have been imported rather than created by us.
+    ///
+    /// `created_at` is the time the spend was first stored (`None` if unknown, in which case the
+    /// whole delay applies from now) and `approved_at` the time it was approved, if it was.
+    pub fn check_broadcast(
+        &self,
+        network: bitcoin::Network,
+        ctx: &SpendContext,
+        created_at: Option<u32>,
+        approved_at: Option<u32>,
+    ) -> Result<(), PolicyViolation> {
+        match self.check_schedule(network, ctx, created_at, approved_at)? {
+            Some(broadcast_at) if ctx.now < broadcast_at => {
+                Err(PolicyViolation::BroadcastDelay(broadcast_at))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The rules to follow for a Spend transaction to be scheduled for broadcast. Same as for
+    /// broadcasting it, except the broadcast delay is not enforced but returned: the time from
+    /// which it may be broadcast, if it is delayed.
+    pub fn check_schedule(
+        &self,
+        network: bitcoin::Network,
+        ctx: &SpendContext,
+        created_at: Option<u32>,
+        approved_at: Option<u32>,
+    ) -> Result<Option<u32>, PolicyViolation> {
+        self.check_spend(network, ctx)?;
+
+        if self.require_approval && approved_at.is_none() {
+            return Err(PolicyViolation::ApprovalRequired);
+        }
+
+        Ok(self.delay_threshold.filter(|t| ctx.outflow >= *t).map(|_| {
+            created_at
+                .unwrap_or(ctx.now)
+                .saturating_add(self.broadcast_delay)
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn spending_policy() {
+        let network = bitcoin::Network::Testnet;
+        let allowed =
+            bitcoin::Address::from_str("tb1qfufcrdyarcg5eph608c6l8vktrc9re6agu4se2").unwrap();
+        let other = bitcoin::Address::from_str("tb1qqurswpc8qurswpc8qurswpc8qurswpc800zzll")
+            .unwrap()
+            .assume_checked();
+        let txo = |addr: &bitcoin::Address, value: u64| bitcoin::TxOut {
+            value: bitcoin::Amount::from_sat(value),
+            script_pubkey: addr.script_pubkey(),
+        };
+        let now = 1_700_000_000;
+        let mut ctx = SpendContext {
+            external_outputs: vec![txo(allowed.assume_checked_ref(), 50_000)],
+            outflow: 51_000,
+            past_outflows: vec![
+                // Unconfirmed, yesterday, last week and earlier.
+                (None, 10_000),
+                (Some(now - DAY_SECS), 20_000),
+                (Some(now - WEEK_SECS), 40_000),
+                (Some(now - WEEK_SECS - 1), 80_000),
+            ],
+            now,
+        };
+
+        // An empty policy allows anything.
+        let mut policy = SpendingPolicy::default();
+        policy.check_spend(network, &ctx).unwrap();
+        policy.check_broadcast(network, &ctx, None, None).unwrap();
+
+        // Outflow limits, over a sliding window.
+        policy.daily_limit = Some(81_000);
+        policy.check_spend(network, &ctx).unwrap();
+        policy.daily_limit = Some(80_999);
+        assert_eq!(
+            policy.check_spend(network, &ctx),
+            Err(PolicyViolation::DailyLimit {
+                limit: 80_999,
+                outflow: 81_000
+            })
+        );
+        policy.daily_limit = None;
+        policy.weekly_limit = Some(121_000);
+        policy.check_spend(network, &ctx).unwrap();
+        policy.weekly_limit = Some(120_000);
+        assert_eq!(
+            policy.check_spend(network, &ctx),
+            Err(PolicyViolation::WeeklyLimit {
+                limit: 120_000,
+                outflow: 121_000
+            })
+        );
+        policy.weekly_limit = None;
+
+        // Destination allowlist. Data carrier outputs are not destinations.
+        policy.allowed_destinations = vec![allowed.clone()];
+        ctx.external_outputs.push(bitcoin::TxOut {
+            value: bitcoin::Amount::ZERO,
+            script_pubkey: bitcoin::ScriptBuf::new_op_return([0xab; 4]),
+        });
+        policy.check_spend(network, &ctx).unwrap();
+        ctx.external_outputs.push(txo(&other, 1_000));
+        assert_eq!(
+            policy.check_spend(network, &ctx),
+            Err(PolicyViolation::DestinationNotAllowed(other.to_string()))
+        );
+        // The broadcast is subject to the same rules.
+        assert_eq!(
+            policy.check_broadcast(network, &ctx, Some(now), Some(now)),
+            Err(PolicyViolation::DestinationNotAllowed(other.to_string()))
+        );
+        ctx.external_outputs.pop();
+
+        // Cool-down for large amounts.
+        policy.delay_threshold = Some(51_001);
+        policy.broadcast_delay = DAY_SECS;
+        policy
+            .check_broadcast(network, &ctx, Some(now), None)
+            .unwrap();
+        policy.delay_threshold = Some(51_000);
+        assert_eq!(
+            policy.check_broadcast(network, &ctx, Some(now - 60), None),
+            Err(PolicyViolation::BroadcastDelay(now - 60 + DAY_SECS))
+        );
+        assert_eq!(
+            policy.check_broadcast(network, &ctx, None, None),
+            Err(PolicyViolation::BroadcastDelay(now + DAY_SECS))
+        );
+        policy
+            .check_broadcast(network, &ctx, Some(now - DAY_SECS), None)
+            .unwrap();
+        // The delay doesn't prevent the creation, nor scheduling the broadcast for later.
+        policy.check_spend(network, &ctx).unwrap();
+        assert_eq!(
+            policy.check_schedule(network, &ctx, Some(now - 60), None),
+            Ok(Some(now - 60 + DAY_SECS))
+        );
+
+        // Approval.
+        policy.require_approval = true;
+        assert_eq!(
+            policy.check_broadcast(network, &ctx, Some(now - DAY_SECS), None),
+            Err(PolicyViolation::ApprovalRequired)
+        );
+        assert_eq!(
+            policy.check_schedule(network, &ctx, Some(now - DAY_SECS), None),
+            Err(PolicyViolation::ApprovalRequired)
+        );
+        policy
+            .check_broadcast(network, &ctx, Some(now - DAY_SECS), Some(now))
+            .unwrap();
+        policy.check_spend(network, &ctx).unwrap();
+    }
+}
@@ -2,9 +2,11 @@ use crate::{
     bitcoin::{BitcoinInterface, Block, BlockChainTip, MempoolEntry, SyncProgress, UTxO},
     config::{BitcoinConfig, Config},
     database::{
-        BlockInfo, Coin, CoinStatus, DatabaseConnection, DatabaseInterface, LabelItem, Wallet,
+        BlockInfo, Coin, CoinStatus, DatabaseConnection, DatabaseInterface, LabelItem,
+        ScheduledBroadcast, SearchFilter, Wallet,
     },
     datadir::DataDirectory,
+    policy::SpendingPolicy,
     DaemonControl, DaemonHandle,
 };
 use coincube_core::descriptors;
@@ -25,6 +27,7 @@ use miniscript::{
 
 pub struct DummyBitcoind {
     pub txs: HashMap<Txid, (Transaction, Option<Block>)>,
+    pub mempool_entries: HashMap<Txid, MempoolEntry>,
 }
 
 impl DummyBitcoind {}
@@ -33,6 +36,7 @@ impl DummyBitcoind {
     pub fn new() -> Self {
         Self {
             txs: HashMap::new(),
+            mempool_entries: HashMap::new(),
         }
     }
 }
@@ -72,6 +76,7 @@ impl BitcoinInterface for DummyBitcoind {
         &mut self,
         _receive_index: bip32::ChildNumber,
         _change_index: bip32::ChildNumber,
+        _gap_limit: u32,
     ) -> Result<Option<BlockChainTip>, String> {
         Ok(None)
     }
@@ -140,8 +145,8 @@ impl BitcoinInterface for DummyBitcoind {
         Vec::new()
     }
 
-    fn mempool_entry(&self, _: &bitcoin::Txid) -> Option<MempoolEntry> {
-        None
+    fn mempool_entry(&self, txid: &bitcoin::Txid) -> Option<MempoolEntry> {
+        self.mempool_entries.get(txid).cloned()
     }
 }
 
@@ -150,14 +155,18 @@ struct DummyDbState {
     change_index: bip32::ChildNumber,
     curr_tip: Option<BlockChainTip>,
     coins: HashMap<bitcoin::OutPoint, Coin>,
+    addresses: HashMap<bitcoin::Address, (bip32::ChildNumber, bool)>,
     txs: HashMap<bitcoin::Txid, bitcoin::Transaction>,
-    spend_txs: HashMap<bitcoin::Txid, (Psbt, Option<u32>)>,
+    spend_txs: HashMap<bitcoin::Txid, (Psbt, Option<u32>, Option<u32>)>,
+    broadcast_queue: Vec<ScheduledBroadcast>,
     labels: HashMap<LabelItem, String>,
     timestamp: u32,
     rescan_timestamp: Option<u32>,
     last_poll_timestamp: Option<u32>,
+    gap_limit: u32,
 }
 
+#[derive(Clone)]
 pub struct DummyDatabase {
     db: sync::Arc<sync::RwLock<DummyDbState>>,
 }
@@ -185,12 +194,15 @@ impl DummyDatabase {
                 change_index: 0.into(),
                 curr_tip: None,
                 coins: HashMap::new(),
+                addresses: HashMap::new(),
                 txs: HashMap::new(),
                 spend_txs: HashMap::new(),
+                broadcast_queue: Vec::new(),
                 labels: HashMap::new(),
                 timestamp: now,
                 rescan_timestamp: None,
                 last_poll_timestamp: None,
+                gap_limit: 200,
             })),
         }
     }
@@ -200,6 +212,19 @@ impl DummyDatabase {
             self.db.write().unwrap().coins.insert(coin.outpoint, coin);
         }
     }
+
+    pub fn insert_address(
+        &mut self,
+        addr: bitcoin::Address,
+        index: bip32::ChildNumber,
+        is_change: bool,
+    ) {
+        self.db
+            .write()
+            .unwrap()
+            .addresses
+            .insert(addr, (index, is_change));
+    }
 }
 
 impl DatabaseConnection for DummyDatabase {
@@ -219,6 +244,7 @@ impl DatabaseConnection for DummyDatabase {
             change_index: db_wallet.change_index,
             rescan_timestamp: db_wallet.rescan_timestamp,
             last_poll_timestamp: db_wallet.last_poll_timestamp,
+            gap_limit: db_wallet.gap_limit,
         }
     }
 
@@ -254,6 +280,88 @@ impl DatabaseConnection for DummyDatabase {
         self.db.write().unwrap().change_index = index;
     }
 
+    fn gap_limit(&mut self) -> u32 {
+        self.db.read().unwrap().gap_limit
+    }
+
+    fn set_gap_limit(&mut self, gap_limit: u32, _: &secp256k1::Secp256k1<secp256k1::VerifyOnly>) {
+        self.db.write().unwrap().gap_limit = gap_limit;
+    }
+
+    fn highest_used_index(&mut self, change: bool) -> Option<bip32::ChildNumber> {
+        self.db
+            .read()
+            .unwrap()
+            .coins
+            .values()
+            .filter(|c| c.is_change == change)
+            .map(|c| c.derivation_index)
+            .max()
+    }
+
+    fn search_coins(&mut self, filter: &SearchFilter) -> HashMap<bitcoin::OutPoint, Coin> {
+        let labels: HashMap<String, String> = self
+            .db
+            .read()
+            .unwrap()
+            .labels
+            .iter()
+            .map(|(item, label)| (item.to_string(), label.clone()))
+            .collect();
+        let label_matches = |items: &[String]| {
+            filter.label.as_ref().is_none_or(|text| {
+                let text = text.to_lowercase();
+                items.iter().any(|item| {
+                    labels
+                        .get(item)
+                        .is_some_and(|label| label.to_lowercase().contains(&text))
+                })
+            })
+        };
+        self.db
+            .read()
+            .unwrap()
+            .coins
+            .iter()
+            .filter(|(op, c)| {
+                filter.min_amount.is_none_or(|min| c.amount >= min)
+                    && filter.max_amount.is_none_or(|max| c.amount <= max)
+                    && filter.matches_time(c.block_info.map(|b| b.time))
+                    && filter.matches_txid(&op.txid)
+                    && label_matches(&[op.to_string(), op.txid.to_string()])
+            })
+            .map(|(op, c)| (*op, *c))
+            .collect()
+    }
+
+    fn search_txids(&mut self, filter: &SearchFilter, limit: u64) -> Vec<bitcoin::Txid> {
+        let mut txids: Vec<_> = self
+            .search_coins(&SearchFilter {
+                start: None,
+                end: None,
+                txid_prefix: None,
+                ..filter.clone()
+            })
+            .values()
+            .map(|c| c.outpoint.txid)
+            .filter(|txid| filter.matches_txid(txid))
+            .collect();
+        txids.sort();
+        txids.dedup();
+        txids.truncate(limit as usize);
+        txids
+    }
+
+    fn address_use_counts(&mut self, change: bool) -> HashMap<bip32::ChildNumber, u32> {
+        let mut counts = HashMap::new();
+        for coin in self.db.read().unwrap().coins.values() {
+            if coin.is_change == change {
+                *counts.entry(coin.derivation_index).or_insert(0) += 1;
+            }
+        }
+        counts
+    }
+
     fn coins(
         &mut self,
         statuses: &[CoinStatus],
@@ -363,9 +471,9 @@ impl DatabaseConnection for DummyDatabase {
 
     fn derivation_index_by_address(
         &mut self,
-        _: &bitcoin::Address,
+        addr: &bitcoin::Address,
     ) -> Option<(bip32::ChildNumber, bool)> {
-        None
+        self.db.read().unwrap().addresses.get(addr).copied()
     }
 
     fn coins_by_outpoints(
@@ -389,7 +497,9 @@ impl DatabaseConnection for DummyDatabase {
             .write()
             .unwrap()
             .spend_txs
-            .insert(txid, (psbt.clone(), None));
+            .entry(txid)
+            .and_modify(|spend| spend.0 = psbt.clone())
+            .or_insert((psbt.clone(), None, None));
     }
 
     fn spend_tx(&mut self, txid: &bitcoin::Txid) -> Option<Psbt> {
@@ -402,7 +512,7 @@ impl DatabaseConnection for DummyDatabase {
             .map(|x| x.0)
     }
 
-    fn list_spend(&mut self) -> Vec<(Psbt, Option<u32>)> {
+    fn list_spend(&mut self) -> Vec<(Psbt, Option<u32>, Option<u32>)> {
         self.db
             .read()
             .unwrap()
@@ -412,8 +522,93 @@ impl DatabaseConnection for DummyDatabase {
             .collect()
     }
 
+    fn spend_tx_timestamps(&mut self, txid: &bitcoin::Txid) -> Option<(Option<u32>, Option<u32>)> {
+        self.db
+            .read()
+            .unwrap()
+            .spend_txs
+            .get(txid)
+            .map(|spend| (spend.1, spend.2))
+    }
+
+    fn approve_spend(&mut self, txid: &bitcoin::Txid) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as u32;
+        if let Some(spend) = self.db.write().unwrap().spend_txs.get_mut(txid) {
+            spend.2.get_or_insert(now);
+        }
+    }
+
     fn delete_spend(&mut self, txid: &bitcoin::Txid) {
-        self.db.write().unwrap().spend_txs.remove(txid);
+        let mut db = self.db.write().unwrap();
+        db.spend_txs.remove(txid);
+        db.broadcast_queue.retain(|entry| &entry.txid != txid);
+    }
+
+    fn schedule_broadcast(
+        &mut self,
+        txid: &bitcoin::Txid,
+        block_height: Option<i32>,
+        timestamp: Option<u32>,
+    ) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as u32;
+        let mut db = self.db.write().unwrap();
+        db.broadcast_queue.retain(|entry| &entry.txid != txid);
+        db.broadcast_queue.push(ScheduledBroadcast {
+            txid: *txid,
+            block_height,
+            timestamp,
+            created_at: now,
+            broadcast_at: None,
+            last_error: None,
+            failed_at: None,
+        });
+    }
+
+    fn scheduled_broadcasts(&mut self) -> Vec<ScheduledBroadcast> {
+        self.db.read().unwrap().broadcast_queue.clone()
+    }
+
+    fn cancel_broadcast(&mut self, txid: &bitcoin::Txid) {
+        self.db
+            .write()
+            .unwrap()
+            .broadcast_queue
+            .retain(|entry| &entry.txid != txid);
+    }
+
+    fn record_broadcast_attempt(&mut self, txid: &bitcoin::Txid, result: Result<(), &str>) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as u32;
+        let mut db = self.db.write().unwrap();
+        if let Some(entry) = db.broadcast_queue.iter_mut().find(|e| &e.txid == txid) {
+            match result {
+                Ok(()) => {
+                    entry.broadcast_at = Some(now);
+                    entry.last_error = None;
+                }
+                Err(e) => entry.last_error = Some(e.to_string()),
+            }
+        }
+    }
+
+    fn fail_broadcast(&mut self, txid: &bitcoin::Txid, error: &str) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as u32;
+        let mut db = self.db.write().unwrap();
+        if let Some(entry) = db.broadcast_queue.iter_mut().find(|e| &e.txid == txid) {
+            entry.failed_at = Some(now);
+            entry.last_error = Some(error.to_string());
+        }
     }
 
     fn rollback_tip(&mut self, _: &BlockChainTip) {
@@ -578,6 +773,7 @@ impl DummyCoincube {
         database: impl DatabaseInterface + 'static,
         rpc_server: bool,
         timelock: u16,
+        spending_policy: Option<SpendingPolicy>,
     ) -> DummyCoincube {
         let tmp_dir = tmp_dir();
         fs::create_dir_all(&tmp_dir).unwrap();
@@ -602,13 +798,14 @@ impl DummyCoincube {
         )
         .unwrap();
         let desc = descriptors::CoincubeDescriptor::new(policy);
-        let config = Config::new(
+        let mut config = Config::new(
             bitcoin_config,
             None,
             log::LevelFilter::Debug,
             desc,
             DataDirectory::new(data_directory),
         );
+        config.spending_policy = spending_policy;
 
         let handle =
             DaemonHandle::start(config, Some(bitcoin_interface), Some(database), rpc_server)
@@ -621,7 +818,7 @@ impl DummyCoincube {
         bitcoin_interface: impl BitcoinInterface + 'static,
         database: impl DatabaseInterface + 'static,
     ) -> DummyCoincube {
-        Self::_new(bitcoin_interface, database, false, 10_000)
+        Self::_new(bitcoin_interface, database, false, 10_000, None)
     }
 
     /// Creates a new DummyCoincube interface with the specified recovery path timelock.
@@ -630,7 +827,22 @@ impl DummyCoincube {
         database: impl DatabaseInterface + 'static,
         timelock: u16,
     ) -> DummyCoincube {
-        Self::_new(bitcoin_interface, database, false, timelock)
+        Self::_new(bitcoin_interface, database, false, timelock, None)
+    }
+
+    /// Creates a new DummyCoincube interface which enforces the given spending policy.
+    pub fn new_policy(
+        bitcoin_interface: impl BitcoinInterface + 'static,
+        database: impl DatabaseInterface + 'static,
+        spending_policy: SpendingPolicy,
+    ) -> DummyCoincube {
+        Self::_new(
+            bitcoin_interface,
+            database,
+            false,
+            10_000,
+            Some(spending_policy),
+        )
     }
 
     /// Creates a new DummyCoincube interface which also spins up an RPC server.
@@ -639,7 +851,7 @@ impl DummyCoincube {
         bitcoin_interface: impl BitcoinInterface + 'static,
         database: impl DatabaseInterface + 'static,
     ) -> DummyCoincube {
-        Self::_new(bitcoin_interface, database, true, 10_000)
+        Self::_new(bitcoin_interface, database, true, 10_000, None)
     }
 
     pub fn control(&self) -> &DaemonControl {
@@ -40,6 +40,33 @@ def test_getinfo(coincubed):
     assert res["last_poll_timestamp"] > last_poll_timestamp
     assert res["receive_index"] == 0
     assert res["change_index"] == 0
+    assert res["gap_limit"] == 200
+    assert res["warnings"] == []
+
+
+def test_gap_limit(coincubed, bitcoind):
+    # The gap limit must be within bounds.
+    for gap_limit in (0, 100_001):
+        with pytest.raises(RpcError, match="Invalid gap limit"):
+            coincubed.rpc.setgaplimit(gap_limit)
+
+    # Revealing many addresses without using them triggers a warning.
+    coincubed.rpc.setgaplimit(10)
+    assert coincubed.rpc.getinfo()["gap_limit"] == 10
+    coincubed.rpc.updatederivationindexes(receive=9)
+    warnings = coincubed.rpc.getinfo()["warnings"]
+    assert len(warnings) == 1 and "receive" in warnings[0]
+
+    # Coins received on an address within the new gap limit are detected.
+    coincubed.rpc.setgaplimit(50)
+    assert coincubed.rpc.getinfo()["warnings"] == []
+    addr = coincubed.rpc.listaddresses(40, 1)["addresses"][0]["receive"]
+    txid = bitcoind.rpc.sendtoaddress(addr, 0.01)
+    wait_for(
+        lambda: any(
+            c["outpoint"].startswith(txid) for c in coincubed.rpc.listcoins()["coins"]
+        )
+    )
 
 
 def test_update_derivation_indexes(coincubed):
@@ -964,6 +991,120 @@ def test_broadcast_spend(coincubed, bitcoind):
     coincubed.rpc.broadcastspend(txid)
 
 
+def test_spending_policy(coincubed, bitcoind):
+    """Test the local spending policy is enforced at creation and broadcast."""
+    allowed_addr = bitcoind.rpc.getnewaddress()
+    coincubed.stop()
+    with open(coincubed.conf_file, "a") as f:
+        f.write("[spending_policy]\n")
+        f.write("weekly_limit = 1000000\n")
+        f.write(f'allowed_destinations = ["{allowed_addr}"]\n')
+        f.write("require_approval = true\n")
+    coincubed.start()
+    assert coincubed.rpc.getinfo()["spending_policy"]["weekly_limit"] == 1_000_000
+
+    addr = coincubed.rpc.getnewaddress()["address"]
+    bitcoind.rpc.sendtoaddress(addr, 0.02)
+    wait_for(lambda: len(coincubed.rpc.listcoins()["coins"]) > 0)
+    outpoints = [c["outpoint"] for c in coincubed.rpc.listcoins()["coins"]]
+
+    # Only the allowed destinations can be paid.
+    other_addr = bitcoind.rpc.getnewaddress()
+    with pytest.raises(RpcError, match=".*is not in the allowlist.*"):
+        coincubed.rpc.createspend({other_addr: 200_000}, outpoints, 2)
+
+    # The spend must be approved before it's broadcast.
+    res = coincubed.rpc.createspend({allowed_addr: 200_000}, outpoints, 2)
+    txid = PSBT.from_base64(res["psbt"]).tx.txid().hex()
+    signed_psbt = coincubed.signer.sign_psbt(PSBT.from_base64(res["psbt"]))
+    coincubed.rpc.updatespend(signed_psbt.to_base64())
+    with pytest.raises(RpcError, match=".*must be approved before being broadcast.*"):
+        coincubed.rpc.broadcastspend(txid)
+    coincubed.rpc.approvespend(txid)
+    spend_txs = coincubed.rpc.listspendtxs([txid])["spend_txs"]
+    assert spend_txs[0]["approved_at"] is not None
+    coincubed.rpc.broadcastspend(txid)
+    bitcoind.generate_block(1, wait_for_mempool=txid)
+    wait_for(lambda: len(coincubed.rpc.listcoins(["spent"])["coins"]) == 1)
+
+    # The previous spend counts toward the outflow limit.
+    wait_for(lambda: len(coincubed.rpc.listcoins(["confirmed"])["coins"]) == 1)
+    outpoints = [c["outpoint"] for c in coincubed.rpc.listcoins(["confirmed"])["coins"]]
+    with pytest.raises(RpcError, match="Weekly outflow limit.*"):
+        coincubed.rpc.createspend({allowed_addr: 800_000}, outpoints, 2)
+
+
+def test_schedule_broadcast(coincubed, bitcoind):
+    """Test the poller broadcasts scheduled Spend transactions once they are due."""
+    addr = coincubed.rpc.getnewaddress()["address"]
+    deposit_txid = bitcoind.rpc.sendtoaddress(addr, 0.01)
+    bitcoind.generate_block(1, wait_for_mempool=deposit_txid)
+    wait_for(lambda: len(coincubed.rpc.listcoins(["confirmed"])["coins"]) == 1)
+    outpoints = [c["outpoint"] for c in coincubed.rpc.listcoins()["coins"]]
+    destinations = {bitcoind.rpc.getnewaddress(): 200_000}
+    res = coincubed.rpc.createspend(destinations, outpoints, 2)
+    txid = PSBT.from_base64(res["psbt"]).tx.txid().hex()
+
+    # Only stored and fully signed transactions can be scheduled.
+    with pytest.raises(RpcError, match="Unknown spend transaction.*"):
+        coincubed.rpc.schedulebroadcast(txid, 1)
+    coincubed.rpc.updatespend(res["psbt"])
+    with pytest.raises(RpcError, match="Failed to finalize the spend transaction.*"):
+        coincubed.rpc.schedulebroadcast(txid, 1)
+    signed_psbt = coincubed.signer.sign_psbt(PSBT.from_base64(res["psbt"]))
+    coincubed.rpc.updatespend(signed_psbt.to_base64())
+    with pytest.raises(RpcError, match=".*block height or a timestamp.*"):
+        coincubed.rpc.schedulebroadcast(txid)
+    # Another transaction spending the same coin.
+    destinations = {bitcoind.rpc.getnewaddress(): 300_000}
+    res = coincubed.rpc.createspend(destinations, outpoints, 2)
+    signed_psbt = coincubed.signer.sign_psbt(PSBT.from_base64(res["psbt"]))
+    coincubed.rpc.updatespend(signed_psbt.to_base64())
+    conflicting_txid = signed_psbt.tx.txid().hex()
+
+    # It is broadcast once the block chain reaches the given height.
+    height = bitcoind.rpc.getblockcount()
+    coincubed.rpc.schedulebroadcast(txid, height + 2)
+    entry = coincubed.rpc.listscheduledbroadcasts()["broadcasts"][0]
+    assert entry["txid"] == txid
+    assert entry["block_height"] == height + 2
+    assert entry["broadcast_at"] is None
+    bitcoind.generate_block(1)
+    wait_for(lambda: coincubed.rpc.getinfo()["block_height"] == height + 1)
+    assert txid not in bitcoind.rpc.getrawmempool()
+    bitcoind.generate_block(1)
+    wait_for(lambda: txid in bitcoind.rpc.getrawmempool())
+    wait_for(
+        lambda: coincubed.rpc.listscheduledbroadcasts()["broadcasts"][0]["broadcast_at"]
+        is not None
+    )
+
+    # Entries can be removed from the queue.
+    coincubed.rpc.cancelscheduledbroadcast(txid)
+    assert coincubed.rpc.listscheduledbroadcasts()["broadcasts"] == []
+
+    # A transaction whose coins were spent already is not broadcast, but marked as failed.
+    bitcoind.generate_block(1, wait_for_mempool=txid)
+    wait_for(lambda: len(coincubed.rpc.listcoins(["spent"])["coins"]) == 1)
+    coincubed.rpc.schedulebroadcast(conflicting_txid, 1)
+    wait_for(
+        lambda: coincubed.rpc.listscheduledbroadcasts()["broadcasts"][0]["failed_at"]
+        is not None
+    )
+    entry = coincubed.rpc.listscheduledbroadcasts()["broadcasts"][0]
+    assert entry["broadcast_at"] is None
+    assert txid in entry["last_error"]
+    # A transaction which was broadcast by other means is recorded as such.
+    coincubed.rpc.schedulebroadcast(txid, 1)
+    wait_for(
+        lambda: coincubed.rpc.listscheduledbroadcasts()["broadcasts"][1]["broadcast_at"]
+        is not None
+    )
+    coincubed.rpc.cancelscheduledbroadcast(conflicting_txid)
+    with pytest.raises(RpcError, match=".*is not scheduled for broadcast.*"):
+        coincubed.rpc.cancelscheduledbroadcast(txid)
+
+
 # Use a descriptor that includes hardened derivation paths so that we can check
 # there is no problem regarding the use of `h` and `'`.
 def test_start_rescan_does_not_error(coincubed_with_deriv_paths, bitcoind):
@@ -1354,6 +1495,54 @@ def test_create_recovery(coincubed, bitcoind):
     sign_and_broadcast(coincubed, bitcoind, reco_psbt, recovery=True)
 
 
+def test_create_heir_recovery(coincubed, bitcoind):
+    """Test the creation of a recovery transaction before its timelock matured."""
+    bitcoind.generate_block(200)
+    destinations = {
+        coincubed.rpc.getnewaddress()["address"]: 0.1,
+        coincubed.rpc.getnewaddress()["address"]: 0.2,
+    }
+    txid = bitcoind.rpc.sendmany("", destinations)
+    bitcoind.generate_block(1, wait_for_mempool=txid)
+    wait_for(
+        lambda: coincubed.rpc.getinfo()["block_height"] == bitcoind.rpc.getblockcount()
+    )
+    outpoints = sorted(c["outpoint"] for c in coincubed.rpc.listcoins()["coins"])
+
+    # Only the recovery paths of the descriptor can be used.
+    with pytest.raises(
+        RpcError, match="No recovery path with timelock '9' in the descriptor"
+    ):
+        coincubed.rpc.createheirrecovery(bitcoind.rpc.getnewaddress(), 2, 9)
+
+    # The recovery path isn't available yet, but the transaction spending all the coins
+    # through it can already be created and signed.
+    with pytest.raises(
+        RpcError,
+        match="No coin currently spendable through this timelocked recovery path",
+    ):
+        coincubed.rpc.createrecovery(bitcoind.rpc.getnewaddress(), 2, 10)
+    res = coincubed.rpc.createheirrecovery(bitcoind.rpc.getnewaddress(), 2, 10)
+    reco_psbt = PSBT.from_base64(res["psbt"])
+    assert (
+        sorted(f"{i.prevout.hash:064x}:{i.prevout.n}" for i in reco_psbt.tx.vin)
+        == outpoints
+    )
+    assert all(i.nSequence == 10 for i in reco_psbt.tx.vin)
+    assert len(reco_psbt.tx.vout) == 1
+
+    # Once the timelock matured, it can be broadcast.
+    bitcoind.generate_block(9)
+    wait_for(
+        lambda: coincubed.rpc.getinfo()["block_height"] == bitcoind.rpc.getblockcount()
+    )
+    txid = sign_and_broadcast(coincubed, bitcoind, reco_psbt, recovery=True)
+    bitcoind.generate_block(1, wait_for_mempool=txid)
+    wait_for(
+        lambda: len(coincubed.rpc.listcoins(["unconfirmed", "confirmed"])["coins"])
+        == 0
+    )
+
 def test_labels(coincubed, bitcoind):
     """Test the creation and updating of labels."""
     # We can set a label for an address.
@@ -1960,3 +2149,415 @@ def test_rbfpsbt_cancel(coincubed, bitcoind):
             for c in coincubed.rpc.listcoins([], [rbf_1_outpoint])["coins"]
         )
     )
+
+
+def test_import_labels(coincubed, bitcoind):
+    """Test importing BIP-0329 labels with the various conflict strategies."""
+    addr = coincubed.rpc.getnewaddress()["address"]
+    txid = bitcoind.rpc.sendtoaddress(addr, 1)
+    wait_for(lambda: len(coincubed.rpc.listcoins()["coins"]) == 1)
+    outpoint = coincubed.rpc.listcoins()["coins"][0]["outpoint"]
+    coincubed.rpc.updatelabels({addr: "mine"})
+    foreign_addr = bitcoind.rpc.getnewaddress()
+
+    labels = [
+        {"type": "addr", "ref": addr, "label": "theirs"},
+        {"type": "tx", "ref": txid, "label": "deposit"},
+        {"type": "output", "ref": outpoint, "label": "coin", "spendable": False},
+        {"type": "addr", "ref": foreign_addr, "label": "foreign"},
+        {"type": "input", "ref": outpoint, "label": "input"},
+    ]
+    res = coincubed.rpc.importlabels(labels)
+    assert res["imported"] == 2
+    assert res["skipped"] == 1
+    assert res["updated"] == 0
+    assert res["foreign"] == [foreign_addr]
+    assert res["unsupported"] == [outpoint]
+    assert res["unspendable"] == [outpoint]
+    assert coincubed.rpc.getlabels([addr, txid, outpoint])["labels"] == {
+        addr: "mine",
+        txid: "deposit",
+        outpoint: "coin",
+    }
+    assert coincubed.rpc.getlabels([foreign_addr])["labels"] == {}
+
+    # Importing the same labels again is a no-op.
+    res = coincubed.rpc.importlabels(labels[1:3])
+    assert res["unchanged"] == 2
+
+    res = coincubed.rpc.importlabels(labels[:1], "merge")
+    assert res["updated"] == 1
+    assert coincubed.rpc.getlabels([addr])["labels"] == {addr: "mine; theirs"}
+
+    res = coincubed.rpc.importlabels(labels[:1], "overwrite")
+    assert res["updated"] == 1
+    assert coincubed.rpc.getlabels([addr])["labels"] == {addr: "theirs"}
+
+    with pytest.raises(RpcError, match="Invalid 'conflict' parameter"):
+        coincubed.rpc.importlabels(labels, "replace")
+
+
+def test_create_message_psbt(coincubed, bitcoind):
+    """Test the creation of BIP-0322 message signing PSBTs."""
+    addr = coincubed.rpc.getnewaddress()["address"]
+    res = coincubed.rpc.createmessagepsbt(addr, "Hello World")
+    psbt = PSBT.from_base64(res["psbt"])
+    assert len(psbt.tx.vin) == 1
+    assert len(psbt.tx.vout) == 1
+    assert psbt.tx.vout[0].nValue == 0
+    assert psbt.tx.vout[0].scriptPubKey == bytes([0x6A])
+
+    # We can only sign for our own addresses.
+    with pytest.raises(RpcError, match="does not belong to this wallet"):
+        coincubed.rpc.createmessagepsbt(bitcoind.rpc.getnewaddress(), "Hello World")
+
+    # We can prove control over coins.
+    txid = bitcoind.rpc.sendtoaddress(coincubed.rpc.getnewaddress()["address"], 1)
+    wait_for(lambda: len(coincubed.rpc.listcoins()["coins"]) == 1)
+    outpoint = coincubed.rpc.listcoins()["coins"][0]["outpoint"]
+    res = coincubed.rpc.createmessagepsbt(addr, "Reserves", [outpoint])
+    psbt = PSBT.from_base64(res["psbt"])
+    assert len(psbt.tx.vin) == 2
+    assert psbt.tx.vin[1].prevout.hash == int(txid, 16)
+
+    # But not unknown ones.
+    with pytest.raises(RpcError, match="Unknown outpoint"):
+        coincubed.rpc.createmessagepsbt(addr, "Reserves", ["00" * 32 + ":0"])
+
+
+def test_create_spend_fractional_feerate(coincubed, bitcoind):
+    """Feerates may be fractional and lower than 1 sat/vb."""
+    addr = coincubed.rpc.getnewaddress()["address"]
+    txid = bitcoind.rpc.sendtoaddress(addr, 0.01)
+    bitcoind.generate_block(1, wait_for_mempool=txid)
+    wait_for(lambda: len(coincubed.rpc.listcoins()["coins"]) == 1)
+    outpoints = [coincubed.rpc.listcoins()["coins"][0]["outpoint"]]
+    destinations = {bitcoind.rpc.getnewaddress(): 200_000}
+
+    # The higher the feerate, the lower the change.
+    change_values = []
+    for feerate in [0.5, 1, 1.5, 2.25]:
+        res = coincubed.rpc.createspend(destinations, outpoints, feerate)
+        psbt = PSBT.from_base64(res["psbt"])
+        assert len(psbt.tx.vout) == 2
+        change_values.append(
+            next(o.nValue for o in psbt.tx.vout if o.nValue != 200_000)
+        )
+    assert change_values == sorted(change_values, reverse=True)
+    assert len(set(change_values)) == len(change_values)
+
+    # Below the minimum relay feerate it's refused.
+    with pytest.raises(RpcError, match="Invalid feerate: 0.099 sats/vb."):
+        coincubed.rpc.createspend(destinations, outpoints, 0.099)
+    # And we don't support a precision finer than a thousandth of a sat/vb.
+    with pytest.raises(RpcError, match="Invalid 'feerate' parameter."):
+        coincubed.rpc.createspend(destinations, outpoints, 1.0001)
+
+
+def test_create_spend_subtract_fee(coincubed, bitcoind):
+    """The fee may be deducted from the value of some of the destinations."""
+    addr = coincubed.rpc.getnewaddress()["address"]
+    txid = bitcoind.rpc.sendtoaddress(addr, 0.01)
+    bitcoind.generate_block(1, wait_for_mempool=txid)
+    wait_for(lambda: len(coincubed.rpc.listcoins()["coins"]) == 1)
+    outpoints = [coincubed.rpc.listcoins()["coins"][0]["outpoint"]]
+
+    # Send the whole value of the coin to a single recipient.
+    dest_addr = bitcoind.rpc.getnewaddress()
+    res = coincubed.rpc.createspend(
+        destinations={dest_addr: 1_000_000},
+        outpoints=outpoints,
+        feerate=2,
+        subtract_fee_from=[dest_addr],
+    )
+    psbt = PSBT.from_base64(res["psbt"])
+    assert len(psbt.tx.vout) == 1
+    fee = 1_000_000 - psbt.tx.vout[0].nValue
+    assert 0 < fee < 1_000
+
+    # Split the fee between two recipients, the change is left untouched.
+    dest_addr_b = bitcoind.rpc.getnewaddress()
+    res = coincubed.rpc.createspend(
+        destinations={dest_addr: 200_000, dest_addr_b: 300_000},
+        outpoints=outpoints,
+        feerate=2,
+        subtract_fee_from=[dest_addr, dest_addr_b],
+    )
+    psbt = PSBT.from_base64(res["psbt"])
+    assert len(psbt.tx.vout) == 3
+    values = sorted(o.nValue for o in psbt.tx.vout)
+    assert values[0] < 200_000 and values[1] < 300_000
+    assert values[2] == 500_000
+    assert abs((200_000 - values[0]) - (300_000 - values[1])) <= 1
+
+    # The address must be one of the destinations.
+    with pytest.raises(RpcError, match="is not one of the destinations"):
+        coincubed.rpc.createspend(
+            destinations={dest_addr: 200_000},
+            outpoints=outpoints,
+            feerate=2,
+            subtract_fee_from=[dest_addr_b],
+        )
+    # And the output must remain above dust once the fee was deducted.
+    with pytest.raises(RpcError, match="Invalid output value"):
+        coincubed.rpc.createspend(
+            destinations={dest_addr: 5_000},
+            outpoints=outpoints,
+            feerate=2,
+            subtract_fee_from=[dest_addr],
+        )
+
+
+def test_create_spend_op_return(coincubed, bitcoind):
+    """A spend may commit to some data in an OP_RETURN output."""
+    addr = coincubed.rpc.getnewaddress()["address"]
+    txid = bitcoind.rpc.sendtoaddress(addr, 0.01)
+    bitcoind.generate_block(1, wait_for_mempool=txid)
+    wait_for(lambda: len(coincubed.rpc.listcoins()["coins"]) == 1)
+    outpoints = [coincubed.rpc.listcoins()["coins"][0]["outpoint"]]
+    destinations = {bitcoind.rpc.getnewaddress(): 200_000}
+
+    # The data may be given as text or hex encoded.
+    for op_return, data in [
+        ({"text": "coincube"}, b"coincube"),
+        ({"hex": "deadbeef"}, bytes.fromhex("deadbeef")),
+    ]:
+        res = coincubed.rpc.createspend(
+            destinations=destinations,
+            outpoints=outpoints,
+            feerate=2,
+            op_return=op_return,
+        )
+        psbt = PSBT.from_base64(res["psbt"])
+        assert len(psbt.tx.vout) == 3
+        data_out = next(o for o in psbt.tx.vout if o.scriptPubKey[0] == 0x6A)
+        assert data_out.nValue == 0
+        assert data_out.scriptPubKey[2:] == data
+
+    # Positional callers may pass null for no data.
+    res_null = coincubed.rpc.createspend(destinations, outpoints, 2, None, None, None)
+    assert len(PSBT.from_base64(res_null["psbt"]).tx.vout) == 2
+
+    # The transaction is relayed and mined.
+    signed_psbt = coincubed.signer.sign_psbt(psbt)
+    coincubed.rpc.updatespend(signed_psbt.to_base64())
+    spend_txid = signed_psbt.tx.txid().hex()
+    coincubed.rpc.broadcastspend(spend_txid)
+    bitcoind.generate_block(1, wait_for_mempool=spend_txid)
+
+    # More than 80 bytes is not standard, and invalid data is refused.
+    with pytest.raises(RpcError, match="larger than the standard maximum of 80 bytes"):
+        coincubed.rpc.createspend(
+            destinations=destinations,
+            outpoints=outpoints,
+            feerate=2,
+            op_return={"hex": "00" * 81},
+        )
+    with pytest.raises(RpcError, match="Invalid 'op_return' parameter."):
+        coincubed.rpc.createspend(
+            destinations=destinations,
+            outpoints=outpoints,
+            feerate=2,
+            op_return={"hex": "zz"},
+        )
+
+
+def test_address_reuse(coincubed, bitcoind):
+    """Addresses receiving coins more than once are flagged, and sending to them is warned."""
+    addr = coincubed.rpc.getnewaddress()["address"]
+    txids = [bitcoind.rpc.sendtoaddress(addr, 0.01) for _ in range(2)]
+    for txid in txids:
+        bitcoind.generate_block(1, wait_for_mempool=txid)
+    wait_for(lambda: len(coincubed.rpc.listcoins()["coins"]) == 2)
+
+    revealed = coincubed.rpc.listrevealedaddresses(False, False, 1)["addresses"][0]
+    assert revealed["address"] == addr
+    assert revealed["used_count"] == 2 and revealed["reused"]
+    listed = coincubed.rpc.listaddresses(revealed["index"], 1)["addresses"][0]
+    assert listed["receive"] == addr
+    assert listed["receive_reused"] and not listed["change_reused"]
+
+    # Sending to our own used address is warned about, but not to a fresh one.
+    outpoints = [c["outpoint"] for c in coincubed.rpc.listcoins()["coins"]][:1]
+    res = coincubed.rpc.createspend({addr: 200_000}, outpoints, 2)
+    assert any("Address reuse" in w and addr in w for w in res["warnings"])
+    new_addr = coincubed.rpc.getnewaddress()["address"]
+    res = coincubed.rpc.createspend({new_addr: 200_000}, outpoints, 2)
+    assert not any("Address reuse" in w for w in res["warnings"])
+
+
+def test_search(coincubed, bitcoind):
+    """Coins and transactions can be searched by label, amount, date, status, address and txid."""
+    addrs = [coincubed.rpc.getnewaddress()["address"] for _ in range(2)]
+    txids = [
+        bitcoind.rpc.sendtoaddress(addr, amount)
+        for addr, amount in zip(addrs, (0.01, 0.02))
+    ]
+    bitcoind.generate_block(1, wait_for_mempool=txids)
+    wait_for(lambda: len(coincubed.rpc.listcoins(["confirmed"])["coins"]) == 2)
+    coincubed.rpc.updatelabels({txids[1]: "Rent for March"})
+
+    def search(*args):
+        coins = coincubed.rpc.searchcoins(*args)["coins"]
+        return [c["outpoint"].split(":")[0] for c in coins]
+
+    assert sorted(search()) == sorted(txids)
+    assert search("rent") == [txids[1]]
+    assert search(None, 1_500_000) == [txids[1]]
+    assert search(None, None, 1_500_000) == [txids[0]]
+    assert search(None, None, None, None, None, ["unconfirmed"]) == []
+    assert search(None, None, None, None, None, None, addrs[0]) == [txids[0]]
+    assert search(None, None, None, None, None, None, None, txids[0][:8]) == [
+        txids[0]
+    ]
+    # The date range applies to the time of the block the coins were confirmed in.
+    blocktime = bitcoind.rpc.getblockheader(bitcoind.rpc.getbestblockhash())["time"]
+    assert sorted(search(None, None, None, blocktime)) == sorted(txids)
+    assert search(None, None, None, blocktime + 1) == []
+    with pytest.raises(RpcError, match="Invalid 'txid_prefix' parameter"):
+        coincubed.rpc.searchcoins(None, None, None, None, None, None, None, "xyz")
+
+    res = coincubed.rpc.searchtransactions(10, "rent")["transactions"]
+    assert [bitcoind.rpc.decoderawtransaction(tx["tx"])["txid"] for tx in res] == [
+        txids[1]
+    ]
+    assert len(coincubed.rpc.searchtransactions(1)["transactions"]) == 1
+    assert len(coincubed.rpc.searchtransactions(10)["transactions"]) == 2
+
+
+def test_balance_history(coincubed, bitcoind):
+    """The balance history has an entry per confirmed transaction, from the oldest."""
+    assert coincubed.rpc.getbalancehistory()["history"] == []
+
+    addr = coincubed.rpc.getnewaddress()["address"]
+    txid = bitcoind.rpc.sendtoaddress(addr, 0.01)
+    wait_for(lambda: len(coincubed.rpc.listcoins(["unconfirmed"])["coins"]) == 1)
+    # Unconfirmed transactions are not part of the history.
+    assert coincubed.rpc.getbalancehistory()["history"] == []
+    bitcoind.generate_block(1, wait_for_mempool=txid)
+    wait_for(lambda: len(coincubed.rpc.getbalancehistory()["history"]) == 1)
+    entry = coincubed.rpc.getbalancehistory()["history"][0]
+    assert entry["txid"] == txid
+    assert entry["delta"] == entry["balance"] == 1_000_000
+
+    # Spend part of it. The balance decreases by the amount sent and the fee.
+    outpoint = coincubed.rpc.listcoins(["confirmed"])["coins"][0]["outpoint"]
+    destinations = {bitcoind.rpc.getnewaddress(): 200_000}
+    res = coincubed.rpc.createspend(destinations, [outpoint], 2)
+    psbt = PSBT.from_base64(res["psbt"])
+    spend_txid = sign_and_broadcast_psbt(coincubed, psbt)
+    bitcoind.generate_block(1, wait_for_mempool=spend_txid)
+    wait_for(lambda: len(coincubed.rpc.getbalancehistory()["history"]) == 2)
+    entry = coincubed.rpc.getbalancehistory()["history"][1]
+    assert entry["txid"] == spend_txid
+    assert entry["delta"] < -200_000
+    assert entry["balance"] == 1_000_000 + entry["delta"]
+
+
+def test_cpfp(coincubed, bitcoind):
+    """A child transaction may bump the fee of an incoming payment that can't be replaced."""
+    # Receive a payment at 1 sat/vb, not signaling for replacement.
+    addr = coincubed.rpc.getnewaddress()["address"]
+    res = bitcoind.rpc.walletcreatefundedpsbt(
+        [],
+        [{addr: 0.01}],
+        0,
+        {"fee_rate": 1, "replaceable": False},
+    )
+    res = bitcoind.rpc.walletprocesspsbt(res["psbt"])
+    parent_txid = bitcoind.rpc.sendrawtransaction(
+        bitcoind.rpc.finalizepsbt(res["psbt"])["hex"]
+    )
+    wait_for(lambda: len(coincubed.rpc.listcoins(["unconfirmed"])["coins"]) == 1)
+    outpoint = coincubed.rpc.listcoins(["unconfirmed"])["coins"][0]["outpoint"]
+    assert parent_txid in outpoint
+
+    # Create a child bringing the package to 10 sat/vb, and broadcast it.
+    res = coincubed.rpc.cpfp([outpoint], 10)
+    psbt = PSBT.from_base64(res["psbt"])
+    assert len(psbt.tx.vin) == 1 and len(psbt.tx.vout) == 1
+    child_txid = sign_and_broadcast_psbt(coincubed, psbt)
+    child_entry = bitcoind.rpc.getmempoolentry(child_txid)
+    # Note that in the mempool entry, "ancestor" includes the child itself.
+    package_feerate = (
+        child_entry["fees"]["ancestor"] * COIN / child_entry["ancestorsize"]
+    )
+    assert 10 <= package_feerate < 10.5
+
+    # The coin is now spent, and a confirmed one can't be bumped.
+    wait_for(lambda: len(coincubed.rpc.listcoins(["spending"])["coins"]) == 1)
+    with pytest.raises(RpcError, match="is already spent"):
+        coincubed.rpc.cpfp([outpoint], 20)
+    bitcoind.generate_block(1, wait_for_mempool=child_txid)
+    wait_for(lambda: len(coincubed.rpc.listcoins(["confirmed"])["coins"]) == 1)
+    child_outpoint = coincubed.rpc.listcoins(["confirmed"])["coins"][0]["outpoint"]
+    with pytest.raises(RpcError, match="is already confirmed"):
+        coincubed.rpc.cpfp([child_outpoint], 20)
+
+
+def test_analyze_psbt(coincubed, bitcoind):
+    """A PSBT can be analyzed against the wallet policy before signing it."""
+    addr = coincubed.rpc.getnewaddress()["address"]
+    txid = bitcoind.rpc.sendtoaddress(addr, 0.01)
+    bitcoind.generate_block(1, wait_for_mempool=txid)
+    wait_for(lambda: len(coincubed.rpc.listcoins(["confirmed"])["coins"]) == 1)
+    outpoint = coincubed.rpc.listcoins(["confirmed"])["coins"][0]["outpoint"]
+
+    # Analyze a spend paying an external address with change back to us.
+    dest_addr = bitcoind.rpc.getnewaddress()
+    res = coincubed.rpc.createspend({dest_addr: 200_000}, [outpoint], 2)
+    psbt = PSBT.from_base64(res["psbt"])
+    res = coincubed.rpc.analyzepsbt(res["psbt"])
+    assert res["txid"] == psbt.tx.txid().hex()
+    assert res["anomalies"] == []
+    assert len(res["inputs"]) == 1
+    assert res["inputs"][0]["outpoint"] == outpoint
+    assert res["inputs"][0]["value"] == 1_000_000
+    assert res["inputs"][0]["ownership"] == "ours"
+    primary_path = res["inputs"][0]["spend_info"]["primary_path"]
+    assert primary_path["sigs_count"] == 0
+    assert primary_path["missing_sigs"] == primary_path["threshold"]
+    ownerships = sorted(o["ownership"] for o in res["outputs"])
+    assert ownerships == ["change", "external"]
+    ext_output = next(o for o in res["outputs"] if o["ownership"] == "external")
+    assert ext_output["address"] == dest_addr
+    assert ext_output["value"] == 200_000
+    assert res["fee"] == 1_000_000 - sum(o["value"] for o in res["outputs"])
+    assert 2_000 <= res["feerate_kvb"] < 2_100
+
+    # An invalid PSBT is rejected.
+    with pytest.raises(RpcError, match="Invalid params"):
+        coincubed.rpc.analyzepsbt("cHNidP8BAA==")
+
+
+def test_consolidation_advice(coincubed, bitcoind):
+    """The small coins are worth consolidating before the feerate rises."""
+    assert coincubed.rpc.getconsolidationadvice(2, 50)["advice"] is None
+
+    # Receive a large coin and a few small ones.
+    for amount in [1, 0.0002, 0.0003, 0.0004]:
+        addr = coincubed.rpc.getnewaddress()["address"]
+        txid = bitcoind.rpc.sendtoaddress(addr, amount)
+        bitcoind.generate_block(1, wait_for_mempool=txid)
+    wait_for(lambda: len(coincubed.rpc.listcoins(["confirmed"])["coins"]) == 4)
+    coins = {
+        c["amount"]: c["outpoint"]
+        for c in coincubed.rpc.listcoins(["confirmed"])["coins"]
+    }
+
+    # Only the small coins are recommended, smallest first.
+    advice = coincubed.rpc.getconsolidationadvice(2, 50)["advice"]
+    assert advice["outpoints"] == [coins[20_000], coins[30_000], coins[40_000]]
+    assert advice["amount"] == 90_000
+    assert 0 < advice["fee"] < advice["savings"]
+
+    # The consolidation is a self-send of these coins.
+    res = coincubed.rpc.createspend({}, advice["outpoints"], 2)
+    psbt = PSBT.from_base64(res["psbt"])
+    assert len(psbt.tx.vin) == 3 and len(psbt.tx.vout) == 1
+    assert 90_000 - psbt.tx.vout[0].nValue <= advice["fee"]
+
+    # Not worth it if the feerate isn't expected to rise.
+    assert coincubed.rpc.getconsolidationadvice(50, 2)["advice"] is None
+    with pytest.raises(RpcError, match="Invalid feerate: 0.05 sats/vb."):
+        coincubed.rpc.getconsolidationadvice(0.05, 2)